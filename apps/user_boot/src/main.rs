@@ -12,7 +12,8 @@ async fn main() -> i32 {
     async_std::println!("user_boot");
     // 初始化文件系统
     trampoline::fs_init().await;
-    let task = trampoline::init_user(vec!["hello".into()], &get_envs()).await.unwrap();
+    let init = trampoline::init_program().await.unwrap_or_else(|| "hello".into());
+    let task = trampoline::init_user(vec![init], &get_envs()).await.unwrap();
     trampoline::wait(&task).await;
     async_std::println!("task count {}", alloc::sync::Arc::strong_count(&task));
     0
@@ -1,5 +1,7 @@
 use alloc::string::String;
 use axerrno::AxResult;
+use alloc::sync::Arc;
+use async_fs::api::Inotify;
 use async_fs::fops::{Directory, File};
 
 pub use async_fs::fops::DirEntry as AxDirEntry;
@@ -8,6 +10,8 @@ pub use async_fs::fops::FilePerm as AxFilePerm;
 pub use async_fs::fops::FileType as AxFileType;
 pub use async_fs::fops::OpenOptions as AxOpenOptions;
 pub use async_io::SeekFrom as AxSeekFrom;
+pub use async_fs::api::InotifyMask as AxInotifyMask;
+pub use async_fs::api::NotifyEvent as AxNotifyEvent;
 use async_io::{AsyncRead, AsyncWrite, AsyncSeek};
 
 #[cfg(feature = "myfs")]
@@ -19,6 +23,9 @@ pub struct AxFileHandle(File);
 /// A handle to an opened directory.
 pub struct AxDirHandle(Directory);
 
+/// A handle to an inotify instance.
+pub struct AxInotifyHandle(Arc<Inotify>);
+
 pub async fn ax_open_file(path: &str, opts: &AxOpenOptions) -> AxResult<AxFileHandle> {
     Ok(AxFileHandle(File::open_withperm(path, opts).await?))
 }
@@ -87,6 +94,22 @@ pub async fn ax_set_current_dir(path: &str) -> AxResult {
     async_fs::api::set_current_dir(path).await
 }
 
+pub fn ax_inotify_init() -> AxInotifyHandle {
+    AxInotifyHandle(Inotify::new())
+}
+
+pub async fn ax_inotify_add_watch(
+    inotify: &AxInotifyHandle,
+    path: &str,
+    mask: AxInotifyMask,
+) -> AxResult<i32> {
+    inotify.0.add_watch(path, mask).await
+}
+
+pub fn ax_inotify_rm_watch(inotify: &AxInotifyHandle, wd: i32) -> AxResult {
+    inotify.0.rm_watch(wd)
+}
+
 use core::{pin::Pin, task::{Context, Poll}};
 
 // impl AsyncRead for AxDirHandle {
@@ -138,6 +161,13 @@ impl AxDirHandle {
     }
 }
 
+impl AxInotifyHandle {
+
+    pub fn poll_next_event(&self, cx: &mut Context<'_>) -> Poll<AxNotifyEvent> {
+        self.0.poll_event(cx)
+    }
+}
+
 impl AsyncRead for AxFileHandle {
     fn read(
         self: Pin<&mut Self>,
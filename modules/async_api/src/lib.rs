@@ -158,6 +158,9 @@ pub mod fs {
         pub type AxFilePerm;
         pub type AxDirEntry;
         pub type AxSeekFrom;
+        pub type AxInotifyHandle;
+        pub type AxInotifyMask;
+        pub type AxNotifyEvent;
         #[cfg(feature = "myfs")]
         pub type AxDisk;
         #[cfg(feature = "myfs")]
@@ -226,6 +229,19 @@ pub mod fs {
         pub async fn ax_current_dir() -> AxResult<alloc::string::String>;
         /// Changes the current working directory to the specified path.
         pub async fn ax_set_current_dir(path: &str) -> AxResult;
+
+        /// Watches the file or directory at `path` for the events in `mask`,
+        /// returns the watch descriptor.
+        pub async fn ax_inotify_add_watch(inotify: &AxInotifyHandle, path: &str, mask: AxInotifyMask) -> AxResult<i32>;
+    }
+
+    define_api! {
+        @cfg "fs";
+
+        /// Creates a new inotify instance.
+        pub fn ax_inotify_init() -> AxInotifyHandle;
+        /// Removes the watch `wd` from the inotify instance.
+        pub fn ax_inotify_rm_watch(inotify: &AxInotifyHandle, wd: i32) -> AxResult;
     }
 }
 
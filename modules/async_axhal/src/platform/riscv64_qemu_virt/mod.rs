@@ -13,6 +13,7 @@ pub fn platform_init() {
     #[cfg(feature = "irq")]
     self::irq::init_percpu();
     self::time::init_percpu();
+    self::time::init_primary();
 }
 
 /// Initializes the platform devices for secondary CPUs.
@@ -56,6 +56,24 @@ pub fn set_oneshot_timer(deadline_ns: u64) {
     sbi_rt::set_timer(nanos_to_ticks(deadline_ns) * loops_pre_tick());
 }
 
+/// goldfish RTC 的物理地址
+const RTC_PADDR: usize = 0x10_1000;
+
+/// 读取 goldfish RTC，返回自 epoch 以来的纳秒数
+fn rtc_nanos() -> u64 {
+    let base = crate::mem::phys_to_virt(RTC_PADDR.into()).as_usize();
+    // 读低 32 位时硬件锁存高 32 位，必须先读低位
+    unsafe {
+        let low = core::ptr::read_volatile(base as *const u32) as u64;
+        let high = core::ptr::read_volatile((base + 4) as *const u32) as u64;
+        (high << 32) | low
+    }
+}
+
+pub(super) fn init_primary() {
+    crate::time::init_epoch_offset(rtc_nanos());
+}
+
 pub(super) fn init_percpu() {
     #[cfg(feature = "irq")]
     sbi_rt::set_timer(0);
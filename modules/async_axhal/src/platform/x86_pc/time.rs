@@ -1,4 +1,5 @@
 use raw_cpuid::CpuId;
+use x86_64::instructions::port::Port;
 
 #[cfg(feature = "irq")]
 const LAPIC_TICKS_PER_SEC: u64 = 1_000_000_00; // TODO: need to calibrate
@@ -56,7 +57,50 @@ pub fn init_early() {
     unsafe { INIT_TICK = core::arch::x86_64::_rdtsc() };
 }
 
+/// 读取 CMOS RTC 的寄存器 `reg`
+fn cmos_read(reg: u8) -> u8 {
+    unsafe {
+        Port::<u8>::new(0x70).write(reg);
+        Port::<u8>::new(0x71).read()
+    }
+}
+
+/// 公历日期距 1970-01-01 的天数
+const fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// 读取 CMOS RTC，返回自 epoch 以来的纳秒数
+fn rtc_nanos() -> u64 {
+    // 等待 RTC 完成更新，避免读到更新到一半的时间
+    while cmos_read(0x0a) & 0x80 != 0 {
+        core::hint::spin_loop();
+    }
+    let mut regs = [0x00, 0x02, 0x04, 0x07, 0x08, 0x09].map(cmos_read);
+    let status_b = cmos_read(0x0b);
+    // 12 小时制时小时的最高位表示下午
+    let pm = regs[2] & 0x80 != 0;
+    regs[2] &= 0x7f;
+    if status_b & 0x04 == 0 {
+        // BCD 编码
+        regs = regs.map(|v| (v & 0x0f) + (v >> 4) * 10);
+    }
+    let [sec, min, mut hour, day, month, year] = regs.map(|v| v as i64);
+    if status_b & 0x02 == 0 {
+        hour = hour % 12 + if pm { 12 } else { 0 };
+    }
+    let days = days_from_civil(2000 + year, month, day);
+    let secs = days * 86400 + hour * 3600 + min * 60 + sec;
+    secs.max(0) as u64 * crate::time::NANOS_PER_SEC
+}
+
 pub(super) fn init_primary() {
+    crate::time::init_epoch_offset(rtc_nanos());
     #[cfg(feature = "irq")]
     unsafe {
         use x2apic::lapic::{TimerDivide, TimerMode};
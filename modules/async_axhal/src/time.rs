@@ -1,6 +1,7 @@
 //! Time-related operations.
 
 pub use core::time::Duration;
+use core::sync::atomic::{AtomicU64, Ordering};
 
 /// A measurement of the system clock.
 ///
@@ -35,6 +36,22 @@ pub fn current_time() -> TimeValue {
     TimeValue::from_nanos(current_time_nanos())
 }
 
+/// 开机时刻对应的实时时间（自 epoch 起的纳秒数），由平台读取 RTC 后设置
+static EPOCH_OFFSET_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// 根据 RTC 读出的实时时间 `rtc_nanos` 设置开机时刻对应的实时时间
+#[allow(unused)]
+pub(crate) fn init_epoch_offset(rtc_nanos: u64) {
+    EPOCH_OFFSET_NANOS.store(rtc_nanos.saturating_sub(current_time_nanos()), Ordering::Relaxed);
+}
+
+/// Returns the current wall time since the Unix epoch in [`TimeValue`].
+///
+/// 平台没有 RTC 时从开机时刻开始计时，与 [`current_time`] 相同
+pub fn wall_time() -> TimeValue {
+    TimeValue::from_nanos(current_time_nanos() + EPOCH_OFFSET_NANOS.load(Ordering::Relaxed))
+}
+
 /// Busy waiting for the given duration.
 pub fn busy_wait(dur: Duration) {
     busy_wait_until(current_time() + dur);
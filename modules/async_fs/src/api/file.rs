@@ -114,6 +114,26 @@ impl Metadata {
     pub const fn blocks(&self) -> u64 {
         self.0.blocks()
     }
+
+    /// Returns the user id of the owner of the file.
+    pub const fn uid(&self) -> u32 {
+        self.0.uid()
+    }
+
+    /// Returns the group id of the owner of the file.
+    pub const fn gid(&self) -> u32 {
+        self.0.gid()
+    }
+
+    /// Returns the time of last modification of the file.
+    pub const fn modified(&self) -> core::time::Duration {
+        self.0.mtime()
+    }
+
+    /// Returns the time of last access of the file.
+    pub const fn accessed(&self) -> core::time::Duration {
+        self.0.atime()
+    }
 }
 
 impl fmt::Debug for Metadata {
@@ -185,4 +205,8 @@ impl FileExt for File {
     async fn executable(&self) -> bool {
         self.executable()
     }
+
+    fn clone_file(&self) -> Box<dyn FileExt> {
+        Box::new(self.clone())
+    }
 }
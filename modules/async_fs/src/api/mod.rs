@@ -9,9 +9,20 @@ pub mod port;
 pub use self::dir::{DirBuilder, DirEntry, ReadDir};
 pub use self::file::{File, FileType, Metadata, OpenOptions, Permissions};
 use axerrno::AxResult;
-use async_vfs::VfsNodeRef;
+use async_vfs::AsyncVfsNodeOps;
 pub use async_io::{Read, Seek, SeekFrom, Write, Result};
 pub use port::*;
+pub use crate::root::{MountFlags, UmountFlags};
+pub use async_vfs::walk::{LookupFlags, PathError};
+pub use async_vfs::{FileSystemInfo, VfsAccess, VfsNodeRef, VfsSeals, VfsSetAttr, VfsXattrFlags};
+pub use crate::fops::FileAttr;
+pub use crate::lock::{
+    flock, release_lock_owner, release_process_locks, set_range_lock, test_range_lock, LockError,
+    LockKind, LockOwner, RangeLock,
+};
+pub use crate::notify::{Inotify, InotifyMask, NotifyEvent};
+#[cfg(feature = "mqueue")]
+pub use crate::fs::mqueue::{MessageQueue, MqNotify, WaitingReceiver, DEFAULT_MAXMSG, DEFAULT_MSGSIZE};
 
 use alloc::{string::String, vec::Vec};
 
@@ -94,6 +105,138 @@ pub async fn rename(old: &str, new: &str) -> Result<()> {
     crate::root::rename(old, new).await
 }
 
+/// Creates a node of type `ty` that only has metadata, such as a FIFO or a
+/// socket, at `path`.
+pub async fn create_node(path: &str, ty: FileType) -> AxResult {
+    crate::root::create_node(None, path, ty).await
+}
+
+/// Creates a new symbolic link `path` pointing to `target`.
+pub async fn symlink(target: &str, path: &str) -> AxResult {
+    crate::root::symlink(target, path).await
+}
+
+/// Creates a new hard link `new` to the existing file `old`.
+pub async fn hard_link(old: &str, new: &str) -> AxResult {
+    crate::root::hard_link(old, new).await
+}
+
+/// Reads the target of the symbolic link `path`.
+pub async fn read_link(path: &str) -> AxResult<String> {
+    crate::root::read_link(path).await
+}
+
+/// The type of the mounted filesystem that `path` is on, as shown in
+/// `/proc/mounts`.
+pub async fn filesystem_type(path: &str) -> AxResult<String> {
+    crate::root::fs_type(path).await
+}
+
+/// Check if two paths are on the same mounted filesystem.
+pub async fn same_filesystem(a: &str, b: &str) -> bool {
+    crate::root::same_filesystem(a, b).await
+}
+
+/// Query the attributes of the file at `path`.
+///
+/// With [`LookupFlags::NOFOLLOW`], the attributes of a symbolic link itself
+/// are returned.
+pub async fn get_attr(path: &str, flags: LookupFlags) -> AxResult<FileAttr> {
+    crate::root::lookup_with(None, path, flags).await?.get_attr().await
+}
+
+/// Change the attributes of the file at `path`.
+pub async fn set_attr(path: &str, attr: &VfsSetAttr, flags: LookupFlags) -> AxResult {
+    if crate::root::is_read_only(None, path).await {
+        return axerrno::ax_err!(PermissionDenied, "read-only filesystem");
+    }
+    crate::root::lookup_with(None, path, flags).await?.setattr(attr).await?;
+    if let Ok(path) = crate::root::absolute_path(path).await {
+        crate::notify::notify(&path, crate::notify::InotifyMask::ATTRIB);
+    }
+    Ok(())
+}
+
+/// Read the value of the extended attribute `name` of the file at `path`
+/// into `buf`.
+///
+/// Returns the length of the value, which is copied into `buf` only if it
+/// fits. [`NotFound`](axerrno::AxError::NotFound) is returned if the file has
+/// no such attribute.
+pub async fn get_xattr(
+    path: &str,
+    flags: LookupFlags,
+    name: &str,
+    buf: &mut [u8],
+) -> AxResult<usize> {
+    crate::root::lookup_with(None, path, flags)
+        .await?
+        .getxattr(name, buf)
+        .await
+}
+
+/// Set the value of the extended attribute `name` of the file at `path`.
+pub async fn set_xattr(
+    path: &str,
+    flags: LookupFlags,
+    name: &str,
+    value: &[u8],
+    xattr_flags: VfsXattrFlags,
+) -> AxResult {
+    if crate::root::is_read_only(None, path).await {
+        return axerrno::ax_err!(PermissionDenied, "read-only filesystem");
+    }
+    crate::root::lookup_with(None, path, flags)
+        .await?
+        .setxattr(name, value, xattr_flags)
+        .await?;
+    if let Ok(path) = crate::root::absolute_path(path).await {
+        crate::notify::notify(&path, crate::notify::InotifyMask::ATTRIB);
+    }
+    Ok(())
+}
+
+/// Read the names of all extended attributes of the file at `path` into
+/// `buf`, each followed by a `'\0'`.
+///
+/// Returns the total length of the names, which are copied only if they fit.
+pub async fn list_xattr(path: &str, flags: LookupFlags, buf: &mut [u8]) -> AxResult<usize> {
+    crate::root::lookup_with(None, path, flags)
+        .await?
+        .listxattr(buf)
+        .await
+}
+
+/// Remove the extended attribute `name` of the file at `path`.
+pub async fn remove_xattr(path: &str, flags: LookupFlags, name: &str) -> AxResult {
+    if crate::root::is_read_only(None, path).await {
+        return axerrno::ax_err!(PermissionDenied, "read-only filesystem");
+    }
+    crate::root::lookup_with(None, path, flags)
+        .await?
+        .removexattr(name)
+        .await?;
+    if let Ok(path) = crate::root::absolute_path(path).await {
+        crate::notify::notify(&path, crate::notify::InotifyMask::ATTRIB);
+    }
+    Ok(())
+}
+
+/// Check if a caller with user id `uid` and group id `gid` is granted
+/// `access` to the file at `path`.
+///
+/// Write access is always denied on a read-only mount.
+pub async fn check_access(path: &str, uid: u32, gid: u32, access: VfsAccess) -> AxResult {
+    let attr = get_attr(path, LookupFlags::empty()).await?;
+    if !attr.permits(uid, gid, access) {
+        return axerrno::ax_err!(PermissionDenied);
+    }
+    if access.contains(VfsAccess::WRITE) && crate::root::is_read_only(None, path).await {
+        return axerrno::ax_err!(PermissionDenied, "read-only filesystem");
+    }
+    Ok(())
+}
+
 /// Check if a path exists.
 pub async fn path_exists(path: &str) -> bool {
     crate::root::lookup(None, path).await.is_ok()
@@ -103,3 +246,162 @@ pub async fn path_exists(path: &str) -> bool {
 pub async fn lookup(path: &str) -> AxResult<VfsNodeRef> {
     crate::root::lookup(None, path).await
 }
+
+/// Resolve `path` to an absolute path with all symbolic links followed.
+///
+/// Relative paths are resolved from the current directory. The last
+/// component may not exist, in which case the returned node is `None`.
+pub async fn resolve_path(
+    path: &str,
+    flags: LookupFlags,
+) -> core::result::Result<(String, Option<VfsNodeRef>), PathError> {
+    crate::root::walk(path, flags, None).await
+}
+
+/// The same as [`resolve_path`], but fails with
+/// [`AxError::PermissionDenied`](axerrno::AxError::PermissionDenied) if the
+/// user `uid` in group `gid` can't search one of the directories on the way.
+pub async fn resolve_path_as(
+    path: &str,
+    flags: LookupFlags,
+    uid: u32,
+    gid: u32,
+) -> core::result::Result<(String, Option<VfsNodeRef>), PathError> {
+    crate::root::walk(path, flags, Some((uid, gid))).await
+}
+
+/// Resolve `path` relative to the opened directory `dir` whose path is
+/// `dir_path`, as the `*at` system calls do.
+///
+/// Components are looked up in `dir` itself, so its files are found even if
+/// `dir_path` no longer names it. An empty `path` resolves to `dir`. Search
+/// permission is checked for `searcher` as in [`resolve_path_as`].
+pub async fn resolve_path_at(
+    dir: &VfsNodeRef,
+    dir_path: &str,
+    path: &str,
+    flags: LookupFlags,
+    searcher: Option<(u32, u32)>,
+) -> core::result::Result<(String, Option<VfsNodeRef>), PathError> {
+    crate::root::walk_at(dir, dir_path, path, flags, searcher).await
+}
+
+/// Check if the last component of `path` is a symbolic link, without
+/// following it.
+pub async fn is_symlink(path: &str) -> bool {
+    match crate::root::walk(path, LookupFlags::NOFOLLOW, None).await {
+        Ok((_, Some(node))) => node
+            .get_attr()
+            .await
+            .is_ok_and(|attr| attr.file_type().is_symlink()),
+        _ => false,
+    }
+}
+
+/// Write all dirty blocks in the block caches back to the devices.
+pub async fn sync() -> AxResult {
+    crate::cache::sync_all().map_err(|_| axerrno::AxError::Io)
+}
+
+/// The interval in milliseconds between two periodic write-backs of the
+/// block caches.
+pub const WRITEBACK_INTERVAL_MS: usize = crate::cache::WRITEBACK_INTERVAL_MS;
+
+/// Get the information of the filesystem containing `path`, following
+/// symbolic links.
+///
+/// Returns [`PathError::Loop`] if there are too many symbolic links.
+pub async fn statfs(path: &str) -> core::result::Result<FileSystemInfo, PathError> {
+    crate::root::statfs(path).await
+}
+
+/// Mount a filesystem of type `fstype` at `target`.
+///
+/// With [`MountFlags::BIND`], the directory `source` is bind mounted at
+/// `target` and `fstype` is ignored. With [`MountFlags::REMOUNT`], only the
+/// flags of the existing mount at `target` are changed.
+pub async fn mount(source: &str, target: &str, fstype: &str, flags: MountFlags) -> AxResult {
+    if flags.contains(MountFlags::REMOUNT) {
+        return crate::root::remount(target, flags).await;
+    }
+    if flags.contains(MountFlags::BIND) {
+        return crate::root::bind_mount(source, target, flags).await;
+    }
+    let fs: alloc::sync::Arc<dyn async_vfs::VfsOps + Unpin> = match fstype {
+        #[cfg(feature = "ramfs")]
+        "tmpfs" | "ramfs" => alloc::sync::Arc::new(crate::fs::ramfs::RamFileSystem::new()),
+        #[cfg(feature = "procfs")]
+        "proc" => crate::mounts::procfs()?,
+        // 所有挂载点共享同一组消息队列
+        #[cfg(feature = "mqueue")]
+        "mqueue" => crate::fs::mqueue::MqueueFileSystem::instance(),
+        // 块设备只有一个且已作为根文件系统
+        _ => return axerrno::ax_err!(Unsupported, "unsupported filesystem type"),
+    };
+    crate::root::mount(fs, source, target, fstype, flags).await
+}
+
+/// Looks up the POSIX message queue `name` in the mqueue filesystem,
+/// returns the queue and the attributes of its file.
+#[cfg(feature = "mqueue")]
+pub fn mq_lookup(name: &str) -> AxResult<(alloc::sync::Arc<MessageQueue>, FileAttr)> {
+    crate::fs::mqueue::MqueueFileSystem::instance()
+        .root_dir_node()
+        .get(name)
+}
+
+/// Adds `queue` to the mqueue filesystem as `name`, whose permissions and
+/// owner are given by `attr`.
+#[cfg(feature = "mqueue")]
+pub fn mq_create(
+    name: &str,
+    queue: MessageQueue,
+    attr: &VfsSetAttr,
+) -> AxResult<alloc::sync::Arc<MessageQueue>> {
+    let root = crate::fs::mqueue::MqueueFileSystem::instance().root_dir_node();
+    let queue = root.create_queue(name, queue, attr)?;
+    crate::root::mqueue_changed();
+    Ok(queue)
+}
+
+/// Removes the POSIX message queue `name`, the queue is destroyed after all
+/// its descriptors are closed.
+#[cfg(feature = "mqueue")]
+pub fn mq_unlink(name: &str) -> AxResult {
+    let root = crate::fs::mqueue::MqueueFileSystem::instance().root_dir_node();
+    root.remove_queue(name)?;
+    crate::root::mqueue_changed();
+    Ok(())
+}
+
+/// Creates an empty file in memory that is not linked in any directory,
+/// which is destroyed after it is closed. Returns it opened for reading and
+/// writing, with the initial `seals`.
+#[cfg(feature = "ramfs")]
+pub fn create_anonymous_file(seals: VfsSeals) -> File {
+    let node = crate::fs::ramfs::FileNode::with_seals(seals);
+    File::new_anonymous(alloc::sync::Arc::new(node))
+}
+
+/// Unmount the filesystem mounted at `target`.
+pub async fn umount(target: &str, flags: UmountFlags) -> AxResult {
+    crate::root::umount(target, flags).await
+}
+
+/// Check if a path is mounted on.
+pub async fn is_mount_point(path: &str) -> bool {
+    match crate::root::absolute_path(path).await {
+        Ok(path) => crate::root::is_mount_point(&path),
+        Err(_) => false,
+    }
+}
+
+/// Whether the root filesystem is a ramfs unpacked from an initramfs.
+pub fn is_initramfs_root() -> bool {
+    crate::root::is_initramfs_root()
+}
+
+/// The content of `/proc/mounts`, generated from the mount tree.
+pub fn mounts_info() -> String {
+    crate::root::mounts_info()
+}
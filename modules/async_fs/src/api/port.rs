@@ -1,10 +1,12 @@
 //! 定义与文件I/O操作相关的trait泛型
 extern crate alloc;
 use core::any::Any;
+use core::task::{Context, Waker};
 
 use alloc::{string::String, boxed::Box};
 use axerrno::{AxError, AxResult};
 use async_io::{AsyncRead, AsyncSeek, AsyncWrite, SeekFrom, Seek, Read, Write};
+use async_vfs::VfsNodeRef;
 pub use async_trait::async_trait;
 
 /// 文件系统信息
@@ -211,6 +213,9 @@ pub trait FileExt: AsyncRead + AsyncWrite + AsyncSeek + AsAny + Send + Sync + Un
     /// whether the file is executable
     async fn executable(&self) -> bool;
 
+    /// 复制一个访问同一文件、但拥有独立指针的对象，映射区域被分割或者随 fork 复制时使用
+    fn clone_file(&self) -> Box<dyn FileExt>;
+
     /// Read from position without changing cursor.
     async fn read_from_seek(&mut self, pos: SeekFrom, buf: &mut [u8]) -> AxResult<usize> {
         // get old position
@@ -281,6 +286,11 @@ pub trait FileIO: AsAny + Send + Sync + Unpin {
         Err(AxError::Unsupported) // 如果没有实现, 则返回Unsupported
     }
 
+    /// 读取但不消耗数据，之后的 read 仍能读到相同的内容。tee 用它复制管道中的数据
+    async fn peek(&self, _buf: &mut [u8]) -> AxResult<usize> {
+        Err(AxError::Unsupported) // 如果没有实现, 则返回Unsupported
+    }
+
     /// 移动指针操作
     async fn seek(&self, _pos: SeekFrom) -> AxResult<u64> {
         Err(AxError::Unsupported) // 如果没有实现, 则返回Unsupported
@@ -303,6 +313,13 @@ pub trait FileIO: AsAny + Send + Sync + Unpin {
         debug!("Function get_path not implemented");
         String::from("Function get_path not implemented")
     }
+    /// 获取目录描述符对应的目录节点，*at 系列调用从该节点解析相对路径
+    ///
+    /// 不是目录描述符时返回 None
+    async fn dir_node(&self) -> Option<VfsNodeRef> {
+        None
+    }
+
     /// 获取文件信息
     async fn get_stat(&self) -> AxResult<Kstat> {
         Err(AxError::Unsupported) // 如果没有实现get_stat, 则返回Unsupported
@@ -358,10 +375,26 @@ pub trait FileIO: AsAny + Send + Sync + Unpin {
         false
     }
 
+    /// 注册 `cx`，在 ready_to_read、ready_to_write 或 is_hang_up 的结果可能变化时唤醒对应的任务
+    ///
+    /// poll/epoll 应先注册再检查就绪状态，以免错过两者之间的变化。
+    /// 返回 false 表示该文件不会主动唤醒等待者，调用者只能定时轮询
+    fn register_ready_waker(&self, _cx: &mut Context<'_>) -> bool {
+        false
+    }
+
+    /// 撤销 `register_ready_waker` 注册的 `waker`，等待者不再关心该文件时调用
+    fn unregister_ready_waker(&self, _waker: &Waker) {}
+
     /// To control the file descriptor
     async fn ioctl(&self, _request: usize, _arg1: usize) -> AxResult<isize> {
         Err(AxError::Unsupported)
     }
+
+    /// 获取 mmap 时作为映射区域后备的对象，不能被映射的文件返回 Unsupported
+    async fn mmap_file(&self) -> AxResult<Box<dyn FileExt>> {
+        Err(AxError::Unsupported)
+    }
 }
 
 // pub trait AsyncFileIOExt {
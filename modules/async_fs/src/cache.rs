@@ -0,0 +1,391 @@
+//! 块设备缓存层
+//!
+//! 位于文件系统与块设备之间，所有通过 [`Disk`](crate::dev::Disk) 进行的读写都会经过这里：
+//!     1. 以块为单位缓存设备数据，容量满时按 LRU 策略淘汰；
+//!     2. 写操作只修改缓存并标记为脏块，淘汰、`sync`/`fsync` 或周期性回写时才真正写入设备；
+//!     3. 检测到顺序访问时进行预读，预读窗口随连续命中逐步翻倍，直到 `block-cache-readahead`。
+//!
+//! 缓存容量、预读窗口上限、回写周期均由 `axconfig` 中的平台配置给出。
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use axdriver::prelude::*;
+use spinlock::{SpinNoIrq, SpinNoPreempt};
+
+const BLOCK_SIZE: usize = 512;
+
+/// 每个块设备缓存的块数
+pub const CACHE_BLOCKS: usize = axconfig::BLOCK_CACHE_BLOCKS;
+
+/// 顺序预读时一次最多预读的块数
+pub const READAHEAD_MAX_BLOCKS: usize = axconfig::BLOCK_CACHE_READAHEAD;
+
+/// 周期性回写的间隔（毫秒）
+pub const WRITEBACK_INTERVAL_MS: usize = axconfig::BLOCK_CACHE_WRITEBACK_INTERVAL;
+
+/// 所有已创建的块缓存，`sync` 时逐个回写
+static BLOCK_CACHES: SpinNoIrq<Vec<Arc<BlockCache>>> = SpinNoIrq::new(Vec::new());
+
+const NIL: usize = usize::MAX;
+
+struct LruNode<V> {
+    key: u64,
+    value: V,
+    prev: usize,
+    next: usize,
+}
+
+/// 定长的 LRU 表，槽位以下标组成双向链表，`head` 为最近使用，`tail` 为最久未使用
+pub(crate) struct LruCache<V> {
+    nodes: Vec<LruNode<V>>,
+    map: BTreeMap<u64, usize>,
+    head: usize,
+    tail: usize,
+    capacity: usize,
+}
+
+impl<V> LruCache<V> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        assert!(capacity > 0);
+        Self {
+            nodes: Vec::with_capacity(capacity),
+            map: BTreeMap::new(),
+            head: NIL,
+            tail: NIL,
+            capacity,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.nodes.len() >= self.capacity
+    }
+
+    pub(crate) fn contains(&self, key: u64) -> bool {
+        self.map.contains_key(&key)
+    }
+
+    fn unlink(&mut self, idx: usize) {
+        let (prev, next) = (self.nodes[idx].prev, self.nodes[idx].next);
+        if prev != NIL {
+            self.nodes[prev].next = next;
+        } else {
+            self.head = next;
+        }
+        if next != NIL {
+            self.nodes[next].prev = prev;
+        } else {
+            self.tail = prev;
+        }
+    }
+
+    fn push_front(&mut self, idx: usize) {
+        self.nodes[idx].prev = NIL;
+        self.nodes[idx].next = self.head;
+        if self.head != NIL {
+            self.nodes[self.head].prev = idx;
+        }
+        self.head = idx;
+        if self.tail == NIL {
+            self.tail = idx;
+        }
+    }
+
+    /// 查找并将其标记为最近使用
+    pub(crate) fn get_mut(&mut self, key: u64) -> Option<&mut V> {
+        let idx = *self.map.get(&key)?;
+        if self.head != idx {
+            self.unlink(idx);
+            self.push_front(idx);
+        }
+        Some(&mut self.nodes[idx].value)
+    }
+
+    /// 查找但不改变 LRU 顺序
+    pub(crate) fn peek_mut(&mut self, key: u64) -> Option<&mut V> {
+        let idx = *self.map.get(&key)?;
+        Some(&mut self.nodes[idx].value)
+    }
+
+    /// 最久未使用的表项，不改变 LRU 顺序
+    pub(crate) fn lru_mut(&mut self) -> Option<(u64, &mut V)> {
+        if self.tail == NIL {
+            return None;
+        }
+        let node = &mut self.nodes[self.tail];
+        Some((node.key, &mut node.value))
+    }
+
+    /// 插入新表项，若表已满则淘汰并返回最久未使用的表项
+    ///
+    /// 调用者需保证 `key` 不在表中
+    pub(crate) fn insert(&mut self, key: u64, value: V) -> Option<(u64, V)> {
+        debug_assert!(!self.map.contains_key(&key));
+        if !self.is_full() {
+            let idx = self.nodes.len();
+            self.nodes.push(LruNode {
+                key,
+                value,
+                prev: NIL,
+                next: NIL,
+            });
+            self.map.insert(key, idx);
+            self.push_front(idx);
+            return None;
+        }
+        // 复用最久未使用的槽位
+        let idx = self.tail;
+        self.unlink(idx);
+        let node = &mut self.nodes[idx];
+        let old_key = core::mem::replace(&mut node.key, key);
+        let old_value = core::mem::replace(&mut node.value, value);
+        self.map.remove(&old_key);
+        self.map.insert(key, idx);
+        self.push_front(idx);
+        Some((old_key, old_value))
+    }
+
+    /// 按 key 升序遍历所有表项，不改变 LRU 顺序
+    pub(crate) fn for_each_mut<E>(
+        &mut self,
+        mut f: impl FnMut(u64, &mut V) -> Result<(), E>,
+    ) -> Result<(), E> {
+        for (&key, &idx) in self.map.iter() {
+            f(key, &mut self.nodes[idx].value)?;
+        }
+        Ok(())
+    }
+}
+
+struct CachedBlock {
+    data: [u8; BLOCK_SIZE],
+    dirty: bool,
+}
+
+struct CacheState {
+    lru: LruCache<CachedBlock>,
+    /// 若下一次访问的块号等于该值，则认为是顺序访问
+    next_seq: u64,
+    /// 当前预读窗口大小
+    ra_window: usize,
+}
+
+/// 一个块设备的缓存，同一设备上的所有 [`Disk`](crate::dev::Disk) 共享同一份缓存
+///
+/// 缓存表由关中断的自旋锁保护，只在其中拷贝数据；设备读写在 `dev` 的锁中进行，
+/// 不会关闭中断。未命中的块总是在持有 `dev` 的锁时读入，因此同一个块不会被重复读入。
+/// 两把锁同时持有时总是先获取 `dev`
+pub struct BlockCache {
+    dev: SpinNoPreempt<AxBlockDevice>,
+    state: SpinNoIrq<CacheState>,
+    num_blocks: u64,
+}
+
+impl BlockCache {
+    /// 为块设备创建缓存，并登记到全局列表中以便 `sync`
+    pub fn new(dev: AxBlockDevice) -> Arc<Self> {
+        assert_eq!(BLOCK_SIZE, dev.block_size());
+        let cache = Arc::new(Self {
+            num_blocks: dev.num_blocks(),
+            dev: SpinNoPreempt::new(dev),
+            state: SpinNoIrq::new(CacheState {
+                lru: LruCache::new(CACHE_BLOCKS),
+                next_seq: 0,
+                ra_window: 0,
+            }),
+        });
+        BLOCK_CACHES.lock().push(cache.clone());
+        cache
+    }
+
+    /// 设备的块数
+    pub fn num_blocks(&self) -> u64 {
+        self.num_blocks
+    }
+
+    /// 当前缓存的块数
+    pub fn cached_blocks(&self) -> usize {
+        self.state.lock().lru.len()
+    }
+
+    /// 从块内偏移 `offset` 处读取数据，`offset + buf.len()` 不能超过块大小
+    pub fn read_at(&self, block_id: u64, offset: usize, buf: &mut [u8]) -> DevResult {
+        self.with_block(block_id, None, |block| {
+            buf.copy_from_slice(&block.data[offset..offset + buf.len()]);
+        })
+    }
+
+    /// 向块内偏移 `offset` 处写入数据，仅修改缓存并标记为脏块
+    ///
+    /// 整块写入时不需要先从设备读入原有数据
+    pub fn write_at(&self, block_id: u64, offset: usize, buf: &[u8]) -> DevResult {
+        let whole = (offset == 0 && buf.len() == BLOCK_SIZE).then_some(buf);
+        self.with_block(block_id, whole, |block| {
+            block.data[offset..offset + buf.len()].copy_from_slice(buf);
+            block.dirty = true;
+        })
+    }
+
+    /// 将所有脏块写回设备
+    pub fn flush(&self) -> DevResult {
+        let mut dev = self.dev.lock();
+        let mut dirty = Vec::new();
+        let _ = self.state.lock().lru.for_each_mut(|block_id, block| {
+            if block.dirty {
+                dirty.push((block_id, block.data));
+                block.dirty = false;
+            }
+            Ok::<(), ()>(())
+        });
+        for (i, (block_id, data)) in dirty.iter().enumerate() {
+            if let Err(err) = dev.write_block(*block_id, data) {
+                self.redirty(dirty[i..].iter().map(|(block_id, _)| *block_id));
+                return Err(err);
+            }
+        }
+        dev.flush()
+    }
+
+    /// 在缓存锁中对块 `block_id` 调用 `f`，未命中时先从设备读入
+    ///
+    /// `whole` 为将要覆盖整块的数据，此时未命中也不需要读设备
+    fn with_block<R>(
+        &self,
+        block_id: u64,
+        whole: Option<&[u8]>,
+        f: impl FnOnce(&mut CachedBlock) -> R,
+    ) -> DevResult<R> {
+        let window = {
+            let mut state = self.state.lock();
+            let sequential = block_id == state.next_seq;
+            state.next_seq = block_id + 1;
+            if let Some(block) = state.lru.get_mut(block_id) {
+                return Ok(f(block));
+            }
+            if whole.is_some() {
+                0
+            } else if sequential {
+                state.ra_window = (state.ra_window * 2).clamp(1, READAHEAD_MAX_BLOCKS);
+                state.ra_window as u64
+            } else {
+                state.ra_window = 0;
+                0
+            }
+        };
+
+        let mut dev = self.dev.lock();
+        let mut block = CachedBlock {
+            data: [0u8; BLOCK_SIZE],
+            dirty: false,
+        };
+        match whole {
+            Some(data) => block.data.copy_from_slice(data),
+            // 等待设备的锁时，该块可能已经被其他 CPU 读入
+            None if !self.state.lock().lru.contains(block_id) => {
+                dev.read_block(block_id, &mut block.data)?
+            }
+            None => {}
+        }
+        // 预读失败不影响本次访问
+        let mut ahead = Vec::new();
+        for id in block_id + 1..(block_id + 1 + window).min(self.num_blocks) {
+            if self.state.lock().lru.contains(id) {
+                continue;
+            }
+            let mut block = CachedBlock {
+                data: [0u8; BLOCK_SIZE],
+                dirty: false,
+            };
+            if dev.read_block(id, &mut block.data).is_err() {
+                break;
+            }
+            ahead.push((id, block));
+        }
+        for (id, block) in ahead {
+            let _ = self.insert(&mut dev, id, block, |_| ());
+        }
+        // 最后插入本次访问的块，使其不会被预读的块淘汰
+        self.insert(&mut dev, block_id, block, f)
+    }
+
+    /// 插入新读入的块，并在缓存锁中对其调用 `f`；块已经在缓存中时不覆盖，直接使用缓存中的块
+    ///
+    /// 缓存已满且最久未使用的块为脏时，先在缓存锁外将其写回，再重新检查
+    fn insert<R>(
+        &self,
+        dev: &mut AxBlockDevice,
+        block_id: u64,
+        block: CachedBlock,
+        f: impl FnOnce(&mut CachedBlock) -> R,
+    ) -> DevResult<R> {
+        loop {
+            let mut state = self.state.lock();
+            if state.lru.contains(block_id) {
+                return Ok(f(state.lru.get_mut(block_id).unwrap()));
+            }
+            let full = state.lru.is_full();
+            let victim = match state.lru.lru_mut() {
+                Some((victim, old)) if full && old.dirty => {
+                    old.dirty = false;
+                    Some((victim, old.data))
+                }
+                _ => None,
+            };
+            match victim {
+                Some((victim, data)) => {
+                    drop(state);
+                    if let Err(err) = dev.write_block(victim, &data) {
+                        self.redirty(core::iter::once(victim));
+                        return Err(err);
+                    }
+                }
+                None => {
+                    state.lru.insert(block_id, block);
+                    return Ok(f(state.lru.get_mut(block_id).unwrap()));
+                }
+            }
+        }
+    }
+
+    /// 写回失败的块若仍在缓存中，重新标记为脏块
+    fn redirty(&self, blocks: impl Iterator<Item = u64>) {
+        let mut state = self.state.lock();
+        for block_id in blocks {
+            if let Some(block) = state.lru.peek_mut(block_id) {
+                block.dirty = true;
+            }
+        }
+    }
+}
+
+/// 回写所有块设备缓存中的脏块
+pub fn sync_all() -> DevResult {
+    let caches = BLOCK_CACHES.lock().clone();
+    for cache in caches {
+        cache.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LruCache;
+
+    #[test]
+    fn lru_evicts_least_recently_used() {
+        let mut lru = LruCache::new(2);
+        assert!(lru.insert(1, 'a').is_none());
+        assert!(lru.insert(2, 'b').is_none());
+        // 访问 1 之后，2 成为最久未使用的表项
+        assert_eq!(lru.get_mut(1), Some(&mut 'a'));
+        assert_eq!(lru.insert(3, 'c'), Some((2, 'b')));
+        assert!(!lru.contains(2));
+        assert_eq!(lru.lru_mut(), Some((1, &mut 'a')));
+        assert_eq!(lru.insert(4, 'd'), Some((1, 'a')));
+        assert_eq!(lru.len(), 2);
+    }
+}
@@ -1,29 +1,44 @@
+use alloc::sync::Arc;
 use axdriver::prelude::*;
 
+use crate::cache::BlockCache;
+
 const BLOCK_SIZE: usize = 512;
 
 /// A disk device with a cursor.
+///
+/// All accesses go through the [`BlockCache`] of the device, which may be
+/// shared by several disks on the same device.
 pub struct Disk {
     block_id: u64,
     offset: usize,
-    dev: AxBlockDevice,
+    cache: Arc<BlockCache>,
 }
 
 #[allow(unused)]
 impl Disk {
     /// Create a new disk.
     pub fn new(dev: AxBlockDevice) -> Self {
-        assert_eq!(BLOCK_SIZE, dev.block_size());
+        Self::from_cache(BlockCache::new(dev))
+    }
+
+    /// Create a new disk on an existing block cache.
+    pub fn from_cache(cache: Arc<BlockCache>) -> Self {
         Self {
             block_id: 0,
             offset: 0,
-            dev,
+            cache,
         }
     }
 
+    /// Get the block cache of the disk.
+    pub fn cache(&self) -> &Arc<BlockCache> {
+        &self.cache
+    }
+
     /// Get the size of the disk.
     pub fn size(&self) -> u64 {
-        self.dev.num_blocks() * BLOCK_SIZE as u64
+        self.cache.num_blocks() * BLOCK_SIZE as u64
     }
 
     /// Get the position of the cursor.
@@ -39,60 +54,30 @@ impl Disk {
 
     /// Read within one block, returns the number of bytes read.
     pub fn read_one(&mut self, buf: &mut [u8]) -> DevResult<usize> {
-        // info!("block id: {}", self.block_id);
-        let read_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
-            // whole block
-            self.dev
-                .read_block(self.block_id, &mut buf[0..BLOCK_SIZE])?;
+        let start = self.offset;
+        let count = buf.len().min(BLOCK_SIZE - self.offset);
+        self.cache.read_at(self.block_id, start, &mut buf[..count])?;
+
+        self.offset += count;
+        if self.offset >= BLOCK_SIZE {
             self.block_id += 1;
-            BLOCK_SIZE
-        } else {
-            // partial block
-            let mut data = [0u8; BLOCK_SIZE];
-            let start = self.offset;
-            let count = buf.len().min(BLOCK_SIZE - self.offset);
-            if start > BLOCK_SIZE {
-                info!("block size: {} start {}", BLOCK_SIZE, start);
-            }
-
-            self.dev.read_block(self.block_id, &mut data)?;
-            buf[..count].copy_from_slice(&data[start..start + count]);
-
-            self.offset += count;
-            if self.offset >= BLOCK_SIZE {
-                self.block_id += 1;
-                self.offset -= BLOCK_SIZE;
-            }
-            count
-        };
-        Ok(read_size)
+            self.offset -= BLOCK_SIZE;
+        }
+        Ok(count)
     }
 
     /// Write within one block, returns the number of bytes written.
     pub fn write_one(&mut self, buf: &[u8]) -> DevResult<usize> {
-        let write_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
-            // whole block
-            self.dev.write_block(self.block_id, &buf[0..BLOCK_SIZE])?;
+        let start = self.offset;
+        let count = buf.len().min(BLOCK_SIZE - self.offset);
+        self.cache.write_at(self.block_id, start, &buf[..count])?;
+
+        self.offset += count;
+        if self.offset >= BLOCK_SIZE {
             self.block_id += 1;
-            BLOCK_SIZE
-        } else {
-            // partial block
-            let mut data = [0u8; BLOCK_SIZE];
-            let start = self.offset;
-            let count = buf.len().min(BLOCK_SIZE - self.offset);
-
-            self.dev.read_block(self.block_id, &mut data)?;
-            data[start..start + count].copy_from_slice(&buf[..count]);
-            self.dev.write_block(self.block_id, &data)?;
-
-            self.offset += count;
-            if self.offset >= BLOCK_SIZE {
-                self.block_id += 1;
-                self.offset -= BLOCK_SIZE;
-            }
-            count
-        };
-        Ok(write_size)
+            self.offset -= BLOCK_SIZE;
+        }
+        Ok(count)
     }
 
     /// Read a single block starting from the specified offset.
@@ -100,8 +85,8 @@ impl Disk {
     pub fn read_offset(&mut self, offset: usize) -> [u8; BLOCK_SIZE] {
         let block_id = offset / BLOCK_SIZE;
         let mut block_data = [0u8; BLOCK_SIZE];
-        self.dev
-            .read_block(block_id as u64, &mut block_data)
+        self.cache
+            .read_at(block_id as u64, 0, &mut block_data)
             .unwrap();
         block_data
     }
@@ -115,8 +100,12 @@ impl Disk {
         );
         assert!(offset % BLOCK_SIZE == 0);
         let block_id = offset / BLOCK_SIZE;
-        self.dev.write_block(block_id as u64, buf).unwrap();
+        self.cache.write_at(block_id as u64, 0, buf).unwrap();
         Ok(buf.len())
     }
-}
 
+    /// Write all dirty cached blocks of the underlying device back.
+    pub fn flush(&mut self) -> DevResult {
+        self.cache.flush()
+    }
+}
@@ -1,12 +1,18 @@
 //! Low-level filesystem operations.
 
+use alloc::string::String;
+use alloc::sync::Arc;
 use axerrno::{ax_err, ax_err_type, AxResult};
-use async_vfs::{AsyncVfsNodeOps, VfsError, VfsNodeOps, VfsNodeRef};
+use async_vfs::{
+    AsyncVfsNodeOps, VfsError, VfsNodeOps, VfsNodeRef, VfsSeals, VfsSetAttr, VfsXattrFlags,
+};
 use async_io::{AsyncRead, AsyncSeek, AsyncWrite, SeekFrom};
 use capability::{Cap, WithCap};
 use core::fmt;
 use core::pin::Pin;
 use core::task::{Context, Poll};
+use crate::notify::InotifyMask;
+use crate::root::Mount;
 
 #[cfg(feature = "myfs")]
 pub use crate::dev::Disk;
@@ -28,6 +34,10 @@ pub struct File {
     node: WithCap<VfsNodeRef>,
     is_append: bool,
     offset: u64,
+    /// 用于事件通知的绝对路径
+    path: Option<String>,
+    /// 文件所在的挂载，匿名文件为 `None`
+    mount: Option<Arc<Mount>>,
 }
 
 impl AsyncRead for File {
@@ -36,12 +46,15 @@ impl AsyncRead for File {
         cx: &mut Context<'_>,
         buf: &mut [u8],
     ) -> Poll<AxResult<usize>> {
-        let Self { node, offset, .. } = self.get_mut();
-        let node = node.access(Cap::READ)?;
+        let this = self.get_mut();
+        let node = this.node.access(Cap::READ)?;
         let read_len = futures_core::ready!(
-            VfsNodeOps::read_at(Pin::new(node), cx, *offset, buf)
+            VfsNodeOps::read_at(Pin::new(node), cx, this.offset, buf)
         )?;
-        *offset += read_len as u64;
+        this.offset += read_len as u64;
+        if read_len > 0 {
+            this.notify(InotifyMask::ACCESS);
+        }
         Poll::Ready(Ok(read_len))
     }
 }
@@ -52,16 +65,19 @@ impl AsyncWrite for File {
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<AxResult<usize>> {
-        let Self { node, is_append, offset } = self.get_mut();
-        let node = node.access(Cap::WRITE)?;
-        if *is_append {
+        let this = self.get_mut();
+        let node = this.node.access(Cap::WRITE)?;
+        if this.is_append {
             let attr = futures_core::ready!(VfsNodeOps::get_attr(Pin::new(node), cx)).unwrap();
-            *offset = attr.size();
+            this.offset = attr.size();
         };
         let write_len = futures_core::ready!(
-            VfsNodeOps::write_at(Pin::new(node), cx, *offset, buf)
+            VfsNodeOps::write_at(Pin::new(node), cx, this.offset, buf)
         ).unwrap();
-        *offset += write_len as u64;
+        this.offset += write_len as u64;
+        if write_len > 0 {
+            this.notify(InotifyMask::MODIFY);
+        }
         Poll::Ready(Ok(write_len))
     }
 
@@ -103,6 +119,8 @@ impl AsyncSeek for File {
 pub struct Directory {
     node: WithCap<VfsNodeRef>,
     entry_idx: usize,
+    /// 目录所在的挂载，相对于该目录的路径都属于它
+    mount: Arc<Mount>,
 }
 
 /// Options and flags which can be used to configure how a file is opened.
@@ -183,7 +201,12 @@ impl OpenOptions {
 }
 
 impl File {
-    async fn _open_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    async fn _open_at(
+        dir: Option<&VfsNodeRef>,
+        dir_mount: Option<&Arc<Mount>>,
+        path: &str,
+        opts: &OpenOptions,
+    ) -> AxResult<Self> {
         debug!("open file: {} {:?}", path, opts);
         if !opts.is_valid() {
             return ax_err!(InvalidInput);
@@ -217,29 +240,123 @@ impl File {
         if !perm_to_cap(attr.perm()).contains(access_cap) {
             return ax_err!(PermissionDenied);
         }
+        let mount = crate::root::mount_of(dir_mount, path).await?;
+        if (opts.write || opts.append || opts.truncate) && mount.is_read_only() {
+            return ax_err!(PermissionDenied, "read-only filesystem");
+        }
         node.open().await?;
         if opts.truncate {
             node.truncate(0).await?;
         }
-        Ok(Self {
+        let file = Self {
             node: WithCap::new(node, access_cap),
             is_append: opts.append,
             offset: 0,
-        })
+            path: crate::root::event_path(dir, path).await,
+            mount: Some(mount),
+        };
+        file.notify(InotifyMask::OPEN);
+        if opts.truncate {
+            file.notify(InotifyMask::MODIFY);
+        }
+        Ok(file)
+    }
+
+    /// 向监视该文件的 inotify 实例投递事件
+    fn notify(&self, mask: InotifyMask) {
+        if let Some(path) = &self.path {
+            crate::notify::notify(path, mask);
+        }
+    }
+
+    /// Opens `node` for reading and writing, which is not linked in any
+    /// directory, e.g. the file created by memfd_create.
+    pub fn new_anonymous(node: VfsNodeRef) -> Self {
+        Self {
+            node: WithCap::new(node, Cap::READ | Cap::WRITE),
+            is_append: false,
+            offset: 0,
+            path: None,
+            mount: None,
+        }
     }
 
     /// Opens a file at the path relative to the current directory. Returns a
     /// [`File`] object.
     pub async fn open_withperm(path: &str, opts: &OpenOptions) -> AxResult<Self> {
-        Self::_open_at(None, path, opts).await
+        Self::_open_at(None, None, path, opts).await
     }
 
     /// Truncates the file to the specified size.
     pub async fn truncate(&self, size: u64) -> AxResult {
         self.node.access(Cap::WRITE)?.truncate(size).await?;
+        self.notify(InotifyMask::MODIFY);
+        Ok(())
+    }
+
+    /// Allocates the disk space of the range `[offset, offset + len)`. The
+    /// file is extended if the range ends beyond its size, unless `keep_size`
+    /// is set.
+    pub async fn allocate(&self, offset: u64, len: u64, keep_size: bool) -> AxResult {
+        self.node
+            .access(Cap::WRITE)?
+            .allocate(offset, len, keep_size)
+            .await?;
+        self.notify(InotifyMask::MODIFY);
+        Ok(())
+    }
+
+    /// Deallocates the range `[offset, offset + len)`, which is read as zeros
+    /// afterwards. The size of the file is not changed.
+    pub async fn punch_hole(&self, offset: u64, len: u64) -> AxResult {
+        self.node.access(Cap::WRITE)?.punch_hole(offset, len).await?;
+        self.notify(InotifyMask::MODIFY);
+        Ok(())
+    }
+
+    /// Zeroes the range `[offset, offset + len)` and allocates its disk space.
+    ///
+    /// On filesystems without holes the zeros are written out.
+    pub async fn zero_range(&self, offset: u64, len: u64, keep_size: bool) -> AxResult {
+        let node = self.node.access(Cap::WRITE)?;
+        match node.punch_hole(offset, len).await {
+            Ok(()) => {}
+            Err(VfsError::Unsupported) => {
+                let end = offset.saturating_add(len).min(node.get_attr().await?.size());
+                let zeros = [0u8; 512];
+                let mut pos = offset;
+                while pos < end {
+                    let chunk = (end - pos).min(zeros.len() as u64) as usize;
+                    match node.write_at(pos, &zeros[..chunk]).await? {
+                        0 => return ax_err!(StorageFull),
+                        n => pos += n as u64,
+                    }
+                }
+            }
+            Err(err) => return Err(err),
+        }
+        node.allocate(offset, len, keep_size).await?;
+        self.notify(InotifyMask::MODIFY);
         Ok(())
     }
 
+    /// Moves the cursor to the next data region (or hole, if `hole` is set)
+    /// at or after `offset`, for `SEEK_DATA` and `SEEK_HOLE`.
+    ///
+    /// Returns `None` and leaves the cursor unchanged if there is no such
+    /// region.
+    pub async fn seek_data(&mut self, offset: u64, hole: bool) -> AxResult<Option<u64>> {
+        let found = self
+            .node
+            .access(Cap::empty())?
+            .seek_data(offset, hole)
+            .await?;
+        if let Some(pos) = found {
+            self.offset = pos;
+        }
+        Ok(found)
+    }
+
     /// Reads the file at the current position. Returns the number of bytes
     /// read.
     ///
@@ -248,6 +365,9 @@ impl File {
         let node = self.node.access(Cap::READ)?;
         let read_len = node.read_at(self.offset, buf).await?;
         self.offset += read_len as u64;
+        if read_len > 0 {
+            self.notify(InotifyMask::ACCESS);
+        }
         Ok(read_len)
     }
 
@@ -257,6 +377,9 @@ impl File {
     pub async fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
         let node = self.node.access(Cap::READ)?;
         let read_len = node.read_at(offset, buf).await?;
+        if read_len > 0 {
+            self.notify(InotifyMask::ACCESS);
+        }
         Ok(read_len)
     }
 
@@ -272,6 +395,9 @@ impl File {
         };
         let write_len = node.write_at(self.offset, buf).await?;
         self.offset += write_len as u64;
+        if write_len > 0 {
+            self.notify(InotifyMask::MODIFY);
+        }
         Ok(write_len)
     }
 
@@ -282,12 +408,17 @@ impl File {
     pub async fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
         let node = self.node.access(Cap::WRITE)?;
         let write_len = node.write_at(offset, buf).await?;
+        if write_len > 0 {
+            self.notify(InotifyMask::MODIFY);
+        }
         Ok(write_len)
     }
 
     /// Flushes the file, writes all buffered data to the underlying device.
+    ///
+    /// Like `fsync` on Linux, it doesn't require the file to be opened for writing.
     pub async fn flush(&self) -> AxResult {
-        self.node.access(Cap::WRITE)?.fsync().await?;
+        self.node.access(Cap::empty())?.fsync().await?;
         Ok(())
     }
 
@@ -310,6 +441,68 @@ impl File {
         self.node.access(Cap::empty())?.get_attr().await
     }
 
+    /// Changes the file attributes, e.g. the timestamps.
+    pub async fn set_attr(&self, attr: &VfsSetAttr) -> AxResult {
+        self.check_writable_mount().await?;
+        self.node.access(Cap::empty())?.setattr(attr).await?;
+        self.notify(InotifyMask::ATTRIB);
+        Ok(())
+    }
+
+    /// Reads the value of the extended attribute `name` into `buf`. Returns
+    /// the length of the value, which is copied only if it fits.
+    pub async fn get_xattr(&self, name: &str, buf: &mut [u8]) -> AxResult<usize> {
+        self.node.access(Cap::empty())?.getxattr(name, buf).await
+    }
+
+    /// Sets the value of the extended attribute `name`.
+    pub async fn set_xattr(&self, name: &str, value: &[u8], flags: VfsXattrFlags) -> AxResult {
+        self.check_writable_mount().await?;
+        self.node
+            .access(Cap::empty())?
+            .setxattr(name, value, flags)
+            .await?;
+        self.notify(InotifyMask::ATTRIB);
+        Ok(())
+    }
+
+    /// Reads the names of all extended attributes into `buf`, each followed
+    /// by a `'\0'`. Returns the total length, the names are copied only if
+    /// they fit.
+    pub async fn list_xattr(&self, buf: &mut [u8]) -> AxResult<usize> {
+        self.node.access(Cap::empty())?.listxattr(buf).await
+    }
+
+    /// Removes the extended attribute `name`.
+    pub async fn remove_xattr(&self, name: &str) -> AxResult {
+        self.check_writable_mount().await?;
+        self.node.access(Cap::empty())?.removexattr(name).await?;
+        self.notify(InotifyMask::ATTRIB);
+        Ok(())
+    }
+
+    /// Identifies the node of the file, see [`VfsNodeOps::node_id`].
+    pub fn node_id(&self) -> u64 {
+        self.node.access(Cap::empty()).map_or(0, |node| node.node_id())
+    }
+
+    /// Gets the seals of the file.
+    pub async fn get_seals(&self) -> AxResult<VfsSeals> {
+        self.node.access(Cap::empty())?.get_seals().await
+    }
+
+    /// Adds `seals` to the file, which must be opened for writing.
+    pub async fn add_seals(&self, seals: VfsSeals) -> AxResult {
+        self.node.access(Cap::WRITE)?.add_seals(seals).await
+    }
+
+    async fn check_writable_mount(&self) -> AxResult {
+        if self.mount.as_ref().is_some_and(|mount| mount.is_read_only()) {
+            return ax_err!(PermissionDenied, "read-only filesystem");
+        }
+        Ok(())
+    }
+
     #[allow(unused)]
     /// whether the file is readable.
     pub fn readable(&self) -> bool {
@@ -330,7 +523,12 @@ impl File {
 }
 
 impl Directory {
-    async fn _open_dir_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    async fn _open_dir_at(
+        dir: Option<&VfsNodeRef>,
+        dir_mount: Option<&Arc<Mount>>,
+        path: &str,
+        opts: &OpenOptions,
+    ) -> AxResult<Self> {
         debug!("open dir: {}", path);
         if !opts.read {
             return ax_err!(InvalidInput);
@@ -349,10 +547,12 @@ impl Directory {
             return ax_err!(PermissionDenied);
         }
 
+        let mount = crate::root::mount_of(dir_mount, path).await?;
         node.open().await?;
         Ok(Self {
             node: WithCap::new(node, access_cap),
             entry_idx: 0,
+            mount,
         })
     }
 
@@ -364,41 +564,53 @@ impl Directory {
         }
     }
 
+    /// 路径位于只读挂载中时返回 PermissionDenied
+    async fn check_writable_at(&self, path: &str) -> AxResult {
+        if crate::root::is_read_only(Some(&self.mount), path).await {
+            return ax_err!(PermissionDenied, "read-only filesystem");
+        }
+        Ok(())
+    }
+
     /// Opens a directory at the path relative to the current directory.
     /// Returns a [`Directory`] object.
     pub async fn open_dir(path: &str, opts: &OpenOptions) -> AxResult<Self> {
-        Self::_open_dir_at(None, path, opts).await
+        Self::_open_dir_at(None, None, path, opts).await
     }
 
     /// Opens a directory at the path relative to this directory. Returns a
     /// [`Directory`] object.
     pub async fn open_dir_at(&self, path: &str, opts: &OpenOptions) -> AxResult<Self> {
-        Self::_open_dir_at(self.access_at(path)?, path, opts).await
+        Self::_open_dir_at(self.access_at(path)?, Some(&self.mount), path, opts).await
     }
 
     /// Opens a file at the path relative to this directory. Returns a [`File`]
     /// object.
     pub async fn open_file_at(&self, path: &str, opts: &OpenOptions) -> AxResult<File> {
-        File::_open_at(self.access_at(path)?, path, opts).await
+        File::_open_at(self.access_at(path)?, Some(&self.mount), path, opts).await
     }
 
     /// Creates an empty file at the path relative to this directory.
     pub async fn create_file(&self, path: &str) -> AxResult<VfsNodeRef> {
+        self.check_writable_at(path).await?;
         crate::root::create_file(self.access_at(path)?, path).await
     }
 
     /// Creates an empty directory at the path relative to this directory.
     pub async fn create_dir(&self, path: &str) -> AxResult {
+        self.check_writable_at(path).await?;
         crate::root::create_dir(self.access_at(path)?, path).await
     }
 
     /// Removes a file at the path relative to this directory.
     pub async fn remove_file(&self, path: &str) -> AxResult {
+        self.check_writable_at(path).await?;
         crate::root::remove_file(self.access_at(path)?, path).await
     }
 
     /// Removes a directory at the path relative to this directory.
     pub async fn remove_dir(&self, path: &str) -> AxResult {
+        self.check_writable_at(path).await?;
         crate::root::remove_dir(self.access_at(path)?, path).await
     }
 
@@ -421,7 +633,7 @@ impl Directory {
         cx: &mut Context<'_>, 
         dirents: &mut [DirEntry]
     ) -> Poll<AxResult<usize>> {
-        let Self { node, entry_idx } = self.get_mut();
+        let Self { node, entry_idx, .. } = self.get_mut();
         let node = node.access(Cap::READ)?;
         let n = futures_core::ready!(VfsNodeOps::read_dir(Pin::new(node), cx, *entry_idx, dirents))?;
         *entry_idx += n;
@@ -440,6 +652,11 @@ impl Directory {
 impl Drop for File {
     fn drop(&mut self) {
         unsafe { self.node.access_unchecked().release().ok() };
+        if self.writable() {
+            self.notify(InotifyMask::CLOSE_WRITE);
+        } else {
+            self.notify(InotifyMask::CLOSE_NOWRITE);
+        }
     }
 }
 
@@ -1,24 +1,205 @@
 
-use alloc::sync::Arc;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
 use core::cell::UnsafeCell;
+use core::time::Duration;
 
-use async_vfs::{VfsDirEntry, VfsError, VfsNodePerm, VfsResult};
+use async_vfs::{FileSystemInfo, VfsDirEntry, VfsError, VfsNodePerm, VfsResult, VfsSetAttr};
 use async_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps};
 use async_sync::Mutex;
-use fatfs::{Dir, File, LossyOemCpConverter, NullTimeProvider, Read, Seek, SeekFrom, Write};
+use fatfs::{Date, DateTime, DirEntry, LossyOemCpConverter, Time, TimeProvider};
+use fatfs::{Read, Seek, SeekFrom, Write};
 use core::{pin::Pin, task::{Context, Poll}};
+use spinlock::SpinNoIrq;
 
 use crate::dev::Disk;
 
 pub const BLOCK_SIZE: usize = 512;
 
+/// FAT 的 magic number
+const MSDOS_SUPER_MAGIC: u64 = 0x4d44;
+
+type File<'a> = fatfs::File<'a, Disk, KernelTimeProvider, LossyOemCpConverter>;
+type Dir<'a> = fatfs::Dir<'a, Disk, KernelTimeProvider, LossyOemCpConverter>;
+
+/// 使用内核的时钟（见 [`crate::set_clock`]）作为 FAT 文件的时间戳
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KernelTimeProvider;
+
+impl TimeProvider for KernelTimeProvider {
+    fn get_current_date(&self) -> Date {
+        date_time_from_duration(crate::now()).date
+    }
+
+    fn get_current_date_time(&self) -> DateTime {
+        date_time_from_duration(crate::now())
+    }
+}
+
+/// 公历日期距 1970-01-01 的天数
+const fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// 距 1970-01-01 `days` 天的公历日期
+const fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// FAT 能表示的最早时间 1980-01-01 00:00:00
+const FAT_EPOCH_SECS: u64 = days_from_civil(1980, 1, 1) as u64 * 86400;
+/// FAT 能表示的最晚时间 2107-12-31 23:59:59
+const FAT_MAX_SECS: u64 = days_from_civil(2108, 1, 1) as u64 * 86400 - 1;
+
+/// 将自 epoch 起的时间转换为 FAT 的时间，超出 FAT 表示范围的时间取最近的边界
+fn date_time_from_duration(time: Duration) -> DateTime {
+    let secs = time.as_secs().clamp(FAT_EPOCH_SECS, FAT_MAX_SECS);
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let secs_of_day = secs % 86400;
+    let millis = if secs == time.as_secs() { time.subsec_millis() } else { 0 };
+    DateTime::new(
+        Date::new(year as u16, month as u16, day as u16),
+        Time::new(
+            (secs_of_day / 3600) as u16,
+            (secs_of_day / 60 % 60) as u16,
+            (secs_of_day % 60) as u16,
+            millis as u16,
+        ),
+    )
+}
+
+fn duration_from_date(date: Date) -> Duration {
+    let days = days_from_civil(date.year as i64, date.month as i64, date.day as i64);
+    Duration::from_secs(days as u64 * 86400)
+}
+
+fn duration_from_date_time(date_time: DateTime) -> Duration {
+    let time = date_time.time;
+    duration_from_date(date_time.date)
+        + Duration::from_secs(time.hour as u64 * 3600 + time.min as u64 * 60 + time.sec as u64)
+        + Duration::from_millis(time.millis as u64)
+}
+
+/// 修改时间写入目录项后的值，精度为 2 秒
+fn stored_mtime(time: Duration) -> Duration {
+    let secs = duration_from_date_time(date_time_from_duration(time)).as_secs();
+    Duration::from_secs(secs & !1)
+}
+
+/// 目录项中记录的时间戳
+///
+/// FAT 的修改时间精度为 2 秒，访问时间只记录日期，没有状态变化时间，用修改时间代替
+#[derive(Debug, Clone, Copy, Default)]
+struct FatTimes {
+    atime: Duration,
+    mtime: Duration,
+}
+
+impl FatTimes {
+    fn from_entry(entry: &DirEntry<'_, Disk, KernelTimeProvider, LossyOemCpConverter>) -> Self {
+        Self {
+            atime: duration_from_date(entry.accessed()),
+            mtime: duration_from_date_time(entry.modified()),
+        }
+    }
+
+    fn fill(&self, attr: &mut VfsNodeAttr) {
+        attr.set_times(self.atime, self.mtime, self.mtime);
+    }
+}
+
 pub struct FatFileSystem {
-    inner: fatfs::FileSystem<Disk, NullTimeProvider, LossyOemCpConverter>,
+    inner: fatfs::FileSystem<Disk, KernelTimeProvider, LossyOemCpConverter>,
     root_dir: UnsafeCell<Option<VfsNodeRef>>,
+    ids: NodeIds,
+}
+
+/// FAT 每次查找都会创建新的节点，时间戳只在节点存在期间缓存，修改时直接写回目录项
+pub struct FileWrapper<'a>(Mutex<File<'a>>, SpinNoIrq<FatTimes>, NodeId);
+pub struct DirWrapper<'a> {
+    dir: Dir<'a>,
+    times: FatTimes,
+    fs: &'a FatFileSystem,
+    /// 相对于文件系统根目录的路径，根目录为空
+    path: String,
+    id: NodeId,
 }
 
-pub struct FileWrapper<'a>(Mutex<File<'a, Disk, NullTimeProvider, LossyOemCpConverter>>);
-pub struct DirWrapper<'a>(Dir<'a, Disk, NullTimeProvider, LossyOemCpConverter>);
+/// 文件的标识，以这块内存的地址作为 [`VfsNodeOps::node_id`]
+///
+/// 节点持有它，因此节点存在期间这个地址不会分配给其他文件
+type NodeId = Arc<()>;
+
+/// 以路径为键的文件标识
+///
+/// 节点每次查找都会重新创建，不能用节点的地址标识文件。FAT 没有硬链接，
+/// 文件由路径确定，重命名时标识随文件移动，删除时丢弃
+struct NodeIds(SpinNoIrq<BTreeMap<String, Weak<()>>>);
+
+impl NodeIds {
+    const fn new() -> Self {
+        Self(SpinNoIrq::new(BTreeMap::new()))
+    }
+
+    /// 路径 `path` 上的文件的标识，没有节点持有时分配新的标识
+    fn get(&self, path: &str) -> NodeId {
+        let mut ids = self.0.lock();
+        if let Some(id) = ids.get(path).and_then(Weak::upgrade) {
+            return id;
+        }
+        ids.retain(|_, id| id.strong_count() > 0);
+        let id = Arc::new(());
+        ids.insert(path.into(), Arc::downgrade(&id));
+        id
+    }
+
+    /// 丢弃 `path` 及其下所有文件的标识
+    fn remove(&self, path: &str) {
+        self.0.lock().retain(|key, _| !is_under(key, path));
+    }
+
+    /// 将 `src` 及其下所有文件的标识移动到 `dst`
+    fn rename(&self, src: &str, dst: &str) {
+        let mut ids = self.0.lock();
+        ids.retain(|key, _| !is_under(key, dst));
+        let moved: Vec<_> = ids.keys().filter(|key| is_under(key, src)).cloned().collect();
+        for key in moved {
+            let id = ids.remove(&key).unwrap();
+            ids.insert(format!("{}{}", dst, &key[src.len()..]), id);
+        }
+    }
+}
+
+/// `path` 是否是 `dir` 本身或者位于 `dir` 之下
+fn is_under(path: &str, dir: &str) -> bool {
+    path.strip_prefix(dir)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// 目录 `dir` 下的路径 `path`
+fn join(dir: &str, path: &str) -> String {
+    match dir.is_empty() {
+        true => path.into(),
+        false => format!("{}/{}", dir, path),
+    }
+}
 
 unsafe impl Sync for FatFileSystem {}
 unsafe impl Send for FatFileSystem {}
@@ -32,53 +213,109 @@ impl FatFileSystem {
     pub fn new(mut disk: Disk) -> Self {
         let opts = fatfs::FormatVolumeOptions::new();
         fatfs::format_volume(&mut disk, opts).expect("failed to format volume");
-        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new())
+        let inner = fatfs::FileSystem::new(disk, Self::options())
             .expect("failed to initialize FAT filesystem");
         Self {
             inner,
             root_dir: UnsafeCell::new(None),
+            ids: NodeIds::new(),
         }
     }
 
     #[cfg(not(feature = "use-ramdisk"))]
     pub fn new(disk: Disk) -> Self {
-        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new())
+        let inner = fatfs::FileSystem::new(disk, Self::options())
             .expect("failed to initialize FAT filesystem");
         Self {
             inner,
             root_dir: UnsafeCell::new(None),
+            ids: NodeIds::new(),
         }
     }
 
+    fn options() -> fatfs::FsOptions<KernelTimeProvider, LossyOemCpConverter> {
+        fatfs::FsOptions::new()
+            .time_provider(KernelTimeProvider)
+            .update_accessed_date(true)
+    }
+
     pub fn init(&'static self) {
         // must be called before later operations
-        unsafe { *self.root_dir.get() = Some(Self::new_dir(self.inner.root_dir())) }
+        let root_dir = self.new_dir(self.inner.root_dir(), FatTimes::default(), String::new());
+        unsafe { *self.root_dir.get() = Some(root_dir) }
     }
 
-    fn new_file(file: File<'_, Disk, NullTimeProvider, LossyOemCpConverter>) -> Arc<FileWrapper> {
-        Arc::new(FileWrapper(Mutex::new(file)))
+    fn new_file<'a>(&self, file: File<'a>, times: FatTimes, path: &str) -> Arc<FileWrapper<'a>> {
+        Arc::new(FileWrapper(Mutex::new(file), SpinNoIrq::new(times), self.ids.get(path)))
     }
 
-    fn new_dir(dir: Dir<'_, Disk, NullTimeProvider, LossyOemCpConverter>) -> Arc<DirWrapper> {
-        Arc::new(DirWrapper(dir))
+    fn new_dir<'a>(&'a self, dir: Dir<'a>, times: FatTimes, path: String) -> Arc<DirWrapper<'a>> {
+        let id = self.ids.get(&path);
+        Arc::new(DirWrapper { dir, times, fs: self, path, id })
+    }
+}
+
+/// 将文件从末尾用零扩展到 `size`
+///
+/// FAT 不支持空洞，越过文件末尾的写入与预分配都要真正写出零，扩展的部分会分配簇
+fn extend_with_zeros(file: &mut File<'_>, size: u64) -> VfsResult {
+    let mut end = file.seek(SeekFrom::End(0)).map_err(as_vfs_err)?;
+    let zeros = [0u8; BLOCK_SIZE];
+    while end < size {
+        let len = (size - end).min(BLOCK_SIZE as u64) as usize;
+        match file.write(&zeros[..len]).map_err(as_vfs_err)? {
+            0 => return Err(VfsError::StorageFull),
+            n => end += n as u64,
+        }
     }
+    Ok(())
 }
 
 impl VfsNodeOps for FileWrapper<'static> {
     async_vfs::impl_vfs_non_dir_default! {}
 
+    fn node_id(&self) -> u64 {
+        Arc::as_ptr(&self.2) as usize as u64
+    }
+
     fn get_attr(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<VfsResult<VfsNodeAttr>> {
         self.0.poll_lock(cx).map(|mut file| {
             let size = file.seek(SeekFrom::End(0)).map_err(as_vfs_err)?;
             let blocks = (size + BLOCK_SIZE as u64 - 1) / BLOCK_SIZE as u64;
             // FAT fs doesn't support permissions, we just set everything to 755
             let perm = VfsNodePerm::from_bits_truncate(0o755);
-            Ok(VfsNodeAttr::new(perm, VfsNodeType::File, size, blocks))
+            let mut attr = VfsNodeAttr::new(perm, VfsNodeType::File, size, blocks);
+            self.1.lock().fill(&mut attr);
+            Ok(attr)
+        })
+    }
+
+    fn setattr(self: Pin<&Self>, cx: &mut Context<'_>, attr: &VfsSetAttr) -> Poll<VfsResult> {
+        // FAT 不记录权限与所有者
+        if attr.mode.is_some() || attr.uid.is_some() || attr.gid.is_some() {
+            return Poll::Ready(Err(VfsError::Unsupported));
+        }
+        self.0.poll_lock(cx).map(|mut file| {
+            let mut times = self.1.lock();
+            if let Some(atime) = attr.atime {
+                let date = date_time_from_duration(atime).date;
+                file.set_accessed(date);
+                times.atime = duration_from_date(date);
+            }
+            if let Some(mtime) = attr.mtime {
+                file.set_modified(date_time_from_duration(mtime));
+                times.mtime = stored_mtime(mtime);
+            }
+            // 立即写回目录项
+            file.flush().map_err(as_vfs_err)
         })
     }
 
     fn read_at(self: Pin<&Self>, cx: &mut Context<'_>, offset: u64, buf: &mut [u8]) -> Poll<VfsResult<usize>> {
         self.0.poll_lock(cx).map(|mut file| {
+            // 与 fatfs 一样只记录访问日期
+            let today = date_time_from_duration(crate::now()).date;
+            self.1.lock().atime = duration_from_date(today);
             file.seek(SeekFrom::Start(offset)).map_err(as_vfs_err)?; // TODO: more efficient
             let buf_len = buf.len();
             let mut now_offset = 0;
@@ -108,6 +345,8 @@ impl VfsNodeOps for FileWrapper<'static> {
         buf: &[u8]
     ) -> Poll<VfsResult<usize>> {
         self.0.poll_lock(cx).map(|mut file| {
+            // fatfs 不能定位到文件末尾之后，先用零填充中间的部分
+            extend_with_zeros(&mut file, offset)?;
             file.seek(SeekFrom::Start(offset)).map_err(as_vfs_err)?; // TODO: more efficient
             let buf_len = buf.len();
             let mut now_offset = 0;
@@ -125,14 +364,50 @@ impl VfsNodeOps for FileWrapper<'static> {
                 now_offset += write_len;
                 probe = probe[write_len..].to_vec();
             }
+            if now_offset > 0 {
+                self.1.lock().mtime = stored_mtime(crate::now());
+            }
             Ok(now_offset)
         })
     }
 
+    fn fsync(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<VfsResult> {
+        // 写回目录项后会继续调用 `Disk::flush`，将块缓存中的脏块写回设备
+        self.0.poll_lock(cx).map(|mut file| file.flush().map_err(as_vfs_err))
+    }
+
     fn truncate(self: Pin<&Self>, cx: &mut Context<'_>, size: u64) -> Poll<VfsResult> {
         self.0.poll_lock(cx).map(|mut file| {
+            // 扩展文件时同样需要写出零
+            extend_with_zeros(&mut file, size)?;
             file.seek(SeekFrom::Start(size)).map_err(as_vfs_err)?; // TODO: more efficient
-            file.truncate().map_err(as_vfs_err)
+            file.truncate().map_err(as_vfs_err)?;
+            self.1.lock().mtime = stored_mtime(crate::now());
+            Ok(())
+        })
+    }
+
+    fn allocate(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        offset: u64,
+        len: u64,
+        keep_size: bool,
+    ) -> Poll<VfsResult> {
+        self.0.poll_lock(cx).map(|mut file| {
+            let end = offset.checked_add(len).ok_or(VfsError::InvalidInput)?;
+            let size = file.seek(SeekFrom::End(0)).map_err(as_vfs_err)?;
+            if end <= size {
+                // 文件内的簇都已经分配
+                return Ok(());
+            }
+            if keep_size {
+                // 簇链的长度由文件大小决定，无法在文件末尾之后保留簇
+                return Err(VfsError::Unsupported);
+            }
+            extend_with_zeros(&mut file, end)?;
+            self.1.lock().mtime = stored_mtime(crate::now());
+            Ok(())
         })
     }
 }
@@ -140,19 +415,26 @@ impl VfsNodeOps for FileWrapper<'static> {
 impl VfsNodeOps for DirWrapper<'static> {
     async_vfs::impl_vfs_dir_default! {}
 
+    fn node_id(&self) -> u64 {
+        Arc::as_ptr(&self.id) as usize as u64
+    }
+
     fn get_attr(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<VfsNodeAttr>> {
-        Poll::Ready(Ok(VfsNodeAttr::new(
+        let mut attr = VfsNodeAttr::new(
             VfsNodePerm::from_bits_truncate(0o755),
             VfsNodeType::Dir,
             BLOCK_SIZE as u64,
             1,
-        )))
+        );
+        self.times.fill(&mut attr);
+        Poll::Ready(Ok(attr))
     }
 
     fn parent(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<Option<VfsNodeRef>> {
-        Poll::Ready(self.0
+        let path = self.path.rsplit_once('/').map_or("", |(parent, _)| parent);
+        Poll::Ready(self.dir
             .open_dir("..")
-            .map_or(None, |dir| Some(FatFileSystem::new_dir(dir)))
+            .map_or(None, |dir| Some(self.fs.new_dir(dir, FatTimes::default(), path.into())))
         )
     }
 
@@ -160,8 +442,8 @@ impl VfsNodeOps for DirWrapper<'static> {
         debug!("lookup at fatfs: {}", path);
         let path = path.trim_matches('/');
         if path.is_empty() || path == "." {
-            let dir = self.0.clone();
-            let dir_wrapper = FatFileSystem::new_dir(dir);
+            let dir = self.dir.clone();
+            let dir_wrapper = self.fs.new_dir(dir, self.times, self.path.clone());
             return Poll::Ready(Ok(dir_wrapper));
         }
 
@@ -175,16 +457,18 @@ impl VfsNodeOps for DirWrapper<'static> {
             return VfsNodeOps::lookup(Pin::new(&dir), cx, rest);
         }
 
-        for entry in self.0.iter() {
+        for entry in self.dir.iter() {
             let Ok(entry) = entry else {
                 return Poll::Ready(Err(VfsError::Io));
             };
 
             if entry.file_name() == path {
+                let times = FatTimes::from_entry(&entry);
+                let path = join(&self.path, path);
                 if entry.is_file() {
-                    return Poll::Ready(Ok(FatFileSystem::new_file(entry.to_file())));
+                    return Poll::Ready(Ok(self.fs.new_file(entry.to_file(), times, &path)));
                 } else if entry.is_dir() {
-                    return Poll::Ready(Ok(FatFileSystem::new_dir(entry.to_dir())));
+                    return Poll::Ready(Ok(self.fs.new_dir(entry.to_dir(), times, path)));
                 }
             }
         }
@@ -202,11 +486,11 @@ impl VfsNodeOps for DirWrapper<'static> {
         }
         match ty {
             VfsNodeType::File => {
-                self.0.create_file(path).map_err(as_vfs_err)?;
+                self.dir.create_file(path).map_err(as_vfs_err)?;
                 Poll::Ready(Ok(()))
             }
             VfsNodeType::Dir => {
-                self.0.create_dir(path).map_err(as_vfs_err)?;
+                self.dir.create_dir(path).map_err(as_vfs_err)?;
                 Poll::Ready(Ok(()))
             }
             _ => Poll::Ready(Err(VfsError::Unsupported)),
@@ -220,7 +504,9 @@ impl VfsNodeOps for DirWrapper<'static> {
         if let Some(rest) = path.strip_prefix("./") {
             return self.remove(cx, rest);
         }
-        Poll::Ready(self.0.remove(path).map_err(as_vfs_err))
+        self.dir.remove(path).map_err(as_vfs_err)?;
+        self.fs.ids.remove(&join(&self.path, path));
+        Poll::Ready(Ok(()))
     }
 
     fn read_dir(
@@ -229,7 +515,7 @@ impl VfsNodeOps for DirWrapper<'static> {
         start_idx: usize, 
         dirents: &mut [VfsDirEntry]
     ) -> Poll<VfsResult<usize>> {
-        let mut iter = self.0.iter().skip(start_idx);
+        let mut iter = self.dir.iter().skip(start_idx);
         for (i, out_entry) in dirents.iter_mut().enumerate() {
             let x = iter.next();
             match x {
@@ -260,11 +546,29 @@ impl VfsNodeOps for DirWrapper<'static> {
             "rename at fatfs, src_path: {}, dst_path: {}",
             src_path, dst_path
         );
+        let src_path = src_path.trim_matches('/');
         let dst_path = dst_path.trim_matches('/');
-        Poll::Ready(self.0
-            .rename(src_path, &self.0, dst_path)
-            .map_err(as_vfs_err)
-        )
+        if src_path == dst_path {
+            return Poll::Ready(Ok(()));
+        }
+        // fatfs 的 rename 不会覆盖已有的目标，按照 rename(2) 的规则先删除它
+        let src_is_dir = self.dir.open_dir(src_path).is_ok();
+        if self.dir.open_dir(dst_path).is_ok() {
+            if !src_is_dir {
+                return Poll::Ready(Err(VfsError::IsADirectory));
+            }
+            self.dir.remove(dst_path).map_err(as_vfs_err)?;
+        } else if self.dir.open_file(dst_path).is_ok() {
+            if src_is_dir {
+                return Poll::Ready(Err(VfsError::NotADirectory));
+            }
+            self.dir.remove(dst_path).map_err(as_vfs_err)?;
+        }
+        self.dir
+            .rename(src_path, &self.dir, dst_path)
+            .map_err(as_vfs_err)?;
+        self.fs.ids.rename(&join(&self.path, src_path), &join(&self.path, dst_path));
+        Poll::Ready(Ok(()))
     }
 
 }
@@ -274,6 +578,19 @@ impl VfsOps for FatFileSystem {
         let root_dir = unsafe { (*self.root_dir.get()).as_ref().unwrap() };
         Poll::Ready(root_dir.clone())
     }
+
+    fn statfs(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<FileSystemInfo>> {
+        // 空闲簇数优先取自 FSInfo 扇区，不可信时扫描 FAT 表
+        let stats = match self.inner.stats() {
+            Ok(stats) => stats,
+            Err(err) => return Poll::Ready(Err(as_vfs_err(err))),
+        };
+        let mut info = FileSystemInfo::new(MSDOS_SUPER_MAGIC, stats.cluster_size() as u64, 255);
+        info.blocks = stats.total_clusters() as u64;
+        info.blocks_free = stats.free_clusters() as u64;
+        info.blocks_avail = info.blocks_free;
+        Poll::Ready(Ok(info))
+    }
 }
 
 impl fatfs::IoBase for Disk {
@@ -314,7 +631,7 @@ impl Write for Disk {
         Ok(write_len)
     }
     fn flush(&mut self) -> Result<(), Self::Error> {
-        Ok(())
+        Disk::flush(self).map_err(|_| ())
     }
 }
 
@@ -352,3 +669,54 @@ const fn as_vfs_err(err: fatfs::Error<()>) -> VfsError {
         _ => VfsError::Io,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_round_trip() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        for days in [0, 3652, 11016, 11017, 19782, 50000] {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+    }
+
+    #[test]
+    fn clamp_to_fat_range() {
+        // 开机计时或早于 1980 年的时间取 FAT 能表示的最早时间
+        let earliest = date_time_from_duration(Duration::from_secs(5));
+        assert_eq!((earliest.date.year, earliest.date.month, earliest.date.day), (1980, 1, 1));
+        assert_eq!(duration_from_date_time(earliest).as_secs(), FAT_EPOCH_SECS);
+        // 2024-02-29 12:34:56.789
+        let time = Duration::from_millis(1_709_210_096_789);
+        let date_time = date_time_from_duration(time);
+        assert_eq!((date_time.date.year, date_time.date.month, date_time.date.day), (2024, 2, 29));
+        assert_eq!((date_time.time.hour, date_time.time.min, date_time.time.sec), (12, 34, 56));
+        assert_eq!(duration_from_date_time(date_time), time);
+        // 修改时间写入目录项后精度为 2 秒
+        assert_eq!(stored_mtime(time).as_secs(), 1_709_210_096);
+        assert_eq!(stored_mtime(Duration::from_secs(1_709_210_097)).as_secs(), 1_709_210_096);
+    }
+
+    #[test]
+    fn node_ids_follow_renames() {
+        let ids = NodeIds::new();
+        let file = ids.get("dir/file");
+        let other = ids.get("other");
+        // 重新查找同一路径得到相同的标识
+        assert!(Arc::ptr_eq(&file, &ids.get("dir/file")));
+        // 目录重命名时其下的文件随之移动
+        ids.rename("dir", "moved");
+        assert!(Arc::ptr_eq(&file, &ids.get("moved/file")));
+        assert!(!Arc::ptr_eq(&file, &ids.get("dir/file")));
+        // 替换已有的目标后，目标路径得到被移动文件的标识
+        ids.rename("other", "moved/file");
+        assert!(Arc::ptr_eq(&other, &ids.get("moved/file")));
+        // 删除后同一路径上的新文件得到新的标识
+        ids.remove("moved");
+        assert!(!Arc::ptr_eq(&other, &ids.get("moved/file")));
+    }
+}
@@ -18,4 +18,15 @@ cfg_if::cfg_if! {
         pub mod fatfs;
         pub use fatfs::BLOCK_SIZE;
     }
-}
\ No newline at end of file
+}
+#[cfg(feature = "ramfs")]
+pub mod ramfs;
+
+#[cfg(feature = "procfs")]
+pub mod procfs;
+
+#[cfg(feature = "mqueue")]
+pub mod mqueue;
+
+#[cfg(feature = "overlayfs")]
+pub mod overlayfs;
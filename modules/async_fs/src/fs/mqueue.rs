@@ -0,0 +1,522 @@
+//! POSIX 消息队列文件系统
+//!
+//! 目录中的每个文件对应一个消息队列，消息按照优先级从高到低、同一优先级内按照发送的顺序取出。
+//! 系统中只有一个 mqueue 实例，mq_open 等系统调用与挂载在 /dev/mqueue 上的目录访问同一组队列。
+//! 与 Linux 相同，读取队列文件得到队列的状态，队列文件不能被写入
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use async_vfs::{FileSystemInfo, VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef};
+use async_vfs::{impl_vfs_dir_default, impl_vfs_non_dir_default, VfsError, VfsNodeType, VfsOps};
+use async_vfs::{VfsResult, VfsSetAttr};
+use axerrno::{AxError, AxResult};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use spinlock::SpinNoIrq;
+use sync::WaitQueue;
+
+use super::ramfs::meta::{impl_meta_xattr, NodeMeta};
+
+/// mqueue 的 magic number
+pub const MQUEUE_MAGIC: u64 = 0x1980_0202;
+
+/// 创建队列时没有指定属性时的最大消息数，与 Linux 的 /proc/sys/fs/mqueue/msg_default 相同
+pub const DEFAULT_MAXMSG: usize = 10;
+
+/// 创建队列时没有指定属性时的最大消息长度，与 Linux 的 /proc/sys/fs/mqueue/msgsize_default 相同
+pub const DEFAULT_MSGSIZE: usize = 8192;
+
+/// mq_notify 注册的通知方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MqNotify {
+    /// 注册通知的进程
+    pub pid: u64,
+    /// 通知时发送的信号，为 None 时 (SIGEV_NONE) 只移除注册
+    pub signo: Option<u32>,
+    /// 随信号传递的 sigev_value
+    pub value: usize,
+}
+
+struct QueueState {
+    /// 以优先级为键的消息
+    messages: BTreeMap<u32, VecDeque<Vec<u8>>>,
+    count: usize,
+    /// 所有消息的总长度
+    bytes: usize,
+    notify: Option<MqNotify>,
+    /// 阻塞在接收中的任务数，有任务等待时新消息直接交给它们而不发出通知
+    receivers: usize,
+}
+
+/// 一个 POSIX 消息队列
+pub struct MessageQueue {
+    maxmsg: usize,
+    msgsize: usize,
+    state: SpinNoIrq<QueueState>,
+    /// 等待消息的接收者
+    recv_waiters: WaitQueue,
+    /// 等待队列腾出空间的发送者
+    send_waiters: WaitQueue,
+}
+
+impl MessageQueue {
+    /// 创建一个最多容纳 `maxmsg` 条、每条最长 `msgsize` 字节的空队列
+    pub fn new(maxmsg: usize, msgsize: usize) -> Self {
+        Self {
+            maxmsg,
+            msgsize,
+            state: SpinNoIrq::new(QueueState {
+                messages: BTreeMap::new(),
+                count: 0,
+                bytes: 0,
+                notify: None,
+                receivers: 0,
+            }),
+            recv_waiters: WaitQueue::new(),
+            send_waiters: WaitQueue::new(),
+        }
+    }
+
+    /// 队列最多容纳的消息数
+    pub fn maxmsg(&self) -> usize {
+        self.maxmsg
+    }
+
+    /// 消息的最大长度
+    pub fn msgsize(&self) -> usize {
+        self.msgsize
+    }
+
+    /// 队列中的消息数
+    pub fn len(&self) -> usize {
+        self.state.lock().count
+    }
+
+    /// 队列是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 队列是否已满
+    pub fn is_full(&self) -> bool {
+        self.len() >= self.maxmsg
+    }
+
+    /// 以优先级 `prio` 放入一条消息，调用者需要保证消息不超过 [`Self::msgsize`]
+    ///
+    /// 成功时返回需要投递的通知，通知在发出后即被移除。
+    /// 队列已满时返回 [`AxError::WouldBlock`]，若给出了 `cx` 则在返回前注册其 waker
+    pub fn send(&self, data: &[u8], prio: u32, cx: Option<&mut Context<'_>>) -> AxResult<Option<MqNotify>> {
+        let mut state = self.state.lock();
+        if state.count >= self.maxmsg {
+            if let Some(cx) = cx {
+                let _ = self.send_waiters.wait_until(cx, || false);
+            }
+            return Err(AxError::WouldBlock);
+        }
+        // 只有空队列收到消息且没有接收者等待时才发出通知
+        let notify = if state.count == 0 && state.receivers == 0 {
+            state.notify.take()
+        } else {
+            None
+        };
+        state.messages.entry(prio).or_default().push_back(data.into());
+        state.count += 1;
+        state.bytes += data.len();
+        drop(state);
+        self.recv_waiters.notify_all();
+        Ok(notify)
+    }
+
+    /// 取出优先级最高的消息，返回消息的长度与优先级，调用者需要保证 `buf` 不小于 [`Self::msgsize`]
+    ///
+    /// 队列为空时返回 [`AxError::WouldBlock`]，若给出了 `cx` 则在返回前注册其 waker
+    pub fn receive(&self, buf: &mut [u8], cx: Option<&mut Context<'_>>) -> AxResult<(usize, u32)> {
+        let mut state = self.state.lock();
+        let Some(mut entry) = state.messages.last_entry() else {
+            if let Some(cx) = cx {
+                let _ = self.recv_waiters.wait_until(cx, || false);
+            }
+            return Err(AxError::WouldBlock);
+        };
+        let prio = *entry.key();
+        let message = entry.get_mut().pop_front().unwrap();
+        if entry.get().is_empty() {
+            entry.remove();
+        }
+        state.count -= 1;
+        state.bytes -= message.len();
+        drop(state);
+        buf[..message.len()].copy_from_slice(&message);
+        self.send_waiters.notify_all();
+        Ok((message.len(), prio))
+    }
+
+    /// 标记一个阻塞接收的任务，返回值被释放时取消标记
+    ///
+    /// 与 Linux 相同，有任务阻塞在接收中时新消息不会触发 mq_notify 的通知
+    pub fn waiting_receiver(&self) -> WaitingReceiver<'_> {
+        self.state.lock().receivers += 1;
+        WaitingReceiver(self)
+    }
+
+    /// 注册通知，已有注册时返回 [`AxError::ResourceBusy`]，即使注册者是同一个进程
+    pub fn set_notify(&self, notify: MqNotify) -> AxResult {
+        let mut state = self.state.lock();
+        if state.notify.is_some() {
+            return Err(AxError::ResourceBusy);
+        }
+        state.notify = Some(notify);
+        Ok(())
+    }
+
+    /// 移除进程 `pid` 注册的通知，由其他进程注册的通知保持不变
+    pub fn clear_notify(&self, pid: u64) {
+        let mut state = self.state.lock();
+        if state.notify.is_some_and(|notify| notify.pid == pid) {
+            state.notify = None;
+        }
+    }
+
+    /// 注册 `cx` 的 waker，队列收到消息或者腾出空间时被唤醒
+    ///
+    /// 用于 poll：队列有消息时可读，未满时可写
+    pub fn register_waker(&self, cx: &mut Context<'_>) {
+        let _ = self.recv_waiters.wait_until(cx, || false);
+        let _ = self.send_waiters.wait_until(cx, || false);
+    }
+
+    /// 移除 [`Self::register_waker`] 注册的 waker
+    pub fn unregister_waker(&self, waker: &core::task::Waker) {
+        self.recv_waiters.remove_task(waker);
+        self.send_waiters.remove_task(waker);
+    }
+
+    /// 读取队列文件得到的状态，与 Linux 的格式相同
+    pub fn status(&self) -> String {
+        let state = self.state.lock();
+        // sigev_notify：SIGEV_SIGNAL 为 0，SIGEV_NONE 为 1
+        let (notify, signo, pid) = match state.notify {
+            Some(MqNotify { pid, signo: Some(signo), .. }) => (0, signo, pid),
+            Some(MqNotify { pid, signo: None, .. }) => (1, 0, pid),
+            None => (0, 0, 0),
+        };
+        format!(
+            "QSIZE:{:<10} NOTIFY:{:<5} SIGNO:{:<5} NOTIFY_PID:{:<6}\n",
+            state.bytes, notify, signo, pid
+        )
+    }
+}
+
+/// 见 [`MessageQueue::waiting_receiver`]
+pub struct WaitingReceiver<'a>(&'a MessageQueue);
+
+impl Drop for WaitingReceiver<'_> {
+    fn drop(&mut self) {
+        self.0.state.lock().receivers -= 1;
+    }
+}
+
+/// 队列文件
+pub struct MqueueNode {
+    queue: Arc<MessageQueue>,
+    meta: SpinNoIrq<NodeMeta>,
+}
+
+impl MqueueNode {
+    fn new(queue: MessageQueue) -> Self {
+        Self {
+            queue: Arc::new(queue),
+            meta: SpinNoIrq::new(NodeMeta::new(VfsNodePerm::default_file())),
+        }
+    }
+
+    /// 节点对应的队列
+    pub fn queue(&self) -> Arc<MessageQueue> {
+        self.queue.clone()
+    }
+
+    fn attr(&self) -> VfsNodeAttr {
+        let size = self.queue.status().len() as u64;
+        let mut attr = VfsNodeAttr::new(VfsNodePerm::default_file(), VfsNodeType::File, size, 0);
+        self.meta.lock().fill(&mut attr);
+        attr
+    }
+}
+
+impl VfsNodeOps for MqueueNode {
+    impl_vfs_non_dir_default! {}
+    impl_meta_xattr! {}
+
+    fn get_attr(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<VfsNodeAttr>> {
+        Poll::Ready(Ok(self.attr()))
+    }
+
+    fn setattr(self: Pin<&Self>, _cx: &mut Context<'_>, attr: &VfsSetAttr) -> Poll<VfsResult> {
+        self.meta.lock().setattr(attr);
+        Poll::Ready(Ok(()))
+    }
+
+    fn read_at(
+        self: Pin<&Self>,
+        _cx: &mut Context<'_>,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Poll<VfsResult<usize>> {
+        let status = self.queue.status();
+        let status = status.as_bytes();
+        let start = status.len().min(offset as usize);
+        let end = status.len().min(start + buf.len());
+        buf[..end - start].copy_from_slice(&status[start..end]);
+        self.meta.lock().accessed();
+        Poll::Ready(Ok(end - start))
+    }
+
+    fn write_at(
+        self: Pin<&Self>,
+        _cx: &mut Context<'_>,
+        _offset: u64,
+        _buf: &[u8],
+    ) -> Poll<VfsResult<usize>> {
+        Poll::Ready(Err(VfsError::InvalidInput))
+    }
+
+    fn fsync(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// mqueue 的根目录，其中只有队列文件
+pub struct MqueueDir {
+    this: Weak<MqueueDir>,
+    parent: SpinNoIrq<Weak<dyn VfsNodeOps + Unpin>>,
+    queues: SpinNoIrq<BTreeMap<String, Arc<MqueueNode>>>,
+    meta: SpinNoIrq<NodeMeta>,
+}
+
+impl MqueueDir {
+    fn new() -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            parent: SpinNoIrq::new(Weak::<Self>::new()),
+            queues: SpinNoIrq::new(BTreeMap::new()),
+            // 与 Linux 相同，任何人都可以创建队列，只有所有者可以删除
+            meta: SpinNoIrq::new(NodeMeta::new(VfsNodePerm::from_bits_truncate(0o1777))),
+        })
+    }
+
+    /// 查找名为 `name` 的队列
+    pub fn get(&self, name: &str) -> VfsResult<(Arc<MessageQueue>, VfsNodeAttr)> {
+        let queues = self.queues.lock();
+        let node = queues.get(name).ok_or(VfsError::NotFound)?;
+        Ok((node.queue(), node.attr()))
+    }
+
+    /// 创建名为 `name` 的队列，权限与所有者由 `attr` 给出
+    pub fn create_queue(&self, name: &str, queue: MessageQueue, attr: &VfsSetAttr) -> VfsResult<Arc<MessageQueue>> {
+        let mut queues = self.queues.lock();
+        if queues.contains_key(name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        let node = Arc::new(MqueueNode::new(queue));
+        node.meta.lock().setattr(attr);
+        let queue = node.queue();
+        queues.insert(name.into(), node);
+        self.meta.lock().modified();
+        Ok(queue)
+    }
+
+    /// 删除名为 `name` 的队列，已经打开的队列在关闭前仍然可用
+    pub fn remove_queue(&self, name: &str) -> VfsResult {
+        self.queues.lock().remove(name).ok_or(VfsError::NotFound)?;
+        self.meta.lock().modified();
+        Ok(())
+    }
+}
+
+/// 去掉路径开头的 "/" 与 "./"，队列文件只能直接位于根目录中
+fn queue_name(path: &str) -> VfsResult<&str> {
+    let mut path = path.trim_start_matches('/');
+    while let Some(rest) = path.strip_prefix("./") {
+        path = rest.trim_start_matches('/');
+    }
+    if path.contains('/') {
+        return Err(VfsError::NotFound);
+    }
+    Ok(path)
+}
+
+impl VfsNodeOps for MqueueDir {
+    impl_vfs_dir_default! {}
+    impl_meta_xattr! {}
+
+    fn get_attr(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<VfsNodeAttr>> {
+        let mut attr = VfsNodeAttr::new_dir(4096, 0);
+        attr.set_nlink(2);
+        self.meta.lock().fill(&mut attr);
+        Poll::Ready(Ok(attr))
+    }
+
+    fn setattr(self: Pin<&Self>, _cx: &mut Context<'_>, attr: &VfsSetAttr) -> Poll<VfsResult> {
+        self.meta.lock().setattr(attr);
+        Poll::Ready(Ok(()))
+    }
+
+    fn parent(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<Option<VfsNodeRef>> {
+        Poll::Ready(self.parent.lock().upgrade())
+    }
+
+    fn lookup(self: Pin<&Self>, _cx: &mut Context<'_>, path: &str) -> Poll<VfsResult<VfsNodeRef>> {
+        let name = match path.trim_start_matches('/') {
+            ".." => return Poll::Ready(self.parent.lock().upgrade().ok_or(VfsError::NotFound)),
+            _ => queue_name(path)?,
+        };
+        let node: Option<VfsNodeRef> = match name {
+            "" | "." => self.this.upgrade().map(|this| this as VfsNodeRef),
+            _ => self.queues.lock().get(name).map(|node| node.clone() as VfsNodeRef),
+        };
+        Poll::Ready(node.ok_or(VfsError::NotFound))
+    }
+
+    fn read_dir(
+        self: Pin<&Self>,
+        _cx: &mut Context<'_>,
+        start_idx: usize,
+        dirents: &mut [VfsDirEntry],
+    ) -> Poll<VfsResult<usize>> {
+        let queues = self.queues.lock();
+        let mut names = queues.keys().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => match names.next() {
+                    Some(name) => *ent = VfsDirEntry::new(name, VfsNodeType::File),
+                    None => return Poll::Ready(Ok(i)),
+                },
+            }
+        }
+        Poll::Ready(Ok(dirents.len()))
+    }
+
+    /// 在目录中创建普通文件即创建一个使用默认属性的队列，不能创建其他类型的文件
+    fn create(self: Pin<&Self>, _cx: &mut Context<'_>, path: &str, ty: VfsNodeType) -> Poll<VfsResult> {
+        let name = queue_name(path)?;
+        if name.is_empty() || name == "." || name == ".." {
+            return Poll::Ready(Err(VfsError::AlreadyExists));
+        }
+        if ty != VfsNodeType::File {
+            return Poll::Ready(Err(VfsError::PermissionDenied));
+        }
+        let queue = MessageQueue::new(DEFAULT_MAXMSG, DEFAULT_MSGSIZE);
+        Poll::Ready(self.create_queue(name, queue, &VfsSetAttr::default()).map(|_| ()))
+    }
+
+    fn remove(self: Pin<&Self>, _cx: &mut Context<'_>, path: &str) -> Poll<VfsResult> {
+        let name = queue_name(path)?;
+        if name.is_empty() || name == "." || name == ".." {
+            return Poll::Ready(Err(VfsError::InvalidInput));
+        }
+        Poll::Ready(self.remove_queue(name))
+    }
+}
+
+/// mqueue 文件系统
+pub struct MqueueFileSystem {
+    root: Arc<MqueueDir>,
+}
+
+/// 系统中唯一的 mqueue 实例
+static MQUEUE: SpinNoIrq<Option<Arc<MqueueFileSystem>>> = SpinNoIrq::new(None);
+
+impl MqueueFileSystem {
+    /// 获取系统中唯一的 mqueue 实例，第一次调用时创建
+    pub fn instance() -> Arc<Self> {
+        MQUEUE
+            .lock()
+            .get_or_insert_with(|| Arc::new(Self { root: MqueueDir::new() }))
+            .clone()
+    }
+
+    /// Returns the root directory node in [`Arc<MqueueDir>`](MqueueDir).
+    pub fn root_dir_node(&self) -> Arc<MqueueDir> {
+        self.root.clone()
+    }
+}
+
+impl VfsOps for MqueueFileSystem {
+    fn mount(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        _path: &str,
+        mount_point: VfsNodeRef,
+    ) -> Poll<VfsResult> {
+        let parent = futures_core::ready!(VfsNodeOps::parent(Pin::new(&mount_point), cx));
+        *self.root.parent.lock() = parent.as_ref().map_or(Weak::<MqueueDir>::new() as _, Arc::downgrade);
+        Poll::Ready(Ok(()))
+    }
+
+    fn root_dir(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsNodeRef> {
+        Poll::Ready(self.root.clone())
+    }
+
+    fn statfs(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<FileSystemInfo>> {
+        Poll::Ready(Ok(FileSystemInfo::new(MQUEUE_MAGIC, 4096, 255)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MessageQueue, MqNotify};
+    use axerrno::AxError;
+
+    #[test]
+    fn test_priority_order() {
+        let queue = MessageQueue::new(4, 8);
+        for (data, prio) in [(b"a", 1), (b"b", 5), (b"c", 1), (b"d", 5)] {
+            queue.send(data, prio, None).unwrap();
+        }
+        assert_eq!(Err(AxError::WouldBlock), queue.send(b"e", 9, None));
+        let mut buf = [0u8; 8];
+        let mut received = alloc::vec::Vec::new();
+        while let Ok((len, prio)) = queue.receive(&mut buf, None) {
+            assert_eq!(1, len);
+            received.push((buf[0], prio));
+        }
+        assert_eq!(
+            alloc::vec![(b'b', 5), (b'd', 5), (b'a', 1), (b'c', 1)],
+            received
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_notify() {
+        let queue = MessageQueue::new(4, 8);
+        let notify = MqNotify {
+            pid: 1,
+            signo: Some(10),
+            value: 0,
+        };
+        queue.set_notify(notify).unwrap();
+        assert_eq!(
+            Err(AxError::ResourceBusy),
+            queue.set_notify(MqNotify { pid: 2, ..notify })
+        );
+        assert_eq!(Err(AxError::ResourceBusy), queue.set_notify(notify));
+        // 有接收者等待时不通知
+        {
+            let _receiver = queue.waiting_receiver();
+            assert_eq!(Ok(None), queue.send(b"x", 0, None));
+        }
+        queue.receive(&mut [0; 8], None).unwrap();
+        assert_eq!(Ok(Some(notify)), queue.send(b"x", 0, None));
+        // 通知发出后注册即被移除
+        queue.receive(&mut [0; 8], None).unwrap();
+        assert_eq!(Ok(None), queue.send(b"x", 0, None));
+        assert!(queue.status().starts_with("QSIZE:1 "));
+    }
+}
@@ -0,0 +1,716 @@
+//! 叠加文件系统：将可写的上层（ramfs）叠加在只读的下层（FAT/ext4 镜像）之上。
+//!
+//! 读取时优先使用上层的文件，上层没有时使用下层；修改下层的文件之前先将其复制到上层（copy-up），
+//! 因此下层永远不会被写入。删除下层的文件时记录一个 whiteout 将其隐藏，
+//! whiteout 同时隐藏该路径下的所有下层文件，之后在该路径重新创建的目录是不透明的，
+//! 不再显示下层的内容。目录的内容由两层合并而成。
+//!
+//! 作为根文件系统挂载时，所有修改都只保存在内存中，每次启动都从原始的镜像开始。
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+use async_vfs::{FileSystemInfo, VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType};
+use async_vfs::{VfsOps, VfsResult, VfsSetAttr, VfsXattrFlags};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use futures_core::ready;
+use spinlock::SpinNoIrq;
+
+/// overlayfs 的 magic number
+const OVERLAYFS_SUPER_MAGIC: u64 = 0x794c_7630;
+/// copy-up 与合并目录时使用的缓冲区大小
+const COPY_BUF_SIZE: usize = 0x10000;
+/// 每次从下层读取的目录项数
+const DIR_BATCH: usize = 32;
+
+/// 两层共享的状态
+struct Layers {
+    upper: VfsNodeRef,
+    lower: VfsNodeRef,
+    /// 被删除的下层路径
+    whiteouts: SpinNoIrq<BTreeSet<String>>,
+    /// 以路径为键的活动节点，同一路径总是得到同一个节点，copy-up 对所有打开者可见
+    nodes: SpinNoIrq<BTreeMap<String, Weak<OverlayNode>>>,
+    /// 挂载点的父目录
+    parent: SpinNoIrq<Option<VfsNodeRef>>,
+}
+
+/// A filesystem that stacks a writable upper layer over a read-only lower
+/// layer, implementing [`async_vfs::VfsOps`].
+pub struct OverlayFileSystem {
+    /// 保存下层的文件系统，使其在叠加文件系统存在期间不被释放
+    _lower_fs: Arc<dyn VfsOps + Unpin>,
+    /// 上层的文件系统，statfs 报告它的空间使用情况
+    upper_fs: Arc<dyn VfsOps + Unpin>,
+    root: Arc<OverlayNode>,
+}
+
+impl OverlayFileSystem {
+    /// Create an overlay of the root directories of `upper_fs` and
+    /// `lower_fs`. Only `upper_fs` is ever modified.
+    pub async fn new(
+        upper_fs: Arc<dyn VfsOps + Unpin>,
+        lower_fs: Arc<dyn VfsOps + Unpin>,
+    ) -> Self {
+        use async_vfs::AsyncVfsOps;
+        let layers = Arc::new(Layers {
+            upper: upper_fs.root_dir().await,
+            lower: lower_fs.root_dir().await,
+            whiteouts: SpinNoIrq::new(BTreeSet::new()),
+            nodes: SpinNoIrq::new(BTreeMap::new()),
+            parent: SpinNoIrq::new(None),
+        });
+        let root = OverlayNode::new(
+            &layers,
+            String::new(),
+            Some(layers.upper.clone()),
+            Some(layers.lower.clone()),
+        );
+        layers.nodes.lock().insert(String::new(), Arc::downgrade(&root));
+        Self {
+            _lower_fs: lower_fs,
+            upper_fs,
+            root,
+        }
+    }
+}
+
+impl VfsOps for OverlayFileSystem {
+    fn mount(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        _path: &str,
+        mount_point: VfsNodeRef,
+    ) -> Poll<VfsResult> {
+        let parent = ready!(VfsNodeOps::parent(Pin::new(&mount_point), cx));
+        *self.root.layers.parent.lock() = parent;
+        Poll::Ready(Ok(()))
+    }
+
+    fn root_dir(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsNodeRef> {
+        Poll::Ready(self.root.clone())
+    }
+
+    fn statfs(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<VfsResult<FileSystemInfo>> {
+        // 与 Linux 一致，报告上层的空间使用情况
+        let mut info = ready!(VfsOps::statfs(Pin::new(&self.upper_fs), cx))?;
+        info.fs_type = OVERLAYFS_SUPER_MAGIC;
+        Poll::Ready(Ok(info))
+    }
+}
+
+impl Layers {
+    fn is_hidden(&self, path: &str) -> bool {
+        is_whited_out(&self.whiteouts.lock(), path)
+    }
+
+    /// 删除或重命名后，`path` 及其下的路径不再对应原来的节点
+    fn forget(&self, path: &str) {
+        let prefix = String::from(path) + "/";
+        self.nodes
+            .lock()
+            .retain(|key, _| key != path && !key.starts_with(&prefix));
+    }
+
+    /// 查找相对于根目录的规范化路径 `path` 对应的节点
+    fn poll_resolve(
+        self: &Arc<Self>,
+        cx: &mut Context<'_>,
+        path: &str,
+    ) -> Poll<VfsResult<Arc<OverlayNode>>> {
+        let cached = self.nodes.lock().get(path).and_then(Weak::upgrade);
+        if let Some(node) = cached {
+            return Poll::Ready(Ok(node));
+        }
+        let upper = ready!(poll_layer_lookup(&self.upper, cx, path))?;
+        let mut lower = match self.is_hidden(path) {
+            true => None,
+            false => ready!(poll_layer_lookup(&self.lower, cx, path))?,
+        };
+        if let (Some(upper), Some(_)) = (&upper, &lower) {
+            // 上层的非目录文件完全覆盖下层，只有两层都是目录时才合并
+            let upper_dir = ready!(VfsNodeOps::get_attr(Pin::new(upper), cx))?.is_dir();
+            let lower_dir = ready!(VfsNodeOps::get_attr(Pin::new(lower.as_ref().unwrap()), cx))?
+                .is_dir();
+            if !(upper_dir && lower_dir) {
+                lower = None;
+            }
+        }
+        if upper.is_none() && lower.is_none() {
+            return Poll::Ready(Err(VfsError::NotFound));
+        }
+        let node = OverlayNode::new(self, path.into(), upper, lower);
+        let mut nodes = self.nodes.lock();
+        // 查找期间其他任务可能已经创建了该路径的节点
+        if let Some(existing) = nodes.get(path).and_then(Weak::upgrade) {
+            // 节点的 Drop 会访问 nodes，必须在释放锁之后再丢弃
+            drop(nodes);
+            drop(node);
+            return Poll::Ready(Ok(existing));
+        }
+        nodes.insert(path.into(), Arc::downgrade(&node));
+        Poll::Ready(Ok(node))
+    }
+}
+
+/// The node (file/directory/symlink) in the overlay filesystem.
+///
+/// It implements [`async_vfs::VfsNodeOps`].
+pub struct OverlayNode {
+    this: Weak<OverlayNode>,
+    layers: Arc<Layers>,
+    /// 相对于根目录的规范化路径
+    path: String,
+    upper: SpinNoIrq<Option<VfsNodeRef>>,
+    lower: Option<VfsNodeRef>,
+}
+
+impl OverlayNode {
+    fn new(
+        layers: &Arc<Layers>,
+        path: String,
+        upper: Option<VfsNodeRef>,
+        lower: Option<VfsNodeRef>,
+    ) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            layers: layers.clone(),
+            path,
+            upper: SpinNoIrq::new(upper),
+            lower,
+        })
+    }
+
+    fn upper(&self) -> Option<VfsNodeRef> {
+        self.upper.lock().clone()
+    }
+
+    /// 当前可见的一层，上层优先
+    fn current(&self) -> VfsNodeRef {
+        self.upper()
+            .or_else(|| self.lower.clone())
+            .expect("overlay node without layers")
+    }
+
+    /// 将节点复制到上层，`with_data` 为假时只创建空文件，用于随后会被截断为 0 的文件
+    fn poll_copy_up(&self, cx: &mut Context<'_>, with_data: bool) -> Poll<VfsResult<VfsNodeRef>> {
+        if let Some(upper) = self.upper() {
+            return Poll::Ready(Ok(upper));
+        }
+        let lower = self.lower.clone().expect("overlay node without layers");
+        let (parent, _) = split_parent(&self.path);
+        let parent = ready!(self.layers.poll_resolve(cx, parent))?;
+        ready!(parent.poll_copy_up(cx, true))?;
+
+        let attr = ready!(VfsNodeOps::get_attr(Pin::new(&lower), cx))?;
+        let upper_root = &self.layers.upper;
+        match attr.file_type() {
+            ty @ VfsNodeType::Dir => ready!(poll_create_upper(upper_root, cx, &self.path, ty))?,
+            ty @ VfsNodeType::File => {
+                ready!(poll_create_upper(upper_root, cx, &self.path, ty))?;
+                if with_data {
+                    let file = ready!(poll_layer_lookup(upper_root, cx, &self.path))?
+                        .ok_or(VfsError::NotFound)?;
+                    ready!(poll_copy_data(&lower, &file, cx, attr.size()))?;
+                }
+            }
+            VfsNodeType::SymLink => {
+                let mut buf = vec![0; 4096];
+                let len = ready!(VfsNodeOps::readlink(Pin::new(&lower), cx, &mut buf))?;
+                let target =
+                    core::str::from_utf8(&buf[..len]).map_err(|_| VfsError::InvalidData)?;
+                match ready!(VfsNodeOps::symlink(Pin::new(upper_root), cx, &self.path, target)) {
+                    // 上一次 copy-up 在等待中被打断时链接可能已经创建
+                    Ok(()) | Err(VfsError::AlreadyExists) => {}
+                    Err(err) => return Poll::Ready(Err(err)),
+                }
+            }
+            _ => return Poll::Ready(Err(VfsError::Unsupported)),
+        }
+        let upper = ready!(poll_layer_lookup(upper_root, cx, &self.path))?
+            .ok_or(VfsError::NotFound)?;
+        let meta = VfsSetAttr {
+            mode: Some(attr.perm()),
+            uid: Some(attr.uid()),
+            gid: Some(attr.gid()),
+            atime: Some(attr.atime()),
+            mtime: Some(attr.mtime()),
+        };
+        ready!(VfsNodeOps::setattr(Pin::new(&upper), cx, &meta))?;
+        ready!(poll_copy_xattrs(&lower, &upper, cx))?;
+        debug!("overlay: copied up {}", self.path);
+        *self.upper.lock() = Some(upper.clone());
+        Poll::Ready(Ok(upper))
+    }
+
+    /// 合并两层的目录项，上层同名的项覆盖下层，被 whiteout 的下层项不可见
+    fn poll_entries(&self, cx: &mut Context<'_>) -> Poll<VfsResult<Vec<(String, VfsNodeType)>>> {
+        let mut entries = vec![
+            (String::from("."), VfsNodeType::Dir),
+            (String::from(".."), VfsNodeType::Dir),
+        ];
+        let upper = match self.upper() {
+            Some(upper) => ready!(poll_list(&upper, cx))?,
+            None => Vec::new(),
+        };
+        let lower = match &self.lower {
+            Some(lower) => ready!(poll_list(lower, cx))?,
+            None => Vec::new(),
+        };
+        let names: BTreeSet<&str> = upper.iter().map(|(name, _)| name.as_str()).collect();
+        let whiteouts = self.layers.whiteouts.lock();
+        let lower: Vec<_> = lower
+            .into_iter()
+            .filter(|(name, _)| {
+                !names.contains(name.as_str())
+                    && !is_whited_out(&whiteouts, &join(&self.path, name))
+            })
+            .collect();
+        drop(whiteouts);
+        entries.extend(upper);
+        entries.extend(lower);
+        Poll::Ready(Ok(entries))
+    }
+}
+
+impl Drop for OverlayNode {
+    fn drop(&mut self) {
+        let mut nodes = self.layers.nodes.lock();
+        if nodes
+            .get(&self.path)
+            .is_some_and(|node| Weak::ptr_eq(node, &self.this))
+        {
+            nodes.remove(&self.path);
+        }
+    }
+}
+
+impl VfsNodeOps for OverlayNode {
+    fn open(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<VfsResult> {
+        VfsNodeOps::open(Pin::new(&self.current()), cx)
+    }
+
+    fn get_attr(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<VfsResult<VfsNodeAttr>> {
+        VfsNodeOps::get_attr(Pin::new(&self.current()), cx)
+    }
+
+    fn setattr(self: Pin<&Self>, cx: &mut Context<'_>, attr: &VfsSetAttr) -> Poll<VfsResult> {
+        let upper = ready!(self.poll_copy_up(cx, true))?;
+        VfsNodeOps::setattr(Pin::new(&upper), cx, attr)
+    }
+
+    fn read_at(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Poll<VfsResult<usize>> {
+        VfsNodeOps::read_at(Pin::new(&self.current()), cx, offset, buf)
+    }
+
+    fn write_at(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        offset: u64,
+        buf: &[u8],
+    ) -> Poll<VfsResult<usize>> {
+        let upper = ready!(self.poll_copy_up(cx, true))?;
+        VfsNodeOps::write_at(Pin::new(&upper), cx, offset, buf)
+    }
+
+    fn fsync(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<VfsResult> {
+        match self.upper() {
+            Some(upper) => VfsNodeOps::fsync(Pin::new(&upper), cx),
+            // 下层不会被修改，没有需要同步的内容
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn truncate(self: Pin<&Self>, cx: &mut Context<'_>, size: u64) -> Poll<VfsResult> {
+        let upper = ready!(self.poll_copy_up(cx, size != 0))?;
+        VfsNodeOps::truncate(Pin::new(&upper), cx, size)
+    }
+
+    fn readlink(self: Pin<&Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<VfsResult<usize>> {
+        VfsNodeOps::readlink(Pin::new(&self.current()), cx, buf)
+    }
+
+    fn allocate(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        offset: u64,
+        len: u64,
+        keep_size: bool,
+    ) -> Poll<VfsResult> {
+        let upper = ready!(self.poll_copy_up(cx, true))?;
+        VfsNodeOps::allocate(Pin::new(&upper), cx, offset, len, keep_size)
+    }
+
+    fn punch_hole(self: Pin<&Self>, cx: &mut Context<'_>, offset: u64, len: u64) -> Poll<VfsResult> {
+        let upper = ready!(self.poll_copy_up(cx, true))?;
+        VfsNodeOps::punch_hole(Pin::new(&upper), cx, offset, len)
+    }
+
+    fn seek_data(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        offset: u64,
+        hole: bool,
+    ) -> Poll<VfsResult<Option<u64>>> {
+        VfsNodeOps::seek_data(Pin::new(&self.current()), cx, offset, hole)
+    }
+
+    fn getxattr(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        name: &str,
+        buf: &mut [u8],
+    ) -> Poll<VfsResult<usize>> {
+        VfsNodeOps::getxattr(Pin::new(&self.current()), cx, name, buf)
+    }
+
+    fn setxattr(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        name: &str,
+        value: &[u8],
+        flags: VfsXattrFlags,
+    ) -> Poll<VfsResult> {
+        let upper = ready!(self.poll_copy_up(cx, true))?;
+        VfsNodeOps::setxattr(Pin::new(&upper), cx, name, value, flags)
+    }
+
+    fn listxattr(self: Pin<&Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<VfsResult<usize>> {
+        VfsNodeOps::listxattr(Pin::new(&self.current()), cx, buf)
+    }
+
+    fn removexattr(self: Pin<&Self>, cx: &mut Context<'_>, name: &str) -> Poll<VfsResult> {
+        let upper = ready!(self.poll_copy_up(cx, true))?;
+        VfsNodeOps::removexattr(Pin::new(&upper), cx, name)
+    }
+
+    fn parent(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<Option<VfsNodeRef>> {
+        if self.path.is_empty() {
+            return Poll::Ready(self.layers.parent.lock().clone());
+        }
+        let (parent, _) = split_parent(&self.path);
+        let parent = ready!(self.layers.poll_resolve(cx, parent));
+        Poll::Ready(parent.ok().map(|node| node as VfsNodeRef))
+    }
+
+    fn lookup(self: Pin<&Self>, cx: &mut Context<'_>, path: &str) -> Poll<VfsResult<VfsNodeRef>> {
+        let path = join(&self.path, path);
+        let node = ready!(self.layers.poll_resolve(cx, &path))?;
+        Poll::Ready(Ok(node))
+    }
+
+    fn create(self: Pin<&Self>, cx: &mut Context<'_>, path: &str, ty: VfsNodeType) -> Poll<VfsResult> {
+        let path = join(&self.path, path);
+        debug!("create {:?} at overlayfs: {}", ty, path);
+        match ready!(self.layers.poll_resolve(cx, &path)) {
+            Ok(_) => return Poll::Ready(Ok(())), // already exists
+            Err(VfsError::NotFound) => {}
+            Err(err) => return Poll::Ready(Err(err)),
+        }
+        let (parent, _) = split_parent(&path);
+        let parent = ready!(self.layers.poll_resolve(cx, parent))?;
+        ready!(parent.poll_copy_up(cx, true))?;
+        VfsNodeOps::create(Pin::new(&self.layers.upper), cx, &path, ty)
+    }
+
+    fn remove(self: Pin<&Self>, cx: &mut Context<'_>, path: &str) -> Poll<VfsResult> {
+        let path = join(&self.path, path);
+        debug!("remove at overlayfs: {}", path);
+        if path.is_empty() {
+            return Poll::Ready(Err(VfsError::InvalidInput));
+        }
+        let node = ready!(self.layers.poll_resolve(cx, &path))?;
+        let attr = ready!(VfsNodeOps::get_attr(Pin::new(&node.current()), cx))?;
+        // 上层的目录可能是空的，但合并后仍有下层的内容
+        if attr.is_dir() && ready!(node.poll_entries(cx))?.len() > 2 {
+            return Poll::Ready(Err(VfsError::DirectoryNotEmpty));
+        }
+        if node.upper().is_some() {
+            ready!(VfsNodeOps::remove(Pin::new(&self.layers.upper), cx, &path))?;
+        }
+        if node.lower.is_some() {
+            self.layers.whiteouts.lock().insert(path.clone());
+        }
+        drop(node);
+        self.layers.forget(&path);
+        Poll::Ready(Ok(()))
+    }
+
+    fn read_dir(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        start_idx: usize,
+        dirents: &mut [VfsDirEntry],
+    ) -> Poll<VfsResult<usize>> {
+        let entries = ready!(self.poll_entries(cx))?;
+        let mut count = 0;
+        for (out, (name, ty)) in dirents.iter_mut().zip(entries.iter().skip(start_idx)) {
+            *out = VfsDirEntry::new(name, *ty);
+            count += 1;
+        }
+        Poll::Ready(Ok(count))
+    }
+
+    fn rename(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        src_path: &str,
+        dst_path: &str,
+    ) -> Poll<VfsResult> {
+        let src = join(&self.path, src_path);
+        let dst = join(&self.path, dst_path);
+        debug!("rename at overlayfs, src_path: {}, dst_path: {}", src, dst);
+        let node = ready!(self.layers.poll_resolve(cx, &src))?;
+        let attr = ready!(VfsNodeOps::get_attr(Pin::new(&node.current()), cx))?;
+        if attr.is_dir() && node.lower.is_some() {
+            // 与 Linux 的 overlayfs 一样不支持移动含有下层内容的目录
+            return Poll::Ready(Err(VfsError::Unsupported));
+        }
+        ready!(node.poll_copy_up(cx, true))?;
+        let (dst_parent, _) = split_parent(&dst);
+        let dst_parent = ready!(self.layers.poll_resolve(cx, dst_parent))?;
+        ready!(dst_parent.poll_copy_up(cx, true))?;
+        // 目标处的下层文件需要被隐藏
+        let dst_in_lower = !self.layers.is_hidden(&dst)
+            && ready!(poll_layer_lookup(&self.layers.lower, cx, &dst))?.is_some();
+        ready!(VfsNodeOps::rename(Pin::new(&self.layers.upper), cx, &src, &dst))?;
+        {
+            let mut whiteouts = self.layers.whiteouts.lock();
+            if node.lower.is_some() {
+                whiteouts.insert(src.clone());
+            }
+            if dst_in_lower {
+                whiteouts.insert(dst.clone());
+            }
+        }
+        drop(node);
+        self.layers.forget(&src);
+        self.layers.forget(&dst);
+        Poll::Ready(Ok(()))
+    }
+
+    fn link(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        path: &str,
+        node: VfsNodeRef,
+    ) -> Poll<VfsResult> {
+        let path = join(&self.path, path);
+        debug!("link at overlayfs: {}", path);
+        let Some(target) = (*node).as_any().downcast_ref::<OverlayNode>() else {
+            return Poll::Ready(Err(VfsError::PermissionDenied));
+        };
+        let upper = ready!(target.poll_copy_up(cx, true))?;
+        let (parent, _) = split_parent(&path);
+        let parent = ready!(self.layers.poll_resolve(cx, parent))?;
+        ready!(parent.poll_copy_up(cx, true))?;
+        VfsNodeOps::link(Pin::new(&self.layers.upper), cx, &path, upper)
+    }
+
+    fn symlink(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        path: &str,
+        target: &str,
+    ) -> Poll<VfsResult> {
+        let path = join(&self.path, path);
+        debug!("symlink at overlayfs: {} -> {}", path, target);
+        match ready!(self.layers.poll_resolve(cx, &path)) {
+            Ok(_) => return Poll::Ready(Err(VfsError::AlreadyExists)),
+            Err(VfsError::NotFound) => {}
+            Err(err) => return Poll::Ready(Err(err)),
+        }
+        let (parent, _) = split_parent(&path);
+        let parent = ready!(self.layers.poll_resolve(cx, parent))?;
+        ready!(parent.poll_copy_up(cx, true))?;
+        VfsNodeOps::symlink(Pin::new(&self.layers.upper), cx, &path, target)
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+/// 在某一层中查找 `path`，不存在时返回 `None`
+fn poll_layer_lookup(
+    root: &VfsNodeRef,
+    cx: &mut Context<'_>,
+    path: &str,
+) -> Poll<VfsResult<Option<VfsNodeRef>>> {
+    if path.is_empty() {
+        return Poll::Ready(Ok(Some(root.clone())));
+    }
+    Poll::Ready(match ready!(VfsNodeOps::lookup(Pin::new(root), cx, path)) {
+        Ok(node) => Ok(Some(node)),
+        Err(VfsError::NotFound | VfsError::NotADirectory) => Ok(None),
+        Err(err) => Err(err),
+    })
+}
+
+/// 在上层创建 `path`，已经存在时视为成功
+fn poll_create_upper(
+    upper: &VfsNodeRef,
+    cx: &mut Context<'_>,
+    path: &str,
+    ty: VfsNodeType,
+) -> Poll<VfsResult> {
+    Poll::Ready(match ready!(VfsNodeOps::create(Pin::new(upper), cx, path, ty)) {
+        // 上一次 copy-up 在等待中被打断时节点可能已经创建
+        Err(VfsError::AlreadyExists) => Ok(()),
+        res => res,
+    })
+}
+
+/// 读出某一层目录中除 `.` 与 `..` 以外的所有项
+fn poll_list(dir: &VfsNodeRef, cx: &mut Context<'_>) -> Poll<VfsResult<Vec<(String, VfsNodeType)>>> {
+    let mut entries = Vec::new();
+    let mut batch: Vec<VfsDirEntry> = (0..DIR_BATCH).map(|_| VfsDirEntry::default()).collect();
+    let mut start = 0;
+    loop {
+        let n = ready!(VfsNodeOps::read_dir(Pin::new(dir), cx, start, &mut batch))?;
+        for entry in &batch[..n] {
+            let name = String::from_utf8_lossy(entry.name_as_bytes());
+            if name != "." && name != ".." {
+                entries.push((name.into_owned(), entry.entry_type()));
+            }
+        }
+        if n < DIR_BATCH {
+            break;
+        }
+        start += n;
+    }
+    Poll::Ready(Ok(entries))
+}
+
+fn poll_copy_data(
+    src: &VfsNodeRef,
+    dst: &VfsNodeRef,
+    cx: &mut Context<'_>,
+    size: u64,
+) -> Poll<VfsResult> {
+    let mut buf = vec![0; COPY_BUF_SIZE];
+    let mut offset = 0;
+    while offset < size {
+        let n = ready!(VfsNodeOps::read_at(Pin::new(src), cx, offset, &mut buf))?;
+        if n == 0 {
+            break;
+        }
+        let mut written = 0;
+        while written < n {
+            let m = ready!(VfsNodeOps::write_at(
+                Pin::new(dst),
+                cx,
+                offset + written as u64,
+                &buf[written..n]
+            ))?;
+            written += m;
+        }
+        offset += n as u64;
+    }
+    Poll::Ready(Ok(()))
+}
+
+/// 复制扩展属性，下层不支持扩展属性时什么也不做
+fn poll_copy_xattrs(src: &VfsNodeRef, dst: &VfsNodeRef, cx: &mut Context<'_>) -> Poll<VfsResult> {
+    let mut names = vec![0; COPY_BUF_SIZE];
+    let len = match ready!(VfsNodeOps::listxattr(Pin::new(src), cx, &mut names)) {
+        Ok(len) if len <= names.len() => len,
+        Ok(_) => return Poll::Ready(Err(VfsError::StorageFull)),
+        Err(VfsError::Unsupported) => return Poll::Ready(Ok(())),
+        Err(err) => return Poll::Ready(Err(err)),
+    };
+    let mut value = vec![0; COPY_BUF_SIZE];
+    for name in names[..len].split(|&c| c == 0).filter(|name| !name.is_empty()) {
+        let name = core::str::from_utf8(name).map_err(|_| VfsError::InvalidData)?;
+        let n = ready!(VfsNodeOps::getxattr(Pin::new(src), cx, name, &mut value))?;
+        let value = value.get(..n).ok_or(VfsError::StorageFull)?;
+        ready!(VfsNodeOps::setxattr(Pin::new(dst), cx, name, value, VfsXattrFlags::empty()))?;
+    }
+    Poll::Ready(Ok(()))
+}
+
+/// `path` 或其任一上级目录是否被 whiteout
+fn is_whited_out(whiteouts: &BTreeSet<String>, path: &str) -> bool {
+    !whiteouts.is_empty()
+        && path
+            .match_indices('/')
+            .map(|(i, _)| &path[..i])
+            .chain(core::iter::once(path))
+            .any(|prefix| whiteouts.contains(prefix))
+}
+
+/// 将相对于 `base` 的 `path` 规范化为相对于根目录的路径，`..` 不会越过根目录
+fn join(base: &str, path: &str) -> String {
+    let mut comps: Vec<&str> = base.split('/').filter(|comp| !comp.is_empty()).collect();
+    for comp in path.split('/') {
+        match comp {
+            "" | "." => {}
+            ".." => {
+                comps.pop();
+            }
+            _ => comps.push(comp),
+        }
+    }
+    comps.join("/")
+}
+
+fn split_parent(path: &str) -> (&str, &str) {
+    path.rsplit_once('/').unwrap_or(("", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::block_on;
+
+    #[test]
+    fn join_normalizes_paths() {
+        assert_eq!(join("", "a/./b/"), "a/b");
+        assert_eq!(join("a/b", "../c"), "a/c");
+        assert_eq!(join("a", "../../.."), "");
+        assert_eq!(join("a", "/b"), "a/b");
+    }
+
+    #[test]
+    fn whiteout_hides_descendants() {
+        let mut whiteouts = BTreeSet::new();
+        assert!(!is_whited_out(&whiteouts, "etc/passwd"));
+        whiteouts.insert(String::from("etc"));
+        assert!(is_whited_out(&whiteouts, "etc"));
+        assert!(is_whited_out(&whiteouts, "etc/passwd"));
+        assert!(!is_whited_out(&whiteouts, "etcetera"));
+        assert!(!is_whited_out(&whiteouts, ""));
+    }
+
+    #[test]
+    fn statfs_reports_upper_layer() {
+        use crate::fs::ramfs::{RamFileSystem, TMPFS_MAGIC};
+        use async_vfs::AsyncVfsOps;
+
+        const PROC_SUPER_MAGIC: u64 = 0x9fa0;
+        let upper = Arc::new(RamFileSystem::new());
+        let lower = Arc::new(RamFileSystem::with_magic(PROC_SUPER_MAGIC));
+        let upper_info = block_on(upper.statfs()).unwrap();
+        assert_eq!(upper_info.fs_type, TMPFS_MAGIC);
+        assert_eq!(upper_info.block_size, 4096);
+        assert_eq!(upper_info.name_len, 255);
+        assert_eq!(upper_info.blocks, 0);
+        assert_eq!(block_on(lower.statfs()).unwrap().fs_type, PROC_SUPER_MAGIC);
+
+        let overlay = block_on(OverlayFileSystem::new(upper, lower));
+        let info = block_on(overlay.statfs()).unwrap();
+        // 空间使用情况来自上层，类型是 overlayfs 自己的
+        assert_eq!(info.fs_type, OVERLAYFS_SUPER_MAGIC);
+        assert_eq!(info.block_size, upper_info.block_size);
+        assert_eq!(info.name_len, upper_info.name_len);
+        assert_eq!(info.flags, 0);
+    }
+}
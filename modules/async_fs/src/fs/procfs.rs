@@ -0,0 +1,64 @@
+//! procfs 中的动态文件
+//!
+//! procfs 本身是一个 ramfs，其中部分文件的内容在每次读取时根据内核状态动态生成，
+//! 例如 `/proc/mounts` 由挂载树生成。
+
+use alloc::string::String;
+use async_vfs::{impl_vfs_non_dir_default, VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm};
+use async_vfs::{VfsNodeType, VfsResult};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// 内容由生成函数给出的只读文件
+pub struct ProcFile {
+    generate: fn() -> String,
+}
+
+impl ProcFile {
+    /// 创建一个动态文件，每次读取时调用 `generate` 生成其内容
+    pub const fn new(generate: fn() -> String) -> Self {
+        Self { generate }
+    }
+}
+
+impl VfsNodeOps for ProcFile {
+    impl_vfs_non_dir_default! {}
+
+    fn get_attr(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<VfsNodeAttr>> {
+        // 与 Linux 一致，procfs 中的文件大小总是 0
+        Poll::Ready(Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o444),
+            VfsNodeType::File,
+            0,
+            0,
+        )))
+    }
+
+    fn read_at(
+        self: Pin<&Self>,
+        _cx: &mut Context<'_>,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Poll<VfsResult<usize>> {
+        let content = (self.generate)();
+        let content = content.as_bytes();
+        let start = content.len().min(offset as usize);
+        let end = content.len().min(offset as usize + buf.len());
+        let src = &content[start..end];
+        buf[..src.len()].copy_from_slice(src);
+        Poll::Ready(Ok(src.len()))
+    }
+
+    fn write_at(
+        self: Pin<&Self>,
+        _cx: &mut Context<'_>,
+        _offset: u64,
+        _buf: &[u8],
+    ) -> Poll<VfsResult<usize>> {
+        Poll::Ready(axerrno::ax_err!(PermissionDenied))
+    }
+
+    fn fsync(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult> {
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,427 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::{string::String, vec::Vec};
+use async_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType};
+use async_vfs::{VfsError, VfsResult, VfsSetAttr};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use spinlock::SpinNoIrq;
+
+use super::file::FileNode;
+use super::meta::{impl_meta_xattr, NodeMeta};
+use super::special::SpecialNode;
+use super::symlink::SymlinkNode;
+
+/// The directory node in the RAM filesystem.
+///
+/// It implements [`async_vfs::VfsNodeOps`].
+pub struct DirNode {
+    this: Weak<DirNode>,
+    parent: SpinNoIrq<Weak<dyn VfsNodeOps + Unpin>>,
+    children: SpinNoIrq<BTreeMap<String, VfsNodeRef>>,
+    meta: SpinNoIrq<NodeMeta>,
+}
+
+impl DirNode {
+    pub(super) fn new(parent: Option<Weak<dyn VfsNodeOps + Unpin>>) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            parent: SpinNoIrq::new(parent.unwrap_or_else(|| Weak::<Self>::new())),
+            children: SpinNoIrq::new(BTreeMap::new()),
+            meta: SpinNoIrq::new(NodeMeta::new(VfsNodePerm::default_dir())),
+        })
+    }
+
+    pub(super) fn set_parent(&self, parent: Option<&VfsNodeRef>) {
+        *self.parent.lock() = parent.map_or(Weak::<Self>::new() as _, Arc::downgrade);
+    }
+
+    /// Returns a string list of all entries in this directory.
+    pub fn get_entries(&self) -> Vec<String> {
+        self.children.lock().keys().cloned().collect()
+    }
+
+    /// Checks whether a node with the given name exists in this directory.
+    pub fn exist(&self, name: &str) -> bool {
+        self.children.lock().contains_key(name)
+    }
+
+    /// Creates a new node with the given name and type in this directory.
+    pub fn create_node(&self, name: &str, ty: VfsNodeType) -> VfsResult {
+        let node: VfsNodeRef = match ty {
+            VfsNodeType::File => Arc::new(FileNode::new()),
+            VfsNodeType::Dir => Self::new(Some(self.this.clone() as Weak<dyn VfsNodeOps + Unpin>)),
+            VfsNodeType::Fifo | VfsNodeType::Socket => Arc::new(SpecialNode::new(ty)),
+            _ => return Err(VfsError::Unsupported),
+        };
+        self.add_node(name, node)
+    }
+
+    /// Adds an existing node into this directory, the node can be of any
+    /// type that implements [`VfsNodeOps`].
+    pub fn add_node(&self, name: &str, node: VfsNodeRef) -> VfsResult {
+        let mut children = self.children.lock();
+        if children.contains_key(name) {
+            log::error!("AlreadyExists {}", name);
+            return Err(VfsError::AlreadyExists);
+        }
+        children.insert(name.into(), node);
+        self.meta.lock().modified();
+        Ok(())
+    }
+
+    /// Adds a hard link to an existing file into this directory.
+    pub fn add_link(&self, name: &str, node: VfsNodeRef) -> VfsResult {
+        let Some(file) = node.as_any().downcast_ref::<FileNode>() else {
+            // 目录不能建立硬链接，其他文件系统的节点也不能链接到这里
+            return Err(VfsError::PermissionDenied);
+        };
+        file.inc_nlink();
+        self.add_node(name, node.clone()).inspect_err(|_| file.dec_nlink())
+    }
+
+    /// Creates a symbolic link with the given name in this directory.
+    pub fn add_symlink(&self, name: &str, target: &str) -> VfsResult {
+        self.add_node(name, Arc::new(SymlinkNode::new(target)))
+    }
+
+    /// Removes a node by the given name in this directory.
+    pub fn remove_node(&self, name: &str) -> VfsResult {
+        let mut children = self.children.lock();
+        let node = children.get(name).ok_or(VfsError::NotFound)?;
+        if let Some(dir) = node.as_any().downcast_ref::<DirNode>() {
+            if !dir.children.lock().is_empty() {
+                return Err(VfsError::DirectoryNotEmpty);
+            }
+        }
+        if let Some(node) = children.remove(name) {
+            unlinked(&node);
+        }
+        self.meta.lock().modified();
+        Ok(())
+    }
+
+    /// Renames the node at `src_path` to `dst_path`, both relative to this
+    /// directory. An existing destination is replaced following the rules of
+    /// rename(2).
+    pub fn rename_node(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        let (src_dir, src_name) = split_parent(src_path);
+        let (dst_dir, dst_name) = split_parent(dst_path);
+        if [src_name, dst_name].iter().any(|name| matches!(*name, "" | "." | "..")) {
+            return Err(VfsError::InvalidInput);
+        }
+        let src_dir = self.lookup_dir(src_dir)?;
+        let dst_dir = self.lookup_dir(dst_dir)?;
+        let node = src_dir
+            .children
+            .lock()
+            .get(src_name)
+            .cloned()
+            .ok_or(VfsError::NotFound)?;
+        let src_is_dir = node.as_any().is::<DirNode>();
+        if src_is_dir && dst_dir.is_descendant_of(&node) {
+            // 目录不能移动到自身或者自己的子目录中
+            return Err(VfsError::InvalidInput);
+        }
+        if let Some(old) = dst_dir.children.lock().get(dst_name) {
+            if Arc::ptr_eq(old, &node) {
+                // 新旧路径是同一个文件的链接，什么也不做
+                return Ok(());
+            }
+            match (src_is_dir, old.as_any().downcast_ref::<DirNode>()) {
+                (true, Some(old)) if !old.children.lock().is_empty() => {
+                    return Err(VfsError::DirectoryNotEmpty)
+                }
+                (false, Some(_)) => return Err(VfsError::IsADirectory),
+                (true, None) => return Err(VfsError::NotADirectory),
+                _ => {}
+            }
+        }
+        src_dir.children.lock().remove(src_name);
+        if let Some(dir) = node.as_any().downcast_ref::<DirNode>() {
+            *dir.parent.lock() = dst_dir.this.clone() as Weak<dyn VfsNodeOps + Unpin>;
+        }
+        if let Some(old) = dst_dir.children.lock().insert(dst_name.into(), node) {
+            unlinked(&old);
+        }
+        src_dir.meta.lock().modified();
+        dst_dir.meta.lock().modified();
+        Ok(())
+    }
+
+    /// 该目录是否是 `node` 本身或者位于 `node` 之下
+    fn is_descendant_of(&self, node: &VfsNodeRef) -> bool {
+        let mut dir = self.this.upgrade();
+        while let Some(current) = dir {
+            if core::ptr::eq(Arc::as_ptr(&current) as *const (), Arc::as_ptr(node) as *const ()) {
+                return true;
+            }
+            dir = current
+                .parent
+                .lock()
+                .upgrade()
+                .and_then(|p| p.as_any().downcast_ref::<DirNode>()?.this.upgrade());
+        }
+        false
+    }
+
+    /// Gets the sub directory node by the given path, without crossing into
+    /// other filesystems.
+    pub fn lookup_dir(&self, path: &str) -> VfsResult<Arc<DirNode>> {
+        let mut dir = self.this.upgrade().ok_or(VfsError::NotFound)?;
+        for name in path.split('/') {
+            dir = match name {
+                "" | "." => dir,
+                ".." => dir
+                    .parent
+                    .lock()
+                    .upgrade()
+                    .and_then(|p| p.as_any().downcast_ref::<DirNode>()?.this.upgrade())
+                    .ok_or(VfsError::NotFound)?,
+                _ => {
+                    let node = dir.children.lock().get(name).cloned();
+                    let node = node.ok_or(VfsError::NotFound)?;
+                    let sub = node.as_any().downcast_ref::<DirNode>();
+                    sub.ok_or(VfsError::NotADirectory)?.this.upgrade().unwrap()
+                }
+            };
+        }
+        Ok(dir)
+    }
+}
+
+impl VfsNodeOps for DirNode {
+    async_vfs::impl_vfs_dir_default! {}
+    impl_meta_xattr! {}
+
+    fn get_attr(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<VfsNodeAttr>> {
+        let mut attr = VfsNodeAttr::new_dir(4096, 0);
+        let subdirs = self
+            .children
+            .lock()
+            .values()
+            .filter(|node| node.as_any().is::<DirNode>())
+            .count();
+        attr.set_nlink(2 + subdirs as u64);
+        self.meta.lock().fill(&mut attr);
+        Poll::Ready(Ok(attr))
+    }
+
+    fn setattr(self: Pin<&Self>, _cx: &mut Context<'_>, attr: &VfsSetAttr) -> Poll<VfsResult> {
+        self.meta.lock().setattr(attr);
+        Poll::Ready(Ok(()))
+    }
+
+    fn parent(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<Option<VfsNodeRef>> {
+        Poll::Ready(self.parent.lock().upgrade())
+    }
+
+    fn lookup(self: Pin<&Self>, cx: &mut Context<'_>, path: &str) -> Poll<VfsResult<VfsNodeRef>> {
+        let (name, rest) = split_path(path);
+        let node = match name {
+            "" | "." => self.this.upgrade().map(|this| this as VfsNodeRef),
+            ".." => self.parent.lock().upgrade(),
+            _ => self.children.lock().get(name).cloned(),
+        };
+        let Some(node) = node else {
+            return Poll::Ready(Err(VfsError::NotFound));
+        };
+        if let Some(rest) = rest {
+            VfsNodeOps::lookup(Pin::new(&node), cx, rest)
+        } else {
+            Poll::Ready(Ok(node))
+        }
+    }
+
+    fn read_dir(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        start_idx: usize,
+        dirents: &mut [VfsDirEntry],
+    ) -> Poll<VfsResult<usize>> {
+        let children: Vec<(String, VfsNodeRef)> = self
+            .children
+            .lock()
+            .iter()
+            .skip(start_idx.max(2) - 2)
+            .take(dirents.len())
+            .map(|(name, node)| (name.clone(), node.clone()))
+            .collect();
+        let mut children = children.iter();
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => {
+                    if let Some((name, node)) = children.next() {
+                        let attr = futures_core::ready!(VfsNodeOps::get_attr(Pin::new(node), cx))?;
+                        *ent = VfsDirEntry::new(name, attr.file_type());
+                    } else {
+                        return Poll::Ready(Ok(i));
+                    }
+                }
+            }
+        }
+        Poll::Ready(Ok(dirents.len()))
+    }
+
+    fn create(self: Pin<&Self>, cx: &mut Context<'_>, path: &str, ty: VfsNodeType) -> Poll<VfsResult> {
+        log::debug!("create {:?} at ramfs: {}", ty, path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            let node = match name {
+                "" | "." => return self.create(cx, rest, ty),
+                ".." => self.parent.lock().upgrade(),
+                _ => self.children.lock().get(name).cloned(),
+            };
+            let Some(node) = node else {
+                return Poll::Ready(Err(VfsError::NotFound));
+            };
+            VfsNodeOps::create(Pin::new(&node), cx, rest, ty)
+        } else if name.is_empty() || name == "." || name == ".." {
+            Poll::Ready(Ok(())) // already exists
+        } else {
+            Poll::Ready(self.create_node(name, ty))
+        }
+    }
+
+    fn remove(self: Pin<&Self>, cx: &mut Context<'_>, path: &str) -> Poll<VfsResult> {
+        log::debug!("remove at ramfs: {}", path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            let node = match name {
+                "" | "." => return self.remove(cx, rest),
+                ".." => self.parent.lock().upgrade(),
+                _ => self.children.lock().get(name).cloned(),
+            };
+            let Some(node) = node else {
+                return Poll::Ready(Err(VfsError::NotFound));
+            };
+            VfsNodeOps::remove(Pin::new(&node), cx, rest)
+        } else if name.is_empty() || name == "." || name == ".." {
+            Poll::Ready(Err(VfsError::InvalidInput)) // remove '.' or '..
+        } else {
+            Poll::Ready(self.remove_node(name))
+        }
+    }
+
+    fn rename(
+        self: Pin<&Self>,
+        _cx: &mut Context<'_>,
+        src_path: &str,
+        dst_path: &str,
+    ) -> Poll<VfsResult> {
+        log::debug!("rename at ramfs, src_path: {}, dst_path: {}", src_path, dst_path);
+        Poll::Ready(self.rename_node(src_path, dst_path))
+    }
+
+    fn link(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        path: &str,
+        node: VfsNodeRef,
+    ) -> Poll<VfsResult> {
+        log::debug!("link at ramfs: {}", path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            let dir = match name {
+                "" | "." => return self.link(cx, rest, node),
+                ".." => self.parent.lock().upgrade(),
+                _ => self.children.lock().get(name).cloned(),
+            };
+            let Some(dir) = dir else {
+                return Poll::Ready(Err(VfsError::NotFound));
+            };
+            VfsNodeOps::link(Pin::new(&dir), cx, rest, node)
+        } else if name.is_empty() || name == "." || name == ".." {
+            Poll::Ready(Err(VfsError::AlreadyExists))
+        } else {
+            Poll::Ready(self.add_link(name, node))
+        }
+    }
+
+    fn symlink(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        path: &str,
+        target: &str,
+    ) -> Poll<VfsResult> {
+        log::debug!("symlink at ramfs: {} -> {}", path, target);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            let dir = match name {
+                "" | "." => return self.symlink(cx, rest, target),
+                ".." => self.parent.lock().upgrade(),
+                _ => self.children.lock().get(name).cloned(),
+            };
+            let Some(dir) = dir else {
+                return Poll::Ready(Err(VfsError::NotFound));
+            };
+            VfsNodeOps::symlink(Pin::new(&dir), cx, rest, target)
+        } else if name.is_empty() || name == "." || name == ".." {
+            Poll::Ready(Err(VfsError::AlreadyExists))
+        } else {
+            Poll::Ready(self.add_symlink(name, target))
+        }
+    }
+}
+
+/// 目录项被删除或覆盖时，减少文件的链接数
+fn unlinked(node: &VfsNodeRef) {
+    if let Some(file) = node.as_any().downcast_ref::<FileNode>() {
+        file.dec_nlink();
+    }
+}
+
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    let trimmed_path = path.trim_start_matches('/');
+    trimmed_path.find('/').map_or((trimmed_path, None), |n| {
+        (&trimmed_path[..n], Some(&trimmed_path[n + 1..]))
+    })
+}
+
+fn split_parent(path: &str) -> (&str, &str) {
+    let trimmed_path = path.trim_matches('/');
+    trimmed_path
+        .rsplit_once('/')
+        .unwrap_or(("", trimmed_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rename_replaces_by_type() {
+        let root = DirNode::new(None);
+        root.create_node("a", VfsNodeType::Dir).unwrap();
+        root.lookup_dir("a").unwrap().create_node("b", VfsNodeType::Dir).unwrap();
+        root.create_node("e", VfsNodeType::Dir).unwrap();
+        root.create_node("f", VfsNodeType::File).unwrap();
+
+        assert_eq!(root.rename_node("a", "a/b/c"), Err(VfsError::InvalidInput));
+        assert_eq!(root.rename_node("a", "a/c"), Err(VfsError::InvalidInput));
+        assert_eq!(root.rename_node("f", "a"), Err(VfsError::IsADirectory));
+        assert_eq!(root.rename_node("a", "f"), Err(VfsError::NotADirectory));
+        assert_eq!(root.rename_node("e", "a"), Err(VfsError::DirectoryNotEmpty));
+        assert_eq!(root.rename_node("a", "a"), Ok(()));
+        assert_eq!(root.rename_node("x", "y"), Err(VfsError::NotFound));
+
+        // 空目录可以被目录替换，移动后的目录的父目录随之改变
+        root.rename_node("a/b", "e").unwrap();
+        assert!(!root.lookup_dir("a").unwrap().exist("b"));
+        let e = root.lookup_dir("e").unwrap();
+        assert!(Arc::ptr_eq(&e.lookup_dir("..").unwrap(), &root));
+        assert_eq!(root.get_entries(), ["a", "e", "f"]);
+    }
+
+    #[test]
+    fn rename_file_over_link_of_itself() {
+        let root = DirNode::new(None);
+        root.create_node("f", VfsNodeType::File).unwrap();
+        let node = root.children.lock().get("f").cloned().unwrap();
+        root.add_link("g", node).unwrap();
+        assert_eq!(root.rename_node("f", "g"), Ok(()));
+        assert_eq!(root.get_entries(), ["f", "g"]);
+    }
+}
@@ -0,0 +1,233 @@
+use async_vfs::{impl_vfs_non_dir_default, VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm};
+use async_vfs::{VfsError, VfsResult, VfsSeals, VfsSetAttr};
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll};
+use spinlock::SpinNoIrq;
+
+use super::meta::{impl_meta_xattr, NodeMeta};
+use super::sparse::SparseContent;
+
+/// The file node in the RAM filesystem.
+///
+/// It implements [`async_vfs::VfsNodeOps`]. The content is sparse: holes
+/// left by seeking past the end or by punching take no memory.
+pub struct FileNode {
+    content: SpinNoIrq<SparseContent>,
+    /// 指向该文件的目录项数
+    nlink: AtomicU64,
+    meta: SpinNoIrq<NodeMeta>,
+    /// 在持有 `content` 的锁时访问，使检查与修改是原子的
+    seals: SpinNoIrq<VfsSeals>,
+}
+
+impl FileNode {
+    /// Create a new empty file node.
+    ///
+    /// 与 Linux 的 tmpfs 相同，它带有 [`VfsSeals::SEAL`]，不能再被添加 seals
+    pub fn new() -> Self {
+        Self::with_seals(VfsSeals::SEAL)
+    }
+
+    /// Create a new empty file node with the initial `seals`, used by memfd.
+    pub fn with_seals(seals: VfsSeals) -> Self {
+        Self {
+            content: SpinNoIrq::new(SparseContent::new()),
+            nlink: AtomicU64::new(1),
+            meta: SpinNoIrq::new(NodeMeta::new(VfsNodePerm::default_file())),
+            seals: SpinNoIrq::new(seals),
+        }
+    }
+
+    /// 检查 seals 是否允许将长度为 `len` 的文件修改为 `new_len`，`write` 表示内容会被修改
+    fn check_seals(&self, len: u64, new_len: u64, write: bool) -> VfsResult {
+        let seals = *self.seals.lock();
+        if (write && seals.intersects(VfsSeals::WRITE | VfsSeals::FUTURE_WRITE))
+            || (new_len < len && seals.contains(VfsSeals::SHRINK))
+            || (new_len > len && seals.contains(VfsSeals::GROW))
+        {
+            return Err(VfsError::PermissionDenied);
+        }
+        Ok(())
+    }
+
+    /// 新增一个指向该文件的硬链接
+    pub(super) fn inc_nlink(&self) {
+        self.nlink.fetch_add(1, Ordering::Relaxed);
+        self.meta.lock().changed();
+    }
+
+    /// 删除一个指向该文件的目录项
+    pub(super) fn dec_nlink(&self) {
+        self.nlink.fetch_sub(1, Ordering::Relaxed);
+        self.meta.lock().changed();
+    }
+
+    /// Replace the whole content of the file.
+    pub fn set_content(&self, data: &[u8]) {
+        self.content.lock().set(data);
+        self.meta.lock().modified();
+    }
+}
+
+impl VfsNodeOps for FileNode {
+    impl_vfs_non_dir_default! {}
+    impl_meta_xattr! {}
+
+    fn get_attr(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<VfsNodeAttr>> {
+        let content = self.content.lock();
+        let mut attr = VfsNodeAttr::new_file(content.len(), content.allocated() / 512);
+        drop(content);
+        attr.set_nlink(self.nlink.load(Ordering::Relaxed));
+        self.meta.lock().fill(&mut attr);
+        Poll::Ready(Ok(attr))
+    }
+
+    fn setattr(self: Pin<&Self>, _cx: &mut Context<'_>, attr: &VfsSetAttr) -> Poll<VfsResult> {
+        self.meta.lock().setattr(attr);
+        Poll::Ready(Ok(()))
+    }
+
+    fn truncate(self: Pin<&Self>, _cx: &mut Context<'_>, size: u64) -> Poll<VfsResult> {
+        let mut content = self.content.lock();
+        self.check_seals(content.len(), size, false)?;
+        content.truncate(size);
+        drop(content);
+        self.meta.lock().modified();
+        Poll::Ready(Ok(()))
+    }
+
+    fn read_at(
+        self: Pin<&Self>,
+        _cx: &mut Context<'_>,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Poll<VfsResult<usize>> {
+        let read_len = self.content.lock().read_at(offset, buf);
+        self.meta.lock().accessed();
+        Poll::Ready(Ok(read_len))
+    }
+
+    fn write_at(
+        self: Pin<&Self>,
+        _cx: &mut Context<'_>,
+        offset: u64,
+        buf: &[u8],
+    ) -> Poll<VfsResult<usize>> {
+        let mut content = self.content.lock();
+        let len = content.len();
+        if !buf.is_empty() {
+            let end = offset.saturating_add(buf.len() as u64);
+            self.check_seals(len, len.max(end), true)?;
+        }
+        content.write_at(offset, buf);
+        drop(content);
+        self.meta.lock().modified();
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn fsync(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn allocate(
+        self: Pin<&Self>,
+        _cx: &mut Context<'_>,
+        offset: u64,
+        len: u64,
+        keep_size: bool,
+    ) -> Poll<VfsResult> {
+        let mut content = self.content.lock();
+        let size = content.len();
+        let new_size = if keep_size { size } else { size.max(offset.saturating_add(len)) };
+        self.check_seals(size, new_size, false)?;
+        content.allocate(offset, len, keep_size);
+        drop(content);
+        self.meta.lock().modified();
+        Poll::Ready(Ok(()))
+    }
+
+    fn punch_hole(self: Pin<&Self>, _cx: &mut Context<'_>, offset: u64, len: u64) -> Poll<VfsResult> {
+        let mut content = self.content.lock();
+        self.check_seals(content.len(), content.len(), true)?;
+        content.punch_hole(offset, len);
+        drop(content);
+        self.meta.lock().modified();
+        Poll::Ready(Ok(()))
+    }
+
+    fn seek_data(
+        self: Pin<&Self>,
+        _cx: &mut Context<'_>,
+        offset: u64,
+        hole: bool,
+    ) -> Poll<VfsResult<Option<u64>>> {
+        Poll::Ready(Ok(self.content.lock().seek_data(offset, hole)))
+    }
+
+    fn get_seals(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<VfsSeals>> {
+        Poll::Ready(Ok(*self.seals.lock()))
+    }
+
+    fn add_seals(self: Pin<&Self>, _cx: &mut Context<'_>, seals: VfsSeals) -> Poll<VfsResult> {
+        // 与写入互斥，之后的写入一定能看到新的 seals
+        let _content = self.content.lock();
+        let mut current = self.seals.lock();
+        if current.contains(VfsSeals::SEAL) {
+            return Poll::Ready(Err(VfsError::PermissionDenied));
+        }
+        current.insert(seals);
+        drop(current);
+        self.meta.lock().changed();
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_vfs::AsyncVfsNodeOps;
+    use test_utils::block_on;
+
+    #[test]
+    fn regular_files_cannot_be_sealed() {
+        let file = FileNode::new();
+        assert_eq!(block_on(file.get_seals()), Ok(VfsSeals::SEAL));
+        assert_eq!(block_on(file.add_seals(VfsSeals::WRITE)), Err(VfsError::PermissionDenied));
+    }
+
+    #[test]
+    fn seals_restrict_size_changes() {
+        let file = FileNode::with_seals(VfsSeals::empty());
+        assert_eq!(block_on(file.write_at(0, b"hello")), Ok(5));
+        block_on(file.add_seals(VfsSeals::SHRINK)).unwrap();
+        assert_eq!(block_on(file.truncate(2)), Err(VfsError::PermissionDenied));
+        // 不改变长度的修改不受影响
+        assert_eq!(block_on(file.truncate(5)), Ok(()));
+        block_on(file.truncate(8)).unwrap();
+
+        block_on(file.add_seals(VfsSeals::GROW)).unwrap();
+        assert_eq!(block_on(file.truncate(9)), Err(VfsError::PermissionDenied));
+        assert_eq!(block_on(file.write_at(6, b"abc")), Err(VfsError::PermissionDenied));
+        assert_eq!(block_on(file.allocate(0, 16, false)), Err(VfsError::PermissionDenied));
+        assert_eq!(block_on(file.allocate(0, 16, true)), Ok(()));
+        assert_eq!(block_on(file.write_at(0, b"HE")), Ok(2));
+    }
+
+    #[test]
+    fn write_seal_forbids_modification() {
+        let file = FileNode::with_seals(VfsSeals::empty());
+        block_on(file.write_at(0, b"data")).unwrap();
+        block_on(file.add_seals(VfsSeals::WRITE | VfsSeals::SEAL)).unwrap();
+        assert_eq!(block_on(file.write_at(0, b"x")), Err(VfsError::PermissionDenied));
+        assert_eq!(block_on(file.punch_hole(0, 2)), Err(VfsError::PermissionDenied));
+        // 空的写入不修改内容
+        assert_eq!(block_on(file.write_at(0, b"")), Ok(0));
+        let mut buf = [0u8; 4];
+        assert_eq!(block_on(file.read_at(0, &mut buf)), Ok(4));
+        assert_eq!(&buf, b"data");
+        // 带有 SEAL 后不能再添加
+        assert_eq!(block_on(file.add_seals(VfsSeals::GROW)), Err(VfsError::PermissionDenied));
+        assert_eq!(block_on(file.get_seals()), Ok(VfsSeals::WRITE | VfsSeals::SEAL));
+    }
+}
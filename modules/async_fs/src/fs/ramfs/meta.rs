@@ -0,0 +1,200 @@
+//! 内存文件系统中节点的权限、所有者、时间戳与扩展属性
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use async_vfs::{VfsError, VfsNodeAttr, VfsNodePerm, VfsResult, VfsSetAttr, VfsXattrFlags};
+use core::time::Duration;
+
+/// 节点的可变元数据，新建的节点属于 root，由调用者通过 setattr 修改
+pub(crate) struct NodeMeta {
+    perm: VfsNodePerm,
+    uid: u32,
+    gid: u32,
+    atime: Duration,
+    mtime: Duration,
+    ctime: Duration,
+    xattrs: BTreeMap<String, Vec<u8>>,
+}
+
+impl NodeMeta {
+    pub(crate) fn new(perm: VfsNodePerm) -> Self {
+        let now = crate::now();
+        Self {
+            perm,
+            uid: 0,
+            gid: 0,
+            atime: now,
+            mtime: now,
+            ctime: now,
+            xattrs: BTreeMap::new(),
+        }
+    }
+
+    /// 将元数据填入 `attr`
+    pub(crate) fn fill(&self, attr: &mut VfsNodeAttr) {
+        attr.set_perm(self.perm);
+        attr.set_owner(self.uid, self.gid);
+        attr.set_times(self.atime, self.mtime, self.ctime);
+    }
+
+    pub(crate) fn setattr(&mut self, attr: &VfsSetAttr) {
+        if let Some(perm) = attr.mode {
+            self.perm = perm;
+        }
+        if let Some(uid) = attr.uid {
+            self.uid = uid;
+        }
+        if let Some(gid) = attr.gid {
+            self.gid = gid;
+        }
+        if let Some(atime) = attr.atime {
+            self.atime = atime;
+        }
+        if let Some(mtime) = attr.mtime {
+            self.mtime = mtime;
+        }
+        self.ctime = crate::now();
+    }
+
+    /// 内容被读取
+    pub(crate) fn accessed(&mut self) {
+        self.atime = crate::now();
+    }
+
+    /// 内容被修改，同时也是一次状态变化
+    pub(crate) fn modified(&mut self) {
+        let now = crate::now();
+        self.mtime = now;
+        self.ctime = now;
+    }
+
+    /// 链接数等状态发生变化
+    pub(crate) fn changed(&mut self) {
+        self.ctime = crate::now();
+    }
+
+    pub(crate) fn getxattr(&self, name: &str, buf: &mut [u8]) -> VfsResult<usize> {
+        let value = self.xattrs.get(name).ok_or(VfsError::NotFound)?;
+        if value.len() <= buf.len() {
+            buf[..value.len()].copy_from_slice(value);
+        }
+        Ok(value.len())
+    }
+
+    pub(crate) fn setxattr(&mut self, name: &str, value: &[u8], flags: VfsXattrFlags) -> VfsResult {
+        let exists = self.xattrs.contains_key(name);
+        if flags.contains(VfsXattrFlags::CREATE) && exists {
+            return Err(VfsError::AlreadyExists);
+        }
+        if flags.contains(VfsXattrFlags::REPLACE) && !exists {
+            return Err(VfsError::NotFound);
+        }
+        self.xattrs.insert(name.into(), value.into());
+        self.changed();
+        Ok(())
+    }
+
+    /// 将所有扩展属性的名字以 '\0' 分隔写入 `buf`
+    pub(crate) fn listxattr(&self, buf: &mut [u8]) -> usize {
+        let len = self.xattrs.keys().map(|name| name.len() + 1).sum();
+        if len <= buf.len() {
+            let mut pos = 0;
+            for name in self.xattrs.keys() {
+                buf[pos..pos + name.len()].copy_from_slice(name.as_bytes());
+                buf[pos + name.len()] = 0;
+                pos += name.len() + 1;
+            }
+        }
+        len
+    }
+
+    pub(crate) fn removexattr(&mut self, name: &str) -> VfsResult {
+        self.xattrs.remove(name).ok_or(VfsError::NotFound)?;
+        self.changed();
+        Ok(())
+    }
+}
+
+/// 为带有 `meta: SpinNoIrq<NodeMeta>` 的节点实现扩展属性相关的操作
+macro_rules! impl_meta_xattr {
+    () => {
+        fn getxattr(
+            self: Pin<&Self>,
+            _cx: &mut Context<'_>,
+            name: &str,
+            buf: &mut [u8],
+        ) -> Poll<VfsResult<usize>> {
+            Poll::Ready(self.meta.lock().getxattr(name, buf))
+        }
+
+        fn setxattr(
+            self: Pin<&Self>,
+            _cx: &mut Context<'_>,
+            name: &str,
+            value: &[u8],
+            flags: async_vfs::VfsXattrFlags,
+        ) -> Poll<VfsResult> {
+            Poll::Ready(self.meta.lock().setxattr(name, value, flags))
+        }
+
+        fn listxattr(self: Pin<&Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<VfsResult<usize>> {
+            Poll::Ready(Ok(self.meta.lock().listxattr(buf)))
+        }
+
+        fn removexattr(self: Pin<&Self>, _cx: &mut Context<'_>, name: &str) -> Poll<VfsResult> {
+            Poll::Ready(self.meta.lock().removexattr(name))
+        }
+    };
+}
+
+pub(crate) use impl_meta_xattr;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setxattr_flags() {
+        let mut meta = NodeMeta::new(VfsNodePerm::from_bits_truncate(0o644));
+        assert_eq!(meta.setxattr("user.a", b"1", VfsXattrFlags::REPLACE), Err(VfsError::NotFound));
+        meta.setxattr("user.a", b"1", VfsXattrFlags::CREATE).unwrap();
+        assert_eq!(meta.setxattr("user.a", b"2", VfsXattrFlags::CREATE), Err(VfsError::AlreadyExists));
+        meta.setxattr("user.a", b"22", VfsXattrFlags::REPLACE).unwrap();
+        // 不带标志时既可以创建也可以替换
+        meta.setxattr("user.a", b"333", VfsXattrFlags::empty()).unwrap();
+        meta.setxattr("user.b", b"", VfsXattrFlags::empty()).unwrap();
+        // 同时带有两个标志时，属性存在与否都会失败
+        let both = VfsXattrFlags::CREATE | VfsXattrFlags::REPLACE;
+        assert_eq!(meta.setxattr("user.a", b"4", both), Err(VfsError::AlreadyExists));
+        assert_eq!(meta.setxattr("user.c", b"4", both), Err(VfsError::NotFound));
+
+        let mut buf = [0u8; 8];
+        assert_eq!(meta.getxattr("user.a", &mut buf), Ok(3));
+        assert_eq!(&buf[..3], b"333");
+        assert_eq!(meta.getxattr("user.b", &mut buf), Ok(0));
+        assert_eq!(meta.getxattr("user.c", &mut buf), Err(VfsError::NotFound));
+    }
+
+    #[test]
+    fn xattr_sizes_and_removal() {
+        let mut meta = NodeMeta::new(VfsNodePerm::from_bits_truncate(0o644));
+        meta.setxattr("user.long", b"abcdef", VfsXattrFlags::empty()).unwrap();
+        meta.setxattr("security.x", b"y", VfsXattrFlags::empty()).unwrap();
+
+        // 缓冲区不足时不写入，只返回需要的长度
+        let mut small = [0u8; 2];
+        assert_eq!(meta.getxattr("user.long", &mut small), Ok(6));
+        assert_eq!(small, [0, 0]);
+        assert_eq!(meta.listxattr(&mut small), 21);
+        assert_eq!(small, [0, 0]);
+
+        let mut buf = [0u8; 32];
+        assert_eq!(meta.listxattr(&mut buf), 21);
+        assert_eq!(&buf[..21], b"security.x\0user.long\0");
+
+        meta.removexattr("user.long").unwrap();
+        assert_eq!(meta.removexattr("user.long"), Err(VfsError::NotFound));
+        assert_eq!(meta.listxattr(&mut buf), 11);
+    }
+}
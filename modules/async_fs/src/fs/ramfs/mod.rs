@@ -0,0 +1,79 @@
+//! 基于内存的文件系统，文件内容与目录结构都保存在内存中。
+//!
+//! 用作 tmpfs，以及 procfs 等伪文件系统的基础。
+
+mod dir;
+mod file;
+pub(crate) mod meta;
+mod sparse;
+mod special;
+mod symlink;
+
+pub use self::dir::DirNode;
+pub use self::file::FileNode;
+pub use self::special::SpecialNode;
+pub use self::symlink::SymlinkNode;
+
+use alloc::sync::Arc;
+use async_vfs::{FileSystemInfo, VfsNodeOps, VfsNodeRef, VfsOps, VfsResult};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// tmpfs 的 magic number
+pub const TMPFS_MAGIC: u64 = 0x0102_1994;
+
+/// A RAM filesystem that implements [`async_vfs::VfsOps`].
+pub struct RamFileSystem {
+    root: Arc<DirNode>,
+    /// statfs 返回的文件系统类型
+    magic: u64,
+}
+
+impl RamFileSystem {
+    /// Create a new instance.
+    pub fn new() -> Self {
+        Self::with_magic(TMPFS_MAGIC)
+    }
+
+    /// Create a new instance that reports `magic` as its type in statfs,
+    /// used by pseudo filesystems built on ramfs.
+    pub fn with_magic(magic: u64) -> Self {
+        Self {
+            root: DirNode::new(None),
+            magic,
+        }
+    }
+
+    /// Returns the root directory node in [`Arc<DirNode>`](DirNode).
+    pub fn root_dir_node(&self) -> Arc<DirNode> {
+        self.root.clone()
+    }
+}
+
+impl VfsOps for RamFileSystem {
+    fn mount(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        _path: &str,
+        mount_point: VfsNodeRef,
+    ) -> Poll<VfsResult> {
+        let parent = futures_core::ready!(VfsNodeOps::parent(Pin::new(&mount_point), cx));
+        self.root.set_parent(parent.as_ref());
+        Poll::Ready(Ok(()))
+    }
+
+    fn root_dir(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsNodeRef> {
+        Poll::Ready(self.root.clone())
+    }
+
+    fn statfs(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<FileSystemInfo>> {
+        // 与 Linux 的 ramfs 一致，内存不设上限，不报告块数
+        Poll::Ready(Ok(FileSystemInfo::new(self.magic, 4096, 255)))
+    }
+}
+
+impl Default for RamFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,220 @@
+//! 内存文件的稀疏内容，只为写入过或预分配的页分配内存，其余部分是读出全零的空洞
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+
+/// 内容按页分配，每页的大小
+pub(super) const PAGE_SIZE: usize = 4096;
+
+type Page = Box<[u8; PAGE_SIZE]>;
+
+fn zeroed_page() -> Page {
+    Box::new([0; PAGE_SIZE])
+}
+
+/// 以页号为键保存已分配的页，文件的大小单独记录
+pub(super) struct SparseContent {
+    pages: BTreeMap<u64, Page>,
+    size: u64,
+}
+
+impl SparseContent {
+    pub(super) const fn new() -> Self {
+        Self {
+            pages: BTreeMap::new(),
+            size: 0,
+        }
+    }
+
+    /// 文件的大小
+    pub(super) fn len(&self) -> u64 {
+        self.size
+    }
+
+    /// 已分配的页所占的字节数
+    pub(super) fn allocated(&self) -> u64 {
+        (self.pages.len() * PAGE_SIZE) as u64
+    }
+
+    /// 用 `data` 替换全部内容
+    pub(super) fn set(&mut self, data: &[u8]) {
+        self.pages.clear();
+        self.size = 0;
+        self.write_at(0, data);
+    }
+
+    pub(super) fn read_at(&self, offset: u64, buf: &mut [u8]) -> usize {
+        if offset >= self.size {
+            return 0;
+        }
+        let len = buf.len().min((self.size - offset) as usize);
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done as u64;
+            let in_page = (pos % PAGE_SIZE as u64) as usize;
+            let n = (PAGE_SIZE - in_page).min(len - done);
+            let dst = &mut buf[done..done + n];
+            match self.pages.get(&(pos / PAGE_SIZE as u64)) {
+                Some(page) => dst.copy_from_slice(&page[in_page..in_page + n]),
+                None => dst.fill(0),
+            }
+            done += n;
+        }
+        len
+    }
+
+    /// 写入数据，超出文件末尾时扩展文件，中间跳过的部分成为空洞
+    pub(super) fn write_at(&mut self, offset: u64, buf: &[u8]) {
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let in_page = (pos % PAGE_SIZE as u64) as usize;
+            let n = (PAGE_SIZE - in_page).min(buf.len() - done);
+            let page = self
+                .pages
+                .entry(pos / PAGE_SIZE as u64)
+                .or_insert_with(zeroed_page);
+            page[in_page..in_page + n].copy_from_slice(&buf[done..done + n]);
+            done += n;
+        }
+        self.size = self.size.max(offset + buf.len() as u64);
+    }
+
+    /// 截断或扩展文件，扩展出的部分是空洞
+    pub(super) fn truncate(&mut self, size: u64) {
+        if size < self.size {
+            // 清零最后一页中新的文件末尾之后的部分，之后再扩展时才能读出零
+            self.zero(size, self.size - size);
+            self.pages.split_off(&size.div_ceil(PAGE_SIZE as u64));
+        }
+        self.size = size;
+    }
+
+    /// 为 `[offset, offset + len)` 分配页，`keep_size` 为假时按需扩展文件
+    pub(super) fn allocate(&mut self, offset: u64, len: u64, keep_size: bool) {
+        if len == 0 {
+            return;
+        }
+        let first = offset / PAGE_SIZE as u64;
+        let last = (offset + len - 1) / PAGE_SIZE as u64;
+        for index in first..=last {
+            self.pages.entry(index).or_insert_with(zeroed_page);
+        }
+        if !keep_size {
+            self.size = self.size.max(offset + len);
+        }
+    }
+
+    /// 释放 `[offset, offset + len)` 中完整的页，其余部分清零，文件大小不变
+    pub(super) fn punch_hole(&mut self, offset: u64, len: u64) {
+        self.zero(offset, len);
+    }
+
+    fn zero(&mut self, offset: u64, len: u64) {
+        let end = offset.saturating_add(len);
+        let first_full = offset.div_ceil(PAGE_SIZE as u64);
+        let end_full = end / PAGE_SIZE as u64;
+        if first_full < end_full {
+            let dropped: alloc::vec::Vec<u64> = self
+                .pages
+                .range(first_full..end_full)
+                .map(|(&index, _)| index)
+                .collect();
+            for index in dropped {
+                self.pages.remove(&index);
+            }
+        }
+        // 首尾不完整的页只清零相应的部分
+        for (start, stop) in [
+            (offset, end.min(first_full * PAGE_SIZE as u64)),
+            ((end_full * PAGE_SIZE as u64).max(offset), end),
+        ] {
+            if start >= stop {
+                continue;
+            }
+            if let Some(page) = self.pages.get_mut(&(start / PAGE_SIZE as u64)) {
+                let from = (start % PAGE_SIZE as u64) as usize;
+                page[from..from + (stop - start) as usize].fill(0);
+            }
+        }
+    }
+
+    /// `offset` 处或之后下一段数据（`hole` 为真时为空洞）的起始位置
+    ///
+    /// 文件末尾之后总是一个隐式的空洞，`offset` 超出文件末尾或之后没有数据时返回 None
+    pub(super) fn seek_data(&self, offset: u64, hole: bool) -> Option<u64> {
+        if offset >= self.size {
+            return None;
+        }
+        let page = PAGE_SIZE as u64;
+        let start = offset / page;
+        let found = if hole {
+            let mut index = start;
+            while self.pages.contains_key(&index) {
+                index += 1;
+            }
+            Some((index * page).max(offset))
+        } else {
+            self.pages
+                .range(start..)
+                .next()
+                .map(|(&index, _)| (index * page).max(offset))
+        };
+        match found {
+            Some(pos) if pos < self.size => Some(pos),
+            // 空洞落在文件末尾之后时，返回文件末尾的隐式空洞
+            _ if hole => Some(self.size),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holes_read_as_zero() {
+        let mut content = SparseContent::new();
+        content.write_at(3 * PAGE_SIZE as u64, b"tail");
+        assert_eq!(content.len(), 3 * PAGE_SIZE as u64 + 4);
+        assert_eq!(content.allocated(), PAGE_SIZE as u64);
+        let mut buf = [0xffu8; 8];
+        assert_eq!(content.read_at(PAGE_SIZE as u64, &mut buf), 8);
+        assert_eq!(buf, [0; 8]);
+        assert_eq!(content.seek_data(0, false), Some(3 * PAGE_SIZE as u64));
+        assert_eq!(content.seek_data(0, true), Some(0));
+        assert_eq!(content.seek_data(3 * PAGE_SIZE as u64, true), Some(content.len()));
+    }
+
+    #[test]
+    fn punch_hole_keeps_size() {
+        let mut content = SparseContent::new();
+        content.set(&[1u8; 3 * PAGE_SIZE]);
+        content.punch_hole(100, 2 * PAGE_SIZE as u64);
+        assert_eq!(content.len(), 3 * PAGE_SIZE as u64);
+        // 只有中间的一页被完整地覆盖
+        assert_eq!(content.allocated(), 2 * PAGE_SIZE as u64);
+        let mut buf = [0u8; 2];
+        content.read_at(99, &mut buf);
+        assert_eq!(buf, [1, 0]);
+        content.read_at(2 * PAGE_SIZE as u64 + 99, &mut buf);
+        assert_eq!(buf, [0, 1]);
+        assert_eq!(content.seek_data(PAGE_SIZE as u64, true), Some(PAGE_SIZE as u64));
+        assert_eq!(content.seek_data(PAGE_SIZE as u64, false), Some(2 * PAGE_SIZE as u64));
+    }
+
+    #[test]
+    fn truncate_then_extend_reads_zero() {
+        let mut content = SparseContent::new();
+        content.set(b"hello world");
+        content.truncate(5);
+        content.truncate(11);
+        let mut buf = [0xffu8; 11];
+        content.read_at(0, &mut buf);
+        assert_eq!(&buf, b"hello\0\0\0\0\0\0");
+        content.allocate(0, 2 * PAGE_SIZE as u64, true);
+        assert_eq!(content.len(), 11);
+        assert_eq!(content.allocated(), 2 * PAGE_SIZE as u64);
+    }
+}
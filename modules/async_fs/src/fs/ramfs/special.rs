@@ -0,0 +1,42 @@
+use async_vfs::{impl_vfs_non_dir_default, VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm};
+use async_vfs::{VfsNodeType, VfsResult, VfsSetAttr};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use spinlock::SpinNoIrq;
+
+use super::meta::{impl_meta_xattr, NodeMeta};
+
+/// The FIFO or socket node in the RAM filesystem.
+///
+/// It implements [`async_vfs::VfsNodeOps`]. The node only keeps the
+/// metadata, the data is kept by the kernel object that opens it.
+pub struct SpecialNode {
+    ty: VfsNodeType,
+    meta: SpinNoIrq<NodeMeta>,
+}
+
+impl SpecialNode {
+    /// Create a new node of type `ty`.
+    pub fn new(ty: VfsNodeType) -> Self {
+        Self {
+            ty,
+            meta: SpinNoIrq::new(NodeMeta::new(VfsNodePerm::default_file())),
+        }
+    }
+}
+
+impl VfsNodeOps for SpecialNode {
+    impl_vfs_non_dir_default! {}
+    impl_meta_xattr! {}
+
+    fn get_attr(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<VfsNodeAttr>> {
+        let mut attr = VfsNodeAttr::new(VfsNodePerm::default_file(), self.ty, 0, 0);
+        self.meta.lock().fill(&mut attr);
+        Poll::Ready(Ok(attr))
+    }
+
+    fn setattr(self: Pin<&Self>, _cx: &mut Context<'_>, attr: &VfsSetAttr) -> Poll<VfsResult> {
+        self.meta.lock().setattr(attr);
+        Poll::Ready(Ok(()))
+    }
+}
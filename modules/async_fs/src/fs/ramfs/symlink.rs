@@ -0,0 +1,55 @@
+use alloc::string::String;
+use async_vfs::{impl_vfs_non_dir_default, VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm};
+use async_vfs::{VfsNodeType, VfsResult, VfsSetAttr};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use spinlock::SpinNoIrq;
+
+use super::meta::{impl_meta_xattr, NodeMeta};
+
+/// The symbolic link node in the RAM filesystem.
+///
+/// It implements [`async_vfs::VfsNodeOps`].
+pub struct SymlinkNode {
+    target: String,
+    meta: SpinNoIrq<NodeMeta>,
+}
+
+impl SymlinkNode {
+    /// Create a new symbolic link pointing to `target`.
+    pub fn new(target: &str) -> Self {
+        Self {
+            target: target.into(),
+            meta: SpinNoIrq::new(NodeMeta::new(VfsNodePerm::from_bits_truncate(0o777))),
+        }
+    }
+}
+
+impl VfsNodeOps for SymlinkNode {
+    impl_vfs_non_dir_default! {}
+    impl_meta_xattr! {}
+
+    fn get_attr(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<VfsNodeAttr>> {
+        let mut attr = VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o777),
+            VfsNodeType::SymLink,
+            self.target.len() as _,
+            0,
+        );
+        self.meta.lock().fill(&mut attr);
+        Poll::Ready(Ok(attr))
+    }
+
+    fn setattr(self: Pin<&Self>, _cx: &mut Context<'_>, attr: &VfsSetAttr) -> Poll<VfsResult> {
+        // 符号链接的权限总是 0777，只有所有者与时间戳可以修改
+        let attr = VfsSetAttr { mode: None, ..*attr };
+        self.meta.lock().setattr(&attr);
+        Poll::Ready(Ok(()))
+    }
+
+    fn readlink(self: Pin<&Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<VfsResult<usize>> {
+        let len = buf.len().min(self.target.len());
+        buf[..len].copy_from_slice(&self.target.as_bytes()[..len]);
+        Poll::Ready(Ok(len))
+    }
+}
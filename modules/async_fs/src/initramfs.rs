@@ -0,0 +1,276 @@
+//! initramfs：将 cpio（newc 格式）归档解包到作为根文件系统的 ramfs 中
+//!
+//! 归档可以由多个 cpio 归档首尾相接组成，之间允许有用于对齐的零字节，与 Linux 的处理方式一致。
+//! 同一 inode 的多个项是硬链接，文件内容保存在其中有数据的那一项中。
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use async_vfs::walk::LookupFlags;
+use async_vfs::{AsyncVfsNodeOps, VfsNodePerm, VfsSetAttr};
+use axerrno::{ax_err, AxError, AxResult};
+use core::time::Duration;
+
+use crate::root;
+
+/// newc 格式的魔数，`070702` 额外带有校验和
+const MAGIC_NEWC: &[u8] = b"070701";
+const MAGIC_CRC: &[u8] = b"070702";
+/// 文件头的长度：6 字节魔数与 13 个 8 位十六进制数
+const HEADER_LEN: usize = 110;
+/// 归档结束的标记
+const TRAILER: &str = "TRAILER!!!";
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+/// 归档中的一项
+#[derive(Debug)]
+struct Entry<'a> {
+    ino: u32,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    nlink: u32,
+    mtime: u32,
+    dev: (u32, u32),
+    name: &'a str,
+    data: &'a [u8],
+}
+
+fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+fn parse_hex(field: &[u8]) -> AxResult<u32> {
+    let s = core::str::from_utf8(field).map_err(|_| AxError::InvalidData)?;
+    u32::from_str_radix(s, 16).map_err(|_| AxError::InvalidData)
+}
+
+/// 依次解析归档中的项，直到所有归档都结束
+struct Entries<'a> {
+    archive: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Entries<'a> {
+    fn new(archive: &'a [u8]) -> Self {
+        Self { archive, pos: 0 }
+    }
+
+    fn parse(&mut self) -> AxResult<Option<Entry<'a>>> {
+        loop {
+            // 跳过归档之间的填充
+            while self.archive.get(self.pos) == Some(&0) {
+                self.pos += 1;
+            }
+            // 最后一项之后可能没有对齐用的填充，此时 pos 会越过归档的末尾
+            let rest = match self.archive.get(self.pos..) {
+                Some(rest) if !rest.is_empty() => rest,
+                _ => return Ok(None),
+            };
+            if rest.len() < HEADER_LEN || !(rest.starts_with(MAGIC_NEWC) || rest.starts_with(MAGIC_CRC)) {
+                return ax_err!(InvalidData, "bad cpio header");
+            }
+            let field = |i: usize| parse_hex(&rest[6 + i * 8..6 + (i + 1) * 8]);
+            let filesize = field(6)? as usize;
+            let namesize = field(11)? as usize;
+            let name_end = HEADER_LEN + namesize;
+            let data_start = align4(name_end);
+            let data_end = data_start + filesize;
+            if namesize == 0 || data_end > rest.len() {
+                return ax_err!(InvalidData, "truncated cpio entry");
+            }
+            // 文件名以 '\0' 结尾
+            let name = core::str::from_utf8(&rest[HEADER_LEN..name_end - 1])
+                .map_err(|_| AxError::InvalidData)?;
+            let entry = Entry {
+                ino: field(0)?,
+                mode: field(1)?,
+                uid: field(2)?,
+                gid: field(3)?,
+                nlink: field(4)?,
+                mtime: field(5)?,
+                dev: (field(7)?, field(8)?),
+                name,
+                data: &rest[data_start..data_end],
+            };
+            self.pos += align4(data_end);
+            if entry.name != TRAILER {
+                return Ok(Some(entry));
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = AxResult<Entry<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.parse();
+        if entry.is_err() {
+            // 出错后不再继续解析
+            self.pos = self.archive.len();
+        }
+        entry.transpose()
+    }
+}
+
+/// 将归档中的名字转换为绝对路径，归档的根目录返回 `None`
+fn entry_path(name: &str) -> Option<String> {
+    let name = name.trim_start_matches("./").trim_matches('/');
+    if name.is_empty() || name == "." {
+        None
+    } else {
+        Some(String::from("/") + name)
+    }
+}
+
+/// 将 cpio 归档解包到根目录中，已经存在的文件会被覆盖
+pub(crate) async fn unpack(archive: &[u8]) -> AxResult {
+    // 已解包的硬链接组，键为所在设备与 inode 号
+    let mut links: BTreeMap<((u32, u32), u32), String> = BTreeMap::new();
+    let mut count = 0;
+    for entry in Entries::new(archive) {
+        let entry = entry?;
+        let Some(path) = entry_path(entry.name) else {
+            continue;
+        };
+        let res = match entry.mode & S_IFMT {
+            S_IFDIR => match root::create_dir(None, &path).await {
+                Ok(()) | Err(AxError::AlreadyExists) => Ok(()),
+                Err(err) => Err(err),
+            },
+            S_IFREG => unpack_file(&entry, &path, &mut links).await,
+            S_IFLNK => {
+                let target = core::str::from_utf8(entry.data).map_err(|_| AxError::InvalidData)?;
+                let _ = root::remove_file(None, &path).await;
+                root::symlink(target, &path).await
+            }
+            _ => {
+                warn!("initramfs: skip {} with unsupported mode {:#o}", path, entry.mode);
+                continue;
+            }
+        };
+        if let Err(err) = res {
+            warn!("initramfs: failed to unpack {}: {:?}", path, err);
+            continue;
+        }
+        if let Ok(node) = root::lookup_with(None, &path, LookupFlags::NOFOLLOW).await {
+            let mtime = Duration::from_secs(entry.mtime as u64);
+            let attr = VfsSetAttr {
+                mode: Some(VfsNodePerm::from_bits_truncate((entry.mode & 0o7777) as u16)),
+                uid: Some(entry.uid),
+                gid: Some(entry.gid),
+                atime: Some(mtime),
+                mtime: Some(mtime),
+            };
+            let _ = node.setattr(&attr).await;
+        }
+        count += 1;
+    }
+    info!("initramfs: unpacked {} entries", count);
+    Ok(())
+}
+
+async fn unpack_file(
+    entry: &Entry<'_>,
+    path: &str,
+    links: &mut BTreeMap<((u32, u32), u32), String>,
+) -> AxResult {
+    if entry.nlink > 1 {
+        if let Some(first) = links.get(&(entry.dev, entry.ino)) {
+            let _ = root::remove_file(None, path).await;
+            root::hard_link(first, path).await?;
+            // 硬链接组的内容通常在最后一项中
+            if !entry.data.is_empty() {
+                write_all(path, entry.data).await?;
+            }
+            return Ok(());
+        }
+        links.insert((entry.dev, entry.ino), path.into());
+    }
+    write_all(path, entry.data).await
+}
+
+/// 创建或截断文件 `path` 并写入 `data`
+async fn write_all(path: &str, data: &[u8]) -> AxResult {
+    let node = match root::create_file(None, path).await {
+        Ok(node) => node,
+        Err(AxError::AlreadyExists) => root::lookup(None, path).await?,
+        Err(err) => return Err(err),
+    };
+    node.truncate(0).await?;
+    let mut written = 0;
+    while written < data.len() {
+        let n = node.write_at(written as u64, &data[written..]).await?;
+        if n == 0 {
+            return ax_err!(StorageFull);
+        }
+        written += n;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+    use alloc::vec::Vec;
+
+    fn push_entry(archive: &mut Vec<u8>, ino: u32, mode: u32, nlink: u32, name: &str, data: &[u8]) {
+        let header = format!(
+            "070701{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}",
+            ino, mode, 0, 0, nlink, 0, data.len(), 0, 0, 0, 0, name.len() + 1, 0
+        );
+        archive.extend_from_slice(header.as_bytes());
+        archive.extend_from_slice(name.as_bytes());
+        archive.push(0);
+        archive.resize(align4(archive.len()), 0);
+        archive.extend_from_slice(data);
+        archive.resize(align4(archive.len()), 0);
+    }
+
+    #[test]
+    fn parse_concatenated_archives() {
+        let mut archive = Vec::new();
+        push_entry(&mut archive, 1, S_IFDIR | 0o755, 2, ".", b"");
+        push_entry(&mut archive, 2, S_IFREG | 0o755, 1, "./init", b"#!/bin/sh\n");
+        push_entry(&mut archive, 0, 0, 1, TRAILER, b"");
+        // 第二个归档前有对齐用的零字节
+        archive.resize(archive.len() + 512, 0);
+        push_entry(&mut archive, 3, S_IFLNK | 0o777, 1, "bin/sh", b"busybox");
+        push_entry(&mut archive, 0, 0, 1, TRAILER, b"");
+
+        let entries: Vec<_> = Entries::new(&archive).collect::<AxResult<_>>().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1].name, "./init");
+        assert_eq!(entries[1].data, b"#!/bin/sh\n");
+        assert_eq!(entries[2].mode & S_IFMT, S_IFLNK);
+        assert_eq!(entries[2].data, b"busybox");
+        assert_eq!(entry_path(entries[0].name), None);
+        assert_eq!(entry_path(entries[1].name).as_deref(), Some("/init"));
+    }
+
+    #[test]
+    fn reject_truncated_archive() {
+        let mut archive = Vec::new();
+        push_entry(&mut archive, 2, S_IFREG | 0o644, 1, "file", b"0123456789");
+        archive.truncate(archive.len() - 8);
+        let mut entries = Entries::new(&archive);
+        assert_eq!(entries.next().unwrap().unwrap_err(), AxError::InvalidData);
+        assert!(entries.next().is_none());
+    }
+
+    #[test]
+    fn parse_unpadded_last_entry() {
+        let mut archive = Vec::new();
+        push_entry(&mut archive, 2, S_IFREG | 0o644, 1, "a", b"x");
+        // 去掉数据之后的填充，数据结束位置不是 4 的倍数
+        archive.truncate(archive.len() - 3);
+        let mut entries = Entries::new(&archive);
+        assert_eq!(entries.next().unwrap().unwrap().data, b"x");
+        assert!(entries.next().is_none());
+    }
+}
@@ -2,6 +2,14 @@
 //! 
 //! dev.rs 中定义了块设备的驱动实现，包括设备大小、寻址位置、读写单个扇区、从指定扇区进行读写等操作
 //! 
+//! cache.rs 中定义了块设备缓存，dev.rs 中的读写都经过缓存，支持 LRU 淘汰、脏块回写与顺序预读
+//! 
+//! lock.rs 中定义了以 inode 为键的建议性文件锁，包括 flock 与 POSIX 记录锁（含 OFD 锁）
+//! 
+//! notify.rs 中定义了 inotify 文件变化通知，文件的创建、删除、重命名与读写会向监视者投递事件
+//! 
+//! initramfs.rs 中定义了 cpio（newc 格式）归档的解包，用于从 initramfs 启动
+//! 
 //! root.rs 中定义了文件系统根目录的实现，包括根目录的初始化、根目录的操作等。
 //! 
 //! fops.rs 中定义了 File、Directory、OpenOptions 等结构。
@@ -18,7 +26,12 @@ extern crate alloc;
 
 mod fs;
 mod dev;
+mod cache;
+mod lock;
+mod notify;
 mod root;
+#[cfg(feature = "initramfs")]
+mod initramfs;
 #[allow(unused)]
 mod mounts;
 
@@ -28,6 +41,23 @@ pub use fs::BLOCK_SIZE;
 
 
 use axdriver::{prelude::*, AxDeviceContainer};
+use core::time::Duration;
+use spinlock::SpinNoIrq;
+
+static CLOCK: SpinNoIrq<Option<fn() -> Duration>> = SpinNoIrq::new(None);
+
+/// 设置文件系统读取当前时间的方式，用于更新文件的时间戳
+///
+/// 未设置时所有时间戳均为 0
+pub fn set_clock(clock: fn() -> Duration) {
+    *CLOCK.lock() = Some(clock);
+}
+
+/// 当前时间，用作文件的时间戳
+pub(crate) fn now() -> Duration {
+    let clock = *CLOCK.lock();
+    clock.map_or(Duration::ZERO, |clock| clock())
+}
 
 /// Initializes filesystems by block devices.
 pub async fn init_filesystems(mut blk_devs: AxDeviceContainer<AxBlockDevice>) {
@@ -36,4 +66,10 @@ pub async fn init_filesystems(mut blk_devs: AxDeviceContainer<AxBlockDevice>) {
     let dev = blk_devs.take_one().expect("No block device found!");
     info!("  use block device 0: {:?}", dev.device_name());
     self::root::init_rootfs(self::dev::Disk::new(dev)).await;
-}
\ No newline at end of file
+}
+/// 以 ramfs 为根文件系统，并将 cpio（newc 格式）的 initramfs 归档解包到其中
+#[cfg(feature = "initramfs")]
+pub async fn init_initramfs(archive: &[u8]) -> axerrno::AxResult {
+    info!("Initialize filesystems from initramfs ({} bytes)...", archive.len());
+    self::root::init_initramfs(archive).await
+}
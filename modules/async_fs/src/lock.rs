@@ -0,0 +1,446 @@
+//! 建议性文件锁
+//!
+//! 支持两类互不影响的锁：
+//! - flock：整个文件上的共享锁或互斥锁，属于打开文件描述；
+//! - 记录锁：字节范围上的读锁或写锁，POSIX 记录锁属于进程，OFD 锁属于打开文件描述，二者之间会相互冲突。
+//!
+//! 所有的锁以 inode 号为键保存在全局的锁表中。无法立即加锁时，请求在持有锁表时注册到 [`WaitQueue`] 上等待，
+//! 任何锁发生变化时唤醒所有等待者重新尝试。POSIX 记录锁在等待前会进行死锁检测。
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::future::poll_fn;
+use core::task::Poll;
+use spinlock::SpinNoIrq;
+use sync::WaitQueue;
+
+/// 锁的持有者
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LockOwner {
+    /// POSIX 记录锁的持有者，即进程号
+    Process(u64),
+    /// flock 锁与 OFD 锁的持有者，即打开文件描述的地址
+    File(usize),
+}
+
+/// 锁的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockKind {
+    /// 共享锁（读锁）
+    Shared,
+    /// 互斥锁（写锁）
+    Exclusive,
+}
+
+/// 加锁失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockError {
+    /// 锁被其他持有者占用，且请求不等待
+    WouldBlock,
+    /// 等待该锁会造成死锁
+    Deadlock,
+}
+
+/// 字节范围 `[start, end)` 上的记录锁，`end` 为 `u64::MAX` 表示一直到文件末尾
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeLock {
+    /// 锁的持有者
+    pub owner: LockOwner,
+    /// 锁的类型
+    pub kind: LockKind,
+    /// 起始位置
+    pub start: u64,
+    /// 结束位置（不含）
+    pub end: u64,
+}
+
+impl RangeLock {
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start < end && start < self.end
+    }
+
+    fn conflicts(&self, owner: LockOwner, kind: LockKind, start: u64, end: u64) -> bool {
+        self.owner != owner
+            && self.overlaps(start, end)
+            && (self.kind == LockKind::Exclusive || kind == LockKind::Exclusive)
+    }
+}
+
+/// 一个 inode 上的所有锁
+#[derive(Debug, Default)]
+struct InodeLocks {
+    flocks: Vec<(usize, LockKind)>,
+    ranges: Vec<RangeLock>,
+}
+
+impl InodeLocks {
+    fn is_empty(&self) -> bool {
+        self.flocks.is_empty() && self.ranges.is_empty()
+    }
+
+    fn flock_conflicts(&self, owner: usize, kind: LockKind) -> bool {
+        self.flocks.iter().any(|&(other, other_kind)| {
+            other != owner && (other_kind == LockKind::Exclusive || kind == LockKind::Exclusive)
+        })
+    }
+
+    fn set_flock(&mut self, owner: usize, kind: Option<LockKind>) {
+        self.flocks.retain(|&(other, _)| other != owner);
+        if let Some(kind) = kind {
+            self.flocks.push((owner, kind));
+        }
+    }
+
+    fn range_conflict(
+        &self,
+        owner: LockOwner,
+        kind: LockKind,
+        start: u64,
+        end: u64,
+    ) -> Option<RangeLock> {
+        self.ranges
+            .iter()
+            .find(|lock| lock.conflicts(owner, kind, start, end))
+            .copied()
+    }
+
+    /// 将 `owner` 在 `[start, end)` 上的锁替换为 `kind`，`kind` 为 `None` 时解锁
+    ///
+    /// 原有的锁与该范围部分重叠时会被拆分，相邻的同类锁会被合并
+    fn set_range(&mut self, owner: LockOwner, kind: Option<LockKind>, start: u64, end: u64) {
+        let mut ranges = Vec::with_capacity(self.ranges.len() + 2);
+        for lock in self.ranges.drain(..) {
+            if lock.owner != owner || !lock.overlaps(start, end) {
+                ranges.push(lock);
+                continue;
+            }
+            if lock.start < start {
+                ranges.push(RangeLock { end: start, ..lock });
+            }
+            if lock.end > end {
+                ranges.push(RangeLock { start: end, ..lock });
+            }
+        }
+        if let Some(kind) = kind {
+            let (mut start, mut end) = (start, end);
+            ranges.retain(|lock| {
+                if lock.owner == owner && lock.kind == kind && lock.start <= end && start <= lock.end
+                {
+                    start = start.min(lock.start);
+                    end = end.max(lock.end);
+                    false
+                } else {
+                    true
+                }
+            });
+            ranges.push(RangeLock {
+                owner,
+                kind,
+                start,
+                end,
+            });
+        }
+        self.ranges = ranges;
+    }
+}
+
+/// 全局的锁表
+struct LockTable {
+    inodes: BTreeMap<u64, InodeLocks>,
+    /// 正在等待记录锁的进程及其请求，用于死锁检测
+    waiting: BTreeMap<u64, (u64, LockKind, u64, u64)>,
+}
+
+impl LockTable {
+    const fn new() -> Self {
+        Self {
+            inodes: BTreeMap::new(),
+            waiting: BTreeMap::new(),
+        }
+    }
+
+    fn remove_if_empty(&mut self, ino: u64) {
+        if self.inodes.get(&ino).is_some_and(InodeLocks::is_empty) {
+            self.inodes.remove(&ino);
+        }
+    }
+
+    /// 阻塞该请求的所有进程
+    fn blockers(&self, ino: u64, owner: LockOwner, kind: LockKind, start: u64, end: u64) -> Vec<u64> {
+        let Some(locks) = self.inodes.get(&ino) else {
+            return Vec::new();
+        };
+        locks
+            .ranges
+            .iter()
+            .filter(|lock| lock.conflicts(owner, kind, start, end))
+            .filter_map(|lock| match lock.owner {
+                LockOwner::Process(pid) => Some(pid),
+                LockOwner::File(_) => None,
+            })
+            .collect()
+    }
+
+    /// 进程 `pid` 等待该请求是否会造成死锁
+    ///
+    /// 沿着等待关系查找，若最终等待的是 `pid` 自己，则形成了环
+    fn would_deadlock(&self, pid: u64, ino: u64, kind: LockKind, start: u64, end: u64) -> bool {
+        let mut stack = self.blockers(ino, LockOwner::Process(pid), kind, start, end);
+        let mut visited = Vec::new();
+        while let Some(blocker) = stack.pop() {
+            if blocker == pid {
+                return true;
+            }
+            if visited.contains(&blocker) {
+                continue;
+            }
+            visited.push(blocker);
+            if let Some(&(ino, kind, start, end)) = self.waiting.get(&blocker) {
+                stack.extend(self.blockers(ino, LockOwner::Process(blocker), kind, start, end));
+            }
+        }
+        false
+    }
+}
+
+static LOCKS: SpinNoIrq<LockTable> = SpinNoIrq::new(LockTable::new());
+
+/// 等待加锁的任务
+static LOCK_WAITERS: WaitQueue = WaitQueue::new();
+
+/// 对 inode `ino` 加 flock 锁，`kind` 为 `None` 时解锁
+///
+/// `wait` 为假且锁被占用时返回 [`LockError::WouldBlock`]，否则等待直到加锁成功
+pub async fn flock(
+    ino: u64,
+    owner: usize,
+    kind: Option<LockKind>,
+    wait: bool,
+) -> Result<(), LockError> {
+    poll_fn(|cx| {
+        let mut table = LOCKS.lock();
+        let locks = table.inodes.entry(ino).or_default();
+        if kind.map_or(true, |kind| !locks.flock_conflicts(owner, kind)) {
+            locks.set_flock(owner, kind);
+            table.remove_if_empty(ino);
+            drop(table);
+            LOCK_WAITERS.notify_all();
+            return Poll::Ready(Ok(()));
+        }
+        table.remove_if_empty(ino);
+        if !wait {
+            return Poll::Ready(Err(LockError::WouldBlock));
+        }
+        // 在持有锁表时注册，释放锁的一方修改锁表后才唤醒，因此不会丢失唤醒
+        let _ = LOCK_WAITERS.wait_until(cx, || false);
+        Poll::Pending
+    })
+    .await
+}
+
+/// 将 `owner` 在 inode `ino` 的 `[start, end)` 范围上的记录锁设置为 `kind`，`kind` 为 `None` 时解锁
+///
+/// `wait` 为假且与其他持有者的锁冲突时返回 [`LockError::WouldBlock`]；
+/// 进程等待会造成死锁时返回 [`LockError::Deadlock`]
+pub async fn set_range_lock(
+    ino: u64,
+    owner: LockOwner,
+    kind: Option<LockKind>,
+    start: u64,
+    end: u64,
+    wait: bool,
+) -> Result<(), LockError> {
+    let pid = match owner {
+        LockOwner::Process(pid) => Some(pid),
+        LockOwner::File(_) => None,
+    };
+    let _guard = WaitingGuard(pid);
+    poll_fn(|cx| {
+        let mut table = LOCKS.lock();
+        let conflict = match (kind, table.inodes.get(&ino)) {
+            (Some(kind), Some(locks)) => locks.range_conflict(owner, kind, start, end),
+            _ => None,
+        };
+        match (kind, conflict) {
+            (_, None) => {
+                table
+                    .inodes
+                    .entry(ino)
+                    .or_default()
+                    .set_range(owner, kind, start, end);
+                table.remove_if_empty(ino);
+                if let Some(pid) = pid {
+                    table.waiting.remove(&pid);
+                }
+                drop(table);
+                LOCK_WAITERS.notify_all();
+                Poll::Ready(Ok(()))
+            }
+            (Some(kind), Some(_)) if wait => {
+                if let Some(pid) = pid {
+                    if table.would_deadlock(pid, ino, kind, start, end) {
+                        table.waiting.remove(&pid);
+                        return Poll::Ready(Err(LockError::Deadlock));
+                    }
+                    table.waiting.insert(pid, (ino, kind, start, end));
+                }
+                // 与 flock 相同，在持有锁表时注册
+                let _ = LOCK_WAITERS.wait_until(cx, || false);
+                Poll::Pending
+            }
+            _ => Poll::Ready(Err(LockError::WouldBlock)),
+        }
+    })
+    .await
+}
+
+/// 等待记录锁的请求结束时清除进程的等待关系
+///
+/// 等待中的请求被取消（future 被丢弃）时，留下的等待关系会让之后的死锁检测误报
+struct WaitingGuard(Option<u64>);
+
+impl Drop for WaitingGuard {
+    fn drop(&mut self) {
+        if let Some(pid) = self.0 {
+            LOCKS.lock().waiting.remove(&pid);
+        }
+    }
+}
+
+/// 查询与该请求冲突的第一个记录锁，没有冲突时返回 `None`
+pub fn test_range_lock(
+    ino: u64,
+    owner: LockOwner,
+    kind: LockKind,
+    start: u64,
+    end: u64,
+) -> Option<RangeLock> {
+    LOCKS
+        .lock()
+        .inodes
+        .get(&ino)
+        .and_then(|locks| locks.range_conflict(owner, kind, start, end))
+}
+
+/// 释放 `owner` 持有的所有锁
+///
+/// 用于进程退出，以及打开文件描述的最后一个引用被关闭时
+pub fn release_lock_owner(owner: LockOwner) {
+    let mut table = LOCKS.lock();
+    table.inodes.retain(|_, locks| {
+        locks.ranges.retain(|lock| lock.owner != owner);
+        if let LockOwner::File(file) = owner {
+            locks.flocks.retain(|&(other, _)| other != file);
+        }
+        !locks.is_empty()
+    });
+    if let LockOwner::Process(pid) = owner {
+        table.waiting.remove(&pid);
+    }
+    drop(table);
+    LOCK_WAITERS.notify_all();
+}
+
+/// 释放进程 `pid` 在 inode `ino` 上的所有 POSIX 记录锁
+///
+/// 进程关闭指向该 inode 的任意一个文件描述符时都会释放这些锁
+pub fn release_process_locks(pid: u64, ino: u64) {
+    let mut table = LOCKS.lock();
+    if let Some(locks) = table.inodes.get_mut(&ino) {
+        locks.set_range(LockOwner::Process(pid), None, 0, u64::MAX);
+    }
+    table.remove_if_empty(ino);
+    drop(table);
+    LOCK_WAITERS.notify_all();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::Context;
+    use test_utils::{poll_once, WakeCounter};
+
+    const P1: LockOwner = LockOwner::Process(1);
+    const P2: LockOwner = LockOwner::Process(2);
+
+    #[test]
+    fn split_and_merge_ranges() {
+        let mut locks = InodeLocks::default();
+        locks.set_range(P1, Some(LockKind::Exclusive), 0, 100);
+        // 解锁中间的一段，原有的锁被拆成两段
+        locks.set_range(P1, None, 40, 60);
+        assert_eq!(locks.ranges.len(), 2);
+        assert!(locks.range_conflict(P2, LockKind::Shared, 40, 60).is_none());
+        assert!(locks.range_conflict(P2, LockKind::Shared, 30, 50).is_some());
+        // 重新加锁后与两侧合并为一段
+        locks.set_range(P1, Some(LockKind::Exclusive), 40, 60);
+        assert_eq!(locks.ranges.len(), 1);
+        assert_eq!((locks.ranges[0].start, locks.ranges[0].end), (0, 100));
+        // 降级中间的一段不会与两侧合并
+        locks.set_range(P1, Some(LockKind::Shared), 40, 60);
+        assert_eq!(locks.ranges.len(), 3);
+        assert!(locks.range_conflict(P2, LockKind::Shared, 40, 60).is_none());
+        assert!(locks.range_conflict(P2, LockKind::Exclusive, 40, 60).is_some());
+    }
+
+    #[test]
+    fn flock_sharing() {
+        let mut locks = InodeLocks::default();
+        locks.set_flock(1, Some(LockKind::Shared));
+        assert!(!locks.flock_conflicts(2, LockKind::Shared));
+        assert!(locks.flock_conflicts(2, LockKind::Exclusive));
+        // 自己持有的锁不冲突，可以直接升级
+        assert!(!locks.flock_conflicts(1, LockKind::Exclusive));
+    }
+
+    #[test]
+    fn detect_deadlock() {
+        let mut table = LockTable::new();
+        let locks = table.inodes.entry(1).or_default();
+        locks.set_range(P1, Some(LockKind::Exclusive), 0, 10);
+        locks.set_range(P2, Some(LockKind::Exclusive), 10, 20);
+        // 进程 1 等待进程 2 持有的锁
+        assert!(!table.would_deadlock(1, 1, LockKind::Exclusive, 10, 20));
+        table.waiting.insert(1, (1, LockKind::Exclusive, 10, 20));
+        // 进程 2 再等待进程 1 持有的锁则形成环
+        assert!(table.would_deadlock(2, 1, LockKind::Exclusive, 0, 10));
+        // 没有被阻塞的请求不会死锁
+        assert!(!table.would_deadlock(2, 1, LockKind::Exclusive, 30, 40));
+    }
+
+    #[test]
+    fn cancelled_wait_is_forgotten() {
+        const INO: u64 = 0x2000;
+        const P11: LockOwner = LockOwner::Process(11);
+        const P12: LockOwner = LockOwner::Process(12);
+        let exclusive = Some(LockKind::Exclusive);
+        assert!(poll_once(set_range_lock(INO, P11, exclusive, 0, 10, false)).is_ready());
+        assert!(poll_once(set_range_lock(INO, P12, exclusive, 10, 20, false)).is_ready());
+        // 进程 11 开始等待后请求被取消
+        assert!(poll_once(set_range_lock(INO, P11, exclusive, 10, 20, true)).is_pending());
+        // 进程 12 等待进程 11 持有的锁不会被误报为死锁
+        assert!(poll_once(set_range_lock(INO, P12, exclusive, 0, 10, true)).is_pending());
+        release_lock_owner(P11);
+        release_lock_owner(P12);
+    }
+
+    #[test]
+    fn unlock_wakes_waiter() {
+        // 锁表是全局的，使用其他测试不会用到的 inode
+        const INO: u64 = 0x1000;
+        let counter = WakeCounter::new();
+        let waker = counter.waker();
+        let mut cx = Context::from_waker(&waker);
+        let exclusive = Some(LockKind::Exclusive);
+        assert!(poll_once(flock(INO, 1, exclusive, false)).is_ready());
+        let mut waiter = pin!(flock(INO, 2, exclusive, true));
+        assert!(waiter.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(counter.count(), 0);
+        // 解锁时唤醒已经注册的等待者，之后它可以加锁
+        assert!(poll_once(flock(INO, 1, None, false)).is_ready());
+        assert_eq!(counter.count(), 1);
+        assert_eq!(Poll::Ready(Ok(())), waiter.as_mut().poll(&mut cx));
+        assert!(poll_once(flock(INO, 2, None, false)).is_ready());
+    }
+}
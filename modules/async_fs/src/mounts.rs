@@ -38,40 +38,45 @@ pub(crate) fn ramfs() -> Arc<fs::ramfs::RamFileSystem> {
 
 #[cfg(feature = "procfs")]
 pub(crate) fn procfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
-    let procfs = fs::ramfs::RamFileSystem::new();
-    let proc_root = procfs.root_dir();
+    use fs::ramfs::{DirNode, FileNode};
+
+    fn add_file(dir: &DirNode, name: &str, content: &[u8]) -> VfsResult {
+        let file = FileNode::new();
+        file.set_content(content);
+        dir.add_node(name, Arc::new(file))
+    }
+
+    /// procfs 的 magic number
+    const PROC_SUPER_MAGIC: u64 = 0x9fa0;
+
+    let procfs = fs::ramfs::RamFileSystem::with_magic(PROC_SUPER_MAGIC);
+    let proc_root = procfs.root_dir_node();
 
     // Create /proc/sys/net/core/somaxconn
-    proc_root.create("sys", VfsNodeType::Dir)?;
-    proc_root.create("sys/net", VfsNodeType::Dir)?;
-    proc_root.create("sys/net/core", VfsNodeType::Dir)?;
-    proc_root.create("sys/net/core/somaxconn", VfsNodeType::File)?;
-    let file_somaxconn = proc_root.clone().lookup("./sys/net/core/somaxconn")?;
-    file_somaxconn.write_at(0, b"4096\n")?;
+    proc_root.create_node("sys", VfsNodeType::Dir)?;
+    proc_root.lookup_dir("sys")?.create_node("net", VfsNodeType::Dir)?;
+    proc_root.lookup_dir("sys/net")?.create_node("core", VfsNodeType::Dir)?;
+    add_file(&proc_root.lookup_dir("sys/net/core")?, "somaxconn", b"4096\n")?;
 
     // Create /proc/sys/vm/overcommit_memory
-    proc_root.create("sys/vm", VfsNodeType::Dir)?;
-    proc_root.create("sys/vm/overcommit_memory", VfsNodeType::File)?;
-    let file_over = proc_root.clone().lookup("./sys/vm/overcommit_memory")?;
-    file_over.write_at(0, b"0\n")?;
+    proc_root.lookup_dir("sys")?.create_node("vm", VfsNodeType::Dir)?;
+    add_file(&proc_root.lookup_dir("sys/vm")?, "overcommit_memory", b"0\n")?;
 
     // Create /proc/self/stat
-    proc_root.create("self", VfsNodeType::Dir)?;
-    proc_root.create("self/stat", VfsNodeType::File)?;
-    proc_root.create("self/exe", VfsNodeType::File)?;
-    proc_root.create("self/status", VfsNodeType::File)?;
+    proc_root.create_node("self", VfsNodeType::Dir)?;
+    let self_dir = proc_root.lookup_dir("self")?;
+    self_dir.create_node("stat", VfsNodeType::File)?;
+    self_dir.create_node("exe", VfsNodeType::File)?;
+    self_dir.create_node("status", VfsNodeType::File)?;
 
     // Create /proc/filesystems
-    proc_root.create("filesystems", VfsNodeType::File)?;
+    add_file(&proc_root, "filesystems", b"nodev\tproc\nnodev\ttmpfs\n\tvfat\n")?;
 
-    #[cfg(feature = "monolithic")]
-    {
-        // Create other file to pass the testcases
-        proc_root.create("meminfo", VfsNodeType::File)?;
-        proc_root.create("mounts", VfsNodeType::File)?;
-        proc_root.create("interrupts", VfsNodeType::File)?;
-        // procfs.mount("interrupts", Arc::new(fs::devfs::Interrupts::default()))?;
-    }
+    // /proc/mounts is generated from the mount tree on every read
+    proc_root.add_node("mounts", Arc::new(fs::procfs::ProcFile::new(crate::root::mounts_info)))?;
+
+    proc_root.create_node("meminfo", VfsNodeType::File)?;
+    proc_root.create_node("interrupts", VfsNodeType::File)?;
     Ok(Arc::new(procfs))
 }
 
@@ -0,0 +1,435 @@
+//! 文件变化通知（inotify）
+//!
+//! 每个 [`Inotify`] 实例以绝对路径为键保存若干监视项，并维护一个事件队列。
+//! 文件系统在创建、删除、重命名与读写文件时调用 [`notify`] 等函数，
+//! 事件被投递到所有监视了该路径本身或其所在目录的实例中，读者在实例的 [`WaitQueue`] 上等待新事件。
+//!
+//! 监视项以路径而不是 inode 标识：被监视的文件重命名后监视项随之移动，
+//! 但通过其他硬链接对文件的修改不会产生事件。
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::{String, ToString};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use axerrno::{ax_err, AxResult};
+use core::future::poll_fn;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::task::{Context, Poll, Waker};
+use spinlock::SpinNoIrq;
+use sync::WaitQueue;
+
+bitflags::bitflags! {
+    /// inotify 的事件类型与监视选项，取值与 Linux 相同
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct InotifyMask: u32 {
+        /// 文件被读取
+        const ACCESS = 0x1;
+        /// 文件被修改
+        const MODIFY = 0x2;
+        /// 元数据被修改
+        const ATTRIB = 0x4;
+        /// 以可写方式打开的文件被关闭
+        const CLOSE_WRITE = 0x8;
+        /// 以只读方式打开的文件被关闭
+        const CLOSE_NOWRITE = 0x10;
+        /// 文件被打开
+        const OPEN = 0x20;
+        /// 文件从被监视的目录中移出
+        const MOVED_FROM = 0x40;
+        /// 文件移入被监视的目录
+        const MOVED_TO = 0x80;
+        /// 在被监视的目录中创建了文件
+        const CREATE = 0x100;
+        /// 从被监视的目录中删除了文件
+        const DELETE = 0x200;
+        /// 被监视的文件本身被删除
+        const DELETE_SELF = 0x400;
+        /// 被监视的文件本身被移动
+        const MOVE_SELF = 0x800;
+        /// 事件队列溢出
+        const Q_OVERFLOW = 0x4000;
+        /// 监视项已被移除
+        const IGNORED = 0x8000;
+        /// 只监视目录
+        const ONLYDIR = 0x100_0000;
+        /// 不跟随符号链接
+        const DONT_FOLLOW = 0x200_0000;
+        /// 不为已删除的子文件产生事件
+        const EXCL_UNLINK = 0x400_0000;
+        /// 路径已被监视时返回错误
+        const MASK_CREATE = 0x1000_0000;
+        /// 与已有的监视项合并而不是替换
+        const MASK_ADD = 0x2000_0000;
+        /// 事件的对象是目录
+        const ISDIR = 0x4000_0000;
+        /// 只产生一次事件
+        const ONESHOT = 0x8000_0000;
+    }
+}
+
+impl InotifyMask {
+    /// 所有可以监视的事件
+    pub const ALL_EVENTS: Self = Self::from_bits_truncate(0xfff);
+    /// 可以在被监视的文件本身上产生的事件
+    const SELF_EVENTS: Self = Self::from_bits_truncate(
+        Self::ACCESS.bits()
+            | Self::MODIFY.bits()
+            | Self::ATTRIB.bits()
+            | Self::CLOSE_WRITE.bits()
+            | Self::CLOSE_NOWRITE.bits()
+            | Self::OPEN.bits()
+            | Self::DELETE_SELF.bits()
+            | Self::MOVE_SELF.bits(),
+    );
+    /// 可以在被监视的目录中的文件上产生的事件
+    const CHILD_EVENTS: Self = Self::from_bits_truncate(
+        Self::ACCESS.bits()
+            | Self::MODIFY.bits()
+            | Self::ATTRIB.bits()
+            | Self::CLOSE_WRITE.bits()
+            | Self::CLOSE_NOWRITE.bits()
+            | Self::OPEN.bits()
+            | Self::MOVED_FROM.bits()
+            | Self::MOVED_TO.bits()
+            | Self::CREATE.bits()
+            | Self::DELETE.bits(),
+    );
+}
+
+/// 一个文件变化事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotifyEvent {
+    /// 产生事件的监视项，队列溢出事件为 -1
+    pub wd: i32,
+    /// 事件类型
+    pub mask: InotifyMask,
+    /// 关联同一次重命名的 MOVED_FROM 与 MOVED_TO 事件
+    pub cookie: u32,
+    /// 事件发生在被监视目录中的文件时为文件名，否则为空
+    pub name: String,
+}
+
+/// 每个实例最多排队的事件数，超过后丢弃事件并产生一个 Q_OVERFLOW 事件
+const MAX_QUEUED_EVENTS: usize = 16384;
+
+struct Watch {
+    path: String,
+    mask: InotifyMask,
+}
+
+#[derive(Default)]
+struct InotifyInner {
+    watches: BTreeMap<i32, Watch>,
+    next_wd: i32,
+    events: VecDeque<NotifyEvent>,
+}
+
+fn push_event(events: &mut VecDeque<NotifyEvent>, event: NotifyEvent) {
+    // 与队尾相同的事件合并为一个
+    if events.back() == Some(&event) {
+        return;
+    }
+    if events.len() >= MAX_QUEUED_EVENTS {
+        if events.back().map_or(true, |last| last.mask != InotifyMask::Q_OVERFLOW) {
+            events.push_back(NotifyEvent {
+                wd: -1,
+                mask: InotifyMask::Q_OVERFLOW,
+                cookie: 0,
+                name: String::new(),
+            });
+        }
+        return;
+    }
+    events.push_back(event);
+}
+
+/// 将绝对路径拆分为所在目录与文件名
+fn split_path(path: &str) -> Option<(&str, &str)> {
+    let pos = path.rfind('/')?;
+    let parent = if pos == 0 { "/" } else { &path[..pos] };
+    Some((parent, &path[pos + 1..]))
+}
+
+impl InotifyInner {
+    fn add_watch(&mut self, path: String, mask: InotifyMask) -> AxResult<i32> {
+        if let Some((&wd, watch)) = self.watches.iter_mut().find(|(_, w)| w.path == path) {
+            if mask.contains(InotifyMask::MASK_CREATE) {
+                return ax_err!(AlreadyExists);
+            }
+            if mask.contains(InotifyMask::MASK_ADD) {
+                watch.mask |= mask;
+            } else {
+                watch.mask = mask;
+            }
+            return Ok(wd);
+        }
+        self.next_wd += 1;
+        self.watches.insert(self.next_wd, Watch { path, mask });
+        Ok(self.next_wd)
+    }
+
+    fn rm_watch(&mut self, wd: i32) -> AxResult {
+        if self.watches.remove(&wd).is_none() {
+            return ax_err!(InvalidInput);
+        }
+        push_event(&mut self.events, ignored_event(wd));
+        Ok(())
+    }
+
+    /// 将路径 `path` 上的事件投递到相应的监视项，返回是否产生了新事件
+    fn deliver(&mut self, path: &str, mask: InotifyMask, cookie: u32) -> bool {
+        let Self { watches, events, .. } = self;
+        let kind = mask & InotifyMask::ALL_EVENTS;
+        let (parent, name) = split_path(path).unwrap_or(("", ""));
+        let queued = events.len();
+        let mut removed = Vec::new();
+        for (&wd, watch) in watches.iter() {
+            let name = if watch.path == path && InotifyMask::SELF_EVENTS.intersects(kind) {
+                ""
+            } else if watch.path == parent && InotifyMask::CHILD_EVENTS.intersects(kind) {
+                name
+            } else {
+                continue;
+            };
+            if watch.mask.intersects(kind) {
+                push_event(events, NotifyEvent { wd, mask, cookie, name: name.to_string() });
+                if watch.mask.contains(InotifyMask::ONESHOT) {
+                    removed.push(wd);
+                }
+            }
+            // 被监视的文件被删除后监视项自动移除
+            if name.is_empty() && kind.contains(InotifyMask::DELETE_SELF) {
+                removed.push(wd);
+            }
+        }
+        for wd in removed {
+            if watches.remove(&wd).is_some() {
+                push_event(events, ignored_event(wd));
+            }
+        }
+        events.len() != queued
+    }
+
+    /// 被监视的路径或其所在目录被重命名后，更新监视项的路径
+    fn rename(&mut self, old: &str, new: &str) {
+        for watch in self.watches.values_mut() {
+            if watch.path == old {
+                watch.path = new.to_string();
+            } else if let Some(rest) = watch.path.strip_prefix(old) {
+                if rest.starts_with('/') {
+                    watch.path = alloc::format!("{}{}", new, rest);
+                }
+            }
+        }
+    }
+}
+
+fn ignored_event(wd: i32) -> NotifyEvent {
+    NotifyEvent {
+        wd,
+        mask: InotifyMask::IGNORED,
+        cookie: 0,
+        name: String::new(),
+    }
+}
+
+/// 一个 inotify 实例
+pub struct Inotify {
+    inner: SpinNoIrq<InotifyInner>,
+    /// 等待新事件的任务
+    waiters: WaitQueue,
+}
+
+/// 所有存活的 inotify 实例
+static INSTANCES: SpinNoIrq<Vec<Weak<Inotify>>> = SpinNoIrq::new(Vec::new());
+
+/// 重命名事件的 cookie
+static NEXT_COOKIE: AtomicU32 = AtomicU32::new(1);
+
+impl Inotify {
+    /// 创建一个新的 inotify 实例
+    pub fn new() -> Arc<Self> {
+        let inotify = Arc::new(Self {
+            inner: SpinNoIrq::new(InotifyInner::default()),
+            waiters: WaitQueue::new(),
+        });
+        INSTANCES.lock().push(Arc::downgrade(&inotify));
+        inotify
+    }
+
+    /// 监视路径 `path`，返回监视项的编号
+    ///
+    /// 路径已被监视时按 `mask` 中的 MASK_ADD 与 MASK_CREATE 修改原有的监视项
+    pub async fn add_watch(&self, path: &str, mask: InotifyMask) -> AxResult<i32> {
+        if !mask.intersects(InotifyMask::ALL_EVENTS) {
+            return ax_err!(InvalidInput);
+        }
+        if mask.contains(InotifyMask::MASK_ADD | InotifyMask::MASK_CREATE) {
+            return ax_err!(InvalidInput);
+        }
+        let path = crate::root::absolute_path(path).await?;
+        self.inner.lock().add_watch(path, mask)
+    }
+
+    /// 移除监视项 `wd`，并产生一个 IGNORED 事件
+    pub fn rm_watch(&self, wd: i32) -> AxResult {
+        self.inner.lock().rm_watch(wd)?;
+        self.waiters.notify_all();
+        Ok(())
+    }
+
+    /// 队列中是否有事件
+    pub fn has_events(&self) -> bool {
+        !self.inner.lock().events.is_empty()
+    }
+
+    /// 取出队首的事件
+    pub fn pop_event(&self) -> Option<NotifyEvent> {
+        self.inner.lock().events.pop_front()
+    }
+
+    /// 队首的事件满足 `f` 时将其取出
+    pub fn pop_event_if(&self, f: impl FnOnce(&NotifyEvent) -> bool) -> Option<NotifyEvent> {
+        let mut inner = self.inner.lock();
+        if f(inner.events.front()?) {
+            inner.events.pop_front()
+        } else {
+            None
+        }
+    }
+
+    /// 队列中有事件时就绪，否则注册 `cx` 等待
+    pub fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<()> {
+        self.waiters.wait_until(cx, || self.has_events())
+    }
+
+    /// 撤销 `poll_ready` 为 `waker` 注册的等待
+    pub fn cancel_ready(&self, waker: &Waker) {
+        self.waiters.remove_task(waker)
+    }
+
+    /// 等待直到队列中有事件
+    pub async fn wait_events(&self) {
+        poll_fn(|cx| self.poll_ready(cx)).await
+    }
+
+    /// 取出队首的事件，没有事件时注册 `cx` 等待
+    pub fn poll_event(&self, cx: &mut Context<'_>) -> Poll<NotifyEvent> {
+        loop {
+            if let Some(event) = self.pop_event() {
+                return Poll::Ready(event);
+            }
+            futures_core::ready!(self.poll_ready(cx));
+        }
+    }
+
+    /// 等待并取出下一个事件
+    pub async fn next_event(&self) -> NotifyEvent {
+        poll_fn(|cx| self.poll_event(cx)).await
+    }
+}
+
+fn live_instances() -> Vec<Arc<Inotify>> {
+    let mut instances = INSTANCES.lock();
+    instances.retain(|inotify| inotify.strong_count() > 0);
+    instances.iter().filter_map(Weak::upgrade).collect()
+}
+
+fn deliver(path: &str, mask: InotifyMask, cookie: u32) {
+    for inotify in live_instances() {
+        if inotify.inner.lock().deliver(path, mask, cookie) {
+            inotify.waiters.notify_all();
+        }
+    }
+}
+
+/// 绝对路径 `path` 上发生了 `mask` 事件
+pub(crate) fn notify(path: &str, mask: InotifyMask) {
+    deliver(path, mask, 0);
+}
+
+/// 绝对路径 `path` 被删除
+pub(crate) fn notify_removed(path: &str, is_dir: bool) {
+    let isdir = if is_dir { InotifyMask::ISDIR } else { InotifyMask::empty() };
+    deliver(path, InotifyMask::DELETE | isdir, 0);
+    deliver(path, InotifyMask::DELETE_SELF, 0);
+}
+
+/// 绝对路径 `old` 被重命名为 `new`
+pub(crate) fn notify_moved(old: &str, new: &str, is_dir: bool) {
+    let isdir = if is_dir { InotifyMask::ISDIR } else { InotifyMask::empty() };
+    let cookie = NEXT_COOKIE.fetch_add(1, Ordering::Relaxed);
+    deliver(old, InotifyMask::MOVED_FROM | isdir, cookie);
+    deliver(new, InotifyMask::MOVED_TO | isdir, cookie);
+    deliver(old, InotifyMask::MOVE_SELF, 0);
+    for inotify in live_instances() {
+        inotify.inner.lock().rename(old, new);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn child_and_self_events() {
+        let mut inner = InotifyInner::default();
+        let dir = inner.add_watch("/tmp".into(), InotifyMask::CREATE | InotifyMask::DELETE).unwrap();
+        let file = inner.add_watch("/tmp/a".into(), InotifyMask::ALL_EVENTS).unwrap();
+        assert!(inner.deliver("/tmp/a", InotifyMask::CREATE, 0));
+        // 目录中的文件修改不在目录监视项的掩码中
+        assert!(inner.deliver("/tmp/a", InotifyMask::MODIFY, 0));
+        assert!(!inner.deliver("/tmp/b/c", InotifyMask::CREATE, 0));
+        let events: Vec<_> = inner.events.drain(..).map(|e| (e.wd, e.mask, e.name)).collect();
+        assert_eq!(
+            events,
+            [
+                (dir, InotifyMask::CREATE, "a".into()),
+                (file, InotifyMask::MODIFY, String::new()),
+            ]
+        );
+        // 文件被删除后其监视项被移除
+        inner.deliver("/tmp/a", InotifyMask::DELETE, 0);
+        inner.deliver("/tmp/a", InotifyMask::DELETE_SELF, 0);
+        let masks: Vec<_> = inner.events.drain(..).map(|e| (e.wd, e.mask)).collect();
+        assert_eq!(
+            masks,
+            [
+                (dir, InotifyMask::DELETE),
+                (file, InotifyMask::DELETE_SELF),
+                (file, InotifyMask::IGNORED),
+            ]
+        );
+        assert!(!inner.watches.contains_key(&file));
+    }
+
+    #[test]
+    fn modify_existing_watch() {
+        let mut inner = InotifyInner::default();
+        let wd = inner.add_watch("/a".into(), InotifyMask::MODIFY).unwrap();
+        let added = inner.add_watch("/a".into(), InotifyMask::ACCESS | InotifyMask::MASK_ADD);
+        assert_eq!(added.unwrap(), wd);
+        assert_eq!(inner.watches[&wd].mask & InotifyMask::ALL_EVENTS, InotifyMask::MODIFY | InotifyMask::ACCESS);
+        assert!(inner.add_watch("/a".into(), InotifyMask::OPEN | InotifyMask::MASK_CREATE).is_err());
+        // 重命名后监视项随之移动，同一前缀的其他路径不受影响
+        let other = inner.add_watch("/ab".into(), InotifyMask::MODIFY).unwrap();
+        let child = inner.add_watch("/a/c".into(), InotifyMask::MODIFY).unwrap();
+        inner.rename("/a", "/b");
+        assert_eq!(inner.watches[&wd].path, "/b");
+        assert_eq!(inner.watches[&other].path, "/ab");
+        assert_eq!(inner.watches[&child].path, "/b/c");
+    }
+
+    #[test]
+    fn coalesce_and_overflow() {
+        let mut events = VecDeque::new();
+        let event = NotifyEvent { wd: 1, mask: InotifyMask::MODIFY, cookie: 0, name: String::new() };
+        push_event(&mut events, event.clone());
+        push_event(&mut events, event.clone());
+        assert_eq!(events.len(), 1);
+        for i in 0..MAX_QUEUED_EVENTS + 10 {
+            push_event(&mut events, NotifyEvent { wd: i as i32, ..event.clone() });
+        }
+        assert_eq!(events.len(), MAX_QUEUED_EVENTS + 1);
+        assert_eq!(events.back().unwrap().mask, InotifyMask::Q_OVERFLOW);
+    }
+}
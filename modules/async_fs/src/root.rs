@@ -1,118 +1,328 @@
 //! Root directory of the filesystem
 //!
-//! TODO: it doesn't work very well if the mount points have containment relationships.
+//! 所有挂载记录在以路径分量为键的挂载树中，查找路径时沿树向下匹配，
+//! 最深的挂载点即为路径所在的文件系统。同一挂载点可以被多次挂载，只有最后一次挂载可见。
+//!
+//! 按路径查找时使用 [`async_vfs::walk`] 逐个分量解析，结果缓存在全局的目录项缓存中，
+//! 创建、删除、重命名以及挂载关系变化时使对应的缓存失效。
 
 use alloc::{
-    string::{String, ToString},
+    collections::BTreeMap,
+    format,
+    string::String,
     sync::Arc,
     vec::Vec,
 };
 use axerrno::{ax_err, AxError, AxResult};
-use async_vfs::{AsyncVfsNodeOps, AsyncVfsOps, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
+use async_vfs::{
+    AsyncVfsNodeOps, AsyncVfsOps, FileSystemInfo, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps,
+    VfsResult, VfsSetAttr,
+};
+use async_vfs::walk::{DentryCache, LookupFlags, MountResolver, PathError};
 use async_sync::Mutex;
 use lazy_init::LazyInit;
+use spinlock::SpinNoIrq;
 use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
 use core::task::{Context, Poll};
 
-use crate::{api::FileType, fs};
+use crate::fs;
+use crate::notify::{self, InotifyMask};
 
 static CURRENT_DIR_PATH: Mutex<String> = Mutex::new(String::new());
 static CURRENT_DIR: LazyInit<Mutex<VfsNodeRef>> = LazyInit::new();
 
-struct MountPoint {
-    path: &'static str,
-    fs: Arc<dyn VfsOps + Unpin>,
+bitflags::bitflags! {
+    /// mount 的挂载参数，取值与 Linux 的 `MS_*` 一致
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct MountFlags: u32 {
+        /// 只读挂载
+        const RDONLY = 1;
+        /// 忽略 set-user-ID 与 set-group-ID 位
+        const NOSUID = 1 << 1;
+        /// 不允许访问设备文件
+        const NODEV = 1 << 2;
+        /// 不允许执行程序
+        const NOEXEC = 1 << 3;
+        /// 修改已有挂载的参数
+        const REMOUNT = 1 << 5;
+        /// 绑定挂载
+        const BIND = 1 << 12;
+        /// 与 BIND 一同使用时递归绑定
+        const REC = 1 << 14;
+    }
 }
 
-struct RootDirectory {
-    main_fs: Arc<dyn VfsOps + Unpin>,
-    mounts: Vec<MountPoint>,
+bitflags::bitflags! {
+    /// umount2 的参数，取值与 Linux 的 `MNT_*` 一致
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct UmountFlags: u32 {
+        /// 强制卸载
+        const FORCE = 1;
+        /// 延迟卸载：立即从挂载树中移除，已打开的文件仍可继续使用
+        const DETACH = 1 << 1;
+        /// 标记挂载为过期
+        const EXPIRE = 1 << 2;
+        /// 不跟随符号链接
+        const NOFOLLOW = 1 << 3;
+    }
 }
 
-static ROOT_DIR: LazyInit<Arc<RootDirectory>> = LazyInit::new();
+/// 挂载后仍然生效的参数
+const PER_MOUNT_FLAGS: MountFlags = MountFlags::RDONLY
+    .union(MountFlags::NOSUID)
+    .union(MountFlags::NODEV)
+    .union(MountFlags::NOEXEC);
+
+static MOUNT_ID: AtomicUsize = AtomicUsize::new(0);
 
-impl MountPoint {
-    #[allow(unused)]
-    pub fn new(path: &'static str, fs: Arc<dyn VfsOps + Unpin>) -> Self {
-        Self { path, fs }
+/// 目录项缓存的容量
+const DENTRY_CACHE_ENTRIES: usize = 4096;
+
+static DCACHE: DentryCache = DentryCache::new(DENTRY_CACHE_ENTRIES);
+
+/// 挂载树中的一个挂载实例
+///
+/// 打开的文件与目录持有所在的挂载，因此延迟卸载的文件系统在它们全部关闭后才被卸载
+pub(crate) struct Mount {
+    /// 挂载顺序，/proc/mounts 按该顺序输出
+    id: usize,
+    fs: Arc<dyn VfsOps + Unpin>,
+    /// 挂载来源，即 /proc/mounts 的第一列
+    source: String,
+    /// 挂载点的绝对路径
+    path: String,
+    fstype: String,
+    /// 该挂载在 fs 中的根目录，普通挂载为空，绑定挂载时为被绑定的目录
+    root: String,
+    /// `root` 对应的节点，路径解析到挂载点时从这里继续
+    root_node: VfsNodeRef,
+    flags: AtomicU32,
+}
+
+impl Mount {
+    fn new(
+        fs: Arc<dyn VfsOps + Unpin>,
+        source: &str,
+        path: &str,
+        fstype: &str,
+        root: &str,
+        root_node: VfsNodeRef,
+        flags: MountFlags,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            id: MOUNT_ID.fetch_add(1, Ordering::Relaxed),
+            fs,
+            source: source.into(),
+            path: path.into(),
+            fstype: fstype.into(),
+            root: root.into(),
+            root_node,
+            flags: AtomicU32::new((flags & PER_MOUNT_FLAGS).bits()),
+        })
+    }
+
+    fn flags(&self) -> MountFlags {
+        MountFlags::from_bits_truncate(self.flags.load(Ordering::Acquire))
+    }
+
+    pub(crate) fn is_read_only(&self) -> bool {
+        self.flags().contains(MountFlags::RDONLY)
+    }
+
+    /// 挂载内的相对路径在 fs 中对应的路径
+    fn fs_path(&self, rel: &str) -> String {
+        match (self.root.is_empty(), rel.is_empty()) {
+            (true, _) => rel.into(),
+            (false, true) => self.root.clone(),
+            (false, false) => format!("{}/{}", self.root, rel),
+        }
     }
 }
 
-impl Drop for MountPoint {
+impl Drop for Mount {
     fn drop(&mut self) {
-        self.fs.umount().unwrap();
+        // 绑定挂载与原挂载共享同一个 fs，只有最后一个引用消失时才真正卸载
+        if Arc::strong_count(&self.fs) == 1 {
+            if let Err(e) = self.fs.umount() {
+                warn!("failed to umount {}: {:?}", self.path, e);
+            }
+        }
     }
 }
 
-impl RootDirectory {
-    pub const fn new(main_fs: Arc<dyn VfsOps + Unpin>) -> Self {
-        Self {
-            main_fs,
-            mounts: Vec::new(),
+#[derive(Default)]
+struct MountNode {
+    children: BTreeMap<String, MountNode>,
+    /// 挂载在该路径上的文件系统，最后一个可见
+    mounts: Vec<Arc<Mount>>,
+}
+
+impl MountNode {
+    fn has_mounts(&self) -> bool {
+        !self.mounts.is_empty() || self.children.values().any(MountNode::has_mounts)
+    }
+
+    fn collect(&self, out: &mut Vec<Arc<Mount>>) {
+        out.extend(self.mounts.iter().cloned());
+        for child in self.children.values() {
+            child.collect(out);
         }
     }
 
-    #[allow(unused)]
-    pub async fn mount(&mut self, path: &'static str, fs: Arc<dyn VfsOps + Unpin>) -> AxResult {
-        if path == "/" {
-            return ax_err!(InvalidInput, "cannot mount root filesystem");
+    /// 移除 `comps` 对应的路径上最后一次挂载，并清理不再有挂载的分支
+    fn remove(&mut self, comps: &[&str], detach: bool) -> AxResult<Arc<Mount>> {
+        let Some((first, rest)) = comps.split_first() else {
+            if self.mounts.is_empty() {
+                return ax_err!(InvalidInput, "not a mount point");
+            }
+            if self.children.values().any(MountNode::has_mounts) {
+                if !detach {
+                    return ax_err!(ResourceBusy, "mount point has sub mounts");
+                }
+                // 延迟卸载时子挂载一同被移除
+                self.children.clear();
+            }
+            // 挂载树之外还有打开的文件或目录引用该挂载
+            if !detach && Arc::strong_count(self.mounts.last().unwrap()) > 1 {
+                return ax_err!(ResourceBusy, "mount point is busy");
+            }
+            return Ok(self.mounts.pop().unwrap());
+        };
+        let child = self
+            .children
+            .get_mut(*first)
+            .ok_or(AxError::InvalidInput)?;
+        let mount = child.remove(rest, detach)?;
+        if !child.has_mounts() {
+            self.children.remove(*first);
         }
-        if !path.starts_with('/') {
-            return ax_err!(InvalidInput, "mount path must start with '/'");
+        Ok(mount)
+    }
+}
+
+/// 以路径分量为键的挂载树
+struct MountTree {
+    root: MountNode,
+}
+
+/// 将路径拆分为分量，并按字面处理 `.` 与 `..`
+fn path_components(path: &str) -> Vec<&str> {
+    let mut comps = Vec::new();
+    for comp in path.split('/') {
+        match comp {
+            "" | "." => {}
+            ".." => {
+                comps.pop();
+            }
+            _ => comps.push(comp),
         }
-        if self.mounts.iter().any(|mp| mp.path == path) {
-            return ax_err!(InvalidInput, "mount point already exists");
+    }
+    comps
+}
+
+impl MountTree {
+    fn new(root_mount: Arc<Mount>) -> Self {
+        let mut root = MountNode::default();
+        root.mounts.push(root_mount);
+        Self { root }
+    }
+
+    /// 找到路径所在的挂载，返回该挂载与路径在挂载内的相对部分
+    fn resolve(&self, path: &str) -> (Arc<Mount>, String) {
+        let comps = path_components(path);
+        let mut node = &self.root;
+        let mut mount = self.root.mounts.last().unwrap();
+        let mut matched = 0;
+        for (i, comp) in comps.iter().enumerate() {
+            match node.children.get(*comp) {
+                Some(child) => node = child,
+                None => break,
+            }
+            if let Some(m) = node.mounts.last() {
+                mount = m;
+                matched = i + 1;
+            }
         }
-        // create the mount point in the main filesystem if it does not exist
-        self.main_fs.root_dir().await.create(path, FileType::Dir).await?;
-        fs.mount(path, self.main_fs.root_dir().await.lookup(path).await?).await?;
-        self.mounts.push(MountPoint::new(path, fs));
-        Ok(())
+        (mount.clone(), comps[matched..].join("/"))
+    }
+
+    fn insert(&mut self, path: &str, mount: Arc<Mount>) {
+        let mut node = &mut self.root;
+        for comp in path_components(path) {
+            node = node.children.entry(comp.into()).or_default();
+        }
+        node.mounts.push(mount);
+    }
+
+    fn find(&self, path: &str) -> Option<&Arc<Mount>> {
+        let mut node = &self.root;
+        for comp in path_components(path) {
+            node = node.children.get(comp)?;
+        }
+        node.mounts.last()
+    }
+
+    fn remove(&mut self, path: &str, detach: bool) -> AxResult<Arc<Mount>> {
+        let comps = path_components(path);
+        if comps.is_empty() {
+            return ax_err!(ResourceBusy, "cannot umount root filesystem");
+        }
+        self.root.remove(&comps, detach)
+    }
+
+    /// 按挂载顺序列出所有挂载
+    fn mounts(&self) -> Vec<Arc<Mount>> {
+        let mut mounts = Vec::new();
+        self.root.collect(&mut mounts);
+        mounts.sort_by_key(|m| m.id);
+        mounts
     }
+}
+
+struct RootDirectory {
+    tree: SpinNoIrq<MountTree>,
+}
 
-    pub fn _umount(&mut self, path: &str) {
-        self.mounts.retain(|mp| mp.path != path);
+static ROOT_DIR: LazyInit<Arc<RootDirectory>> = LazyInit::new();
+/// 根文件系统是否是由 initramfs 解包得到的 ramfs
+static INITRAMFS_ROOT: AtomicBool = AtomicBool::new(false);
+
+impl RootDirectory {
+    pub fn new(
+        main_fs: Arc<dyn VfsOps + Unpin>,
+        root_node: VfsNodeRef,
+        source: &str,
+        fstype: &str,
+    ) -> Self {
+        let root_mount = Mount::new(main_fs, source, "/", fstype, "", root_node, MountFlags::empty());
+        Self {
+            tree: SpinNoIrq::new(MountTree::new(root_mount)),
+        }
     }
 
     pub fn contains(&self, path: &str) -> bool {
-        self.mounts.iter().any(|mp| mp.path == path)
+        self.tree.lock().find(path).is_some()
+    }
+
+    fn is_read_only(&self, path: &str) -> bool {
+        self.tree.lock().resolve(path).0.is_read_only()
     }
 
     fn lookup_mounted_fs<F, T>(&self, path: &str, f: F) -> Poll<AxResult<T>>
     where
-        F: FnOnce(Arc<dyn VfsOps + Unpin>, &str) -> Poll<AxResult<T>>,
+        F: FnOnce(&Mount, &str, &str) -> Poll<AxResult<T>>,
     {
         debug!("lookup at root: {}", path);
-        let path = path.trim_matches('/');
-        if let Some(rest) = path.strip_prefix("./") {
-            return self.lookup_mounted_fs(rest, f);
-        }
+        // 挂载树的锁不能跨越对文件系统的调用
+        let (mount, rel) = self.tree.lock().resolve(path);
+        f(&mount, &mount.fs_path(&rel), &rel)
+    }
+}
 
-        let mut idx = 0;
-        let mut max_len = 0;
-
-        // Find the filesystem that has the longest mounted path match
-        // TODO: more efficient, e.g. trie
-
-        for (i, mp) in self.mounts.iter().enumerate() {
-            // skip the first '/'
-            // two conditions
-            // 1. path == mp.path, e.g. dev
-            // 2. path == mp.path + '/', e.g. dev/
-            let prev = mp.path[1..].to_string() + "/";
-            if path.starts_with(&mp.path[1..])
-                && (path.len() == prev.len() - 1 || path.starts_with(&prev))
-                && prev.len() > max_len
-            {
-                max_len = mp.path.len() - 1;
-                idx = i;
-            }
-        }
-        if max_len == 0 {
-            f(self.main_fs.clone(), path) // not matched any mount point
-        } else {
-            f(self.mounts[idx].fs.clone(), &path[max_len..]) // matched at `idx`
-        }
+impl MountResolver for RootDirectory {
+    fn mounted_root(&self, path: &str) -> Option<VfsNodeRef> {
+        self.tree.lock().find(path).map(|mount| mount.root_node.clone())
     }
 }
 
@@ -120,43 +330,98 @@ impl VfsNodeOps for RootDirectory {
     async_vfs::impl_vfs_dir_default! {}
 
     fn get_attr(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<VfsResult<VfsNodeAttr>> {
-        let root_dir = futures_core::ready!(
-            VfsOps::root_dir(Pin::new(&self.main_fs), cx)
-        );
-        VfsNodeOps::get_attr(Pin::new(&root_dir), cx)    
+        self.lookup_mounted_fs("/", |mount, fs_path, _| {
+            let root_dir = futures_core::ready!(
+                VfsOps::root_dir(Pin::new(&mount.fs), cx)
+            );
+            if fs_path.is_empty() {
+                VfsNodeOps::get_attr(Pin::new(&root_dir), cx)
+            } else {
+                let node = futures_core::ready!(VfsNodeOps::lookup(Pin::new(&root_dir), cx, fs_path))?;
+                VfsNodeOps::get_attr(Pin::new(&node), cx)
+            }
+        })
+    }
+
+    fn setattr(self: Pin<&Self>, cx: &mut Context<'_>, attr: &VfsSetAttr) -> Poll<VfsResult> {
+        self.lookup_mounted_fs("/", |mount, fs_path, _| {
+            let root_dir = futures_core::ready!(
+                VfsOps::root_dir(Pin::new(&mount.fs), cx)
+            );
+            if fs_path.is_empty() {
+                VfsNodeOps::setattr(Pin::new(&root_dir), cx, attr)
+            } else {
+                let node = futures_core::ready!(VfsNodeOps::lookup(Pin::new(&root_dir), cx, fs_path))?;
+                VfsNodeOps::setattr(Pin::new(&node), cx, attr)
+            }
+        })
     }
 
     fn lookup(self: Pin<&Self>, cx: &mut Context<'_>, _path: &str) -> Poll<VfsResult<VfsNodeRef>> {
-        self.lookup_mounted_fs(_path, |fs, rest_path| {
+        self.lookup_mounted_fs(_path, |mount, fs_path, _| {
             let root_dir = futures_core::ready!(
-                VfsOps::root_dir(Pin::new(&fs), cx)
+                VfsOps::root_dir(Pin::new(&mount.fs), cx)
             );
-            VfsNodeOps::lookup(Pin::new(&root_dir), cx, rest_path)
+            VfsNodeOps::lookup(Pin::new(&root_dir), cx, fs_path)
         })
     }
 
     fn create(self: Pin<&Self>, cx: &mut Context<'_>, path: &str, ty: VfsNodeType) -> Poll<VfsResult> {
-        self.lookup_mounted_fs(path, |fs, rest_path| {
-            if rest_path.is_empty() {
+        self.lookup_mounted_fs(path, |mount, fs_path, rel| {
+            if rel.is_empty() {
                 Poll::Ready(Ok(())) // already exists
+            } else if mount.is_read_only() {
+                Poll::Ready(ax_err!(PermissionDenied, "read-only filesystem"))
             } else {
                 let root_dir = futures_core::ready!(
-                    VfsOps::root_dir(Pin::new(&fs), cx)
+                    VfsOps::root_dir(Pin::new(&mount.fs), cx)
                 );
-                VfsNodeOps::create(Pin::new(&root_dir), cx, rest_path, ty)
+                VfsNodeOps::create(Pin::new(&root_dir), cx, fs_path, ty)
             }
         })
     }
 
     fn remove(self: Pin<&Self>, cx: &mut Context<'_>, path: &str) -> Poll<VfsResult> {
-        self.lookup_mounted_fs(path, |fs, rest_path| {
-            if rest_path.is_empty() {
+        self.lookup_mounted_fs(path, |mount, fs_path, rel| {
+            if rel.is_empty() {
                 Poll::Ready(ax_err!(PermissionDenied)) // cannot remove mount points
+            } else if mount.is_read_only() {
+                Poll::Ready(ax_err!(PermissionDenied, "read-only filesystem"))
+            } else {
+                let root_dir = futures_core::ready!(
+                    VfsOps::root_dir(Pin::new(&mount.fs), cx)
+                );
+                VfsNodeOps::remove(Pin::new(&root_dir), cx, fs_path)
+            }
+        })
+    }
+
+    fn link(self: Pin<&Self>, cx: &mut Context<'_>, path: &str, node: VfsNodeRef) -> Poll<VfsResult> {
+        self.lookup_mounted_fs(path, |mount, fs_path, rel| {
+            if rel.is_empty() {
+                Poll::Ready(ax_err!(AlreadyExists))
+            } else if mount.is_read_only() {
+                Poll::Ready(ax_err!(PermissionDenied, "read-only filesystem"))
+            } else {
+                let root_dir = futures_core::ready!(
+                    VfsOps::root_dir(Pin::new(&mount.fs), cx)
+                );
+                VfsNodeOps::link(Pin::new(&root_dir), cx, fs_path, node)
+            }
+        })
+    }
+
+    fn symlink(self: Pin<&Self>, cx: &mut Context<'_>, path: &str, target: &str) -> Poll<VfsResult> {
+        self.lookup_mounted_fs(path, |mount, fs_path, rel| {
+            if rel.is_empty() {
+                Poll::Ready(ax_err!(AlreadyExists))
+            } else if mount.is_read_only() {
+                Poll::Ready(ax_err!(PermissionDenied, "read-only filesystem"))
             } else {
                 let root_dir = futures_core::ready!(
-                    VfsOps::root_dir(Pin::new(&fs), cx)
+                    VfsOps::root_dir(Pin::new(&mount.fs), cx)
                 );
-                VfsNodeOps::remove(Pin::new(&root_dir), cx, rest_path)
+                VfsNodeOps::symlink(Pin::new(&root_dir), cx, fs_path, target)
             }
         })
     }
@@ -167,14 +432,21 @@ impl VfsNodeOps for RootDirectory {
         src_path: &str, 
         dst_path: &str
     ) -> Poll<VfsResult> {
-        self.lookup_mounted_fs(src_path, |fs, rest_path| {
-            if rest_path.is_empty() {
+        let (dst_mount, dst_rel) = self.tree.lock().resolve(dst_path);
+        self.lookup_mounted_fs(src_path, |mount, fs_path, rel| {
+            if rel.is_empty() || dst_rel.is_empty() {
                 Poll::Ready(ax_err!(PermissionDenied)) // cannot rename mount points
+            } else if !Arc::ptr_eq(&mount.fs, &dst_mount.fs) {
+                // 跨文件系统的 rename 需要由调用者通过复制实现
+                Poll::Ready(ax_err!(Unsupported, "rename across filesystems"))
+            } else if mount.is_read_only() || dst_mount.is_read_only() {
+                Poll::Ready(ax_err!(PermissionDenied, "read-only filesystem"))
             } else {
                 let root_dir = futures_core::ready!(
-                    VfsOps::root_dir(Pin::new(&fs), cx)
+                    VfsOps::root_dir(Pin::new(&mount.fs), cx)
                 );
-                VfsNodeOps::rename(Pin::new(&root_dir), cx, rest_path, dst_path)
+                let dst_fs_path = dst_mount.fs_path(&dst_rel);
+                VfsNodeOps::rename(Pin::new(&root_dir), cx, fs_path, &dst_fs_path)
             }
         })
     }
@@ -184,31 +456,229 @@ pub(crate) async fn init_rootfs(disk: crate::dev::Disk) {
     cfg_if::cfg_if! {
         if #[cfg(feature = "myfs")] { // override the default filesystem
             let main_fs = fs::myfs::new_myfs(disk);
+            let fstype = "myfs";
         } else if #[cfg(feature = "lwext4_rust")] {
             static EXT4_FS: LazyInit<Arc<fs::lwext4_rust::Ext4FileSystem>> = LazyInit::new();
             EXT4_FS.init_by(Arc::new(fs::lwext4_rust::Ext4FileSystem::new(disk)));
             let main_fs = EXT4_FS.clone();
+            let fstype = "ext4";
         } else if #[cfg(feature = "ext4_rs")] {
             static EXT4_FS: LazyInit<Arc<fs::ext4_rs::Ext4FileSystem>> = LazyInit::new();
             EXT4_FS.init_by(Arc::new(fs::ext4_rs::Ext4FileSystem::new(disk)));
             let main_fs = EXT4_FS.clone();
+            let fstype = "ext4";
         } else if #[cfg(feature = "another_ext4")] {
             static EXT4_FS: LazyInit<Arc<fs::another_ext4::Ext4FileSystem>> = LazyInit::new();
             EXT4_FS.init_by(Arc::new(fs::another_ext4::Ext4FileSystem::new(disk)));
             let main_fs = EXT4_FS.clone();
+            let fstype = "ext4";
         } else if #[cfg(feature = "fatfs")] {
             // default to be fatfs
             static FAT_FS: LazyInit<Arc<fs::fatfs::FatFileSystem>> = LazyInit::new();
             FAT_FS.init_by(Arc::new(fs::fatfs::FatFileSystem::new(disk)));
             FAT_FS.init();
             let main_fs = FAT_FS.clone();
+            let fstype = "vfat";
         }
     }
-    let root_dir = RootDirectory::new(main_fs);
+    #[cfg(feature = "overlayfs")]
+    let (main_fs, fstype): (Arc<dyn VfsOps + Unpin>, _) = {
+        info!("mounting {} as the lower layer of the root overlay", fstype);
+        let upper = crate::mounts::ramfs();
+        let overlay = fs::overlayfs::OverlayFileSystem::new(upper, main_fs).await;
+        (Arc::new(overlay), "overlay")
+    };
+    init_root(main_fs, "/dev/vda", fstype).await;
+}
+
+/// 以 ramfs 为根文件系统，并将 initramfs 归档解包到其中
+#[cfg(feature = "initramfs")]
+pub(crate) async fn init_initramfs(archive: &[u8]) -> AxResult {
+    init_root(crate::mounts::ramfs(), "rootfs", "rootfs").await;
+    INITRAMFS_ROOT.store(true, Ordering::Relaxed);
+    crate::initramfs::unpack(archive).await
+}
+
+async fn init_root(main_fs: Arc<dyn VfsOps + Unpin>, source: &str, fstype: &str) {
+    let root_node = main_fs.root_dir().await;
+    let root_dir = RootDirectory::new(main_fs, root_node, source, fstype);
 
     ROOT_DIR.init_by(Arc::new(root_dir));
     CURRENT_DIR.init_by(Mutex::new(ROOT_DIR.clone()));
     *CURRENT_DIR_PATH.lock().await = "/".into();
+
+    #[cfg(feature = "procfs")]
+    {
+        // create the mount point in the main filesystem if it does not exist
+        if let Err(AxError::NotFound) = lookup(None, "/proc").await {
+            create_dir(None, "/proc").await.expect("failed to create /proc");
+        }
+        let procfs = crate::mounts::procfs().expect("failed to initialize procfs");
+        mount(procfs, "proc", "/proc", "proc", MountFlags::empty())
+            .await
+            .expect("failed to mount procfs");
+    }
+
+    #[cfg(feature = "mqueue")]
+    {
+        // 根文件系统只读等情况下无法创建挂载点，此时 mq_open 等系统调用仍然可用
+        for dir in ["/dev", "/dev/mqueue"] {
+            if let Err(AxError::NotFound) = lookup(None, dir).await {
+                let _ = create_dir(None, dir).await;
+            }
+        }
+        let mqueue = fs::mqueue::MqueueFileSystem::instance();
+        if let Err(e) = mount(mqueue, "mqueue", "/dev/mqueue", "mqueue", MountFlags::empty()).await {
+            warn!("failed to mount mqueue: {:?}", e);
+        }
+    }
+}
+
+/// 将文件系统挂载到 `target`，`target` 必须是已存在的目录
+pub(crate) async fn mount(
+    fs: Arc<dyn VfsOps + Unpin>,
+    source: &str,
+    target: &str,
+    fstype: &str,
+    flags: MountFlags,
+) -> AxResult {
+    let target = absolute_path(target).await?;
+    let mount_point = lookup(None, &target).await?;
+    if !mount_point.get_attr().await?.is_dir() {
+        return ax_err!(NotADirectory);
+    }
+    fs.mount(&target, mount_point).await?;
+    let root_node = fs.root_dir().await;
+    let mount = Mount::new(fs, source, &target, fstype, "", root_node, flags);
+    ROOT_DIR.tree.lock().insert(&target, mount);
+    DCACHE.clear();
+    info!("mounted {} ({}) at {}", source, fstype, target);
+    Ok(())
+}
+
+/// 将 `source` 目录绑定挂载到 `target`，二者访问同一份内容
+pub(crate) async fn bind_mount(source: &str, target: &str, flags: MountFlags) -> AxResult {
+    let source = absolute_path(source).await?;
+    let target = absolute_path(target).await?;
+    let root_node = lookup(None, &source).await?;
+    if !root_node.get_attr().await?.is_dir() {
+        return ax_err!(NotADirectory);
+    }
+    if !lookup(None, &target).await?.get_attr().await?.is_dir() {
+        return ax_err!(NotADirectory);
+    }
+    let mut tree = ROOT_DIR.tree.lock();
+    let (src_mount, rel) = tree.resolve(&source);
+    let mount = Mount::new(
+        src_mount.fs.clone(),
+        &src_mount.source,
+        &target,
+        &src_mount.fstype,
+        &src_mount.fs_path(&rel),
+        root_node,
+        flags,
+    );
+    tree.insert(&target, mount);
+    drop(tree);
+    DCACHE.clear();
+    info!("bind mounted {} at {}", source, target);
+    Ok(())
+}
+
+/// 修改已有挂载的参数，例如切换只读
+pub(crate) async fn remount(target: &str, flags: MountFlags) -> AxResult {
+    let target = absolute_path(target).await?;
+    let tree = ROOT_DIR.tree.lock();
+    let Some(mount) = tree.find(&target) else {
+        return ax_err!(InvalidInput, "not a mount point");
+    };
+    mount.flags.store((flags & PER_MOUNT_FLAGS).bits(), Ordering::Release);
+    Ok(())
+}
+
+/// 卸载 `target` 上最后一次挂载的文件系统
+///
+/// 不带 [`UmountFlags::DETACH`] 时，若其下还有子挂载或者仍有打开的文件则返回
+/// [`AxError::ResourceBusy`]；延迟卸载时挂载立即从挂载树中移除，文件系统在最后一个
+/// 打开的文件关闭后才真正卸载。
+pub(crate) async fn umount(target: &str, flags: UmountFlags) -> AxResult {
+    let target = absolute_path(target).await?;
+    // 目标不存在时返回 NotFound，而不是“不是挂载点”
+    lookup(None, &target).await?;
+    let mount = ROOT_DIR
+        .tree
+        .lock()
+        .remove(&target, flags.contains(UmountFlags::DETACH))?;
+    DCACHE.clear();
+    info!("umounted {}", mount.path);
+    drop(mount);
+    Ok(())
+}
+
+/// 根文件系统是否由 initramfs 解包而来
+pub(crate) fn is_initramfs_root() -> bool {
+    INITRAMFS_ROOT.load(Ordering::Relaxed)
+}
+
+/// 路径是否是挂载点
+pub(crate) fn is_mount_point(path: &str) -> bool {
+    ROOT_DIR.contains(path)
+}
+
+/// 路径所在的挂载
+///
+/// 相对于已打开目录的路径由该目录所在的文件系统解析，不会进入其下的其他挂载，
+/// 因此属于该目录所在的挂载 `dir_mount`
+pub(crate) async fn mount_of(dir_mount: Option<&Arc<Mount>>, path: &str) -> AxResult<Arc<Mount>> {
+    match dir_mount {
+        Some(mount) if !path.starts_with('/') => Ok(mount.clone()),
+        _ => Ok(ROOT_DIR.tree.lock().resolve(&absolute_path(path).await?).0),
+    }
+}
+
+/// 路径是否位于只读挂载中，`dir_mount` 为相对路径所基于的目录所在的挂载
+pub(crate) async fn is_read_only(dir_mount: Option<&Arc<Mount>>, path: &str) -> bool {
+    mount_of(dir_mount, path)
+        .await
+        .is_ok_and(|mount| mount.is_read_only())
+}
+
+/// 路径所在文件系统的信息，`flags` 取自所在挂载的参数
+///
+/// 返回 [`PathError`]，使调用者能够区分符号链接成环
+pub(crate) async fn statfs(path: &str) -> Result<FileSystemInfo, PathError> {
+    let (path, node) = walk(path, LookupFlags::empty(), None).await?;
+    if node.is_none() {
+        return Err(PathError::Vfs(AxError::NotFound));
+    }
+    let (mount, _) = ROOT_DIR.tree.lock().resolve(&path);
+    let mut info = mount.fs.statfs().await?;
+    // statfs 的 ST_RDONLY、ST_NOSUID、ST_NODEV、ST_NOEXEC 与 MountFlags 的取值相同
+    let flags = MountFlags::RDONLY | MountFlags::NOSUID | MountFlags::NODEV | MountFlags::NOEXEC;
+    info.flags = (mount.flags() & flags).bits() as u64;
+    Ok(info)
+}
+
+/// 生成 `/proc/mounts` 的内容，每行依次为来源、挂载点、类型、参数
+pub(crate) fn mounts_info() -> String {
+    let mounts = ROOT_DIR.tree.lock().mounts();
+    let mut info = String::new();
+    for mount in mounts {
+        let flags = mount.flags();
+        let mut opts = String::from(if flags.contains(MountFlags::RDONLY) { "ro" } else { "rw" });
+        for (flag, name) in [
+            (MountFlags::NOSUID, "nosuid"),
+            (MountFlags::NODEV, "nodev"),
+            (MountFlags::NOEXEC, "noexec"),
+        ] {
+            if flags.contains(flag) {
+                opts += ",";
+                opts += name;
+            }
+        }
+        info += &format!("{} {} {} {} 0 0\n", mount.source, mount.path, mount.fstype, opts);
+    }
+    info
 }
 
 async fn parent_node_of(dir: Option<&VfsNodeRef>, path: &str) -> VfsNodeRef {
@@ -232,10 +702,88 @@ pub(crate) async fn absolute_path(path: &str) -> AxResult<String> {
     }
 }
 
+/// 用于事件通知的绝对路径，相对于已打开目录的路径无法确定时为 `None`
+pub(crate) async fn event_path(dir: Option<&VfsNodeRef>, path: &str) -> Option<String> {
+    if dir.is_some() && !path.starts_with('/') {
+        None
+    } else {
+        absolute_path(path).await.ok()
+    }
+}
+
+/// 使 `path` 对应的目录项缓存失效
+async fn invalidate(dir: Option<&VfsNodeRef>, path: &str) {
+    if dir.is_some() && !path.starts_with('/') {
+        // 相对于某个已打开目录的路径无法确定绝对路径
+        DCACHE.clear();
+    } else if let Ok(path) = absolute_path(path).await {
+        DCACHE.invalidate(&path);
+    }
+}
+
+/// 消息队列不经过路径被创建或删除，挂载点下缓存的查找结果随之失效
+#[cfg(feature = "mqueue")]
+pub(crate) fn mqueue_changed() {
+    DCACHE.clear();
+}
+
+/// AxError 中没有与 ELOOP 对应的错误，符号链接成环时视为路径不存在
+///
+/// 需要报告 ELOOP 的调用者应当直接使用 [`walk`] 返回的 [`PathError`]
+fn path_err(err: PathError) -> AxError {
+    match err {
+        PathError::Loop => AxError::NotFound,
+        PathError::Vfs(err) => err,
+    }
+}
+
+/// 解析路径，返回跟随符号链接后的绝对路径与对应节点，最后一个分量不存在时节点为 `None`
+///
+/// `searcher` 不为 `None` 时检查其对途经的每个目录是否有搜索权限
+pub(crate) async fn walk(
+    path: &str,
+    flags: LookupFlags,
+    searcher: Option<(u32, u32)>,
+) -> Result<(String, Option<VfsNodeRef>), PathError> {
+    if path.is_empty() {
+        return Err(PathError::Vfs(AxError::NotFound));
+    }
+    let path = if path.starts_with('/') {
+        String::from(path)
+    } else {
+        CURRENT_DIR_PATH.lock().await.clone() + path
+    };
+    async_vfs::walk::walk(&**ROOT_DIR, &DCACHE, &path, flags, searcher).await
+}
+
+/// 从已打开的目录 `dir`（路径为 `dir_path`）解析 `path`
+pub(crate) async fn walk_at(
+    dir: &VfsNodeRef,
+    dir_path: &str,
+    path: &str,
+    flags: LookupFlags,
+    searcher: Option<(u32, u32)>,
+) -> Result<(String, Option<VfsNodeRef>), PathError> {
+    async_vfs::walk::walk_at(&**ROOT_DIR, &DCACHE, (dir_path, dir), path, flags, searcher).await
+}
+
 pub(crate) async fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
+    lookup_with(dir, path, LookupFlags::empty()).await
+}
+
+/// 按 `flags` 查找路径，例如不跟随最后的符号链接
+pub(crate) async fn lookup_with(
+    dir: Option<&VfsNodeRef>,
+    path: &str,
+    flags: LookupFlags,
+) -> AxResult<VfsNodeRef> {
     if path.is_empty() {
         return ax_err!(NotFound);
     }
+    if dir.is_none() || path.starts_with('/') {
+        let (_, node) = walk(path, flags, None).await.map_err(path_err)?;
+        return node.ok_or(AxError::NotFound);
+    }
     let node = parent_node_of(dir, path).await.lookup(path).await?;
     if path.ends_with('/') && !node.get_attr().await?.is_dir() {
         ax_err!(NotADirectory)
@@ -252,26 +800,64 @@ pub(crate) async fn create_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResul
     }
     let parent = parent_node_of(dir, path).await;
     parent.create(path, VfsNodeType::File).await?;
+    invalidate(dir, path).await;
+    if let Some(path) = event_path(dir, path).await {
+        notify::notify(&path, InotifyMask::CREATE);
+    }
     parent.lookup(path).await
 }
 
 pub(crate) async fn create_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
     match lookup(dir, path).await {
         Ok(_) => ax_err!(AlreadyExists),
-        Err(AxError::NotFound) => parent_node_of(dir, path).await.create(path, VfsNodeType::Dir).await,
+        Err(AxError::NotFound) => {
+            parent_node_of(dir, path).await.create(path, VfsNodeType::Dir).await?;
+            invalidate(dir, path).await;
+            if let Some(path) = event_path(dir, path).await {
+                notify::notify(&path, InotifyMask::CREATE | InotifyMask::ISDIR);
+            }
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// 创建 FIFO、socket 等只有元数据的节点，不支持的文件系统返回 Unsupported
+pub(crate) async fn create_node(dir: Option<&VfsNodeRef>, path: &str, ty: VfsNodeType) -> AxResult {
+    if path.is_empty() {
+        return ax_err!(NotFound);
+    } else if path.ends_with('/') {
+        return ax_err!(NotADirectory);
+    }
+    match lookup_with(dir, path, LookupFlags::NOFOLLOW).await {
+        Ok(_) => ax_err!(AlreadyExists),
+        Err(AxError::NotFound) => {
+            parent_node_of(dir, path).await.create(path, ty).await?;
+            invalidate(dir, path).await;
+            if let Some(path) = event_path(dir, path).await {
+                notify::notify(&path, InotifyMask::CREATE);
+            }
+            Ok(())
+        }
         Err(e) => Err(e),
     }
 }
 
 pub(crate) async fn remove_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
-    let node = lookup(dir, path).await?;
+    // 删除符号链接本身，而不是其指向的文件
+    let node = lookup_with(dir, path, LookupFlags::NOFOLLOW).await?;
     let attr = node.get_attr().await?;
     if attr.is_dir() {
         ax_err!(IsADirectory)
     } else if !attr.perm().owner_writable() {
         ax_err!(PermissionDenied)
     } else {
-        parent_node_of(dir, path).await.remove(path).await
+        parent_node_of(dir, path).await.remove(path).await?;
+        invalidate(dir, path).await;
+        if let Some(path) = event_path(dir, path).await {
+            notify::notify_removed(&path, false);
+        }
+        Ok(())
     }
 }
 
@@ -300,7 +886,12 @@ pub(crate) async fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult
     } else if !attr.perm().owner_writable() {
         ax_err!(PermissionDenied)
     } else {
-        parent_node_of(dir, path).await.remove(path).await
+        parent_node_of(dir, path).await.remove(path).await?;
+        invalidate(dir, path).await;
+        if let Some(path) = event_path(dir, path).await {
+            notify::notify_removed(&path, true);
+        }
+        Ok(())
     }
 }
 
@@ -333,9 +924,160 @@ pub(crate) async fn set_current_dir(path: &str) -> AxResult {
 }
 
 pub(crate) async fn rename(old: &str, new: &str) -> AxResult {
-    if parent_node_of(None, new).await.lookup(new).await.is_ok() {
-        warn!("dst file already exist, now remove it");
-        remove_file(None, new).await?;
+    let is_dir = match lookup_with(None, old, LookupFlags::NOFOLLOW).await {
+        Ok(node) => node.get_attr().await.is_ok_and(|attr| attr.is_dir()),
+        Err(_) => false,
+    };
+    parent_node_of(None, old).await.rename(old, new).await?;
+    invalidate(None, old).await;
+    invalidate(None, new).await;
+    if let (Ok(old), Ok(new)) = (absolute_path(old).await, absolute_path(new).await) {
+        notify::notify_moved(&old, &new, is_dir);
+    }
+    Ok(())
+}
+
+/// 创建指向 `target` 的符号链接 `path`，`target` 不需要存在
+pub(crate) async fn symlink(target: &str, path: &str) -> AxResult {
+    if path.is_empty() || target.is_empty() {
+        return ax_err!(NotFound);
+    }
+    if lookup_with(None, path, LookupFlags::NOFOLLOW).await.is_ok() {
+        return ax_err!(AlreadyExists);
+    }
+    parent_node_of(None, path).await.symlink(path, target).await?;
+    invalidate(None, path).await;
+    if let Ok(path) = absolute_path(path).await {
+        notify::notify(&path, InotifyMask::CREATE);
+    }
+    Ok(())
+}
+
+/// 为 `old` 创建硬链接 `new`，`old` 为符号链接时链接到符号链接本身
+pub(crate) async fn hard_link(old: &str, new: &str) -> AxResult {
+    let node = lookup_with(None, old, LookupFlags::NOFOLLOW).await?;
+    if node.get_attr().await?.is_dir() {
+        return ax_err!(PermissionDenied, "hard link to directory");
+    }
+    if lookup_with(None, new, LookupFlags::NOFOLLOW).await.is_ok() {
+        return ax_err!(AlreadyExists);
+    }
+    parent_node_of(None, new).await.link(new, node).await?;
+    invalidate(None, new).await;
+    if let Ok(new) = absolute_path(new).await {
+        notify::notify(&new, InotifyMask::CREATE);
+    }
+    Ok(())
+}
+
+/// 读取符号链接 `path` 的内容
+pub(crate) async fn read_link(path: &str) -> AxResult<String> {
+    let node = lookup_with(None, path, LookupFlags::NOFOLLOW).await?;
+    let mut buf = alloc::vec![0u8; node.get_attr().await?.size() as usize];
+    let len = node.readlink(&mut buf).await?;
+    buf.truncate(len);
+    String::from_utf8(buf).map_err(|_| AxError::InvalidData)
+}
+
+/// 路径所在文件系统的类型，即 /proc/mounts 的第三列
+pub(crate) async fn fs_type(path: &str) -> AxResult<String> {
+    Ok(mount_of(None, path).await?.fstype.clone())
+}
+
+/// 两个路径是否位于同一个文件系统中
+pub(crate) async fn same_filesystem(a: &str, b: &str) -> bool {
+    let (Ok(a), Ok(b)) = (absolute_path(a).await, absolute_path(b).await) else {
+        return false;
+    };
+    let tree = ROOT_DIR.tree.lock();
+    Arc::ptr_eq(&tree.resolve(&a).0.fs, &tree.resolve(&b).0.fs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct EmptyNode;
+
+    impl VfsNodeOps for EmptyNode {}
+
+    /// 卸载时计数的文件系统
+    struct CountingFs {
+        umounts: Arc<AtomicUsize>,
+    }
+
+    impl VfsOps for CountingFs {
+        fn umount(&self) -> VfsResult {
+            self.umounts.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+
+        fn root_dir(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsNodeRef> {
+            Poll::Ready(Arc::new(EmptyNode))
+        }
+    }
+
+    fn new_mount(path: &str, umounts: &Arc<AtomicUsize>) -> Arc<Mount> {
+        let fs = Arc::new(CountingFs { umounts: umounts.clone() });
+        Mount::new(fs, "none", path, "test", "", Arc::new(EmptyNode), MountFlags::empty())
+    }
+
+    #[test]
+    fn later_mount_shadows_earlier() {
+        let umounts = Arc::new(AtomicUsize::new(0));
+        let mut tree = MountTree::new(new_mount("/", &umounts));
+        let first = new_mount("/mnt", &umounts);
+        let second = new_mount("/mnt", &umounts);
+        let (first_id, second_id) = (first.id, second.id);
+        tree.insert("/mnt", first);
+        tree.insert("/mnt/", second);
+
+        let (mount, rel) = tree.resolve("/mnt/a/b");
+        assert_eq!((mount.id, rel.as_str()), (second_id, "a/b"));
+        assert_ne!(tree.resolve("/mntx").0.id, second_id);
+        drop(mount);
+
+        assert_eq!(tree.remove("/mnt", false).unwrap().id, second_id);
+        assert_eq!(umounts.load(Ordering::Relaxed), 1);
+        assert_eq!(tree.resolve("/mnt/a").0.id, first_id);
+        assert_eq!(tree.remove("/mnt", false).unwrap().id, first_id);
+        assert!(tree.find("/mnt").is_none());
+        assert_eq!(tree.remove("/mnt", false).err(), Some(AxError::InvalidInput));
+        assert_eq!(tree.remove("/", true).err(), Some(AxError::ResourceBusy));
+    }
+
+    #[test]
+    fn busy_mount_needs_detach() {
+        let umounts = Arc::new(AtomicUsize::new(0));
+        let mut tree = MountTree::new(new_mount("/", &umounts));
+        tree.insert("/mnt", new_mount("/mnt", &umounts));
+        tree.insert("/mnt/sub", new_mount("/mnt/sub", &umounts));
+
+        // 有子挂载
+        assert_eq!(tree.remove("/mnt", false).err(), Some(AxError::ResourceBusy));
+        assert!(tree.remove("/mnt/sub", false).is_ok());
+
+        // 打开的文件持有挂载
+        let (open, _) = tree.resolve("/mnt/file");
+        assert_eq!(tree.remove("/mnt", false).err(), Some(AxError::ResourceBusy));
+        let detached = tree.remove("/mnt", true).unwrap();
+        drop(detached);
+        assert!(tree.find("/mnt").is_none());
+        assert_eq!(umounts.load(Ordering::Relaxed), 1);
+        drop(open);
+        assert_eq!(umounts.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn detach_removes_sub_mounts() {
+        let umounts = Arc::new(AtomicUsize::new(0));
+        let mut tree = MountTree::new(new_mount("/", &umounts));
+        tree.insert("/mnt", new_mount("/mnt", &umounts));
+        tree.insert("/mnt/sub", new_mount("/mnt/sub", &umounts));
+
+        drop(tree.remove("/mnt", true).unwrap());
+        assert!(tree.find("/mnt/sub").is_none());
+        assert_eq!(tree.resolve("/mnt/sub/a").1, "mnt/sub/a");
+        assert_eq!(umounts.load(Ordering::Relaxed), 2);
     }
-    parent_node_of(None, old).await.rename(old, new).await
 }
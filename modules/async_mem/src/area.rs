@@ -196,12 +196,16 @@ impl MapArea {
         if let Some(page) = &self.pages[page_index] {
             if let Some(backend) = &mut self.backend {
                 if backend.writable().await {
-                    let _ = backend
+                    // 例如 memfd 在映射之后被加上了 F_SEAL_WRITE
+                    if backend
                         .write_to_seek(
                             SeekFrom::Start((page_index * PAGE_SIZE_4K) as u64),
                             page.lock().await.as_slice(),
                         ).await
-                        .unwrap();
+                        .is_err()
+                    {
+                        warn!("Failed to write back page {} to backend", page_index);
+                    }
                 }
             }
         } else {
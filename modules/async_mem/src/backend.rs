@@ -1,12 +1,14 @@
 use core::{pin::Pin, task::{Context, Poll}};
 use alloc::boxed::Box;
-use async_fs::api::{AsAny, File, FileExt};
+use async_fs::api::FileExt;
 use async_io::{AsyncRead, AsyncSeek, Seek, SeekFrom};
 
 type BackEndFile = Box<dyn FileExt>;
 
 /// File backend for Lazy load `MapArea`. `file` should be a file holding a offset value. Normally,
 /// `MemBackend` won't share a file with other things, so we use a `Box` here.
+///
+/// Any object implementing [`FileExt`] can be used as the backend.
 pub struct MemBackend {
     file: BackEndFile,
 }
@@ -51,15 +53,8 @@ impl MemBackend {
 
 impl Clone for MemBackend {
     fn clone(&self) -> Self {
-        let file = self
-            .file
-            .as_any()
-            .downcast_ref::<File>()
-            .expect("Cloning a MemBackend with a non-file object")
-            .clone();
-
         Self {
-            file: Box::new(file),
+            file: self.file.clone_file(),
         }
     }
 }
@@ -0,0 +1,234 @@
+//! System V IPC 的公共部分
+//!
+//! 共享内存、信号量集与消息队列各自拥有一个 [`IpcIds`]，它们使用相同的键、标识符与权限规则，
+//! 因此可以像 Linux 一样通过 `*_INFO` 与 `*_STAT` 命令逐个枚举
+extern crate alloc;
+
+use alloc::{collections::BTreeMap, sync::Arc};
+
+/// 不与其他进程共享的键，每次都创建新的对象
+pub const IPC_PRIVATE: i32 = 0;
+
+/// 每种对象的最大数目，标识符为 `seq * IPCMNI + index`
+pub const IPCMNI: usize = 32768;
+
+/// 对象的所有者与权限，即 `struct kern_ipc_perm`
+#[derive(Clone, Copy, Debug)]
+pub struct IpcPerm {
+    pub key: i32,
+    pub uid: u32,
+    pub gid: u32,
+    pub cuid: u32,
+    pub cgid: u32,
+    /// 低 9 位为权限
+    pub mode: u16,
+}
+
+impl IpcPerm {
+    /// 由 `uid` 与 `gid` 创建的对象
+    pub fn new(key: i32, uid: u32, gid: u32, mode: u16) -> Self {
+        Self {
+            key,
+            uid,
+            gid,
+            cuid: uid,
+            cgid: gid,
+            mode: mode & 0o777,
+        }
+    }
+
+    /// 用户 `uid` 与组 `gid` 是否拥有 `flag` 中请求的权限
+    ///
+    /// 与 Linux 的 ipcperms 相同，`flag` 的三组权限位被合并为一组后与调用者所属类别的权限比较，root 总是被允许
+    pub fn permits(&self, uid: u32, gid: u32, flag: u16) -> bool {
+        if uid == 0 {
+            return true;
+        }
+        let requested = (flag >> 6 | flag >> 3 | flag) & 0o7;
+        let granted = if uid == self.uid || uid == self.cuid {
+            self.mode >> 6
+        } else if gid == self.gid || gid == self.cgid {
+            self.mode >> 3
+        } else {
+            self.mode
+        };
+        requested & !granted & 0o7 == 0
+    }
+
+    /// `uid` 能否修改或删除对象，只有所有者、创建者与 root 可以
+    pub fn is_owner(&self, uid: u32) -> bool {
+        uid == 0 || uid == self.uid || uid == self.cuid
+    }
+
+    /// IPC_SET：修改所有者与权限
+    pub fn set(&mut self, uid: u32, gid: u32, mode: u16) {
+        self.uid = uid;
+        self.gid = gid;
+        self.mode = (self.mode & !0o777) | (mode & 0o777);
+    }
+}
+
+struct IpcEntry<T> {
+    id: i32,
+    key: i32,
+    object: Arc<T>,
+}
+
+/// 一种 IPC 对象的命名空间，记录标识符与键到对象的映射
+pub struct IpcIds<T> {
+    /// 以位置为键的对象
+    entries: BTreeMap<usize, IpcEntry<T>>,
+    /// 不为 IPC_PRIVATE 的键到位置的映射
+    keys: BTreeMap<i32, usize>,
+    /// 下一个对象的序号
+    seq: u16,
+    /// 对象的最大数目
+    limit: usize,
+}
+
+impl<T> IpcIds<T> {
+    /// 创建一个最多容纳 `limit` 个对象的命名空间，`limit` 不能超过 [`IPCMNI`]
+    pub const fn new(limit: usize) -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            keys: BTreeMap::new(),
+            seq: 0,
+            limit,
+        }
+    }
+
+    /// 标识符对应的位置
+    fn index_of(id: i32) -> Option<usize> {
+        (id >= 0).then_some(id as usize % IPCMNI)
+    }
+
+    /// 根据标识符查找对象，位置上的对象已被替换时返回 None
+    pub fn get(&self, id: i32) -> Option<Arc<T>> {
+        let entry = self.entries.get(&Self::index_of(id)?)?;
+        (entry.id == id).then(|| entry.object.clone())
+    }
+
+    /// 根据键查找对象，返回标识符与对象
+    pub fn find_key(&self, key: i32) -> Option<(i32, Arc<T>)> {
+        if key == IPC_PRIVATE {
+            return None;
+        }
+        let entry = self.entries.get(self.keys.get(&key)?)?;
+        Some((entry.id, entry.object.clone()))
+    }
+
+    /// 以键 `key` 加入对象，对象数目达到上限时返回 None
+    ///
+    /// 调用者需要保证 `key` 为 IPC_PRIVATE 或者尚未被使用
+    pub fn insert(&mut self, key: i32, object: T) -> Option<(i32, Arc<T>)> {
+        if self.entries.len() >= self.limit {
+            return None;
+        }
+        // 使用最小的空闲位置
+        let index = self
+            .entries
+            .keys()
+            .enumerate()
+            .find(|(expected, index)| expected != *index)
+            .map_or(self.entries.len(), |(expected, _)| expected);
+        let seq = self.seq;
+        self.seq = if (seq as usize + 1) * IPCMNI > i32::MAX as usize {
+            0
+        } else {
+            seq + 1
+        };
+        let id = (seq as usize * IPCMNI + index) as i32;
+        let object = Arc::new(object);
+        self.entries.insert(
+            index,
+            IpcEntry {
+                id,
+                key,
+                object: object.clone(),
+            },
+        );
+        if key != IPC_PRIVATE {
+            self.keys.insert(key, index);
+        }
+        Some((id, object))
+    }
+
+    /// 移除对象，之后它的键可以被重新使用
+    pub fn remove(&mut self, id: i32) -> Option<Arc<T>> {
+        let index = Self::index_of(id)?;
+        if self.entries.get(&index)?.id != id {
+            return None;
+        }
+        let entry = self.entries.remove(&index)?;
+        if entry.key != IPC_PRIVATE {
+            self.keys.remove(&entry.key);
+        }
+        Some(entry.object)
+    }
+
+    /// 根据位置查找对象，用于 `*_STAT` 命令
+    pub fn get_by_index(&self, index: usize) -> Option<(i32, Arc<T>)> {
+        let entry = self.entries.get(&index)?;
+        Some((entry.id, entry.object.clone()))
+    }
+
+    /// 已被使用的最大位置，`*_INFO` 命令返回它
+    pub fn max_index(&self) -> Option<usize> {
+        self.entries.keys().next_back().copied()
+    }
+
+    /// 对象的数目
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 是否没有对象
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 遍历所有对象
+    pub fn iter(&self) -> impl Iterator<Item = (i32, &Arc<T>)> {
+        self.entries.values().map(|entry| (entry.id, &entry.object))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IpcIds, IpcPerm, IPCMNI, IPC_PRIVATE};
+
+    #[test]
+    fn test_ids() {
+        let mut ids = IpcIds::new(2);
+        let (first, _) = ids.insert(7, 'a').unwrap();
+        let (second, _) = ids.insert(IPC_PRIVATE, 'b').unwrap();
+        assert!(ids.insert(8, 'c').is_none());
+        assert_eq!(Some('a'), ids.find_key(7).map(|(_, c)| *c));
+        assert!(ids.find_key(IPC_PRIVATE).is_none());
+        assert_eq!(Some(1), ids.max_index());
+
+        // 重新使用空闲的位置时标识符不同，旧的标识符失效
+        assert_eq!(Some('a'), ids.remove(first).as_deref().copied());
+        assert!(ids.find_key(7).is_none());
+        let (third, _) = ids.insert(7, 'c').unwrap();
+        assert_eq!(first as usize % IPCMNI, third as usize % IPCMNI);
+        assert_ne!(first, third);
+        assert!(ids.get(first).is_none());
+        assert_eq!(Some('c'), ids.get(third).as_deref().copied());
+        assert_eq!(
+            Some((second, 'b')),
+            ids.get_by_index(1).map(|(id, c)| (id, *c))
+        );
+    }
+
+    #[test]
+    fn test_perm() {
+        let perm = IpcPerm::new(1, 1000, 100, 0o640);
+        assert!(perm.permits(1000, 1, 0o600));
+        assert!(perm.permits(1001, 100, 0o444));
+        assert!(!perm.permits(1001, 100, 0o222));
+        assert!(!perm.permits(1001, 1, 0o444));
+        assert!(perm.permits(0, 0, 0o666));
+        assert!(perm.is_owner(1000) && !perm.is_owner(1001));
+    }
+}
@@ -2,16 +2,16 @@
 #![cfg_attr(not(test), no_std)]
 mod area;
 mod backend;
+pub mod ipc;
 mod shared;
 pub use area::MapArea;
 use axerrno::{AxError, AxResult};
 pub use backend::MemBackend;
+pub use shared::{SharedMem, SharedMemInfo};
 
 extern crate alloc;
 use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
-use core::sync::atomic::{AtomicI32, Ordering};
 use page_table_entry::GenericPTE;
-use shared::SharedMem;
 use spinlock::SpinNoIrq;
 #[macro_use]
 extern crate log;
@@ -22,26 +22,22 @@ use axhal::{
     paging::{MappingFlags, PageSize, PageTable, PagingError},
 };
 
-// TODO: a real allocator
-static SHMID: AtomicI32 = AtomicI32::new(1);
+/// The maximum number of shared memory segments.
+pub const SHMMNI: usize = 4096;
 
-/// This struct only hold SharedMem that are not IPC_PRIVATE. IPC_PRIVATE SharedMem will be stored
-/// in MemorySet::detached_mem.
+/// All shared memory segments, including the IPC_PRIVATE ones, indexed by shmid and key.
 ///
 /// This is the only place we can query a SharedMem using its shmid.
 ///
-/// It holds an Arc to the SharedMem. If the Arc::strong_count() is 1, SharedMem will be dropped.
-pub static SHARED_MEMS: SpinNoIrq<BTreeMap<i32, Arc<SharedMem>>> = SpinNoIrq::new(BTreeMap::new());
-
-/// The map from key to shmid. It's used to query shmid from key.
-pub static KEY_TO_SHMID: SpinNoIrq<BTreeMap<i32, i32>> = SpinNoIrq::new(BTreeMap::new());
+/// It holds an Arc to the SharedMem. After IPC_RMID removes it from here, the SharedMem is
+/// dropped once all its attachments are gone.
+pub static SHARED_MEMS: SpinNoIrq<ipc::IpcIds<SharedMem>> = SpinNoIrq::new(ipc::IpcIds::new(SHMMNI));
 
 /// PageTable + MemoryArea for a process (task)
 pub struct MemorySet {
     page_table: PageTable,
     owned_mem: BTreeMap<usize, MapArea>,
 
-    private_mem: BTreeMap<i32, Arc<SharedMem>>,
     attached_mem: Vec<(VirtAddr, MappingFlags, Arc<SharedMem>)>,
 }
 
@@ -56,7 +52,6 @@ impl MemorySet {
         Self {
             page_table: PageTable::try_new().expect("Error allocating page table."),
             owned_mem: BTreeMap::new(),
-            private_mem: BTreeMap::new(),
             attached_mem: Vec::new(),
         }
     }
@@ -88,7 +83,6 @@ impl MemorySet {
         Self {
             page_table,
             owned_mem: BTreeMap::new(),
-            private_mem: BTreeMap::new(),
             attached_mem: Vec::new(),
         }
     }
@@ -455,48 +449,9 @@ impl MemorySet {
             .map_err(|_| AxError::InvalidInput)
     }
 
-    /// Create a new SharedMem with given key.
-    /// You need to add the returned SharedMem to global SHARED_MEMS or process's private_mem.
-    ///
-    /// Panics: SharedMem with the key already exist.
-    pub fn create_shared_mem(
-        key: i32,
-        size: usize,
-        pid: u64,
-        uid: u32,
-        gid: u32,
-        mode: u16,
-    ) -> AxResult<(i32, SharedMem)> {
-        let mut key_map = KEY_TO_SHMID.lock();
-
-        let shmid = SHMID.fetch_add(1, Ordering::Release);
-        key_map.insert(key, shmid);
-
-        let mem = SharedMem::try_new(key, size, pid, uid, gid, mode)?;
-
-        Ok((shmid, mem))
-    }
-
-    /// Panics: shmid is already taken.
-    pub fn add_shared_mem(shmid: i32, mem: SharedMem) {
-        let mut mem_map = SHARED_MEMS.lock();
-
-        assert!(mem_map.insert(shmid, Arc::new(mem)).is_none());
-    }
-
-    /// Panics: shmid is already taken in the process.
-    pub fn add_private_shared_mem(&mut self, shmid: i32, mem: SharedMem) {
-        assert!(self.private_mem.insert(shmid, Arc::new(mem)).is_none());
-    }
-
     /// Get a SharedMem by shmid.
     pub fn get_shared_mem(shmid: i32) -> Option<Arc<SharedMem>> {
-        SHARED_MEMS.lock().get(&shmid).cloned()
-    }
-
-    /// Get a private SharedMem by shmid.
-    pub fn get_private_shared_mem(&self, shmid: i32) -> Option<Arc<SharedMem>> {
-        self.private_mem.get(&shmid).cloned()
+        SHARED_MEMS.lock().get(shmid)
     }
 
     /// Attach a SharedMem to the memory set.
@@ -678,7 +633,6 @@ impl MemorySet {
             page_table,
             owned_mem,
 
-            private_mem: self.private_mem.clone(),
             attached_mem: Vec::new(),
         };
 
@@ -2,14 +2,16 @@ use axalloc::GlobalPage;
 use axerrno::AxResult;
 use axhal::{
     mem::{virt_to_phys, PhysAddr, PAGE_SIZE_4K},
-    time::current_time,
+    time::wall_time,
 };
+use spinlock::SpinNoIrq;
+
+use crate::ipc::IpcPerm;
 
-#[allow(dead_code)]
 pub struct SharedMem {
     pages: GlobalPage,
     /// The information of the shared memory.
-    pub info: SharedMemInfo,
+    pub info: SpinNoIrq<SharedMemInfo>,
 }
 
 impl SharedMem {
@@ -27,11 +29,10 @@ impl SharedMem {
         let num_pages = (size + PAGE_SIZE_4K - 1) / PAGE_SIZE_4K;
 
         let pages = GlobalPage::alloc_contiguous(num_pages, PAGE_SIZE_4K)?;
-        let size = pages.size();
 
         Ok(Self {
             pages,
-            info: SharedMemInfo::new(key, size, pid, uid, gid, mode),
+            info: SpinNoIrq::new(SharedMemInfo::new(key, size, pid, uid, gid, mode)),
         })
     }
 
@@ -46,27 +47,19 @@ impl SharedMem {
     }
 }
 
-#[allow(dead_code)]
+/// 共享内存的属性，即 `struct shmid_kernel` 中用户可见的部分
 pub struct SharedMemInfo {
-    perm: SharedMemPermInfo,
-    size: usize,
-
-    a_time: usize,
-    d_time: usize,
-    c_time: usize,
+    pub perm: IpcPerm,
+    pub size: usize,
 
-    c_pid: u64,
-    l_pid: u64,
-}
+    /// 最后一次 attach、detach 与修改的时间，以秒为单位
+    pub a_time: usize,
+    pub d_time: usize,
+    pub c_time: usize,
 
-#[allow(dead_code)]
-pub struct SharedMemPermInfo {
-    key: i32,
-    uid: u32,
-    gid: u32,
-    cuid: u32,
-    cgid: u32,
-    mode: u16,
+    /// 创建者与最后一次 attach 或 detach 的进程
+    pub c_pid: u64,
+    pub l_pid: u64,
 }
 
 impl SharedMemInfo {
@@ -75,18 +68,11 @@ impl SharedMemInfo {
     /// This function should be called by SharedMem::try_new().
     fn new(key: i32, size: usize, pid: u64, uid: u32, gid: u32, mode: u16) -> Self {
         Self {
-            perm: SharedMemPermInfo {
-                key,
-                uid,
-                gid,
-                cuid: uid,
-                cgid: gid,
-                mode,
-            },
+            perm: IpcPerm::new(key, uid, gid, mode),
             size,
             a_time: 0,
             d_time: 0,
-            c_time: current_time().as_secs() as usize,
+            c_time: wall_time().as_secs() as usize,
 
             c_pid: pid,
             l_pid: 0,
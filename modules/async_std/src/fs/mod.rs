@@ -2,6 +2,7 @@
 
 mod dir;
 mod file;
+mod notify;
 
 use crate::io;
 use async_io::Write;
@@ -11,6 +12,7 @@ use alloc::{string::String, vec::Vec};
 
 pub use self::dir::{DirBuilder, DirEntry, ReadDir};
 pub use self::file::{File, FileType, Metadata, OpenOptions, Permissions};
+pub use self::notify::{Event, Inotify, WatchMask};
 
 /// Read the entire contents of a file into a bytes vector.
 #[cfg(feature = "alloc")]
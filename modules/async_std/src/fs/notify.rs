@@ -0,0 +1,64 @@
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::io::Result;
+use async_io::AsyncStream;
+
+use async_api::fs as api;
+
+/// The kinds of events to watch for, and the kind of a received [`Event`].
+pub type WatchMask = api::AxInotifyMask;
+
+/// A file change event produced by an [`Inotify`] instance.
+pub type Event = api::AxNotifyEvent;
+
+/// A stream of file change events on the watched paths.
+///
+/// The stream never ends; it yields a new [`Event`] whenever a watched
+/// file or a file in a watched directory is created, removed, renamed,
+/// read or written.
+pub struct Inotify {
+    inner: api::AxInotifyHandle,
+}
+
+impl Inotify {
+    /// Creates a new inotify instance without any watch.
+    pub fn new() -> Self {
+        Self {
+            inner: api::ax_inotify_init(),
+        }
+    }
+
+    /// Watches the file or directory at `path` for the events in `mask`.
+    ///
+    /// Returns the watch descriptor, which is the `wd` of the events
+    /// produced by this watch.
+    pub async fn add_watch(&self, path: &str, mask: WatchMask) -> Result<i32> {
+        api::ax_inotify_add_watch(&self.inner, path, mask).await
+    }
+
+    /// Removes the watch `wd`. A [`WatchMask::IGNORED`] event is generated.
+    pub fn rm_watch(&self, wd: i32) -> Result<()> {
+        api::ax_inotify_rm_watch(&self.inner, wd)
+    }
+}
+
+impl Default for Inotify {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AsyncStream for Inotify {
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_event(cx).map(Some)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, None)
+    }
+}
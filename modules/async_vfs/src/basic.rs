@@ -8,7 +8,10 @@ use core::ops::{Deref, DerefMut};
 use core::task::{Context, Poll};
 use core::pin::Pin;
 
-use crate::structs::{FileSystemInfo, VfsDirEntry, VfsNodeAttr, VfsNodeType};
+use crate::structs::{
+    FileSystemInfo, VfsDirEntry, VfsNodeAttr, VfsNodeType, VfsSeals, VfsSetAttr,
+    VfsXattrFlags,
+};
 
 /// A wrapper of [`Arc<dyn VfsNodeOps>`].
 pub type VfsNodeRef = Arc<dyn VfsNodeOps + Unpin>;
@@ -133,11 +136,28 @@ pub trait VfsNodeOps: Send + Sync {
         Ok(())
     }
 
+    /// Identifies the file of the node. All nodes of the same file, even
+    /// through different hard links, have the same id, and it does not
+    /// change when the file is renamed.
+    ///
+    /// The default is the address of the node, which is enough for
+    /// filesystems that keep a single node per file. Filesystems that create
+    /// a new node on each lookup must override it.
+    fn node_id(&self) -> u64 {
+        self as *const Self as *const () as usize as u64
+    }
+
     /// Get the attributes of the node.
     fn get_attr(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<VfsNodeAttr>> {
         Poll::Ready(ax_err!(Unsupported))
     }
 
+    /// Change the attributes of the node, such as the permission mode, the
+    /// owner and the timestamps.
+    fn setattr(self: Pin<&Self>, _cx: &mut Context<'_>, _attr: &VfsSetAttr) -> Poll<VfsResult> {
+        Poll::Ready(ax_err!(Unsupported))
+    }
+
     // file operations:
 
     /// Read data from the file at the given offset.
@@ -170,6 +190,117 @@ pub trait VfsNodeOps: Send + Sync {
         Poll::Ready(ax_err!(InvalidInput))
     }
 
+    /// Read the target of the symbolic link into `buf`.
+    ///
+    /// Return the length of the target, or [`InvalidInput`](VfsError::InvalidInput)
+    /// if the node is not a symbolic link.
+    fn readlink(self: Pin<&Self>, _cx: &mut Context<'_>, _buf: &mut [u8]) -> Poll<VfsResult<usize>> {
+        Poll::Ready(ax_err!(InvalidInput))
+    }
+
+    /// Allocate the disk space of the range `[offset, offset + len)`, so that
+    /// later writes to the range will not fail for lack of space.
+    ///
+    /// The file is extended if the range ends beyond its size, unless
+    /// `keep_size` is set.
+    fn allocate(
+        self: Pin<&Self>, 
+        _cx: &mut Context<'_>, 
+        _offset: u64, 
+        _len: u64, 
+        _keep_size: bool
+    ) -> Poll<VfsResult> {
+        Poll::Ready(ax_err!(Unsupported))
+    }
+
+    /// Deallocate the range `[offset, offset + len)`, which is read as zeros
+    /// afterwards. The size of the file is not changed.
+    fn punch_hole(self: Pin<&Self>, _cx: &mut Context<'_>, _offset: u64, _len: u64) -> Poll<VfsResult> {
+        Poll::Ready(ax_err!(Unsupported))
+    }
+
+    /// Find the start of the next data region (or hole, if `hole` is set) at
+    /// or after `offset`.
+    ///
+    /// Return `None` if there is no more data, or `offset` is beyond the end
+    /// of the file. By default the whole file is treated as data, followed
+    /// by the implicit hole at the end of the file.
+    fn seek_data(
+        self: Pin<&Self>, 
+        cx: &mut Context<'_>, 
+        offset: u64, 
+        hole: bool
+    ) -> Poll<VfsResult<Option<u64>>> {
+        self.get_attr(cx).map(|attr| {
+            let size = attr?.size();
+            Ok(match (offset < size, hole) {
+                (false, _) => None,
+                (true, false) => Some(offset),
+                (true, true) => Some(size),
+            })
+        })
+    }
+
+    /// Read the value of the extended attribute `name` into `buf`.
+    ///
+    /// Return the length of the value, which is copied into `buf` only if it
+    /// fits. Return [`NotFound`](VfsError::NotFound) if there is no such
+    /// attribute.
+    fn getxattr(
+        self: Pin<&Self>, 
+        _cx: &mut Context<'_>, 
+        _name: &str, 
+        _buf: &mut [u8]
+    ) -> Poll<VfsResult<usize>> {
+        Poll::Ready(ax_err!(Unsupported))
+    }
+
+    /// Set the value of the extended attribute `name`.
+    ///
+    /// With [`VfsXattrFlags::CREATE`] it fails with
+    /// [`AlreadyExists`](VfsError::AlreadyExists) if the attribute exists;
+    /// with [`VfsXattrFlags::REPLACE`] it fails with
+    /// [`NotFound`](VfsError::NotFound) if the attribute does not exist.
+    fn setxattr(
+        self: Pin<&Self>, 
+        _cx: &mut Context<'_>, 
+        _name: &str, 
+        _value: &[u8], 
+        _flags: VfsXattrFlags
+    ) -> Poll<VfsResult> {
+        Poll::Ready(ax_err!(Unsupported))
+    }
+
+    /// Read the names of all extended attributes into `buf`, each followed
+    /// by a `'\0'`.
+    ///
+    /// Return the total length of the names, which are copied into `buf`
+    /// only if they fit.
+    fn listxattr(self: Pin<&Self>, _cx: &mut Context<'_>, _buf: &mut [u8]) -> Poll<VfsResult<usize>> {
+        Poll::Ready(ax_err!(Unsupported))
+    }
+
+    /// Remove the extended attribute `name`.
+    fn removexattr(self: Pin<&Self>, _cx: &mut Context<'_>, _name: &str) -> Poll<VfsResult> {
+        Poll::Ready(ax_err!(Unsupported))
+    }
+
+    /// Get the seals of the file.
+    ///
+    /// Return [`Unsupported`](VfsError::Unsupported) if the file system does
+    /// not support sealing.
+    fn get_seals(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<VfsSeals>> {
+        Poll::Ready(ax_err!(Unsupported))
+    }
+
+    /// Add `seals` to the seals of the file.
+    ///
+    /// Fail with [`PermissionDenied`](VfsError::PermissionDenied) if the file
+    /// has been sealed with [`VfsSeals::SEAL`].
+    fn add_seals(self: Pin<&Self>, _cx: &mut Context<'_>, _seals: VfsSeals) -> Poll<VfsResult> {
+        Poll::Ready(ax_err!(Unsupported))
+    }
+
     // directory operations:
 
     /// Get the parent directory of this directory.
@@ -218,6 +349,28 @@ pub trait VfsNodeOps: Send + Sync {
         Poll::Ready(ax_err!(Unsupported))
     }
 
+    /// Create a hard link with the given `path` in the directory, which
+    /// refers to the existing `node`.
+    fn link(
+        self: Pin<&Self>, 
+        _cx: &mut Context<'_>, 
+        _path: &str, 
+        _node: VfsNodeRef
+    ) -> Poll<VfsResult> {
+        Poll::Ready(ax_err!(Unsupported))
+    }
+
+    /// Create a symbolic link with the given `path` in the directory, whose
+    /// content is `target`.
+    fn symlink(
+        self: Pin<&Self>, 
+        _cx: &mut Context<'_>, 
+        _path: &str, 
+        _target: &str
+    ) -> Poll<VfsResult> {
+        Poll::Ready(ax_err!(Unsupported))
+    }
+
     /// Convert `&self` to [`&dyn Any`][1] that can use
     /// [`Any::downcast_ref`][2].
     ///
@@ -300,6 +453,93 @@ macro_rules! deref_async_vfsnodeops {
             Pin::new(&**self).rename(cx, src_path, dst_path)
         }
 
+        fn readlink(self: Pin<&Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<VfsResult<usize>> {
+            Pin::new(&**self).readlink(cx, buf)
+        }
+
+        fn allocate(
+            self: Pin<&Self>, 
+            cx: &mut Context<'_>, 
+            offset: u64, 
+            len: u64, 
+            keep_size: bool
+        ) -> Poll<VfsResult> {
+            Pin::new(&**self).allocate(cx, offset, len, keep_size)
+        }
+
+        fn punch_hole(self: Pin<&Self>, cx: &mut Context<'_>, offset: u64, len: u64) -> Poll<VfsResult> {
+            Pin::new(&**self).punch_hole(cx, offset, len)
+        }
+
+        fn seek_data(
+            self: Pin<&Self>, 
+            cx: &mut Context<'_>, 
+            offset: u64, 
+            hole: bool
+        ) -> Poll<VfsResult<Option<u64>>> {
+            Pin::new(&**self).seek_data(cx, offset, hole)
+        }
+
+        fn getxattr(
+            self: Pin<&Self>, 
+            cx: &mut Context<'_>, 
+            name: &str, 
+            buf: &mut [u8]
+        ) -> Poll<VfsResult<usize>> {
+            Pin::new(&**self).getxattr(cx, name, buf)
+        }
+
+        fn setxattr(
+            self: Pin<&Self>, 
+            cx: &mut Context<'_>, 
+            name: &str, 
+            value: &[u8], 
+            flags: VfsXattrFlags
+        ) -> Poll<VfsResult> {
+            Pin::new(&**self).setxattr(cx, name, value, flags)
+        }
+
+        fn listxattr(self: Pin<&Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<VfsResult<usize>> {
+            Pin::new(&**self).listxattr(cx, buf)
+        }
+
+        fn removexattr(self: Pin<&Self>, cx: &mut Context<'_>, name: &str) -> Poll<VfsResult> {
+            Pin::new(&**self).removexattr(cx, name)
+        }
+
+        fn get_seals(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<VfsResult<VfsSeals>> {
+            Pin::new(&**self).get_seals(cx)
+        }
+
+        fn add_seals(self: Pin<&Self>, cx: &mut Context<'_>, seals: VfsSeals) -> Poll<VfsResult> {
+            Pin::new(&**self).add_seals(cx, seals)
+        }
+
+        fn setattr(self: Pin<&Self>, cx: &mut Context<'_>, attr: &VfsSetAttr) -> Poll<VfsResult> {
+            Pin::new(&**self).setattr(cx, attr)
+        }
+
+        fn link(
+            self: Pin<&Self>, 
+            cx: &mut Context<'_>, 
+            path: &str, 
+            node: VfsNodeRef
+        ) -> Poll<VfsResult> {
+            Pin::new(&**self).link(cx, path, node)
+        }
+
+        fn symlink(
+            self: Pin<&Self>, 
+            cx: &mut Context<'_>, 
+            path: &str, 
+            target: &str
+        ) -> Poll<VfsResult> {
+            Pin::new(&**self).symlink(cx, path, target)
+        }
+
+        fn node_id(&self) -> u64 {
+            (**self).node_id()
+        }
     };
 }
 
@@ -387,4 +627,92 @@ where
     ) -> Poll<VfsResult> {
         self.get_ref().as_ref().rename(cx, src_path, dst_path)
     }
+
+    fn readlink(self: Pin<&Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<VfsResult<usize>> {
+        self.get_ref().as_ref().readlink(cx, buf)
+    }
+
+    fn allocate(
+        self: Pin<&Self>, 
+        cx: &mut Context<'_>, 
+        offset: u64, 
+        len: u64, 
+        keep_size: bool
+    ) -> Poll<VfsResult> {
+        self.get_ref().as_ref().allocate(cx, offset, len, keep_size)
+    }
+
+    fn punch_hole(self: Pin<&Self>, cx: &mut Context<'_>, offset: u64, len: u64) -> Poll<VfsResult> {
+        self.get_ref().as_ref().punch_hole(cx, offset, len)
+    }
+
+    fn seek_data(
+        self: Pin<&Self>, 
+        cx: &mut Context<'_>, 
+        offset: u64, 
+        hole: bool
+    ) -> Poll<VfsResult<Option<u64>>> {
+        self.get_ref().as_ref().seek_data(cx, offset, hole)
+    }
+
+    fn getxattr(
+        self: Pin<&Self>, 
+        cx: &mut Context<'_>, 
+        name: &str, 
+        buf: &mut [u8]
+    ) -> Poll<VfsResult<usize>> {
+        self.get_ref().as_ref().getxattr(cx, name, buf)
+    }
+
+    fn setxattr(
+        self: Pin<&Self>, 
+        cx: &mut Context<'_>, 
+        name: &str, 
+        value: &[u8], 
+        flags: VfsXattrFlags
+    ) -> Poll<VfsResult> {
+        self.get_ref().as_ref().setxattr(cx, name, value, flags)
+    }
+
+    fn listxattr(self: Pin<&Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<VfsResult<usize>> {
+        self.get_ref().as_ref().listxattr(cx, buf)
+    }
+
+    fn removexattr(self: Pin<&Self>, cx: &mut Context<'_>, name: &str) -> Poll<VfsResult> {
+        self.get_ref().as_ref().removexattr(cx, name)
+    }
+
+    fn get_seals(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<VfsResult<VfsSeals>> {
+        self.get_ref().as_ref().get_seals(cx)
+    }
+
+    fn add_seals(self: Pin<&Self>, cx: &mut Context<'_>, seals: VfsSeals) -> Poll<VfsResult> {
+        self.get_ref().as_ref().add_seals(cx, seals)
+    }
+
+    fn setattr(self: Pin<&Self>, cx: &mut Context<'_>, attr: &VfsSetAttr) -> Poll<VfsResult> {
+        self.get_ref().as_ref().setattr(cx, attr)
+    }
+
+    fn link(
+        self: Pin<&Self>, 
+        cx: &mut Context<'_>, 
+        path: &str, 
+        node: VfsNodeRef
+    ) -> Poll<VfsResult> {
+        self.get_ref().as_ref().link(cx, path, node)
+    }
+
+    fn symlink(
+        self: Pin<&Self>, 
+        cx: &mut Context<'_>, 
+        path: &str, 
+        target: &str
+    ) -> Poll<VfsResult> {
+        self.get_ref().as_ref().symlink(cx, path, target)
+    }
+
+    fn node_id(&self) -> u64 {
+        (**self).node_id()
+    }
 }
@@ -17,6 +17,19 @@
 //!         12. read_dir
 //!         13. rename
 //!         14. as_any
+//!         15. readlink
+//!         16. link
+//!         17. symlink
+//!         18. setattr
+//!         19. allocate
+//!         20. punch_hole
+//!         21. seek_data
+//!         22. getxattr
+//!         23. setxattr
+//!         24. listxattr
+//!         25. removexattr
+//!         26. get_seals
+//!         27. add_seals
 //!     2. VfsOps trait：定义了文件系统的接口
 //!         1. mount
 //!         2. format
@@ -29,7 +42,9 @@
 //! 
 //! path.rs 中提供了路径解析函数
 //! 
-//! structs.rs 中定义了 VfsDirEntry、VfsNodeAttr、VfsNodePerm、VfsNodeType、FileSystemInfo 等结构体
+//! walk.rs 中提供了跟随符号链接、跨越挂载点的异步路径解析，以及目录项缓存
+//! 
+//! structs.rs 中定义了 VfsDirEntry、VfsNodeAttr、VfsSetAttr、VfsNodePerm、VfsNodeType、VfsXattrFlags、VfsSeals、FileSystemInfo 等结构体
 //! 
 //! macros.rs 中定义了一些宏，给普通文件提供与目录操作相关的接口的虚拟实现，给目录文件提供与普通文件相关的接口的虚拟实现
 #![cfg_attr(not(test), no_std)]
@@ -41,11 +56,15 @@ mod macros;
 mod structs;
 
 pub mod path;
+pub mod walk;
 mod basic;
 mod vfs;
 mod vfs_node;
 
-pub use crate::structs::{FileSystemInfo, VfsDirEntry, VfsNodeAttr, VfsNodePerm, VfsNodeType};
+pub use crate::structs::{
+    FileSystemInfo, VfsAccess, VfsDirEntry, VfsNodeAttr, VfsNodePerm, VfsNodeType, VfsSetAttr,
+    VfsSeals, VfsXattrFlags,
+};
 pub use basic::{VfsOps, VfsError, VfsNodeOps, VfsNodeRef, VfsResult};
 pub use vfs::AsyncVfsOps;
 pub use vfs_node::AsyncVfsNodeOps;
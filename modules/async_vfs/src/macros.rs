@@ -38,6 +38,34 @@ macro_rules! impl_vfs_dir_default {
             core::task::Poll::Ready($crate::__priv::ax_err!(IsADirectory))
         }
 
+        fn allocate(
+            self: core::pin::Pin<&Self>, 
+            _cx: &mut core::task::Context<'_>, 
+            _offset: u64, 
+            _len: u64, 
+            _keep_size: bool
+        ) -> core::task::Poll<$crate::VfsResult> {
+            core::task::Poll::Ready($crate::__priv::ax_err!(IsADirectory))
+        }
+
+        fn punch_hole(
+            self: core::pin::Pin<&Self>, 
+            _cx: &mut core::task::Context<'_>, 
+            _offset: u64, 
+            _len: u64
+        ) -> core::task::Poll<$crate::VfsResult> {
+            core::task::Poll::Ready($crate::__priv::ax_err!(IsADirectory))
+        }
+
+        fn seek_data(
+            self: core::pin::Pin<&Self>, 
+            _cx: &mut core::task::Context<'_>, 
+            _offset: u64, 
+            _hole: bool
+        ) -> core::task::Poll<$crate::VfsResult<Option<u64>>> {
+            core::task::Poll::Ready($crate::__priv::ax_err!(IsADirectory))
+        }
+
         #[inline]
         fn as_any(&self) -> &dyn core::any::Any {
             self
@@ -87,6 +115,24 @@ macro_rules! impl_vfs_non_dir_default {
             core::task::Poll::Ready($crate::__priv::ax_err!(NotADirectory))
         }
 
+        fn link(
+            self: core::pin::Pin<&Self>, 
+            _cx: &mut core::task::Context<'_>, 
+            _path: &str, 
+            _node: $crate::VfsNodeRef
+        ) -> core::task::Poll<$crate::VfsResult> {
+            core::task::Poll::Ready($crate::__priv::ax_err!(NotADirectory))
+        }
+
+        fn symlink(
+            self: core::pin::Pin<&Self>, 
+            _cx: &mut core::task::Context<'_>, 
+            _path: &str, 
+            _target: &str
+        ) -> core::task::Poll<$crate::VfsResult> {
+            core::task::Poll::Ready($crate::__priv::ax_err!(NotADirectory))
+        }
+
         #[inline]
         fn as_any(&self) -> &dyn core::any::Any {
             self
@@ -1,8 +1,50 @@
-/// Filesystem attributes.
+use core::time::Duration;
+
+/// Filesystem attributes, returned by [`VfsOps::statfs`].
+///
+/// Block counts are in units of `block_size`. Filesystems that do not track
+/// a quantity leave it as zero, as Linux does for e.g. ramfs.
 ///
-/// Currently not used.
-#[non_exhaustive]
-pub struct FileSystemInfo;
+/// [`VfsOps::statfs`]: crate::VfsOps::statfs
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileSystemInfo {
+    /// Type of the filesystem, the same magic number as Linux (`*_SUPER_MAGIC`).
+    pub fs_type: u64,
+    /// Optimal transfer block size.
+    pub block_size: u64,
+    /// Total data blocks in the filesystem.
+    pub blocks: u64,
+    /// Free blocks in the filesystem.
+    pub blocks_free: u64,
+    /// Free blocks available to unprivileged users.
+    pub blocks_avail: u64,
+    /// Total inodes in the filesystem.
+    pub files: u64,
+    /// Free inodes in the filesystem.
+    pub files_free: u64,
+    /// Maximum length of a file name.
+    pub name_len: u64,
+    /// Mount flags (`ST_*`), filled in by the mount layer.
+    pub flags: u64,
+}
+
+impl FileSystemInfo {
+    /// Creates a `FileSystemInfo` with the given type and block size, and
+    /// all counts zero.
+    pub const fn new(fs_type: u64, block_size: u64, name_len: u64) -> Self {
+        Self {
+            fs_type,
+            block_size,
+            blocks: 0,
+            blocks_free: 0,
+            blocks_avail: 0,
+            files: 0,
+            files_free: 0,
+            name_len,
+            flags: 0,
+        }
+    }
+}
 
 /// Node (file/directory) attributes.
 #[allow(dead_code)]
@@ -16,6 +58,36 @@ pub struct VfsNodeAttr {
     size: u64,
     /// Number of 512B blocks allocated.
     blocks: u64,
+    /// Number of hard links.
+    nlink: u64,
+    /// User id of the owner.
+    uid: u32,
+    /// Group id of the owner.
+    gid: u32,
+    /// Time of last access, since the epoch.
+    atime: Duration,
+    /// Time of last modification, since the epoch.
+    mtime: Duration,
+    /// Time of last status change, since the epoch.
+    ctime: Duration,
+}
+
+/// Attributes to be changed by [`VfsNodeOps::setattr`], `None` fields are
+/// left unchanged.
+///
+/// [`VfsNodeOps::setattr`]: crate::VfsNodeOps::setattr
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VfsSetAttr {
+    /// New permission mode.
+    pub mode: Option<VfsNodePerm>,
+    /// New owner.
+    pub uid: Option<u32>,
+    /// New group.
+    pub gid: Option<u32>,
+    /// New time of last access.
+    pub atime: Option<Duration>,
+    /// New time of last modification.
+    pub mtime: Option<Duration>,
 }
 
 bitflags::bitflags! {
@@ -42,6 +114,58 @@ bitflags::bitflags! {
         const OTHER_WRITE = 0o2;
         /// Others have execute permission.
         const OTHER_EXEC = 0o1;
+
+        /// Set user id on execution.
+        const SET_UID = 0o4000;
+        /// Set group id on execution.
+        const SET_GID = 0o2000;
+        /// Only the owner can remove or rename entries in the directory.
+        const STICKY = 0o1000;
+    }
+}
+
+bitflags::bitflags! {
+    /// Access to be checked by [`VfsNodeAttr::permits`], the same as the
+    /// `R_OK`, `W_OK` and `X_OK` of `access(2)`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct VfsAccess: u8 {
+        /// Read access.
+        const READ = 4;
+        /// Write access.
+        const WRITE = 2;
+        /// Execute access for a file, or search access for a directory.
+        const EXEC = 1;
+    }
+}
+
+bitflags::bitflags! {
+    /// Flags of [`VfsNodeOps::setxattr`](crate::VfsNodeOps::setxattr), the
+    /// same as the `XATTR_CREATE` and `XATTR_REPLACE` of `setxattr(2)`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct VfsXattrFlags: u32 {
+        /// Fail if the attribute already exists.
+        const CREATE = 1;
+        /// Fail if the attribute does not exist.
+        const REPLACE = 2;
+    }
+}
+
+bitflags::bitflags! {
+    /// Seals of a file, the same as the `F_SEAL_*` of `fcntl(2)`. Each seal
+    /// forbids a kind of modification, and can never be removed.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct VfsSeals: u32 {
+        /// No more seals can be added.
+        const SEAL = 0x1;
+        /// The file cannot be shrunk.
+        const SHRINK = 0x2;
+        /// The file cannot be grown.
+        const GROW = 0x4;
+        /// The content of the file cannot be modified.
+        const WRITE = 0x8;
+        /// The same as `WRITE`, but the existing shared writable mappings
+        /// are still allowed to modify the content.
+        const FUTURE_WRITE = 0x10;
     }
 }
 
@@ -205,6 +329,12 @@ impl VfsNodeAttr {
             ty,
             size,
             blocks,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            atime: Duration::ZERO,
+            mtime: Duration::ZERO,
+            ctime: Duration::ZERO,
         }
     }
 
@@ -215,6 +345,12 @@ impl VfsNodeAttr {
             ty: VfsNodeType::File,
             size,
             blocks,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            atime: Duration::ZERO,
+            mtime: Duration::ZERO,
+            ctime: Duration::ZERO,
         }
     }
 
@@ -226,6 +362,12 @@ impl VfsNodeAttr {
             ty: VfsNodeType::Dir,
             size,
             blocks,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            atime: Duration::ZERO,
+            mtime: Duration::ZERO,
+            ctime: Duration::ZERO,
         }
     }
 
@@ -239,6 +381,16 @@ impl VfsNodeAttr {
         self.blocks
     }
 
+    /// Returns the number of hard links to the node.
+    pub const fn nlink(&self) -> u64 {
+        self.nlink
+    }
+
+    /// Sets the number of hard links to the node.
+    pub fn set_nlink(&mut self, nlink: u64) {
+        self.nlink = nlink
+    }
+
     /// Returns the permission of the node.
     pub const fn perm(&self) -> VfsNodePerm {
         self.mode
@@ -263,6 +415,69 @@ impl VfsNodeAttr {
     pub const fn is_dir(&self) -> bool {
         self.ty.is_dir()
     }
+
+    /// Whether the node is a symbolic link.
+    pub const fn is_symlink(&self) -> bool {
+        self.ty.is_symlink()
+    }
+
+    /// Returns the user id of the owner.
+    pub const fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// Returns the group id of the owner.
+    pub const fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    /// Sets the owner and the group of the node.
+    pub fn set_owner(&mut self, uid: u32, gid: u32) {
+        self.uid = uid;
+        self.gid = gid;
+    }
+
+    /// Returns the time of last access.
+    pub const fn atime(&self) -> Duration {
+        self.atime
+    }
+
+    /// Returns the time of last modification.
+    pub const fn mtime(&self) -> Duration {
+        self.mtime
+    }
+
+    /// Returns the time of last status change.
+    pub const fn ctime(&self) -> Duration {
+        self.ctime
+    }
+
+    /// Sets the time of last access, modification and status change.
+    pub fn set_times(&mut self, atime: Duration, mtime: Duration, ctime: Duration) {
+        self.atime = atime;
+        self.mtime = mtime;
+        self.ctime = ctime;
+    }
+
+    /// Whether a caller with user id `uid` and group id `gid` is granted
+    /// `access` to the node.
+    ///
+    /// root 用户可以读写任何节点，但只有当任意一个执行位被设置时才能执行文件
+    pub fn permits(&self, uid: u32, gid: u32, access: VfsAccess) -> bool {
+        let mode = self.mode.bits() as u32;
+        if uid == 0 {
+            return !access.contains(VfsAccess::EXEC) || self.ty.is_dir() || mode & 0o111 != 0;
+        }
+        let class = if uid == self.uid {
+            mode >> 6
+        } else if gid == self.gid {
+            mode >> 3
+        } else {
+            mode
+        };
+        let granted = class & 0o7;
+        granted & access.bits() as u32 == access.bits() as u32
+    }
 }
 
 impl VfsDirEntry {
@@ -303,3 +518,35 @@ impl VfsDirEntry {
         &self.d_name[..len]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attr(mode: u16, ty: VfsNodeType, uid: u32, gid: u32) -> VfsNodeAttr {
+        let mut attr = VfsNodeAttr::new(VfsNodePerm::from_bits_truncate(mode), ty, 0, 0);
+        attr.set_owner(uid, gid);
+        attr
+    }
+
+    #[test]
+    fn permits_owner_group_other() {
+        let file = attr(0o640, VfsNodeType::File, 1000, 100);
+        assert!(file.permits(1000, 100, VfsAccess::READ | VfsAccess::WRITE));
+        assert!(file.permits(1001, 100, VfsAccess::READ));
+        assert!(!file.permits(1001, 100, VfsAccess::WRITE));
+        assert!(!file.permits(1001, 101, VfsAccess::READ));
+        // the owner class is used even if it grants less than the group class
+        let file = attr(0o070, VfsNodeType::File, 1000, 100);
+        assert!(!file.permits(1000, 100, VfsAccess::READ));
+    }
+
+    #[test]
+    fn permits_root() {
+        let file = attr(0o000, VfsNodeType::File, 1000, 100);
+        assert!(file.permits(0, 0, VfsAccess::READ | VfsAccess::WRITE));
+        assert!(!file.permits(0, 0, VfsAccess::EXEC));
+        assert!(attr(0o001, VfsNodeType::File, 1000, 100).permits(0, 0, VfsAccess::EXEC));
+        assert!(attr(0o000, VfsNodeType::Dir, 1000, 100).permits(0, 0, VfsAccess::EXEC));
+    }
+}
@@ -0,0 +1,21 @@
+use core::pin::Pin;
+use core::future::Future;
+use core::task::{Context, Poll};
+
+use crate::{VfsNodeOps, VfsResult, VfsSeals};
+
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct AddSealsFuture<'a, T: Unpin + ?Sized> {
+    pub(crate) vnode: &'a T,
+    pub(crate) seals: VfsSeals
+}
+
+impl<T: VfsNodeOps + Unpin + ?Sized> Future for AddSealsFuture<'_, T> {
+    type Output = VfsResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Self { vnode, seals } = self.get_mut();
+        Pin::new(*vnode).add_seals(cx, *seals)
+    }
+}
@@ -0,0 +1,23 @@
+use core::pin::Pin;
+use core::future::Future;
+use core::task::{Context, Poll};
+
+use crate::{VfsNodeOps, VfsResult};
+
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct AllocateFuture<'a, T: Unpin + ?Sized> {
+    pub(crate) vnode: &'a T,
+    pub(crate) offset: u64,
+    pub(crate) len: u64,
+    pub(crate) keep_size: bool
+}
+
+impl<T: VfsNodeOps + Unpin + ?Sized> Future for AllocateFuture<'_, T> {
+    type Output = VfsResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Self { vnode, offset, len, keep_size } = self.get_mut();
+        Pin::new(*vnode).allocate(cx, *offset, *len, *keep_size)
+    }
+}
@@ -0,0 +1,20 @@
+use core::pin::Pin;
+use core::future::Future;
+use core::task::{Context, Poll};
+
+use crate::{VfsNodeOps, VfsResult, VfsSeals};
+
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct GetSealsFuture<'a, T: Unpin + ?Sized> {
+    pub(crate) vnode: &'a T
+}
+
+impl<T: VfsNodeOps + Unpin + ?Sized> Future for GetSealsFuture<'_, T> {
+    type Output = VfsResult<VfsSeals>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Self { vnode } = self.get_mut();
+        Pin::new(*vnode).get_seals(cx)
+    }
+}
@@ -0,0 +1,22 @@
+use core::pin::Pin;
+use core::future::Future;
+use core::task::{Context, Poll};
+
+use crate::{VfsNodeOps, VfsNodeRef, VfsResult};
+
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct LinkFuture<'a, T: Unpin + ?Sized> {
+    pub(crate) vnode: &'a T,
+    pub(crate) path: &'a str, 
+    pub(crate) node: VfsNodeRef,
+}
+
+impl<T: VfsNodeOps + Unpin + ?Sized> Future for LinkFuture<'_, T> {
+    type Output = VfsResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Self { vnode, path, node } = self.get_mut();
+        Pin::new(*vnode).link(cx, path, node.clone())
+    }
+}
@@ -0,0 +1,21 @@
+use core::pin::Pin;
+use core::future::Future;
+use core::task::{Context, Poll};
+
+use crate::{VfsNodeOps, VfsResult};
+
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct ListxattrFuture<'a, T: Unpin + ?Sized> {
+    pub(crate) vnode: &'a T,
+    pub(crate) buf: &'a mut [u8]
+}
+
+impl<T: VfsNodeOps + Unpin + ?Sized> Future for ListxattrFuture<'_, T> {
+    type Output = VfsResult<usize>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Self { vnode, buf } = self.get_mut();
+        Pin::new(*vnode).listxattr(cx, buf)
+    }
+}
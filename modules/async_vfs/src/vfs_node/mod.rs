@@ -1,28 +1,56 @@
+use add_seals::AddSealsFuture;
+use allocate::AllocateFuture;
 use create::CreateFuture;
 use fsync::FsyncFuture;
 use get_attr::GetAttrFuture;
+use get_seals::GetSealsFuture;
+use getxattr::GetxattrFuture;
+use link::LinkFuture;
+use listxattr::ListxattrFuture;
 use lookup::LookupFuture;
 use open::OpenFuture;
 use parent::ParentFuture;
+use punch_hole::PunchHoleFuture;
 use read_at::ReadAtFuture;
 use read_dir::ReadDirFuture;
+use readlink::ReadlinkFuture;
 use remove::RemoveFuture;
+use removexattr::RemovexattrFuture;
 use rename::RenameFuture;
+use seek_data::SeekDataFuture;
+use setattr::SetattrFuture;
+use setxattr::SetxattrFuture;
+use symlink::SymlinkFuture;
 use truncate::TruncateFuture;
 use write_at::WriteAtFuture;
 
-use crate::{VfsDirEntry, VfsNodeOps, VfsNodeType};
+use crate::{
+    VfsDirEntry, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsSeals, VfsSetAttr, VfsXattrFlags,
+};
 
+mod add_seals;
+mod allocate;
 mod create;
 mod fsync;
 mod get_attr;
+mod get_seals;
+mod getxattr;
+mod link;
+mod listxattr;
 mod lookup;
 mod open;
 mod parent;
+mod punch_hole;
 mod read_at;
 mod read_dir;
+mod readlink;
 mod remove;
+mod removexattr;
 mod rename;
+mod seek_data;
+mod setattr;
+mod setxattr;
+mod symlink;
 mod truncate;
 mod write_at;
 
@@ -43,6 +71,14 @@ pub trait AsyncVfsNodeOps: VfsNodeOps {
         GetAttrFuture { vnode: self}
     }
 
+    /// Change the attributes of the node.
+    fn setattr<'a>(self: &'a Self, attr: &'a VfsSetAttr) -> SetattrFuture<'a, Self> 
+    where 
+        Self: Unpin
+    {
+        SetattrFuture { vnode: self, attr }
+    }
+
     // file operations:
 
     /// Read data from the file at the given offset.
@@ -85,6 +121,88 @@ pub trait AsyncVfsNodeOps: VfsNodeOps {
         TruncateFuture { vnode: self, size }
     }
 
+    /// Allocate the disk space of the range `[offset, offset + len)`.
+    fn allocate<'a>(
+        self: &'a Self, 
+        offset: u64, 
+        len: u64, 
+        keep_size: bool
+    ) -> AllocateFuture<'a, Self> 
+    where 
+        Self: Unpin
+    {
+        AllocateFuture { vnode: self, offset, len, keep_size }
+    }
+
+    /// Deallocate the range `[offset, offset + len)`.
+    fn punch_hole<'a>(self: &'a Self, offset: u64, len: u64) -> PunchHoleFuture<'a, Self> 
+    where 
+        Self: Unpin
+    {
+        PunchHoleFuture { vnode: self, offset, len }
+    }
+
+    /// Find the start of the next data region (or hole) at or after `offset`.
+    fn seek_data<'a>(self: &'a Self, offset: u64, hole: bool) -> SeekDataFuture<'a, Self> 
+    where 
+        Self: Unpin
+    {
+        SeekDataFuture { vnode: self, offset, hole }
+    }
+
+    /// Read the value of the extended attribute `name` into `buf`.
+    fn getxattr<'a>(self: &'a Self, name: &'a str, buf: &'a mut [u8]) -> GetxattrFuture<'a, Self> 
+    where 
+        Self: Unpin
+    {
+        GetxattrFuture { vnode: self, name, buf }
+    }
+
+    /// Set the value of the extended attribute `name`.
+    fn setxattr<'a>(
+        self: &'a Self, 
+        name: &'a str, 
+        value: &'a [u8], 
+        flags: VfsXattrFlags
+    ) -> SetxattrFuture<'a, Self> 
+    where 
+        Self: Unpin
+    {
+        SetxattrFuture { vnode: self, name, value, flags }
+    }
+
+    /// Read the names of all extended attributes into `buf`.
+    fn listxattr<'a>(self: &'a Self, buf: &'a mut [u8]) -> ListxattrFuture<'a, Self> 
+    where 
+        Self: Unpin
+    {
+        ListxattrFuture { vnode: self, buf }
+    }
+
+    /// Remove the extended attribute `name`.
+    fn removexattr<'a>(self: &'a Self, name: &'a str) -> RemovexattrFuture<'a, Self> 
+    where 
+        Self: Unpin
+    {
+        RemovexattrFuture { vnode: self, name }
+    }
+
+    /// Get the seals of the file.
+    fn get_seals<'a>(self: &'a Self) -> GetSealsFuture<'a, Self> 
+    where 
+        Self: Unpin
+    {
+        GetSealsFuture { vnode: self }
+    }
+
+    /// Add `seals` to the seals of the file.
+    fn add_seals<'a>(self: &'a Self, seals: VfsSeals) -> AddSealsFuture<'a, Self> 
+    where 
+        Self: Unpin
+    {
+        AddSealsFuture { vnode: self, seals }
+    }
+
     // directory operations:
 
     /// Get the parent directory of this directory.
@@ -149,6 +267,32 @@ pub trait AsyncVfsNodeOps: VfsNodeOps {
         RenameFuture { vnode: self, src_path, dst_path }
     }
 
+    /// Read the target of the symbolic link into `buf`.
+    fn readlink<'a>(self: &'a Self, buf: &'a mut [u8]) -> ReadlinkFuture<'a, Self> 
+    where 
+        Self: Unpin
+    {
+        ReadlinkFuture { vnode: self, buf }
+    }
+
+    /// Create a hard link with the given `path` in the directory, which
+    /// refers to the existing `node`.
+    fn link<'a>(self: &'a Self, path: &'a str, node: VfsNodeRef) -> LinkFuture<'a, Self> 
+    where 
+        Self: Unpin
+    {
+        LinkFuture { vnode: self, path, node }
+    }
+
+    /// Create a symbolic link with the given `path` in the directory, whose
+    /// content is `target`.
+    fn symlink<'a>(self: &'a Self, path: &'a str, target: &'a str) -> SymlinkFuture<'a, Self> 
+    where 
+        Self: Unpin
+    {
+        SymlinkFuture { vnode: self, path, target }
+    }
+
 }
 
 impl<T: VfsNodeOps + ?Sized> AsyncVfsNodeOps for T {}
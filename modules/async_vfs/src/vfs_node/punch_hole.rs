@@ -0,0 +1,22 @@
+use core::pin::Pin;
+use core::future::Future;
+use core::task::{Context, Poll};
+
+use crate::{VfsNodeOps, VfsResult};
+
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct PunchHoleFuture<'a, T: Unpin + ?Sized> {
+    pub(crate) vnode: &'a T,
+    pub(crate) offset: u64,
+    pub(crate) len: u64
+}
+
+impl<T: VfsNodeOps + Unpin + ?Sized> Future for PunchHoleFuture<'_, T> {
+    type Output = VfsResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Self { vnode, offset, len } = self.get_mut();
+        Pin::new(*vnode).punch_hole(cx, *offset, *len)
+    }
+}
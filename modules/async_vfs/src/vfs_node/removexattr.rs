@@ -0,0 +1,21 @@
+use core::pin::Pin;
+use core::future::Future;
+use core::task::{Context, Poll};
+
+use crate::{VfsNodeOps, VfsResult};
+
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct RemovexattrFuture<'a, T: Unpin + ?Sized> {
+    pub(crate) vnode: &'a T,
+    pub(crate) name: &'a str
+}
+
+impl<T: VfsNodeOps + Unpin + ?Sized> Future for RemovexattrFuture<'_, T> {
+    type Output = VfsResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Self { vnode, name } = self.get_mut();
+        Pin::new(*vnode).removexattr(cx, name)
+    }
+}
@@ -0,0 +1,22 @@
+use core::pin::Pin;
+use core::future::Future;
+use core::task::{Context, Poll};
+
+use crate::{VfsNodeOps, VfsResult};
+
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct SeekDataFuture<'a, T: Unpin + ?Sized> {
+    pub(crate) vnode: &'a T,
+    pub(crate) offset: u64,
+    pub(crate) hole: bool
+}
+
+impl<T: VfsNodeOps + Unpin + ?Sized> Future for SeekDataFuture<'_, T> {
+    type Output = VfsResult<Option<u64>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Self { vnode, offset, hole } = self.get_mut();
+        Pin::new(*vnode).seek_data(cx, *offset, *hole)
+    }
+}
@@ -0,0 +1,21 @@
+use core::pin::Pin;
+use core::future::Future;
+use core::task::{Context, Poll};
+
+use crate::{VfsNodeOps, VfsResult, VfsSetAttr};
+
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct SetattrFuture<'a, T: Unpin + ?Sized> {
+    pub(crate) vnode: &'a T,
+    pub(crate) attr: &'a VfsSetAttr
+}
+
+impl<T: VfsNodeOps + Unpin + ?Sized> Future for SetattrFuture<'_, T> {
+    type Output = VfsResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Self { vnode, attr } = self.get_mut();
+        Pin::new(*vnode).setattr(cx, attr)
+    }
+}
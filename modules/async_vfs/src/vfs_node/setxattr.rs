@@ -0,0 +1,23 @@
+use core::pin::Pin;
+use core::future::Future;
+use core::task::{Context, Poll};
+
+use crate::{VfsNodeOps, VfsResult, VfsXattrFlags};
+
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct SetxattrFuture<'a, T: Unpin + ?Sized> {
+    pub(crate) vnode: &'a T,
+    pub(crate) name: &'a str,
+    pub(crate) value: &'a [u8],
+    pub(crate) flags: VfsXattrFlags
+}
+
+impl<T: VfsNodeOps + Unpin + ?Sized> Future for SetxattrFuture<'_, T> {
+    type Output = VfsResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Self { vnode, name, value, flags } = self.get_mut();
+        Pin::new(*vnode).setxattr(cx, name, value, *flags)
+    }
+}
@@ -0,0 +1,22 @@
+use core::pin::Pin;
+use core::future::Future;
+use core::task::{Context, Poll};
+
+use crate::{VfsNodeOps, VfsResult};
+
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct SymlinkFuture<'a, T: Unpin + ?Sized> {
+    pub(crate) vnode: &'a T,
+    pub(crate) path: &'a str, 
+    pub(crate) target: &'a str,
+}
+
+impl<T: VfsNodeOps + Unpin + ?Sized> Future for SymlinkFuture<'_, T> {
+    type Output = VfsResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Self { vnode, path, target } = self.get_mut();
+        Pin::new(*vnode).symlink(cx, path, target)
+    }
+}
@@ -0,0 +1,502 @@
+//! 路径解析：逐个分量查找节点，处理 `.`、`..`、挂载点与符号链接。
+//!
+//! 解析过程中维护已解析分量组成的栈，`..` 直接弹栈，因此从挂载的文件系统根目录
+//! 执行 `..` 会回到挂载点所在的文件系统。查找结果（包括不存在的路径）会记录在
+//! [`DentryCache`] 中，文件系统内容或挂载发生变化时需要由调用者使缓存失效。
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Bound;
+use spinlock::SpinNoIrq;
+
+use crate::{AsyncVfsNodeOps, VfsAccess, VfsError, VfsNodeRef};
+
+bitflags::bitflags! {
+    /// 路径解析的参数
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct LookupFlags: u32 {
+        /// 最后一个分量是符号链接时不跟随，对应 O_NOFOLLOW 与 AT_SYMLINK_NOFOLLOW
+        const NOFOLLOW = 1;
+        /// 最后一个分量存在时必须是目录
+        const DIRECTORY = 1 << 1;
+    }
+}
+
+/// 一次解析中最多跟随的符号链接数，与 Linux 的 MAXSYMLINKS 一致
+pub const MAX_SYMLINK_FOLLOWS: usize = 40;
+
+/// 符号链接目标的最大长度
+const MAX_LINK_LEN: usize = 4096;
+
+/// 路径解析的错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathError {
+    /// 跟随的符号链接过多，通常是出现了环，对应 ELOOP
+    Loop,
+    /// 访问文件系统时的错误
+    Vfs(VfsError),
+}
+
+impl From<VfsError> for PathError {
+    fn from(err: VfsError) -> Self {
+        Self::Vfs(err)
+    }
+}
+
+/// 提供挂载信息，供路径解析在挂载点处切换文件系统
+pub trait MountResolver: Send + Sync {
+    /// 返回挂载在绝对路径 `path` 上的文件系统根目录，`path` 不是挂载点时返回 `None`。
+    ///
+    /// `"/"` 必须总是有挂载。
+    fn mounted_root(&self, path: &str) -> Option<VfsNodeRef>;
+}
+
+/// 以规范化的绝对路径为键的目录项缓存
+///
+/// 值为 `None` 的是负向缓存，表示该路径不存在。缓存满时淘汰最久没有被访问的项。
+pub struct DentryCache {
+    inner: SpinNoIrq<DentryCacheInner>,
+    capacity: usize,
+}
+
+struct DentryCacheInner {
+    /// 路径到查找结果与最近一次访问的序号
+    entries: BTreeMap<String, (Option<VfsNodeRef>, u64)>,
+    /// 访问序号到路径，序号最小的是最久没有被访问的项
+    recency: BTreeMap<u64, String>,
+    clock: u64,
+}
+
+impl DentryCacheInner {
+    /// 将 `path` 标记为最近访问，返回其中的查找结果
+    fn touch(&mut self, path: &str) -> Option<&mut Option<VfsNodeRef>> {
+        let (node, stamp) = self.entries.get_mut(path)?;
+        let key = self.recency.remove(stamp).unwrap();
+        self.clock += 1;
+        *stamp = self.clock;
+        self.recency.insert(self.clock, key);
+        Some(node)
+    }
+
+    fn remove(&mut self, path: &str) {
+        if let Some((_, stamp)) = self.entries.remove(path) {
+            self.recency.remove(&stamp);
+        }
+    }
+}
+
+impl DentryCache {
+    /// 创建一个最多缓存 `capacity` 项的缓存
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            inner: SpinNoIrq::new(DentryCacheInner {
+                entries: BTreeMap::new(),
+                recency: BTreeMap::new(),
+                clock: 0,
+            }),
+            capacity,
+        }
+    }
+
+    /// 查找缓存，未命中返回 `None`，命中负向缓存返回 `Some(None)`
+    pub fn get(&self, path: &str) -> Option<Option<VfsNodeRef>> {
+        self.inner.lock().touch(path).cloned()
+    }
+
+    /// 记录一次查找结果
+    pub fn insert(&self, path: &str, node: Option<VfsNodeRef>) {
+        let mut inner = self.inner.lock();
+        if let Some(entry) = inner.touch(path) {
+            *entry = node;
+            return;
+        }
+        if inner.entries.len() >= self.capacity {
+            if let Some((_, oldest)) = inner.recency.pop_first() {
+                inner.entries.remove(&oldest);
+            }
+        }
+        inner.clock += 1;
+        let stamp = inner.clock;
+        inner.entries.insert(path.to_string(), (node, stamp));
+        inner.recency.insert(stamp, path.to_string());
+    }
+
+    /// 使 `path` 及其下所有路径的缓存失效
+    pub fn invalidate(&self, path: &str) {
+        let path = path.trim_end_matches('/');
+        let mut inner = self.inner.lock();
+        inner.remove(path);
+        let prefix = path.to_string() + "/";
+        let stale: Vec<String> = inner
+            .entries
+            .range::<str, _>((Bound::Included(prefix.as_str()), Bound::Unbounded))
+            .map(|(key, _)| key)
+            .take_while(|key| key.starts_with(&prefix))
+            .cloned()
+            .collect();
+        for key in stale {
+            inner.remove(&key);
+        }
+    }
+
+    /// 清空缓存，挂载关系变化时使用
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock();
+        inner.entries.clear();
+        inner.recency.clear();
+    }
+
+    /// 当前缓存的项数
+    pub fn len(&self) -> usize {
+        self.inner.lock().entries.len()
+    }
+
+    /// 缓存是否为空
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().entries.is_empty()
+    }
+}
+
+/// 将已解析的分量拼接为绝对路径
+fn join(stack: &[(String, VfsNodeRef)]) -> String {
+    if stack.is_empty() {
+        return String::from("/");
+    }
+    let mut path = String::new();
+    for (name, _) in stack {
+        path.push('/');
+        path.push_str(name);
+    }
+    path
+}
+
+/// 将路径分量逆序压入待解析的栈中，使第一个分量位于栈顶
+fn push_components(pending: &mut Vec<String>, path: &str) {
+    pending.extend(
+        path.split('/')
+            .filter(|name| !name.is_empty() && *name != ".")
+            .rev()
+            .map(String::from),
+    );
+}
+
+/// 读取符号链接的目标
+async fn read_link(node: &VfsNodeRef, size: u64) -> Result<String, PathError> {
+    let len = (size as usize).min(MAX_LINK_LEN);
+    let mut buf = alloc::vec![0u8; len];
+    let len = node.readlink(&mut buf).await?;
+    buf.truncate(len);
+    String::from_utf8(buf).map_err(|_| PathError::Vfs(VfsError::InvalidData))
+}
+
+/// 解析绝对路径 `path`，返回跟随符号链接后的规范路径与对应节点。
+///
+/// 中间分量必须存在且是目录；最后一个分量不存在时返回的节点为 `None`，
+/// 便于调用者创建文件。路径以 `/` 结尾时总是跟随最后的符号链接，且要求其是目录。
+///
+/// `searcher` 为调用者的用户与组，在每个目录中查找前检查其是否有搜索（执行）权限，
+/// 没有时返回 [`VfsError::PermissionDenied`]；为 `None` 时不检查。
+pub async fn walk(
+    mounts: &dyn MountResolver,
+    dcache: &DentryCache,
+    path: &str,
+    flags: LookupFlags,
+    searcher: Option<(u32, u32)>,
+) -> Result<(String, Option<VfsNodeRef>), PathError> {
+    if !path.starts_with('/') {
+        return Err(PathError::Vfs(VfsError::InvalidInput));
+    }
+    walk_from(mounts, dcache, None, path, flags, searcher).await
+}
+
+/// 从已打开的目录解析路径，`dir` 为该目录的路径与节点。
+///
+/// 相对路径的分量直接在目录节点中查找，不经过目录项缓存，因此目录被移动后
+/// 仍然能找到其中的文件；`..` 越过该目录时按其路径回到上级目录。
+/// `path` 为空时返回目录本身，绝对路径与 [`walk`] 相同。
+pub async fn walk_at(
+    mounts: &dyn MountResolver,
+    dcache: &DentryCache,
+    dir: (&str, &VfsNodeRef),
+    path: &str,
+    flags: LookupFlags,
+    searcher: Option<(u32, u32)>,
+) -> Result<(String, Option<VfsNodeRef>), PathError> {
+    if path.starts_with('/') {
+        return walk(mounts, dcache, path, flags, searcher).await;
+    }
+    let (dir_path, node) = dir;
+    let base = dir_path.trim_matches('/');
+    let base = (!base.is_empty()).then(|| (String::from(base), node.clone()));
+    walk_from(mounts, dcache, base, path, flags, searcher).await
+}
+
+/// `base` 为起始目录，其名字是去掉首尾 `/` 的完整路径；为 `None` 时从根目录开始
+async fn walk_from(
+    mounts: &dyn MountResolver,
+    dcache: &DentryCache,
+    base: Option<(String, VfsNodeRef)>,
+    path: &str,
+    mut flags: LookupFlags,
+    searcher: Option<(u32, u32)>,
+) -> Result<(String, Option<VfsNodeRef>), PathError> {
+    if path.len() > 1 && path.ends_with('/') {
+        flags.remove(LookupFlags::NOFOLLOW);
+        flags.insert(LookupFlags::DIRECTORY);
+    }
+    let root = mounts
+        .mounted_root("/")
+        .ok_or(PathError::Vfs(VfsError::NotFound))?;
+
+    // 栈底为起始目录时，其下的分量不使用以路径为键的缓存
+    let mut based = base.is_some();
+    let mut stack: Vec<(String, VfsNodeRef)> = base.into_iter().collect();
+    let mut pending = Vec::new();
+    push_components(&mut pending, path);
+    let mut follows = 0;
+
+    while let Some(name) = pending.pop() {
+        if name == ".." {
+            if let Some((name, _)) = stack.pop() {
+                if based && stack.is_empty() {
+                    based = false;
+                    let parent = name.rsplit_once('/').map_or("", |(parent, _)| parent);
+                    push_components(&mut pending, parent);
+                }
+            }
+            continue;
+        }
+        let is_last = pending.is_empty();
+        let parent = stack.last().map_or(&root, |(_, node)| node).clone();
+        if let Some((uid, gid)) = searcher {
+            // 不支持查询属性的文件系统上不做检查
+            if let Ok(attr) = parent.get_attr().await {
+                if !attr.permits(uid, gid, VfsAccess::EXEC) {
+                    return Err(PathError::Vfs(VfsError::PermissionDenied));
+                }
+            }
+        }
+        let abs_path = if stack.is_empty() {
+            format!("/{}", name)
+        } else {
+            format!("{}/{}", join(&stack), name)
+        };
+
+        let cached = if based { None } else { dcache.get(&abs_path) };
+        let node = match cached {
+            Some(node) => node,
+            None => {
+                let node = match parent.lookup(&name).await {
+                    Ok(node) => Some(mounts.mounted_root(&abs_path).unwrap_or(node)),
+                    Err(VfsError::NotFound) => None,
+                    Err(err) => return Err(err.into()),
+                };
+                if !based {
+                    dcache.insert(&abs_path, node.clone());
+                }
+                node
+            }
+        };
+        let Some(node) = node else {
+            if is_last {
+                return Ok((abs_path, None));
+            }
+            return Err(PathError::Vfs(VfsError::NotFound));
+        };
+
+        let attr = node.get_attr().await?;
+        if attr.file_type().is_symlink() && !(is_last && flags.contains(LookupFlags::NOFOLLOW)) {
+            follows += 1;
+            if follows > MAX_SYMLINK_FOLLOWS {
+                return Err(PathError::Loop);
+            }
+            let target = read_link(&node, attr.size()).await?;
+            if target.starts_with('/') {
+                stack.clear();
+                based = false;
+            }
+            push_components(&mut pending, &target);
+            continue;
+        }
+        if !attr.is_dir() && (!is_last || flags.contains(LookupFlags::DIRECTORY)) {
+            return Err(PathError::Vfs(VfsError::NotADirectory));
+        }
+        stack.push((name, node));
+    }
+
+    let node = stack.last().map_or(&root, |(_, node)| node).clone();
+    Ok((join(&stack), Some(node)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+    use alloc::sync::Arc;
+    use core::pin::Pin;
+    use core::task::{Context, Poll};
+    use test_utils::block_on;
+
+    struct Dir(BTreeMap<&'static str, VfsNodeRef>);
+    struct Link(&'static str);
+    struct File;
+    /// 只有所有者 1000 能够搜索的目录，其中有 `file`
+    struct Private;
+
+    impl VfsNodeOps for Dir {
+        fn get_attr(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<VfsNodeAttr>> {
+            Poll::Ready(Ok(VfsNodeAttr::new_dir(0, 0)))
+        }
+
+        fn lookup(self: Pin<&Self>, _cx: &mut Context<'_>, path: &str) -> Poll<VfsResult<VfsNodeRef>> {
+            Poll::Ready(self.0.get(path).cloned().ok_or(VfsError::NotFound))
+        }
+    }
+
+    impl VfsNodeOps for Link {
+        fn get_attr(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<VfsNodeAttr>> {
+            let perm = VfsNodePerm::from_bits_truncate(0o777);
+            Poll::Ready(Ok(VfsNodeAttr::new(perm, VfsNodeType::SymLink, self.0.len() as _, 0)))
+        }
+
+        fn readlink(self: Pin<&Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<VfsResult<usize>> {
+            buf[..self.0.len()].copy_from_slice(self.0.as_bytes());
+            Poll::Ready(Ok(self.0.len()))
+        }
+    }
+
+    impl VfsNodeOps for Private {
+        fn get_attr(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<VfsNodeAttr>> {
+            let mut attr = VfsNodeAttr::new(VfsNodePerm::from_bits_truncate(0o700), VfsNodeType::Dir, 0, 0);
+            attr.set_owner(1000, 1000);
+            Poll::Ready(Ok(attr))
+        }
+
+        fn lookup(self: Pin<&Self>, _cx: &mut Context<'_>, path: &str) -> Poll<VfsResult<VfsNodeRef>> {
+            match path {
+                "file" => Poll::Ready(Ok(Arc::new(File))),
+                _ => Poll::Ready(Err(VfsError::NotFound)),
+            }
+        }
+    }
+
+    impl VfsNodeOps for File {
+        fn get_attr(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<VfsResult<VfsNodeAttr>> {
+            Poll::Ready(Ok(VfsNodeAttr::new_file(0, 0)))
+        }
+    }
+
+    struct Mounts(BTreeMap<&'static str, VfsNodeRef>);
+
+    impl MountResolver for Mounts {
+        fn mounted_root(&self, path: &str) -> Option<VfsNodeRef> {
+            self.0.get(path).cloned()
+        }
+    }
+
+    fn dir(children: &[(&'static str, VfsNodeRef)]) -> VfsNodeRef {
+        Arc::new(Dir(children.iter().cloned().collect()))
+    }
+
+    /// `/` 下有 `mnt`、`bin -> usr/bin`、`usr/bin/sh`、`loop -> loop`，
+    /// `/mnt` 上挂载了另一个文件系统，其中有 `up -> ../usr`
+    fn setup() -> Mounts {
+        let usr = dir(&[("bin", dir(&[("sh", Arc::new(File))]))]);
+        let root = dir(&[
+            ("mnt", dir(&[])),
+            ("usr", usr),
+            ("bin", Arc::new(Link("usr/bin"))),
+            ("loop", Arc::new(Link("loop"))),
+            ("home", Arc::new(Private)),
+        ]);
+        let mnt = dir(&[("up", Arc::new(Link("../usr")))]);
+        Mounts([("/", root), ("/mnt", mnt)].into_iter().collect())
+    }
+
+    fn resolve(mounts: &Mounts, dcache: &DentryCache, path: &str, flags: LookupFlags) -> Result<String, PathError> {
+        block_on(walk(mounts, dcache, path, flags, None)).map(|(path, _)| path)
+    }
+
+    #[test]
+    fn test_walk() {
+        let mounts = setup();
+        let dcache = DentryCache::new(64);
+        let none = LookupFlags::empty();
+        assert_eq!(resolve(&mounts, &dcache, "/bin/sh", none), Ok("/usr/bin/sh".into()));
+        assert_eq!(resolve(&mounts, &dcache, "/bin", LookupFlags::NOFOLLOW), Ok("/bin".into()));
+        assert_eq!(resolve(&mounts, &dcache, "/bin/", LookupFlags::NOFOLLOW), Ok("/usr/bin".into()));
+        // `..` 从挂载的文件系统回到挂载点所在的文件系统
+        assert_eq!(resolve(&mounts, &dcache, "/mnt/up/bin/sh", none), Ok("/usr/bin/sh".into()));
+        assert_eq!(resolve(&mounts, &dcache, "/mnt/../bin/sh/", none), Err(PathError::Vfs(VfsError::NotADirectory)));
+        assert_eq!(resolve(&mounts, &dcache, "/loop", none), Err(PathError::Loop));
+        assert_eq!(resolve(&mounts, &dcache, "/loop", LookupFlags::NOFOLLOW), Ok("/loop".into()));
+        assert_eq!(resolve(&mounts, &dcache, "/mnt/new", none), Ok("/mnt/new".into()));
+        assert_eq!(resolve(&mounts, &dcache, "/mnt/new/file", none), Err(PathError::Vfs(VfsError::NotFound)));
+        assert_eq!(dcache.get("/mnt/new").map(|node| node.is_none()), Some(true));
+        dcache.invalidate("/mnt");
+        assert!(dcache.get("/mnt/new").is_none() && dcache.get("/mnt").is_none());
+        assert!(dcache.get("/usr/bin/sh").is_some());
+    }
+
+    #[test]
+    fn search_permission() {
+        let mounts = setup();
+        let dcache = DentryCache::new(64);
+        let none = LookupFlags::empty();
+        let walk_as = |path: &str, searcher: Option<(u32, u32)>| {
+            block_on(walk(&mounts, &dcache, path, none, searcher)).map(|(path, _)| path)
+        };
+        assert_eq!(walk_as("/home/file", Some((1000, 100))), Ok("/home/file".into()));
+        assert_eq!(walk_as("/home/file", Some((0, 0))), Ok("/home/file".into()));
+        // 缓存命中时同样需要检查
+        assert_eq!(walk_as("/home/file", Some((1001, 100))), Err(PathError::Vfs(VfsError::PermissionDenied)));
+        assert_eq!(walk_as("/home/missing", Some((1001, 100))), Err(PathError::Vfs(VfsError::PermissionDenied)));
+        // 目录本身不需要搜索权限
+        assert_eq!(walk_as("/home", Some((1001, 100))), Ok("/home".into()));
+        assert_eq!(walk_as("/home/file", None), Ok("/home/file".into()));
+    }
+
+    #[test]
+    fn walk_from_open_dir() {
+        let mounts = setup();
+        let dcache = DentryCache::new(64);
+        let none = LookupFlags::empty();
+        let root = mounts.mounted_root("/").unwrap();
+        let usr = block_on(root.lookup("usr")).unwrap();
+        // 目录已被移动到别处，记录的路径 `/old/usr` 不再存在
+        let walk_in = |path: &str| {
+            block_on(walk_at(&mounts, &dcache, ("/old/usr/", &usr), path, none, None))
+                .map(|(path, node)| (path, node.is_some()))
+        };
+        assert_eq!(walk_in("bin/sh"), Ok(("/old/usr/bin/sh".into(), true)));
+        assert_eq!(walk_in(""), Ok(("/old/usr".into(), true)));
+        assert_eq!(walk_in("bin/new"), Ok(("/old/usr/bin/new".into(), false)));
+        assert_eq!(walk_in("/bin/sh"), Ok(("/usr/bin/sh".into(), true)));
+        assert!(dcache.get("/old/usr/bin").is_none());
+        // `..` 越过起始目录后按其路径解析
+        let up = block_on(walk_at(&mounts, &dcache, ("/usr", &usr), "../mnt/up/bin", none, None));
+        assert_eq!(up.map(|(path, _)| path), Ok("/usr/bin".into()));
+    }
+
+    #[test]
+    fn dentry_cache_evicts_least_recently_used() {
+        let dcache = DentryCache::new(2);
+        dcache.insert("/a", None);
+        dcache.insert("/b", None);
+        assert!(dcache.get("/a").is_some());
+        dcache.insert("/c", None);
+        assert!(dcache.get("/b").is_none());
+        assert!(dcache.get("/a").is_some() && dcache.get("/c").is_some());
+        // 更新已有的项不会淘汰其他项
+        dcache.insert("/a", None);
+        assert_eq!(dcache.len(), 2);
+        dcache.insert("/d", None);
+        assert!(dcache.get("/c").is_none() && dcache.get("/a").is_some());
+        dcache.invalidate("/a");
+        assert_eq!(dcache.len(), 1);
+        dcache.insert("/e", None);
+        assert!(dcache.get("/d").is_some() && dcache.get("/e").is_some());
+    }
+}
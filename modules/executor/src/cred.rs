@@ -0,0 +1,68 @@
+//! 进程的用户凭证，用于文件的权限检查
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// 进程的真实与有效用户 id、用户组 id
+///
+/// 文件系统的权限检查使用有效 id，`access` 使用真实 id
+pub struct Credentials {
+    uid: AtomicU32,
+    euid: AtomicU32,
+    gid: AtomicU32,
+    egid: AtomicU32,
+}
+
+impl Credentials {
+    /// root 用户的凭证
+    pub const fn root() -> Self {
+        Self {
+            uid: AtomicU32::new(0),
+            euid: AtomicU32::new(0),
+            gid: AtomicU32::new(0),
+            egid: AtomicU32::new(0),
+        }
+    }
+
+    /// 真实用户 id
+    pub fn uid(&self) -> u32 {
+        self.uid.load(Ordering::Acquire)
+    }
+
+    /// 有效用户 id
+    pub fn euid(&self) -> u32 {
+        self.euid.load(Ordering::Acquire)
+    }
+
+    /// 真实用户组 id
+    pub fn gid(&self) -> u32 {
+        self.gid.load(Ordering::Acquire)
+    }
+
+    /// 有效用户组 id
+    pub fn egid(&self) -> u32 {
+        self.egid.load(Ordering::Acquire)
+    }
+
+    /// 有效用户是否为 root
+    pub fn is_root(&self) -> bool {
+        self.euid() == 0
+    }
+
+    /// 设置真实与有效用户 id
+    pub fn set_uid(&self, uid: u32, euid: u32) {
+        self.uid.store(uid, Ordering::Release);
+        self.euid.store(euid, Ordering::Release);
+    }
+
+    /// 设置真实与有效用户组 id
+    pub fn set_gid(&self, gid: u32, egid: u32) {
+        self.gid.store(gid, Ordering::Release);
+        self.egid.store(egid, Ordering::Release);
+    }
+
+    /// 复制另一个进程的凭证，用于创建子进程
+    pub fn copy_from(&self, other: &Credentials) {
+        self.set_uid(other.uid(), other.euid());
+        self.set_gid(other.gid(), other.egid());
+    }
+}
@@ -1,15 +1,15 @@
 
 use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
 use alloc::{collections::btree_map::BTreeMap, string::{String, ToString}, sync::Arc, vec::Vec, vec};
-use axerrno::{AxError, AxResult};
-use async_fs::api::{FileIO, OpenFlags};
+use axerrno::AxResult;
+use async_fs::api::OpenFlags;
 use async_mem::MemorySet;
 use axhal::mem::VirtAddr;
 use taskctx::{BaseScheduler, TaskRef};
 use spinlock::SpinNoIrq;
-use sync::Mutex;
+use sync::{Mutex, WaitQueue};
 use taskctx::{Scheduler, TaskId};
-use crate::{fd_manager::{FdManager, FdTable}, stdio::{Stderr, Stdin, Stdout}};
+use crate::{cred::Credentials, fd_manager::{FdEntry, FdManager, FdTable}, stdio::{Stderr, Stdin, Stdout}};
 
 const FD_LIMIT_ORIGIN: usize = 1025;
 pub const KERNEL_EXECUTOR_ID: u64 = 1;
@@ -29,6 +29,8 @@ pub struct Executor {
     pub is_zombie: AtomicBool,
     /// 退出状态码
     pub exit_code: AtomicI32,
+    /// 等待进程退出的任务，例如轮询 pidfd 的任务
+    pub exit_waiters: WaitQueue,
 
     /// 地址空间
     pub memory_set: Arc<Mutex<MemorySet>>,
@@ -40,6 +42,8 @@ pub struct Executor {
     pub blocked_by_vfork: Mutex<bool>,
     /// 该进程可执行文件所在的路径
     pub file_path: Mutex<String>,
+    /// 用户凭证
+    pub cred: Credentials,
 }
 
 unsafe impl Sync for Executor {}
@@ -67,11 +71,13 @@ impl Executor {
             fd_manager: FdManager::new(fd_table, cwd, mask, FD_LIMIT_ORIGIN),
             is_zombie: AtomicBool::new(false),
             exit_code: AtomicI32::new(0),
+            exit_waiters: WaitQueue::new(),
             memory_set,
             heap_bottom: AtomicU64::new(heap_bottom),
             heap_top: AtomicU64::new(heap_bottom),
             blocked_by_vfork: Mutex::new(false),
             file_path: Mutex::new(String::new()),
+            cred: Credentials::root(),
         }
     }
 
@@ -79,17 +85,17 @@ impl Executor {
     pub fn new_init() -> Self {
         let new_fd_table: FdTable = Arc::new(Mutex::new(vec![
             // 标准输入
-            Some(Arc::new(Stdin {
+            Some(FdEntry::new(Arc::new(Stdin {
                 flags: Mutex::new(OpenFlags::empty()),
-            })),
+            }))),
             // 标准输出
-            Some(Arc::new(Stdout {
+            Some(FdEntry::new(Arc::new(Stdout {
                 flags: Mutex::new(OpenFlags::empty()),
-            })),
+            }))),
             // 标准错误
-            Some(Arc::new(Stderr {
+            Some(FdEntry::new(Arc::new(Stderr {
                 flags: Mutex::new(OpenFlags::empty()),
-            })),
+            }))),
         ]));
         Executor::new(
             TaskId::new(), 
@@ -142,6 +148,13 @@ impl Executor {
         self.is_zombie.store(status, Ordering::Release)
     }
 
+    /// 记录退出码并将 Executor（进程）标记为僵尸状态，唤醒等待它退出的任务
+    pub fn set_exited(&self, exit_code: i32) {
+        self.set_exit_code(exit_code);
+        self.set_zombie(true);
+        self.exit_waiters.notify_all();
+    }
+
     /// 获取 Executor（进程）的堆顶
     pub fn get_heap_top(&self) -> u64 {
         self.heap_top.load(Ordering::Acquire)
@@ -274,19 +287,4 @@ impl Executor {
             .manual_alloc_type_for_lazy(obj).await
     }
 
-    /// 为进程分配一个文件描述符
-    pub fn alloc_fd(&self, fd_table: &mut Vec<Option<Arc<dyn FileIO>>>) -> AxResult<usize> {
-        for (i, fd) in fd_table.iter().enumerate() {
-            if fd.is_none() {
-                return Ok(i);
-            }
-        }
-        if fd_table.len() >= self.fd_manager.get_limit() as usize {
-            debug!("fd table is full");
-            return Err(AxError::StorageFull);
-        }
-        fd_table.push(None);
-        Ok(fd_table.len() - 1)
-    }
-
 }
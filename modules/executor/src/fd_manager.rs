@@ -1,20 +1,57 @@
-//! todo 重构fd_table, fd_allocator
+//! 文件描述符的管理
+//!
+//! 文件描述符表中的每一项 [`FdEntry`] 由两部分组成：
+//! - 打开文件描述（open file description），即 `Arc<dyn FileIO>`，其中保存了读写偏移、
+//!   文件状态标志与文件本身，由 `dup` 出的所有文件描述符共享；
+//! - 文件描述符标志 [`FdFlags`]，只属于该文件描述符，例如 `FD_CLOEXEC`。
+//!
+//! 系统调用应通过 [`FdManager`] 提供的接口分配、复制与关闭文件描述符，不直接操作文件描述符表。
 use core::sync::atomic::{AtomicI32, AtomicU64};
 
 use alloc::string::String;
 use alloc::sync::Arc;
 use async_fs::api::{FileIO, OpenFlags};
+use axerrno::{AxError, AxResult};
 
 use alloc::vec::Vec;
+use bitflags::bitflags;
 use sync::Mutex;
 
 use crate::stdio::{Stdin, Stdout};
 
-pub type FdTable = Arc<Mutex<Vec<Option<Arc<dyn FileIO>>>>>;
+bitflags! {
+    /// 文件描述符标志，不与复制出的文件描述符共享
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct FdFlags: u32 {
+        /// 执行 `exec()` 时关闭该文件描述符
+        const CLOEXEC = 1;
+    }
+}
+
+/// 文件描述符表中的一项
+#[derive(Clone)]
+pub struct FdEntry {
+    /// 共享的打开文件描述
+    pub file: Arc<dyn FileIO>,
+    /// 文件描述符标志
+    pub flags: FdFlags,
+}
+
+impl FdEntry {
+    /// 创建一个不带标志的文件描述符表项
+    pub fn new(file: Arc<dyn FileIO>) -> Self {
+        Self {
+            file,
+            flags: FdFlags::empty(),
+        }
+    }
+}
+
+pub type FdTable = Arc<Mutex<Vec<Option<FdEntry>>>>;
 
 pub struct FdManager {
     /// 保存文件描述符的数组
-    pub fd_table: FdTable,
+    pub(crate) fd_table: FdTable,
     /// 保存文件描述符的数组的最大长度
     pub limit: AtomicU64,
     /// 创建文件时的mode的掩码
@@ -59,26 +96,278 @@ impl FdManager {
         old_mask
     }
 
-    /// 在执行 `exec()` 时关闭标记为 `CLOEXEC` 的文件
-    pub async fn close_on_exec(&self) {
+    /// 复制整个文件描述符表，用于创建新进程
+    pub async fn fork_table(&self) -> FdTable {
+        Arc::new(Mutex::new(self.fd_table.lock().await.clone()))
+    }
+
+    /// 获取文件描述符对应的打开文件描述
+    pub async fn get(&self, fd: usize) -> Option<Arc<dyn FileIO>> {
+        match self.fd_table.lock().await.get(fd) {
+            Some(Some(entry)) => Some(entry.file.clone()),
+            _ => None,
+        }
+    }
+
+    /// 获取所有打开的文件描述符及其打开文件描述
+    pub async fn entries(&self) -> Vec<(usize, Arc<dyn FileIO>)> {
+        self.fd_table
+            .lock()
+            .await
+            .iter()
+            .enumerate()
+            .filter_map(|(fd, entry)| entry.as_ref().map(|entry| (fd, entry.file.clone())))
+            .collect()
+    }
+
+    /// 获取文件描述符标志，文件描述符不存在时返回 `InvalidInput`
+    pub async fn get_flags(&self, fd: usize) -> AxResult<FdFlags> {
+        match self.fd_table.lock().await.get(fd) {
+            Some(Some(entry)) => Ok(entry.flags),
+            _ => Err(AxError::InvalidInput),
+        }
+    }
+
+    /// 设置文件描述符标志，不影响复制出的其他文件描述符
+    pub async fn set_flags(&self, fd: usize, flags: FdFlags) -> AxResult {
+        match self.fd_table.lock().await.get_mut(fd) {
+            Some(Some(entry)) => {
+                entry.flags = flags;
+                Ok(())
+            }
+            _ => Err(AxError::InvalidInput),
+        }
+    }
+
+    /// 在 `fd_table` 中找到不小于 `min` 的最小空闲文件描述符
+    fn find_free(&self, fd_table: &mut Vec<Option<FdEntry>>, min: usize) -> AxResult<usize> {
+        let limit = self.get_limit() as usize;
+        if let Some(fd) = (min..fd_table.len()).find(|&fd| fd_table[fd].is_none()) {
+            return Ok(fd);
+        }
+        let fd = fd_table.len().max(min);
+        if fd >= limit {
+            debug!("fd table is full");
+            return Err(AxError::StorageFull);
+        }
+        fd_table.resize(fd + 1, None);
+        Ok(fd)
+    }
+
+    /// 分配一个最小的空闲文件描述符指向 `file`
+    ///
+    /// 文件描述符达到上限时返回 `StorageFull`
+    pub async fn alloc(&self, file: Arc<dyn FileIO>, flags: FdFlags) -> AxResult<usize> {
+        self.alloc_from(0, file, flags).await
+    }
+
+    /// 分配一个不小于 `min` 的最小空闲文件描述符指向 `file`
+    pub async fn alloc_from(
+        &self,
+        min: usize,
+        file: Arc<dyn FileIO>,
+        flags: FdFlags,
+    ) -> AxResult<usize> {
         let mut fd_table = self.fd_table.lock().await;
-        for (index, fd) in fd_table.iter_mut().enumerate() {
-            if let Some(f) = fd {
-                if f.get_status().await.is_close_on_exec() {
-                    info!("close fd: {} on exec", index);
-                    fd.take();
+        let fd = self.find_free(&mut fd_table, min)?;
+        fd_table[fd] = Some(FdEntry { file, flags });
+        Ok(fd)
+    }
+
+    /// 复制文件描述符 `fd` 到不小于 `min` 的最小空闲文件描述符，新的文件描述符的标志为 `flags`
+    ///
+    /// `fd` 不存在时返回 `InvalidInput`，文件描述符达到上限时返回 `StorageFull`
+    pub async fn dup(&self, fd: usize, min: usize, flags: FdFlags) -> AxResult<usize> {
+        let mut fd_table = self.fd_table.lock().await;
+        let Some(Some(entry)) = fd_table.get(fd) else {
+            return Err(AxError::InvalidInput);
+        };
+        let file = entry.file.clone();
+        let new_fd = self.find_free(&mut fd_table, min)?;
+        fd_table[new_fd] = Some(FdEntry { file, flags });
+        Ok(new_fd)
+    }
+
+    /// 复制文件描述符 `fd` 到 `new_fd`，`new_fd` 已经打开时先将其关闭
+    ///
+    /// `fd` 不存在或 `new_fd` 超出上限时返回 `InvalidInput`
+    pub async fn dup2(&self, fd: usize, new_fd: usize, flags: FdFlags) -> AxResult<usize> {
+        let mut fd_table = self.fd_table.lock().await;
+        let Some(Some(entry)) = fd_table.get(fd) else {
+            return Err(AxError::InvalidInput);
+        };
+        if new_fd >= self.get_limit() as usize {
+            return Err(AxError::InvalidInput);
+        }
+        let file = entry.file.clone();
+        if new_fd >= fd_table.len() {
+            fd_table.resize(new_fd + 1, None);
+        }
+        fd_table[new_fd] = Some(FdEntry { file, flags });
+        Ok(new_fd)
+    }
+
+    /// 关闭文件描述符，返回其指向的打开文件描述
+    ///
+    /// 打开文件描述在最后一个指向它的文件描述符关闭后才会被释放
+    pub async fn close(&self, fd: usize) -> AxResult<Arc<dyn FileIO>> {
+        match self.fd_table.lock().await.get_mut(fd).and_then(Option::take) {
+            Some(entry) => Ok(entry.file),
+            None => Err(AxError::InvalidInput),
+        }
+    }
+
+    /// 关闭 `[first, last]` 范围内的所有文件描述符，返回被关闭的打开文件描述
+    ///
+    /// `cloexec_only` 为真时不关闭，而是为它们设置 [`FdFlags::CLOEXEC`]
+    pub async fn close_range(
+        &self,
+        first: usize,
+        last: usize,
+        cloexec_only: bool,
+    ) -> Vec<Arc<dyn FileIO>> {
+        let mut fd_table = self.fd_table.lock().await;
+        let end = fd_table.len().min(last.saturating_add(1));
+        let mut closed = Vec::new();
+        for entry in fd_table.iter_mut().take(end).skip(first) {
+            if cloexec_only {
+                if let Some(entry) = entry {
+                    entry.flags |= FdFlags::CLOEXEC;
                 }
+            } else if let Some(entry) = entry.take() {
+                closed.push(entry.file);
+            }
+        }
+        closed
+    }
+
+    /// 在执行 `exec()` 时关闭标记为 `CLOEXEC` 的文件，返回被关闭的打开文件描述
+    ///
+    /// 与 [`FdManager::close`] 相同，调用者需要释放进程在这些文件上持有的锁
+    pub async fn close_on_exec(&self) -> Vec<Arc<dyn FileIO>> {
+        let mut fd_table = self.fd_table.lock().await;
+        let mut closed = Vec::new();
+        for (index, fd) in fd_table.iter_mut().enumerate() {
+            if fd
+                .as_ref()
+                .is_some_and(|entry| entry.flags.contains(FdFlags::CLOEXEC))
+            {
+                info!("close fd: {} on exec", index);
+                closed.extend(fd.take().map(|entry| entry.file));
             }
         }
         if fd_table[0].is_none() {
-            fd_table[0] = Some(Arc::new(Stdin {
+            fd_table[0] = Some(FdEntry::new(Arc::new(Stdin {
                 flags: Mutex::new(OpenFlags::empty()),
-            }));
+            })));
         }
         if fd_table[1].is_none() {
-            fd_table[1] = Some(Arc::new(Stdout {
+            fd_table[1] = Some(FdEntry::new(Arc::new(Stdout {
                 flags: Mutex::new(OpenFlags::empty()),
-            }));
+            })));
         }
+        closed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::block_on;
+
+    fn manager(limit: usize) -> FdManager {
+        FdManager::new(
+            Arc::new(Mutex::new(Vec::new())),
+            Arc::new(Mutex::new(String::from("/"))),
+            Arc::new(AtomicI32::new(0o022)),
+            limit,
+        )
+    }
+
+    fn file() -> Arc<dyn FileIO> {
+        Arc::new(Stdin {
+            flags: Mutex::new(OpenFlags::empty()),
+        })
+    }
+
+    #[test]
+    fn alloc_takes_lowest_free_fd() {
+        let fds = manager(4);
+        block_on(async {
+            assert_eq!(fds.alloc(file(), FdFlags::empty()).await, Ok(0));
+            assert_eq!(fds.alloc(file(), FdFlags::CLOEXEC).await, Ok(1));
+            assert_eq!(fds.alloc_from(3, file(), FdFlags::empty()).await, Ok(3));
+            assert_eq!(fds.alloc(file(), FdFlags::empty()).await, Ok(2));
+            assert_eq!(fds.alloc(file(), FdFlags::empty()).await, Err(AxError::StorageFull));
+            fds.close(1).await.unwrap();
+            assert_eq!(fds.close(1).await.err(), Some(AxError::InvalidInput));
+            // 新的文件描述符不继承被关闭的文件描述符的标志
+            assert_eq!(fds.alloc(file(), FdFlags::empty()).await, Ok(1));
+            assert_eq!(fds.get_flags(1).await, Ok(FdFlags::empty()));
+        });
+    }
+
+    #[test]
+    fn dup_shares_file_but_not_flags() {
+        let fds = manager(8);
+        block_on(async {
+            let fd = fds.alloc(file(), FdFlags::CLOEXEC).await.unwrap();
+            let new_fd = fds.dup(fd, 0, FdFlags::empty()).await.unwrap();
+            assert_eq!(new_fd, 1);
+            assert!(Arc::ptr_eq(&fds.get(fd).await.unwrap(), &fds.get(new_fd).await.unwrap()));
+            assert_eq!(fds.get_flags(fd).await, Ok(FdFlags::CLOEXEC));
+            assert_eq!(fds.get_flags(new_fd).await, Ok(FdFlags::empty()));
+            assert_eq!(fds.dup(5, 0, FdFlags::empty()).await, Err(AxError::InvalidInput));
+        });
+    }
+
+    #[test]
+    fn dup2_replaces_target() {
+        let fds = manager(8);
+        block_on(async {
+            let first = file();
+            fds.alloc(first.clone(), FdFlags::empty()).await.unwrap();
+            fds.alloc(file(), FdFlags::CLOEXEC).await.unwrap();
+            assert_eq!(fds.dup2(0, 1, FdFlags::empty()).await, Ok(1));
+            assert!(Arc::ptr_eq(&fds.get(1).await.unwrap(), &first));
+            assert_eq!(fds.get_flags(1).await, Ok(FdFlags::empty()));
+            // 目标超出当前的表长时扩展文件描述符表
+            assert_eq!(fds.dup2(0, 6, FdFlags::CLOEXEC).await, Ok(6));
+            assert_eq!(fds.get_flags(6).await, Ok(FdFlags::CLOEXEC));
+            assert_eq!(fds.dup2(0, 8, FdFlags::empty()).await, Err(AxError::InvalidInput));
+            assert_eq!(fds.dup2(3, 4, FdFlags::empty()).await, Err(AxError::InvalidInput));
+        });
+    }
+
+    #[test]
+    fn close_range_returns_closed_files() {
+        let fds = manager(8);
+        block_on(async {
+            for _ in 0..5 {
+                fds.alloc(file(), FdFlags::empty()).await.unwrap();
+            }
+            assert_eq!(fds.close_range(1, 2, true).await.len(), 0);
+            assert_eq!(fds.get_flags(1).await, Ok(FdFlags::CLOEXEC));
+            assert_eq!(fds.get_flags(3).await, Ok(FdFlags::empty()));
+
+            assert_eq!(fds.close_range(3, usize::MAX, false).await.len(), 2);
+            let fds_left: Vec<usize> = fds.entries().await.into_iter().map(|(fd, _)| fd).collect();
+            assert_eq!(fds_left, [0, 1, 2]);
+        });
+    }
+
+    #[test]
+    fn close_on_exec_closes_marked_fds() {
+        let fds = manager(8);
+        block_on(async {
+            fds.alloc(file(), FdFlags::CLOEXEC).await.unwrap();
+            fds.alloc(file(), FdFlags::empty()).await.unwrap();
+            fds.alloc(file(), FdFlags::CLOEXEC).await.unwrap();
+            assert_eq!(fds.close_on_exec().await.len(), 2);
+            // 标准输入被重新打开
+            let fds_left: Vec<usize> = fds.entries().await.into_iter().map(|(fd, _)| fd).collect();
+            assert_eq!(fds_left, [0, 1]);
+            assert_eq!(fds.get_flags(0).await, Ok(FdFlags::empty()));
+        });
     }
 }
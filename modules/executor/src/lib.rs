@@ -6,6 +6,7 @@ extern crate alloc;
 extern crate axlog;
 
 mod api;
+mod cred;
 mod current;
 mod executor;
 mod fd_manager;
@@ -16,6 +17,7 @@ pub mod flags;
 pub use loader::load_app;
 
 pub use api::*;
+pub use cred::Credentials;
 pub use current::CurrentExecutor;
 pub use executor::Executor;
 pub type ExecutorRef = alloc::sync::Arc<Executor>;
@@ -1,17 +1,23 @@
 //! 模拟的链接、挂载模块
 //! fat32本身不支持符号链接和硬链接，两个指向相同文件的目录条目将会被chkdsk报告为交叉链接并修复
+//!
+//! 支持链接的文件系统（如 ramfs）直接使用文件系统的链接操作，这里的映射表只用于
+//! FAT 文件系统上的链接
 extern crate alloc;
 use alloc::collections::BTreeMap;
 use alloc::format;
 // use alloc::format;
 use alloc::string::{String, ToString};
 use axerrno::{AxError, AxResult};
-use async_fs::api::{canonicalize, path_exists, remove_file, FileIOType};
+use async_fs::api::{
+    canonicalize, filesystem_type, path_exists, remove_file, resolve_path, resolve_path_as,
+    resolve_path_at, symlink, LookupFlags, PathError, VfsNodeRef,
+};
 // use axfs::api::FileIOType;
 use axlog::{debug, info, trace};
 use sync::Mutex;
 
-use crate::current_executor;
+use crate::{current_executor, CurrentExecutor};
 
 // use crate::current_process;
 #[allow(unused)]
@@ -22,20 +28,78 @@ pub const AT_FDCWD: usize = -100isize as usize;
 pub struct FilePath(String);
 
 impl FilePath {
-    /// 创建一个 FilePath, 传入的 path 会被 canonicalize, 故可以是相对路径
+    /// 创建一个 FilePath, 传入的 path 会跟随符号链接解析为绝对路径, 故可以是相对路径
     pub async fn new(path: &str) -> AxResult<Self> {
-        let new_path = canonicalize(path).await;
-        if new_path.is_err() {
-            return Err(AxError::NotFound);
-        }
-        let mut new_path = String::from(new_path.unwrap().trim());
+        Self::new_with_flags(path, LookupFlags::empty())
+            .await
+            .map_err(|err| match err {
+                PathError::Vfs(AxError::PermissionDenied) => AxError::PermissionDenied,
+                _ => AxError::NotFound,
+            })
+    }
+
+    /// 按 `flags` 解析路径并创建 FilePath
+    ///
+    /// 路径中的符号链接由文件系统的路径解析处理；若中间的目录不存在，
+    /// 则退化为按字面规范化，由之后的打开等操作报告错误。
+    /// 当前进程对途经的目录没有搜索权限时返回 PermissionDenied
+    pub async fn new_with_flags(path: &str, flags: LookupFlags) -> Result<Self, PathError> {
+        let path = path.trim();
+        let resolved = match CurrentExecutor::try_get() {
+            Some(executor) => {
+                resolve_path_as(path, flags, executor.cred.euid(), executor.cred.egid()).await
+            }
+            None => resolve_path(path, flags).await,
+        };
+        Self::from_resolved(path, resolved, flags).await
+    }
+
+    /// 从已打开的目录 `dir` 按 `flags` 解析 `path` 并创建 FilePath，`dir_path` 为该目录的路径
+    ///
+    /// 路径在目录节点中查找，目录被移动后仍能解析其中的文件；`path` 为空时得到目录本身
+    pub async fn new_at(
+        dir: &VfsNodeRef,
+        dir_path: &str,
+        path: &str,
+        flags: LookupFlags,
+    ) -> Result<Self, PathError> {
+        let path = path.trim();
+        let searcher = CurrentExecutor::try_get()
+            .map(|executor| (executor.cred.euid(), executor.cred.egid()));
+        let resolved = resolve_path_at(dir, dir_path, path, flags, searcher).await;
+        // 解析失败时按拼接后的路径规范化
+        let full_path = if path.is_empty() {
+            String::from(dir_path)
+        } else if dir_path.ends_with('/') {
+            format!("{}{}", dir_path, path)
+        } else {
+            format!("{}/{}", dir_path, path)
+        };
+        Self::from_resolved(&full_path, resolved, flags).await
+    }
+
+    /// 由路径解析的结果创建 FilePath，解析失败时按字面规范化 `path`
+    async fn from_resolved(
+        path: &str,
+        resolved: Result<(String, Option<VfsNodeRef>), PathError>,
+        flags: LookupFlags,
+    ) -> Result<Self, PathError> {
         // canonicalize中没有处理末尾的空格、换行符等
+        let mut new_path = match resolved {
+            Ok((resolved, _)) => resolved,
+            Err(PathError::Loop) => return Err(PathError::Loop),
+            Err(err @ PathError::Vfs(AxError::PermissionDenied)) => return Err(err),
+            Err(PathError::Vfs(_)) => canonicalize(path).await?,
+        };
         if path.ends_with('/') && !new_path.ends_with('/') {
             // 如果原始路径以 '/' 结尾，那么canonicalize后的路径也应该以 '/' 结尾
             new_path.push('/');
         }
+        // 不跟随最后的链接时，模拟的链接也不解析
+        if flags.contains(LookupFlags::NOFOLLOW) {
+            return Ok(Self(new_path));
+        }
         let new_path = real_path(&new_path).await;
-        // assert!(!path.ends_with("/"), "path should not end with '/', link only support file");      // 链接只支持文件
         Ok(Self(new_path))
     }
 
@@ -149,38 +213,85 @@ pub unsafe fn raw_ptr_to_ref_str(start: *const u8) -> &'static str {
     }
 }
 
-/// 用户看到的文件到实际文件的映射
-static LINK_PATH_MAP: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
-/// 实际文件(而不是用户文件)到链接数的映射
-pub static LINK_COUNT_MAP: Mutex<BTreeMap<String, usize>> = Mutex::new(BTreeMap::new());
+/// 模拟的链接表，只记录 FAT 文件系统上的链接
+struct LinkTable {
+    /// 用户看到的文件到实际文件的映射
+    paths: BTreeMap<String, String>,
+    /// 实际文件(而不是用户文件)到链接数的映射
+    counts: BTreeMap<String, usize>,
+}
+
+impl LinkTable {
+    const fn new() -> Self {
+        Self {
+            paths: BTreeMap::new(),
+            counts: BTreeMap::new(),
+        }
+    }
+
+    /// 链接指向的实际文件
+    fn resolve(&self, link: &str) -> Option<&String> {
+        self.paths.get(link)
+    }
+
+    /// `link` 是链接时返回实际文件的链接数，否则返回 0
+    fn count(&self, link: &str) -> usize {
+        self.paths
+            .get(link)
+            .and_then(|target| self.counts.get(target))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// 删除链接，返回它指向的实际文件，以及该文件是否已经没有链接
+    fn remove(&mut self, link: &str) -> Option<(String, bool)> {
+        let target = self.paths.remove(link)?;
+        let count = self.counts.entry(target.clone()).or_insert(0);
+        assert!(*count > 0, "before removing, the link count should > 0");
+        *count -= 1;
+        let unused = *count == 0;
+        if unused {
+            self.counts.remove(&target);
+        }
+        Some((target, unused))
+    }
+
+    /// 建立从 `link` 到 `target` 的链接，返回被替换的旧链接所指向的、已经没有链接的文件
+    fn insert(&mut self, link: &str, target: &str) -> Option<String> {
+        if self.paths.get(link).is_some_and(|old| old == target) {
+            return None;
+        }
+        let unused = self
+            .remove(link)
+            .and_then(|(old, unused)| unused.then_some(old));
+        self.paths.insert(link.to_string(), target.to_string());
+        *self.counts.entry(target.to_string()).or_insert(0) += 1;
+        unused
+    }
+}
+
+static LINKS: Mutex<LinkTable> = Mutex::new(LinkTable::new());
+
+/// 文件系统的类型是否需要模拟链接，目前只有 FAT
+fn needs_emulation(fstype: &str) -> bool {
+    fstype == "vfat"
+}
+
+/// 路径所在的文件系统是否使用模拟的链接
+pub async fn link_emulated(path: &FilePath) -> bool {
+    filesystem_type(path.path())
+        .await
+        .is_ok_and(|fstype| needs_emulation(&fstype))
+}
 
 /// 将用户提供的路径转换成实际的路径
 ///
 /// 如果在链接列表中找不到，则直接返回自己
 pub async fn real_path(src_path: &String) -> String {
     trace!("parse_file_name: {}", src_path);
-    let map = LINK_PATH_MAP.lock().await;
-    // 找到对应的链接
-    match map.get(src_path) {
+    match LINKS.lock().await.resolve(src_path) {
         Some(dest_path) => dest_path.clone(),
-        None => {
-            // 特判gcc的文件夹链接情况，即将一个文件夹前缀换成另一个文件夹前缀
-            static GCC_DIR_SRC: &str =
-                "/riscv64-linux-musl-native/lib/gcc/riscv64-linux-musl/11.2.1/include";
-            static GCC_DIR_DST: &str = "/riscv64-linux-musl-native/include";
-
-            static MUSL_DIR_SRC: &str = "/riscv64-linux-musl-native/riscv64-linux-musl/include";
-            static MUSL_DIR_DST: &str = "/riscv64-linux-musl-native/include";
-            if src_path.starts_with(GCC_DIR_SRC) {
-                // 替换src为dst
-                GCC_DIR_DST.to_string() + src_path.strip_prefix(GCC_DIR_SRC).unwrap()
-            } else if src_path.starts_with(MUSL_DIR_SRC) {
-                // 替换src为dst
-                MUSL_DIR_DST.to_string() + src_path.strip_prefix(MUSL_DIR_SRC).unwrap()
-            } else {
-                src_path.clone()
-            }
-        }
+        None => src_path.clone(),
     }
 }
 
@@ -193,60 +304,31 @@ pub async fn real_path(src_path: &String) -> String {
 /// 这样的话，如果新建了dir1/A，那么就会报错(create_new)或者覆盖原文件(create)，从而影响到dir2/B
 pub async fn remove_link(src_path: &FilePath) -> Option<String> {
     trace!("remove_link: {}", src_path.path());
-    let mut map = LINK_PATH_MAP.lock().await;
-    // 找到对应的链接
-    match map.remove(&src_path.path().to_string()) {
-        Some(dest_path) => {
-            // 更新链接数
-            let mut count_map = LINK_COUNT_MAP.lock().await;
-            let count = count_map.entry(dest_path.clone()).or_insert(0);
-            assert!(*count > 0, "before removing, the link count should > 0");
-            *count -= 1;
-            // 如果链接数为0，那么删除文件
-            if *count == 0 {
-                debug!("link num down to zero, remove file: {}", dest_path);
-                let _ = remove_file(dest_path.as_str());
-            }
-            Some(dest_path)
-        }
-        None => None,
+    let (dest_path, unused) = LINKS.lock().await.remove(src_path.path())?;
+    // 如果链接数为0，那么删除文件
+    if unused {
+        debug!("link num down to zero, remove file: {}", dest_path);
+        let _ = remove_file(dest_path.as_str()).await;
     }
+    Some(dest_path)
 }
 
 /// 获取文件的链接数
 ///
-/// 如果文件不存在，那么返回 0
-/// 如果文件存在，但是没有链接，那么返回 1
-/// 如果文件存在，且有链接，那么返回链接数
+/// 如果文件不是模拟的链接，那么返回 0，链接数由文件系统记录
+/// 如果文件是模拟的链接，那么返回实际文件的链接数
 pub async fn get_link_count(src_path: &String) -> usize {
     trace!("get_link_count: {}", src_path);
-    let map = LINK_PATH_MAP.lock().await;
-    // 找到对应的链接
-    match map.get(src_path) {
-        Some(dest_path) => {
-            let count_map = LINK_COUNT_MAP.lock().await;
-            let count = count_map.get(dest_path).unwrap();
-            *count
-        }
-        None => {
-            // if path_exists(src_path.path()) {
-            //     1
-            // } else {
-            //     0
-            // }
-            0
-        }
-    }
+    LINKS.lock().await.count(src_path)
 }
 
 /// 创建一个链接
 ///
 /// 返回是否创建成功(已存在的链接也会返回 true)
-/// 创建新文件时注意调用该函数创建链接
+///
+/// 链接位于 FAT 文件系统时记录在模拟的链接表中，其余文件系统上直接创建符号链接
 pub async fn create_link(src_path: &FilePath, dest_path: &FilePath) -> bool {
     info!("create_link: {} -> {}", src_path.path(), dest_path.path());
-    // assert!(src_path.is_file() && dest_path.is_file(), "link only support file");
-    // assert_ne!(src_path.path(), dest_path.path(), "link src and dest should not be the same");  // 否则在第一步删除旧链接时可能会删除源文件
     // 检查是否是文件
     if !src_path.is_file() || !dest_path.is_file() {
         debug!("link only support file");
@@ -257,36 +339,33 @@ pub async fn create_link(src_path: &FilePath, dest_path: &FilePath) -> bool {
         debug!("link dest file not exists");
         return false;
     }
-    // 一次性锁定LINK_PATH_MAP，避免重复加锁解锁
-    let mut map = LINK_PATH_MAP.lock().await;
-    // 检查链接是否已存在，并处理旧链接
-    if let Some(old_dest_path) = map.get(&src_path.path().to_string()) {
-        if old_dest_path != &dest_path.path().to_string() {
-            // 旧链接存在且与新链接不同，移除旧链接
-            drop(map); // 释放锁，因为remove_link可能需要锁
-            remove_link(src_path).await;
-            map = LINK_PATH_MAP.lock().await; // 重新获取锁
-        } else {
-            // 链接已存在且相同，无需进一步操作
-            debug!("link already exists");
-            return true;
-        }
+    if !link_emulated(src_path).await {
+        return match symlink(dest_path.path(), src_path.path()).await {
+            Ok(()) | Err(AxError::AlreadyExists) => true,
+            Err(e) => {
+                debug!("failed to create symlink: {:?}", e);
+                false
+            }
+        };
+    }
+    let unused = LINKS.lock().await.insert(src_path.path(), dest_path.path());
+    if let Some(old_dest_path) = unused {
+        debug!("link num down to zero, remove file: {}", old_dest_path);
+        let _ = remove_file(old_dest_path.as_str()).await;
     }
-
-
-    // 创建新链接
-    map.insert(
-        src_path.path().to_string(),
-        dest_path.path().to_string().clone(),
-    );
-
-    // 更新链接计数
-    let mut count_map = LINK_COUNT_MAP.lock().await;
-    let count = count_map.entry(dest_path.path().to_string()).or_insert(0);
-    *count += 1;
     true
 }
 
+/// 需要作为目录时，以及以.或..结尾时, 在 `path` 末尾加上/告诉FilePath它是一个目录
+fn mark_dir(path: &mut String, force_dir: bool) {
+    if force_dir && !path.ends_with('/') {
+        path.push('/');
+    }
+    if path.ends_with('.') {
+        path.push('/');
+    }
+}
+
 /// To deal with the path and return the canonicalized path
 ///
 /// * `dir_fd` - The file descriptor of the directory, if it is AT_FDCWD, the call operates on the current working directory
@@ -295,18 +374,21 @@ pub async fn create_link(src_path: &FilePath, dest_path: &FilePath) -> bool {
 ///
 /// * `force_dir` - If true, the path will be treated as a directory
 ///
+/// * `flags` - How to resolve the path, e.g. whether to follow the symbolic link at the end
+///
 /// The path will be dealt with links and the path will be canonicalized
 pub async fn deal_with_path(
     dir_fd: usize,
     path_addr: Option<*const u8>,
     force_dir: bool,
-) -> AxResult<FilePath> {
+    flags: LookupFlags,
+) -> Result<FilePath, PathError> {
     let executor = current_executor();
     let mut path = "".to_string();
     if let Some(path_addr) = path_addr {
         if path_addr.is_null() {
             axlog::warn!("path address is null");
-            return Err(AxError::BadAddress);
+            return Err(AxError::BadAddress.into());
         }
         executor
             .manual_alloc_for_lazy((path_addr as usize).into()).await
@@ -323,43 +405,36 @@ pub async fn deal_with_path(
             // return Some(FilePath::new(".").unwrap());
             path = String::from(".");
         } else {
-            let fd_table = executor.fd_manager.fd_table.lock().await;
-            if dir_fd >= fd_table.len() {
-                axlog::warn!("fd index out of range");
-                return Err(AxError::InvalidInput);
-            }
-            match fd_table[dir_fd].as_ref() {
+            match executor.fd_manager.get(dir_fd).await {
                 Some(dir) => {
-                    let dir = dir.clone();
-                    path = dir.get_path().await;
+                    let dir_path = dir.get_path().await;
+                    if let Some(node) = dir.dir_node().await {
+                        return FilePath::new_at(&node, &dir_path, "", flags).await;
+                    }
+                    path = dir_path;
                 }
                 None => {
                     axlog::warn!("fd not exist");
-                    return Err(AxError::NotFound);
+                    return Err(AxError::InvalidInput.into());
                 }
             }
         }
     } else if !path.starts_with('/') && dir_fd != AT_FDCWD && dir_fd as u32 != AT_FDCWD as u32 {
-        // 如果不是绝对路径, 且dir_fd不是AT_FDCWD, 则需要将dir_fd和path拼接起来
-        let fd_table = executor.fd_manager.fd_table.lock().await;
-        if dir_fd >= fd_table.len() {
-            axlog::warn!("fd index out of range");
-            return Err(AxError::InvalidInput);
-        }
-        match fd_table[dir_fd].as_ref() {
+        // 如果不是绝对路径, 且dir_fd不是AT_FDCWD, 则从dir_fd对应的目录开始解析
+        match executor.fd_manager.get(dir_fd).await {
             Some(dir) => {
-                if dir.get_type().await != FileIOType::DirDesc {
+                let Some(node) = dir.dir_node().await else {
                     axlog::warn!("selected fd {} is not a dir", dir_fd);
-                    return Err(AxError::NotADirectory);
-                }
-                let dir = dir.clone();
-                // 有没有可能dir的尾部一定是一个/号，所以不用手工添加/
-                path = format!("{}{}", dir.get_path().await, path);
-                axlog::warn!("handled_path: {} dir: {}", path, dir.get_path().await);
+                    return Err(AxError::NotADirectory.into());
+                };
+                mark_dir(&mut path, force_dir);
+                let dir_path = dir.get_path().await;
+                debug!("handled_path: {} dir: {}", path, dir_path);
+                return FilePath::new_at(&node, &dir_path, &path, flags).await;
             }
             None => {
                 axlog::warn!("fd not exist");
-                return Err(AxError::NotFound);
+                return Err(AxError::InvalidInput.into());
             }
         }
     }
@@ -369,12 +444,38 @@ pub async fn deal_with_path(
         assert!(cwd.ends_with('/'));
         path = format!("{}{}", cwd, path);
     }
-    if force_dir && !path.ends_with('/') {
-        path = format!("{}/", path);
+    mark_dir(&mut path, force_dir);
+    FilePath::new_with_flags(path.as_str(), flags).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_fat_is_emulated() {
+        assert!(needs_emulation("vfat"));
+        for fstype in ["rootfs", "tmpfs", "overlay", "proc", "ext4"] {
+            assert!(!needs_emulation(fstype));
+        }
     }
-    if path.ends_with('.') {
-        // 如果path以.或..结尾, 则加上/告诉FilePath::new它是一个目录
-        path = format!("{}/", path);
+
+    #[test]
+    fn link_table_counts_links() {
+        let mut table = LinkTable::new();
+        assert_eq!(table.insert("/a", "/file"), None);
+        assert_eq!(table.insert("/b", "/file"), None);
+        assert_eq!(table.insert("/b", "/file"), None);
+        assert_eq!(table.count("/a"), 2);
+        assert_eq!(table.count("/file"), 0);
+        assert_eq!(table.resolve("/b").map(String::as_str), Some("/file"));
+
+        assert_eq!(table.remove("/a"), Some(("/file".to_string(), false)));
+        assert_eq!(table.remove("/a"), None);
+        // 替换最后一个链接时，旧的实际文件不再被引用
+        assert_eq!(table.insert("/b", "/other"), Some("/file".to_string()));
+        assert_eq!(table.count("/b"), 1);
+        assert_eq!(table.remove("/b"), Some(("/other".to_string(), true)));
+        assert!(table.resolve("/b").is_none());
     }
-    FilePath::new(path.as_str()).await
 }
@@ -5,7 +5,9 @@ use async_mem::MemorySet;
 use axconfig::{MAX_USER_HEAP_SIZE, MAX_USER_STACK_SIZE, USER_HEAP_BASE, USER_STACK_TOP};
 use axhal::{mem::VirtAddr, paging::MappingFlags};
 use elf_parser::{get_app_stack_region, get_auxv_vector, get_elf_entry, get_elf_segments, get_relocate_pairs};
-use crate::link::real_path;
+use crate::cred::Credentials;
+use crate::link::FilePath;
+use async_fs::api::VfsAccess;
 use axerrno::{AxError, AxResult};
 use xmas_elf::program::SegmentData;
 
@@ -13,15 +15,24 @@ use xmas_elf::program::SegmentData;
 
 
 /// 返回应用程序入口，用户栈底，用户堆底
+///
+/// `cred` 是加载程序的进程的凭证，以它的有效用户与用户组检查程序的执行权限
 pub async fn load_app(
     name: String,
     mut args: Vec<String>,
     envs: &Vec<String>,
     memory_set: &mut MemorySet,
+    cred: &Credentials,
 ) -> AxResult<(VirtAddr, VirtAddr, VirtAddr)> {
     if name.ends_with(".sh") {
         args = [vec![String::from("busybox"), String::from("sh")], args].concat();
-        return Box::pin(load_app("busybox".to_string(), args, envs, memory_set)).await;
+        return Box::pin(load_app("busybox".to_string(), args, envs, memory_set, cred)).await;
+    }
+    if let Err(AxError::PermissionDenied) =
+        async_fs::api::check_access(name.as_str(), cred.euid(), cred.egid(), VfsAccess::EXEC).await
+    {
+        info!("App not executable: {}", name);
+        return Err(AxError::PermissionDenied);
     }
     let elf_data = if let Ok(ans) = async_fs::api::read(name.as_str()).await {
         ans
@@ -43,9 +54,12 @@ pub async fn load_app(
         let interp_path = from_utf8(interp).expect("Interpreter path isn't valid UTF-8");
         // remove trailing '\0'
         let interp_path = interp_path.trim_matches(char::from(0)).to_string();
-        let real_interp_path = real_path(&interp_path).await;
+        let real_interp_path = match FilePath::new(&interp_path).await {
+            Ok(path) => path.path().to_string(),
+            Err(_) => interp_path,
+        };
         args = [vec![real_interp_path.clone()], args].concat();
-        return Box::pin(load_app(real_interp_path, args, envs, memory_set)).await;
+        return Box::pin(load_app(real_interp_path, args, envs, memory_set, cred)).await;
     }
     info!("load app args: {:?} name: {}", args, name);
     let elf_base_addr = Some(0x400_0000);
@@ -0,0 +1,144 @@
+//! 查找 initramfs 归档的位置
+//!
+//! 引导程序（如 QEMU 的 `-initrd` 参数）将归档加载到内存中，并在设备树的 `/chosen` 节点中通过
+//! `linux,initrd-start` 与 `linux,initrd-end` 属性给出其物理地址范围。
+//! 未找到时使用编译时通过 `AX_INITRAMFS` 环境变量链接进内核的归档（`initramfs_builtin` feature）。
+
+use axhal::mem::{phys_to_virt, PhysAddr};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+/// 引导程序加载的归档的物理地址范围，起始地址为 0 表示没有
+static INITRD_START: AtomicUsize = AtomicUsize::new(0);
+static INITRD_END: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(feature = "initramfs_builtin")]
+static BUILTIN: &[u8] = include_bytes!(env!("AX_INITRAMFS"));
+
+fn be32(data: &[u8], off: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(off..off + 4)?.try_into().ok()?))
+}
+
+/// 属性值为 32 位或 64 位的大端整数
+fn prop_value(value: &[u8]) -> Option<usize> {
+    match value.len() {
+        4 => Some(u32::from_be_bytes(value.try_into().ok()?) as usize),
+        8 => Some(u64::from_be_bytes(value.try_into().ok()?) as usize),
+        _ => None,
+    }
+}
+
+/// 在设备树的 `/chosen` 节点中查找 initrd 的物理地址范围
+fn find_in_fdt(fdt: &[u8]) -> Option<(usize, usize)> {
+    let off_struct = be32(fdt, 8)? as usize;
+    let off_strings = be32(fdt, 12)? as usize;
+    let (mut start, mut end) = (None, None);
+    let mut pos = off_struct;
+    let mut depth = 0;
+    let mut in_chosen = false;
+    loop {
+        let token = be32(fdt, pos)?;
+        pos += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let len = fdt.get(pos..)?.iter().position(|&b| b == 0)?;
+                depth += 1;
+                if depth == 2 && &fdt[pos..pos + len] == b"chosen" {
+                    in_chosen = true;
+                }
+                pos = (pos + len + 1 + 3) & !3;
+            }
+            FDT_END_NODE => {
+                if in_chosen && depth == 2 {
+                    break;
+                }
+                depth -= 1;
+            }
+            FDT_PROP => {
+                let len = be32(fdt, pos)? as usize;
+                let name_off = off_strings + be32(fdt, pos + 4)? as usize;
+                let value = fdt.get(pos + 8..pos + 8 + len)?;
+                pos = (pos + 8 + len + 3) & !3;
+                // 只关心 `/chosen` 节点自身的属性
+                if !(in_chosen && depth == 2) {
+                    continue;
+                }
+                let name_len = fdt.get(name_off..)?.iter().position(|&b| b == 0)?;
+                match &fdt[name_off..name_off + name_len] {
+                    b"linux,initrd-start" => start = prop_value(value),
+                    b"linux,initrd-end" => end = prop_value(value),
+                    _ => {}
+                }
+            }
+            FDT_NOP => {}
+            FDT_END => break,
+            _ => return None,
+        }
+    }
+    match (start?, end?) {
+        (start, end) if start < end => Some((start, end)),
+        _ => None,
+    }
+}
+
+/// 从设备树中记录引导程序加载的归档，须在初始化内存分配器之前调用
+pub(crate) fn probe(dtb: usize) {
+    if dtb == 0 {
+        return;
+    }
+    let dtb_ptr = phys_to_virt(PhysAddr::from(dtb)).as_usize() as *const u8;
+    // SAFETY: 设备树由引导程序放在内存中，其头部给出了总长度
+    let header = unsafe { core::slice::from_raw_parts(dtb_ptr, 8) };
+    if be32(header, 0) != Some(FDT_MAGIC) {
+        return;
+    }
+    let total = be32(header, 4).unwrap() as usize;
+    let fdt = unsafe { core::slice::from_raw_parts(dtb_ptr, total) };
+    if let Some((start, end)) = find_in_fdt(fdt) {
+        info!("Found initramfs at [{:#x}, {:#x}).", start, end);
+        INITRD_START.store(start, Ordering::Relaxed);
+        INITRD_END.store(end, Ordering::Relaxed);
+    }
+}
+
+/// 引导程序加载的归档占用的物理地址范围，这部分内存不能交给内存分配器
+pub(crate) fn reserved() -> Option<(usize, usize)> {
+    let start = INITRD_START.load(Ordering::Relaxed);
+    let end = INITRD_END.load(Ordering::Relaxed);
+    (start != 0).then_some((start, end))
+}
+
+/// 要解包的归档，引导程序加载的归档优先于链接进内核的归档
+pub(crate) fn archive() -> Option<&'static [u8]> {
+    if let Some((start, end)) = reserved() {
+        let ptr = phys_to_virt(PhysAddr::from(start)).as_usize() as *const u8;
+        // SAFETY: 这段内存在 `release` 之前不会交给内存分配器
+        return Some(unsafe { core::slice::from_raw_parts(ptr, end - start) });
+    }
+    #[cfg(feature = "initramfs_builtin")]
+    return Some(BUILTIN);
+    #[allow(unreachable_code)]
+    None
+}
+
+/// 归档解包完成后，将引导程序加载归档占用的内存交给内存分配器
+pub(crate) fn release() {
+    let Some((start, end)) = reserved() else {
+        return;
+    };
+    INITRD_START.store(0, Ordering::Relaxed);
+    let start = PhysAddr::from(start).align_up_4k();
+    let end = PhysAddr::from(end).align_down_4k();
+    if start < end {
+        let size = end.as_usize() - start.as_usize();
+        if axalloc::global_add_memory(phys_to_virt(start).as_usize(), size).is_ok() {
+            info!("Freed initramfs memory: {} KiB.", size / 1024);
+        }
+    }
+}
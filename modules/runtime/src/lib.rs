@@ -11,6 +11,8 @@
 //! - `multitask`: Enable multi-threading support.
 //! - `smp`: Enable SMP (symmetric multiprocessing) support.
 //! - `fs`: Enable filesystem support.
+//! - `initramfs`: Boot from a cpio archive passed by the bootloader and unpacked into a ramfs root.
+//! - `initramfs_builtin`: Link the cpio archive at `AX_INITRAMFS` into the kernel as a fallback.
 //! - `net`: Enable networking support.
 //! - `display`: Enable graphics support.
 //!
@@ -28,6 +30,9 @@ mod lang_items;
 #[cfg(feature = "smp")]
 mod mp;
 
+#[cfg(feature = "initramfs")]
+mod initrd;
+
 #[cfg(feature = "smp")]
 pub use self::mp::{entered_cpus_num, rust_main_secondary};
 
@@ -126,6 +131,8 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) {
             r.flags
         );
     }
+    #[cfg(feature = "initramfs")]
+    initrd::probe(dtb);
     init_allocator();
 
     #[cfg(feature = "paging")]
@@ -179,7 +186,18 @@ async fn init_devices() -> i32 {
     let all_devices = axdriver::init_drivers();
 
     #[cfg(feature = "fs")]
-    async_fs::init_filesystems(all_devices.block).await;
+    {
+        async_fs::set_clock(axhal::time::wall_time);
+        #[cfg(feature = "initramfs")]
+        if let Some(archive) = initrd::archive() {
+            async_fs::init_initramfs(archive).await.expect("failed to unpack initramfs");
+            initrd::release();
+        } else {
+            async_fs::init_filesystems(all_devices.block).await;
+        }
+        #[cfg(not(feature = "initramfs"))]
+        async_fs::init_filesystems(all_devices.block).await;
+    }
 
     #[cfg(feature = "net")]
     axnet::init_network(all_devices.net);
@@ -194,33 +212,57 @@ pub fn exit_main() { }
 
 #[allow(dead_code)]
 fn init_allocator() {
-    use axhal::mem::{memory_regions, phys_to_virt, MemRegionFlags};
+    use axhal::mem::phys_to_virt;
 
     info!("Initialize global memory allocator...");
     info!("  use {} allocator.", axalloc::global_allocator().name());
 
     let mut max_region_size = 0;
-    let mut max_region_paddr = 0.into();
-    for r in memory_regions() {
-        if r.flags.contains(MemRegionFlags::FREE) && r.size > max_region_size {
-            max_region_size = r.size;
-            max_region_paddr = r.paddr;
+    let mut max_region_paddr = 0;
+    for (paddr, size) in free_regions() {
+        if size > max_region_size {
+            max_region_size = size;
+            max_region_paddr = paddr;
         }
     }
-    for r in memory_regions() {
-        if r.flags.contains(MemRegionFlags::FREE) && r.paddr == max_region_paddr {
-            axalloc::global_init(phys_to_virt(r.paddr).as_usize(), r.size);
+    for (paddr, size) in free_regions() {
+        if paddr == max_region_paddr {
+            axalloc::global_init(phys_to_virt(paddr.into()).as_usize(), size);
             break;
         }
     }
-    for r in memory_regions() {
-        if r.flags.contains(MemRegionFlags::FREE) && r.paddr != max_region_paddr {
-            axalloc::global_add_memory(phys_to_virt(r.paddr).as_usize(), r.size)
+    for (paddr, size) in free_regions() {
+        if paddr != max_region_paddr {
+            axalloc::global_add_memory(phys_to_virt(paddr.into()).as_usize(), size)
                 .expect("add heap memory region failed");
         }
     }
 }
 
+/// 可以交给内存分配器的物理内存区域，除去了引导程序加载的 initramfs 归档所占的部分
+#[allow(dead_code)]
+fn free_regions() -> impl Iterator<Item = (usize, usize)> {
+    use axhal::mem::{memory_regions, MemRegionFlags, PAGE_SIZE_4K};
+
+    #[cfg(feature = "initramfs")]
+    let reserved = initrd::reserved().map(|(start, end)| {
+        (start & !(PAGE_SIZE_4K - 1), (end + PAGE_SIZE_4K - 1) & !(PAGE_SIZE_4K - 1))
+    });
+    #[cfg(not(feature = "initramfs"))]
+    let reserved: Option<(usize, usize)> = None;
+
+    memory_regions()
+        .filter(|r| r.flags.contains(MemRegionFlags::FREE))
+        .flat_map(move |r| {
+            let (start, end) = (r.paddr.as_usize(), r.paddr.as_usize() + r.size);
+            let pieces = match reserved {
+                Some((rs, re)) if rs < end && start < re => [(start, rs.max(start)), (re.min(end), end)],
+                _ => [(start, end), (end, end)],
+            };
+            pieces.into_iter().filter(|&(s, e)| s < e).map(|(s, e)| (s, e - s))
+        })
+}
+
 cfg_if::cfg_if! {
     if #[cfg(feature = "paging")] {
         use axhal::paging::PageTable;
@@ -83,6 +83,18 @@ impl WaitTaskList {
         false
     }
 
+    /// remove all nodes of the given task without waking it
+    pub fn remove_task(&mut self, waker: &Waker) {
+        let mut cursor = self.list.cursor_front_mut();
+        while let Some(node) = cursor.current() {
+            if node.waker.will_wake(waker) {
+                cursor.remove_current();
+            } else {
+                cursor.move_next();
+            }
+        }
+    }
+
     /// notify first task and remove it
     pub fn notify_one(&mut self) -> bool {
         if let Some(node) = self.list.pop_front() {
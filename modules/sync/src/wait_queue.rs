@@ -23,7 +23,7 @@ impl WaitQueue {
 
     /// 当前任务进入阻塞状态，将 cx 注册到等待队列中
     pub fn wait<'a>(&'a self) -> WaitFuture<'a> {
-        WaitFuture { wq: self, flag: false }
+        WaitFuture { wq: self, node: None }
     }
 
     /// 当前任务等待某个条件成功
@@ -94,6 +94,13 @@ impl WaitQueue {
         self.queue.lock().notify_task(waker)
     }
 
+    /// 将给定任务注册的所有等待项移出等待队列，但不唤醒它
+    ///
+    /// 任务不再等待时（如超时返回）应调用，以免之后的唤醒落在已经退出的任务上
+    pub fn remove_task(&self, waker: &Waker) {
+        self.queue.lock().remove_task(waker)
+    }
+
     /// Wakes up one task in the wait queue, usually the first one.
     pub fn notify_one(&self) -> bool {
         self.queue.lock().notify_one()
@@ -107,21 +114,36 @@ impl WaitQueue {
 
 pub struct WaitFuture<'a> {
     wq: &'a WaitQueue,
-    flag: bool
+    /// 第一次轮询时加入等待队列的节点
+    node: Option<Arc<WaitWakerNode>>,
 }
 
 impl<'a> Future for WaitFuture<'a> {
     type Output = ();
 
     fn poll(self: core::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let waker_node = Arc::new(WaitWakerNode::new(cx.waker().clone()));
-        let Self { wq, flag } = self.get_mut();
-        if !*flag {
-            wq.queue.lock().prepare_to_wait(waker_node);
-            Poll::Pending
-        } else {
-            wq.queue.lock().remove(&waker_node);
-            Poll::Ready(())
+        let Self { wq, node } = self.get_mut();
+        match node.take() {
+            None => {
+                let waker_node = Arc::new(WaitWakerNode::new(cx.waker().clone()));
+                wq.queue.lock().prepare_to_wait(waker_node.clone());
+                *node = Some(waker_node);
+                Poll::Pending
+            }
+            Some(waker_node) => {
+                // 被唤醒时节点已经出队，移除一个不在队列中的节点什么也不做
+                wq.queue.lock().remove(&waker_node);
+                Poll::Ready(())
+            }
+        }
+    }
+}
+
+impl Drop for WaitFuture<'_> {
+    /// 等待被取消时将节点移出等待队列
+    fn drop(&mut self) {
+        if let Some(waker_node) = self.node.take() {
+            self.wq.queue.lock().remove(&waker_node);
         }
     }
 }
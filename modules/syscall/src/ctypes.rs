@@ -112,6 +112,14 @@ impl TimeSecs {
         self.tv_sec * axconfig::TIMER_FREQUENCY + (nanos_to_ticks(self.tv_nsec as u64) as usize)
     }
 
+    /// 由 [`Duration`](core::time::Duration) 构造一个 TimeSecs
+    pub fn from_duration(time: core::time::Duration) -> Self {
+        TimeSecs {
+            tv_sec: time.as_secs() as usize,
+            tv_nsec: time.subsec_nanos() as usize,
+        }
+    }
+
     /// set the Timesecs to the given time
     ///
     /// If the nsec is UTIME_NOW, set the time to now
@@ -329,11 +337,54 @@ numeric_enum_macro::numeric_enum! {
         F_GETFL = 3,
         /// 设置 flags 信息
         F_SETFL = 4,
+        /// 查询与给定记录锁冲突的锁
+        F_GETLK = 5,
+        /// 设置或释放记录锁，冲突时立即返回
+        F_SETLK = 6,
+        /// 设置或释放记录锁，冲突时等待
+        F_SETLKW = 7,
+        /// 查询与给定 OFD 锁冲突的锁
+        F_OFD_GETLK = 36,
+        /// 设置或释放 OFD 锁，冲突时立即返回
+        F_OFD_SETLK = 37,
+        /// 设置或释放 OFD 锁，冲突时等待
+        F_OFD_SETLKW = 38,
         /// 复制 fd，然后设置 cloexec 信息，即 exec 成功时删除该 fd
         F_DUPFD_CLOEXEC = 1030,
+        /// 修改管道的容量
+        F_SETPIPE_SZ = 1031,
+        /// 获取管道的容量
+        F_GETPIPE_SZ = 1032,
+        /// 为 memfd 添加 seals
+        F_ADD_SEALS = 1033,
+        /// 获取文件的 seals
+        F_GET_SEALS = 1034,
     }
 }
 
+/// fcntl 记录锁使用的结构体
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Flock {
+    /// 锁的类型，F_RDLCK、F_WRLCK 或 F_UNLCK
+    pub l_type: i16,
+    /// l_start 的起点，SEEK_SET、SEEK_CUR 或 SEEK_END
+    pub l_whence: i16,
+    /// 锁的起始位置
+    pub l_start: i64,
+    /// 锁的长度，为 0 时表示一直到文件末尾
+    pub l_len: i64,
+    /// 持有冲突锁的进程，仅由 F_GETLK 返回
+    pub l_pid: i32,
+}
+
+/// 读锁
+pub const F_RDLCK: i16 = 0;
+/// 写锁
+pub const F_WRLCK: i16 = 1;
+/// 解锁
+pub const F_UNLCK: i16 = 2;
+
 /// syscall_info 用到的 结构体
 #[repr(C)]
 #[derive(Debug)]
@@ -491,7 +542,7 @@ bitflags! {
 #[repr(C)]
 #[derive(Debug)]
 pub struct FsStat {
-    /// 是个 magic number，每个知名的 fs 都各有定义，但显然我们没有
+    /// 文件系统类型的 magic number，与 Linux 的 `*_SUPER_MAGIC` 一致
     pub f_type: i64,
     /// 最优传输块大小
     pub f_bsize: i64,
@@ -507,31 +558,33 @@ pub struct FsStat {
     pub f_ffree: u64,
     /// 文件系统编号，但实际上对于不同的OS差异很大，所以不会特地去用
     pub f_fsid: [i32; 2],
-    /// 文件名长度限制，这个OS默认FAT已经使用了加长命名
+    /// 文件名长度限制
     pub f_namelen: isize,
     /// 片大小
     pub f_frsize: isize,
-    /// 一些选项，但其实也没用到
+    /// 挂载参数，即 `ST_*` 标志位
     pub f_flags: isize,
     /// 空余 padding
     pub f_spare: [isize; 4],
 }
 
-/// 获取一个基础的fsstat
-pub fn get_fs_stat() -> FsStat {
-    FsStat {
-        f_type: 0,
-        f_bsize: 1024,
-        f_blocks: 0x4000_0000 / 512,
-        f_bfree: 1,
-        f_bavail: 1,
-        f_files: 1,
-        f_ffree: 1,
-        f_fsid: [0, 0],
-        f_namelen: 256,
-        f_frsize: 0x1000,
-        f_flags: 0,
-        f_spare: [0, 0, 0, 0],
+impl From<async_fs::api::FileSystemInfo> for FsStat {
+    fn from(info: async_fs::api::FileSystemInfo) -> Self {
+        Self {
+            f_type: info.fs_type as i64,
+            f_bsize: info.block_size as i64,
+            f_blocks: info.blocks,
+            f_bfree: info.blocks_free,
+            f_bavail: info.blocks_avail,
+            f_files: info.files,
+            f_ffree: info.files_free,
+            f_fsid: [0, 0],
+            f_namelen: info.name_len as isize,
+            f_frsize: info.block_size as isize,
+            // ST_VALID：f_flags 有效
+            f_flags: (info.flags | 0x20) as isize,
+            f_spare: [0, 0, 0, 0],
+        }
     }
 }
 
@@ -698,3 +751,27 @@ pub struct CloneArgs {
     /// 该信息 Starry 暂未支持
     pub cgroup: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::FsStat;
+    use async_fs::api::FileSystemInfo;
+
+    #[test]
+    fn fs_stat_from_info() {
+        let mut info = FileSystemInfo::new(0x4d44, 4096, 255);
+        info.blocks = 100;
+        info.blocks_free = 40;
+        info.blocks_avail = 30;
+        // ST_RDONLY | ST_NOEXEC
+        info.flags = 0x1 | 0x8;
+        let stat = FsStat::from(info);
+        assert_eq!(stat.f_type, 0x4d44);
+        assert_eq!((stat.f_bsize, stat.f_frsize), (4096, 4096));
+        assert_eq!((stat.f_blocks, stat.f_bfree, stat.f_bavail), (100, 40, 30));
+        assert_eq!((stat.f_files, stat.f_ffree), (0, 0));
+        assert_eq!(stat.f_namelen, 255);
+        // 总是带有 ST_VALID
+        assert_eq!(stat.f_flags, 0x1 | 0x8 | 0x20);
+    }
+}
@@ -13,7 +13,7 @@ use ctypes::*;
 mod syscall;
 mod syscall_fs;
 mod syscall_mem;
-// mod syscall_net;
+mod syscall_net;
 mod syscall_task;
 
 pub use async_fs::api::{File, OpenFlags};
@@ -1,18 +1,31 @@
-use axhal::time::current_ticks;
-use bitflags::bitflags;
+//! epoll 实例
+//!
+//! 每个被监视的文件对应一个 [`EpollItem`]，它本身作为 waker 注册在文件上。文件的状态变化时唤醒该 waker，
+//! 把对应的项放入 epoll 实例的就绪队列，epoll_wait 只检查就绪队列中的项，开销与就绪的文件数成正比。
+//!
+//! 注册在文件上的 waker 被唤醒一次后就会失效，因此每次检查一个项之前都会重新注册。
+//! 不会主动唤醒等待者的文件（如标准输入）无法放入就绪队列，每次 epoll_wait 都要检查它们。
+//! 实例被关闭时不撤销注册在文件上的 waker，因为释放可能就发生在文件唤醒 waker 的过程中，
+//! 这些 waker 在文件下一次唤醒时被丢弃。
 extern crate alloc;
-use alloc::{
-    collections::{BTreeMap, BTreeSet},
-    sync::Arc,
-    vec::Vec,
-};
-use axerrno::{AxError, AxResult};
 
-use axfs::api::{AsyncFileIOExt, FileIO, FileIOExt, FileIOType, OpenFlags, SeekFrom};
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::task::Wake;
+use alloc::vec::Vec;
+use async_fs::api::{async_trait, FileIO, FileIOType, OpenFlags};
+use axhal::time::{current_time, TimeValue};
+use bitflags::bitflags;
+use core::future::poll_fn;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker};
+use spinlock::SpinNoIrq;
+use sync::WaitQueue;
 
+use crate::syscall_fs::imp::{file_events, sleep_until_woken};
 use crate::SyscallError;
-use async_executor::current_executor;
-use axsync::Mutex;
 
 bitflags! {
     /// 定义epoll事件的类别
@@ -36,9 +49,24 @@ bitflags! {
     }
 }
 
-#[repr(packed(1))]
-#[derive(Debug, Clone, Copy)]
+/// 与 EPOLLEXCLUSIVE 一起使用时允许的标志
+const EXCLUSIVE_OK_BITS: EpollEventType = EpollEventType::EPOLLIN
+    .union(EpollEventType::EPOLLOUT)
+    .union(EpollEventType::EPOLLERR)
+    .union(EpollEventType::EPOLLHUP)
+    .union(EpollEventType::EPOLLWAKEUP)
+    .union(EpollEventType::EPOLLET)
+    .union(EpollEventType::EPOLLEXCLUSIVE);
+
+/// epoll 实例之间嵌套的最大深度
+const EP_MAX_NESTS: usize = 4;
+
 /// 定义一个epoll事件
+///
+/// x86_64 上 `struct epoll_event` 是紧凑排列的，其他架构上 `data` 按 8 字节对齐
+#[repr(C)]
+#[cfg_attr(target_arch = "x86_64", repr(packed))]
+#[derive(Debug, Clone, Copy)]
 pub struct EpollEvent {
     /// 事件类型
     pub event_type: EpollEventType,
@@ -59,215 +87,515 @@ numeric_enum_macro::numeric_enum! {
     }
 }
 
-pub struct EpollFile {
-    /// 定义内部可变变量
-    /// 由于存在clone，所以要用arc指针包围
-    pub inner: Arc<Mutex<EpollFileInner>>,
+#[derive(Clone, Copy)]
+struct ItemState {
+    /// 关心的事件与 EPOLLET 等标志
+    events: EpollEventType,
+    data: u64,
+    /// EPOLLONESHOT 的项报告一次后被禁用，直到 EPOLL_CTL_MOD；被删除的项也会被禁用
+    disabled: bool,
+}
 
-    /// 文件打开的标志位
-    pub flags: Mutex<OpenFlags>,
+/// epoll 实例中被监视的一个文件
+struct EpollItem {
+    fd: i32,
+    /// 不持有文件的引用，文件关闭后对应的项在下次检查时被移除
+    file: Weak<dyn FileIO>,
+    epoll: Weak<EpollFile>,
+    state: SpinNoIrq<ItemState>,
+    /// 文件能否在状态变化时主动唤醒 waker
+    notifying: AtomicBool,
+    /// 是否已经在就绪队列中
+    queued: AtomicBool,
 }
 
-pub struct EpollFileInner {
-    /// 监控的所有事件，通过map来进行映射，根据fd找到对应的event
-    monitor_list: BTreeMap<i32, EpollEvent>,
-    /// 响应的事件集
-    _response_list: BTreeSet<i32>,
+impl EpollItem {
+    fn waker(self: &Arc<Self>) -> Waker {
+        Waker::from(self.clone())
+    }
+
+    fn notifying(&self) -> bool {
+        self.notifying.load(Ordering::Acquire)
+    }
+
+    /// 重新在文件上注册 waker，之前注册但还未被唤醒的 waker 会被撤销
+    fn rearm(self: &Arc<Self>, file: &dyn FileIO) {
+        let waker = self.waker();
+        file.unregister_ready_waker(&waker);
+        file.register_ready_waker(&mut Context::from_waker(&waker));
+    }
+
+    fn is_exclusive(&self) -> bool {
+        self.state.lock().events.contains(EpollEventType::EPOLLEXCLUSIVE)
+    }
+}
+
+impl Wake for EpollItem {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    /// 文件的状态可能发生了变化，将该项放入就绪队列并唤醒等待的任务
+    fn wake_by_ref(self: &Arc<Self>) {
+        // 这里可能持有文件等待队列的锁，不能再访问文件
+        let Some(epoll) = self.epoll.upgrade() else {
+            return;
+        };
+        if self.state.lock().disabled {
+            return;
+        }
+        epoll.push_ready(self);
+        if self.is_exclusive() && !exclusive_chosen(self) {
+            return;
+        }
+        epoll.waiters.notify_one();
+        epoll.poll_waiters.notify_all();
+    }
+}
+
+/// 以 EPOLLEXCLUSIVE 监视同一文件的项，键为文件的地址
+static EXCLUSIVE_GROUPS: SpinNoIrq<BTreeMap<usize, Vec<Weak<EpollItem>>>> =
+    SpinNoIrq::new(BTreeMap::new());
+
+fn file_key(file: &Weak<dyn FileIO>) -> usize {
+    file.as_ptr() as *const () as usize
+}
+
+/// 文件就绪时，同组的项都会被放入各自的就绪队列，但只有第一个有任务在等待的 epoll 实例会被唤醒
+///
+/// 同一次唤醒中组内每一项得到的结果相同，因此只有一个实例被唤醒
+fn exclusive_chosen(item: &Arc<EpollItem>) -> bool {
+    let mut groups = EXCLUSIVE_GROUPS.lock();
+    let Some(group) = groups.get_mut(&file_key(&item.file)) else {
+        return true;
+    };
+    group.retain(|other| other.strong_count() > 0);
+    let chosen = group.iter().filter_map(Weak::upgrade).find(|other| {
+        other
+            .epoll
+            .upgrade()
+            .is_some_and(|epoll| epoll.waiting.load(Ordering::Acquire) > 0)
+    });
+    chosen.map_or(true, |chosen| Arc::ptr_eq(&chosen, item))
+}
+
+fn remove_exclusive(item: &Arc<EpollItem>) {
+    let mut groups = EXCLUSIVE_GROUPS.lock();
+    let key = file_key(&item.file);
+    if let Some(group) = groups.get_mut(&key) {
+        group.retain(|other| other.strong_count() > 0 && !Weak::ptr_eq(other, &Arc::downgrade(item)));
+        if group.is_empty() {
+            groups.remove(&key);
+        }
+    }
+}
+
+/// 文件当前发生的、`interest` 关心的事件，错误与挂起总是会被报告
+async fn item_events(file: &dyn FileIO, interest: EpollEventType) -> EpollEventType {
+    let mut revents = EpollEventType::from_bits_truncate(file_events(file).await.bits() as u32);
+    if revents.contains(EpollEventType::EPOLLIN) {
+        revents |= EpollEventType::EPOLLRDNORM;
+    }
+    if revents.contains(EpollEventType::EPOLLOUT) {
+        revents |= EpollEventType::EPOLLWRNORM;
+    }
+    revents & (interest | EpollEventType::EPOLLERR | EpollEventType::EPOLLHUP)
+}
+
+/// epoll 实例
+pub struct EpollFile {
+    this: Weak<EpollFile>,
+    /// 监控的所有文件，根据fd找到对应的项
+    items: SpinNoIrq<BTreeMap<i32, Arc<EpollItem>>>,
+    /// 可能就绪的项
+    ready: SpinNoIrq<VecDeque<Arc<EpollItem>>>,
+    /// 不会主动唤醒 waker 的文件对应的项
+    polled: SpinNoIrq<Vec<Arc<EpollItem>>>,
+    /// 在 epoll_wait 中等待的任务
+    waiters: WaitQueue,
+    /// 在 epoll_wait 中等待的任务数目
+    waiting: AtomicUsize,
+    /// 通过 poll 或者外层的 epoll 等待该实例就绪的任务
+    poll_waiters: WaitQueue,
+    /// 文件打开的标志位
+    flags: SpinNoIrq<OpenFlags>,
 }
 
 impl EpollFile {
     /// 新建一个epoll文件
-    pub fn new() -> Self {
-        Self {
-            inner: Arc::new(Mutex::new(EpollFileInner {
-                monitor_list: BTreeMap::new(),
-                _response_list: BTreeSet::new(),
-            })),
-            flags: Mutex::new(OpenFlags::empty()),
-        }
+    pub fn new(flags: OpenFlags) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            items: SpinNoIrq::new(BTreeMap::new()),
+            ready: SpinNoIrq::new(VecDeque::new()),
+            polled: SpinNoIrq::new(Vec::new()),
+            waiters: WaitQueue::new(),
+            waiting: AtomicUsize::new(0),
+            poll_waiters: WaitQueue::new(),
+            flags: SpinNoIrq::new(flags),
+        })
     }
 
-    /// 获取另外一份epoll文件，存储在fd manager中
-    /// 这是对Arc的clone，即获取指针副本
-    pub fn clone(&self) -> Self {
-        Self {
-            inner: self.inner.clone(),
-            flags: Mutex::new(*self.flags.lock()),
+    fn push_ready(&self, item: &Arc<EpollItem>) {
+        if !item.queued.swap(true, Ordering::AcqRel) {
+            self.ready.lock().push_back(item.clone());
         }
     }
 
-    /// 判断fd是否在monitor_list中
-    pub fn contains(&self, fd: i32) -> bool {
-        let inner = self.inner.lock();
-        if inner.monitor_list.contains_key(&fd) {
+    /// 监视 `target` 是否会形成环，或者使嵌套过深
+    fn creates_loop(&self, target: &EpollFile, depth: usize) -> bool {
+        if core::ptr::eq(self, target) || depth >= EP_MAX_NESTS {
             return true;
         }
-        false
+        let files: Vec<Arc<dyn FileIO>> =
+            target.items.lock().values().filter_map(|item| item.file.upgrade()).collect();
+        files.iter().any(|file| {
+            (**file)
+                .as_any()
+                .downcast_ref::<EpollFile>()
+                .is_some_and(|inner| self.creates_loop(inner, depth + 1))
+        })
     }
 
     /// 控制指定的事件，改变其对应的事件内容
     ///
-    /// 成功返回0，错误返回对应的编号
-    pub fn epoll_ctl(
+    /// `file` 是 `fd` 对应的文件，`event` 在 DEL 时被忽略
+    pub async fn epoll_ctl(
         &self,
         op: EpollCtl,
         fd: i32,
+        file: Arc<dyn FileIO>,
         event: EpollEvent,
-    ) -> Result<isize, SyscallError> {
-        let mut inner = self.inner.lock();
+    ) -> Result<(), SyscallError> {
+        let events = event.event_type;
+        let data = event.data;
+        let exclusive = events.contains(EpollEventType::EPOLLEXCLUSIVE);
+        let target = (*file).as_any().downcast_ref::<EpollFile>();
         match op {
-            // 添加事件
             EpollCtl::ADD => {
-                if inner.monitor_list.contains_key(&fd) {
-                    // return Err(SyscallError::EEXIST);
-                    // TODO : fd close callback ?
-                    inner.monitor_list.insert(fd, event);
+                if matches!(file.get_type().await, FileIOType::FileDesc | FileIOType::DirDesc) {
+                    return Err(SyscallError::EPERM);
+                }
+                if exclusive && (!EXCLUSIVE_OK_BITS.contains(events) || target.is_some()) {
+                    return Err(SyscallError::EINVAL);
+                }
+                if let Some(target) = target {
+                    if core::ptr::eq(self, target) {
+                        return Err(SyscallError::EINVAL);
+                    }
+                    if self.creates_loop(target, 0) {
+                        return Err(SyscallError::ELOOP);
+                    }
+                }
+                if self.find_item(fd, &file).is_some() {
+                    return Err(SyscallError::EEXIST);
+                }
+                let item = Arc::new(EpollItem {
+                    fd,
+                    file: Arc::downgrade(&file),
+                    epoll: self.this.clone(),
+                    state: SpinNoIrq::new(ItemState { events, data, disabled: false }),
+                    notifying: AtomicBool::new(false),
+                    queued: AtomicBool::new(false),
+                });
+                // fd 之前对应的文件已经关闭，或者 fd 被复用为其他文件
+                let stale = self.items.lock().insert(fd, item.clone());
+                if let Some(stale) = stale {
+                    self.forget(&stale);
+                }
+                if exclusive {
+                    EXCLUSIVE_GROUPS
+                        .lock()
+                        .entry(file_key(&item.file))
+                        .or_default()
+                        .push(Arc::downgrade(&item));
+                }
+                // 注册 waker 的同时得知文件能否主动唤醒
+                let notifying = file.register_ready_waker(&mut Context::from_waker(&item.waker()));
+                item.notifying.store(notifying, Ordering::Release);
+                if notifying {
+                    // 下一次 epoll_wait 检查它是否已经就绪
+                    item.wake_by_ref();
+                } else {
+                    self.polled.lock().push(item);
+                    self.waiters.notify_one();
+                    self.poll_waiters.notify_all();
                 }
-                inner.monitor_list.insert(fd, event);
             }
-            // 删除事件
             EpollCtl::DEL => {
-                if !inner.monitor_list.contains_key(&fd) {
-                    return Err(SyscallError::ENOENT);
-                }
-                inner.monitor_list.remove(&fd);
+                let item = self.find_item(fd, &file).ok_or(SyscallError::ENOENT)?;
+                self.forget(&item);
+                file.unregister_ready_waker(&item.waker());
             }
-            // 修改对应事件
             EpollCtl::MOD => {
-                // 对于不存在的事件，返回错误
-                // 即modify要求原先文件存在对应事件，才能进行“修改”
-                if !inner.monitor_list.contains_key(&fd) {
-                    return Err(SyscallError::ENOENT);
+                let item = self.find_item(fd, &file).ok_or(SyscallError::ENOENT)?;
+                if exclusive || item.is_exclusive() {
+                    return Err(SyscallError::EINVAL);
+                }
+                *item.state.lock() = ItemState { events, data, disabled: false };
+                if item.notifying() {
+                    item.wake_by_ref();
                 }
-                inner.monitor_list.insert(fd, event);
             }
         }
-        Ok(0)
+        Ok(())
     }
 
-    /// 实现epoll wait，在规定超时时间内收集达到触发条件的事件
-    ///
-    /// 实现原理和ppoll很像
-    pub async fn epoll_wait(&self, expire_time: usize) -> AxResult<Vec<EpollEvent>> {
-        let mut ret_events = Vec::new();
-        loop {
-            let current_process = current_executor();
-            for (fd, req_event) in self.inner.lock().await.monitor_list.iter() {
-                let fd_table = current_process.fd_manager.fd_table.lock().await;
-                if let Some(file) = &fd_table[*fd as usize] {
-                    let mut ret_event_type = EpollEventType::empty();
-                    // read unalign: copy the field contents to a local variable
-                    let req_type = req_event.event_type;
-                    if file.is_hang_up().await {
-                        ret_event_type |= EpollEventType::EPOLLHUP;
-                    }
-                    if file.in_exceptional_conditions().await {
-                        ret_event_type |= EpollEventType::EPOLLERR;
-                    }
-                    if file.ready_to_read().await && req_type.contains(EpollEventType::EPOLLIN) {
-                        ret_event_type |= EpollEventType::EPOLLIN;
-                    }
-                    if file.ready_to_write().await && req_type.contains(EpollEventType::EPOLLOUT) {
-                        ret_event_type |= EpollEventType::EPOLLOUT;
-                    }
-                    if !ret_event_type.is_empty() {
-                        let mut ret_event = *req_event;
-                        ret_event.event_type = ret_event_type;
-                        ret_events.push(ret_event);
-                    }
-                    // 若文件存在但未响应，此时不加入到ret中，并以此作为是否终止的条件
-                } else {
-                    // 若文件不存在，认为不存在也是一种响应，所以要加入到ret中，并以此作为是否终止的条件
-                    ret_events.push(EpollEvent {
-                        event_type: EpollEventType::EPOLLERR,
-                        data: req_event.data,
-                    });
-                }
+    fn find_item(&self, fd: i32, file: &Arc<dyn FileIO>) -> Option<Arc<EpollItem>> {
+        let items = self.items.lock();
+        let item = items.get(&fd)?;
+        item.file
+            .upgrade()
+            .is_some_and(|old| Arc::ptr_eq(&old, file))
+            .then(|| item.clone())
+    }
+
+    /// 将项从实例中移除，已经在就绪队列中的项在被取出时丢弃
+    fn forget(&self, item: &Arc<EpollItem>) {
+        item.state.lock().disabled = true;
+        let mut items = self.items.lock();
+        if items.get(&item.fd).is_some_and(|other| Arc::ptr_eq(other, item)) {
+            items.remove(&item.fd);
+        }
+        drop(items);
+        if !item.notifying() {
+            self.polled.lock().retain(|other| !Arc::ptr_eq(other, item));
+        }
+        if item.is_exclusive() {
+            remove_exclusive(item);
+        }
+    }
+
+    /// 检查就绪队列中的项，最多返回 `max` 个事件
+    async fn collect(&self, max: usize) -> Vec<EpollEvent> {
+        let mut batch: VecDeque<Arc<EpollItem>> = core::mem::take(&mut *self.ready.lock());
+        batch.extend(self.polled.lock().iter().cloned());
+        let mut events = Vec::new();
+        let mut requeue = Vec::new();
+        while events.len() < max {
+            let Some(item) = batch.pop_front() else {
+                break;
+            };
+            if item.notifying() {
+                // 之后的唤醒需要重新将它放入就绪队列
+                item.queued.store(false, Ordering::Release);
             }
-            if !ret_events.is_empty() {
-                // 此时收到了响应，直接返回
-                return Ok(ret_events);
+            let state = *item.state.lock();
+            if state.disabled {
+                continue;
             }
-            // 否则直接block
-            if current_ticks() as usize > expire_time {
-                return Ok(ret_events);
+            let Some(file) = item.file.upgrade() else {
+                self.forget(&item);
+                continue;
+            };
+            if item.notifying() {
+                item.rearm(file.as_ref());
             }
-            yield_now_task();
+            let revents = item_events(file.as_ref(), state.events).await;
+            if revents.is_empty() {
+                continue;
+            }
+            events.push(EpollEvent {
+                event_type: revents,
+                data: state.data,
+            });
+            if state.events.contains(EpollEventType::EPOLLONESHOT) {
+                item.state.lock().disabled = true;
+            } else if !state.events.contains(EpollEventType::EPOLLET) && item.notifying() {
+                // 水平触发的项在下一次 epoll_wait 时仍需检查
+                requeue.push(item);
+            }
+        }
+        // 没来得及检查的项仍在就绪队列中
+        let mut ready = self.ready.lock();
+        for item in batch.into_iter().filter(|item| item.notifying()) {
+            ready.push_back(item);
+        }
+        drop(ready);
+        for item in requeue {
+            self.push_ready(&item);
+        }
+        if !self.ready.lock().is_empty() && self.waiting.load(Ordering::Acquire) > 0 {
+            self.waiters.notify_one();
+        }
+        events
+    }
 
-            if current_process.have_signals().is_some() {
-                return Err(AxError::Timeout);
+    /// 实现epoll wait，等待到有事件发生或者到达 `deadline`，最多返回 `max` 个事件
+    ///
+    /// `deadline` 为 None 时一直等待
+    pub async fn epoll_wait(&self, max: usize, deadline: Option<TimeValue>) -> Vec<EpollEvent> {
+        let waker = poll_fn(|cx| Poll::Ready(cx.waker().clone())).await;
+        loop {
+            let events = self.collect(max).await;
+            if !events.is_empty() || deadline.is_some_and(|deadline| current_time() >= deadline) {
+                return events;
+            }
+            self.waiting.fetch_add(1, Ordering::AcqRel);
+            let ready = poll_fn(|cx| {
+                let ready = self.ready.lock();
+                if ready.is_empty() {
+                    let _ = self.waiters.wait_until(cx, || false);
+                }
+                Poll::Ready(!ready.is_empty())
+            })
+            .await;
+            if !ready {
+                // TODO: 内核支持信号后，还需要在收到未被屏蔽的信号时返回 EINTR
+                let notified = self.polled.lock().is_empty();
+                sleep_until_woken(deadline, notified).await;
             }
+            self.waiters.remove_task(&waker);
+            self.waiting.fetch_sub(1, Ordering::AcqRel);
         }
     }
 }
 
 /// EpollFile也是一种文件，应当为其实现一个file io trait
-impl AsyncFileIOExt for EpollFile {
-    fn read(&self, _buf: &mut [u8]) -> AxResult<usize> {
-        Err(AxError::Unsupported)
-    }
-    fn write(&self, _buf: &[u8]) -> AxResult<usize> {
-        Err(AxError::Unsupported)
-    }
-    fn flush(&self) -> AxResult {
-        Err(AxError::Unsupported)
-    }
-    fn seek(&self, _pos: SeekFrom) -> AxResult<u64> {
-        Err(AxError::Unsupported)
-    }
-    fn readable(&self) -> bool {
+#[async_trait]
+impl FileIO for EpollFile {
+    async fn readable(&self) -> bool {
         false
     }
-    fn writable(&self) -> bool {
+
+    async fn writable(&self) -> bool {
         false
     }
-    fn executable(&self) -> bool {
+
+    async fn executable(&self) -> bool {
         false
     }
-    /// epoll file也是一个文件描述符
-    fn get_type(&self) -> FileIOType {
-        FileIOType::FileDesc
+
+    async fn get_type(&self) -> FileIOType {
+        FileIOType::Other
     }
 
-    fn set_close_on_exec(&self, is_set: bool) -> bool {
-        if is_set {
-            // 设置close_on_exec位置
-            *self.flags.lock() |= OpenFlags::CLOEXEC;
-        } else {
-            *self.flags.lock() &= !OpenFlags::CLOEXEC;
-        }
+    async fn get_path(&self) -> String {
+        String::from("anon_inode:[eventpoll]")
+    }
+
+    async fn set_status(&self, flags: OpenFlags) -> bool {
+        *self.flags.lock() = flags;
         true
     }
 
-    fn get_status(&self) -> OpenFlags {
+    async fn get_status(&self) -> OpenFlags {
         *self.flags.lock()
     }
 
-    fn ready_to_read(&self) -> bool {
-        // 如果当前epoll事件确实正在等待事件响应，那么可以认为事件准备好read，尽管无法读到实际内容
-        let process = current_executor();
-        let fd_table = process.fd_manager.fd_table.lock();
-        for (fd, req_event) in self.inner.lock().monitor_list.iter() {
-            if let Some(file) = fd_table[*fd as usize].as_ref() {
-                let mut ret_event_type = EpollEventType::empty();
-                let req_type = req_event.event_type;
-                if file.is_hang_up() {
-                    ret_event_type |= EpollEventType::EPOLLHUP;
-                }
-                if file.in_exceptional_conditions() {
-                    ret_event_type |= EpollEventType::EPOLLERR;
-                }
-                if file.ready_to_read() && req_type.contains(EpollEventType::EPOLLIN) {
-                    ret_event_type |= EpollEventType::EPOLLIN;
-                }
-                if file.ready_to_write() && req_type.contains(EpollEventType::EPOLLOUT) {
-                    ret_event_type |= EpollEventType::EPOLLOUT;
-                }
-                if !ret_event_type.is_empty() {
+    /// 有被监视的文件发生了关心的事件时可读，只检查可能就绪的项
+    async fn ready_to_read(&self) -> bool {
+        let mut items: Vec<Arc<EpollItem>> = self.ready.lock().iter().cloned().collect();
+        items.extend(self.polled.lock().iter().cloned());
+        for item in items {
+            let state = *item.state.lock();
+            if state.disabled {
+                continue;
+            }
+            if let Some(file) = item.file.upgrade() {
+                if !item_events(file.as_ref(), state.events).await.is_empty() {
                     return true;
                 }
             }
         }
         false
     }
+
+    fn register_ready_waker(&self, cx: &mut Context<'_>) -> bool {
+        let _ = self.poll_waiters.wait_until(cx, || false);
+        self.polled.lock().is_empty()
+    }
+
+    fn unregister_ready_waker(&self, waker: &Waker) {
+        self.poll_waiters.remove_task(waker);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_fs::ctype::pipe::make_pipe;
+    use test_utils::block_on as run;
+
+    fn event(events: EpollEventType, data: u64) -> EpollEvent {
+        EpollEvent { event_type: events, data }
+    }
+
+    /// 取出就绪的事件，返回它们的事件类型与数据
+    fn collect(epoll: &EpollFile) -> Vec<(u32, u64)> {
+        run(epoll.collect(8))
+            .into_iter()
+            .map(|event| ({ event.event_type }.bits(), { event.data }))
+            .collect()
+    }
+
+    const IN: u32 = EpollEventType::EPOLLIN.bits();
+
+    #[test]
+    fn level_triggered_reports_until_drained() {
+        let epoll = EpollFile::new(OpenFlags::empty());
+        let (read_end, write_end) = make_pipe(OpenFlags::NON_BLOCK);
+        run(epoll.epoll_ctl(EpollCtl::ADD, 3, read_end.clone(), event(EpollEventType::EPOLLIN, 7)))
+            .unwrap();
+        assert!(collect(&epoll).is_empty());
+        run(write_end.write(b"ab")).unwrap();
+        assert_eq!(collect(&epoll), [(IN, 7)]);
+        assert_eq!(collect(&epoll), [(IN, 7)]);
+        let mut buf = [0u8; 2];
+        run(read_end.read(&mut buf)).unwrap();
+        assert!(collect(&epoll).is_empty());
+    }
+
+    #[test]
+    fn edge_triggered_reports_each_change_once() {
+        let epoll = EpollFile::new(OpenFlags::empty());
+        let (read_end, write_end) = make_pipe(OpenFlags::NON_BLOCK);
+        let events = EpollEventType::EPOLLIN | EpollEventType::EPOLLET;
+        run(epoll.epoll_ctl(EpollCtl::ADD, 3, read_end.clone(), event(events, 1))).unwrap();
+        run(write_end.write(b"a")).unwrap();
+        assert_eq!(collect(&epoll), [(IN, 1)]);
+        // 数据没有被读走，但状态没有再次变化
+        assert!(collect(&epoll).is_empty());
+        run(write_end.write(b"b")).unwrap();
+        assert_eq!(collect(&epoll), [(IN, 1)]);
+        assert!(collect(&epoll).is_empty());
+    }
+
+    #[test]
+    fn oneshot_is_disabled_until_modified() {
+        let epoll = EpollFile::new(OpenFlags::empty());
+        let (read_end, write_end) = make_pipe(OpenFlags::NON_BLOCK);
+        let events = EpollEventType::EPOLLIN | EpollEventType::EPOLLONESHOT;
+        run(epoll.epoll_ctl(EpollCtl::ADD, 3, read_end.clone(), event(events, 1))).unwrap();
+        run(write_end.write(b"a")).unwrap();
+        assert_eq!(collect(&epoll), [(IN, 1)]);
+        run(write_end.write(b"b")).unwrap();
+        assert!(collect(&epoll).is_empty());
+        // EPOLL_CTL_MOD 重新启用该项
+        run(epoll.epoll_ctl(EpollCtl::MOD, 3, read_end.clone(), event(events, 2))).unwrap();
+        assert_eq!(collect(&epoll), [(IN, 2)]);
+        assert!(collect(&epoll).is_empty());
+    }
+
+    #[test]
+    fn ctl_errors() {
+        let epoll = EpollFile::new(OpenFlags::empty());
+        let (read_end, _write_end) = make_pipe(OpenFlags::NON_BLOCK);
+        let file: Arc<dyn FileIO> = read_end;
+        let ev = event(EpollEventType::EPOLLIN, 0);
+        assert_eq!(run(epoll.epoll_ctl(EpollCtl::MOD, 3, file.clone(), ev)), Err(SyscallError::ENOENT));
+        run(epoll.epoll_ctl(EpollCtl::ADD, 3, file.clone(), ev)).unwrap();
+        assert_eq!(run(epoll.epoll_ctl(EpollCtl::ADD, 3, file.clone(), ev)), Err(SyscallError::EEXIST));
+        // EPOLLEXCLUSIVE 不能与 EPOLLONESHOT 一起使用，也不能通过 MOD 设置
+        let exclusive = EpollEventType::EPOLLIN | EpollEventType::EPOLLEXCLUSIVE;
+        let bad = event(exclusive | EpollEventType::EPOLLONESHOT, 0);
+        assert_eq!(run(epoll.epoll_ctl(EpollCtl::ADD, 4, file.clone(), bad)), Err(SyscallError::EINVAL));
+        let mod_exclusive = event(exclusive, 0);
+        assert_eq!(run(epoll.epoll_ctl(EpollCtl::MOD, 3, file.clone(), mod_exclusive)), Err(SyscallError::EINVAL));
+        run(epoll.epoll_ctl(EpollCtl::DEL, 3, file.clone(), ev)).unwrap();
+        assert_eq!(run(epoll.epoll_ctl(EpollCtl::DEL, 3, file, ev)), Err(SyscallError::ENOENT));
+        // 不能监视自己
+        let this: Arc<dyn FileIO> = epoll.clone();
+        assert_eq!(run(epoll.epoll_ctl(EpollCtl::ADD, 5, this, ev)), Err(SyscallError::EINVAL));
+    }
 }
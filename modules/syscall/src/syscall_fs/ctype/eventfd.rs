@@ -1,9 +1,17 @@
-use alloc::sync::Arc;
+//! eventfd：内核维护的 64 位计数器
+//!
+//! 计数为零时读者在 `read_waiters` 上等待，计数将要溢出时写者在 `write_waiters` 上等待
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use async_fs::api::{async_trait, FileIO, FileIOType, OpenFlags};
 use axerrno::{AxError, AxResult};
-use axfs::api::{FileIO, FileIOType, OpenFlags};
-use axsync::Mutex;
-use axtask::yield_now;
 use bitflags::bitflags;
+use core::future::poll_fn;
+use core::task::{Context, Poll, Waker};
+use spinlock::SpinNoIrq;
+use sync::WaitQueue;
 
 bitflags! {
     // https://sites.uclouvain.be/SystInfo/usr/include/sys/eventfd.h.html
@@ -15,177 +23,222 @@ bitflags! {
     }
 }
 
+/// 计数器能保存的最大值
+const MAX_VALUE: u64 = u64::MAX - 1;
+
 // https://man7.org/linux/man-pages/man2/eventfd2.2.html
 pub struct EventFd {
-    value: Arc<Mutex<u64>>,
-    flags: u32,
+    value: SpinNoIrq<u64>,
+    semaphore: bool,
+    flags: SpinNoIrq<OpenFlags>,
+    /// 等待计数变为非零的读者
+    read_waiters: WaitQueue,
+    /// 等待计数减小的写者
+    write_waiters: WaitQueue,
 }
 
 impl EventFd {
-    pub fn new(initval: u64, flags: u32) -> EventFd {
+    pub fn new(initval: u64, flags: EventFdFlag) -> EventFd {
+        let mut status = OpenFlags::RDWR;
+        if flags.contains(EventFdFlag::EFD_NONBLOCK) {
+            status |= OpenFlags::NON_BLOCK;
+        }
         EventFd {
-            value: Arc::new(Mutex::new(initval)),
-            flags,
+            value: SpinNoIrq::new(initval),
+            semaphore: flags.contains(EventFdFlag::EFD_SEMAPHORE),
+            flags: SpinNoIrq::new(status),
+            read_waiters: WaitQueue::new(),
+            write_waiters: WaitQueue::new(),
         }
     }
 
-    fn should_block(&self) -> bool {
-        self.flags & EventFdFlag::EFD_NONBLOCK.bits() == 0
-    }
-
-    fn has_semaphore_set(&self) -> bool {
-        self.flags & EventFdFlag::EFD_SEMAPHORE.bits() != 0
+    fn is_non_block(&self) -> bool {
+        self.flags.lock().contains(OpenFlags::NON_BLOCK)
     }
 }
 
+#[async_trait]
 impl FileIO for EventFd {
-    fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
+    /// If EFD_SEMAPHORE was not specified, a read returns the counter's value and resets it to zero.
+    /// Otherwise a read returns the value 1 and decrements the counter by 1.
+    /// If the counter is zero, the call either blocks until it becomes nonzero,
+    /// or fails with the error EAGAIN if the file descriptor has been made nonblocking.
+    async fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
         let len: usize = core::mem::size_of::<u64>();
         if buf.len() < len {
             return Err(AxError::InvalidInput);
         }
-
-        loop {
-            let mut value_guard = self.value.lock();
-            // If EFD_SEMAPHORE was not specified and the eventfd counter has a nonzero value, then a read returns 8 bytes containing that value,
-            // and the counter's value is reset to zero.
-            if !self.has_semaphore_set() && *value_guard != 0 {
-                buf[0..len].copy_from_slice(&value_guard.to_ne_bytes());
-                *value_guard = 0;
-                return Ok(len);
-            }
-
-            // If EFD_SEMAPHORE was specified and the eventfd counter has a nonzero value, then a read returns 8 bytes containing the value 1,
-            // and the counter's value is decremented by 1.
-            if self.has_semaphore_set() && *value_guard != 0 {
-                let result: u64 = 1;
-                buf[0..len].copy_from_slice(&result.to_ne_bytes());
-                let _ = value_guard.checked_add_signed(-1);
-                return Ok(len);
-            }
-
-            // If the eventfd counter is zero at the time of the call to read,
-            // then the call either blocks until the counter becomes nonzero (at which time, the read proceeds as described above)
-            // or fails with the error EAGAIN if the file descriptor has been made nonblocking.
-            if *value_guard != 0 {
-                buf[0..len].copy_from_slice(&value_guard.to_ne_bytes());
-                return Ok(len);
-            }
-
-            if self.should_block() {
-                drop(value_guard);
-                yield_now()
-            } else {
-                return Err(AxError::WouldBlock);
+        let non_block = self.is_non_block();
+        let result = poll_fn(|cx| {
+            let mut value = self.value.lock();
+            if *value == 0 {
+                if non_block {
+                    return Poll::Ready(Err(AxError::WouldBlock));
+                }
+                let _ = self.read_waiters.wait_until(cx, || false);
+                return Poll::Pending;
             }
-        }
-    }
-
-    fn write(&self, buf: &[u8]) -> AxResult<usize> {
+            let result = if self.semaphore { 1 } else { *value };
+            *value -= result;
+            drop(value);
+            self.write_waiters.notify_all();
+            Poll::Ready(Ok(result))
+        })
+        .await?;
+        buf[0..len].copy_from_slice(&result.to_ne_bytes());
+        Ok(len)
+    }
+
+    /// A write fails with the error EINVAL if the size of the supplied buffer is less than 8 bytes,
+    /// or if an attempt is made to write the value 0xffffffffffffffff.
+    /// If the addition would overflow the counter, the call blocks until a read is performed,
+    /// or fails with the error EAGAIN if the file descriptor has been made nonblocking.
+    async fn write(&self, buf: &[u8]) -> AxResult<usize> {
         let len: usize = core::mem::size_of::<u64>();
-
-        // A write fails with the error EINVAL if the size of the supplied buffer is less than 8 bytes,
-        // or if an attempt is made to write the value 0xffffffffffffffff.
+        if buf.len() < len {
+            return Err(AxError::InvalidInput);
+        }
         let val = u64::from_ne_bytes(buf[0..len].try_into().unwrap());
-        if buf.len() < 8 || val == u64::MAX {
+        if val == u64::MAX {
             return Err(AxError::InvalidInput);
         }
-
-        loop {
-            let mut value_guard = self.value.lock();
-            // The maximum value that may be stored in the counter is the largest unsigned 64-bit value minus 1 (i.e., 0xfffffffffffffffe).
-            match value_guard.checked_add(val + 1) {
-                // no overflow
-                Some(_) => {
-                    *value_guard += val;
-                    return Ok(len);
-                }
-                // overflow
-                None => {
-                    if self.should_block() {
-                        drop(value_guard);
-                        yield_now()
-                    } else {
-                        return Err(AxError::WouldBlock);
-                    }
+        let non_block = self.is_non_block();
+        poll_fn(|cx| {
+            let mut value = self.value.lock();
+            if MAX_VALUE - *value < val {
+                if non_block {
+                    return Poll::Ready(Err(AxError::WouldBlock));
                 }
+                let _ = self.write_waiters.wait_until(cx, || false);
+                return Poll::Pending;
             }
-        }
+            *value += val;
+            drop(value);
+            self.read_waiters.notify_all();
+            Poll::Ready(Ok(len))
+        })
+        .await
     }
 
-    fn readable(&self) -> bool {
+    async fn readable(&self) -> bool {
         true
     }
 
-    fn writable(&self) -> bool {
+    async fn writable(&self) -> bool {
         true
     }
 
-    fn executable(&self) -> bool {
+    async fn executable(&self) -> bool {
         false
     }
 
-    fn get_type(&self) -> FileIOType {
+    async fn get_type(&self) -> FileIOType {
         FileIOType::Other
     }
 
+    async fn get_path(&self) -> String {
+        String::from("anon_inode:[eventfd]")
+    }
+
     // The file descriptor is readable if the counter has a value greater than 0
-    fn ready_to_read(&self) -> bool {
+    async fn ready_to_read(&self) -> bool {
         *self.value.lock() > 0
     }
 
     // The file descriptor is writable if it is possible to write a value of at least "1" without blocking.
-    fn ready_to_write(&self) -> bool {
-        *self.value.lock() < u64::MAX - 1
+    async fn ready_to_write(&self) -> bool {
+        *self.value.lock() < MAX_VALUE
     }
 
-    fn get_status(&self) -> OpenFlags {
-        let mut status = OpenFlags::RDWR;
-        if self.flags & EventFdFlag::EFD_NONBLOCK.bits() != 0 {
-            status |= OpenFlags::NON_BLOCK;
-        }
-        if self.flags & EventFdFlag::EFD_CLOEXEC.bits() != 0 {
-            status |= OpenFlags::CLOEXEC;
-        }
+    /// 同一个唤醒器可能被多次注册，先移除旧的，避免等待队列中出现重复的唤醒器
+    fn register_ready_waker(&self, cx: &mut Context<'_>) -> bool {
+        self.unregister_ready_waker(cx.waker());
+        let _ = self.read_waiters.wait_until(cx, || false);
+        let _ = self.write_waiters.wait_until(cx, || false);
+        true
+    }
+
+    fn unregister_ready_waker(&self, waker: &Waker) {
+        self.read_waiters.remove_task(waker);
+        self.write_waiters.remove_task(waker);
+    }
 
-        status
+    /// 只有 O_NONBLOCK 可以修改
+    async fn set_status(&self, flags: OpenFlags) -> bool {
+        let mut status = self.flags.lock();
+        *status = (*status - OpenFlags::NON_BLOCK) | (flags & OpenFlags::NON_BLOCK);
+        true
+    }
+
+    async fn get_status(&self) -> OpenFlags {
+        *self.flags.lock()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::EventFd;
+    use super::{EventFd, EventFdFlag};
+    use async_fs::api::FileIO;
     use axerrno::AxError;
-    use axfs::api::FileIO;
+    use core::task::Context;
+    use test_utils::{block_on as run, WakeCounter};
 
     #[test]
     fn test_read() {
-        let event_fd = EventFd::new(42, 0);
-        let event_fd_val = 0u64;
-        let len = event_fd.read(&mut event_fd_val.to_ne_bytes()).unwrap();
+        let event_fd = EventFd::new(42, EventFdFlag::empty());
+        let mut buf = [0u8; 8];
+        let len = run(event_fd.read(&mut buf)).unwrap();
 
-        assert_eq!(42, event_fd_val);
-        assert_eq!(4, len);
+        assert_eq!(42, u64::from_ne_bytes(buf));
+        assert_eq!(8, len);
     }
 
     #[test]
     fn test_read_with_bad_input() {
-        let event_fd = EventFd::new(42, 0);
-        let event_fd_val = 0u32;
-        let result = event_fd.read(&mut event_fd_val.to_ne_bytes());
+        let event_fd = EventFd::new(42, EventFdFlag::empty());
+        let mut buf = [0u8; 4];
+        let result = run(event_fd.read(&mut buf));
         assert_eq!(Err(AxError::InvalidInput), result);
     }
 
     #[test]
     fn test_write() {
-        let event_fd = EventFd::new(42, 0);
+        let event_fd = EventFd::new(42, EventFdFlag::empty());
         let val = 12u64;
-        event_fd
-            .write(&val.to_ne_bytes()[0..core::mem::size_of::<u64>()])
-            .unwrap();
+        run(event_fd.write(&val.to_ne_bytes())).unwrap();
+
+        let mut buf = [0u8; 8];
+        run(event_fd.read(&mut buf)).unwrap();
+        assert_eq!(54, u64::from_ne_bytes(buf));
+    }
 
-        let event_fd_val = 0u64;
-        event_fd.read(&mut event_fd_val.to_ne_bytes()).unwrap();
-        assert_eq!(54, event_fd_val);
+    #[test]
+    fn test_semaphore() {
+        let event_fd = EventFd::new(2, EventFdFlag::EFD_SEMAPHORE | EventFdFlag::EFD_NONBLOCK);
+        let mut buf = [0u8; 8];
+        for _ in 0..2 {
+            run(event_fd.read(&mut buf)).unwrap();
+            assert_eq!(1, u64::from_ne_bytes(buf));
+        }
+        assert_eq!(Err(AxError::WouldBlock), run(event_fd.read(&mut buf)));
+    }
+
+    #[test]
+    fn test_write_overflow() {
+        let event_fd = EventFd::new(super::MAX_VALUE, EventFdFlag::EFD_NONBLOCK);
+        assert_eq!(Err(AxError::WouldBlock), run(event_fd.write(&1u64.to_ne_bytes())));
+        assert_eq!(Err(AxError::InvalidInput), run(event_fd.write(&u64::MAX.to_ne_bytes())));
+    }
+
+    #[test]
+    fn test_register_waker_twice() {
+        let counter = WakeCounter::new();
+        let waker = counter.waker();
+        let event_fd = EventFd::new(0, EventFdFlag::EFD_NONBLOCK);
+        // 重复注册同一个唤醒器，只应被唤醒一次
+        assert!(event_fd.register_ready_waker(&mut Context::from_waker(&waker)));
+        assert!(event_fd.register_ready_waker(&mut Context::from_waker(&waker)));
+        run(event_fd.write(&1u64.to_ne_bytes())).unwrap();
+        assert_eq!(1, counter.count());
     }
 }
@@ -0,0 +1,131 @@
+//! inotify 实例对应的文件
+//!
+//! 读取时以 `struct inotify_event` 的格式返回尽可能多的完整事件
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use async_fs::api::{async_trait, FileIO, FileIOType, Inotify, NotifyEvent, OpenFlags};
+use axerrno::{AxError, AxResult};
+use core::task::{Context, Waker};
+use sync::Mutex;
+
+/// `struct inotify_event` 中文件名之前的部分的大小
+const EVENT_HEADER_SIZE: usize = 16;
+
+/// 事件序列化后的大小，文件名以 '\0' 结尾并补齐到 16 字节
+fn event_size(event: &NotifyEvent) -> usize {
+    EVENT_HEADER_SIZE + name_len(event)
+}
+
+fn name_len(event: &NotifyEvent) -> usize {
+    if event.name.is_empty() {
+        0
+    } else {
+        (event.name.len() + 1).next_multiple_of(EVENT_HEADER_SIZE)
+    }
+}
+
+/// 将事件按 `struct inotify_event` 的格式写入 `buf`
+fn write_event(event: &NotifyEvent, buf: &mut [u8]) {
+    let len = name_len(event);
+    buf[0..4].copy_from_slice(&event.wd.to_ne_bytes());
+    buf[4..8].copy_from_slice(&event.mask.bits().to_ne_bytes());
+    buf[8..12].copy_from_slice(&event.cookie.to_ne_bytes());
+    buf[12..16].copy_from_slice(&(len as u32).to_ne_bytes());
+    let name = &mut buf[EVENT_HEADER_SIZE..EVENT_HEADER_SIZE + len];
+    name.fill(0);
+    name[..event.name.len()].copy_from_slice(event.name.as_bytes());
+}
+
+/// inotify 文件描述符
+pub struct InotifyFile {
+    /// 对应的 inotify 实例
+    pub inotify: Arc<Inotify>,
+    /// 文件的状态标志，只使用其中的 NON_BLOCK
+    flags: Mutex<OpenFlags>,
+}
+
+impl InotifyFile {
+    /// 创建一个新的 inotify 实例
+    pub fn new(flags: OpenFlags) -> Self {
+        Self {
+            inotify: Inotify::new(),
+            flags: Mutex::new(flags),
+        }
+    }
+}
+
+#[async_trait]
+impl FileIO for InotifyFile {
+    async fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
+        loop {
+            let mut written = 0;
+            while let Some(event) = self
+                .inotify
+                .pop_event_if(|event| written + event_size(event) <= buf.len())
+            {
+                write_event(&event, &mut buf[written..]);
+                written += event_size(&event);
+            }
+            if written > 0 {
+                return Ok(written);
+            }
+            if self.inotify.has_events() {
+                // 缓冲区放不下一个完整的事件
+                return Err(AxError::InvalidInput);
+            }
+            if self.flags.lock().await.contains(OpenFlags::NON_BLOCK) {
+                return Err(AxError::WouldBlock);
+            }
+            self.inotify.wait_events().await;
+        }
+    }
+
+    async fn readable(&self) -> bool {
+        true
+    }
+
+    async fn writable(&self) -> bool {
+        false
+    }
+
+    async fn executable(&self) -> bool {
+        false
+    }
+
+    async fn get_type(&self) -> FileIOType {
+        FileIOType::Other
+    }
+
+    async fn get_path(&self) -> String {
+        String::from("anon_inode:inotify")
+    }
+
+    async fn set_status(&self, flags: OpenFlags) -> bool {
+        *self.flags.lock().await = flags;
+        true
+    }
+
+    async fn get_status(&self) -> OpenFlags {
+        *self.flags.lock().await
+    }
+
+    async fn ready_to_read(&self) -> bool {
+        self.inotify.has_events()
+    }
+
+    async fn ready_to_write(&self) -> bool {
+        false
+    }
+
+    fn register_ready_waker(&self, cx: &mut Context<'_>) -> bool {
+        let _ = self.inotify.poll_ready(cx);
+        true
+    }
+
+    fn unregister_ready_waker(&self, waker: &Waker) {
+        self.inotify.cancel_ready(waker);
+    }
+}
@@ -2,14 +2,20 @@ pub mod dir;
 
 pub mod file;
 
+pub mod inotify;
+
 // pub mod mount;
 
-// pub mod pipe;
+pub mod pipe;
 
 pub use file::FileDesc;
 
-// pub mod epoll;
+pub mod epoll;
+
+pub mod eventfd;
+
+pub mod timerfd;
 
-// pub mod eventfd;
+pub mod mqueue;
 
-// pub mod pidfd;
+pub mod pidfd;
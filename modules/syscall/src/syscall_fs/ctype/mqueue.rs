@@ -0,0 +1,154 @@
+//! POSIX 消息队列的文件描述符
+//!
+//! 队列本身保存在 mqueue 文件系统中，描述符只记录打开方式。
+//! 与 Linux 相同，读取描述符得到队列的状态，poll 在队列非空时可读、未满时可写
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use async_fs::api::{async_trait, FileIO, FileIOType, MessageQueue, OpenFlags};
+use axerrno::{AxError, AxResult};
+use core::task::{Context, Waker};
+use spinlock::SpinNoIrq;
+
+/// mq_open 与 mq_getsetattr 使用的 `struct mq_attr`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MqAttr {
+    /// 只有 O_NONBLOCK 有意义
+    pub mq_flags: i64,
+    pub mq_maxmsg: i64,
+    pub mq_msgsize: i64,
+    /// 队列中的消息数，只读
+    pub mq_curmsgs: i64,
+    __reserved: [i64; 4],
+}
+
+/// 通知方式：发送信号
+pub const SIGEV_SIGNAL: i32 = 0;
+/// 通知方式：不通知
+pub const SIGEV_NONE: i32 = 1;
+/// 通知方式：创建线程，由 C 库借助 netlink 套接字实现
+pub const SIGEV_THREAD: i32 = 2;
+
+/// mq_notify 使用的 `struct sigevent`
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct SigEvent {
+    pub sigev_value: usize,
+    pub sigev_signo: i32,
+    pub sigev_notify: i32,
+    __pad: [u8; 48],
+}
+
+const _: () = assert!(core::mem::size_of::<SigEvent>() == 64);
+
+// https://man7.org/linux/man-pages/man7/mq_overview.7.html
+pub struct MqueueFile {
+    queue: Arc<MessageQueue>,
+    flags: SpinNoIrq<OpenFlags>,
+    /// 读取状态时的偏移
+    offset: SpinNoIrq<usize>,
+}
+
+impl MqueueFile {
+    pub fn new(queue: Arc<MessageQueue>, flags: OpenFlags) -> MqueueFile {
+        MqueueFile {
+            queue,
+            flags: SpinNoIrq::new(flags),
+            offset: SpinNoIrq::new(0),
+        }
+    }
+
+    /// 描述符对应的队列
+    pub fn queue(&self) -> &Arc<MessageQueue> {
+        &self.queue
+    }
+
+    pub fn is_non_block(&self) -> bool {
+        self.flags.lock().contains(OpenFlags::NON_BLOCK)
+    }
+
+    /// 队列当前的属性
+    pub fn attr(&self) -> MqAttr {
+        MqAttr {
+            mq_flags: if self.is_non_block() {
+                OpenFlags::NON_BLOCK.bits() as i64
+            } else {
+                0
+            },
+            mq_maxmsg: self.queue.maxmsg() as i64,
+            mq_msgsize: self.queue.msgsize() as i64,
+            mq_curmsgs: self.queue.len() as i64,
+            ..Default::default()
+        }
+    }
+}
+
+#[async_trait]
+impl FileIO for MqueueFile {
+    /// 读取队列的状态
+    async fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
+        let status = self.queue.status();
+        let mut offset = self.offset.lock();
+        let start = (*offset).min(status.len());
+        let len = buf.len().min(status.len() - start);
+        buf[..len].copy_from_slice(&status.as_bytes()[start..start + len]);
+        *offset += len;
+        Ok(len)
+    }
+
+    /// 消息只能通过 mq_timedsend 发送
+    async fn write(&self, _buf: &[u8]) -> AxResult<usize> {
+        Err(AxError::InvalidInput)
+    }
+
+    async fn readable(&self) -> bool {
+        self.flags.lock().readable()
+    }
+
+    async fn writable(&self) -> bool {
+        self.flags.lock().writable()
+    }
+
+    async fn executable(&self) -> bool {
+        false
+    }
+
+    async fn get_type(&self) -> FileIOType {
+        FileIOType::Other
+    }
+
+    async fn get_path(&self) -> String {
+        String::from("mqueue")
+    }
+
+    async fn ready_to_read(&self) -> bool {
+        !self.queue.is_empty()
+    }
+
+    async fn ready_to_write(&self) -> bool {
+        !self.queue.is_full()
+    }
+
+    fn register_ready_waker(&self, cx: &mut Context<'_>) -> bool {
+        self.queue.register_waker(cx);
+        true
+    }
+
+    fn unregister_ready_waker(&self, waker: &Waker) {
+        self.queue.unregister_waker(waker);
+    }
+
+    /// 只有 O_NONBLOCK 可以修改
+    async fn set_status(&self, flags: OpenFlags) -> bool {
+        let mut status = self.flags.lock();
+        *status = (*status - OpenFlags::NON_BLOCK) | (flags & OpenFlags::NON_BLOCK);
+        true
+    }
+
+    async fn get_status(&self) -> OpenFlags {
+        *self.flags.lock()
+    }
+}
@@ -1,92 +1,114 @@
+//! pidfd：指向一个进程的文件描述符
+//!
+//! 它持有目标进程的 [`Executor`]，因此进程退出后仍然可以通过它查询进程的状态。
+//! 进程退出时 pidfd 变为可读，可以被 poll 与 epoll 等待
 extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::String;
 use alloc::sync::Arc;
-use axfs::api::{FileIO, OpenFlags};
-use axprocess::{current_process, Process, PID2PC};
-use axsync::Mutex;
+use async_fs::api::{async_trait, FileIO, FileIOType, OpenFlags};
+use axerrno::{AxError, AxResult};
+use core::task::{Context, Waker};
+use executor::{current_executor, Executor, FdFlags};
+use spinlock::SpinNoIrq;
 
-use crate::{SyscallError, SyscallResult};
+use crate::syscall_fs::imp::fd_err;
+use crate::SyscallResult;
 
 pub struct PidFd {
-    flags: Mutex<OpenFlags>,
-    process: Arc<Process>,
+    flags: SpinNoIrq<OpenFlags>,
+    executor: Arc<Executor>,
 }
 
 impl PidFd {
-    /// Create a new PidFd
-    pub fn new(process: Arc<Process>, flags: OpenFlags) -> Self {
+    /// 创建一个指向 `executor` 的 pidfd，`flags` 中只有 O_NONBLOCK 有效
+    pub fn new(executor: Arc<Executor>, flags: OpenFlags) -> Self {
         Self {
-            flags: Mutex::new(flags),
-            process,
+            flags: SpinNoIrq::new(OpenFlags::RDWR | (flags & OpenFlags::NON_BLOCK)),
+            executor,
         }
     }
 
+    /// 目标进程
+    pub fn executor(&self) -> &Arc<Executor> {
+        &self.executor
+    }
+
     pub fn pid(&self) -> u64 {
-        self.process.pid()
+        self.executor.pid().as_u64()
     }
 }
 
+#[async_trait]
 impl FileIO for PidFd {
-    fn read(&self, _buf: &mut [u8]) -> axerrno::AxResult<usize> {
-        Err(axerrno::AxError::Unsupported)
+    /// 与 Linux 相同，pidfd 不能被读写
+    async fn read(&self, _buf: &mut [u8]) -> AxResult<usize> {
+        Err(AxError::InvalidInput)
     }
-    fn write(&self, _buf: &[u8]) -> axerrno::AxResult<usize> {
-        Err(axerrno::AxError::Unsupported)
-    }
-    fn seek(&self, _pos: axfs::api::SeekFrom) -> axerrno::AxResult<u64> {
-        Err(axerrno::AxError::Unsupported)
+
+    async fn write(&self, _buf: &[u8]) -> AxResult<usize> {
+        Err(AxError::InvalidInput)
     }
-    /// To check whether the target process is still alive
-    fn readable(&self) -> bool {
-        self.process.get_zombie()
+
+    async fn readable(&self) -> bool {
+        true
     }
 
-    fn writable(&self) -> bool {
+    async fn writable(&self) -> bool {
         false
     }
 
-    fn executable(&self) -> bool {
+    async fn executable(&self) -> bool {
         false
     }
 
-    fn get_type(&self) -> axfs::api::FileIOType {
-        axfs::api::FileIOType::Other
+    async fn get_type(&self) -> FileIOType {
+        FileIOType::Other
+    }
+
+    async fn get_path(&self) -> String {
+        String::from("anon_inode:[pidfd]")
     }
 
-    fn get_status(&self) -> OpenFlags {
-        self.flags.lock().clone()
+    /// 目标进程退出后可读
+    async fn ready_to_read(&self) -> bool {
+        self.executor.get_zombie()
     }
 
-    fn set_status(&self, flags: OpenFlags) -> bool {
-        *self.flags.lock() = flags;
+    async fn ready_to_write(&self) -> bool {
+        false
+    }
+
+    fn register_ready_waker(&self, cx: &mut Context<'_>) -> bool {
+        let _ = self.executor.exit_waiters.wait_until(cx, || false);
         true
     }
 
-    fn set_close_on_exec(&self, is_set: bool) -> bool {
-        if is_set {
-            // 设置close_on_exec位置
-            *self.flags.lock() |= OpenFlags::CLOEXEC;
-        } else {
-            *self.flags.lock() &= !OpenFlags::CLOEXEC;
-        }
+    fn unregister_ready_waker(&self, waker: &Waker) {
+        self.executor.exit_waiters.remove_task(waker);
+    }
+
+    /// 只有 O_NONBLOCK 可以修改
+    async fn set_status(&self, flags: OpenFlags) -> bool {
+        let mut status = self.flags.lock();
+        *status = (*status - OpenFlags::NON_BLOCK) | (flags & OpenFlags::NON_BLOCK);
         true
     }
+
+    async fn get_status(&self) -> OpenFlags {
+        *self.flags.lock()
+    }
 }
 
-pub fn new_pidfd(pid: u64, mut flags: OpenFlags) -> SyscallResult {
-    // It is set to close the file descriptor on exec
-    flags |= OpenFlags::CLOEXEC;
-    let pid2fd = PID2PC.lock();
-
-    let pidfd = pid2fd
-        .get(&pid)
-        .map(|target_process| PidFd::new(Arc::clone(target_process), flags))
-        .ok_or(SyscallError::EINVAL)?;
-    drop(pid2fd);
-    let process = current_process();
-    let mut fd_table = process.fd_manager.fd_table.lock();
-    let fd = process
-        .alloc_fd(&mut fd_table)
-        .map_err(|_| SyscallError::EMFILE)?;
-    fd_table[fd] = Some(Arc::new(pidfd));
+/// 在当前进程中分配一个指向 `executor` 的 pidfd，它总是带有 FD_CLOEXEC
+///
+/// 供 pidfd_open 使用
+pub async fn new_pidfd(executor: Arc<Executor>, flags: OpenFlags) -> SyscallResult {
+    let fd = current_executor()
+        .fd_manager
+        .alloc(Arc::new(PidFd::new(executor, flags)), FdFlags::CLOEXEC)
+        .await
+        .map_err(fd_err)?;
     Ok(fd as isize)
 }
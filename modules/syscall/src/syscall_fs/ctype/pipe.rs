@@ -1,269 +1,559 @@
-use axfs::api::{FileIO, FileIOType, OpenFlags};
-extern crate alloc;
-use alloc::sync::{Arc, Weak};
-use axerrno::AxResult;
-use axlog::{info, trace};
-
-use axsync::Mutex;
-use axtask::yield_now;
-
-/// IPC pipe
-pub struct Pipe {
-    #[allow(unused)]
-    readable: bool,
-    #[allow(unused)]
-    writable: bool,
-    buffer: Arc<Mutex<PipeRingBuffer>>,
-    #[allow(unused)]
-    flags: Mutex<OpenFlags>,
-}
-
-impl Pipe {
-    /// create readable pipe
-    pub fn read_end_with_buffer(buffer: Arc<Mutex<PipeRingBuffer>>, flags: OpenFlags) -> Self {
-        Self {
-            readable: true,
-            writable: false,
-            buffer,
-            flags: Mutex::new(flags | OpenFlags::RDONLY),
-        }
-    }
-    /// create writable pipe
-    pub fn write_end_with_buffer(buffer: Arc<Mutex<PipeRingBuffer>>, flags: OpenFlags) -> Self {
-        Self {
-            readable: false,
-            writable: true,
-            buffer,
-            flags: Mutex::new(flags | OpenFlags::WRONLY),
-        }
-    }
-    /// is it set non block?
-    pub fn is_non_block(&self) -> bool {
-        self.flags.lock().contains(OpenFlags::NON_BLOCK)
-    }
-}
-
-const RING_BUFFER_SIZE: usize = 0x4000;
-
-#[derive(Copy, Clone, PartialEq)]
-enum RingBufferStatus {
-    Full,
-    Empty,
-    Normal,
-}
-
-pub struct PipeRingBuffer {
-    arr: [u8; RING_BUFFER_SIZE],
-    head: usize,
-    tail: usize,
-    status: RingBufferStatus,
-    write_end: Option<Weak<Pipe>>,
-}
-
-impl PipeRingBuffer {
-    pub fn new() -> Self {
-        Self {
-            arr: [0; RING_BUFFER_SIZE],
-            head: 0,
-            tail: 0,
-            status: RingBufferStatus::Empty,
-            write_end: None,
-        }
-    }
-
-    pub fn set_write_end(&mut self, write_end: &Arc<Pipe>) {
-        self.write_end = Some(Arc::downgrade(write_end));
-    }
-
-    pub fn write_byte(&mut self, byte: u8) {
-        self.status = RingBufferStatus::Normal;
-        self.arr[self.tail] = byte;
-        self.tail = (self.tail + 1) % RING_BUFFER_SIZE;
-        if self.tail == self.head {
-            self.status = RingBufferStatus::Full;
-        }
-    }
-    pub fn read_byte(&mut self) -> u8 {
-        self.status = RingBufferStatus::Normal;
-        let c = self.arr[self.head];
-        self.head = (self.head + 1) % RING_BUFFER_SIZE;
-        if self.head == self.tail {
-            self.status = RingBufferStatus::Empty;
-        }
-        c
-    }
-    pub fn available_read(&self) -> usize {
-        if self.status == RingBufferStatus::Empty {
-            0
-        } else if self.tail > self.head {
-            self.tail - self.head
-        } else {
-            self.tail + RING_BUFFER_SIZE - self.head
-        }
-    }
-    pub fn available_write(&self) -> usize {
-        if self.status == RingBufferStatus::Full {
-            0
-        } else {
-            RING_BUFFER_SIZE - self.available_read()
-        }
-    }
-    pub fn all_write_ends_closed(&self) -> bool {
-        self.write_end.as_ref().unwrap().upgrade().is_none()
-    }
-}
-
-/// Return (read_end, write_end)
-pub fn make_pipe(flags: OpenFlags) -> (Arc<Pipe>, Arc<Pipe>) {
-    trace!("kernel: make_pipe");
-    let buffer = Arc::new(Mutex::new(PipeRingBuffer::new()));
-    let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone(), flags));
-    let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone(), flags));
-    buffer.lock().set_write_end(&write_end);
-    (read_end, write_end)
-}
-
-impl FileIO for Pipe {
-    fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
-        assert!(self.readable());
-        let want_to_read = buf.len();
-        let mut buf_iter = buf.iter_mut();
-        let mut already_read = 0usize;
-        loop {
-            let mut ring_buffer = self.buffer.lock();
-            let loop_read = ring_buffer.available_read();
-            info!("kernel: Pipe::read: loop_read = {}", loop_read);
-            if loop_read == 0 {
-                if axprocess::current_process().have_signals().is_some() {
-                    return Err(axerrno::AxError::Interrupted);
-                }
-                info!(
-                    "kernel: Pipe::read: all_write_ends_closed = {}",
-                    ring_buffer.all_write_ends_closed()
-                );
-                if Arc::strong_count(&self.buffer) < 2 || ring_buffer.all_write_ends_closed() {
-                    return Ok(already_read);
-                }
-
-                if self.is_non_block() {
-                    yield_now();
-                    return Err(axerrno::AxError::WouldBlock);
-                }
-                drop(ring_buffer);
-                yield_now();
-                continue;
-            }
-            for _ in 0..loop_read {
-                if let Some(byte_ref) = buf_iter.next() {
-                    *byte_ref = ring_buffer.read_byte();
-                    already_read += 1;
-                    if already_read == want_to_read {
-                        return Ok(want_to_read);
-                    }
-                } else {
-                    break;
-                }
-            }
-
-            return Ok(already_read);
-        }
-    }
-
-    fn write(&self, buf: &[u8]) -> AxResult<usize> {
-        info!("kernel: Pipe::write");
-        assert!(self.writable());
-        let want_to_write = buf.len();
-        let mut buf_iter = buf.iter();
-        let mut already_write = 0usize;
-        loop {
-            let mut ring_buffer = self.buffer.lock();
-            let loop_write = ring_buffer.available_write();
-            if loop_write == 0 {
-                drop(ring_buffer);
-
-                if Arc::strong_count(&self.buffer) < 2 || self.is_non_block() {
-                    // 读入端关闭
-                    return Ok(already_write);
-                }
-                yield_now();
-                continue;
-            }
-
-            // write at most loop_write bytes
-            for _ in 0..loop_write {
-                if let Some(byte_ref) = buf_iter.next() {
-                    ring_buffer.write_byte(*byte_ref);
-                    already_write += 1;
-                    if already_write == want_to_write {
-                        drop(ring_buffer);
-                        return Ok(want_to_write);
-                    }
-                } else {
-                    break;
-                }
-            }
-            return Ok(already_write);
-        }
-    }
-
-    fn executable(&self) -> bool {
-        false
-    }
-    fn readable(&self) -> bool {
-        self.readable
-    }
-    fn writable(&self) -> bool {
-        self.writable
-    }
-
-    fn get_type(&self) -> FileIOType {
-        FileIOType::Pipe
-    }
-
-    fn is_hang_up(&self) -> bool {
-        if self.readable {
-            if self.buffer.lock().available_read() == 0
-                && self.buffer.lock().all_write_ends_closed()
-            {
-                // 写入端关闭且缓冲区读完了
-                true
-            } else {
-                false
-            }
-        } else {
-            // 否则在写入端，只关心读入端是否被关闭
-            Arc::strong_count(&self.buffer) < 2
-        }
-    }
-
-    fn ready_to_read(&self) -> bool {
-        self.readable && self.buffer.lock().available_read() != 0
-    }
-
-    fn ready_to_write(&self) -> bool {
-        self.writable && self.buffer.lock().available_write() != 0
-    }
-
-    /// 设置文件状态
-    fn set_status(&self, flags: OpenFlags) -> bool {
-        *self.flags.lock() = flags;
-        true
-    }
-
-    /// 获取文件状态
-    fn get_status(&self) -> OpenFlags {
-        *self.flags.lock()
-    }
-
-    /// 设置 close_on_exec 位
-    /// 设置成功返回false
-    fn set_close_on_exec(&self, is_set: bool) -> bool {
-        if is_set {
-            // 设置close_on_exec位置
-            *self.flags.lock() |= OpenFlags::CLOEXEC;
-        } else {
-            *self.flags.lock() &= !OpenFlags::CLOEXEC;
-        }
-        true
-    }
-}
+//! 管道
+//!
+//! 读写两端共享一个环形缓冲区。缓冲区为空时读者在 `read_waiters` 上等待数据或所有写端关闭，
+//! 空间不足时写者在 `write_waiters` 上等待读者取走数据或所有读端关闭。
+//! 检查条件与注册等待都在缓冲区的锁内进行，状态改变后再唤醒，因此不会丢失唤醒。
+//!
+//! 命名管道（FIFO）在文件系统中只有一个节点，打开它的各个文件共享同一个缓冲区，见 [`open_fifo`]。
+//! 所有读端与写端都关闭后缓冲区被释放，其中的数据被丢弃
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use async_fs::api::{async_trait, FileIO, FileIOType, Kstat, LookupFlags, OpenFlags, VfsNodeRef};
+use axerrno::{ax_err, AxError, AxResult};
+use axhal::mem::PAGE_SIZE_4K;
+use core::future::poll_fn;
+use core::task::{Context, Poll, Waker};
+use spinlock::SpinNoIrq;
+use sync::WaitQueue;
+
+use super::file::{inode_of, kstat_from_attr};
+use crate::SyscallError;
+
+/// 不超过该长度的写入是原子的，不会与其他写者的数据交错
+pub const PIPE_BUF: usize = 4096;
+/// 管道的默认容量
+const DEFAULT_CAPACITY: usize = 16 * PAGE_SIZE_4K;
+/// F_SETPIPE_SZ 能设置的最大容量，即 `/proc/sys/fs/pipe-max-size` 的默认值
+pub const PIPE_MAX_SIZE: usize = 0x10_0000;
+
+/// 正在被打开的命名管道，以文件系统节点的地址为键
+///
+/// 缓冲区持有节点的引用，因此表项存在期间节点的地址不会被其他节点重新使用
+static FIFOS: SpinNoIrq<BTreeMap<usize, Weak<PipeShared>>> = SpinNoIrq::new(BTreeMap::new());
+
+struct PipeBuffer {
+    data: VecDeque<u8>,
+    capacity: usize,
+    /// 仍然打开的读端与写端的数目
+    readers: usize,
+    writers: usize,
+    /// 曾经打开过的读端与写端的数目，等待另一端打开的命名管道用它发现短暂打开后又关闭的对端
+    reader_opens: usize,
+    writer_opens: usize,
+}
+
+impl PipeBuffer {
+    fn free(&self) -> usize {
+        self.capacity.saturating_sub(self.data.len())
+    }
+
+    /// 将缓冲区开头的数据复制到 `buf` 中，返回复制的字节数
+    fn copy_to(&self, buf: &mut [u8]) -> usize {
+        let (front, back) = self.data.as_slices();
+        let n = front.len().min(buf.len());
+        buf[..n].copy_from_slice(&front[..n]);
+        let m = back.len().min(buf.len() - n);
+        buf[n..n + m].copy_from_slice(&back[..m]);
+        n + m
+    }
+}
+
+struct PipeShared {
+    buffer: SpinNoIrq<PipeBuffer>,
+    /// 等待数据或写端打开的读者
+    read_waiters: WaitQueue,
+    /// 等待空间或读端打开的写者
+    write_waiters: WaitQueue,
+    /// 命名管道在文件系统中的节点
+    node: Option<VfsNodeRef>,
+}
+
+impl PipeShared {
+    fn new(readers: usize, writers: usize, node: Option<VfsNodeRef>) -> Self {
+        Self {
+            buffer: SpinNoIrq::new(PipeBuffer {
+                data: VecDeque::new(),
+                capacity: DEFAULT_CAPACITY,
+                readers,
+                writers,
+                reader_opens: readers,
+                writer_opens: writers,
+            }),
+            read_waiters: WaitQueue::new(),
+            write_waiters: WaitQueue::new(),
+            node,
+        }
+    }
+}
+
+impl Drop for PipeShared {
+    fn drop(&mut self) {
+        if let Some(node) = &self.node {
+            let mut fifos = FIFOS.lock();
+            let key = node_key(node);
+            // 表项可能已经属于之后打开的缓冲区
+            if fifos.get(&key).is_some_and(|shared| shared.strong_count() == 0) {
+                fifos.remove(&key);
+            }
+        }
+    }
+}
+
+fn node_key(node: &VfsNodeRef) -> usize {
+    Arc::as_ptr(node) as *const () as usize
+}
+
+/// IPC pipe
+pub struct Pipe {
+    shared: Arc<PipeShared>,
+    readable: bool,
+    /// 只有以 O_RDWR 打开的命名管道同时可读写
+    writable: bool,
+    flags: SpinNoIrq<OpenFlags>,
+    /// 命名管道打开时使用的路径
+    path: Option<String>,
+}
+
+/// Return (read_end, write_end)
+pub fn make_pipe(flags: OpenFlags) -> (Arc<Pipe>, Arc<Pipe>) {
+    let shared = Arc::new(PipeShared::new(1, 1, None));
+    let flags = flags & OpenFlags::NON_BLOCK;
+    let read_end = Arc::new(Pipe {
+        shared: shared.clone(),
+        readable: true,
+        writable: false,
+        flags: SpinNoIrq::new(flags | OpenFlags::RDONLY),
+        path: None,
+    });
+    let write_end = Arc::new(Pipe {
+        shared,
+        readable: false,
+        writable: true,
+        flags: SpinNoIrq::new(flags | OpenFlags::WRONLY),
+        path: None,
+    });
+    (read_end, write_end)
+}
+
+/// 打开文件系统中 `path` 处的命名管道 `node`，读写方向由 `flags` 的访问模式决定
+///
+/// 与 Linux 相同：只读打开在没有写端时等待写端打开，O_NONBLOCK 时立即返回；
+/// 只写打开在没有读端时等待读端打开，O_NONBLOCK 时返回 ENXIO；O_RDWR 打开总是立即返回
+pub async fn open_fifo(
+    node: VfsNodeRef,
+    path: &str,
+    flags: OpenFlags,
+) -> Result<Arc<Pipe>, SyscallError> {
+    let shared = {
+        let mut fifos = FIFOS.lock();
+        let key = node_key(&node);
+        match fifos.get(&key).and_then(Weak::upgrade) {
+            Some(shared) => shared,
+            None => {
+                let shared = Arc::new(PipeShared::new(0, 0, Some(node)));
+                fifos.insert(key, Arc::downgrade(&shared));
+                shared
+            }
+        }
+    };
+    open_end(shared, path, flags).await
+}
+
+/// 在命名管道共享的缓冲区上打开一端，需要时等待对端打开
+async fn open_end(
+    shared: Arc<PipeShared>,
+    path: &str,
+    flags: OpenFlags,
+) -> Result<Arc<Pipe>, SyscallError> {
+    let readable = flags.readable();
+    let writable = flags.writable();
+    let non_block = flags.contains(OpenFlags::NON_BLOCK);
+    let mut buffer = shared.buffer.lock();
+    if writable && !readable && non_block && buffer.readers == 0 {
+        return Err(SyscallError::ENXIO);
+    }
+    if readable {
+        buffer.readers += 1;
+        buffer.reader_opens += 1;
+    }
+    if writable {
+        buffer.writers += 1;
+        buffer.writer_opens += 1;
+    }
+    let (reader_opens, writer_opens) = (buffer.reader_opens, buffer.writer_opens);
+    drop(buffer);
+    let mode = if readable && writable {
+        OpenFlags::RDWR
+    } else if writable {
+        OpenFlags::WRONLY
+    } else {
+        OpenFlags::RDONLY
+    };
+    let pipe = Arc::new(Pipe {
+        shared: shared.clone(),
+        readable,
+        writable,
+        flags: SpinNoIrq::new(mode | (flags & OpenFlags::NON_BLOCK)),
+        path: Some(String::from(path)),
+    });
+    // 正在等待这一端打开的对端
+    shared.read_waiters.notify_all();
+    shared.write_waiters.notify_all();
+    if non_block || (readable && writable) {
+        return Ok(pipe);
+    }
+    poll_fn(|cx| {
+        let buffer = shared.buffer.lock();
+        let (connected, waiters) = if readable {
+            (buffer.writers > 0 || buffer.writer_opens != writer_opens, &shared.read_waiters)
+        } else {
+            (buffer.readers > 0 || buffer.reader_opens != reader_opens, &shared.write_waiters)
+        };
+        if connected {
+            return Poll::Ready(());
+        }
+        let _ = waiters.wait_until(cx, || false);
+        Poll::Pending
+    })
+    .await;
+    Ok(pipe)
+}
+
+impl Pipe {
+    /// is it set non block?
+    pub fn is_non_block(&self) -> bool {
+        self.flags.lock().contains(OpenFlags::NON_BLOCK)
+    }
+
+    /// 管道的容量，即 F_GETPIPE_SZ
+    pub fn capacity(&self) -> usize {
+        self.shared.buffer.lock().capacity
+    }
+
+    /// 修改管道的容量，即 F_SETPIPE_SZ，返回实际设置的容量
+    ///
+    /// 容量向上取整为 2 的幂个页，不能小于缓冲区中已有的数据
+    pub fn set_capacity(&self, size: usize) -> AxResult<usize> {
+        if size > PIPE_MAX_SIZE {
+            return ax_err!(PermissionDenied);
+        }
+        let capacity = size.div_ceil(PAGE_SIZE_4K).max(1).next_power_of_two() * PAGE_SIZE_4K;
+        let mut buffer = self.shared.buffer.lock();
+        if capacity < buffer.data.len() {
+            return ax_err!(ResourceBusy);
+        }
+        buffer.capacity = capacity;
+        drop(buffer);
+        self.shared.write_waiters.notify_all();
+        Ok(capacity)
+    }
+
+    /// 读取或预览管道中的数据，没有数据时等待，直到有数据或所有写端关闭
+    async fn read_with(&self, buf: &mut [u8], consume: bool) -> AxResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let non_block = self.is_non_block();
+        poll_fn(|cx| {
+            let mut buffer = self.shared.buffer.lock();
+            if !buffer.data.is_empty() {
+                let n = buffer.copy_to(buf);
+                if !consume {
+                    return Poll::Ready(Ok(n));
+                }
+                buffer.data.drain(..n);
+                drop(buffer);
+                self.shared.write_waiters.notify_all();
+                return Poll::Ready(Ok(n));
+            }
+            if buffer.writers == 0 {
+                return Poll::Ready(Ok(0));
+            }
+            if non_block {
+                return Poll::Ready(Err(AxError::WouldBlock));
+            }
+            let _ = self.shared.read_waiters.wait_until(cx, || false);
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut buffer = self.shared.buffer.lock();
+        if self.readable {
+            buffer.readers -= 1;
+        }
+        if self.writable {
+            buffer.writers -= 1;
+        }
+        drop(buffer);
+        // 另一端的等待者需要知道这一端已经关闭
+        self.shared.read_waiters.notify_all();
+        self.shared.write_waiters.notify_all();
+    }
+}
+
+#[async_trait]
+impl FileIO for Pipe {
+    async fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
+        self.read_with(buf, true).await
+    }
+
+    async fn peek(&self, buf: &mut [u8]) -> AxResult<usize> {
+        self.read_with(buf, false).await
+    }
+
+    /// 不超过 PIPE_BUF 的写入要么全部写入，要么等待；更长的写入在阻塞模式下写完全部数据才返回。
+    /// 所有读端都已关闭时返回 ConnectionReset，由系统调用转换为 EPIPE
+    async fn write(&self, buf: &[u8]) -> AxResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let non_block = self.is_non_block();
+        let atomic = buf.len() <= PIPE_BUF;
+        let mut written = 0;
+        poll_fn(|cx| loop {
+            let mut buffer = self.shared.buffer.lock();
+            if buffer.readers == 0 {
+                // TODO: 内核支持信号后，还需要向当前进程发送 SIGPIPE
+                return Poll::Ready(if written > 0 { Ok(written) } else { Err(AxError::ConnectionReset) });
+            }
+            let rest = buf.len() - written;
+            let free = buffer.free();
+            if free == 0 || (atomic && free < rest) {
+                if non_block {
+                    return Poll::Ready(if written > 0 { Ok(written) } else { Err(AxError::WouldBlock) });
+                }
+                let _ = self.shared.write_waiters.wait_until(cx, || false);
+                return Poll::Pending;
+            }
+            let n = free.min(rest);
+            buffer.data.extend(&buf[written..written + n]);
+            written += n;
+            drop(buffer);
+            self.shared.read_waiters.notify_all();
+            if written == buf.len() || non_block {
+                return Poll::Ready(Ok(written));
+            }
+        })
+        .await
+    }
+
+    async fn readable(&self) -> bool {
+        self.readable
+    }
+
+    async fn writable(&self) -> bool {
+        self.writable
+    }
+
+    async fn executable(&self) -> bool {
+        false
+    }
+
+    async fn get_type(&self) -> FileIOType {
+        FileIOType::Pipe
+    }
+
+    async fn get_path(&self) -> String {
+        self.path.clone().unwrap_or_else(|| String::from("pipe:"))
+    }
+
+    /// 命名管道的属性来自文件系统中的节点
+    async fn get_stat(&self) -> AxResult<Kstat> {
+        let Some(path) = &self.path else {
+            return Err(AxError::Unsupported);
+        };
+        let attr = async_fs::api::get_attr(path, LookupFlags::empty()).await?;
+        Ok(kstat_from_attr(&attr, inode_of(path).await))
+    }
+
+    /// 读端：所有写端都已关闭；写端：所有读端都已关闭
+    async fn is_hang_up(&self) -> bool {
+        let buffer = self.shared.buffer.lock();
+        (self.readable && buffer.writers == 0) || (self.writable && buffer.readers == 0)
+    }
+
+    async fn ready_to_read(&self) -> bool {
+        self.readable && !self.shared.buffer.lock().data.is_empty()
+    }
+
+    /// 至少能原子地写入 PIPE_BUF 字节时才认为可写
+    async fn ready_to_write(&self) -> bool {
+        self.writable && self.shared.buffer.lock().free() >= PIPE_BUF
+    }
+
+    /// 先移除同一个唤醒器之前的注册，避免等待队列中出现重复的唤醒器
+    fn register_ready_waker(&self, cx: &mut Context<'_>) -> bool {
+        self.unregister_ready_waker(cx.waker());
+        if self.readable {
+            let _ = self.shared.read_waiters.wait_until(cx, || false);
+        }
+        if self.writable {
+            let _ = self.shared.write_waiters.wait_until(cx, || false);
+        }
+        true
+    }
+
+    fn unregister_ready_waker(&self, waker: &Waker) {
+        self.shared.read_waiters.remove_task(waker);
+        self.shared.write_waiters.remove_task(waker);
+    }
+
+    /// 只有 O_NONBLOCK 可以修改，读写方向保持不变
+    async fn set_status(&self, flags: OpenFlags) -> bool {
+        let mut status = self.flags.lock();
+        *status = (*status - OpenFlags::NON_BLOCK) | (flags & OpenFlags::NON_BLOCK);
+        true
+    }
+
+    async fn get_status(&self) -> OpenFlags {
+        *self.flags.lock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_utils::{block_on as run, poll_once, WakeCounter};
+
+    fn read_all(pipe: &Pipe) -> usize {
+        let mut buf = [0u8; 1024];
+        let mut total = 0;
+        while let Ok(n) = run(pipe.read(&mut buf)) {
+            if n == 0 {
+                break;
+            }
+            total += n;
+        }
+        total
+    }
+
+    #[test]
+    fn small_writes_are_atomic() {
+        let (read_end, write_end) = make_pipe(OpenFlags::NON_BLOCK);
+        assert_eq!(write_end.set_capacity(1), Ok(PAGE_SIZE_4K));
+        assert_eq!(run(write_end.write(&[1; 4000])), Ok(4000));
+        // 剩余的空间放不下全部数据时，不超过 PIPE_BUF 的写入一个字节也不写
+        assert_eq!(run(write_end.write(&[2; 200])), Err(AxError::WouldBlock));
+        assert!(!run(write_end.ready_to_write()));
+        // 更长的写入可以只写入一部分
+        assert_eq!(run(write_end.write(&[3; PIPE_BUF + 1])), Ok(PAGE_SIZE_4K - 4000));
+        assert_eq!(read_all(&read_end), PAGE_SIZE_4K);
+        assert!(run(write_end.ready_to_write()));
+    }
+
+    #[test]
+    fn blocking_atomic_write_waits_for_space() {
+        let (read_end, write_end) = make_pipe(OpenFlags::empty());
+        write_end.set_capacity(PAGE_SIZE_4K).unwrap();
+        run(write_end.write(&[0; PAGE_SIZE_4K - 10])).unwrap();
+        assert!(poll_once(write_end.write(&[1; 20])).is_pending());
+        let mut buf = [0u8; 10];
+        assert_eq!(run(read_end.read(&mut buf)), Ok(10));
+        assert_eq!(run(write_end.write(&[1; 20])), Ok(20));
+    }
+
+    #[test]
+    fn eof_and_broken_pipe() {
+        let (read_end, write_end) = make_pipe(OpenFlags::empty());
+        let mut buf = [0u8; 8];
+        // 没有数据且写端打开时读者等待
+        assert!(poll_once(read_end.read(&mut buf)).is_pending());
+        run(write_end.write(b"abc")).unwrap();
+        assert_eq!(run(read_end.peek(&mut buf)), Ok(3));
+        drop(write_end);
+        // 写端关闭后先读出剩余的数据，再读到 EOF
+        assert!(run(read_end.is_hang_up()));
+        assert_eq!(run(read_end.read(&mut buf)), Ok(3));
+        assert_eq!(&buf[..3], b"abc");
+        assert_eq!(run(read_end.read(&mut buf)), Ok(0));
+
+        let (read_end, write_end) = make_pipe(OpenFlags::empty());
+        drop(read_end);
+        assert_eq!(run(write_end.write(b"x")), Err(AxError::ConnectionReset));
+    }
+
+    fn fifo() -> Arc<PipeShared> {
+        Arc::new(PipeShared::new(0, 0, None))
+    }
+
+    #[test]
+    fn fifo_open_waits_for_peer() {
+        let shared = fifo();
+        let mut reader = Box::pin(open_end(shared.clone(), "/fifo", OpenFlags::RDONLY));
+        assert!(poll_once(reader.as_mut()).is_pending());
+        // 写端打开后双方都能返回
+        let writer = run(open_end(shared.clone(), "/fifo", OpenFlags::WRONLY)).unwrap();
+        let reader = run(reader).unwrap();
+        run(writer.write(b"hi")).unwrap();
+        let mut buf = [0u8; 2];
+        assert_eq!(run(reader.read(&mut buf)), Ok(2));
+        assert_eq!(run(reader.get_status()).bits(), OpenFlags::RDONLY.bits());
+
+        // 对端打开后立即关闭，等待者同样被唤醒
+        let shared = fifo();
+        let mut writer = Box::pin(open_end(shared.clone(), "/fifo", OpenFlags::WRONLY));
+        assert!(poll_once(writer.as_mut()).is_pending());
+        drop(run(open_end(shared.clone(), "/fifo", OpenFlags::RDONLY)).unwrap());
+        let writer = run(writer).unwrap();
+        assert_eq!(run(writer.write(b"x")), Err(AxError::ConnectionReset));
+    }
+
+    #[test]
+    fn fifo_nonblocking_open() {
+        let shared = fifo();
+        // 没有读端时非阻塞的只写打开失败
+        let write_only = OpenFlags::WRONLY | OpenFlags::NON_BLOCK;
+        assert_eq!(run(open_end(shared.clone(), "/fifo", write_only)).err(), Some(SyscallError::ENXIO));
+        // 非阻塞的只读打开立即返回，没有写端时读到 EOF
+        let read_only = OpenFlags::RDONLY | OpenFlags::NON_BLOCK;
+        let reader = run(open_end(shared.clone(), "/fifo", read_only)).unwrap();
+        let mut buf = [0u8; 1];
+        assert_eq!(run(reader.read(&mut buf)), Ok(0));
+        let writer = run(open_end(shared.clone(), "/fifo", write_only)).unwrap();
+        assert_eq!(run(reader.read(&mut buf)), Err(AxError::WouldBlock));
+        run(writer.write(b"z")).unwrap();
+        assert_eq!(run(reader.read(&mut buf)), Ok(1));
+    }
+
+    #[test]
+    fn fifo_rdwr_open_never_waits() {
+        let both = run(open_end(fifo(), "/fifo", OpenFlags::RDWR)).unwrap();
+        assert!(run(both.readable()) && run(both.writable()));
+        run(both.write(b"loop")).unwrap();
+        let mut buf = [0u8; 4];
+        assert_eq!(run(both.read(&mut buf)), Ok(4));
+        assert_eq!(run(both.get_path()), "/fifo");
+    }
+
+    #[test]
+    fn set_capacity_rounds_to_pages() {
+        let (_read_end, write_end) = make_pipe(OpenFlags::NON_BLOCK);
+        assert_eq!(write_end.capacity(), DEFAULT_CAPACITY);
+        assert_eq!(write_end.set_capacity(3 * PAGE_SIZE_4K), Ok(4 * PAGE_SIZE_4K));
+        assert_eq!(write_end.set_capacity(PIPE_MAX_SIZE + 1), Err(AxError::PermissionDenied));
+        run(write_end.write(&[0; 2 * PAGE_SIZE_4K])).unwrap();
+        // 不能缩小到放不下已有的数据
+        assert_eq!(write_end.set_capacity(PAGE_SIZE_4K), Err(AxError::ResourceBusy));
+        assert_eq!(write_end.capacity(), 4 * PAGE_SIZE_4K);
+    }
+
+    #[test]
+    fn register_waker_twice() {
+        let (read_end, write_end) = make_pipe(OpenFlags::NON_BLOCK);
+        let counter = WakeCounter::new();
+        let waker = counter.waker();
+        // 重复注册同一个唤醒器，只应被唤醒一次
+        assert!(read_end.register_ready_waker(&mut Context::from_waker(&waker)));
+        assert!(read_end.register_ready_waker(&mut Context::from_waker(&waker)));
+        run(write_end.write(b"abc")).unwrap();
+        assert_eq!(counter.count(), 1);
+    }
+}
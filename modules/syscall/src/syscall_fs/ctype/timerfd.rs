@@ -0,0 +1,333 @@
+//! timerfd：通过文件描述符通知到期的定时器
+//!
+//! 到期时间统一以单调时钟 [`current_time`] 记录，CLOCK_REALTIME 的绝对时间在设置时换算。
+//! 开启 `irq` 特性时，定时器通过 [`sync::set_alarm_wakeup`] 在到期时唤醒等待者；
+//! 否则等待者只能自行睡眠到到期时间，poll/epoll 会定期轮询它
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use async_fs::api::{async_trait, FileIO, FileIOType, OpenFlags};
+use axerrno::{AxError, AxResult};
+use axhal::time::{current_time, TimeValue};
+use bitflags::bitflags;
+use core::future::poll_fn;
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+use spinlock::SpinNoIrq;
+use sync::WaitQueue;
+
+use crate::syscall_fs::imp::sleep_until_woken;
+use crate::TimeSecs;
+
+bitflags! {
+    /// timerfd_create 的 flags
+    #[derive(Clone, Copy, Debug)]
+    pub struct TimerFdFlag: u32 {
+        const TFD_NONBLOCK = 0x800;
+        const TFD_CLOEXEC = 0x80000;
+    }
+}
+
+bitflags! {
+    /// timerfd_settime 的 flags
+    #[derive(Clone, Copy, Debug)]
+    pub struct TimerSetFlag: u32 {
+        /// 到期时间是绝对时间
+        const TFD_TIMER_ABSTIME = 0x1;
+        /// 实时时钟被修改时取消定时器，目前时钟不能被修改，因此忽略
+        const TFD_TIMER_CANCEL_ON_SET = 0x2;
+    }
+}
+
+/// timerfd_settime/timerfd_gettime 使用的 `struct itimerspec`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ITimerSpec {
+    /// 周期定时器的间隔，为 0 时只到期一次
+    pub it_interval: TimeSecs,
+    /// 第一次到期的时间，为 0 时关闭定时器
+    pub it_value: TimeSecs,
+}
+
+/// 定时器的状态
+#[derive(Default)]
+struct TimerState {
+    /// 下一次到期的单调时间，为 None 时定时器未启动
+    next: Option<TimeValue>,
+    interval: Duration,
+    /// 上次 read 之后到期的次数
+    expirations: u64,
+}
+
+impl TimerState {
+    /// 按照当前时间 `now` 更新到期次数与下一次到期时间
+    fn advance(&mut self, now: TimeValue) {
+        let Some(next) = self.next else {
+            return;
+        };
+        if now < next {
+            return;
+        }
+        if self.interval.is_zero() {
+            self.expirations += 1;
+            self.next = None;
+            return;
+        }
+        let interval = self.interval.as_nanos();
+        let overrun = (now - next).as_nanos() / interval + 1;
+        self.expirations = self.expirations.saturating_add(overrun as u64);
+        self.next = Some(next + Duration::from_nanos((interval * overrun) as u64));
+    }
+}
+
+/// 定时器的共享部分，同时作为到期闹钟的唤醒器
+struct TimerInner {
+    state: SpinNoIrq<TimerState>,
+    waiters: WaitQueue,
+}
+
+impl Wake for TimerInner {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let mut state = self.state.lock();
+        state.advance(current_time());
+        let next = state.next;
+        drop(state);
+        // 周期定时器继续等待下一次到期
+        #[cfg(feature = "irq")]
+        if let Some(next) = next {
+            sync::set_alarm_wakeup(next, Waker::from(self.clone()));
+        }
+        #[cfg(not(feature = "irq"))]
+        let _ = next;
+        self.waiters.notify_all();
+    }
+}
+
+// https://man7.org/linux/man-pages/man2/timerfd_create.2.html
+pub struct TimerFd {
+    inner: Arc<TimerInner>,
+    /// 到期时唤醒等待者的闹钟
+    alarm: Waker,
+    /// 是否为 CLOCK_REALTIME 定时器
+    realtime: bool,
+    flags: SpinNoIrq<OpenFlags>,
+}
+
+impl TimerFd {
+    pub fn new(realtime: bool, flags: TimerFdFlag) -> TimerFd {
+        let mut status = OpenFlags::RDONLY;
+        if flags.contains(TimerFdFlag::TFD_NONBLOCK) {
+            status |= OpenFlags::NON_BLOCK;
+        }
+        let inner = Arc::new(TimerInner {
+            state: SpinNoIrq::new(TimerState::default()),
+            waiters: WaitQueue::new(),
+        });
+        TimerFd {
+            alarm: Waker::from(inner.clone()),
+            inner,
+            realtime,
+            flags: SpinNoIrq::new(status),
+        }
+    }
+
+    /// 实时时钟与单调时钟之差
+    fn realtime_offset(&self) -> Duration {
+        axhal::time::wall_time() - current_time()
+    }
+
+    /// 获取定时器当前的设置，`it_value` 为距离下一次到期的相对时间
+    pub fn get_time(&self) -> ITimerSpec {
+        let mut state = self.inner.state.lock();
+        let now = current_time();
+        state.advance(now);
+        ITimerSpec {
+            it_interval: TimeSecs::from_duration(state.interval),
+            it_value: TimeSecs::from_duration(
+                state.next.map_or(Duration::ZERO, |next| next.saturating_sub(now)),
+            ),
+        }
+    }
+
+    /// 设置定时器，返回原先的设置
+    ///
+    /// 调用者需要保证 `new_value` 中的时间合法
+    pub fn set_time(&self, new_value: &ITimerSpec, flags: TimerSetFlag) -> ITimerSpec {
+        let old_value = self.get_time();
+        let value = Duration::new(new_value.it_value.tv_sec as u64, new_value.it_value.tv_nsec as u32);
+        let interval = Duration::new(
+            new_value.it_interval.tv_sec as u64,
+            new_value.it_interval.tv_nsec as u32,
+        );
+        let next = if value.is_zero() {
+            None
+        } else if flags.contains(TimerSetFlag::TFD_TIMER_ABSTIME) {
+            if self.realtime {
+                Some(value.saturating_sub(self.realtime_offset()))
+            } else {
+                Some(value)
+            }
+        } else {
+            Some(current_time() + value)
+        };
+        let mut state = self.inner.state.lock();
+        state.next = next;
+        state.interval = interval;
+        state.expirations = 0;
+        drop(state);
+        #[cfg(feature = "irq")]
+        {
+            sync::cancel_alarm(&self.alarm);
+            if let Some(next) = next {
+                sync::set_alarm_wakeup(next, self.alarm.clone());
+            }
+        }
+        // 唤醒等待者重新计算到期时间
+        self.inner.waiters.notify_all();
+        old_value
+    }
+
+    fn is_non_block(&self) -> bool {
+        self.flags.lock().contains(OpenFlags::NON_BLOCK)
+    }
+}
+
+impl Drop for TimerFd {
+    fn drop(&mut self) {
+        #[cfg(feature = "irq")]
+        sync::cancel_alarm(&self.alarm);
+    }
+}
+
+#[async_trait]
+impl FileIO for TimerFd {
+    /// 读取上次读取之后定时器到期的次数，缓冲区不足 8 字节时返回 EINVAL
+    ///
+    /// 尚未到期时阻塞，非阻塞模式下返回 EAGAIN
+    async fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
+        let len: usize = core::mem::size_of::<u64>();
+        if buf.len() < len {
+            return Err(AxError::InvalidInput);
+        }
+        let non_block = self.is_non_block();
+        let waker = poll_fn(|cx| Poll::Ready(cx.waker().clone())).await;
+        let expirations = loop {
+            // 在释放锁之前注册，避免错过 set_time 的通知
+            let checked = poll_fn(|cx| {
+                let mut state = self.inner.state.lock();
+                state.advance(current_time());
+                if state.expirations > 0 {
+                    return Poll::Ready(Ok(core::mem::take(&mut state.expirations)));
+                }
+                if !non_block {
+                    let _ = self.inner.waiters.wait_until(cx, || false);
+                }
+                Poll::Ready(Err(state.next))
+            })
+            .await;
+            match checked {
+                Ok(expirations) => break expirations,
+                Err(_) if non_block => return Err(AxError::WouldBlock),
+                Err(next) => {
+                    sleep_until_woken(next, true).await;
+                    self.inner.waiters.remove_task(&waker);
+                }
+            }
+        };
+        buf[0..len].copy_from_slice(&expirations.to_ne_bytes());
+        Ok(len)
+    }
+
+    async fn write(&self, _buf: &[u8]) -> AxResult<usize> {
+        Err(AxError::InvalidInput)
+    }
+
+    async fn readable(&self) -> bool {
+        true
+    }
+
+    async fn writable(&self) -> bool {
+        false
+    }
+
+    async fn executable(&self) -> bool {
+        false
+    }
+
+    async fn get_type(&self) -> FileIOType {
+        FileIOType::Other
+    }
+
+    async fn get_path(&self) -> String {
+        String::from("anon_inode:[timerfd]")
+    }
+
+    async fn ready_to_read(&self) -> bool {
+        let mut state = self.inner.state.lock();
+        state.advance(current_time());
+        state.expirations > 0
+    }
+
+    async fn ready_to_write(&self) -> bool {
+        false
+    }
+
+    /// 只有在闹钟可用时才会在到期时主动通知
+    fn register_ready_waker(&self, cx: &mut Context<'_>) -> bool {
+        let _ = self.inner.waiters.wait_until(cx, || false);
+        cfg!(feature = "irq")
+    }
+
+    fn unregister_ready_waker(&self, waker: &Waker) {
+        self.inner.waiters.remove_task(waker);
+    }
+
+    /// 只有 O_NONBLOCK 可以修改
+    async fn set_status(&self, flags: OpenFlags) -> bool {
+        let mut status = self.flags.lock();
+        *status = (*status - OpenFlags::NON_BLOCK) | (flags & OpenFlags::NON_BLOCK);
+        true
+    }
+
+    async fn get_status(&self) -> OpenFlags {
+        *self.flags.lock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TimerState;
+    use core::time::Duration;
+
+    #[test]
+    fn test_advance_oneshot() {
+        let mut state = TimerState {
+            next: Some(Duration::from_secs(1)),
+            ..Default::default()
+        };
+        state.advance(Duration::from_millis(500));
+        assert_eq!(0, state.expirations);
+        state.advance(Duration::from_secs(3));
+        assert_eq!(1, state.expirations);
+        assert_eq!(None, state.next);
+    }
+
+    #[test]
+    fn test_advance_interval() {
+        let mut state = TimerState {
+            next: Some(Duration::from_secs(1)),
+            interval: Duration::from_millis(300),
+            expirations: 0,
+        };
+        state.advance(Duration::from_millis(1700));
+        assert_eq!(3, state.expirations);
+        assert_eq!(Some(Duration::from_millis(1900)), state.next);
+    }
+}
@@ -14,15 +14,32 @@ numeric_enum_macro::numeric_enum! {
 #[derive(Eq, PartialEq, Debug, Copy, Clone)]
 pub enum FsSyscallId {
     // fs
+    SETXATTR = 5,
+    LSETXATTR = 6,
+    FSETXATTR = 7,
+    GETXATTR = 8,
+    LGETXATTR = 9,
+    FGETXATTR = 10,
+    LISTXATTR = 11,
+    LLISTXATTR = 12,
+    FLISTXATTR = 13,
+    REMOVEXATTR = 14,
+    LREMOVEXATTR = 15,
+    FREMOVEXATTR = 16,
     GETCWD = 17,
     EVENTFD = 19,
     EPOLL_CREATE = 20,
     EPOLL_CTL = 21,
     EPOLL_PWAIT = 22,
+    INOTIFY_INIT1 = 26,
+    INOTIFY_ADD_WATCH = 27,
+    INOTIFY_RM_WATCH = 28,
     DUP = 23,
     DUP3 = 24,
     FCNTL64 = 25,
+    FLOCK = 32,
     IOCTL = 29,
+    MKNODAT = 33,
     MKDIRAT = 34,
     SYMLINKAT = 36,
     UNLINKAT = 35,
@@ -31,11 +48,15 @@ pub enum FsSyscallId {
     UNMOUNT = 39,
     MOUNT = 40,
     STATFS = 43,
+    FSTATFS = 44,
     FTRUNCATE64 = 46,
+    FALLOCATE = 47,
     FACCESSAT = 48,
     CHDIR = 49,
+    FCHMOD = 52,
     FCHMODAT = 53,
-    FCHOWN = 54,
+    FCHOWNAT = 54,
+    FCHOWN = 55,
     OPENAT = 56,
     CLOSE = 57,
     PIPE2 = 59,
@@ -50,16 +71,32 @@ pub enum FsSyscallId {
     PREAD64 = 67,
     PWRITE64 = 68,
     SENDFILE64 = 71,
+    SPLICE = 76,
+    TEE = 77,
     PSELECT6 = 72,
     PREADLINKAT = 78,
     FSTAT = 80,
     SYNC = 81,
     FSYNC = 82,
+    FDATASYNC = 83,
+    TIMERFD_CREATE = 85,
+    TIMERFD_SETTIME = 86,
+    TIMERFD_GETTIME = 87,
     UTIMENSAT = 88,
+    MQ_OPEN = 180,
+    MQ_UNLINK = 181,
+    MQ_TIMEDSEND = 182,
+    MQ_TIMEDRECEIVE = 183,
+    MQ_NOTIFY = 184,
+    MQ_GETSETATTR = 185,
     RENAMEAT2 = 276,
+    MEMFD_CREATE = 279,
     COPYFILERANGE = 285,
     STATX = 291,
     PIDFD_OPEN = 434,
+    PIDFD_GETFD = 438,
+    CLOSE_RANGE = 436,
+    EPOLL_PWAIT2 = 441,
 }
 }
 
@@ -75,6 +112,9 @@ numeric_enum_macro::numeric_enum! {
         STAT = 4,
         EVENTFD = 284,
         EVENTFD2 = 290,
+        TIMERFD_CREATE = 283,
+        TIMERFD_SETTIME = 286,
+        TIMERFD_GETTIME = 287,
         GETCWD = 79,
         UNLINK = 87,
         EPOLL_CREATE = 213,
@@ -84,6 +124,7 @@ numeric_enum_macro::numeric_enum! {
         DUP2 = 33,
         DUP3 = 292,
         FCNTL64 = 72,
+        FLOCK = 73,
         IOCTL = 16,
         MKDIRAT = 258,
         SYMLINKAT = 266,
@@ -95,11 +136,15 @@ numeric_enum_macro::numeric_enum! {
         UNMOUNT = 166,
         MOUNT = 165,
         STATFS = 137,
+        FSTATFS = 138,
         FTRUNCATE64 = 77,
+        FALLOCATE = 285,
         FACCESSAT = 269,
         ACCESS = 21,
         CHDIR = 80,
         FCHMODAT = 268,
+        FCHMOD = 91,
+        FCHOWNAT = 260,
         OPENAT = 257,
         CLOSE = 3,
         PIPE = 22,
@@ -117,6 +162,8 @@ numeric_enum_macro::numeric_enum! {
         PREAD64 = 17,
         PWRITE64 = 18,
         SENDFILE64 = 40,
+        SPLICE = 275,
+        TEE = 276,
         SELECT = 23,
         PSELECT6 = 270,
         READLINK = 89,
@@ -126,6 +173,7 @@ numeric_enum_macro::numeric_enum! {
         LSTAT = 6,
         SYNC = 162,
         FSYNC = 74,
+        FDATASYNC = 75,
         UTIMENSAT = 280,
         RENAMEAT = 264,
         RENAMEAT2 = 316,
@@ -134,8 +182,35 @@ numeric_enum_macro::numeric_enum! {
         EPOLL_PWAIT = 281,
         STATX = 332,
         CHOWN = 92,
-        MKNOD = 259,
+        MKNOD = 133,
+        MKNODAT = 259,
+        INOTIFY_INIT = 253,
+        INOTIFY_ADD_WATCH = 254,
+        INOTIFY_RM_WATCH = 255,
+        INOTIFY_INIT1 = 294,
         FCHOWN = 93,
+        SETXATTR = 188,
+        LSETXATTR = 189,
+        FSETXATTR = 190,
+        GETXATTR = 191,
+        LGETXATTR = 192,
+        FGETXATTR = 193,
+        LISTXATTR = 194,
+        LLISTXATTR = 195,
+        FLISTXATTR = 196,
+        REMOVEXATTR = 197,
+        LREMOVEXATTR = 198,
+        FREMOVEXATTR = 199,
+        MQ_OPEN = 240,
+        MQ_UNLINK = 241,
+        MQ_TIMEDSEND = 242,
+        MQ_TIMEDRECEIVE = 243,
+        MQ_NOTIFY = 244,
+        MQ_GETSETATTR = 245,
+        MEMFD_CREATE = 319,
         PIDFD_OPEN = 434,
+        PIDFD_GETFD = 438,
+        CLOSE_RANGE = 436,
+        EPOLL_PWAIT2 = 441,
     }
 }
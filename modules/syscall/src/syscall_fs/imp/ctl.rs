@@ -1,26 +1,40 @@
 //! 对文件系统的管理,包括目录项的创建、文件权限设置等内容
 use async_fs::api::{
-    remove_dir, remove_file, rename, ConsoleWinSize, OpenFlags, Permissions, FIOCLEX, FIONBIO,
-    TCGETS, TIOCGPGRP, TIOCGWINSZ, TIOCSPGRP,
+    rename, ConsoleWinSize, FileType, LookupFlags, OpenFlags, Permissions,
+    VfsAccess, VfsSeals, VfsSetAttr, FIOCLEX, FIONBIO, TCGETS, TIOCGPGRP, TIOCGWINSZ, TIOCSPGRP,
 };
+use axerrno::AxError;
+use axhal::time::wall_time;
+use core::time::Duration;
 use axlog::{debug, error, info};
 use core::ptr::{self, copy_nonoverlapping};
 use async_io::Stream;
-use alloc::string::ToString;
+use alloc::string::{String, ToString};
 
 use crate::{
     syscall_fs::{
-        ctype::{file::new_fd, FileDesc},
-        solve_path,
+        check_parent_access, check_remove,
+        ctype::{pipe::Pipe, FileDesc},
+        fcntl_lock, fd_err,
+        init_owner, link_err, solve_path, solve_path_with_flags,
     },
     DirEnt, DirEntType, Fcntl64Cmd, RenameFlags, SyscallError, SyscallResult, TimeSecs,
+    UTIME_NOW, UTIME_OMIT,
 };
 use axhal::mem::VirtAddr;
 use executor::{
     current_executor,
-    link::{FilePath, AT_FDCWD},
+    link::{raw_ptr_to_ref_str, FilePath, AT_FDCWD},
+    FdFlags,
 };
 
+/// Do not follow the symbolic link at the end of the path.
+pub const AT_SYMLINK_NOFOLLOW: usize = 0x100;
+/// Use the effective ids instead of the real ids in faccessat.
+pub const AT_EACCESS: usize = 0x200;
+/// Operate on the file descriptor `dir_fd` itself if the path is empty.
+pub const AT_EMPTY_PATH: usize = 0x1000;
+
 extern crate alloc;
 
 /// 功能:获取当前工作目录；
@@ -90,9 +104,11 @@ pub async fn syscall_mkdirat(args: [usize; 6]) -> SyscallResult {
         // 文件已存在
         return Err(SyscallError::EEXIST);
     }
-    let _ = async_fs::api::create_dir(path.path());
+    check_parent_access(&path).await?;
+    let _ = async_fs::api::create_dir(path.path()).await;
     // 只要文件夹存在就返回0
     if async_fs::api::path_exists(path.path()).await {
+        init_owner(path.path(), mode).await;
         Ok(0)
     } else {
         Err(SyscallError::EPERM)
@@ -113,6 +129,71 @@ pub fn syscall_mkdir(args: [usize; 6]) -> SyscallResult {
     syscall_mkdirat(temp_args)
 }
 
+/// 文件类型的掩码
+const S_IFMT: u32 = 0o170000;
+const S_IFSOCK: u32 = 0o140000;
+const S_IFREG: u32 = 0o100000;
+const S_IFBLK: u32 = 0o060000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFCHR: u32 = 0o020000;
+const S_IFIFO: u32 = 0o010000;
+
+/// 功能:创建一个文件系统节点，例如命名管道；
+/// # Arguments
+/// * `dir_fd`: usize, 相对路径的起点目录
+/// * `path`: *const u8, 节点的路径
+/// * `mode`: u32, 文件类型与权限，类型为 0 时创建普通文件
+/// * `dev`: usize, 设备号，只对设备文件有意义
+/// # Return
+/// 成功执行,返回0。文件系统不支持该类型的节点时返回 EPERM
+pub async fn syscall_mknodat(args: [usize; 6]) -> SyscallResult {
+    let dir_fd = args[0];
+    let path = args[1] as *const u8;
+    let mode = args[2] as u32;
+    let ty = match mode & S_IFMT {
+        0 | S_IFREG => FileType::File,
+        S_IFIFO => FileType::Fifo,
+        S_IFSOCK => FileType::Socket,
+        // 目录只能由 mkdir 创建；没有文件系统支持设备节点
+        S_IFDIR | S_IFCHR | S_IFBLK => return Err(SyscallError::EPERM),
+        _ => return Err(SyscallError::EINVAL),
+    };
+    let path = solve_path(dir_fd, Some(path), false).await?;
+    debug!(
+        "Into syscall_mknodat. dirfd: {}, path: {:?}, mode: {:o}",
+        dir_fd,
+        path.path(),
+        mode
+    );
+    if async_fs::api::get_attr(path.path(), LookupFlags::NOFOLLOW)
+        .await
+        .is_ok()
+    {
+        return Err(SyscallError::EEXIST);
+    }
+    check_parent_access(&path).await?;
+    match async_fs::api::create_node(path.path(), ty).await {
+        Ok(()) => {}
+        Err(AxError::AlreadyExists) => return Err(SyscallError::EEXIST),
+        Err(AxError::NotFound) => return Err(SyscallError::ENOENT),
+        Err(AxError::NotADirectory) => return Err(SyscallError::ENOTDIR),
+        Err(_) => return Err(SyscallError::EPERM),
+    }
+    init_owner(path.path(), mode).await;
+    Ok(0)
+}
+
+/// 功能:创建一个文件系统节点；
+/// # Arguments
+/// * `path`: *const u8
+/// * `mode`: u32
+/// * `dev`: usize
+#[cfg(target_arch = "x86_64")]
+pub async fn syscall_mknod(args: [usize; 6]) -> SyscallResult {
+    let temp_args = [AT_FDCWD, args[0], args[1], args[2], 0, 0];
+    syscall_mknodat(temp_args).await
+}
+
 /// 功能:切换工作目录；
 /// # Arguments
 /// * `path``: *const u8, 需要切换到的目录。
@@ -304,26 +385,22 @@ pub async fn syscall_renameat2(args: [usize; 6]) -> SyscallResult {
         // 相同文件不用改
         return Ok(0);
     }
+    // 目录不能移动到自己的子目录中
+    let old_dir = old_path.path().trim_end_matches('/');
+    if new_path.path().starts_with(old_dir) && new_path.path()[old_dir.len()..].starts_with('/') {
+        return Err(SyscallError::EINVAL);
+    }
+    check_remove(&old_path).await?;
+    if async_fs::api::path_exists(new_path.path()).await {
+        check_remove(&new_path).await?;
+    } else {
+        check_parent_access(&new_path).await?;
+    }
     if !flags.contains(RenameFlags::EXCHANGE) {
-        // 当新文件存在，先删掉新文件
-        // 此时若存在新文件，默认是没有 NOREPLACE 的
-        if async_fs::api::path_exists(new_path.path()).await {
-            let new_metadata = async_fs::api::metadata(new_path.path()).await.unwrap();
-            if new_metadata.is_dir() {
-                if let Err(err) = remove_dir(new_path.path()).await {
-                    error!("error: {:?}", err);
-                    return Err(SyscallError::EPERM);
-                }
-            } else if new_metadata.is_file() {
-                if let Err(err) = remove_file(new_path.path()).await {
-                    error!("error: {:?}", err);
-                    return Err(SyscallError::EPERM);
-                }
-            }
-        }
+        // 已存在的新文件由文件系统的 rename 按照替换规则处理
         if let Err(err) = rename(old_path.path(), new_path.path()).await {
             error!("error: {:?}", err);
-            return Err(SyscallError::EPERM);
+            return Err(link_err(err));
         }
     } else {
         // 当前不支持交换
@@ -355,67 +432,106 @@ pub async fn syscall_fcntl64(args: [usize; 6]) -> SyscallResult {
     let cmd = args[1];
     let arg = args[2];
     let process = current_executor();
-    let mut fd_table = process.fd_manager.fd_table.lock().await;
-
-    if fd >= fd_table.len() {
-        debug!("fd {} is out of range", fd);
-        return Err(SyscallError::EBADF);
-    }
-    if fd_table[fd].is_none() {
+    let Some(file) = process.fd_manager.get(fd).await else {
         debug!("fd {} is none", fd);
         return Err(SyscallError::EBADF);
-    }
-    let file = fd_table[fd].clone().unwrap();
+    };
     info!("fd: {}, cmd: {}", fd, cmd);
     match Fcntl64Cmd::try_from(cmd) {
         Ok(Fcntl64Cmd::F_DUPFD) => {
-            let new_fd = if let Ok(fd) = process.alloc_fd(&mut fd_table) {
-                fd
-            } else {
-                // 文件描述符达到上限了
-                return Err(SyscallError::EMFILE);
-            };
-            fd_table[new_fd] = fd_table[fd].clone();
+            // 新的文件描述符不小于 arg,且不继承 FD_CLOEXEC
+            let new_fd = process
+                .fd_manager
+                .dup(fd, arg, FdFlags::empty())
+                .await
+                .map_err(|e| match e {
+                    AxError::StorageFull if arg >= process.fd_manager.get_limit() as usize => {
+                        SyscallError::EINVAL
+                    }
+                    e => fd_err(e),
+                })?;
             Ok(new_fd as isize)
         }
         Ok(Fcntl64Cmd::F_GETFD) => {
-            if file.get_status().await.contains(OpenFlags::CLOEXEC) {
-                Ok(1)
-            } else {
-                Ok(0)
-            }
+            let flags = process.fd_manager.get_flags(fd).await.map_err(fd_err)?;
+            Ok(flags.bits() as isize)
         }
         Ok(Fcntl64Cmd::F_SETFD) => {
-            if file.set_close_on_exec((arg & 1) != 0).await {
-                Ok(0)
-            } else {
-                error!("file.set_close_on_exec");
-                Err(SyscallError::EINVAL)
-            }
+            // 只修改该文件描述符自身的标志,不影响 dup 出的其他文件描述符
+            let flags = FdFlags::from_bits_truncate(arg as u32);
+            process.fd_manager.set_flags(fd, flags).await.map_err(fd_err)?;
+            Ok(0)
+        }
+        Ok(Fcntl64Cmd::F_GETFL) => {
+            // O_CLOEXEC 属于文件描述符标志,不在文件状态标志中返回
+            Ok((file.get_status().await - OpenFlags::CLOEXEC).bits() as isize)
         }
-        Ok(Fcntl64Cmd::F_GETFL) => Ok(file.get_status().await.bits() as isize),
         Ok(Fcntl64Cmd::F_SETFL) => {
             if let Some(flags) = OpenFlags::from_bits(arg as u32) {
-                let _ = file.set_status(flags);
+                // 状态标志属于共享的打开文件描述
+                let _ = file.set_status(flags).await;
                 Ok(0)
             } else {
                 Err(SyscallError::EINVAL)
             }
         }
         Ok(Fcntl64Cmd::F_DUPFD_CLOEXEC) => {
-            let new_fd = if let Ok(fd) = process.alloc_fd(&mut fd_table) {
-                fd
-            } else {
-                // 文件描述符达到上限了
-                return Err(SyscallError::EMFILE);
+            let new_fd = process
+                .fd_manager
+                .dup(fd, arg, FdFlags::CLOEXEC)
+                .await
+                .map_err(|e| match e {
+                    AxError::StorageFull if arg >= process.fd_manager.get_limit() as usize => {
+                        SyscallError::EINVAL
+                    }
+                    e => fd_err(e),
+                })?;
+            Ok(new_fd as isize)
+        }
+        Ok(
+            cmd @ (Fcntl64Cmd::F_GETLK
+            | Fcntl64Cmd::F_SETLK
+            | Fcntl64Cmd::F_SETLKW
+            | Fcntl64Cmd::F_OFD_GETLK
+            | Fcntl64Cmd::F_OFD_SETLK
+            | Fcntl64Cmd::F_OFD_SETLKW),
+        ) => fcntl_lock(file, cmd, arg).await,
+        Ok(Fcntl64Cmd::F_SETPIPE_SZ) => {
+            let Some(pipe) = (*file).as_any().downcast_ref::<Pipe>() else {
+                return Err(SyscallError::EBADF);
             };
-
-            if file.set_close_on_exec((arg & 1) != 0).await {
-                fd_table[new_fd] = fd_table[fd].clone();
-                Ok(new_fd as isize)
-            } else {
-                error!("file.F_DUPFD_CLOEXEC");
-                Err(SyscallError::EINVAL)
+            match pipe.set_capacity(arg) {
+                Ok(capacity) => Ok(capacity as isize),
+                Err(AxError::ResourceBusy) => Err(SyscallError::EBUSY),
+                Err(_) => Err(SyscallError::EPERM),
+            }
+        }
+        Ok(Fcntl64Cmd::F_GETPIPE_SZ) => match (*file).as_any().downcast_ref::<Pipe>() {
+            Some(pipe) => Ok(pipe.capacity() as isize),
+            None => Err(SyscallError::EBADF),
+        },
+        Ok(Fcntl64Cmd::F_ADD_SEALS) => {
+            // 只有 memfd 支持 seals，其他文件返回 EINVAL
+            let Some(desc) = (*file).as_any().downcast_ref::<FileDesc>() else {
+                return Err(SyscallError::EINVAL);
+            };
+            let Some(seals) = VfsSeals::from_bits(arg as u32) else {
+                return Err(SyscallError::EINVAL);
+            };
+            match desc.file.lock().await.add_seals(seals).await {
+                Ok(()) => Ok(0),
+                // 已带有 F_SEAL_SEAL，或者文件不是以可写方式打开的
+                Err(AxError::PermissionDenied) => Err(SyscallError::EPERM),
+                Err(_) => Err(SyscallError::EINVAL),
+            }
+        }
+        Ok(Fcntl64Cmd::F_GET_SEALS) => {
+            let Some(desc) = (*file).as_any().downcast_ref::<FileDesc>() else {
+                return Err(SyscallError::EINVAL);
+            };
+            match desc.file.lock().await.get_seals().await {
+                Ok(seals) => Ok(seals.bits() as isize),
+                Err(_) => Err(SyscallError::EINVAL),
             }
         }
         _ => {
@@ -437,21 +553,15 @@ pub async fn syscall_ioctl(args: [usize; 6]) -> SyscallResult {
     let request = args[1];
     let argp = args[2];
     let process = current_executor();
-    let fd_table = process.fd_manager.fd_table.lock().await;
     info!("fd: {}, request: {}, argp: {}", fd, request, argp);
-    if fd >= fd_table.len() {
-        debug!("fd {} is out of range", fd);
-        return Err(SyscallError::EBADF);
-    }
-    if fd_table[fd].is_none() {
+    let Some(file) = process.fd_manager.get(fd).await else {
         debug!("fd {} is none", fd);
         return Err(SyscallError::EBADF);
-    }
+    };
     if process.manual_alloc_for_lazy(argp.into()).await.is_err() {
         return Err(SyscallError::EFAULT); // 地址不合法
     }
 
-    let file = fd_table[fd].clone().unwrap();
     match request {
         TIOCGWINSZ => {
             let winsize = argp as *mut ConsoleWinSize;
@@ -476,34 +586,206 @@ pub async fn syscall_ioctl(args: [usize; 6]) -> SyscallResult {
             }
             return Ok(0);
         }
-        FIOCLEX => Ok(0),
+        FIOCLEX => {
+            let flags = process.fd_manager.get_flags(fd).await.map_err(fd_err)?;
+            process
+                .fd_manager
+                .set_flags(fd, flags | FdFlags::CLOEXEC)
+                .await
+                .map_err(fd_err)?;
+            Ok(0)
+        }
         _ => Err(SyscallError::EOPNOTSUPP),
     }
 }
 
+/// 将修改文件属性时的错误转换为系统调用的错误码
+fn setattr_err(err: AxError) -> SyscallError {
+    match err {
+        AxError::NotFound => SyscallError::ENOENT,
+        AxError::NotADirectory => SyscallError::ENOTDIR,
+        // 权限已经检查过，只可能是只读挂载
+        AxError::PermissionDenied => SyscallError::EROFS,
+        _ => SyscallError::EPERM,
+    }
+}
+
+/// 修改 `path` 的属性，文件系统不支持修改属性时（如 fat32）视为成功
+async fn set_attr(path: &str, attr: &VfsSetAttr, flags: LookupFlags) -> SyscallResult {
+    match async_fs::api::set_attr(path, attr, flags).await {
+        Ok(()) | Err(AxError::Unsupported) => Ok(0),
+        Err(e) => Err(setattr_err(e)),
+    }
+}
+
+/// 获取文件描述符对应的路径
+async fn fd_path(fd: usize) -> Result<String, SyscallError> {
+    match current_executor().fd_manager.get(fd).await {
+        Some(file) => Ok(file.get_path().await),
+        None => Err(SyscallError::EBADF),
+    }
+}
+
 /// 53
 /// 修改文件权限
-/// mode: 0o777, 3位八进制数字
+/// mode: 0o7777, 4位八进制数字
 /// path为相对路径:
 ///     1. 若dir_fd为AT_FDCWD,则相对于当前工作目录
 ///     2. 若dir_fd为AT_FDCWD以外的值,则相对于dir_fd所指的目录
 /// path为绝对路径:
 ///     忽视dir_fd,直接根据path访问
+///
+/// 只有文件的所有者与root可以修改权限
 /// # Arguments
 /// * `dir_fd`: usize, 目录的文件描述符
 /// * `path`: *const u8, 文件的路径
 /// * `mode`: usize, 文件的权限
+/// * `flags`: usize, 可设置为0或AT_SYMLINK_NOFOLLOW
 pub async fn syscall_fchmodat(args: [usize; 6]) -> SyscallResult {
     let dir_fd = args[0];
     let path = args[1] as *const u8;
     let mode = args[2];
-    let file_path = solve_path(dir_fd, Some(path), false).await?;
-    async_fs::api::metadata(file_path.path()).await
-        .map(|mut metadata| {
-            metadata.set_permissions(Permissions::from_bits_truncate(mode as u16));
-            Ok(0)
-        })
-        .unwrap_or_else(|_| Err(SyscallError::ENOENT))
+    let flags = args[3];
+    let lookup = if flags & AT_SYMLINK_NOFOLLOW != 0 {
+        LookupFlags::NOFOLLOW
+    } else {
+        LookupFlags::empty()
+    };
+    let file_path = solve_path_with_flags(dir_fd, Some(path), false, lookup).await?;
+    chmod(file_path.path(), mode as u32, lookup).await
+}
+
+/// 52
+/// 修改文件描述符对应文件的权限
+/// # Arguments
+/// * `fd`: usize, 文件描述符
+/// * `mode`: usize, 文件的权限
+pub async fn syscall_fchmod(args: [usize; 6]) -> SyscallResult {
+    let path = fd_path(args[0]).await?;
+    chmod(&path, args[1] as u32, LookupFlags::empty()).await
+}
+
+/// 修改文件的权限
+/// # Arguments
+/// * `path`: *const u8, 文件的路径
+/// * `mode`: usize, 文件的权限
+#[cfg(target_arch = "x86_64")]
+pub async fn syscall_chmod(args: [usize; 6]) -> SyscallResult {
+    let temp_args = [AT_FDCWD, args[0], args[1], 0, 0, 0];
+    syscall_fchmodat(temp_args).await
+}
+
+async fn chmod(path: &str, mode: u32, lookup: LookupFlags) -> SyscallResult {
+    let attr = async_fs::api::get_attr(path, lookup).await.map_err(setattr_err)?;
+    let cred = &current_executor().cred;
+    let mut perm = Permissions::from_bits_truncate((mode & 0o7777) as u16);
+    if !cred.is_root() {
+        if cred.euid() != attr.uid() {
+            return Err(SyscallError::EPERM);
+        }
+        if cred.egid() != attr.gid() {
+            // 不属于文件所在的用户组时不能设置 set-group-ID 位
+            perm.remove(Permissions::SET_GID);
+        }
+    }
+    let attr = VfsSetAttr {
+        mode: Some(perm),
+        ..Default::default()
+    };
+    set_attr(path, &attr, lookup).await
+}
+
+/// 54
+/// 修改文件的所有者与用户组
+///
+/// 只有root可以修改所有者；文件的所有者可以把用户组修改为自己所在的用户组。
+/// 修改普通文件的所有者或用户组后,其 set-user-ID 与 set-group-ID 位被清除
+/// # Arguments
+/// * `dir_fd`: usize, 目录的文件描述符
+/// * `path`: *const u8, 文件的路径
+/// * `owner`: usize, 新的所有者,为-1时不修改
+/// * `group`: usize, 新的用户组,为-1时不修改
+/// * `flags`: usize, 可设置为0、AT_SYMLINK_NOFOLLOW或AT_EMPTY_PATH
+pub async fn syscall_fchownat(args: [usize; 6]) -> SyscallResult {
+    let dir_fd = args[0];
+    let path = args[1] as *const u8;
+    let owner = args[2] as u32;
+    let group = args[3] as u32;
+    let flags = args[4];
+    let lookup = if flags & AT_SYMLINK_NOFOLLOW != 0 {
+        LookupFlags::NOFOLLOW
+    } else {
+        LookupFlags::empty()
+    };
+    let file_path = if flags & AT_EMPTY_PATH != 0 && user_path_is_empty(path).await? {
+        fd_path(dir_fd).await?
+    } else {
+        solve_path_with_flags(dir_fd, Some(path), false, lookup).await?.path().to_string()
+    };
+    chown(&file_path, owner, group, lookup).await
+}
+
+/// 55
+/// 修改文件描述符对应文件的所有者与用户组
+/// # Arguments
+/// * `fd`: usize, 文件描述符
+/// * `owner`: usize, 新的所有者,为-1时不修改
+/// * `group`: usize, 新的用户组,为-1时不修改
+pub async fn syscall_fchown(args: [usize; 6]) -> SyscallResult {
+    let path = fd_path(args[0]).await?;
+    chown(&path, args[1] as u32, args[2] as u32, LookupFlags::empty()).await
+}
+
+/// 修改文件的所有者与用户组
+/// # Arguments
+/// * `path`: *const u8, 文件的路径
+/// * `owner`: usize, 新的所有者,为-1时不修改
+/// * `group`: usize, 新的用户组,为-1时不修改
+#[cfg(target_arch = "x86_64")]
+pub async fn syscall_chown(args: [usize; 6]) -> SyscallResult {
+    let temp_args = [AT_FDCWD, args[0], args[1], args[2], 0, 0];
+    syscall_fchownat(temp_args).await
+}
+
+async fn chown(path: &str, owner: u32, group: u32, lookup: LookupFlags) -> SyscallResult {
+    let attr = async_fs::api::get_attr(path, lookup).await.map_err(setattr_err)?;
+    let uid = (owner != u32::MAX && owner != attr.uid()).then_some(owner);
+    let gid = (group != u32::MAX && group != attr.gid()).then_some(group);
+    let cred = &current_executor().cred;
+    if !cred.is_root()
+        && (uid.is_some() || cred.euid() != attr.uid() || gid.is_some_and(|gid| gid != cred.egid()))
+    {
+        return Err(SyscallError::EPERM);
+    }
+    let mut perm = attr.perm();
+    let mode = if attr.is_file() && (uid.is_some() || gid.is_some()) {
+        perm.remove(Permissions::SET_UID | Permissions::SET_GID);
+        Some(perm)
+    } else {
+        None
+    };
+    let attr = VfsSetAttr {
+        mode,
+        uid,
+        gid,
+        ..Default::default()
+    };
+    set_attr(path, &attr, lookup).await
+}
+
+/// 读取用户态传入的路径,判断其是否为空串
+async fn user_path_is_empty(path: *const u8) -> Result<bool, SyscallError> {
+    if path.is_null() {
+        return Ok(true);
+    }
+    if current_executor()
+        .manual_alloc_for_lazy((path as usize).into())
+        .await
+        .is_err()
+    {
+        return Err(SyscallError::EFAULT);
+    }
+    Ok(unsafe { raw_ptr_to_ref_str(path) }.is_empty())
 }
 
 /// 48
@@ -516,47 +798,50 @@ pub async fn syscall_fchmodat(args: [usize; 6]) -> SyscallResult {
 ///        file exists and grants read, write, and execute permissions,
 ///        respectively.
 /// 0: F_OK, 1: X_OK, 2: W_OK, 4: R_OK
+///
+/// 默认使用真实用户 id 检查,设置AT_EACCESS时使用有效用户 id
 /// # Arguments
 /// * `dir_fd`: usize, 目录的文件描述符
 /// * `path`: *const u8, 文件的路径
 /// * `mode`: usize, 文件的权限
+/// * `flags`: usize, 可设置为0、AT_EACCESS或AT_SYMLINK_NOFOLLOW
 pub async fn syscall_faccessat(args: [usize; 6]) -> SyscallResult {
     let dir_fd = args[0];
     let path = args[1] as *const u8;
     let mode = args[2];
-    // todo: 有问题,实际上需要考虑当前进程对应的用户UID和文件拥有者之间的关系
-    // 现在一律当作root用户处理
-    let file_path = solve_path(dir_fd, Some(path), false).await?;
+    let flags = args[3];
+    let Some(access) = VfsAccess::from_bits(mode as u8).filter(|_| mode <= 7) else {
+        return Err(SyscallError::EINVAL);
+    };
+    let lookup = if flags & AT_SYMLINK_NOFOLLOW != 0 {
+        LookupFlags::NOFOLLOW
+    } else {
+        LookupFlags::empty()
+    };
+    let file_path = solve_path_with_flags(dir_fd, Some(path), false, lookup).await?;
     axlog::info!("syscall_faccessat file_path : {:?}", file_path);
-    if let Ok(metadata) = async_fs::api::metadata(file_path.path()).await {
-        if mode == 0 {
-            //F_OK
-            // 文件存在返回0,不存在返回-1
-            if async_fs::api::path_exists(file_path.path()).await {
-                Ok(0)
-            } else {
-                Err(SyscallError::ENOENT)
-            }
-        } else {
-            // 逐位对比
-            let mut ret = true;
-            if mode & 1 != 0 {
-                // X_OK
-                ret &= metadata.permissions().contains(Permissions::OWNER_EXEC)
-            }
-            if mode & 2 != 0 {
-                // W_OK
-                ret &= metadata.permissions().contains(Permissions::OWNER_WRITE)
-            }
-            if mode & 4 != 0 {
-                // R_OK
-                ret &= metadata.permissions().contains(Permissions::OWNER_READ)
-            }
-            Ok(ret as isize - 1)
-        }
+    let attr = match async_fs::api::get_attr(file_path.path(), lookup).await {
+        Ok(attr) => attr,
+        Err(AxError::NotFound) => return Err(SyscallError::ENOENT),
+        Err(AxError::NotADirectory) => return Err(SyscallError::ENOTDIR),
+        // 文件存在，但文件系统不支持查询属性
+        Err(_) => return Ok(0),
+    };
+    let cred = &current_executor().cred;
+    let (uid, gid) = if flags & AT_EACCESS != 0 {
+        (cred.euid(), cred.egid())
     } else {
-        Err(SyscallError::ENOENT)
+        (cred.uid(), cred.gid())
+    };
+    if !attr.permits(uid, gid, access) {
+        return Err(SyscallError::EACCES);
+    }
+    if access.contains(VfsAccess::WRITE)
+        && async_fs::api::check_access(file_path.path(), 0, 0, VfsAccess::WRITE).await.is_err()
+    {
+        return Err(SyscallError::EROFS);
     }
+    Ok(0)
 }
 
 /// 48
@@ -566,13 +851,11 @@ pub async fn syscall_faccessat(args: [usize; 6]) -> SyscallResult {
 /// * `path`: *const u8, 文件的路径
 /// * `mode`: usize, 文件的权限
 #[cfg(target_arch = "x86_64")]
-pub fn syscall_access(args: [usize; 6]) -> SyscallResult {
+pub async fn syscall_access(args: [usize; 6]) -> SyscallResult {
     let path = args[0];
     let mode = args[1];
     let temp_args = [AT_FDCWD, path, mode, 0, 0, 0];
-    // todo: 有问题,实际上需要考虑当前进程对应的用户UID和文件拥有者之间的关系
-    // 现在一律当作root用户处理
-    syscall_faccessat(temp_args)
+    syscall_faccessat(temp_args).await
 }
 
 /// 删除目录
@@ -587,88 +870,108 @@ pub fn syscall_rmdir(args: [usize; 6]) -> SyscallResult {
     super::syscall_unlinkat(temp_args)
 }
 
+/// 将用户传入的时间转换为要设置的时间,`UTIME_OMIT` 表示不修改
+fn utime(time: &TimeSecs) -> Option<Duration> {
+    match time.tv_nsec {
+        UTIME_OMIT => None,
+        UTIME_NOW => Some(wall_time()),
+        nsec => Some(Duration::new(time.tv_sec as u64, nsec as u32)),
+    }
+}
+
 /// 88
 /// 用于修改文件或目录的时间戳(timestamp)
-/// 如果 fir_fd < 0,它和 path 共同决定要找的文件；
-/// 如果 fir_fd >=0,它就是文件对应的 fd
+/// 如果 path 为 NULL,修改的是 dir_fd 对应的文件；
+/// 否则 dir_fd 和 path 共同决定要找的文件
+///
+/// 设为当前时间需要是文件的所有者或对文件有写权限,设为其他时间需要是文件的所有者
 /// # Arguments
 /// * `dir_fd`: usize, 目录的文件描述符
 /// * `path`: *const u8, 文件的路径
-/// * `times`: *const TimeSecs, 时间戳
-/// * `flags`: usize, 选项
+/// * `times`: *const TimeSecs, 时间戳,依次为 atime 与 mtime,为 NULL 时均设为当前时间
+/// * `flags`: usize, 可设置为0或AT_SYMLINK_NOFOLLOW
 pub async fn syscall_utimensat(args: [usize; 6]) -> SyscallResult {
     let dir_fd = args[0];
     let path = args[1] as *const u8;
     let times = args[2] as *const TimeSecs;
-    let _flags = args[3];
+    let flags = args[3];
     let process = current_executor();
-    // info!("dir_fd: {}, path: {}", dir_fd as usize, path as usize);
-    if dir_fd != AT_FDCWD && (dir_fd as isize) < 0 {
-        return Err(SyscallError::EBADF); // 错误的文件描述符
-    }
-
-    if dir_fd == AT_FDCWD
-        && process
-            .manual_alloc_for_lazy((path as usize).into()).await
-            .is_err()
-    {
-        return Err(SyscallError::EFAULT); // 地址不合法
-    }
-    // 需要设置的时间
-    let (new_atime, new_mtime) = if times.is_null() {
-        (TimeSecs::now(), TimeSecs::now())
+    // 需要设置的时间，以及是否只是设为当前时间
+    let (atime, mtime, only_now) = if times.is_null() {
+        (Some(wall_time()), Some(wall_time()), true)
     } else {
         if process.manual_alloc_type_for_lazy(times).await.is_err() {
             return Err(SyscallError::EFAULT);
         }
-        unsafe { (*times, *(times.add(1))) } //  注意传入的TimeVal中 sec和nsec都是usize, 但TimeValue中nsec是u32
-    };
-    // 感觉以下仿照maturin的实现不太合理,并没有真的把时间写给文件,只是写给了一个新建的临时的fd
-    if (dir_fd as isize) > 0 {
-        // let file = process_inner.fd_manager.fd_table[dir_fd].clone();
-        // if !file.unwrap().lock().set_time(new_atime, new_mtime) {
-        //     error!("Set time failed: unknown reason.");
-        //     return ErrorNo::EPERM as isize;
-        // }
-        let fd_table = process.fd_manager.fd_table.lock().await;
-        if dir_fd > fd_table.len() || fd_table[dir_fd].is_none() {
-            return Err(SyscallError::EBADF);
+        let times = unsafe { [*times, *(times.add(1))] };
+        let special = |t: &TimeSecs| t.tv_nsec == UTIME_NOW || t.tv_nsec == UTIME_OMIT;
+        if times.iter().any(|t| t.tv_nsec >= 1_000_000_000 && !special(t)) {
+            return Err(SyscallError::EINVAL);
         }
-        if let Some(file) = fd_table[dir_fd].as_ref() {
-            if let Some(fat_file) = file.as_any().downcast_ref::<FileDesc>() {
-                // if !fat_file.set_time(new_atime, new_mtime) {
-                //     error!("Set time failed: unknown reason.");
-                //     return ErrorNo::EPERM as isize;
-                // }
-                fat_file.stat.lock().await.atime.set_as_utime(&new_atime);
-                fat_file.stat.lock().await.mtime.set_as_utime(&new_mtime);
-            } else {
+        (utime(&times[0]), utime(&times[1]), times.iter().all(special))
+    };
+    let lookup = if flags & AT_SYMLINK_NOFOLLOW != 0 {
+        LookupFlags::NOFOLLOW
+    } else {
+        LookupFlags::empty()
+    };
+    let file_path = if path.is_null() {
+        fd_path(dir_fd).await?
+    } else {
+        solve_path_with_flags(dir_fd, Some(path), false, lookup).await?.path().to_string()
+    };
+    if atime.is_none() && mtime.is_none() {
+        return Ok(0);
+    }
+
+    let attr = match async_fs::api::get_attr(&file_path, lookup).await {
+        Ok(attr) => Some(attr),
+        Err(AxError::NotFound) => return Err(SyscallError::ENOENT),
+        Err(AxError::NotADirectory) => return Err(SyscallError::ENOTDIR),
+        Err(_) => None,
+    };
+    if let Some(attr) = attr {
+        let cred = &process.cred;
+        if !cred.is_root() && cred.euid() != attr.uid() {
+            if !only_now {
                 return Err(SyscallError::EPERM);
             }
+            if !attr.permits(cred.euid(), cred.egid(), VfsAccess::WRITE) {
+                return Err(SyscallError::EACCES);
+            }
         }
-        Ok(0)
+    }
+    let new_attr = VfsSetAttr {
+        atime,
+        mtime,
+        ..Default::default()
+    };
+    // 修改已打开的文件时直接通过其节点写入，使 fstat 能立即看到新的时间戳
+    let file = if path.is_null() {
+        process.fd_manager.get(dir_fd).await
     } else {
-        let file_path = solve_path(dir_fd, Some(path), false).await?;
-        if !async_fs::api::path_exists(file_path.path()).await {
-            if !async_fs::api::path_exists(file_path.dir().unwrap()).await {
-                return Err(SyscallError::ENOTDIR);
-            } else {
-                return Err(SyscallError::ENOENT);
+        None
+    };
+    let desc = file.as_ref().and_then(|file| (**file).as_any().downcast_ref::<FileDesc>());
+    let result = match desc {
+        Some(desc) => desc.file.lock().await.set_attr(&new_attr).await,
+        None => async_fs::api::set_attr(&file_path, &new_attr, lookup).await,
+    };
+    match result {
+        Ok(()) => Ok(0),
+        Err(AxError::Unsupported) => {
+            // 文件系统不记录时间戳时，记录在打开的文件描述符中
+            if let Some(desc) = desc {
+                let mut stat = desc.stat.lock().await;
+                if let Some(atime) = atime {
+                    stat.atime = TimeSecs::from_duration(atime);
+                }
+                if let Some(mtime) = mtime {
+                    stat.mtime = TimeSecs::from_duration(mtime);
+                }
             }
+            Ok(0)
         }
-        let file = new_fd(file_path.path().to_string(), 0.into()).await.unwrap();
-        file.stat.lock().await.atime.set_as_utime(&new_atime);
-        file.stat.lock().await.mtime.set_as_utime(&new_mtime);
-        Ok(0)
+        Err(e) => Err(setattr_err(e)),
     }
 }
-
-// /// To open a file descriptor that refers to the PID directory of the given process
-// pub async fn syscall_pidfd_open(args: [usize; 6]) -> SyscallResult {
-//     let pid = args[0] as u32;
-//     let flags = args[1] as u32;
-//     new_pidfd(
-//         pid as u64,
-//         OpenFlags::from_bits(flags).ok_or(SyscallError::EINVAL)?,
-//     )
-// }
@@ -2,34 +2,68 @@
 //! multiple file descriptors to see if I/O is possible on any of
 //! them.
 extern crate alloc;
-use crate::{SigMaskFlag, SyscallError, SyscallResult};
+use crate::{SyscallError, SyscallResult, TimeSecs};
 use alloc::sync::Arc;
-use axhal::{mem::VirtAddr, time::current_ticks};
-use axprocess::current_process;
+use async_fs::api::{FileIO, OpenFlags};
+use axhal::{
+    mem::VirtAddr,
+    time::{current_time, TimeValue},
+};
+use core::time::Duration;
+use executor::{current_executor, FdFlags};
 
-use crate::syscall_fs::ctype::epoll::{EpollCtl, EpollEvent, EpollFile};
+use super::{fd_err, timespec_deadline};
+use crate::syscall_fs::ctype::epoll::{EpollCtl, EpollEvent, EpollEventType, EpollFile};
+
+/// Set the close-on-exec flag on the new file descriptor.
+const EPOLL_CLOEXEC: usize = 0x80000;
+/// epoll_wait 一次最多返回的事件数目
+const EP_MAX_EVENTS: usize = i32::MAX as usize / core::mem::size_of::<EpollEvent>();
+
+/// 获取 `epfd` 对应的 epoll 文件
+async fn epoll_of(epfd: usize) -> Result<Arc<dyn FileIO>, SyscallError> {
+    let Some(file) = current_executor().fd_manager.get(epfd).await else {
+        return Err(SyscallError::EBADF);
+    };
+    if (*file).as_any().downcast_ref::<EpollFile>().is_none() {
+        return Err(SyscallError::EINVAL);
+    }
+    Ok(file)
+}
 
-/// For epoll_create, Since Linux 2.6.8, the size argument is ignored, but must be greater than zero;
-///
-///
 /// For epoll_create1, If flags is 0, then, other than the fact that the obsolete size argument is dropped, epoll_create1()
 ///  is the same as epoll_create().
 ///
 /// If flag equals to EPOLL_CLOEXEC, than set the cloexec flag for the fd
 /// # Arguments
 /// * `flag` - usize
-pub fn syscall_epoll_create1(args: [usize; 6]) -> SyscallResult {
-    let _flag = args[0];
-    let file = EpollFile::new();
-    let process = current_process();
-    let mut fd_table = process.fd_manager.fd_table.lock();
-    if let Ok(num) = process.alloc_fd(&mut fd_table) {
-        fd_table[num] = Some(Arc::new(file));
-        Ok(num as isize)
+pub async fn syscall_epoll_create1(args: [usize; 6]) -> SyscallResult {
+    let flags = args[0];
+    if flags & !EPOLL_CLOEXEC != 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    let fd_flags = if flags & EPOLL_CLOEXEC != 0 {
+        FdFlags::CLOEXEC
     } else {
-        // ErrorNo::EMFILE as isize
-        Err(SyscallError::EMFILE)
+        FdFlags::empty()
+    };
+    let fd = current_executor()
+        .fd_manager
+        .alloc(EpollFile::new(OpenFlags::RDWR), fd_flags)
+        .await
+        .map_err(fd_err)?;
+    Ok(fd as isize)
+}
+
+/// For epoll_create, Since Linux 2.6.8, the size argument is ignored, but must be greater than zero;
+/// # Arguments
+/// * `size` - i32
+#[cfg(target_arch = "x86_64")]
+pub async fn syscall_epoll_create(args: [usize; 6]) -> SyscallResult {
+    if args[0] as i32 <= 0 {
+        return Err(SyscallError::EINVAL);
     }
+    syscall_epoll_create1([0; 6]).await
 }
 
 /// 执行syscall_epoll_ctl，修改文件对应的响应事件
@@ -40,132 +74,113 @@ pub fn syscall_epoll_create1(args: [usize; 6]) -> SyscallResult {
 /// * `epfd`: i32, epoll文件的fd
 /// * `op`: i32, 修改操作的类型
 /// * `fd`: i32, 接受事件的文件的fd
-/// * `event`: *const EpollEvent, 接受的事件
-pub fn syscall_epoll_ctl(args: [usize; 6]) -> SyscallResult {
-    let epfd = args[0] as i32;
+/// * `event`: *const EpollEvent, 接受的事件，op 为 EPOLL_CTL_DEL 时可以为空
+pub async fn syscall_epoll_ctl(args: [usize; 6]) -> SyscallResult {
+    let epfd = args[0];
     let op = args[1] as i32;
     let fd = args[2] as i32;
     let event = args[3] as *const EpollEvent;
-    let process = current_process();
-    if process.manual_alloc_type_for_lazy(event).is_err() {
-        return Err(SyscallError::EFAULT);
-    }
-    let fd_table = process.fd_manager.fd_table.lock();
-    let event = unsafe { *event };
-    if fd_table[fd as usize].is_none() {
-        return Err(SyscallError::EBADF);
-    }
-    let op = if let Ok(val) = EpollCtl::try_from(op) {
-        val
-    } else {
+    let Ok(op) = EpollCtl::try_from(op) else {
         return Err(SyscallError::EINVAL);
     };
-    if let Some(file) = fd_table[epfd as usize].as_ref() {
-        if let Some(epoll_file) = file.as_any().downcast_ref::<EpollFile>() {
-            epoll_file.epoll_ctl(op, fd, event)
-        } else {
-            Err(SyscallError::EBADF)
+    let event = if matches!(op, EpollCtl::DEL) {
+        EpollEvent {
+            event_type: EpollEventType::empty(),
+            data: 0,
         }
     } else {
-        Err(SyscallError::EBADF)
-    }
+        if event.is_null() || current_executor().manual_alloc_type_for_lazy(event).await.is_err() {
+            return Err(SyscallError::EFAULT);
+        }
+        unsafe { *event }
+    };
+    let epoll = epoll_of(epfd).await?;
+    let Some(file) = current_executor().fd_manager.get(fd as usize).await else {
+        return Err(SyscallError::EBADF);
+    };
+    let epoll_file = (*epoll).as_any().downcast_ref::<EpollFile>().unwrap();
+    epoll_file.epoll_ctl(op, fd, file, event).await?;
+    Ok(0)
 }
 
-/// 执行syscall_epoll_wait系统调用
-///
-/// # Arguments
-/// * `epfd`: i32, epoll文件的fd
-/// * `event`: *mut EpollEvent, 接受事件的数组
-/// * `max_event`: i32, 最大的响应事件数量,必须大于0
-/// * `timeout`: i32, 超时时间，是一段相对时间，需要手动转化为绝对时间
-///
-/// ret: 实际写入的响应事件数目
-pub fn syscall_epoll_wait(args: [usize; 6]) -> SyscallResult {
-    let epfd = args[0] as i32;
-    let event = args[1] as *mut EpollEvent;
-    let max_event = args[2] as i32;
-    let timeout = args[3] as i32;
-    if max_event <= 0 {
+/// 等待 epoll 实例上的事件，并将其写入用户空间的数组中
+async fn epoll_wait(
+    epfd: usize,
+    events: *mut EpollEvent,
+    max_events: i32,
+    deadline: Option<TimeValue>,
+) -> SyscallResult {
+    if max_events <= 0 || max_events as usize > EP_MAX_EVENTS {
         return Err(SyscallError::EINVAL);
     }
-    let max_event = max_event as usize;
-    let process = current_process();
-    let start: VirtAddr = (event as usize).into();
-    // FIXME: this is a temporary solution
-    // the memory will out of mapped memory if the max_event is too large
-    // maybe give the max_event a limit is a better solution
-    let max_event = core::cmp::min(max_event, 400);
-    let end = start + max_event * core::mem::size_of::<EpollEvent>();
-    if process.manual_alloc_range_for_lazy(start, end).is_err() {
+    let max_events = max_events as usize;
+    let start: VirtAddr = (events as usize).into();
+    let end = start + max_events * core::mem::size_of::<EpollEvent>();
+    if current_executor().manual_alloc_range_for_lazy(start, end).await.is_err() {
         return Err(SyscallError::EFAULT);
     }
-
-    let epoll_file = {
-        let fd_table = process.fd_manager.fd_table.lock();
-        if let Some(file) = fd_table[epfd as usize].as_ref() {
-            if let Some(epoll_file) = file.as_any().downcast_ref::<EpollFile>() {
-                epoll_file.clone()
-            } else {
-                return Err(SyscallError::EBADF);
-            }
-        } else {
-            return Err(SyscallError::EBADF);
-        }
-    };
-
-    let timeout = if timeout > 0 {
-        current_ticks() as usize + timeout as usize
-    } else {
-        usize::MAX
-    };
-    let ret_events = epoll_file.epoll_wait(timeout);
-    if ret_events.is_err() {
-        return Err(SyscallError::EINTR);
-    }
-    let ret_events = ret_events.unwrap();
-    let real_len = ret_events.len().min(max_event);
-    for (i, e) in ret_events.iter().enumerate().take(real_len) {
+    let epoll = epoll_of(epfd).await?;
+    let epoll_file = (*epoll).as_any().downcast_ref::<EpollFile>().unwrap();
+    let ret_events = epoll_file.epoll_wait(max_events, deadline).await;
+    for (i, event) in ret_events.iter().enumerate() {
         unsafe {
-            *(event.add(i)) = *e;
+            *(events.add(i)) = *event;
         }
     }
-    Ok(real_len as isize)
+    Ok(ret_events.len() as isize)
 }
 
-/// Implement syscall_epoll_pwait system call
-///
-/// - Set the signal mask of the current process to the value pointed to by sigmask
-/// - Invoke syscall_epoll_wait
-/// - Restore the signal mask of the current process
-pub fn syscall_epoll_pwait(args: [usize; 6]) -> SyscallResult {
-    let sigmask = args[4] as *const usize;
+/// 将以毫秒为单位的超时时间转换为截止时间，负数表示一直等待
+fn msecs_deadline(timeout: i32) -> Option<TimeValue> {
+    (timeout >= 0).then(|| current_time() + Duration::from_millis(timeout as u64))
+}
 
-    let process = current_process();
+/// 检查 epoll_pwait 的信号掩码参数
+///
+/// 目前内核不支持信号，掩码本身被忽略
+async fn check_sigmask(sigmask: *const usize, sigsetsize: usize) -> Result<(), SyscallError> {
     if sigmask.is_null() {
-        return syscall_epoll_wait(args);
+        return Ok(());
+    }
+    if sigsetsize != core::mem::size_of::<usize>() {
+        return Err(SyscallError::EINVAL);
     }
-    if process.manual_alloc_type_for_lazy(sigmask).is_err() {
+    if current_executor().manual_alloc_type_for_lazy(sigmask).await.is_err() {
         return Err(SyscallError::EFAULT);
     }
-    let old_mask: usize = 0;
-    let temp_args = [
-        SigMaskFlag::Setmask as usize,
-        sigmask as usize,
-        (&old_mask) as *const _ as usize,
-        8,
-        0,
-        0,
-    ];
-    crate::syscall_task::syscall_sigprocmask(temp_args)?;
-    let ret = syscall_epoll_wait(args)?;
-    let temp_args = [
-        SigMaskFlag::Setmask as usize,
-        &old_mask as *const _ as usize,
-        0,
-        8,
-        0,
-        0,
-    ];
-    crate::syscall_task::syscall_sigprocmask(temp_args)?;
-    Ok(ret)
+    // TODO: 内核支持信号后，在等待期间将信号掩码设置为 *sigmask
+    Ok(())
+}
+
+/// 执行syscall_epoll_wait系统调用
+///
+/// # Arguments
+/// * `epfd`: i32, epoll文件的fd
+/// * `event`: *mut EpollEvent, 接受事件的数组
+/// * `max_event`: i32, 最大的响应事件数量,必须大于0
+/// * `timeout`: i32, 超时时间，以毫秒为单位的相对时间，为负数时一直等待
+///
+/// ret: 实际写入的响应事件数目
+#[cfg(target_arch = "x86_64")]
+pub async fn syscall_epoll_wait(args: [usize; 6]) -> SyscallResult {
+    let deadline = msecs_deadline(args[3] as i32);
+    epoll_wait(args[0], args[1] as *mut EpollEvent, args[2] as i32, deadline).await
+}
+
+/// Implement syscall_epoll_pwait system call
+///
+/// 与 epoll_wait 相同，额外的 `sigmask: *const usize` 与 `sigsetsize: usize` 参数指定等待期间的信号掩码
+pub async fn syscall_epoll_pwait(args: [usize; 6]) -> SyscallResult {
+    check_sigmask(args[4] as *const usize, args[5]).await?;
+    let deadline = msecs_deadline(args[3] as i32);
+    epoll_wait(args[0], args[1] as *mut EpollEvent, args[2] as i32, deadline).await
+}
+
+/// Implement syscall_epoll_pwait2 system call
+///
+/// 与 epoll_pwait 相同，但超时时间 `timeout: *const TimeSecs` 以纳秒精度给出，为空时一直等待
+pub async fn syscall_epoll_pwait2(args: [usize; 6]) -> SyscallResult {
+    check_sigmask(args[4] as *const usize, args[5]).await?;
+    let deadline = timespec_deadline(args[3] as *const TimeSecs).await?;
+    epoll_wait(args[0], args[1] as *mut EpollEvent, args[2] as i32, deadline).await
 }
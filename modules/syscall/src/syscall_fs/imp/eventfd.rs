@@ -1,22 +1,31 @@
-use alloc::sync::Arc;
-use axprocess::current_process;
+extern crate alloc;
 
-use crate::syscall_fs::ctype::eventfd::EventFd;
+use crate::syscall_fs::ctype::eventfd::{EventFd, EventFdFlag};
 use crate::{SyscallError, SyscallResult};
+use alloc::sync::Arc;
+use executor::{current_executor, FdFlags};
 
-pub fn syscall_eventfd(args: [usize; 6]) -> SyscallResult {
-    let initval = args[0] as u64;
-    let flags = args[1] as u32;
+use super::fd_err;
 
-    let process = current_process();
-    let mut fd_table = process.fd_manager.fd_table.lock();
-    let fd_num = if let Ok(fd) = process.alloc_fd(&mut fd_table) {
-        fd
+/// 创建一个 eventfd 对象
+///
+/// # Arguments
+/// * `initval`: u32, 计数器的初始值
+/// * `flags`: u32, EFD_SEMAPHORE、EFD_NONBLOCK 与 EFD_CLOEXEC 的组合
+pub async fn syscall_eventfd(args: [usize; 6]) -> SyscallResult {
+    let initval = args[0] as u32 as u64;
+    let Some(flags) = EventFdFlag::from_bits(args[1] as u32) else {
+        return Err(SyscallError::EINVAL);
+    };
+    let fd_flags = if flags.contains(EventFdFlag::EFD_CLOEXEC) {
+        FdFlags::CLOEXEC
     } else {
-        return Err(SyscallError::EPERM);
+        FdFlags::empty()
     };
-
-    fd_table[fd_num] = Some(Arc::new(EventFd::new(initval, flags)));
-
-    Ok(fd_num as isize)
+    let fd = current_executor()
+        .fd_manager
+        .alloc(Arc::new(EventFd::new(initval, flags)), fd_flags)
+        .await
+        .map_err(fd_err)?;
+    Ok(fd as isize)
 }
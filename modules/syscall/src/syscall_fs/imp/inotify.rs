@@ -0,0 +1,113 @@
+//! 负责 inotify 相关的系统调用
+//!
+//! 事件由 [`async_fs::api::Inotify`] 在文件系统的操作中产生，这里负责创建 inotify 文件描述符与管理监视项
+extern crate alloc;
+
+use crate::{syscall_fs::ctype::inotify::InotifyFile, SyscallError, SyscallResult};
+use alloc::sync::Arc;
+use async_fs::api::{FileIO, InotifyMask, LookupFlags, OpenFlags};
+use axerrno::AxError;
+use axlog::debug;
+use executor::{current_executor, link::AT_FDCWD, FdFlags};
+
+use super::{fd_err, solve_path_with_flags};
+
+/// Set the O_NONBLOCK file status flag on the new open file description.
+const IN_NONBLOCK: usize = 0x800;
+/// Set the close-on-exec flag on the new file descriptor.
+const IN_CLOEXEC: usize = 0x80000;
+
+/// 获取 `fd` 对应的 inotify 文件
+async fn inotify_of(fd: usize) -> Result<Arc<dyn FileIO>, SyscallError> {
+    let Some(file) = current_executor().fd_manager.get(fd).await else {
+        return Err(SyscallError::EBADF);
+    };
+    if (*file).as_any().downcast_ref::<InotifyFile>().is_none() {
+        return Err(SyscallError::EINVAL);
+    }
+    Ok(file)
+}
+
+/// 功能:创建一个 inotify 实例；
+/// # Arguments
+/// * `flags`: usize, 可设置为 IN_NONBLOCK 与 IN_CLOEXEC 的组合。
+/// # Return
+/// 成功执行,返回新的文件描述符。失败,返回-1。
+pub async fn syscall_inotify_init1(args: [usize; 6]) -> SyscallResult {
+    let flags = args[0];
+    if flags & !(IN_NONBLOCK | IN_CLOEXEC) != 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    let status = if flags & IN_NONBLOCK != 0 {
+        OpenFlags::RDONLY | OpenFlags::NON_BLOCK
+    } else {
+        OpenFlags::RDONLY
+    };
+    let fd_flags = if flags & IN_CLOEXEC != 0 {
+        FdFlags::CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
+    let fd = current_executor()
+        .fd_manager
+        .alloc(Arc::new(InotifyFile::new(status)), fd_flags)
+        .await
+        .map_err(fd_err)?;
+    Ok(fd as isize)
+}
+
+/// 功能:创建一个 inotify 实例,等价于 flags 为 0 的 inotify_init1；
+#[cfg(target_arch = "x86_64")]
+pub async fn syscall_inotify_init(_args: [usize; 6]) -> SyscallResult {
+    syscall_inotify_init1([0; 6]).await
+}
+
+/// 功能:为 inotify 实例添加或修改对某个路径的监视；
+/// # Arguments
+/// * `fd`: usize, inotify 实例的文件描述符。
+/// * `path`: *const u8, 被监视的路径,相对路径相对于当前目录。
+/// * `mask`: u32, 需要监视的事件与 IN_ONLYDIR、IN_DONT_FOLLOW、IN_MASK_ADD 等选项。
+/// # Return
+/// 成功执行,返回监视项的编号。失败,返回-1。
+pub async fn syscall_inotify_add_watch(args: [usize; 6]) -> SyscallResult {
+    let fd = args[0];
+    let path = args[1] as *const u8;
+    let mask = InotifyMask::from_bits_truncate(args[2] as u32);
+    let file = inotify_of(fd).await?;
+    let lookup = if mask.contains(InotifyMask::DONT_FOLLOW) {
+        LookupFlags::NOFOLLOW
+    } else {
+        LookupFlags::empty()
+    };
+    let path = solve_path_with_flags(AT_FDCWD, Some(path), false, lookup).await?;
+    debug!("inotify_add_watch: fd {} path {} mask {:?}", fd, path.path(), mask);
+    let attr = async_fs::api::get_attr(path.path(), lookup)
+        .await
+        .map_err(|_| SyscallError::ENOENT)?;
+    if mask.contains(InotifyMask::ONLYDIR) && !attr.is_dir() {
+        return Err(SyscallError::ENOTDIR);
+    }
+    let inotify = &(*file).as_any().downcast_ref::<InotifyFile>().unwrap().inotify;
+    match inotify.add_watch(path.path(), mask).await {
+        Ok(wd) => Ok(wd as isize),
+        Err(AxError::AlreadyExists) => Err(SyscallError::EEXIST),
+        Err(_) => Err(SyscallError::EINVAL),
+    }
+}
+
+/// 功能:移除 inotify 实例中的一个监视项；
+/// # Arguments
+/// * `fd`: usize, inotify 实例的文件描述符。
+/// * `wd`: i32, 监视项的编号。
+/// # Return
+/// 成功执行,返回0。失败,返回-1。
+pub async fn syscall_inotify_rm_watch(args: [usize; 6]) -> SyscallResult {
+    let fd = args[0];
+    let wd = args[1] as i32;
+    let file = inotify_of(fd).await?;
+    let inotify = &(*file).as_any().downcast_ref::<InotifyFile>().unwrap().inotify;
+    inotify
+        .rm_watch(wd)
+        .map(|_| 0)
+        .map_err(|_| SyscallError::EINVAL)
+}
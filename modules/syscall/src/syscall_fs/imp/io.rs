@@ -1,22 +1,25 @@
 //! 负责与 IO 相关的系统调用
 extern crate alloc;
-use crate::syscall_fs::solve_path;
+use crate::syscall_fs::{
+    check_access, check_parent_access, fd_err, init_owner, release_file_locks,
+    solve_path_with_flags,
+};
 // use crate::syscall_net::Socket;
 use crate::{SyscallError, SyscallResult};
 use alloc::sync::Arc;
 use axerrno::AxError;
-use async_fs::api::{FileIOType, OpenFlags};
+use async_fs::api::{is_symlink, FileIO, FileIOType, LookupFlags, OpenFlags, SeekFrom, VfsAccess};
 use axlog::{debug, info};
-use executor::current_executor;
-use executor::link::create_link;
+use executor::{current_executor, FdFlags};
 use alloc::string::ToString;
 
 
 use crate::syscall_fs::ctype::{
     dir::new_dir,
+    FileDesc,
     // epoll::{EpollCtl, EpollEvent, EpollEventType, EpollFile},
     file::{new_fd, new_inode},
-    // pipe::make_pipe,
+    pipe::{make_pipe, open_fifo},
 };
 /// 功能:从一个文件描述符中读取；
 /// # Arguments
@@ -45,9 +48,8 @@ pub async fn syscall_read(args: [usize; 6]) -> SyscallResult {
         Err(_) => return Err(SyscallError::EFAULT),
     };
 
-    let file = match process.fd_manager.fd_table.lock().await.get(fd) {
-        Some(Some(f)) => f.clone(),
-        _ => return Err(SyscallError::EBADF),
+    let Some(file) = process.fd_manager.get(fd).await else {
+        return Err(SyscallError::EBADF);
     };
 
     if file.get_type().await == FileIOType::DirDesc {
@@ -85,6 +87,7 @@ pub async fn syscall_read(args: [usize; 6]) -> SyscallResult {
         Ok(len) => Ok(len as isize),
         Err(AxError::WouldBlock) => Err(SyscallError::EAGAIN),
         Err(AxError::InvalidInput) => Err(SyscallError::EINVAL),
+        Err(AxError::NotConnected) => Err(SyscallError::ENOTCONN),
         Err(_) => Err(SyscallError::EPERM),
     }
 }
@@ -116,9 +119,8 @@ pub async fn syscall_write(args: [usize; 6]) -> SyscallResult {
         Err(_) => return Err(SyscallError::EFAULT),
     };
 
-    let file = match process.fd_manager.fd_table.lock().await.get(fd) {
-        Some(Some(f)) => f.clone(),
-        _ => return Err(SyscallError::EBADF),
+    let Some(file) = process.fd_manager.get(fd).await else {
+        return Err(SyscallError::EBADF);
     };
 
     if file.get_type().await == FileIOType::DirDesc {
@@ -152,10 +154,12 @@ pub async fn syscall_write(args: [usize; 6]) -> SyscallResult {
     match file.write(buf).await {
         Ok(len) => Ok(len as isize),
         // socket with send half closed
+        // 管道的读端已经关闭
         // TODO: send a SIGPIPE signal to the process
         Err(axerrno::AxError::ConnectionReset) => Err(SyscallError::EPIPE),
         Err(AxError::WouldBlock) => Err(SyscallError::EAGAIN),
         Err(AxError::InvalidInput) => Err(SyscallError::EINVAL),
+        Err(AxError::NotConnected) => Err(SyscallError::ENOTCONN),
         Err(_) => Err(SyscallError::EPERM),
     }
 }
@@ -212,54 +216,60 @@ pub async fn syscall_write(args: [usize; 6]) -> SyscallResult {
 //     Ok(write_len)
 // }
 
-// /// 功能:创建管道；
-// /// # Arguments
-// /// * `fd[2]`: *mut u32, 用于保存2个文件描述符。其中,`fd[0]`为管道的读出端,`fd[1]`为管道的写入端。
-// /// * `flags`: usize, 用于指定管道的属性。
-// /// 返回值:成功执行,返回0。失败,返回-1。
-// ///
-// /// 注意:`fd[2]`是32位数组,所以这里的 fd 是 u32 类型的指针,而不是 usize 类型的指针。
-// pub fn syscall_pipe2(args: [usize; 6]) -> SyscallResult {
-//     let fd = args[0] as *mut u32;
-//     let flags = args[1] as u32;
-//     axlog::info!("Into syscall_pipe2. fd: {} flags: {}", fd as usize, flags);
-//     let process = current_executor();
-//     if process.manual_alloc_for_lazy((fd as usize).into()).is_err() {
-//         return Err(SyscallError::EINVAL);
-//     }
-//     let (read, write) = make_pipe(OpenFlags::from_bits_truncate(flags));
-//     let mut fd_table = process.fd_manager.fd_table.lock();
-//     let fd_num = if let Ok(fd) = process.alloc_fd(&mut fd_table) {
-//         fd
-//     } else {
-//         return Err(SyscallError::EPERM);
-//     };
-//     fd_table[fd_num] = Some(read);
-//     let fd_num2 = if let Ok(fd) = process.alloc_fd(&mut fd_table) {
-//         fd
-//     } else {
-//         return Err(SyscallError::EPERM);
-//     };
-//     fd_table[fd_num2] = Some(write);
-//     info!("read end: {} write: end: {}", fd_num, fd_num2);
-//     unsafe {
-//         core::ptr::write(fd, fd_num as u32);
-//         core::ptr::write(fd.offset(1), fd_num2 as u32);
-//     }
-//     Ok(0)
-// }
+/// 功能:创建管道；
+/// # Arguments
+/// * `fd[2]`: *mut u32, 用于保存2个文件描述符。其中,`fd[0]`为管道的读出端,`fd[1]`为管道的写入端。
+/// * `flags`: usize, O_NONBLOCK 与 O_CLOEXEC 的组合。
+/// 返回值:成功执行,返回0。失败,返回-1。
+///
+/// 注意:`fd[2]`是32位数组,所以这里的 fd 是 u32 类型的指针,而不是 usize 类型的指针。
+pub async fn syscall_pipe2(args: [usize; 6]) -> SyscallResult {
+    let fd = args[0] as *mut u32;
+    let flags = args[1] as u32;
+    info!("Into syscall_pipe2. fd: {} flags: {}", fd as usize, flags);
+    let Some(flags) = OpenFlags::from_bits(flags) else {
+        return Err(SyscallError::EINVAL);
+    };
+    if !(OpenFlags::NON_BLOCK | OpenFlags::CLOEXEC).contains(flags) {
+        return Err(SyscallError::EINVAL);
+    }
+    let process = current_executor();
+    if fd.is_null()
+        || process
+            .manual_alloc_range_for_lazy((fd as usize).into(), (fd as usize + 8).into())
+            .await
+            .is_err()
+    {
+        return Err(SyscallError::EFAULT);
+    }
+    let fd_flags = if flags.contains(OpenFlags::CLOEXEC) {
+        FdFlags::CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
+    let (read, write) = make_pipe(flags);
+    let read_fd = process.fd_manager.alloc(read, fd_flags).await.map_err(fd_err)?;
+    let write_fd = match process.fd_manager.alloc(write, fd_flags).await {
+        Ok(fd) => fd,
+        Err(err) => {
+            let _ = process.fd_manager.close(read_fd).await;
+            return Err(fd_err(err));
+        }
+    };
+    info!("read end: {} write: end: {}", read_fd, write_fd);
+    unsafe {
+        core::ptr::write(fd, read_fd as u32);
+        core::ptr::write(fd.offset(1), write_fd as u32);
+    }
+    Ok(0)
+}
 
-// /// 功能:创建管道；
-// /// # Arguments
-// /// * `fd[2]`: *mut u32, 用于保存2个文件描述符。其中,`fd[0]`为管道的读出端,`fd[1]`为管道的写入端。
-// /// 返回值:成功执行,返回0。失败,返回-1。
-// ///
-// /// 注意:`fd[2]`是32位数组,所以这里的 fd 是 u32 类型的指针,而不是 usize 类型的指针。
-// #[cfg(target_arch = "x86_64")]
-// pub fn syscall_pipe(mut args: [usize; 6]) -> SyscallResult {
-//     args[1] = 0;
-//     syscall_pipe2(args)
-// }
+/// 功能:创建管道,等价于 flags 为 0 的 pipe2；
+#[cfg(target_arch = "x86_64")]
+pub async fn syscall_pipe(mut args: [usize; 6]) -> SyscallResult {
+    args[1] = 0;
+    syscall_pipe2(args).await
+}
 
 /// 功能:复制文件描述符；
 /// # Arguments
@@ -268,65 +278,46 @@ pub async fn syscall_write(args: [usize; 6]) -> SyscallResult {
 pub async fn syscall_dup(args: [usize; 6]) -> SyscallResult {
     let fd = args[0];
     let process = current_executor();
-    let mut fd_table = process.fd_manager.fd_table.lock().await;
-    if fd >= fd_table.len() {
-        debug!("fd {} is out of range", fd);
-        return Err(SyscallError::EBADF);
+    // 新的文件描述符不继承 FD_CLOEXEC
+    let new_fd = process
+        .fd_manager
+        .dup(fd, 0, FdFlags::empty())
+        .await
+        .map_err(fd_err)?;
+    Ok(new_fd as isize)
+}
+
+/// 功能: 将一个文件从一个文件描述符复制到另一个文件描述符
+/// # Arguments
+/// * fd: usize, 原文件所在的文件描述符
+/// * new_fd: usize, 新的文件描述符
+/// 返回值:成功执行,返回新的文件描述符。失败,返回-1。
+#[cfg(target_arch = "x86_64")]
+pub async fn syscall_dup2(args: [usize; 6]) -> SyscallResult {
+    let fd = args[0];
+    let new_fd = args[1];
+    let process = current_executor();
+    if fd == new_fd {
+        // 与 dup3 不同,fd 与 new_fd 相同时只检查 fd 是否有效
+        return match process.fd_manager.get(fd).await {
+            Some(_) => Ok(new_fd as isize),
+            None => Err(SyscallError::EBADF),
+        };
     }
-    if fd_table[fd].is_none() {
-        debug!("fd {} is a closed fd", fd);
-        return Err(SyscallError::EBADF);
+    info!("dup2 fd {} to new fd {}", fd, new_fd);
+    // 就算new_fd已经被打开了,也可以被重新替代掉
+    let replaced = process.fd_manager.get(new_fd).await;
+    process
+        .fd_manager
+        .dup2(fd, new_fd, FdFlags::empty())
+        .await
+        .map_err(fd_err)?;
+    if let Some(file) = replaced {
+        release_file_locks(file).await;
     }
-
-    let new_fd = if let Ok(fd) = process.alloc_fd(&mut fd_table) {
-        fd
-    } else {
-        // 文件描述符达到上限了
-        return Err(SyscallError::EMFILE);
-    };
-    fd_table[new_fd] = fd_table[fd].clone();
-
     Ok(new_fd as isize)
 }
 
-// /// 功能: 将一个文件从一个文件描述符复制到另一个文件描述符
-// /// # Arguments
-// /// * fd: usize, 原文件所在的文件描述符
-// /// * new_fd: usize, 新的文件描述符
-// /// 返回值:成功执行,返回新的文件描述符。失败,返回-1。
-// #[cfg(target_arch = "x86_64")]
-// pub fn syscall_dup2(args: [usize; 6]) -> SyscallResult {
-//     let fd = args[0];
-//     let new_fd = args[1];
-//     let process = current_process();
-//     let mut fd_table = process.fd_manager.fd_table.lock();
-//     if fd >= fd_table.len() {
-//         debug!("fd {} is out of range", fd);
-//         return Err(SyscallError::EPERM);
-//     }
-//     if fd_table[fd].is_none() {
-//         debug!("fd {} is not opened", fd);
-//         return Err(SyscallError::EPERM);
-//     }
-//     if new_fd >= fd_table.len() {
-//         if new_fd >= (process.fd_manager.get_limit() as usize) {
-//             // 超出了资源限制
-//             return Err(SyscallError::EBADF);
-//         }
-//         for _i in fd_table.len()..new_fd + 1 {
-//             fd_table.push(None);
-//         }
-//     }
-//     // if process_inner.fd_manager.fd_table[new_fd].is_some() {
-//     //     debug!("new_fd {} is already opened", new_fd);
-//     //     return ErrorNo::EINVAL as isize;
-//     // }
-//     info!("dup2 fd {} to new fd {}", fd, new_fd);
-//     // 就算new_fd已经被打开了,也可以被重新替代掉
-//     fd_table[new_fd] = fd_table[fd].clone();
-//     Ok(new_fd as isize)
-// }
-
 /// 功能:复制文件描述符,并指定了新的文件描述符；
 /// # Arguments
 /// * fd: usize, 原文件所在的文件描述符
@@ -337,33 +328,28 @@ pub async fn syscall_dup3(args: [usize; 6]) -> SyscallResult {
     let fd = args[0];
     let new_fd = args[1];
     let flags = args[2];
-    let process = current_executor();
-    let mut fd_table = process.fd_manager.fd_table.lock().await;
-    if fd >= fd_table.len() {
-        debug!("fd {} is out of range", fd);
-        return Err(SyscallError::EPERM);
-    }
-    if fd_table[fd].is_none() {
-        debug!("fd {} is not opened", fd);
-        return Err(SyscallError::EPERM);
+    if flags as u32 & !crate::ctypes::O_CLOEXEC != 0 {
+        return Err(SyscallError::EINVAL);
     }
     if fd == new_fd {
         debug!("oldfd is equal to newfd");
         return Err(SyscallError::EINVAL);
     }
-    if new_fd >= fd_table.len() {
-        if new_fd >= (process.fd_manager.get_limit() as usize) {
-            // 超出了资源限制
-            return Err(SyscallError::EBADF);
-        }
-        for _i in fd_table.len()..new_fd + 1 {
-            fd_table.push(None);
-        }
-    }
+    let fd_flags = if flags as u32 & crate::ctypes::O_CLOEXEC != 0 {
+        FdFlags::CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
     info!("dup3 fd {} to new fd {} with flags {}", fd, new_fd, flags);
-    fd_table[new_fd] = fd_table[fd].clone();
-    if flags as u32 & crate::ctypes::O_CLOEXEC != 0 {
-        let _ = fd_table[new_fd].as_mut().unwrap().set_close_on_exec(true).await;
+    let process = current_executor();
+    let replaced = process.fd_manager.get(new_fd).await;
+    process
+        .fd_manager
+        .dup2(fd, new_fd, fd_flags)
+        .await
+        .map_err(fd_err)?;
+    if let Some(file) = replaced {
+        release_file_locks(file).await;
     }
     Ok(new_fd as isize)
 }
@@ -382,26 +368,77 @@ pub async fn syscall_openat(args: [usize; 6]) -> SyscallResult {
     let fd = args[0];
     let path = args[1] as *const u8;
     let flags = args[2];
-    let _mode = args[3] as u8;
+    let mode = args[3] as u32;
     let force_dir = OpenFlags::from(flags).is_dir();
-    let path = solve_path(fd, Some(path), force_dir).await?;
+    let lookup_flags = if OpenFlags::from(flags).contains(OpenFlags::NOFOLLOW) {
+        LookupFlags::NOFOLLOW
+    } else {
+        LookupFlags::empty()
+    };
+    let path = solve_path_with_flags(fd, Some(path), force_dir, lookup_flags).await?;
+    if lookup_flags.contains(LookupFlags::NOFOLLOW) && is_symlink(path.path()).await {
+        // O_NOFOLLOW 时最后一个分量不能是符号链接
+        return Err(SyscallError::ELOOP);
+    }
+    let open_flags = OpenFlags::from(flags);
+    let existed = async_fs::api::path_exists(path.path()).await;
+    if existed {
+        let mut access = VfsAccess::empty();
+        if open_flags.readable() {
+            access |= VfsAccess::READ;
+        }
+        if open_flags.writable() || open_flags.contains(OpenFlags::TRUNC) {
+            access |= VfsAccess::WRITE;
+        }
+        check_access(path.path(), access).await?;
+    } else if open_flags.creatable() || force_dir {
+        check_parent_access(&path).await?;
+    }
     let process = current_executor();
-    let mut fd_table = process.fd_manager.fd_table.lock().await;
-    let fd_num: usize = if let Ok(fd) = process.alloc_fd(&mut fd_table) {
-        fd
+    // O_CLOEXEC 是文件描述符标志,不属于共享的打开文件描述
+    let fd_flags = if open_flags.contains(OpenFlags::CLOEXEC) {
+        FdFlags::CLOEXEC
     } else {
-        return Err(SyscallError::EMFILE);
+        FdFlags::empty()
     };
-    debug!("allocated fd_num: {}", fd_num);
     // 分配 inode
     new_inode(path.path().to_string()).await.unwrap();
+    // 命名管道的数据不在文件系统中，打开它的文件共享同一个管道
+    if existed {
+        let is_fifo = async_fs::api::get_attr(path.path(), LookupFlags::empty())
+            .await
+            .is_ok_and(|attr| attr.file_type().is_fifo());
+        if is_fifo {
+            if open_flags.creatable() && open_flags.new_creatable() {
+                return Err(SyscallError::EEXIST);
+            }
+            let node = async_fs::api::lookup(path.path())
+                .await
+                .map_err(|_| SyscallError::ENOENT)?;
+            let pipe = open_fifo(node, path.path(), open_flags).await?;
+            let fd_num = process
+                .fd_manager
+                .alloc(pipe, fd_flags)
+                .await
+                .map_err(fd_err)?;
+            return Ok(fd_num as isize);
+        }
+    }
     // 如果是DIR
     info!("path: {:?}", path.path());
     if path.is_dir() {
         debug!("open dir");
         if let Ok(dir) = new_dir(path.path().to_string(), flags.into()).await {
             debug!("new dir_desc successfully allocated: {}", path.path());
-            fd_table[fd_num] = Some(Arc::new(dir));
+            if !existed {
+                init_owner(path.path(), mode).await;
+            }
+            let fd_num = process
+                .fd_manager
+                .alloc(Arc::new(dir), fd_flags)
+                .await
+                .map_err(fd_err)?;
+            debug!("allocated fd_num: {}", fd_num);
             Ok(fd_num as isize)
         } else {
             debug!("open dir failed");
@@ -413,8 +450,15 @@ pub async fn syscall_openat(args: [usize; 6]) -> SyscallResult {
         debug!("open file");
         if let Ok(file) = new_fd(path.path().to_string(), flags.into()).await {
             debug!("new file_desc successfully allocated");
-            fd_table[fd_num] = Some(Arc::new(file));
-            let _ = create_link(&path, &path); // 不需要检查是否成功,因为如果成功,说明是新建的文件,如果失败,说明已经存在了
+            if !existed {
+                init_owner(path.path(), mode).await;
+            }
+            let fd_num = process
+                .fd_manager
+                .alloc(Arc::new(file), fd_flags)
+                .await
+                .map_err(fd_err)?;
+            debug!("allocated fd_num: {}", fd_num);
             Ok(fd_num as isize)
         } else {
             debug!("open file failed");
@@ -460,35 +504,11 @@ pub async fn syscall_close(args: [usize; 6]) -> SyscallResult {
     info!("Into syscall_close. fd: {}", fd);
 
     let process = current_executor();
-    let mut fd_table = process.fd_manager.fd_table.lock().await;
-    if fd >= fd_table.len() {
-        debug!("fd {} is out of range", fd);
-        return Err(SyscallError::EPERM);
-    }
-    // if fd == 3 {
-    //     debug!("fd {} is reserved for cwd", fd);
-    //     return -1;
-    // }
-    if fd_table[fd].is_none() {
+    let Ok(file) = process.fd_manager.close(fd).await else {
         debug!("fd {} is none", fd);
-        return Err(SyscallError::EPERM);
-    }
-    // // let file = process_inner.fd_manager.fd_table[fd].unwrap();
-    // for i in 0..fd_table.len() {
-    //     if let Some(file) = fd_table[i].as_ref() {
-    //         if let Some(epoll_file) = file.as_any().downcast_ref::<EpollFile>() {
-    //             if epoll_file.contains(fd as i32) {
-    //                 let ev = EpollEvent {
-    //                     event_type: EpollEventType::EPOLLMSG,
-    //                     data: 0,
-    //                 };
-    //                 epoll_file.epoll_ctl(EpollCtl::DEL, fd as i32, ev)?;
-    //             }
-    //         }
-    //     }
-    // }
-
-    fd_table[fd] = None;
+        return Err(SyscallError::EBADF);
+    };
+    release_file_locks(file).await;
     // for i in 0..process_inner.fd_table.len() {
     //     if let Some(file) = process_inner.fd_table[i].as_ref() {
     //         debug!("fd: {} has file", i);
@@ -498,6 +518,34 @@ pub async fn syscall_close(args: [usize; 6]) -> SyscallResult {
     Ok(0)
 }
 
+/// Unshare the file descriptor table before closing the file descriptors.
+const CLOSE_RANGE_UNSHARE: usize = 1 << 1;
+/// Set FD_CLOEXEC on the file descriptors instead of closing them.
+const CLOSE_RANGE_CLOEXEC: usize = 1 << 2;
+
+/// 功能:关闭一个范围内的所有文件描述符；
+/// # Arguments
+/// * `first`: usize, 要关闭的第一个文件描述符。
+/// * `last`: usize, 要关闭的最后一个文件描述符,可以超出已打开的范围。
+/// * `flags`: usize, 可设置为0、CLOSE_RANGE_UNSHARE或CLOSE_RANGE_CLOEXEC。
+/// 返回值:成功执行,返回0。失败,返回-1。
+pub async fn syscall_close_range(args: [usize; 6]) -> SyscallResult {
+    let first = args[0] as u32 as usize;
+    let last = args[1] as u32 as usize;
+    let flags = args[2];
+    if flags & !(CLOSE_RANGE_UNSHARE | CLOSE_RANGE_CLOEXEC) != 0 || first > last {
+        return Err(SyscallError::EINVAL);
+    }
+    info!("close_range: [{}, {}] flags: {:#x}", first, last, flags);
+    // 文件描述符表不在进程间共享,CLOSE_RANGE_UNSHARE 无需额外处理
+    let process = current_executor();
+    let cloexec = flags & CLOSE_RANGE_CLOEXEC != 0;
+    for file in process.fd_manager.close_range(first, last, cloexec).await {
+        release_file_locks(file).await;
+    }
+    Ok(0)
+}
+
 // /// 67
 // /// pread64
 // /// 从文件的指定位置读取数据,并且不改变文件的读写指针
@@ -555,291 +603,202 @@ pub async fn syscall_close(args: [usize; 6]) -> SyscallResult {
 //         .unwrap_or_else(|_| Err(SyscallError::EINVAL))
 // }
 
-// /// 71
-// /// sendfile64
-// /// 将一个文件的内容发送到另一个文件中
-// /// 如果offset为NULL,则从当前读写指针开始读取,读取完毕后会更新读写指针
-// /// 如果offset不为NULL,则从offset指定的位置开始读取,读取完毕后不会更新读写指针,但是会更新offset的值
-// /// # Arguments
-// /// * `out_fd`: usize
-// /// * `in_fd`: usize
-// /// * `offset`: *mut usize
-// /// * `count`: usize
-// pub fn syscall_sendfile64(args: [usize; 6]) -> SyscallResult {
-//     let out_fd = args[0];
-//     let in_fd = args[1];
-//     let offset = args[2] as *mut usize;
-//     let count = args[3];
-//     info!("send from {} to {}, count: {}", in_fd, out_fd, count);
-//     let process = current_executor();
-//     let out_file = process.fd_manager.fd_table.lock()[out_fd].clone().unwrap();
-//     let in_file = process.fd_manager.fd_table.lock()[in_fd].clone().unwrap();
-//     let old_in_offset = in_file.seek(SeekFrom::Current(0)).unwrap();
-
-//     let mut buf = vec![0u8; count];
-//     if !offset.is_null() {
-//         // 如果offset不为NULL,则从offset指定的位置开始读取
-//         let in_offset = unsafe { *offset };
-//         in_file.seek(SeekFrom::Start(in_offset as u64)).unwrap();
-//         let ret = in_file.read(buf.as_mut_slice());
-//         unsafe { *offset = in_offset + ret.unwrap() };
-//         in_file.seek(SeekFrom::Start(old_in_offset)).unwrap();
-//         let buf = buf[..ret.unwrap()].to_vec();
-//         Ok(out_file.write(buf.as_slice()).unwrap() as isize)
-//     } else {
-//         // 如果offset为NULL,则从当前读写指针开始读取
-//         let ret = in_file.read(buf.as_mut_slice());
-//         info!("in fd: {}, count: {}", in_fd, count);
-//         let buf = buf[..ret.unwrap()].to_vec();
-//         info!("read len: {}", buf.len());
-//         info!("write len: {}", buf.as_slice().len());
-//         Ok(out_file.write(buf.as_slice()).unwrap() as isize)
-//     }
-// }
-
-// /// 78
-// /// readlinkat
-// /// 读取符号链接文件的内容
-// /// * 如果buf为NULL,则返回符号链接文件的长度
-// /// * 如果buf不为NULL,则将符号链接文件的内容写入buf中
-// /// 如果写入的内容超出了buf_size则直接截断
-// /// # Arguments
-// /// * `dir_fd`: usize
-// /// * `path`: *const u8
-// /// * `buf`: *mut u8
-// /// * `bufsiz`: usize
-// pub fn syscall_readlinkat(args: [usize; 6]) -> SyscallResult {
-//     let dir_fd = args[0];
-//     let path = args[1] as *const u8;
-//     let buf = args[2] as *mut u8;
-//     let bufsiz = args[3];
-//     let process = current_executor();
-//     if process
-//         .manual_alloc_for_lazy((path as usize).into())
-//         .is_err()
-//     {
-//         return Err(SyscallError::EFAULT);
-//     }
-//     if !buf.is_null()
-//         && process
-//             .manual_alloc_for_lazy((buf as usize).into())
-//             .is_err()
-//     {
-//         return Err(SyscallError::EFAULT);
-//     }
-
-//     let path = solve_path(dir_fd, Some(path), false)?;
-
-//     axlog::info!("read link at: {}", path.path());
-//     // 获取进程自身的符号链接信息
-//     if path.path() == "/proc/self/exe" {
-//         // 获取该进程符号链接对应的真正地址
-//         let file_real_path = process.get_file_path();
-//         let len = bufsiz.min(file_real_path.len());
-//         let slice = unsafe { core::slice::from_raw_parts_mut(buf, len) };
-//         slice.copy_from_slice(&file_real_path.as_bytes()[..len]);
-
-//         return Ok(file_real_path.len() as isize);
-//     }
-
-//     if *path.path() != real_path(&(path.path().to_string())) {
-//         // 说明链接存在
-//         let path = path.path();
-//         let len = bufsiz.min(path.len());
-//         let slice = unsafe { core::slice::from_raw_parts_mut(buf, len) };
-//         slice.copy_from_slice(&path.as_bytes()[..len]);
-//         return Ok(path.len() as isize);
-//     }
-//     Err(SyscallError::EINVAL)
-// }
-
-// /// readlinkat
-// /// 读取符号链接文件的内容
-// /// 如果buf为NULL,则返回符号链接文件的长度
-// /// 如果buf不为NULL,则将符号链接文件的内容写入buf中
-// /// 如果写入的内容超出了buf_size则直接截断
-// /// # Arguments
-// /// * `path`: *const u8
-// /// * `buf`: *mut u8
-// /// * `bufsiz`: usize
-// #[cfg(target_arch = "x86_64")]
-// pub fn syscall_readlink(args: [usize; 6]) -> SyscallResult {
-//     use axprocess::link::AT_FDCWD;
-
-//     let temp_args = [AT_FDCWD, args[0], args[1], args[2], 0, 0];
-//     syscall_readlinkat(temp_args)
-// }
-
-// /// 62
-// /// 移动文件描述符的读写指针
-// /// # Arguments
-// /// * `fd`: usize
-// /// * `offset`: isize
-// /// * `whence`: usize
-// pub fn syscall_lseek(args: [usize; 6]) -> SyscallResult {
-//     let fd = args[0];
-//     let offset = args[1] as isize;
-//     let whence = args[2];
-//     let process = current_executor();
-//     info!("fd: {} offset: {} whence: {}", fd, offset, whence);
-//     if fd >= process.fd_manager.fd_table.lock().len() || fd < 3 {
-//         debug!("fd {} is out of range", fd);
-//         return Err(SyscallError::EBADF);
-//     }
-//     let fd_table = process.fd_manager.fd_table.lock();
-//     if let Some(file) = fd_table[fd].as_ref() {
-//         if file.get_type() == FileIOType::DirDesc {
-//             debug!("fd is a dir");
-//             return Err(SyscallError::EISDIR);
-//         }
-//         let ans = if whence == 0 {
-//             // 即SEEK_SET
-//             file.seek(SeekFrom::Start(offset as u64))
-//         } else if whence == 1 {
-//             // 即SEEK_CUR
-//             file.seek(SeekFrom::Current(offset as i64))
-//         } else if whence == 2 {
-//             // 即SEEK_END
-//             file.seek(SeekFrom::End(offset as i64))
-//         } else {
-//             return Err(SyscallError::EINVAL);
-//         };
-//         if let Ok(now_offset) = ans {
-//             Ok(now_offset as isize)
-//         } else {
-//             Err(SyscallError::EINVAL)
-//         }
-//     } else {
-//         debug!("fd {} is none", fd);
-//         Err(SyscallError::EBADF)
-//     }
-// }
+/// Seek to the given offset.
+const SEEK_SET: usize = 0;
+/// Seek relative to the current offset.
+const SEEK_CUR: usize = 1;
+/// Seek relative to the end of the file.
+const SEEK_END: usize = 2;
+/// Seek to the next data region at or after the given offset.
+const SEEK_DATA: usize = 3;
+/// Seek to the next hole at or after the given offset.
+const SEEK_HOLE: usize = 4;
+
+/// 功能:移动文件描述符的读写指针；
+/// # Arguments
+/// * `fd`: usize, 文件描述符。
+/// * `offset`: isize, 偏移量。
+/// * `whence`: usize, SEEK_SET、SEEK_CUR、SEEK_END,或者用于稀疏文件的 SEEK_DATA 与 SEEK_HOLE。
+/// 返回值:成功执行,返回新的读写指针。失败,返回-1。
+pub async fn syscall_lseek(args: [usize; 6]) -> SyscallResult {
+    let fd = args[0];
+    let offset = args[1] as isize;
+    let whence = args[2];
+    info!("lseek: fd: {} offset: {} whence: {}", fd, offset, whence);
+    let Some(file) = current_executor().fd_manager.get(fd).await else {
+        return Err(SyscallError::EBADF);
+    };
+    match file.get_type().await {
+        FileIOType::DirDesc => return Err(SyscallError::EISDIR),
+        FileIOType::Pipe | FileIOType::Socket => return Err(SyscallError::ESPIPE),
+        _ => {}
+    }
+    let pos = match whence {
+        SEEK_SET => SeekFrom::Start(offset as u64),
+        SEEK_CUR => SeekFrom::Current(offset as i64),
+        SEEK_END => SeekFrom::End(offset as i64),
+        SEEK_DATA | SEEK_HOLE => {
+            let Some(desc) = (*file).as_any().downcast_ref::<FileDesc>() else {
+                return Err(SyscallError::EINVAL);
+            };
+            if offset < 0 {
+                return Err(SyscallError::ENXIO);
+            }
+            let found = desc
+                .file
+                .lock()
+                .await
+                .seek_data(offset as u64, whence == SEEK_HOLE)
+                .await
+                .map_err(|_| SyscallError::EINVAL)?;
+            // 偏移超出文件末尾,或其后没有数据
+            return found.map(|pos| pos as isize).ok_or(SyscallError::ENXIO);
+        }
+        _ => return Err(SyscallError::EINVAL),
+    };
+    if matches!(pos, SeekFrom::Start(_)) && offset < 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    match file.seek(pos).await {
+        Ok(now_offset) => Ok(now_offset as isize),
+        Err(AxError::Unsupported) => Err(SyscallError::ESPIPE),
+        Err(_) => Err(SyscallError::EINVAL),
+    }
+}
 
 // /// 82
 // /// 写回硬盘
-// #[allow(unused)]
-// /// # Arguments
-// /// * `fd`: usize
-// pub fn syscall_fsync(args: [usize; 6]) -> SyscallResult {
-//     let fd = args[0];
-//     let process = current_executor();
-//     if fd >= process.fd_manager.fd_table.lock().len() || fd < 3 {
-//         debug!("fd {} is out of range", fd);
-//         return Err(SyscallError::EBADF);
-//     }
-//     let fd_table = process.fd_manager.fd_table.lock();
-//     if let Some(file) = fd_table[fd].clone() {
-//         // if file.flush().is_err() {}
-//         Ok(0)
-//     } else {
-//         debug!("fd {} is none", fd);
-//         Err(SyscallError::EBADF)
-//     }
-// }
-
-// /**
-// 该系统调用应复制文件描述符 fd_in 中的至多 len 个字节到文件描述符 fd_out 中。
-// 若 off_in 为 NULL,则复制时应从文件描述符 fd_in 本身的文件偏移处开始读取,并将其文件偏移增加成功复制的字节数；否则,从 *off_in 指定的文件偏移处开始读取,不改变 fd_in 的文件偏移,而是将 *off_in 增加成功复制的字节数。
-// 参数 off_out 的行为类似:若 off_out 为 NULL,则复制时从文件描述符 fd_out 本身的文件偏移处开始写入,并将其文件偏移增加成功复制的字节数；否则,从 *off_out 指定的文件偏移处开始写入,不改变 fd_out 的文件偏移,而是将 *off_out 增加成功复制的字节数。
-// 该系统调用的返回值为成功复制的字节数,出现错误时返回负值。若读取 fd_in 时的文件偏移超过其大小,则直接返回 0,不进行复制。
-// 本题中,fd_in 和 fd_out 总指向文件系统中两个不同的普通文件；flags 总为 0,没有实际作用。
-//  */
-// /// # Arguments
-// /// * `fd_in`: usize
-// /// * `off_in`: *mut usize
-// /// * `fd_out`: usize
-// /// * `off_out`: *mut usize
-// /// * `len`: usize
-// /// * `flags`: usize
-// pub fn syscall_copyfilerange(args: [usize; 6]) -> SyscallResult {
-//     let fd_in = args[0];
-//     let off_in = args[1] as *mut usize;
-//     let fd_out = args[2];
-//     let off_out = args[3] as *mut usize;
-//     let len = args[4];
-//     let flags = args[5];
-//     let in_offset = if off_in.is_null() {
-//         -1
-//     } else {
-//         unsafe { *off_in as isize }
-//     };
-//     let out_offset = if off_out.is_null() {
-//         -1
-//     } else {
-//         unsafe { *off_out as isize }
-//     };
-//     if len == 0 {
-//         return Ok(0);
-//     }
-//     info!(
-//         "copyfilerange: fd_in: {}, fd_out: {}, off_in: {}, off_out: {}, len: {}, flags: {}",
-//         fd_in, fd_out, in_offset, out_offset, len, flags
-//     );
-//     let process = current_executor();
-//     let fd_table = process.fd_manager.fd_table.lock();
-//     let out_file = fd_table[fd_out].clone().unwrap();
-//     let in_file = fd_table[fd_in].clone().unwrap();
-//     let old_in_offset = in_file.seek(SeekFrom::Current(0)).unwrap();
-//     let old_out_offset = out_file.seek(SeekFrom::Current(0)).unwrap();
-
-//     // if in_file.lock().get_stat().unwrap().st_size < (in_offset as u64) + len as u64 {
-//     //     return 0;
-//     // }
-
-//     // set offset
-//     if !off_in.is_null() {
-//         in_file.seek(SeekFrom::Start(in_offset as u64)).unwrap();
-//     }
-
-//     if !off_out.is_null() {
-//         out_file.seek(SeekFrom::Start(out_offset as u64)).unwrap();
-//     }
-
-//     // copy
-//     let mut buf = vec![0; len];
-//     let read_len = in_file.read(buf.as_mut_slice()).unwrap();
-//     // debug!("copy content: {:?}", &buf[..read_len]);
+/// 功能:将文件在内存中被修改的数据同步到存储设备上；
+/// # Arguments
+/// * `fd`: usize, 要同步的文件描述符，只读打开的文件同样可以同步。
+/// 返回值:成功执行,返回0。管道、socket 等不支持同步的文件返回 EINVAL。
+pub async fn syscall_fsync(args: [usize; 6]) -> SyscallResult {
+    let fd = args[0];
+    let process = current_executor();
+    let Some(file) = process.fd_manager.get(fd).await else {
+        debug!("fd {} is none", fd);
+        return Err(SyscallError::EBADF);
+    };
+    match file.get_type().await {
+        FileIOType::FileDesc => {}
+        // 目录的修改在操作时已经交给文件系统，没有单独需要同步的数据
+        FileIOType::DirDesc => return Ok(0),
+        _ => return Err(SyscallError::EINVAL),
+    }
+    match file.flush().await {
+        Ok(()) => Ok(0),
+        Err(AxError::InvalidInput | AxError::Unsupported) => Err(SyscallError::EINVAL),
+        Err(_) => Err(SyscallError::EIO),
+    }
+}
 
-//     let write_len = out_file.write(&buf[..read_len]).unwrap();
-//     // assert_eq!(read_len, write_len);    // tmp
+/// 功能:将所有块设备缓存中的脏块写回存储设备；
+/// 返回值:总是成功,返回0。
+pub async fn syscall_sync() -> SyscallResult {
+    if let Err(e) = async_fs::api::sync().await {
+        axlog::warn!("sync failed: {:?}", e);
+    }
+    Ok(0)
+}
 
-//     // set offset | modify off_in & off_out
-//     if !off_in.is_null() {
-//         in_file.seek(SeekFrom::Start(old_in_offset)).unwrap();
-//         unsafe {
-//             *off_in += read_len;
-//         }
-//     }
-//     if !off_out.is_null() {
-//         out_file.seek(SeekFrom::Start(old_out_offset)).unwrap();
-//         unsafe {
-//             *off_out += write_len;
-//         }
-//     }
+/// 获取 `fd` 对应的可写的普通文件,用于 ftruncate 与 fallocate
+async fn writable_file_desc(fd: usize) -> Result<Arc<dyn FileIO>, SyscallError> {
+    let Some(file) = current_executor().fd_manager.get(fd).await else {
+        return Err(SyscallError::EBADF);
+    };
+    match file.get_type().await {
+        FileIOType::FileDesc => {}
+        FileIOType::DirDesc => return Err(SyscallError::EISDIR),
+        FileIOType::Pipe => return Err(SyscallError::ESPIPE),
+        _ => return Err(SyscallError::ENODEV),
+    }
+    if !file.writable().await {
+        return Err(SyscallError::EBADF);
+    }
+    Ok(file)
+}
 
-//     Ok(write_len as isize)
-// }
+/// 功能:将文件截断或扩展到指定的长度,扩展出的部分读出为零；
+/// # Arguments
+/// * `fd`: usize, 以可写方式打开的文件描述符。
+/// * `len`: usize, 新的长度。
+/// 返回值:成功执行,返回0。失败,返回-1。
+pub async fn syscall_ftruncate64(args: [usize; 6]) -> SyscallResult {
+    let fd = args[0];
+    let len = args[1] as isize;
+    info!("ftruncate: fd: {}, len: {}", fd, len);
+    if len < 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    // 不是普通文件时返回 EINVAL
+    let file = writable_file_desc(fd).await.map_err(|err| match err {
+        SyscallError::ESPIPE | SyscallError::ENODEV => SyscallError::EINVAL,
+        err => err,
+    })?;
+    match file.truncate(len as usize).await {
+        Ok(()) => Ok(0),
+        Err(AxError::StorageFull) => Err(SyscallError::ENOSPC),
+        Err(AxError::PermissionDenied) => Err(SyscallError::EPERM),
+        Err(_) => Err(SyscallError::EINVAL),
+    }
+}
 
-// /// # Arguments
-// /// * `fd`: usize
-// /// * `len`: usize
-// pub fn syscall_ftruncate64(args: [usize; 6]) -> SyscallResult {
-//     let fd = args[0];
-//     let len = args[1];
-//     let process = current_executor();
-//     info!("fd: {}, len: {}", fd, len);
-//     let fd_table = process.fd_manager.fd_table.lock();
-//     if fd >= fd_table.len() {
-//         return Err(SyscallError::EINVAL);
-//     }
-//     if fd_table[fd].is_none() {
-//         return Err(SyscallError::EINVAL);
-//     }
+/// Keep the file size unchanged.
+const FALLOC_FL_KEEP_SIZE: usize = 0x01;
+/// Deallocate the range, which is read as zeros afterwards.
+const FALLOC_FL_PUNCH_HOLE: usize = 0x02;
+/// Zero the range and allocate its disk space.
+const FALLOC_FL_ZERO_RANGE: usize = 0x10;
 
-//     if let Some(file) = fd_table[fd].as_ref() {
-//         if file.truncate(len).is_err() {
-//             return Err(SyscallError::EINVAL);
-//         }
-//     }
-//     Ok(0)
-// }
+/// 功能:为文件预分配空间,或者在文件中打洞、清零一段范围；
+/// # Arguments
+/// * `fd`: usize, 以可写方式打开的文件描述符。
+/// * `mode`: usize, 为 0 时预分配并按需扩展文件；可以是 FALLOC_FL_KEEP_SIZE、
+///   FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE,或 FALLOC_FL_ZERO_RANGE 与 FALLOC_FL_KEEP_SIZE 的组合。
+/// * `offset`: usize, 范围的起始位置。
+/// * `len`: usize, 范围的长度。
+/// 返回值:成功执行,返回0。失败,返回-1。文件系统不支持相应的操作时返回 EOPNOTSUPP。
+pub async fn syscall_fallocate(args: [usize; 6]) -> SyscallResult {
+    let fd = args[0];
+    let mode = args[1];
+    let offset = args[2] as i64;
+    let len = args[3] as i64;
+    info!(
+        "fallocate: fd: {}, mode: {:#x}, offset: {}, len: {}",
+        fd, mode, offset, len
+    );
+    if offset < 0 || len <= 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    if offset.checked_add(len).is_none() {
+        return Err(SyscallError::EFBIG);
+    }
+    let keep_size = mode & FALLOC_FL_KEEP_SIZE != 0;
+    let op = mode & !FALLOC_FL_KEEP_SIZE;
+    if op & !(FALLOC_FL_PUNCH_HOLE | FALLOC_FL_ZERO_RANGE) != 0 || op.count_ones() > 1 {
+        // 其余的模式(如 COLLAPSE_RANGE、INSERT_RANGE)暂不支持
+        return Err(SyscallError::EOPNOTSUPP);
+    }
+    if op == FALLOC_FL_PUNCH_HOLE && !keep_size {
+        return Err(SyscallError::EOPNOTSUPP);
+    }
+    let file = writable_file_desc(fd).await?;
+    let Some(desc) = (*file).as_any().downcast_ref::<FileDesc>() else {
+        return Err(SyscallError::ENODEV);
+    };
+    let (offset, len) = (offset as u64, len as u64);
+    let file = desc.file.lock().await;
+    let ret = match op {
+        FALLOC_FL_PUNCH_HOLE => file.punch_hole(offset, len).await,
+        FALLOC_FL_ZERO_RANGE => file.zero_range(offset, len, keep_size).await,
+        _ => file.allocate(offset, len, keep_size).await,
+    };
+    match ret {
+        Ok(()) => Ok(0),
+        Err(AxError::Unsupported) => Err(SyscallError::EOPNOTSUPP),
+        Err(AxError::StorageFull) => Err(SyscallError::ENOSPC),
+        Err(AxError::InvalidInput) => Err(SyscallError::EINVAL),
+        Err(AxError::PermissionDenied) => Err(SyscallError::EBADF),
+        Err(_) => Err(SyscallError::EIO),
+    }
+}
@@ -1,16 +1,38 @@
-// const STDIN: usize = 0;
-// const STDOUT: usize = 1;
-// const STDERR: usize = 2;
+//! 负责与链接相关的系统调用
+//!
+//! 文件系统支持链接时直接使用文件系统的链接操作，fat32 上退化为
+//! [`executor::link`] 中模拟的链接
 extern crate alloc;
 
 use crate::{SyscallError, SyscallResult};
+use alloc::string::{String, ToString};
+use async_fs::api::LookupFlags;
+use axerrno::AxError;
 use axlog::debug;
-use async_executor::link::{create_link, remove_link, FilePath};
+use executor::{
+    current_executor,
+    link::{create_link, link_emulated, raw_ptr_to_ref_str, real_path, remove_link, FilePath},
+};
 
-use super::solve_path;
+use super::{check_parent_access, check_remove, init_owner, solve_path, solve_path_with_flags};
 
-/// Special value used to indicate openat should use the current working directory.
-pub const AT_REMOVEDIR: usize = 0x200; // Remove directory instead of unlinking file.
+/// Remove directory instead of unlinking file.
+pub const AT_REMOVEDIR: usize = 0x200;
+/// Follow symbolic link of `old_path` in linkat.
+pub const AT_SYMLINK_FOLLOW: usize = 0x400;
+
+/// 将链接相关的错误转换为系统调用的错误码
+pub(crate) fn link_err(err: AxError) -> SyscallError {
+    match err {
+        AxError::NotFound => SyscallError::ENOENT,
+        AxError::AlreadyExists => SyscallError::EEXIST,
+        AxError::NotADirectory => SyscallError::ENOTDIR,
+        AxError::IsADirectory => SyscallError::EISDIR,
+        AxError::DirectoryNotEmpty => SyscallError::ENOTEMPTY,
+        AxError::InvalidInput => SyscallError::EINVAL,
+        _ => SyscallError::EPERM,
+    }
+}
 
 /// 功能:创建文件的链接；
 /// # Arguments
@@ -18,36 +40,167 @@ pub const AT_REMOVEDIR: usize = 0x200; // Remove directory instead of unlinking
 /// * `old_path`: *const u8, 文件原来的名字。如果old_path是相对路径,则它是相对于old_dir_fd目录而言的。如果old_path是相对路径,且old_dir_fd的值为AT_FDCWD,则它是相对于当前路径而言的。如果old_path是绝对路径,则old_dir_fd被忽略。
 /// * `new_dir_fd`: usize, 新文件名所在的目录。
 /// * `new_path`: *const u8, 文件的新名字。new_path的使用规则同old_path。
-/// * `flags`: usize, 在2.6.18内核之前,应置为0。其它的值详见`man 2 linkat`。
+/// * `flags`: usize, 可设置为0或AT_SYMLINK_FOLLOW,后者表示old_path为符号链接时链接到其指向的文件。
 /// # Return
 /// 成功执行,返回0。失败,返回-1。
-#[allow(dead_code)]
-pub async fn sys_linkat(args: [usize; 6]) -> SyscallResult {
+pub async fn syscall_linkat(args: [usize; 6]) -> SyscallResult {
     let old_dir_fd = args[0];
     let old_path = args[1] as *const u8;
     let new_dir_fd = args[2];
     let new_path = args[3] as *const u8;
-    let _flags = args[4];
-
-    let old_path = solve_path(old_dir_fd, Some(old_path), false)?;
-    let new_path = solve_path(new_dir_fd, Some(new_path), false)?;
-    if create_link(&old_path, &new_path).await {
-        Ok(0)
+    let flags = args[4];
+    if flags & !AT_SYMLINK_FOLLOW != 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    let lookup = if flags & AT_SYMLINK_FOLLOW != 0 {
+        LookupFlags::empty()
     } else {
-        Err(SyscallError::EINVAL)
+        LookupFlags::NOFOLLOW
+    };
+    let old_path = solve_path_with_flags(old_dir_fd, Some(old_path), false, lookup).await?;
+    let new_path = solve_path_with_flags(new_dir_fd, Some(new_path), false, LookupFlags::NOFOLLOW).await?;
+    debug!("linkat: {} -> {}", new_path.path(), old_path.path());
+
+    if !async_fs::api::same_filesystem(old_path.path(), new_path.path()).await {
+        return Err(SyscallError::EXDEV);
+    }
+    check_parent_access(&new_path).await?;
+    let emulated = link_emulated(&new_path).await;
+    match async_fs::api::hard_link(old_path.path(), new_path.path()).await {
+        Ok(()) => Ok(0),
+        Err(AxError::Unsupported) if emulated => {
+            if async_fs::api::path_exists(new_path.path()).await {
+                return Err(SyscallError::EEXIST);
+            }
+            if create_link(&new_path, &old_path).await {
+                Ok(0)
+            } else {
+                Err(SyscallError::ENOENT)
+            }
+        }
+        Err(e) => Err(link_err(e)),
+    }
+}
+
+/// 功能:创建符号链接；
+/// # Arguments
+/// * `target`: *const u8, 符号链接的内容,不需要指向已存在的文件。
+/// * `new_dir_fd`: usize, 符号链接所在目录的文件描述符。
+/// * `link_path`: *const u8, 符号链接的名字,使用规则同linkat的new_path。
+/// # Return
+/// 成功执行,返回0。失败,返回-1。
+pub async fn syscall_symlinkat(args: [usize; 6]) -> SyscallResult {
+    let target = args[0] as *const u8;
+    let new_dir_fd = args[1];
+    let link_path = args[2] as *const u8;
+    if current_executor()
+        .manual_alloc_for_lazy((target as usize).into())
+        .await
+        .is_err()
+    {
+        return Err(SyscallError::EFAULT);
+    }
+    let target = unsafe { raw_ptr_to_ref_str(target) }.to_string();
+    if target.is_empty() {
+        return Err(SyscallError::ENOENT);
+    }
+    let link_path =
+        solve_path_with_flags(new_dir_fd, Some(link_path), false, LookupFlags::NOFOLLOW).await?;
+    debug!("symlinkat: {} -> {}", link_path.path(), target);
+    check_parent_access(&link_path).await?;
+
+    let emulated = link_emulated(&link_path).await;
+    match async_fs::api::symlink(&target, link_path.path()).await {
+        Ok(()) => {
+            init_owner(link_path.path(), 0o777).await;
+            Ok(0)
+        }
+        Err(AxError::Unsupported) if emulated => {
+            // 模拟的链接只能指向已存在的文件
+            let target = FilePath::new(&target).await.map_err(|_| SyscallError::ENOENT)?;
+            if create_link(&link_path, &target).await {
+                Ok(0)
+            } else {
+                Err(SyscallError::ENOENT)
+            }
+        }
+        Err(e) => Err(link_err(e)),
     }
 }
 
+/// 功能:读取符号链接的内容；
+/// * 写入buf的内容不以'\0'结尾,超出bufsiz的部分被截断
+/// # Arguments
+/// * `dir_fd`: usize, 符号链接所在目录的文件描述符。
+/// * `path`: *const u8, 符号链接的名字。
+/// * `buf`: *mut u8, 存放符号链接内容的缓冲区。
+/// * `bufsiz`: usize, 缓冲区的大小。
+/// # Return
+/// 成功执行,返回写入buf的字节数。失败,返回-1。
+pub async fn syscall_readlinkat(args: [usize; 6]) -> SyscallResult {
+    let dir_fd = args[0];
+    let path = args[1] as *const u8;
+    let buf = args[2] as *mut u8;
+    let bufsiz = args[3];
+    if buf.is_null() || (bufsiz as isize) <= 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    let executor = current_executor();
+    if executor
+        .manual_alloc_range_for_lazy((buf as usize).into(), (buf as usize + bufsiz).into())
+        .await
+        .is_err()
+    {
+        return Err(SyscallError::EFAULT);
+    }
+    let path = solve_path_with_flags(dir_fd, Some(path), false, LookupFlags::NOFOLLOW).await?;
+    debug!("readlinkat: {}", path.path());
+
+    let content: String = if path.path() == "/proc/self/exe" {
+        // 获取该进程符号链接对应的真正地址
+        executor.get_file_path().await
+    } else {
+        match async_fs::api::read_link(path.path()).await {
+            Ok(target) => target,
+            Err(AxError::NotFound) => {
+                // 模拟的链接不在文件系统中
+                let real = real_path(&path.path().to_string()).await;
+                if real == path.path() {
+                    return Err(SyscallError::ENOENT);
+                }
+                real
+            }
+            Err(_) => return Err(SyscallError::EINVAL),
+        }
+    };
+    let len = bufsiz.min(content.len());
+    let slice = unsafe { core::slice::from_raw_parts_mut(buf, len) };
+    slice.copy_from_slice(&content.as_bytes()[..len]);
+    Ok(len as isize)
+}
+
+/// 功能:读取符号链接的内容；
+/// # Arguments
+/// * `path`: *const u8, 符号链接的名字。
+/// * `buf`: *mut u8, 存放符号链接内容的缓冲区。
+/// * `bufsiz`: usize, 缓冲区的大小。
+#[cfg(target_arch = "x86_64")]
+pub async fn syscall_readlink(args: [usize; 6]) -> SyscallResult {
+    use executor::link::AT_FDCWD;
+    let temp_args = [AT_FDCWD, args[0], args[1], args[2], 0, 0];
+    syscall_readlinkat(temp_args).await
+}
+
 /// 功能:移除指定文件的链接
 /// # Arguments
 /// * `path`: *const u8, 要删除的链接的名字。
 /// # Return
 /// 成功执行,返回0。失败,返回-1。
 #[cfg(target_arch = "x86_64")]
-pub fn syscall_unlink(args: [usize; 6]) -> SyscallResult {
-    let path = args[0] as *const u8;
-    let temp_args = [axprocess::link::AT_FDCWD, path as usize, 0, 0, 0, 0];
-    syscall_unlinkat(temp_args)
+pub async fn syscall_unlink(args: [usize; 6]) -> SyscallResult {
+    use executor::link::AT_FDCWD;
+    let temp_args = [AT_FDCWD, args[0], 0, 0, 0, 0];
+    syscall_unlinkat(temp_args).await
 }
 
 /// 功能:移除指定文件的链接(可用于删除文件);
@@ -61,27 +214,28 @@ pub async fn syscall_unlinkat(args: [usize; 6]) -> SyscallResult {
     let dir_fd = args[0];
     let path = args[1] as *const u8;
     let flags = args[2];
-    let path = solve_path(dir_fd, Some(path), false)?;
-
-    if path.start_with(&FilePath::new("/proc").await.unwrap()) {
-        return Ok(-1);
+    if flags & !AT_REMOVEDIR != 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    if flags & AT_REMOVEDIR != 0 {
+        let path = solve_path(dir_fd, Some(path), true).await?;
+        debug!("unlinkat: rmdir {}", path.path());
+        check_remove(&path).await?;
+        return async_fs::api::remove_dir(path.path())
+            .await
+            .map(|_| 0)
+            .map_err(link_err);
     }
 
-    // remove dir
-    if flags == AT_REMOVEDIR {
-        if let Err(e) = axfs::api::remove_dir(path.path()).await {
-            debug!("rmdir error: {:?}", e);
-            return Err(SyscallError::EINVAL);
-        }
+    // 删除的是链接本身，而不是链接指向的文件
+    let path = solve_path_with_flags(dir_fd, Some(path), false, LookupFlags::NOFOLLOW).await?;
+    debug!("unlinkat: {}", path.path());
+    check_remove(&path).await?;
+    if remove_link(&path).await.is_some() {
         return Ok(0);
     }
-    let metadata = axfs::api::metadata(path.path()).await.unwrap();
-    if metadata.is_dir() {
-        return Err(SyscallError::EISDIR);
-    }
-    if remove_link(&path).await.is_none() {
-        debug!("unlink file error");
-        return Err(SyscallError::EINVAL);
-    }
-    Ok(0)
+    async_fs::api::remove_file(path.path())
+        .await
+        .map(|_| 0)
+        .map_err(link_err)
 }
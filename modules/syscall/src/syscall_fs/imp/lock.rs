@@ -0,0 +1,211 @@
+//! 负责文件锁相关的系统调用，包括 flock 与 fcntl 的记录锁
+//!
+//! 锁本身由 [`async_fs::api`] 中的锁表管理，以 [`file_identity`] 标识文件，这里负责解析参数、
+//! 确定锁的持有者，以及在关闭文件与进程退出时释放锁
+extern crate alloc;
+
+use crate::{
+    syscall_fs::ctype::file::file_identity, Fcntl64Cmd, Flock, SyscallError, SyscallResult, F_RDLCK,
+    F_UNLCK, F_WRLCK,
+};
+use alloc::{collections::BTreeMap, sync::Arc};
+use async_fs::api::{FileIO, LockError, LockKind, LockOwner, SeekFrom};
+use axlog::{debug, info};
+use executor::current_executor;
+
+/// Shared lock.
+const LOCK_SH: usize = 1;
+/// Exclusive lock.
+const LOCK_EX: usize = 2;
+/// Don't block when locking.
+const LOCK_NB: usize = 4;
+/// Unlock.
+const LOCK_UN: usize = 8;
+
+/// 打开文件描述作为锁的持有者时的标识
+fn file_owner(file: &Arc<dyn FileIO>) -> usize {
+    Arc::as_ptr(file) as *const () as usize
+}
+
+fn lock_err(err: LockError) -> SyscallError {
+    match err {
+        LockError::WouldBlock => SyscallError::EAGAIN,
+        LockError::Deadlock => SyscallError::EDEADLK,
+    }
+}
+
+/// 功能:对打开的文件加建议性的整文件锁；
+/// # Arguments
+/// * `fd`: usize, 文件描述符。
+/// * `operation`: usize, LOCK_SH、LOCK_EX 或 LOCK_UN,可以与 LOCK_NB 组合。
+/// 返回值:成功执行,返回0。失败,返回-1。
+///
+/// 锁属于打开文件描述,dup 出的文件描述符共享同一把锁
+pub async fn syscall_flock(args: [usize; 6]) -> SyscallResult {
+    let fd = args[0];
+    let operation = args[1];
+    let Some(file) = current_executor().fd_manager.get(fd).await else {
+        return Err(SyscallError::EBADF);
+    };
+    let kind = match operation & !LOCK_NB {
+        LOCK_SH => Some(LockKind::Shared),
+        LOCK_EX => Some(LockKind::Exclusive),
+        LOCK_UN => None,
+        _ => return Err(SyscallError::EINVAL),
+    };
+    let ino = file_identity(&file).await;
+    info!("flock: fd {} ino {} operation {:#x}", fd, ino, operation);
+    async_fs::api::flock(ino, file_owner(&file), kind, operation & LOCK_NB == 0)
+        .await
+        .map_err(lock_err)?;
+    Ok(0)
+}
+
+/// 计算记录锁覆盖的范围 `[start, end)`
+async fn lock_range(file: &Arc<dyn FileIO>, lock: &Flock) -> Result<(u64, u64), SyscallError> {
+    let base = match lock.l_whence {
+        0 => 0,
+        1 => file
+            .seek(SeekFrom::Current(0))
+            .await
+            .map_err(|_| SyscallError::ESPIPE)? as i64,
+        2 => file
+            .get_stat()
+            .await
+            .map_err(|_| SyscallError::EINVAL)?
+            .st_size as i64,
+        _ => return Err(SyscallError::EINVAL),
+    };
+    let start = base.checked_add(lock.l_start).ok_or(SyscallError::EOVERFLOW)?;
+    let (start, end) = match lock.l_len {
+        0 => (start, u64::MAX),
+        len if len > 0 => {
+            let end = start.checked_add(len).ok_or(SyscallError::EOVERFLOW)?;
+            (start, end as u64)
+        }
+        // 长度为负时锁住 start 之前的 |len| 个字节
+        len => (start.checked_add(len).ok_or(SyscallError::EINVAL)?, start as u64),
+    };
+    if start < 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    Ok((start as u64, end))
+}
+
+/// 处理 fcntl 中与记录锁相关的命令
+///
+/// F_SETLK 系列的锁属于进程,F_OFD_SETLK 系列的锁属于打开文件描述,二者之间会相互冲突
+pub async fn fcntl_lock(file: Arc<dyn FileIO>, cmd: Fcntl64Cmd, arg: usize) -> SyscallResult {
+    let process = current_executor();
+    let lock_ptr = arg as *mut Flock;
+    if process.manual_alloc_type_for_lazy(lock_ptr).await.is_err() {
+        return Err(SyscallError::EFAULT);
+    }
+    let mut lock = unsafe { *lock_ptr };
+    let is_ofd = matches!(
+        cmd,
+        Fcntl64Cmd::F_OFD_GETLK | Fcntl64Cmd::F_OFD_SETLK | Fcntl64Cmd::F_OFD_SETLKW
+    );
+    if is_ofd && lock.l_pid != 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    let owner = if is_ofd {
+        LockOwner::File(file_owner(&file))
+    } else {
+        LockOwner::Process(process.pid().as_u64())
+    };
+    let kind = match lock.l_type {
+        F_RDLCK => Some(LockKind::Shared),
+        F_WRLCK => Some(LockKind::Exclusive),
+        F_UNLCK => None,
+        _ => return Err(SyscallError::EINVAL),
+    };
+    let (start, end) = lock_range(&file, &lock).await?;
+    let ino = file_identity(&file).await;
+    debug!("fcntl lock: ino {} {:?} {:?} [{}, {})", ino, owner, kind, start, end);
+
+    match cmd {
+        Fcntl64Cmd::F_GETLK | Fcntl64Cmd::F_OFD_GETLK => {
+            let Some(kind) = kind else {
+                return Err(SyscallError::EINVAL);
+            };
+            match async_fs::api::test_range_lock(ino, owner, kind, start, end) {
+                None => lock.l_type = F_UNLCK,
+                Some(conflict) => {
+                    lock.l_type = match conflict.kind {
+                        LockKind::Shared => F_RDLCK,
+                        LockKind::Exclusive => F_WRLCK,
+                    };
+                    lock.l_whence = 0;
+                    lock.l_start = conflict.start as i64;
+                    lock.l_len = if conflict.end == u64::MAX {
+                        0
+                    } else {
+                        (conflict.end - conflict.start) as i64
+                    };
+                    // OFD 锁不属于任何进程
+                    lock.l_pid = match conflict.owner {
+                        LockOwner::Process(pid) => pid as i32,
+                        LockOwner::File(_) => -1,
+                    };
+                }
+            }
+            unsafe {
+                *lock_ptr = lock;
+            }
+            Ok(0)
+        }
+        _ => {
+            // 读锁要求文件可读,写锁要求文件可写
+            match kind {
+                Some(LockKind::Shared) if !file.readable().await => {
+                    return Err(SyscallError::EBADF)
+                }
+                Some(LockKind::Exclusive) if !file.writable().await => {
+                    return Err(SyscallError::EBADF)
+                }
+                _ => {}
+            }
+            let wait = matches!(cmd, Fcntl64Cmd::F_SETLKW | Fcntl64Cmd::F_OFD_SETLKW);
+            async_fs::api::set_range_lock(ino, owner, kind, start, end, wait)
+                .await
+                .map_err(lock_err)?;
+            Ok(0)
+        }
+    }
+}
+
+/// 关闭文件描述符 `file` 后释放相应的锁
+///
+/// 进程在该文件上的 POSIX 记录锁总是被释放；flock 锁与 OFD 锁在打开文件描述的最后一个引用关闭时释放
+pub async fn release_file_locks(file: Arc<dyn FileIO>) {
+    let pid = current_executor().pid().as_u64();
+    let ino = file_identity(&file).await;
+    async_fs::api::release_process_locks(pid, ino);
+    if Arc::strong_count(&file) == 1 {
+        async_fs::api::release_lock_owner(LockOwner::File(file_owner(&file)));
+    }
+}
+
+/// 执行 `exec()` 时关闭标记为 `CLOEXEC` 的文件描述符，并与 close 相同地释放相应的锁
+pub async fn close_files_on_exec() {
+    for file in current_executor().fd_manager.close_on_exec().await {
+        release_file_locks(file).await;
+    }
+}
+
+/// 进程退出时关闭所有的文件描述符,并释放其持有的所有锁
+pub async fn release_locks_on_exit() {
+    let process = current_executor();
+    let mut files = BTreeMap::new();
+    for (_, file) in process.fd_manager.entries().await {
+        files.entry(file_owner(&file)).or_insert(file);
+    }
+    process.fd_manager.close_range(0, usize::MAX, false).await;
+    for (owner, file) in files {
+        if Arc::strong_count(&file) == 1 {
+            async_fs::api::release_lock_owner(LockOwner::File(owner));
+        }
+    }
+    async_fs::api::release_lock_owner(LockOwner::Process(process.pid().as_u64()));
+}
@@ -0,0 +1,101 @@
+//! memfd：不在任何目录中的内存文件
+//!
+//! 它是一个普通的 [`FileDesc`]，因此可以被读写、ftruncate、以 MAP_SHARED 映射，以及通过 Unix 域套接字传递。
+//! seals 记录在文件节点上，由文件系统检查，见 [`VfsSeals`]
+extern crate alloc;
+
+use crate::syscall_fs::ctype::FileDesc;
+use crate::{SyscallError, SyscallResult};
+use alloc::format;
+use alloc::sync::Arc;
+use async_fs::api::{OpenFlags, Permissions, VfsSeals, VfsSetAttr};
+use bitflags::bitflags;
+use executor::link::raw_ptr_to_ref_str;
+use executor::{current_executor, FdFlags};
+use sync::Mutex;
+
+use super::fd_err;
+
+/// 名字的最大长度，加上 `memfd:` 前缀后不超过 NAME_MAX
+const MFD_NAME_MAX: usize = 249;
+
+bitflags! {
+    /// memfd_create 的 flags
+    #[derive(Debug, Clone, Copy)]
+    pub struct MemfdFlags: u32 {
+        const MFD_CLOEXEC = 0x1;
+        const MFD_ALLOW_SEALING = 0x2;
+        // unimplemented:
+        const MFD_HUGETLB = 0x4;
+        /// 不可执行，同时允许添加 seals
+        const MFD_NOEXEC_SEAL = 0x8;
+        const MFD_EXEC = 0x10;
+    }
+}
+
+/// 创建一个 memfd，返回以读写方式打开它的文件描述符
+///
+/// # Arguments
+/// * `name`: *const u8, 文件的名字，只用于 /proc/self/fd 中显示的路径，可以重复
+/// * `flags`: u32, MemfdFlags 的组合
+pub async fn syscall_memfd_create(args: [usize; 6]) -> SyscallResult {
+    let name = args[0] as *const u8;
+    let Some(flags) = MemfdFlags::from_bits(args[1] as u32) else {
+        return Err(SyscallError::EINVAL);
+    };
+    if flags.contains(MemfdFlags::MFD_HUGETLB)
+        || flags.contains(MemfdFlags::MFD_NOEXEC_SEAL | MemfdFlags::MFD_EXEC)
+    {
+        return Err(SyscallError::EINVAL);
+    }
+    let process = current_executor();
+    if name.is_null()
+        || process
+            .manual_alloc_for_lazy((name as usize).into())
+            .await
+            .is_err()
+    {
+        return Err(SyscallError::EFAULT);
+    }
+    let name = unsafe { raw_ptr_to_ref_str(name) };
+    if name.len() > MFD_NAME_MAX {
+        return Err(SyscallError::EINVAL);
+    }
+
+    // 不允许添加 seals 的 memfd 与 tmpfs 中的文件相同，带有 F_SEAL_SEAL
+    let seals = if flags.intersects(MemfdFlags::MFD_ALLOW_SEALING | MemfdFlags::MFD_NOEXEC_SEAL) {
+        VfsSeals::empty()
+    } else {
+        VfsSeals::SEAL
+    };
+    let mode = if flags.contains(MemfdFlags::MFD_NOEXEC_SEAL) {
+        0o666
+    } else {
+        0o777
+    };
+    let file = async_fs::api::create_anonymous_file(seals);
+    let set_attr = VfsSetAttr {
+        mode: Some(Permissions::from_bits_truncate(mode)),
+        uid: Some(process.cred.euid()),
+        gid: Some(process.cred.egid()),
+        ..Default::default()
+    };
+    if file.set_attr(&set_attr).await.is_err() {
+        return Err(SyscallError::ENOMEM);
+    }
+
+    // 与 Linux 相同，路径表示它已被删除
+    let path = format!("/memfd:{} (deleted)", name);
+    let desc = FileDesc::new(&path, Arc::new(Mutex::new(file)), OpenFlags::RDWR);
+    let fd_flags = if flags.contains(MemfdFlags::MFD_CLOEXEC) {
+        FdFlags::CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
+    let fd = process
+        .fd_manager
+        .alloc(Arc::new(desc), fd_flags)
+        .await
+        .map_err(fd_err)?;
+    Ok(fd as isize)
+}
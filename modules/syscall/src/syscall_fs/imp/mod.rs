@@ -2,39 +2,79 @@
 extern crate alloc;
 
 mod ctl;
-// mod epoll;
-// mod eventfd;
+mod epoll;
+mod eventfd;
+mod inotify;
 mod io;
-// mod link;
-// mod mount;
-// mod poll;
+mod link;
+mod lock;
+mod memfd;
+mod mount;
+mod mqueue;
+mod perm;
+mod pidfd;
+mod poll;
+mod splice;
 mod stat;
+mod timerfd;
+mod xattr;
 use axerrno::AxError;
+use async_fs::api::{LookupFlags, PathError};
 use executor::link::{deal_with_path, FilePath};
 pub use ctl::*;
-// pub use epoll::*;
-// pub use eventfd::*;
+pub use epoll::*;
+pub use eventfd::*;
+pub use inotify::*;
 pub use io::*;
-// pub use link::*;
-// pub use mount::*;
-// pub use poll::*;
+pub use link::*;
+pub use lock::*;
+pub use memfd::*;
+pub use mount::*;
+pub use mqueue::*;
+pub use perm::*;
+pub use pidfd::*;
+pub use poll::*;
+pub use splice::*;
 pub use stat::*;
+pub use timerfd::*;
+pub use xattr::*;
 
 use crate::SyscallError;
 
+/// Convert the error returned by [`executor::FdManager`] to the error of a syscall.
+pub fn fd_err(err: AxError) -> SyscallError {
+    match err {
+        AxError::StorageFull => SyscallError::EMFILE,
+        _ => SyscallError::EBADF,
+    }
+}
+
 /// To get the real path of the directory or the file by the given path and the directory fd.
 pub async fn solve_path(
     dir_fd: usize,
     path_addr: Option<*const u8>,
     force_dir: bool,
 ) -> Result<FilePath, SyscallError> {
-    match deal_with_path(dir_fd, path_addr, force_dir).await {
+    solve_path_with_flags(dir_fd, path_addr, force_dir, LookupFlags::empty()).await
+}
+
+/// The same as [`solve_path`], but resolves the path according to `flags`,
+/// e.g. not following the symbolic link at the end with [`LookupFlags::NOFOLLOW`].
+pub async fn solve_path_with_flags(
+    dir_fd: usize,
+    path_addr: Option<*const u8>,
+    force_dir: bool,
+    flags: LookupFlags,
+) -> Result<FilePath, SyscallError> {
+    match deal_with_path(dir_fd, path_addr, force_dir, flags).await {
         Ok(path) => Ok(path),
+        Err(PathError::Loop) => Err(SyscallError::ELOOP),
         // Only invalid for file descriptor
-        Err(AxError::InvalidInput) => Err(SyscallError::EBADF),
-        Err(AxError::NotFound) => Err(SyscallError::ENOENT),
-        Err(AxError::NotADirectory) => Err(SyscallError::ENOTDIR),
-        Err(AxError::BadAddress) => Err(SyscallError::EFAULT),
+        Err(PathError::Vfs(AxError::InvalidInput)) => Err(SyscallError::EBADF),
+        Err(PathError::Vfs(AxError::NotFound)) => Err(SyscallError::ENOENT),
+        Err(PathError::Vfs(AxError::NotADirectory)) => Err(SyscallError::ENOTDIR),
+        Err(PathError::Vfs(AxError::BadAddress)) => Err(SyscallError::EFAULT),
+        Err(PathError::Vfs(AxError::PermissionDenied)) => Err(SyscallError::EACCES),
         Err(_) => Err(SyscallError::EPERM),
     }
 }
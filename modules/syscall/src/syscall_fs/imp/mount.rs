@@ -1,14 +1,44 @@
 use crate::{syscall_fs::solve_path, SyscallError, SyscallResult};
-use axprocess::{
-    current_process,
+use async_fs::api::{MountFlags, UmountFlags};
+use axerrno::AxError;
+use executor::{
+    current_executor,
     link::{raw_ptr_to_ref_str, AT_FDCWD},
 };
 
-// use super::{deal_with_path, AT_FDCWD};
-use crate::syscall_fs::ctype::mount::{check_mounted, mount_fat_fs, umount_fat_fs};
 extern crate alloc;
-use alloc::string::ToString;
+use alloc::string::{String, ToString};
 use axlog::debug;
+
+/// 将挂载相关的错误转换为系统调用的错误码
+fn mount_err(err: AxError) -> SyscallError {
+    match err {
+        AxError::NotFound => SyscallError::ENOENT,
+        AxError::NotADirectory => SyscallError::ENOTDIR,
+        AxError::ResourceBusy => SyscallError::EBUSY,
+        AxError::InvalidInput => SyscallError::EINVAL,
+        AxError::Unsupported => SyscallError::ENODEV,
+        AxError::PermissionDenied => SyscallError::EACCES,
+        AxError::NoMemory => SyscallError::ENOMEM,
+        _ => SyscallError::EINVAL,
+    }
+}
+
+/// 读取用户态传入的字符串，指针为 NULL 时返回空串
+async fn user_str(ptr: *const u8) -> Result<String, SyscallError> {
+    if ptr.is_null() {
+        return Ok(String::new());
+    }
+    if current_executor()
+        .manual_alloc_for_lazy((ptr as usize).into())
+        .await
+        .is_err()
+    {
+        return Err(SyscallError::EFAULT);
+    }
+    Ok(unsafe { raw_ptr_to_ref_str(ptr) }.to_string())
+}
+
 /// 功能:挂载文件系统；
 /// # Arguments
 /// * `special`: *const u8, 挂载设备
@@ -17,75 +47,34 @@ use axlog::debug;
 /// * `flags`: usize, 挂载参数
 /// * `data`: *const u8, 传递给文件系统的字符串参数,可为NULL
 /// 返回值:成功返回0,失败返回-1
-pub fn syscall_mount(args: [usize; 6]) -> SyscallResult {
+pub async fn syscall_mount(args: [usize; 6]) -> SyscallResult {
     let special = args[0] as *const u8;
     let dir = args[1] as *const u8;
     let fs_type = args[2] as *const u8;
-    let _flags = args[3];
+    let flags = MountFlags::from_bits_truncate(args[3] as u32);
     let _data = args[4] as *const u8;
-    let device_path = solve_path(AT_FDCWD, Some(special), false)?;
-    axlog::error!("syscall_mount dev: {:?}", args);
-    // 这里dir必须以"/"结尾,但在shell中输入时,不需要以"/"结尾
-    let mount_path = solve_path(AT_FDCWD, Some(dir), true)?;
-    axlog::error!("syscall_mount mount: {:?}", args);
-
-    let process = current_process();
-    if process
-        .manual_alloc_for_lazy((fs_type as usize).into())
-        .is_err()
-    {
-        return Err(SyscallError::EINVAL);
-    }
-
-    let fs_type = unsafe { raw_ptr_to_ref_str(fs_type).to_string() };
-    let mut _data_str = "".to_string();
-    if !_data.is_null() {
-        if process
-            .manual_alloc_for_lazy((_data as usize).into())
-            .is_err()
-        {
-            return Err(SyscallError::EINVAL);
-        }
-        // data可以为NULL, 必须判断, 否则会panic, 发生LoadPageFault
-        _data_str = unsafe { raw_ptr_to_ref_str(_data) }.to_string();
-    }
-    if device_path.is_dir() {
-        debug!("device_path should not be a dir");
-        return Err(SyscallError::EPERM);
-    }
-    if !mount_path.is_dir() {
-        debug!("mount_path should be a dir");
+    if !current_executor().cred.is_root() {
         return Err(SyscallError::EPERM);
     }
+    // 这里dir必须以"/"结尾,但在shell中输入时,不需要以"/"结尾
+    let mount_path = solve_path(AT_FDCWD, Some(dir), true).await?;
+    let fs_type = user_str(fs_type).await?;
+    debug!(
+        "syscall_mount: {:?} at {} type {} flags {:?}",
+        special, mount_path.path(), fs_type, flags
+    );
 
-    // 如果mount_path不存在,则创建
-    if !axfs::api::path_exists(mount_path.path()) {
-        if let Err(e) = axfs::api::create_dir(mount_path.path()) {
-            debug!("create mount path error: {:?}", e);
-            return Err(SyscallError::EPERM);
-        }
-    }
-
-    if fs_type != "vfat" {
-        debug!("fs_type can only be vfat.");
-        return Err(SyscallError::EPERM);
-    }
-    // 检查挂载点路径是否存在
-    if !axfs::api::path_exists(mount_path.path()) {
-        debug!("mount path not exist");
-        return Err(SyscallError::EPERM);
-    }
-    // 查挂载点是否已经被挂载
-    if check_mounted(&mount_path) {
-        debug!("mount path includes mounted fs");
-        return Err(SyscallError::EPERM);
-    }
-    // 挂载
-    if !mount_fat_fs(&device_path, &mount_path) {
-        debug!("mount error");
-        return Err(SyscallError::EPERM);
-    }
+    // 绑定挂载时 special 为被绑定的目录，其余情况下只是一个名字
+    let source = if flags.contains(MountFlags::BIND) {
+        solve_path(AT_FDCWD, Some(special), true).await?.path().to_string()
+    } else {
+        let source = user_str(special).await?;
+        if source.is_empty() { String::from("none") } else { source }
+    };
 
+    async_fs::api::mount(&source, mount_path.path(), &fs_type, flags)
+        .await
+        .map_err(mount_err)?;
     Ok(0)
 }
 
@@ -95,27 +84,24 @@ pub fn syscall_mount(args: [usize; 6]) -> SyscallResult {
 /// # Arguments
 /// * `dir`: *const u8, 指定卸载目录
 /// * `flags`: usize, 卸载参数
-pub fn syscall_umount(args: [usize; 6]) -> SyscallResult {
+pub async fn syscall_umount(args: [usize; 6]) -> SyscallResult {
     let dir = args[0] as *const u8;
-    let flags = args[1];
-    let mount_path = solve_path(AT_FDCWD, Some(dir), true)?;
-    axlog::error!("syscall_umount: {:?}", args);
-
-    if flags != 0 {
-        debug!("flags unimplemented");
+    let Some(flags) = UmountFlags::from_bits(args[1] as u32) else {
+        return Err(SyscallError::EINVAL);
+    };
+    if !current_executor().cred.is_root() {
         return Err(SyscallError::EPERM);
     }
+    let mount_path = solve_path(AT_FDCWD, Some(dir), true).await?;
+    debug!("syscall_umount: {} flags {:?}", mount_path.path(), flags);
 
-    // 检查挂载点路径是否存在
-    if !axfs::api::path_exists(mount_path.path()) {
-        debug!("mount path not exist");
-        return Err(SyscallError::EPERM);
-    }
-    // 从挂载点中删除
-    if !umount_fat_fs(&mount_path) {
-        debug!("umount error");
-        return Err(SyscallError::EPERM);
+    if flags.contains(UmountFlags::EXPIRE)
+        && flags.intersects(UmountFlags::FORCE | UmountFlags::DETACH)
+    {
+        return Err(SyscallError::EINVAL);
     }
-
+    async_fs::api::umount(mount_path.path(), flags)
+        .await
+        .map_err(mount_err)?;
     Ok(0)
 }
@@ -0,0 +1,420 @@
+//! POSIX 消息队列
+//!
+//! 队列的名字不含开头的 `/`，由 C 库去掉。阻塞的发送与接收可以被绝对的 CLOCK_REALTIME 超时时间打断
+extern crate alloc;
+
+use crate::syscall_fs::ctype::mqueue::{
+    MqAttr, MqueueFile, SigEvent, SIGEV_NONE, SIGEV_SIGNAL, SIGEV_THREAD,
+};
+use crate::{SyscallError, SyscallResult, TimeSecs};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use async_fs::api::{
+    FileIO, MessageQueue, MqNotify, OpenFlags, Permissions, VfsAccess, VfsSetAttr, DEFAULT_MAXMSG,
+    DEFAULT_MSGSIZE,
+};
+use axerrno::AxError;
+use axhal::time::{current_time, wall_time, TimeValue};
+use core::future::poll_fn;
+use core::task::Poll;
+use core::time::Duration;
+use executor::link::raw_ptr_to_ref_str;
+use executor::{current_executor, FdFlags};
+
+use super::{fd_err, sleep_until_woken};
+
+/// 队列名的最大长度
+const NAME_MAX: usize = 255;
+/// 消息优先级的上限
+const MQ_PRIO_MAX: usize = 32768;
+/// 普通用户创建队列时 mq_maxmsg 与 mq_msgsize 的上限，即 /proc/sys/fs/mqueue 中的默认值
+const MSG_MAX: i64 = DEFAULT_MAXMSG as i64;
+const MSGSIZE_MAX: i64 = DEFAULT_MSGSIZE as i64;
+/// root 用户创建队列时的上限
+const HARD_MSGMAX: i64 = 65536;
+const HARD_MSGSIZEMAX: i64 = 16 * 1024 * 1024;
+
+/// 检查用户空间的 `[start, start + len)` 是否可以访问
+async fn check_range(start: usize, len: usize) -> Result<(), SyscallError> {
+    if len == 0 {
+        return Ok(());
+    }
+    if start == 0 {
+        return Err(SyscallError::EFAULT);
+    }
+    let end = start.checked_add(len - 1).ok_or(SyscallError::EFAULT)?;
+    current_executor()
+        .manual_alloc_range_for_lazy(start.into(), end.into())
+        .await
+        .map_err(|_| SyscallError::EFAULT)
+}
+
+/// 读取并检查用户传入的队列名
+async fn read_name(ptr: *const u8) -> Result<String, SyscallError> {
+    if ptr.is_null()
+        || current_executor()
+            .manual_alloc_for_lazy((ptr as usize).into())
+            .await
+            .is_err()
+    {
+        return Err(SyscallError::EFAULT);
+    }
+    let name = unsafe { raw_ptr_to_ref_str(ptr) };
+    if name.is_empty() {
+        return Err(SyscallError::ENOENT);
+    }
+    if name.len() > NAME_MAX {
+        return Err(SyscallError::ENAMETOOLONG);
+    }
+    if name.contains('/') || name == "." || name == ".." {
+        return Err(SyscallError::EACCES);
+    }
+    Ok(String::from(name))
+}
+
+/// 获取 `fd` 对应的消息队列
+async fn mqueue_of(fd: usize) -> Result<Arc<dyn FileIO>, SyscallError> {
+    let Some(file) = current_executor().fd_manager.get(fd).await else {
+        return Err(SyscallError::EBADF);
+    };
+    if (*file).as_any().downcast_ref::<MqueueFile>().is_none() {
+        return Err(SyscallError::EBADF);
+    }
+    Ok(file)
+}
+
+/// 读取绝对的 CLOCK_REALTIME 超时时间，并换算为单调时钟，空指针表示一直等待
+async fn realtime_deadline(timeout: *const TimeSecs) -> Result<Option<TimeValue>, SyscallError> {
+    if timeout.is_null() {
+        return Ok(None);
+    }
+    if current_executor().manual_alloc_type_for_lazy(timeout).await.is_err() {
+        return Err(SyscallError::EFAULT);
+    }
+    let timeout = unsafe { *timeout };
+    if (timeout.tv_sec as isize) < 0 || timeout.tv_nsec >= 1_000_000_000 {
+        return Err(SyscallError::EINVAL);
+    }
+    let deadline = Duration::new(timeout.tv_sec as u64, timeout.tv_nsec as u32);
+    Ok(Some(deadline.saturating_sub(wall_time() - current_time())))
+}
+
+/// 投递 mq_notify 注册的通知
+///
+/// 内核还没有信号子系统，mq_notify 不接受 SIGEV_SIGNAL，这里只会收到 SIGEV_NONE 的通知，
+/// 它在发出时移除注册，不需要做其他事。信号子系统实现后，应当向 `notify.pid` 发送 `notify.signo`，
+/// siginfo 的 si_code 为 SI_MESGQ，si_value 为 `notify.value`
+fn deliver_notify(notify: MqNotify) {
+    debug_assert!(notify.signo.is_none());
+}
+
+/// 打开或创建一个消息队列
+///
+/// # Arguments
+/// * `name`: *const u8, 队列名，不含开头的 `/`
+/// * `oflag`: i32, 访问模式与 O_CREAT、O_EXCL、O_NONBLOCK、O_CLOEXEC 的组合
+/// * `mode`: u32, 创建队列时的权限，会去掉 umask
+/// * `attr`: *const MqAttr, 创建队列时的 mq_maxmsg 与 mq_msgsize，为空时使用默认值
+pub async fn syscall_mq_open(args: [usize; 6]) -> SyscallResult {
+    let name = read_name(args[0] as *const u8).await?;
+    let oflag = OpenFlags::from_bits_truncate(args[1] as u32);
+    let mode = args[2] as u32;
+    let attr = args[3] as *const MqAttr;
+    if args[1] & 0b11 == 0b11 {
+        return Err(SyscallError::EINVAL);
+    }
+    let executor = current_executor();
+    let (euid, egid) = (executor.cred.euid(), executor.cred.egid());
+    let queue = loop {
+        match async_fs::api::mq_lookup(&name) {
+            Ok(_) if oflag.contains(OpenFlags::CREATE | OpenFlags::EXCLUSIVE) => {
+                return Err(SyscallError::EEXIST);
+            }
+            Ok((queue, file_attr)) => {
+                let mut access = VfsAccess::empty();
+                if oflag.readable() {
+                    access |= VfsAccess::READ;
+                }
+                if oflag.writable() {
+                    access |= VfsAccess::WRITE;
+                }
+                if !file_attr.permits(euid, egid, access) {
+                    return Err(SyscallError::EACCES);
+                }
+                break queue;
+            }
+            Err(AxError::NotFound) if !oflag.contains(OpenFlags::CREATE) => {
+                return Err(SyscallError::ENOENT);
+            }
+            Err(AxError::NotFound) => {}
+            Err(_) => return Err(SyscallError::EACCES),
+        }
+        let (maxmsg, msgsize) = if attr.is_null() {
+            (DEFAULT_MAXMSG, DEFAULT_MSGSIZE)
+        } else {
+            if executor.manual_alloc_type_for_lazy(attr).await.is_err() {
+                return Err(SyscallError::EFAULT);
+            }
+            let attr = unsafe { *attr };
+            let (msg_max, msgsize_max) = if executor.cred.is_root() {
+                (HARD_MSGMAX, HARD_MSGSIZEMAX)
+            } else {
+                (MSG_MAX, MSGSIZE_MAX)
+            };
+            if !(1..=msg_max).contains(&attr.mq_maxmsg) || !(1..=msgsize_max).contains(&attr.mq_msgsize) {
+                return Err(SyscallError::EINVAL);
+            }
+            (attr.mq_maxmsg as usize, attr.mq_msgsize as usize)
+        };
+        let umask = executor.fd_manager.get_mask() as u32;
+        let set_attr = VfsSetAttr {
+            mode: Some(Permissions::from_bits_truncate((mode & !umask & 0o777) as u16)),
+            uid: Some(euid),
+            gid: Some(egid),
+            ..Default::default()
+        };
+        match async_fs::api::mq_create(&name, MessageQueue::new(maxmsg, msgsize), &set_attr) {
+            Ok(queue) => break queue,
+            // 其他进程同时创建了同名的队列，重新查找
+            Err(AxError::AlreadyExists) => continue,
+            Err(_) => return Err(SyscallError::ENOSPC),
+        }
+    };
+    let status = oflag & (OpenFlags::WRONLY | OpenFlags::RDWR | OpenFlags::NON_BLOCK);
+    let fd_flags = if oflag.contains(OpenFlags::CLOEXEC) {
+        FdFlags::CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
+    let fd = executor
+        .fd_manager
+        .alloc(Arc::new(MqueueFile::new(queue, status)), fd_flags)
+        .await
+        .map_err(fd_err)?;
+    Ok(fd as isize)
+}
+
+/// 删除一个消息队列，已经打开的描述符仍然可以使用
+///
+/// # Arguments
+/// * `name`: *const u8, 队列名，不含开头的 `/`
+pub async fn syscall_mq_unlink(args: [usize; 6]) -> SyscallResult {
+    let name = read_name(args[0] as *const u8).await?;
+    let Ok((_, attr)) = async_fs::api::mq_lookup(&name) else {
+        return Err(SyscallError::ENOENT);
+    };
+    // 队列所在的目录设置了粘滞位
+    let cred = &current_executor().cred;
+    if !cred.is_root() && cred.euid() != attr.uid() {
+        return Err(SyscallError::EPERM);
+    }
+    match async_fs::api::mq_unlink(&name) {
+        Ok(()) => Ok(0),
+        Err(_) => Err(SyscallError::ENOENT),
+    }
+}
+
+/// 向消息队列发送一条消息，队列已满时阻塞
+///
+/// # Arguments
+/// * `mqdes`: i32, 消息队列的描述符
+/// * `msg_ptr`: *const u8, 消息内容
+/// * `msg_len`: usize, 消息长度，不能超过 mq_msgsize
+/// * `msg_prio`: u32, 消息的优先级，数值越大越先被接收
+/// * `abs_timeout`: *const TimeSecs, 绝对的 CLOCK_REALTIME 超时时间，为空时一直等待
+pub async fn syscall_mq_timedsend(args: [usize; 6]) -> SyscallResult {
+    let (msg_ptr, msg_len, prio) = (args[1], args[2], args[3] as u32);
+    if prio as usize >= MQ_PRIO_MAX {
+        return Err(SyscallError::EINVAL);
+    }
+    let deadline = realtime_deadline(args[4] as *const TimeSecs).await?;
+    let file = mqueue_of(args[0]).await?;
+    let mq = (*file).as_any().downcast_ref::<MqueueFile>().unwrap();
+    if !file.writable().await {
+        return Err(SyscallError::EBADF);
+    }
+    let queue = mq.queue();
+    if msg_len > queue.msgsize() {
+        return Err(SyscallError::EMSGSIZE);
+    }
+    check_range(msg_ptr, msg_len).await?;
+    let data: Vec<u8> = if msg_len == 0 {
+        Vec::new()
+    } else {
+        unsafe { core::slice::from_raw_parts(msg_ptr as *const u8, msg_len) }.to_vec()
+    };
+    let non_block = mq.is_non_block();
+    let waker = poll_fn(|cx| Poll::Ready(cx.waker().clone())).await;
+    loop {
+        // 队列已满时在释放锁之前注册，避免错过接收者的通知
+        let sent = poll_fn(|cx| Poll::Ready(queue.send(&data, prio, (!non_block).then_some(cx)))).await;
+        match sent {
+            Ok(notify) => {
+                if let Some(notify) = notify {
+                    deliver_notify(notify);
+                }
+                return Ok(0);
+            }
+            Err(_) if non_block => return Err(SyscallError::EAGAIN),
+            Err(_) => {
+                if deadline.is_some_and(|deadline| current_time() >= deadline) {
+                    queue.unregister_waker(&waker);
+                    return Err(SyscallError::ETIMEDOUT);
+                }
+                sleep_until_woken(deadline, true).await;
+                queue.unregister_waker(&waker);
+            }
+        }
+    }
+}
+
+/// 从消息队列取出优先级最高的消息，队列为空时阻塞，返回消息的长度
+///
+/// # Arguments
+/// * `mqdes`: i32, 消息队列的描述符
+/// * `msg_ptr`: *mut u8, 接收消息的缓冲区
+/// * `msg_len`: usize, 缓冲区长度，不能小于 mq_msgsize
+/// * `msg_prio`: *mut u32, 不为空时写入消息的优先级
+/// * `abs_timeout`: *const TimeSecs, 绝对的 CLOCK_REALTIME 超时时间，为空时一直等待
+pub async fn syscall_mq_timedreceive(args: [usize; 6]) -> SyscallResult {
+    let (msg_ptr, msg_len, prio_ptr) = (args[1], args[2], args[3] as *mut u32);
+    let deadline = realtime_deadline(args[4] as *const TimeSecs).await?;
+    let file = mqueue_of(args[0]).await?;
+    let mq = (*file).as_any().downcast_ref::<MqueueFile>().unwrap();
+    if !file.readable().await {
+        return Err(SyscallError::EBADF);
+    }
+    let queue = mq.queue();
+    if msg_len < queue.msgsize() {
+        return Err(SyscallError::EMSGSIZE);
+    }
+    check_range(msg_ptr, msg_len).await?;
+    if !prio_ptr.is_null() && current_executor().manual_alloc_type_for_lazy(prio_ptr).await.is_err() {
+        return Err(SyscallError::EFAULT);
+    }
+    let buf: &mut [u8] = if msg_len == 0 {
+        &mut []
+    } else {
+        unsafe { core::slice::from_raw_parts_mut(msg_ptr as *mut u8, msg_len) }
+    };
+    let non_block = mq.is_non_block();
+    let waker = poll_fn(|cx| Poll::Ready(cx.waker().clone())).await;
+    let (len, prio) = loop {
+        let received = poll_fn(|cx| Poll::Ready(queue.receive(buf, (!non_block).then_some(cx)))).await;
+        match received {
+            Ok(received) => break received,
+            Err(_) if non_block => return Err(SyscallError::EAGAIN),
+            Err(_) => {
+                if deadline.is_some_and(|deadline| current_time() >= deadline) {
+                    queue.unregister_waker(&waker);
+                    return Err(SyscallError::ETIMEDOUT);
+                }
+                // 等待期间到达的消息直接交给接收者，不触发通知
+                let receiver = queue.waiting_receiver();
+                sleep_until_woken(deadline, true).await;
+                drop(receiver);
+                queue.unregister_waker(&waker);
+            }
+        }
+    };
+    if !prio_ptr.is_null() {
+        unsafe { *prio_ptr = prio };
+    }
+    Ok(len as isize)
+}
+
+/// 注册或移除消息队列的通知，通知在空队列收到消息时发出一次
+///
+/// # Arguments
+/// * `mqdes`: i32, 消息队列的描述符
+/// * `sevp`: *const SigEvent, 通知方式，为空时移除当前进程的注册
+///
+/// 不支持 SIGEV_THREAD：它需要 netlink 套接字；内核还没有信号子系统，SIGEV_SIGNAL 返回 ENOSYS
+pub async fn syscall_mq_notify(args: [usize; 6]) -> SyscallResult {
+    let sevp = args[1] as *const SigEvent;
+    let executor = current_executor();
+    let pid = executor.pid().as_u64();
+    let sevent = if sevp.is_null() {
+        None
+    } else {
+        if executor.manual_alloc_type_for_lazy(sevp).await.is_err() {
+            return Err(SyscallError::EFAULT);
+        }
+        Some(unsafe { *sevp })
+    };
+    let signo = match sevent {
+        None => None,
+        Some(SigEvent { sigev_notify: SIGEV_NONE, .. }) => Some(None),
+        Some(SigEvent {
+            sigev_notify: SIGEV_SIGNAL,
+            sigev_signo,
+            ..
+        }) if (1..=64).contains(&sigev_signo) => {
+            warn!("[mq_notify] SIGEV_SIGNAL is not supported");
+            return Err(SyscallError::ENOSYS);
+        }
+        Some(SigEvent {
+            sigev_notify: SIGEV_THREAD,
+            ..
+        }) => {
+            warn!("[mq_notify] SIGEV_THREAD is not supported");
+            return Err(SyscallError::EINVAL);
+        }
+        Some(_) => return Err(SyscallError::EINVAL),
+    };
+    let file = mqueue_of(args[0]).await?;
+    let queue = (*file).as_any().downcast_ref::<MqueueFile>().unwrap().queue();
+    let Some(signo) = signo else {
+        queue.clear_notify(pid);
+        return Ok(0);
+    };
+    let notify = MqNotify {
+        pid,
+        signo,
+        value: sevent.map_or(0, |sevent| sevent.sigev_value),
+    };
+    match queue.set_notify(notify) {
+        Ok(()) => Ok(0),
+        Err(_) => Err(SyscallError::EBUSY),
+    }
+}
+
+/// 获取或修改消息队列描述符的属性，只有 mq_flags 中的 O_NONBLOCK 可以修改
+///
+/// # Arguments
+/// * `mqdes`: i32, 消息队列的描述符
+/// * `newattr`: *const MqAttr, 不为空时设置新的属性
+/// * `oldattr`: *mut MqAttr, 不为空时写入原先的属性
+pub async fn syscall_mq_getsetattr(args: [usize; 6]) -> SyscallResult {
+    let newattr = args[1] as *const MqAttr;
+    let oldattr = args[2] as *mut MqAttr;
+    let executor = current_executor();
+    let new = if newattr.is_null() {
+        None
+    } else {
+        if executor.manual_alloc_type_for_lazy(newattr).await.is_err() {
+            return Err(SyscallError::EFAULT);
+        }
+        let new = unsafe { *newattr };
+        if new.mq_flags & !(OpenFlags::NON_BLOCK.bits() as i64) != 0 {
+            return Err(SyscallError::EINVAL);
+        }
+        Some(new)
+    };
+    let file = mqueue_of(args[0]).await?;
+    let mq = (*file).as_any().downcast_ref::<MqueueFile>().unwrap();
+    let old = mq.attr();
+    if let Some(new) = new {
+        let status = file.get_status().await - OpenFlags::NON_BLOCK;
+        let non_block = OpenFlags::from_bits_truncate(new.mq_flags as u32);
+        file.set_status(status | non_block).await;
+    }
+    if !oldattr.is_null() {
+        if executor.manual_alloc_type_for_lazy(oldattr).await.is_err() {
+            return Err(SyscallError::EFAULT);
+        }
+        unsafe { *oldattr = old };
+    }
+    Ok(0)
+}
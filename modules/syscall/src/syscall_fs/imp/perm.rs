@@ -0,0 +1,65 @@
+//! 文件的权限检查，使用当前进程的有效用户凭证
+//!
+//! 不支持查询属性的文件系统上的检查一律通过
+use crate::SyscallError;
+use async_fs::api::{LookupFlags, VfsAccess, VfsSetAttr};
+use axerrno::AxError;
+use executor::{current_executor, link::FilePath};
+
+/// 检查当前进程对 `path` 是否有 `access` 权限
+pub async fn check_access(path: &str, access: VfsAccess) -> Result<(), SyscallError> {
+    let cred = &current_executor().cred;
+    match async_fs::api::check_access(path, cred.euid(), cred.egid(), access).await {
+        Ok(()) | Err(AxError::Unsupported) => Ok(()),
+        Err(AxError::NotFound) => Err(SyscallError::ENOENT),
+        Err(AxError::NotADirectory) => Err(SyscallError::ENOTDIR),
+        Err(_) => Err(SyscallError::EACCES),
+    }
+}
+
+/// 检查当前进程能否在 `path` 所在的目录中创建或删除目录项
+pub async fn check_parent_access(path: &FilePath) -> Result<(), SyscallError> {
+    let dir = path.dir().map_err(|_| SyscallError::ENOTDIR)?;
+    check_access(dir, VfsAccess::WRITE | VfsAccess::EXEC).await
+}
+
+/// 删除或重命名 `path` 前的检查
+///
+/// 除了需要对所在目录有写权限外，若目录设置了粘滞位，则只有文件或目录的所有者才能删除
+pub async fn check_remove(path: &FilePath) -> Result<(), SyscallError> {
+    check_parent_access(path).await?;
+    let cred = &current_executor().cred;
+    if cred.is_root() {
+        return Ok(());
+    }
+    let dir = path.dir().map_err(|_| SyscallError::ENOTDIR)?;
+    let (Ok(dir_attr), Ok(attr)) = (
+        async_fs::api::get_attr(dir, LookupFlags::empty()).await,
+        async_fs::api::get_attr(path.path(), LookupFlags::NOFOLLOW).await,
+    ) else {
+        return Ok(());
+    };
+    let euid = cred.euid();
+    if dir_attr.perm().contains(async_fs::api::Permissions::STICKY)
+        && euid != attr.uid()
+        && euid != dir_attr.uid()
+    {
+        return Err(SyscallError::EPERM);
+    }
+    Ok(())
+}
+
+/// 新建的文件属于当前进程，权限为 `mode` 去掉 umask 后的结果
+///
+/// 文件系统不支持修改属性时保持其默认值
+pub async fn init_owner(path: &str, mode: u32) {
+    let executor = current_executor();
+    let umask = executor.fd_manager.get_mask() as u32;
+    let attr = VfsSetAttr {
+        mode: Some(async_fs::api::Permissions::from_bits_truncate((mode & !umask & 0o7777) as u16)),
+        uid: Some(executor.cred.euid()),
+        gid: Some(executor.cred.egid()),
+        ..Default::default()
+    };
+    let _ = async_fs::api::set_attr(path, &attr, LookupFlags::NOFOLLOW).await;
+}
@@ -0,0 +1,119 @@
+//! pidfd_open 与 pidfd_getfd
+extern crate alloc;
+
+use crate::syscall_fs::ctype::pidfd::{new_pidfd, PidFd};
+use crate::{SyscallError, SyscallResult};
+use alloc::sync::Arc;
+use async_fs::api::OpenFlags;
+use executor::{current_executor, Credentials, Executor, FdFlags, PID2PC};
+
+use super::fd_err;
+
+/// pidfd_open 唯一支持的 flag，与 O_NONBLOCK 相同
+pub const PIDFD_NONBLOCK: u32 = 0o4000;
+
+/// 根据文件描述符获取 pidfd 指向的进程
+pub async fn pidfd_target(fd: usize) -> Result<Arc<Executor>, SyscallError> {
+    let file = current_executor()
+        .fd_manager
+        .get(fd)
+        .await
+        .ok_or(SyscallError::EBADF)?;
+    let pidfd = (*file)
+        .as_any()
+        .downcast_ref::<PidFd>()
+        .ok_or(SyscallError::EBADF)?;
+    Ok(pidfd.executor().clone())
+}
+
+/// 打开一个指向进程 `pid` 的 pidfd，它总是带有 FD_CLOEXEC
+///
+/// # Arguments
+/// * `pid`: i32, 目标进程
+/// * `flags`: u32, 0 或 PIDFD_NONBLOCK
+pub async fn syscall_pidfd_open(args: [usize; 6]) -> SyscallResult {
+    let pid = args[0] as i32;
+    let flags = args[1] as u32;
+    if pid <= 0 || flags & !PIDFD_NONBLOCK != 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    let executor = PID2PC
+        .lock()
+        .await
+        .get(&(pid as u64))
+        .cloned()
+        .ok_or(SyscallError::ESRCH)?;
+    new_pidfd(executor, OpenFlags::from_bits_truncate(flags)).await
+}
+
+/// 凭证为 `cred` 的进程能否取得凭证为 `target` 的进程的文件描述符
+fn may_access_fds(cred: &Credentials, target: &Credentials) -> bool {
+    cred.is_root()
+        || (cred.uid() == target.uid()
+            && cred.uid() == target.euid()
+            && cred.gid() == target.gid()
+            && cred.gid() == target.egid())
+}
+
+/// 复制 pidfd 所指进程的文件描述符 `targetfd`，新的文件描述符带有 FD_CLOEXEC
+///
+/// 与 Linux 的 PTRACE_MODE_ATTACH_REALCREDS 检查相似，调用者需要是 root，
+/// 或者实际用户与组和目标进程的实际、有效用户与组都相同
+///
+/// # Arguments
+/// * `pidfd`: usize
+/// * `targetfd`: usize, 目标进程中的文件描述符
+/// * `flags`: u32, 目前必须为 0
+pub async fn syscall_pidfd_getfd(args: [usize; 6]) -> SyscallResult {
+    let pidfd = args[0];
+    let target_fd = args[1];
+    let flags = args[2] as u32;
+    if flags != 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    let target = pidfd_target(pidfd).await?;
+    let process = current_executor();
+    if !may_access_fds(&process.cred, &target.cred) {
+        return Err(SyscallError::EPERM);
+    }
+    if target.get_zombie() {
+        return Err(SyscallError::ESRCH);
+    }
+    let file = target
+        .fd_manager
+        .get(target_fd)
+        .await
+        .ok_or(SyscallError::EBADF)?;
+    let fd = process
+        .fd_manager
+        .alloc(file, FdFlags::CLOEXEC)
+        .await
+        .map_err(fd_err)?;
+    Ok(fd as isize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cred(uid: (u32, u32), gid: (u32, u32)) -> Credentials {
+        let cred = Credentials::root();
+        cred.set_uid(uid.0, uid.1);
+        cred.set_gid(gid.0, gid.1);
+        cred
+    }
+
+    #[test]
+    fn getfd_permission() {
+        let root = Credentials::root();
+        let user = cred((1000, 1000), (100, 100));
+        assert!(may_access_fds(&root, &user));
+        assert!(may_access_fds(&user, &cred((1000, 1000), (100, 100))));
+        assert!(!may_access_fds(&user, &root));
+        // 目标的有效用户或组不同（如 set-user-ID 程序）时不允许
+        assert!(!may_access_fds(&user, &cred((1000, 0), (100, 100))));
+        assert!(!may_access_fds(&user, &cred((1000, 1000), (100, 0))));
+        // 有效用户为 root 的调用者总是被允许
+        assert!(may_access_fds(&cred((1000, 0), (100, 100)), &root));
+    }
+}
@@ -1,17 +1,34 @@
-use axfs::api::FileIO;
-use axhal::{mem::VirtAddr, time::current_ticks};
-use async_executor::current_executor;
-use axsignal::signal_no::SignalNo;
-use bitflags::bitflags;
+//! poll/ppoll/select/pselect6
+//!
+//! 每一轮等待先向所有文件注册当前任务的 waker，再检查它们的就绪状态，没有就绪的文件时休眠，
+//! 直到某个文件的状态变化、超时或者被其他原因唤醒，然后重新检查。
+//! 注册在检查之前，因此两者之间发生的变化同样会唤醒任务，不会丢失。
+//!
+//! 内核尚不支持信号，等待不会被信号打断，只会因为文件事件或者超时而返回，不会返回 EINTR。
 extern crate alloc;
-use crate::{SyscallError, SyscallResult, TimeSecs, TimeVal};
-use alloc::{sync::Arc, vec::Vec};
+
+use crate::{SyscallError, SyscallResult, TimeSecs};
+use alloc::{sync::Arc, vec, vec::Vec};
+use async_fs::api::{FileIO, FileIOType};
+use axhal::{
+    mem::VirtAddr,
+    time::{current_time, TimeValue},
+};
+use axlog::debug;
+use bitflags::bitflags;
+use core::future::poll_fn;
+use core::task::Poll;
+use core::time::Duration;
+use executor::current_executor;
+
 bitflags! {
     /// 在文件上等待或者发生过的事件
-    #[derive(Clone, Copy,Debug)]
+    #[derive(Clone, Copy, Debug)]
     pub struct PollEvents: u16 {
         /// 可读
         const IN = 0x0001;
+        /// 有紧急数据可读
+        const PRI = 0x0002;
         /// 可写
         const OUT = 0x0004;
         /// 错误
@@ -23,14 +40,6 @@ bitflags! {
     }
 }
 
-#[derive(Default)]
-/// file set used for ppoll
-struct PpollFdSet {
-    files: Vec<Arc<FileIO>>,
-    fds: Vec<usize>,
-    shadow_bitset: ShadowBitset,
-}
-
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 /// file descriptor used for poll
@@ -43,368 +52,400 @@ pub struct PollFd {
     pub revents: PollEvents,
 }
 
-/// 定义一个bitset,用于查找掩码
-struct ShadowBitset {
-    /// start address of the bitset which is in user space
-    addr: *mut usize,
-    /// 是包含的bit数目,而不是字节数目
-    len: usize,
+/// 不会主动唤醒等待者、就绪状态又可能变化的文件（如标准输入）重新检查就绪状态的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// 一个被等待的文件
+struct PollEntry {
+    fd: usize,
+    /// fd 无效时为 None
+    file: Option<Arc<dyn FileIO>>,
+    /// 关心的事件，只有这些事件会被报告
+    events: PollEvents,
+    /// 发生的事件
+    revents: PollEvents,
 }
 
-impl Default for ShadowBitset {
-    fn default() -> Self {
-        Self {
-            addr: core::ptr::null_mut(),
-            len: 0,
-        }
+/// 检查文件当前发生的事件
+pub(crate) async fn file_events(file: &dyn FileIO) -> PollEvents {
+    let mut revents = PollEvents::empty();
+    if file.ready_to_read().await {
+        revents |= PollEvents::IN;
     }
-}
-
-impl ShadowBitset {
-    /// create a new bitset
-    pub fn new(addr: *mut usize, len: usize) -> Self {
-        Self { addr, len }
+    if file.ready_to_write().await {
+        revents |= PollEvents::OUT;
     }
-
-    /// check if the index is set
-    pub fn check(&self, index: usize) -> bool {
-        if index >= self.len {
-            return false;
-        }
-        // 因为一次add会移动八个字节,所以这里需要除以64,即8个字节,每一个字节8位
-        let byte_index = index / 64;
-        let bit_index = index & 0x3f;
-        unsafe { *self.addr.add(byte_index) & (1 << bit_index) != 0 }
+    if file.in_exceptional_conditions().await {
+        revents |= PollEvents::PRI;
     }
+    if file.is_hang_up().await {
+        revents |= PollEvents::HUP;
+    }
+    revents
+}
 
-    /// set the index in the bitset
-    pub fn set(&mut self, index: usize) {
-        if index >= self.len {
-            return;
-        }
-        let byte_index = index / 64;
-        let bit_index = index & 0x3f;
-        unsafe {
-            *self.addr.add(byte_index) |= 1 << bit_index;
-        }
+/// 在 `now` 开始休眠时最晚应被定时器唤醒的时间，None 表示只能被文件唤醒
+fn wake_time(now: TimeValue, deadline: Option<TimeValue>, notified: bool) -> Option<TimeValue> {
+    if notified {
+        deadline
+    } else {
+        let next = now + POLL_INTERVAL;
+        Some(deadline.map_or(next, |deadline| deadline.min(next)))
     }
+}
 
-    // 清空自己
-    pub fn clear(&self) {
-        for i in 0..=(self.len - 1) / 64 {
-            unsafe {
-                *(self.addr.add(i)) = 0;
-            }
+/// 休眠直到被唤醒或者到达 `deadline`，`deadline` 为 None 时只能被唤醒
+///
+/// 调用前应已将当前任务的 waker 注册到关心的文件上。`notified` 为 false 表示其中有文件不会主动唤醒等待者，
+/// 此时最多休眠 [`POLL_INTERVAL`]
+pub(crate) async fn sleep_until_woken(deadline: Option<TimeValue>, notified: bool) {
+    match wake_time(current_time(), deadline, notified) {
+        Some(wake_at) => {
+            executor::sleep_until(wake_at).await;
+        }
+        None => {
+            let mut pending = true;
+            poll_fn(|_| {
+                if core::mem::take(&mut pending) {
+                    Poll::Pending
+                } else {
+                    Poll::Ready(())
+                }
+            })
+            .await
         }
     }
+}
 
-    /// check if the bitset is valid
-    ///
-    /// if the addr is null, it is invalid
-    pub fn valid(&self) -> bool {
-        self.addr as usize != 0
-    }
+/// 文件的就绪状态是否不会变化，普通文件与目录属于这种情况，不需要定时重新检查
+async fn has_fixed_events(file: &dyn FileIO) -> bool {
+    matches!(file.get_type().await, FileIOType::FileDesc | FileIOType::DirDesc)
 }
 
-/// 实现ppoll系统调用
+/// 等待 `entries` 中的文件发生关心的事件，或者到达 `deadline`，返回发生了事件的文件数目
 ///
-/// fds：一个PollFd列表
-/// expire_time：时间戳,用来记录是否超时
-///
-/// 返回值：(usize, Vec<PollFd>) 第一个参数遵守 ppoll 系统调用的返回值约定,第二个参数为返回的 `PollFd` 列表
-fn ppoll(mut fds: Vec<PollFd>, expire_time: usize) -> (isize, Vec<PollFd>) {
+/// `deadline` 为 None 时一直等待
+async fn do_poll(entries: &mut [PollEntry], deadline: Option<TimeValue>) -> usize {
+    let waker = poll_fn(|cx| Poll::Ready(cx.waker().clone())).await;
+    let mut fixed = Vec::with_capacity(entries.len());
+    for entry in entries.iter() {
+        fixed.push(match &entry.file {
+            Some(file) => has_fixed_events(file.as_ref()).await,
+            None => true,
+        });
+    }
     loop {
-        // 满足事件要求而被触发的事件描述符数量
-        let mut set: isize = 0;
-        let process = current_executor();
-        for poll_fd in &mut fds {
-            let fd_table = process.fd_manager.fd_table.lock();
-            if let Some(file) = fd_table[poll_fd.fd as usize].as_ref() {
-                poll_fd.revents = PollEvents::empty();
-                // let file = file.lock();
-                if file.in_exceptional_conditions() {
-                    poll_fd.revents |= PollEvents::ERR;
-                }
-                if file.is_hang_up() {
-                    poll_fd.revents |= PollEvents::HUP;
-                }
-                if poll_fd.events.contains(PollEvents::IN) && file.ready_to_read() {
-                    poll_fd.revents |= PollEvents::IN;
+        // 只有就绪状态会变化、又不会主动唤醒等待者的文件需要定时重新检查
+        let notified = poll_fn(|cx| {
+            let mut notified = true;
+            for (entry, fixed) in entries.iter().zip(&fixed) {
+                if let Some(file) = &entry.file {
+                    notified &= file.register_ready_waker(cx) || *fixed;
                 }
-                if poll_fd.events.contains(PollEvents::OUT) && file.ready_to_write() {
-                    poll_fd.revents |= PollEvents::OUT;
-                }
-                // 如果返回事件不为空,代表有响应
-                if !poll_fd.revents.is_empty() {
-                    set += 1;
-                }
-            } else {
-                // 不存在也是一种响应
-                poll_fd.revents = PollEvents::ERR;
+            }
+            Poll::Ready(notified)
+        })
+        .await;
+
+        let mut set = 0;
+        for entry in entries.iter_mut() {
+            entry.revents = match &entry.file {
+                Some(file) => file_events(file.as_ref()).await & entry.events,
+                None => PollEvents::NVAL,
+            };
+            if !entry.revents.is_empty() {
                 set += 1;
             }
         }
-        if set > 0 {
-            return (set, fds);
+        let expired = deadline.is_some_and(|deadline| current_time() >= deadline);
+        if set == 0 && !expired {
+            // TODO: 内核支持信号后，还需要在收到未被屏蔽的信号时返回 EINTR
+            sleep_until_woken(deadline, notified).await;
+        }
+        for file in entries.iter().filter_map(|entry| entry.file.as_ref()) {
+            file.unregister_ready_waker(&waker);
         }
-        if current_ticks() as usize > expire_time {
-            // 过期了,直接返回
-            return (0, fds);
+        if set > 0 || expired {
+            return set;
         }
-        yield_now_task();
+    }
+}
+
+/// 检查用户给出的相对超时时间，负数或者纳秒数超过一秒时返回 EINVAL
+fn relative_timeout(sec: usize, nsec: usize) -> Result<Duration, SyscallError> {
+    if (sec as isize) < 0 || nsec >= 1_000_000_000 {
+        return Err(SyscallError::EINVAL);
+    }
+    Ok(Duration::new(sec as u64, nsec as u32))
+}
+
+/// 将相对的超时时间转换为截止时间，时间不合法时返回 EINVAL
+pub(crate) fn deadline_after(sec: usize, nsec: usize) -> Result<TimeValue, SyscallError> {
+    Ok(current_time() + relative_timeout(sec, nsec)?)
+}
 
-        if process.have_signals().is_some() {
-            // 有信号,此时停止处理,直接返回
-            return (0, fds);
+/// 读取用户给出的 `struct timespec` 超时时间，空指针表示一直等待
+pub(crate) async fn timespec_deadline(timeout: *const TimeSecs) -> Result<Option<TimeValue>, SyscallError> {
+    if timeout.is_null() {
+        return Ok(None);
+    }
+    if current_executor().manual_alloc_type_for_lazy(timeout).await.is_err() {
+        return Err(SyscallError::EFAULT);
+    }
+    let timeout = unsafe { *timeout };
+    deadline_after(timeout.tv_sec, timeout.tv_nsec).map(Some)
+}
+
+async fn ppoll(ufds: *mut PollFd, nfds: usize, deadline: Option<TimeValue>) -> SyscallResult {
+    let process = current_executor();
+    if nfds as u64 > process.fd_manager.get_limit() {
+        return Err(SyscallError::EINVAL);
+    }
+    if nfds > 0 {
+        let start: VirtAddr = (ufds as usize).into();
+        let end = start + nfds * core::mem::size_of::<PollFd>();
+        if process.manual_alloc_range_for_lazy(start, end).await.is_err() {
+            return Err(SyscallError::EFAULT);
         }
     }
+    let mut fds: Vec<PollFd> = (0..nfds).map(|i| unsafe { *ufds.add(i) }).collect();
+
+    // 负数的 fd 被忽略，其 revents 为 0
+    let mut entries = Vec::new();
+    for poll_fd in fds.iter().filter(|poll_fd| poll_fd.fd >= 0) {
+        let fd = poll_fd.fd as usize;
+        entries.push(PollEntry {
+            fd,
+            file: process.fd_manager.get(fd).await,
+            // 错误与挂起总是会被报告
+            events: poll_fd.events | PollEvents::ERR | PollEvents::HUP,
+            revents: PollEvents::empty(),
+        });
+    }
+    let set = do_poll(&mut entries, deadline).await;
+
+    let mut entries = entries.iter();
+    for poll_fd in fds.iter_mut() {
+        poll_fd.revents = if poll_fd.fd >= 0 {
+            entries.next().unwrap().revents
+        } else {
+            PollEvents::empty()
+        };
+    }
+    // 将得到的fd存储到原先的指针中
+    for (i, poll_fd) in fds.iter().enumerate() {
+        unsafe {
+            *(ufds.add(i)) = *poll_fd;
+        }
+    }
+    Ok(set as isize)
 }
 
 /// 实现ppoll系统调用
 ///
-/// 其中timeout是一段相对时间,需要计算出相对于当前时间戳的绝对时间戳
+/// 其中timeout是一段相对时间,为空时一直等待
 ///
 /// # Arguments
 /// * `ufds` - *mut PollFd
 /// * `nfds` - usize
 /// * `timeout` - *const TimeSecs
-/// * `mask` - usize
-pub fn syscall_ppoll(args: [usize; 6]) -> SyscallResult {
+/// * `mask` - usize, 目前内核不支持信号,因此被忽略
+pub async fn syscall_ppoll(args: [usize; 6]) -> SyscallResult {
     let ufds = args[0] as *mut PollFd;
     let nfds = args[1];
     let timeout = args[2] as *const TimeSecs;
     let _mask = args[3];
-    let process = current_executor();
-
-    let start: VirtAddr = (ufds as usize).into();
-    let end = start + nfds * core::mem::size_of::<PollFd>();
-    if process.manual_alloc_range_for_lazy(start, end).is_err() {
-        return Err(SyscallError::EFAULT);
-    }
-
-    let mut fds: Vec<PollFd> = Vec::new();
-
-    for i in 0..nfds {
-        unsafe {
-            fds.push(*(ufds.add(i)));
-        }
-    }
-
-    let expire_time = if timeout as usize != 0 {
-        if process.manual_alloc_type_for_lazy(timeout).is_err() {
-            return Err(SyscallError::EFAULT);
-        }
-        current_ticks() as usize + unsafe { (*timeout).get_ticks() }
-    } else {
-        usize::MAX
-    };
-
-    let (set, ret_fds) = ppoll(fds, expire_time);
-    // 将得到的fd存储到原先的指针中
-    for (i, fd) in ret_fds.iter().enumerate() {
-        unsafe {
-            *(ufds.add(i)) = *fd;
-        }
-    }
-    Ok(set)
+    let deadline = timespec_deadline(timeout).await?;
+    ppoll(ufds, nfds, deadline).await
 }
 
 /// 实现poll系统调用
 /// # Arguments
 /// * `ufds` - *mut PollFd
 /// * `nfds` - usize
-/// * `timeout_msecs` - usize
+/// * `timeout_msecs` - i32, 为负数时一直等待
 #[cfg(target_arch = "x86_64")]
-pub fn syscall_poll(args: [usize; 6]) -> SyscallResult {
+pub async fn syscall_poll(args: [usize; 6]) -> SyscallResult {
     let ufds = args[0] as *mut PollFd;
     let nfds = args[1];
-    let timeout_msecs = args[2];
-    let process = current_process();
+    let timeout_msecs = args[2] as i32;
+    let deadline = if timeout_msecs < 0 {
+        None
+    } else {
+        Some(current_time() + Duration::from_millis(timeout_msecs as u64))
+    };
+    ppoll(ufds, nfds, deadline).await
+}
 
-    let start: VirtAddr = (ufds as usize).into();
-    let end = start + nfds * core::mem::size_of::<PollFd>();
-    if process.manual_alloc_range_for_lazy(start, end).is_err() {
-        return Err(SyscallError::EFAULT);
-    }
+/// 用户空间中的 fd_set 位图
+struct FdSet {
+    addr: *mut usize,
+    bits: Vec<usize>,
+}
 
-    let mut fds: Vec<PollFd> = Vec::new();
+impl FdSet {
+    const BITS: usize = usize::BITS as usize;
 
-    for i in 0..nfds {
-        unsafe {
-            fds.push(*(ufds.add(i)));
+    /// 读取地址 `addr` 处包含 `nfds` 个 fd 的位图，空指针表示不关心这一类事件
+    async fn read(addr: *mut usize, nfds: usize) -> Result<Option<Self>, SyscallError> {
+        if addr.is_null() {
+            return Ok(None);
         }
+        let words = nfds.div_ceil(Self::BITS);
+        let start: VirtAddr = (addr as usize).into();
+        let end = start + words * core::mem::size_of::<usize>();
+        if current_executor().manual_alloc_range_for_lazy(start, end).await.is_err() {
+            return Err(SyscallError::EFAULT);
+        }
+        let mut bits: Vec<usize> = (0..words).map(|i| unsafe { *addr.add(i) }).collect();
+        // 只关心前 nfds 个 fd
+        if nfds % Self::BITS != 0 {
+            bits[words - 1] &= (1 << (nfds % Self::BITS)) - 1;
+        }
+        Ok(Some(Self { addr, bits }))
     }
-    let expire_time = current_ticks() as usize
-        + crate::TimeVal::from_micro(timeout_msecs).turn_to_ticks() as usize;
 
-    let (set, ret_fds) = ppoll(fds, expire_time);
-    // 将得到的fd存储到原先的指针中
-    for (i, fd) in ret_fds.iter().enumerate() {
-        unsafe {
-            *(ufds.add(i)) = *fd;
+    fn contains(&self, fd: usize) -> bool {
+        self.bits[fd / Self::BITS] & (1 << (fd % Self::BITS)) != 0
+    }
+
+    /// 将结果写回用户空间
+    fn write(&self, result: &[usize]) {
+        for (i, word) in result.iter().enumerate() {
+            unsafe {
+                *self.addr.add(i) = *word;
+            }
         }
     }
-    Ok(set)
 }
 
-/// 根据给定的地址和长度新建一个fd set,包括文件描述符指针数组,文件描述符数值数组,以及一个bitset
-fn init_fd_set(addr: *mut usize, len: usize) -> Result<PpollFdSet, SyscallError> {
+/// select 的三个集合分别对应的 poll 事件，与 Linux 的 POLLIN_SET、POLLOUT_SET、POLLEX_SET 相同
+const SELECT_EVENTS: [PollEvents; 3] = [
+    PollEvents::IN.union(PollEvents::HUP).union(PollEvents::ERR),
+    PollEvents::OUT.union(PollEvents::ERR),
+    PollEvents::PRI,
+];
+
+async fn select(
+    nfds: usize,
+    fd_sets: [*mut usize; 3],
+    deadline: Option<TimeValue>,
+) -> SyscallResult {
     let process = current_executor();
-    if len >= process.fd_manager.get_limit() as usize {
-        axlog::error!(
-            "[pselect6()] len {len} >= limit {}",
-            process.fd_manager.get_limit()
-        );
+    if nfds as u64 > process.fd_manager.get_limit() {
         return Err(SyscallError::EINVAL);
     }
-
-    let shadow_bitset = ShadowBitset::new(addr, len);
-    if addr.is_null() {
-        return Ok(PpollFdSet {
-            shadow_bitset,
-            ..Default::default()
-        });
+    let mut sets = Vec::new();
+    for addr in fd_sets {
+        sets.push(FdSet::read(addr, nfds).await?);
     }
 
-    let start: VirtAddr = (addr as usize).into();
-    let end = start + (len + 7) / 8;
-    if process.manual_alloc_range_for_lazy(start, end).is_err() {
-        axlog::error!("[pselect6()] addr {addr:?} invalid");
-        return Err(SyscallError::EFAULT);
+    let mut entries = Vec::new();
+    for fd in 0..nfds {
+        let mut events = PollEvents::empty();
+        for (set, set_events) in sets.iter().zip(SELECT_EVENTS) {
+            if set.as_ref().is_some_and(|set| set.contains(fd)) {
+                events |= set_events;
+            }
+        }
+        if events.is_empty() {
+            continue;
+        }
+        let Some(file) = process.fd_manager.get(fd).await else {
+            return Err(SyscallError::EBADF);
+        };
+        entries.push(PollEntry {
+            fd,
+            file: Some(file),
+            events,
+            revents: PollEvents::empty(),
+        });
     }
-
-    let mut fds = Vec::new();
-    let mut files = Vec::new();
-    for fd in 0..len {
-        if shadow_bitset.check(fd) {
-            let fd_table = process.fd_manager.fd_table.lock();
-            if let Some(file) = fd_table[fd].as_ref() {
-                files.push(Arc::clone(file));
-                fds.push(fd);
-            } else {
-                return Err(SyscallError::EBADF);
+    debug!("[select()] fds: {:?}", entries.iter().map(|entry| entry.fd).collect::<Vec<_>>());
+    do_poll(&mut entries, deadline).await;
+
+    let mut count = 0;
+    for (set, set_events) in sets.iter().zip(SELECT_EVENTS) {
+        let Some(set) = set else {
+            continue;
+        };
+        let mut result = vec![0usize; set.bits.len()];
+        for entry in entries.iter() {
+            if set.contains(entry.fd) && entry.revents.intersects(set_events) {
+                result[entry.fd / FdSet::BITS] |= 1 << (entry.fd % FdSet::BITS);
+                count += 1;
             }
         }
+        set.write(&result);
     }
-
-    shadow_bitset.clear();
-
-    Ok(PpollFdSet {
-        files,
-        fds,
-        shadow_bitset,
-    })
+    Ok(count)
 }
 
-// 实现 select 系统调用
+/// 实现 select 系统调用
 /// # Arguments
 /// * `nfds` - usize
 /// * `readfds` - *mut usize
 /// * `writefds` - *mut usize
 /// * `exceptfds` - *mut usize
-/// * `timeout` - *const TimeSecs
+/// * `timeout` - *const TimeVal, 为空时一直等待
 #[cfg(target_arch = "x86_64")]
-pub fn syscall_select(mut args: [usize; 6]) -> SyscallResult {
-    args[5] = 0;
-    syscall_pselect6(args)
+pub async fn syscall_select(args: [usize; 6]) -> SyscallResult {
+    let timeout = args[4] as *const crate::TimeVal;
+    let deadline = if timeout.is_null() {
+        None
+    } else {
+        if current_executor().manual_alloc_type_for_lazy(timeout).await.is_err() {
+            return Err(SyscallError::EFAULT);
+        }
+        let timeout = unsafe { *timeout };
+        if timeout.usec >= 1_000_000 {
+            return Err(SyscallError::EINVAL);
+        }
+        Some(deadline_after(timeout.sec, timeout.usec * 1000)?)
+    };
+    let fd_sets = [args[1] as *mut usize, args[2] as *mut usize, args[3] as *mut usize];
+    select(args[0], fd_sets, deadline).await
 }
+
 /// 实现pselect6系统调用
 /// # Arguments
 /// * `nfds` - usize
 /// * `readfds` - *mut usize
 /// * `writefds` - *mut usize
 /// * `exceptfds` - *mut usize
-/// * `timeout` - *const TimeVal
-/// * `mask` - usize
-pub fn syscall_pselect6(args: [usize; 6]) -> SyscallResult {
-    let nfds = args[0];
-    let readfds = args[1] as *mut usize;
-    let writefds = args[2] as *mut usize;
-    let exceptfds = args[3] as *mut usize;
-    let timeout = args[4] as *const TimeVal;
-    let _mask = args[5];
-    let (rfiles, rfds, mut rset) = match init_fd_set(readfds, nfds) {
-        Ok(ans) => (ans.files, ans.fds, ans.shadow_bitset),
-        Err(e) => return Err(e),
-    };
-    let (wfiles, wfds, mut wset) = match init_fd_set(writefds, nfds) {
-        Ok(ans) => (ans.files, ans.fds, ans.shadow_bitset),
-        Err(e) => return Err(e),
-    };
-    let (efiles, efds, mut eset) = match init_fd_set(exceptfds, nfds) {
-        Ok(ans) => (ans.files, ans.fds, ans.shadow_bitset),
-        Err(e) => return Err(e),
-    };
-    let process = current_executor();
-
-    let expire_time = if !timeout.is_null() {
-        if process
-            .memory_set
-            .lock()
-            .lock()
-            .manual_alloc_type_for_lazy(timeout)
-            .is_err()
-        {
-            axlog::error!("[pselect6()] timeout addr {timeout:?} invalid");
-            return Err(SyscallError::EFAULT);
-        }
-        current_ticks() as usize + unsafe { (*timeout).turn_to_ticks() as usize }
-    } else {
-        usize::MAX
-    };
-
-    axlog::debug!("[pselect6()]: r: {rfds:?}, w: {wfds:?}, e: {efds:?}");
+/// * `timeout` - *const TimeSecs, 为空时一直等待
+/// * `sigmask` - usize, 目前内核不支持信号,因此被忽略
+pub async fn syscall_pselect6(args: [usize; 6]) -> SyscallResult {
+    let timeout = args[4] as *const TimeSecs;
+    let _sigmask = args[5];
+    let deadline = timespec_deadline(timeout).await?;
+    let fd_sets = [args[1] as *mut usize, args[2] as *mut usize, args[3] as *mut usize];
+    select(args[0], fd_sets, deadline).await
+}
 
-    loop {
-        // Why yield first?
-        //
-        // 当用户程序中出现如下结构：
-        // while (true) { select(); }
-        // 如果存在 ready 的 fd,select() 立即返回,
-        // 但并不完全满足用户程序的要求,可能出现死循环。
-        //
-        // 因此先 yield 避免其他进程 starvation。
-        //
-        // 可见 iperf 测例。
-        yield_now_task();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let mut set = 0;
-        if rset.valid() {
-            for i in 0..rfds.len() {
-                if rfiles[i].ready_to_read() {
-                    rset.set(rfds[i]);
-                    set += 1;
-                }
-            }
-        }
-        if wset.valid() {
-            for i in 0..wfds.len() {
-                if wfiles[i].ready_to_write() {
-                    wset.set(wfds[i]);
-                    set += 1;
-                }
-            }
-        }
-        if eset.valid() {
-            for i in 0..efds.len() {
-                if efiles[i].in_exceptional_conditions() {
-                    eset.set(efds[i]);
-                    set += 1;
-                }
-            }
-        }
-        if set > 0 {
-            return Ok(set as isize);
-        }
-        if current_ticks() as usize > expire_time {
-            return Ok(0);
-        }
-        // TODO: fix this and use mask to ignore specific signal
+    #[test]
+    fn relative_timeout_is_validated() {
+        assert_eq!(relative_timeout(0, 0), Ok(Duration::ZERO));
+        assert_eq!(relative_timeout(2, 999_999_999), Ok(Duration::new(2, 999_999_999)));
+        assert_eq!(relative_timeout(0, 1_000_000_000), Err(SyscallError::EINVAL));
+        assert_eq!(relative_timeout(-1isize as usize, 0), Err(SyscallError::EINVAL));
+    }
 
-        if let Some(signalno) = process.have_signals() {
-            if signalno == SignalNo::SIGKILL as usize {
-                return Err(SyscallError::EINTR);
-            }
-        }
+    #[test]
+    fn wake_time_respects_deadline() {
+        let now = Duration::from_secs(100);
+        let soon = now + POLL_INTERVAL / 2;
+        let later = now + Duration::from_secs(5);
+        // 所有文件都会主动唤醒时只需等待截止时间
+        assert_eq!(wake_time(now, None, true), None);
+        assert_eq!(wake_time(now, Some(later), true), Some(later));
+        // 否则定期醒来重新检查，但不晚于截止时间
+        assert_eq!(wake_time(now, None, false), Some(now + POLL_INTERVAL));
+        assert_eq!(wake_time(now, Some(later), false), Some(now + POLL_INTERVAL));
+        assert_eq!(wake_time(now, Some(soon), false), Some(soon));
     }
 }
@@ -0,0 +1,503 @@
+//! 负责在内核中直接传输文件数据的系统调用，包括 sendfile、splice、tee 与 copy_file_range
+//!
+//! 数据经由内核缓冲区在两个 [`FileIO`] 之间分块搬运，不经过用户态的缓冲区。
+//! 每搬运完一块就主动让出一次，大文件的拷贝不会一直占用当前的 CPU
+extern crate alloc;
+
+use crate::{
+    syscall_fs::ctype::{file::file_identity, FileDesc},
+    SyscallError, SyscallResult,
+};
+use alloc::{sync::Arc, vec};
+use async_fs::api::{FileIO, FileIOType, SeekFrom};
+use axerrno::{AxError, AxResult};
+use axlog::{debug, info};
+use executor::{current_executor, yield_now};
+
+/// 每次搬运的数据块的大小
+const CHUNK_SIZE: usize = 0x10000;
+
+/// Attempt to move pages instead of copying.
+const SPLICE_F_MOVE: usize = 1;
+/// Do not block on I/O.
+const SPLICE_F_NONBLOCK: usize = 2;
+/// More data will be coming in a subsequent splice.
+const SPLICE_F_MORE: usize = 4;
+/// Unused for splice.
+const SPLICE_F_GIFT: usize = 8;
+
+fn io_err(err: AxError) -> SyscallError {
+    match err {
+        AxError::WouldBlock => SyscallError::EAGAIN,
+        // 管道的读端已经关闭
+        AxError::ConnectionReset => SyscallError::EPIPE,
+        AxError::IsADirectory => SyscallError::EISDIR,
+        AxError::StorageFull => SyscallError::ENOSPC,
+        AxError::BadAddress => SyscallError::EFAULT,
+        AxError::InvalidInput | AxError::Unsupported => SyscallError::EINVAL,
+        _ => SyscallError::EIO,
+    }
+}
+
+/// 获取 `fd` 对应的文件，并检查其是否可读或可写
+async fn file_of(fd: usize, read: bool) -> Result<Arc<dyn FileIO>, SyscallError> {
+    let Some(file) = current_executor().fd_manager.get(fd).await else {
+        return Err(SyscallError::EBADF);
+    };
+    let permitted = if read {
+        file.readable().await
+    } else {
+        file.writable().await
+    };
+    if !permitted {
+        return Err(SyscallError::EBADF);
+    }
+    Ok(file)
+}
+
+/// 读取用户传入的 `loff_t` 偏移量，指针为空时返回 None
+async fn user_offset(ptr: *mut i64) -> Result<Option<u64>, SyscallError> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    if current_executor()
+        .manual_alloc_type_for_lazy(ptr)
+        .await
+        .is_err()
+    {
+        return Err(SyscallError::EFAULT);
+    }
+    match unsafe { *ptr } {
+        off if off < 0 => Err(SyscallError::EINVAL),
+        off => Ok(Some(off as u64)),
+    }
+}
+
+/// 传输结束后，将用户传入的偏移量增加实际传输的字节数
+fn advance_offset(ptr: *mut i64, len: usize) {
+    if !ptr.is_null() {
+        unsafe {
+            *ptr += len as i64;
+        }
+    }
+}
+
+/// 从普通文件的 `pos` 处读取，不使用也不改变文件描述共享的偏移
+async fn read_at(file: &Arc<dyn FileIO>, pos: u64, buf: &mut [u8]) -> AxResult<usize> {
+    match (**file).as_any().downcast_ref::<FileDesc>() {
+        Some(desc) => desc.file.lock().await.read_at(pos, buf).await,
+        None => Err(AxError::Unsupported),
+    }
+}
+
+/// 向普通文件的 `pos` 处写入，不使用也不改变文件描述共享的偏移
+async fn write_at(file: &Arc<dyn FileIO>, pos: u64, buf: &[u8]) -> AxResult<usize> {
+    match (**file).as_any().downcast_ref::<FileDesc>() {
+        Some(desc) => desc.file.lock().await.write_at(pos, buf).await,
+        None => Err(AxError::Unsupported),
+    }
+}
+
+/// 将 `buf` 全部写入 `dst`，`pos` 不为空时从该位置开始写
+///
+/// 返回写入的字节数，以及没有写完时遇到的错误
+async fn write_all(
+    dst: &Arc<dyn FileIO>,
+    pos: Option<u64>,
+    buf: &[u8],
+) -> (usize, Option<AxError>) {
+    let mut written = 0;
+    while written < buf.len() {
+        let res = match pos {
+            Some(pos) => write_at(dst, pos + written as u64, &buf[written..]).await,
+            None => dst.write(&buf[written..]).await,
+        };
+        match res {
+            Ok(0) => return (written, Some(AxError::StorageFull)),
+            Ok(n) => written += n,
+            Err(err) => return (written, Some(err)),
+        }
+    }
+    (written, None)
+}
+
+/// 将 `src` 中至多 `count` 字节的数据搬运到 `dst`，返回实际传输的字节数
+///
+/// `src_pos` 与 `dst_pos` 不为空时在指定的位置读写，这一端必须是普通文件，
+/// 它的文件偏移不会被使用，也不会改变；为空时使用并推进文件自身的偏移。
+///
+/// 源端一次读到的数据不足一块时说明已经到达文件末尾；源端不是普通文件时，
+/// 已经传输了部分数据后不再等待新的数据，与 Linux 上 splice 的行为一致
+async fn transfer(
+    src: &Arc<dyn FileIO>,
+    src_pos: Option<u64>,
+    dst: &Arc<dyn FileIO>,
+    dst_pos: Option<u64>,
+    count: usize,
+) -> Result<usize, SyscallError> {
+    let streaming = src.get_type().await != FileIOType::FileDesc;
+    if (src_pos.is_some() && streaming)
+        || (dst_pos.is_some() && dst.get_type().await != FileIOType::FileDesc)
+    {
+        return Err(SyscallError::ESPIPE);
+    }
+    let mut buf = vec![0u8; count.min(CHUNK_SIZE)];
+    let mut total = 0;
+    while total < count {
+        if total > 0 && streaming && !src.ready_to_read().await {
+            break;
+        }
+        let want = (count - total).min(buf.len());
+        let read = match src_pos {
+            Some(pos) => read_at(src, pos + total as u64, &mut buf[..want]).await,
+            None => src.read(&mut buf[..want]).await,
+        };
+        let read = match read {
+            Ok(n) => n,
+            Err(_) if total > 0 => break,
+            Err(err) => return Err(io_err(err)),
+        };
+        if read == 0 {
+            break;
+        }
+        let (written, err) =
+            write_all(dst, dst_pos.map(|pos| pos + total as u64), &buf[..read]).await;
+        total += written;
+        if written < read {
+            if src_pos.is_none() && !streaming {
+                // 退回没有写出的部分，使源文件的偏移与实际传输的字节数一致
+                let _ = src
+                    .seek(SeekFrom::Current(written as i64 - read as i64))
+                    .await;
+            }
+            return match err {
+                Some(err) if total == 0 => Err(io_err(err)),
+                _ => Ok(total),
+            };
+        }
+        if read < want {
+            break;
+        }
+        yield_now().await;
+    }
+    Ok(total)
+}
+
+/// 功能:在两个文件描述符之间传输数据；
+/// # Arguments
+/// * `out_fd`: usize, 写入数据的文件描述符。
+/// * `in_fd`: usize, 读取数据的文件描述符。
+/// * `offset`: *mut i64, 为空时从 in_fd 当前的偏移开始读取并更新该偏移；
+///   否则从 *offset 处开始读取,不改变 in_fd 的偏移,而是将 *offset 增加传输的字节数。
+/// * `count`: usize, 最多传输的字节数。
+/// 返回值:成功执行,返回传输的字节数。失败,返回-1。
+pub async fn syscall_sendfile64(args: [usize; 6]) -> SyscallResult {
+    let out_fd = args[0];
+    let in_fd = args[1];
+    let offset = args[2] as *mut i64;
+    let count = args[3];
+    info!("sendfile: from {} to {}, count: {}", in_fd, out_fd, count);
+    let in_file = file_of(in_fd, true).await?;
+    let out_file = file_of(out_fd, false).await?;
+    let in_type = in_file.get_type().await;
+    if in_type == FileIOType::DirDesc {
+        return Err(SyscallError::EINVAL);
+    }
+    let in_pos = user_offset(offset).await?;
+    if in_pos.is_some() && in_type == FileIOType::Pipe {
+        return Err(SyscallError::ESPIPE);
+    }
+    let len = transfer(&in_file, in_pos, &out_file, None, count).await?;
+    advance_offset(offset, len);
+    Ok(len as isize)
+}
+
+/// 功能:在两个普通文件之间复制数据；
+/// # Arguments
+/// * `fd_in`: usize, 源文件的文件描述符。
+/// * `off_in`: *mut i64, 为空时使用并更新 fd_in 自身的偏移,否则从 *off_in 处读取并将其增加复制的字节数。
+/// * `fd_out`: usize, 目标文件的文件描述符。
+/// * `off_out`: *mut i64, 与 off_in 类似。
+/// * `len`: usize, 最多复制的字节数。
+/// * `flags`: usize, 必须为 0。
+/// 返回值:成功执行,返回复制的字节数,源文件的偏移超过其大小时返回 0。失败,返回-1。
+pub async fn syscall_copy_file_range(args: [usize; 6]) -> SyscallResult {
+    let fd_in = args[0];
+    let off_in = args[1] as *mut i64;
+    let fd_out = args[2];
+    let off_out = args[3] as *mut i64;
+    let len = args[4];
+    let flags = args[5];
+    info!(
+        "copy_file_range: fd_in: {}, fd_out: {}, len: {}, flags: {}",
+        fd_in, fd_out, len, flags
+    );
+    if flags != 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    let in_file = file_of(fd_in, true).await?;
+    let out_file = file_of(fd_out, false).await?;
+    for file in [&in_file, &out_file] {
+        match file.get_type().await {
+            FileIOType::FileDesc => {}
+            FileIOType::DirDesc => return Err(SyscallError::EISDIR),
+            _ => return Err(SyscallError::EINVAL),
+        }
+    }
+    let in_pos = user_offset(off_in).await?;
+    let out_pos = user_offset(off_out).await?;
+    if len == 0 {
+        return Ok(0);
+    }
+    // 同一个文件内的复制不允许源区间与目标区间重叠，硬链接与不同的打开也是同一个文件
+    if file_identity(&in_file).await == file_identity(&out_file).await {
+        let start_in = match in_pos {
+            Some(pos) => pos,
+            None => in_file.seek(SeekFrom::Current(0)).await.map_err(io_err)?,
+        };
+        let start_out = match out_pos {
+            Some(pos) => pos,
+            None => out_file.seek(SeekFrom::Current(0)).await.map_err(io_err)?,
+        };
+        let len = len as u64;
+        if start_in < start_out.saturating_add(len) && start_out < start_in.saturating_add(len) {
+            debug!("copy_file_range: overlapping ranges in {}", in_file.get_path().await);
+            return Err(SyscallError::EINVAL);
+        }
+    }
+    let copied = transfer(&in_file, in_pos, &out_file, out_pos, len).await?;
+    advance_offset(off_in, copied);
+    advance_offset(off_out, copied);
+    Ok(copied as isize)
+}
+
+/// 检查非阻塞的 splice 与 tee 能否立即进行
+async fn check_nonblock(
+    src: &Arc<dyn FileIO>,
+    dst: &Arc<dyn FileIO>,
+    flags: usize,
+) -> Result<(), SyscallError> {
+    if flags & SPLICE_F_NONBLOCK == 0 {
+        return Ok(());
+    }
+    if src.get_type().await == FileIOType::Pipe
+        && !src.ready_to_read().await
+        && !src.is_hang_up().await
+    {
+        return Err(SyscallError::EAGAIN);
+    }
+    if dst.get_type().await == FileIOType::Pipe && !dst.ready_to_write().await {
+        return Err(SyscallError::EAGAIN);
+    }
+    Ok(())
+}
+
+/// 功能:在管道与另一个文件描述符之间传输数据；
+/// # Arguments
+/// * `fd_in`: usize, 读取数据的文件描述符。
+/// * `off_in`: *mut i64, fd_in 为管道时必须为空；否则为空时使用 fd_in 自身的偏移,不为空时从 *off_in 处读取并将其增加传输的字节数。
+/// * `fd_out`: usize, 写入数据的文件描述符。
+/// * `off_out`: *mut i64, 与 off_in 类似。
+/// * `len`: usize, 最多传输的字节数。
+/// * `flags`: usize, SPLICE_F_MOVE、SPLICE_F_NONBLOCK、SPLICE_F_MORE 与 SPLICE_F_GIFT 的组合。
+/// 返回值:成功执行,返回传输的字节数。失败,返回-1。
+///
+/// fd_in 与 fd_out 中至少有一个是管道
+pub async fn syscall_splice(args: [usize; 6]) -> SyscallResult {
+    let fd_in = args[0];
+    let off_in = args[1] as *mut i64;
+    let fd_out = args[2];
+    let off_out = args[3] as *mut i64;
+    let len = args[4];
+    let flags = args[5];
+    info!(
+        "splice: fd_in: {}, fd_out: {}, len: {}, flags: {:#x}",
+        fd_in, fd_out, len, flags
+    );
+    if flags & !(SPLICE_F_MOVE | SPLICE_F_NONBLOCK | SPLICE_F_MORE | SPLICE_F_GIFT) != 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    let in_file = file_of(fd_in, true).await?;
+    let out_file = file_of(fd_out, false).await?;
+    let in_pipe = in_file.get_type().await == FileIOType::Pipe;
+    let out_pipe = out_file.get_type().await == FileIOType::Pipe;
+    if !in_pipe && !out_pipe {
+        return Err(SyscallError::EINVAL);
+    }
+    if Arc::ptr_eq(&in_file, &out_file) {
+        return Err(SyscallError::EINVAL);
+    }
+    if (in_pipe && !off_in.is_null()) || (out_pipe && !off_out.is_null()) {
+        return Err(SyscallError::ESPIPE);
+    }
+    let in_pos = user_offset(off_in).await?;
+    let out_pos = user_offset(off_out).await?;
+    if len == 0 {
+        return Ok(0);
+    }
+    check_nonblock(&in_file, &out_file, flags).await?;
+    let moved = transfer(&in_file, in_pos, &out_file, out_pos, len).await?;
+    advance_offset(off_in, moved);
+    advance_offset(off_out, moved);
+    Ok(moved as isize)
+}
+
+/// 功能:复制一个管道中的数据到另一个管道,但不消耗源管道中的数据；
+/// # Arguments
+/// * `fd_in`: usize, 源管道的读端。
+/// * `fd_out`: usize, 目标管道的写端。
+/// * `len`: usize, 最多复制的字节数。
+/// * `flags`: usize, 与 splice 的 flags 相同。
+/// 返回值:成功执行,返回复制的字节数。失败,返回-1。
+pub async fn syscall_tee(args: [usize; 6]) -> SyscallResult {
+    let fd_in = args[0];
+    let fd_out = args[1];
+    let len = args[2];
+    let flags = args[3];
+    info!("tee: fd_in: {}, fd_out: {}, len: {}", fd_in, fd_out, len);
+    if flags & !(SPLICE_F_MOVE | SPLICE_F_NONBLOCK | SPLICE_F_MORE | SPLICE_F_GIFT) != 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    let in_file = file_of(fd_in, true).await?;
+    let out_file = file_of(fd_out, false).await?;
+    if in_file.get_type().await != FileIOType::Pipe
+        || out_file.get_type().await != FileIOType::Pipe
+        || Arc::ptr_eq(&in_file, &out_file)
+    {
+        return Err(SyscallError::EINVAL);
+    }
+    if len == 0 {
+        return Ok(0);
+    }
+    check_nonblock(&in_file, &out_file, flags).await?;
+    let mut buf = vec![0u8; len.min(CHUNK_SIZE)];
+    let peeked = in_file.peek(&mut buf).await.map_err(io_err)?;
+    let (written, err) = write_all(&out_file, None, &buf[..peeked]).await;
+    match err {
+        Some(err) if written == 0 => Err(io_err(err)),
+        _ => Ok(written as isize),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    use alloc::collections::VecDeque;
+    use alloc::vec::Vec;
+    use async_fs::api::async_trait;
+    use spinlock::SpinNoIrq;
+    use test_utils::block_on as run;
+
+    /// 按块产生数据的管道，每次读取至多得到一块
+    struct Chunks(SpinNoIrq<VecDeque<Vec<u8>>>);
+
+    /// 至多接收 `limit` 字节的管道
+    struct Sink {
+        data: SpinNoIrq<Vec<u8>>,
+        limit: usize,
+    }
+
+    #[async_trait]
+    impl FileIO for Chunks {
+        async fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
+            let mut chunks = self.0.lock();
+            let Some(mut chunk) = chunks.pop_front() else {
+                return Ok(0);
+            };
+            let n = chunk.len().min(buf.len());
+            buf[..n].copy_from_slice(&chunk[..n]);
+            if n < chunk.len() {
+                chunks.push_front(chunk.split_off(n));
+            }
+            Ok(n)
+        }
+
+        async fn readable(&self) -> bool {
+            true
+        }
+
+        async fn writable(&self) -> bool {
+            false
+        }
+
+        async fn executable(&self) -> bool {
+            false
+        }
+
+        async fn get_type(&self) -> FileIOType {
+            FileIOType::Pipe
+        }
+
+        async fn ready_to_read(&self) -> bool {
+            !self.0.lock().is_empty()
+        }
+    }
+
+    #[async_trait]
+    impl FileIO for Sink {
+        async fn write(&self, buf: &[u8]) -> AxResult<usize> {
+            let mut data = self.data.lock();
+            let n = buf.len().min(self.limit - data.len());
+            data.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        async fn readable(&self) -> bool {
+            false
+        }
+
+        async fn writable(&self) -> bool {
+            true
+        }
+
+        async fn executable(&self) -> bool {
+            false
+        }
+
+        async fn get_type(&self) -> FileIOType {
+            FileIOType::Pipe
+        }
+    }
+
+    fn pipes(chunks: &[&[u8]], limit: usize) -> (Arc<dyn FileIO>, Arc<Sink>) {
+        let chunks = chunks.iter().map(|chunk| chunk.to_vec()).collect();
+        let sink = Arc::new(Sink {
+            data: SpinNoIrq::new(Vec::new()),
+            limit,
+        });
+        (Arc::new(Chunks(SpinNoIrq::new(chunks))), sink)
+    }
+
+    #[test]
+    fn transfer_in_chunks() {
+        let big = vec![1u8; CHUNK_SIZE + 10];
+        let (src, sink) = pipes(&[&big[..], &[2; 100], &[3; 5]], usize::MAX);
+        let dst: Arc<dyn FileIO> = sink.clone();
+        // 第一块读满，第二块只读到 10 字节，之后不足一块时结束
+        assert_eq!(run(transfer(&src, None, &dst, None, 4 * CHUNK_SIZE)), Ok(CHUNK_SIZE + 10));
+        assert_eq!(*sink.data.lock(), big);
+        // 不超过 count
+        assert_eq!(run(transfer(&src, None, &dst, None, 30)), Ok(30));
+        assert_eq!(run(transfer(&src, None, &dst, None, 100)), Ok(70));
+        assert_eq!(sink.data.lock().len(), CHUNK_SIZE + 110);
+    }
+
+    #[test]
+    fn transfer_stops_at_full_destination() {
+        let (src, sink) = pipes(&[&[1; 10]], 4);
+        let dst: Arc<dyn FileIO> = sink.clone();
+        assert_eq!(run(transfer(&src, None, &dst, None, 10)), Ok(4));
+        let (src, _) = pipes(&[&[1; 10]], 0);
+        assert_eq!(run(transfer(&src, None, &dst, None, 10)), Err(SyscallError::ENOSPC));
+    }
+
+    #[test]
+    fn positions_need_regular_files() {
+        let (src, sink) = pipes(&[&[1; 10]], 10);
+        let dst: Arc<dyn FileIO> = sink.clone();
+        assert_eq!(run(transfer(&src, Some(0), &dst, None, 10)), Err(SyscallError::ESPIPE));
+        assert_eq!(run(transfer(&src, None, &dst, Some(0), 10)), Err(SyscallError::ESPIPE));
+        assert!(sink.data.lock().is_empty());
+    }
+}
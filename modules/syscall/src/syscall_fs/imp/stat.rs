@@ -1,14 +1,31 @@
 //! 获取文件系统状态信息
 //!
 
-use crate::{SyscallError, SyscallResult};
-use async_fs::api::Kstat;
+use crate::{
+    syscall_fs::{
+        ctype::file::{inode_of, kstat_from_attr},
+        solve_path_with_flags, AT_EMPTY_PATH, AT_SYMLINK_NOFOLLOW,
+    },
+    FsStat, FsStatx, FsStatxTimestamp, SyscallError, SyscallResult,
+};
+use alloc::string::ToString;
+use async_fs::api::{FileIOType, FileSystemInfo, Kstat, LookupFlags, PathError};
+use axerrno::AxError;
 use axlog::{debug, info};
+use axhal::mem::PAGE_SIZE_4K;
+use executor::link::AT_FDCWD;
 use executor::{
     current_executor,
-    // link::{raw_ptr_to_ref_str, FilePath, AT_FDCWD},
+    link::{get_link_count, raw_ptr_to_ref_str},
 };
 
+extern crate alloc;
+
+/// Do not trigger automounts, which do not exist here.
+const AT_NO_AUTOMOUNT: usize = 0x800;
+/// The synchronization flags of statx.
+const AT_STATX_SYNC_TYPE: usize = 0x6000;
+
 // use crate::syscall_fs::ctype::mount::get_stat_in_fs;
 
 /// 实现 stat 系列系统调用
@@ -19,17 +36,10 @@ pub async fn syscall_fstat(args: [usize; 6]) -> SyscallResult {
     let fd = args[0];
     let kst = args[1] as *mut Kstat;
     let process = current_executor();
-    let fd_table = process.fd_manager.fd_table.lock().await;
-
-    if fd >= fd_table.len() {
-        debug!("fd {} is out of range", fd);
-        return Err(SyscallError::EPERM);
-    }
-    if fd_table[fd].is_none() {
+    let Some(file) = process.fd_manager.get(fd).await else {
         debug!("fd {} is none", fd);
-        return Err(SyscallError::EPERM);
-    }
-    let file = fd_table[fd].clone().unwrap();
+        return Err(SyscallError::EBADF);
+    };
 
     match file.get_stat().await {
         Ok(stat) => {
@@ -46,113 +56,237 @@ pub async fn syscall_fstat(args: [usize; 6]) -> SyscallResult {
     }
 }
 
-// /// 获取文件状态信息，但是给出的是目录 fd 和相对路径。
-// /// # Arguments
-// /// * `dir_fd` - usize
-// /// * `path` - *const u8
-// /// * `kst` - *mut Kstat
-// pub fn syscall_fstatat(args: [usize; 6]) -> SyscallResult {
-//     let dir_fd = args[0];
-//     let path = args[1] as *const u8;
-//     let kst = args[2] as *mut Kstat;
-//     let file_path = if let Ok(file_path) = solve_path(dir_fd, Some(path), false) {
-//         // error!("test {:?}", file_path);
-//         file_path
-//     } else {
-//         // x86 下应用会调用 newfstatat(1, "", {st_mode=S_IFCHR|0620, st_rdev=makedev(0x88, 0xe), ...}, AT_EMPTY_PATH) = 0
-//         // 去尝试检查 STDOUT 的属性。这里暂时先特判，以后再改成真正的 stdout 的属性
-//         let path = unsafe { raw_ptr_to_ref_str(path) };
-//         if path.is_empty() && dir_fd == 1 {
-//             unsafe {
-//                 (*kst).st_mode = 0o20000 | 0o220u32;
-//                 (*kst).st_ino = 1;
-//                 (*kst).st_nlink = 1;
-//             }
-//             return Ok(0);
-//         }
-//         panic!("Wrong path at syscall_fstatat: {}(dir_fd={})", path, dir_fd);
-//     };
-//     info!("path : {}", file_path.path());
-//     if !axfs::api::path_exists(file_path.path()) {
-//         return Err(SyscallError::ENOENT);
-//     }
-//     match get_stat_in_fs(&file_path) {
-//         Ok(stat) => unsafe {
-//             *kst = stat;
-//             info!("stat: {:?}", stat);
-//             Ok(0)
-//         },
-//         Err(error_no) => {
-//             debug!("get stat error: {:?}", error_no);
-//             Err(error_no)
-//         }
-//     }
-// }
-
-// /// 获取文件状态信息
-// /// # Arguments
-// /// * `path` - *const u8
-// /// * `kst` - *mut Kstat
-// #[cfg(target_arch = "x86_64")]
-// pub fn syscall_lstat(args: [usize; 6]) -> SyscallResult {
-//     let path = args[0];
-//     let kst = args[1];
-//     let temp_args = [AT_FDCWD, path, kst, 0, 0, 0];
-//     syscall_fstatat(temp_args)
-// }
-
-// /// 获取文件状态信息
-// /// # Arguments
-// /// * `path` - *const u8
-// /// * `stat_ptr` - *mut Kstat
-// #[cfg(target_arch = "x86_64")]
-// pub fn syscall_stat(args: [usize; 6]) -> SyscallResult {
-//     let path = args[0];
-//     let stat_ptr = args[1];
-//     let temp_args = [AT_FDCWD, path, stat_ptr, 0, 0, 0];
-//     syscall_fstatat(temp_args)
-// }
-
-// /// 获取文件系统的信息
-// /// # Arguments
-// /// * `path` - *const u8
-// /// * `stat` - *mut FsStat
-// pub fn syscall_statfs(args: [usize; 6]) -> SyscallResult {
-//     let path = args[0] as *const u8;
-//     let stat = args[1] as *mut FsStat;
-//     let _file_path = solve_path(AT_FDCWD, Some(path), false)?;
-//     axlog::warn!("Only support fs_stat for root");
-
-//     unsafe {
-//         *stat = get_fs_stat();
-//     }
-
-//     Ok(0)
-// }
-
-// /// get file status (extended)
-// /// https://man7.org/linux/man-pages/man2/statx.2.html
-// /// This function returns information about a file, storing it in the
-// /// buffer pointed to by statxbuf.  The returned buffer is a
-// /// structure of the following type
-// ///
-// ///
-// pub fn syscall_statx(args: [usize; 6]) -> SyscallResult {
-//     let dir_fd = args[0];
-//     let path = args[1] as *const u8;
-//     let stat = args[4] as *mut FsStatx;
-//     let file_path = solve_path(dir_fd, Some(path), false)?;
-//     if !axfs::api::path_exists(file_path.path()) {
-//         return Err(SyscallError::ENOENT);
-//     }
-//     if let Ok(p) = FilePath::new("/") {
-//         if file_path.equal_to(&p) {
-//             // 目前只支持访问根目录文件系统的信息
-//             axlog::warn!("Only support fs_stat for root");
-//             unsafe {
-//                 *stat = FsStatx::new();
-//             }
-//         }
-//     }
-//     Ok(0)
-// }
+/// 获取 `dir_fd` 与 `path` 对应文件的属性，`path` 为空且设置了 AT_EMPTY_PATH 时返回 `None`，
+/// 表示需要查询 `dir_fd` 本身
+async fn stat_at(
+    dir_fd: usize,
+    path: *const u8,
+    flags: usize,
+) -> Result<Option<Kstat>, SyscallError> {
+    if flags & !(AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH | AT_NO_AUTOMOUNT) != 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    if path.is_null() {
+        return Err(SyscallError::EFAULT);
+    }
+    if current_executor()
+        .manual_alloc_for_lazy((path as usize).into())
+        .await
+        .is_err()
+    {
+        return Err(SyscallError::EFAULT);
+    }
+    if unsafe { raw_ptr_to_ref_str(path) }.is_empty() {
+        return if flags & AT_EMPTY_PATH != 0 {
+            Ok(None)
+        } else {
+            Err(SyscallError::ENOENT)
+        };
+    }
+    let lookup = if flags & AT_SYMLINK_NOFOLLOW != 0 {
+        LookupFlags::NOFOLLOW
+    } else {
+        LookupFlags::empty()
+    };
+    let file_path = solve_path_with_flags(dir_fd, Some(path), false, lookup).await?;
+    info!("path : {}", file_path.path());
+    let attr = match async_fs::api::get_attr(file_path.path(), lookup).await {
+        Ok(attr) => attr,
+        Err(AxError::NotADirectory) => return Err(SyscallError::ENOTDIR),
+        Err(_) => return Err(SyscallError::ENOENT),
+    };
+    let mut kstat = kstat_from_attr(&attr, inode_of(file_path.path()).await);
+    // 模拟的链接由映射表计数
+    let count = get_link_count(&file_path.path().to_string()).await;
+    if count > 0 {
+        kstat.st_nlink = count as _;
+    }
+    Ok(Some(kstat))
+}
+
+/// 获取 fd 对应文件的属性
+async fn stat_fd(fd: usize) -> Result<Kstat, SyscallError> {
+    let Some(file) = current_executor().fd_manager.get(fd).await else {
+        return Err(SyscallError::EBADF);
+    };
+    file.get_stat().await.map_err(|e| {
+        debug!("get stat error: {:?}", e);
+        SyscallError::EPERM
+    })
+}
+
+/// 获取文件状态信息，但是给出的是目录 fd 和相对路径。
+/// # Arguments
+/// * `dir_fd` - usize
+/// * `path` - *const u8
+/// * `kst` - *mut Kstat
+/// * `flags` - usize, 可设置为0、AT_SYMLINK_NOFOLLOW或AT_EMPTY_PATH
+pub async fn syscall_fstatat(args: [usize; 6]) -> SyscallResult {
+    let dir_fd = args[0];
+    let path = args[1] as *const u8;
+    let kst = args[2] as *mut Kstat;
+    let flags = args[3];
+    let stat = match stat_at(dir_fd, path, flags).await? {
+        Some(stat) => stat,
+        None => stat_fd(dir_fd).await?,
+    };
+    if current_executor().manual_alloc_type_for_lazy(kst).await.is_err() {
+        return Err(SyscallError::EFAULT);
+    }
+    unsafe {
+        *kst = stat;
+    }
+    info!("stat: {:?}", stat);
+    Ok(0)
+}
+
+/// 获取文件状态信息，不跟随最后的符号链接
+/// # Arguments
+/// * `path` - *const u8
+/// * `kst` - *mut Kstat
+#[cfg(target_arch = "x86_64")]
+pub async fn syscall_lstat(args: [usize; 6]) -> SyscallResult {
+    let path = args[0];
+    let kst = args[1];
+    let temp_args = [AT_FDCWD, path, kst, AT_SYMLINK_NOFOLLOW, 0, 0];
+    syscall_fstatat(temp_args).await
+}
+
+/// 获取文件状态信息
+/// # Arguments
+/// * `path` - *const u8
+/// * `stat_ptr` - *mut Kstat
+#[cfg(target_arch = "x86_64")]
+pub async fn syscall_stat(args: [usize; 6]) -> SyscallResult {
+    let path = args[0];
+    let stat_ptr = args[1];
+    let temp_args = [AT_FDCWD, path, stat_ptr, 0, 0, 0];
+    syscall_fstatat(temp_args).await
+}
+
+/// 将文件系统的信息写入用户空间的 `buf`
+async fn write_fs_stat(buf: *mut FsStat, info: FileSystemInfo) -> SyscallResult {
+    if buf.is_null()
+        || current_executor()
+            .manual_alloc_type_for_lazy(buf as *const FsStat)
+            .await
+            .is_err()
+    {
+        return Err(SyscallError::EFAULT);
+    }
+    unsafe {
+        *buf = info.into();
+    }
+    Ok(0)
+}
+
+fn statfs_err(err: PathError) -> SyscallError {
+    let PathError::Vfs(err) = err else {
+        return SyscallError::ELOOP;
+    };
+    match err {
+        AxError::PermissionDenied => SyscallError::EACCES,
+        AxError::NotADirectory => SyscallError::ENOTDIR,
+        AxError::NotFound => SyscallError::ENOENT,
+        AxError::Unsupported => SyscallError::ENOSYS,
+        _ => SyscallError::EIO,
+    }
+}
+
+/// 功能:获取文件系统的信息
+/// # Arguments
+/// * `path` - *const u8, 文件系统中任意一个文件的路径，跟随符号链接
+/// * `buf` - *mut FsStat
+pub async fn syscall_statfs(args: [usize; 6]) -> SyscallResult {
+    let path = args[0] as *const u8;
+    let buf = args[1] as *mut FsStat;
+    if path.is_null()
+        || current_executor()
+            .manual_alloc_for_lazy((path as usize).into())
+            .await
+            .is_err()
+    {
+        return Err(SyscallError::EFAULT);
+    }
+    let path = solve_path_with_flags(AT_FDCWD, Some(path), false, LookupFlags::empty()).await?;
+    let info = async_fs::api::statfs(path.path()).await.map_err(statfs_err)?;
+    write_fs_stat(buf, info).await
+}
+
+/// 管道、socket 等不在文件系统中的文件所属的伪文件系统
+const PIPEFS_MAGIC: u64 = 0x5049_5045;
+const SOCKFS_MAGIC: u64 = 0x534f_434b;
+const ANON_INODE_FS_MAGIC: u64 = 0x0904_1934;
+
+/// 功能:获取 fd 对应文件所在文件系统的信息
+/// # Arguments
+/// * `fd` - usize
+/// * `buf` - *mut FsStat
+pub async fn syscall_fstatfs(args: [usize; 6]) -> SyscallResult {
+    let fd = args[0];
+    let buf = args[1] as *mut FsStat;
+    let Some(file) = current_executor().fd_manager.get(fd).await else {
+        return Err(SyscallError::EBADF);
+    };
+    let info = match file.get_type().await {
+        FileIOType::FileDesc | FileIOType::DirDesc => async_fs::api::statfs(&file.get_path().await)
+            .await
+            .map_err(statfs_err)?,
+        FileIOType::Pipe => FileSystemInfo::new(PIPEFS_MAGIC, PAGE_SIZE_4K as u64, 255),
+        FileIOType::Socket => FileSystemInfo::new(SOCKFS_MAGIC, PAGE_SIZE_4K as u64, 255),
+        _ => FileSystemInfo::new(ANON_INODE_FS_MAGIC, PAGE_SIZE_4K as u64, 255),
+    };
+    write_fs_stat(buf, info).await
+}
+
+/// statx 中基本信息的掩码,即 stat 能返回的所有信息
+const STATX_BASIC_STATS: u32 = 0x7ff;
+
+/// get file status (extended)
+/// https://man7.org/linux/man-pages/man2/statx.2.html
+/// This function returns information about a file, storing it in the
+/// buffer pointed to by statxbuf.
+/// # Arguments
+/// * `dir_fd` - usize
+/// * `path` - *const u8
+/// * `flags` - usize, 可设置为0、AT_SYMLINK_NOFOLLOW或AT_EMPTY_PATH
+/// * `mask` - u32, 需要的信息,基本信息总是返回
+/// * `statx` - *mut FsStatx
+pub async fn syscall_statx(args: [usize; 6]) -> SyscallResult {
+    let dir_fd = args[0];
+    let path = args[1] as *const u8;
+    // 同步方式的标志位与本地文件系统无关
+    let flags = args[2] & !AT_STATX_SYNC_TYPE;
+    let statx = args[4] as *mut FsStatx;
+    let stat = match stat_at(dir_fd, path, flags).await? {
+        Some(stat) => stat,
+        None => stat_fd(dir_fd).await?,
+    };
+    if current_executor().manual_alloc_type_for_lazy(statx).await.is_err() {
+        return Err(SyscallError::EFAULT);
+    }
+    let timestamp = |sec: isize, nsec: isize| FsStatxTimestamp {
+        tv_sec: sec as i64,
+        tv_nsec: nsec as u32,
+    };
+    unsafe {
+        *statx = FsStatx {
+            stx_mask: STATX_BASIC_STATS,
+            stx_blksize: stat.st_blksize,
+            stx_nlink: stat.st_nlink as u32,
+            stx_uid: stat.st_uid,
+            stx_gid: stat.st_gid,
+            stx_mode: stat.st_mode as u16,
+            stx_ino: stat.st_ino,
+            stx_size: stat.st_size,
+            stx_blocks: stat.st_blocks,
+            stx_atime: timestamp(stat.st_atime_sec, stat.st_atime_nsec),
+            stx_ctime: timestamp(stat.st_ctime_sec, stat.st_ctime_nsec),
+            stx_mtime: timestamp(stat.st_mtime_sec, stat.st_mtime_nsec),
+            stx_dev_major: (stat.st_dev >> 8) as u32,
+            stx_dev_minor: (stat.st_dev & 0xff) as u32,
+            ..Default::default()
+        };
+    }
+    Ok(0)
+}
@@ -0,0 +1,105 @@
+extern crate alloc;
+
+use crate::syscall_fs::ctype::timerfd::{ITimerSpec, TimerFd, TimerFdFlag, TimerSetFlag};
+use crate::{SyscallError, SyscallResult};
+use alloc::sync::Arc;
+use async_fs::api::FileIO;
+use executor::{current_executor, FdFlags};
+
+use super::fd_err;
+
+const CLOCK_REALTIME: usize = 0;
+const CLOCK_MONOTONIC: usize = 1;
+/// 内核没有挂起状态，CLOCK_BOOTTIME 与 CLOCK_MONOTONIC 相同
+const CLOCK_BOOTTIME: usize = 7;
+
+/// 获取 `fd` 对应的 timerfd
+async fn timerfd_of(fd: usize) -> Result<Arc<dyn FileIO>, SyscallError> {
+    let Some(file) = current_executor().fd_manager.get(fd).await else {
+        return Err(SyscallError::EBADF);
+    };
+    if (*file).as_any().downcast_ref::<TimerFd>().is_none() {
+        return Err(SyscallError::EINVAL);
+    }
+    Ok(file)
+}
+
+/// 将定时器的设置写入用户空间
+async fn write_spec(addr: *mut ITimerSpec, spec: ITimerSpec) -> Result<(), SyscallError> {
+    if current_executor().manual_alloc_type_for_lazy(addr).await.is_err() {
+        return Err(SyscallError::EFAULT);
+    }
+    unsafe { *addr = spec };
+    Ok(())
+}
+
+/// 创建一个 timerfd
+///
+/// # Arguments
+/// * `clockid`: i32, CLOCK_REALTIME、CLOCK_MONOTONIC 或 CLOCK_BOOTTIME
+/// * `flags`: u32, TFD_NONBLOCK 与 TFD_CLOEXEC 的组合
+pub async fn syscall_timerfd_create(args: [usize; 6]) -> SyscallResult {
+    let realtime = match args[0] {
+        CLOCK_REALTIME => true,
+        CLOCK_MONOTONIC | CLOCK_BOOTTIME => false,
+        _ => return Err(SyscallError::EINVAL),
+    };
+    let Some(flags) = TimerFdFlag::from_bits(args[1] as u32) else {
+        return Err(SyscallError::EINVAL);
+    };
+    let fd_flags = if flags.contains(TimerFdFlag::TFD_CLOEXEC) {
+        FdFlags::CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
+    let fd = current_executor()
+        .fd_manager
+        .alloc(Arc::new(TimerFd::new(realtime, flags)), fd_flags)
+        .await
+        .map_err(fd_err)?;
+    Ok(fd as isize)
+}
+
+/// 启动或停止 timerfd 的定时器
+///
+/// # Arguments
+/// * `fd`: i32, timerfd
+/// * `flags`: i32, TFD_TIMER_ABSTIME 表示 `new_value.it_value` 为绝对时间
+/// * `new_value`: *const ITimerSpec, 新的设置，`it_value` 为 0 时停止定时器
+/// * `old_value`: *mut ITimerSpec, 不为空时写入原先的设置
+pub async fn syscall_timerfd_settime(args: [usize; 6]) -> SyscallResult {
+    let fd = args[0];
+    let Some(flags) = TimerSetFlag::from_bits(args[1] as u32) else {
+        return Err(SyscallError::EINVAL);
+    };
+    let new_value = args[2] as *const ITimerSpec;
+    let old_value = args[3] as *mut ITimerSpec;
+    let file = timerfd_of(fd).await?;
+    if new_value.is_null() || current_executor().manual_alloc_type_for_lazy(new_value).await.is_err() {
+        return Err(SyscallError::EFAULT);
+    }
+    let new_value = unsafe { *new_value };
+    for time in [new_value.it_interval, new_value.it_value] {
+        if (time.tv_sec as isize) < 0 || time.tv_nsec >= 1_000_000_000 {
+            return Err(SyscallError::EINVAL);
+        }
+    }
+    let timer = (*file).as_any().downcast_ref::<TimerFd>().unwrap();
+    let old = timer.set_time(&new_value, flags);
+    if !old_value.is_null() {
+        write_spec(old_value, old).await?;
+    }
+    Ok(0)
+}
+
+/// 获取 timerfd 当前的设置，`it_value` 为距离下一次到期的相对时间
+///
+/// # Arguments
+/// * `fd`: i32, timerfd
+/// * `curr_value`: *mut ITimerSpec, 写入当前设置的位置
+pub async fn syscall_timerfd_gettime(args: [usize; 6]) -> SyscallResult {
+    let file = timerfd_of(args[0]).await?;
+    let timer = (*file).as_any().downcast_ref::<TimerFd>().unwrap();
+    write_spec(args[1] as *mut ITimerSpec, timer.get_time()).await?;
+    Ok(0)
+}
@@ -0,0 +1,354 @@
+//! 负责扩展属性相关的系统调用
+//!
+//! 每种操作都有三个版本：按路径、按路径但不跟随末尾的符号链接(l 前缀)、按文件描述符(f 前缀)。
+//! 属性名必须带有 user.、trusted.、security. 或 system. 命名空间前缀，
+//! 其中 trusted. 与 security. 只有 root 可以修改，trusted. 对其他用户不可见
+extern crate alloc;
+
+use crate::{syscall_fs::ctype::FileDesc, SyscallError, SyscallResult};
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use async_fs::api::{FileIO, FileIOType, LookupFlags, VfsAccess, VfsXattrFlags};
+use axerrno::{AxError, AxResult};
+use axlog::debug;
+use executor::{
+    current_executor,
+    link::{raw_ptr_to_ref_str, AT_FDCWD},
+};
+
+use super::{check_access, solve_path_with_flags};
+
+/// 属性名的最大长度
+const XATTR_NAME_MAX: usize = 255;
+/// 属性值的最大长度
+const XATTR_SIZE_MAX: usize = 65536;
+/// 属性名列表的最大长度
+const XATTR_LIST_MAX: usize = 65536;
+
+/// 属性名允许使用的命名空间
+const XATTR_NAMESPACES: [&str; 4] = ["user.", "trusted.", "security.", "system."];
+
+fn xattr_err(err: AxError) -> SyscallError {
+    match err {
+        AxError::NotFound => SyscallError::ENODATA,
+        AxError::AlreadyExists => SyscallError::EEXIST,
+        AxError::Unsupported => SyscallError::EOPNOTSUPP,
+        // 只读挂载
+        AxError::PermissionDenied => SyscallError::EROFS,
+        AxError::StorageFull => SyscallError::ENOSPC,
+        _ => SyscallError::EIO,
+    }
+}
+
+/// 扩展属性所在的文件
+enum Target {
+    /// 按路径查找，`LookupFlags` 决定是否跟随末尾的符号链接
+    Path(String, LookupFlags),
+    /// 打开的普通文件
+    File(Arc<dyn FileIO>),
+}
+
+impl Target {
+    async fn of_path(path: usize, flags: LookupFlags) -> Result<Self, SyscallError> {
+        let path = solve_path_with_flags(AT_FDCWD, Some(path as *const u8), false, flags).await?;
+        // 先确认文件存在，避免与属性不存在的 NotFound 混淆
+        async_fs::api::get_attr(path.path(), flags)
+            .await
+            .map_err(|_| SyscallError::ENOENT)?;
+        Ok(Self::Path(path.path().to_string(), flags))
+    }
+
+    async fn of_fd(fd: usize) -> Result<Self, SyscallError> {
+        let Some(file) = current_executor().fd_manager.get(fd).await else {
+            return Err(SyscallError::EBADF);
+        };
+        match file.get_type().await {
+            FileIOType::FileDesc => Ok(Self::File(file)),
+            FileIOType::DirDesc => Ok(Self::Path(file.get_path().await, LookupFlags::NOFOLLOW)),
+            _ => Err(SyscallError::EOPNOTSUPP),
+        }
+    }
+
+    /// 用于权限检查的路径
+    async fn path(&self) -> String {
+        match self {
+            Self::Path(path, _) => path.clone(),
+            Self::File(file) => file.get_path().await,
+        }
+    }
+
+    fn desc(file: &Arc<dyn FileIO>) -> &FileDesc {
+        (**file).as_any().downcast_ref::<FileDesc>().unwrap()
+    }
+
+    async fn get(&self, name: &str, buf: &mut [u8]) -> AxResult<usize> {
+        match self {
+            Self::Path(path, flags) => async_fs::api::get_xattr(path, *flags, name, buf).await,
+            Self::File(file) => Self::desc(file).file.lock().await.get_xattr(name, buf).await,
+        }
+    }
+
+    async fn set(&self, name: &str, value: &[u8], flags: VfsXattrFlags) -> AxResult {
+        match self {
+            Self::Path(path, lookup) => {
+                async_fs::api::set_xattr(path, *lookup, name, value, flags).await
+            }
+            Self::File(file) => {
+                let file = Self::desc(file).file.lock().await;
+                file.set_xattr(name, value, flags).await
+            }
+        }
+    }
+
+    async fn list(&self, buf: &mut [u8]) -> AxResult<usize> {
+        match self {
+            Self::Path(path, flags) => async_fs::api::list_xattr(path, *flags, buf).await,
+            Self::File(file) => Self::desc(file).file.lock().await.list_xattr(buf).await,
+        }
+    }
+
+    async fn remove(&self, name: &str) -> AxResult {
+        match self {
+            Self::Path(path, flags) => async_fs::api::remove_xattr(path, *flags, name).await,
+            Self::File(file) => Self::desc(file).file.lock().await.remove_xattr(name).await,
+        }
+    }
+}
+
+/// 读取用户传入的属性名，并检查其长度与命名空间
+async fn read_name(name: usize) -> Result<&'static str, SyscallError> {
+    if name == 0
+        || current_executor()
+            .manual_alloc_for_lazy(name.into())
+            .await
+            .is_err()
+    {
+        return Err(SyscallError::EFAULT);
+    }
+    let name = unsafe { raw_ptr_to_ref_str(name as *const u8) };
+    if name.is_empty() || name.len() > XATTR_NAME_MAX {
+        return Err(SyscallError::ERANGE);
+    }
+    if !XATTR_NAMESPACES
+        .iter()
+        .any(|prefix| name.len() > prefix.len() && name.starts_with(prefix))
+    {
+        return Err(SyscallError::EOPNOTSUPP);
+    }
+    Ok(name)
+}
+
+/// 当前进程能否看到命名空间为 `name` 的属性
+fn visible(name: &str) -> bool {
+    !name.starts_with("trusted.") || current_executor().cred.is_root()
+}
+
+/// 检查当前进程能否读取或修改 `target` 的属性 `name`
+async fn check_xattr_access(
+    target: &Target,
+    name: &str,
+    access: VfsAccess,
+) -> Result<(), SyscallError> {
+    if name.starts_with("user.") {
+        return check_access(&target.path().await, access).await;
+    }
+    let is_root = current_executor().cred.is_root();
+    match name.split_once('.').map(|(ns, _)| ns) {
+        Some("trusted") if !is_root => Err(if access.contains(VfsAccess::WRITE) {
+            SyscallError::EPERM
+        } else {
+            SyscallError::ENODATA
+        }),
+        Some("security") if !is_root && access.contains(VfsAccess::WRITE) => {
+            Err(SyscallError::EPERM)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// 将内核中的数据复制到用户的缓冲区，`size` 为 0 时只返回所需的长度
+async fn copy_out(data: &[u8], buf: usize, size: usize) -> SyscallResult {
+    if size == 0 {
+        return Ok(data.len() as isize);
+    }
+    if data.len() > size {
+        return Err(SyscallError::ERANGE);
+    }
+    if !data.is_empty() {
+        current_executor()
+            .manual_alloc_range_for_lazy(buf.into(), (buf + data.len() - 1).into())
+            .await
+            .map_err(|_| SyscallError::EFAULT)?;
+        unsafe {
+            core::ptr::copy_nonoverlapping(data.as_ptr(), buf as *mut u8, data.len());
+        }
+    }
+    Ok(data.len() as isize)
+}
+
+async fn do_getxattr(target: Target, name: usize, buf: usize, size: usize) -> SyscallResult {
+    let name = read_name(name).await?;
+    check_xattr_access(&target, name, VfsAccess::READ).await?;
+    let mut value = vec![0u8; size.min(XATTR_SIZE_MAX)];
+    let len = target.get(name, &mut value).await.map_err(xattr_err)?;
+    debug!("getxattr: {} len {}", name, len);
+    if len > value.len() {
+        // 缓冲区放不下时文件系统不会复制属性值
+        return if size == 0 {
+            Ok(len as isize)
+        } else {
+            Err(SyscallError::ERANGE)
+        };
+    }
+    copy_out(&value[..len], buf, size).await
+}
+
+async fn do_setxattr(
+    target: Target,
+    name: usize,
+    value: usize,
+    size: usize,
+    flags: usize,
+) -> SyscallResult {
+    let Some(flags) = VfsXattrFlags::from_bits(flags as u32) else {
+        return Err(SyscallError::EINVAL);
+    };
+    let name = read_name(name).await?;
+    if size > XATTR_SIZE_MAX {
+        return Err(SyscallError::E2BIG);
+    }
+    check_xattr_access(&target, name, VfsAccess::WRITE).await?;
+    let value: &[u8] = if size == 0 {
+        &[]
+    } else {
+        current_executor()
+            .manual_alloc_range_for_lazy(value.into(), (value + size - 1).into())
+            .await
+            .map_err(|_| SyscallError::EFAULT)?;
+        unsafe { core::slice::from_raw_parts(value as *const u8, size) }
+    };
+    debug!("setxattr: {} len {} flags {:?}", name, size, flags);
+    target.set(name, value, flags).await.map_err(xattr_err)?;
+    Ok(0)
+}
+
+async fn do_listxattr(target: Target, buf: usize, size: usize) -> SyscallResult {
+    let mut names = vec![0u8; XATTR_LIST_MAX];
+    let len = target.list(&mut names).await.map_err(xattr_err)?;
+    if len > names.len() {
+        return Err(SyscallError::E2BIG);
+    }
+    // 去掉当前进程看不到的属性
+    let list: Vec<u8> = names[..len]
+        .split_inclusive(|&c| c == 0)
+        .filter(|name| core::str::from_utf8(name).map_or(false, visible))
+        .flatten()
+        .copied()
+        .collect();
+    copy_out(&list, buf, size).await
+}
+
+async fn do_removexattr(target: Target, name: usize) -> SyscallResult {
+    let name = read_name(name).await?;
+    check_xattr_access(&target, name, VfsAccess::WRITE).await?;
+    target.remove(name).await.map_err(xattr_err)?;
+    Ok(0)
+}
+
+/// 功能:设置文件的扩展属性；
+/// # Arguments
+/// * `path`: *const u8, 文件路径。
+/// * `name`: *const u8, 属性名。
+/// * `value`: *const u8, 属性值。
+/// * `size`: usize, 属性值的长度。
+/// * `flags`: usize, 为 0,或者 XATTR_CREATE、XATTR_REPLACE 之一。
+/// 返回值:成功执行,返回0。失败,返回-1。
+pub async fn syscall_setxattr(args: [usize; 6]) -> SyscallResult {
+    let target = Target::of_path(args[0], LookupFlags::empty()).await?;
+    do_setxattr(target, args[1], args[2], args[3], args[4]).await
+}
+
+/// 功能:设置文件的扩展属性,路径末尾是符号链接时设置链接本身的属性；
+pub async fn syscall_lsetxattr(args: [usize; 6]) -> SyscallResult {
+    let target = Target::of_path(args[0], LookupFlags::NOFOLLOW).await?;
+    do_setxattr(target, args[1], args[2], args[3], args[4]).await
+}
+
+/// 功能:设置打开的文件的扩展属性；
+pub async fn syscall_fsetxattr(args: [usize; 6]) -> SyscallResult {
+    let target = Target::of_fd(args[0]).await?;
+    do_setxattr(target, args[1], args[2], args[3], args[4]).await
+}
+
+/// 功能:读取文件的扩展属性；
+/// # Arguments
+/// * `path`: *const u8, 文件路径。
+/// * `name`: *const u8, 属性名。
+/// * `value`: *mut u8, 用于存放属性值的缓冲区。
+/// * `size`: usize, 缓冲区的长度,为 0 时只返回属性值的长度。
+/// 返回值:成功执行,返回属性值的长度。失败,返回-1。
+pub async fn syscall_getxattr(args: [usize; 6]) -> SyscallResult {
+    let target = Target::of_path(args[0], LookupFlags::empty()).await?;
+    do_getxattr(target, args[1], args[2], args[3]).await
+}
+
+/// 功能:读取文件的扩展属性,路径末尾是符号链接时读取链接本身的属性；
+pub async fn syscall_lgetxattr(args: [usize; 6]) -> SyscallResult {
+    let target = Target::of_path(args[0], LookupFlags::NOFOLLOW).await?;
+    do_getxattr(target, args[1], args[2], args[3]).await
+}
+
+/// 功能:读取打开的文件的扩展属性；
+pub async fn syscall_fgetxattr(args: [usize; 6]) -> SyscallResult {
+    let target = Target::of_fd(args[0]).await?;
+    do_getxattr(target, args[1], args[2], args[3]).await
+}
+
+/// 功能:列出文件的所有扩展属性名；
+/// # Arguments
+/// * `path`: *const u8, 文件路径。
+/// * `list`: *mut u8, 用于存放属性名的缓冲区,每个属性名以 '\0' 结尾。
+/// * `size`: usize, 缓冲区的长度,为 0 时只返回所需的长度。
+/// 返回值:成功执行,返回属性名列表的长度。失败,返回-1。
+pub async fn syscall_listxattr(args: [usize; 6]) -> SyscallResult {
+    let target = Target::of_path(args[0], LookupFlags::empty()).await?;
+    do_listxattr(target, args[1], args[2]).await
+}
+
+/// 功能:列出文件的所有扩展属性名,路径末尾是符号链接时列出链接本身的属性；
+pub async fn syscall_llistxattr(args: [usize; 6]) -> SyscallResult {
+    let target = Target::of_path(args[0], LookupFlags::NOFOLLOW).await?;
+    do_listxattr(target, args[1], args[2]).await
+}
+
+/// 功能:列出打开的文件的所有扩展属性名；
+pub async fn syscall_flistxattr(args: [usize; 6]) -> SyscallResult {
+    let target = Target::of_fd(args[0]).await?;
+    do_listxattr(target, args[1], args[2]).await
+}
+
+/// 功能:删除文件的扩展属性；
+/// # Arguments
+/// * `path`: *const u8, 文件路径。
+/// * `name`: *const u8, 属性名。
+/// 返回值:成功执行,返回0。失败,返回-1。
+pub async fn syscall_removexattr(args: [usize; 6]) -> SyscallResult {
+    let target = Target::of_path(args[0], LookupFlags::empty()).await?;
+    do_removexattr(target, args[1]).await
+}
+
+/// 功能:删除文件的扩展属性,路径末尾是符号链接时删除链接本身的属性；
+pub async fn syscall_lremovexattr(args: [usize; 6]) -> SyscallResult {
+    let target = Target::of_path(args[0], LookupFlags::NOFOLLOW).await?;
+    do_removexattr(target, args[1]).await
+}
+
+/// 功能:删除打开的文件的扩展属性；
+pub async fn syscall_fremovexattr(args: [usize; 6]) -> SyscallResult {
+    let target = Target::of_fd(args[0]).await?;
+    do_removexattr(target, args[1]).await
+}
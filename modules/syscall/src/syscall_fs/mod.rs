@@ -27,99 +27,134 @@ pub async fn fs_syscall(syscall_id: fs_syscall_id::FsSyscallId, args: [usize; 6]
     match syscall_id {
         OPENAT => syscall_openat(args).await,
         CLOSE => syscall_close(args).await,
+        CLOSE_RANGE => syscall_close_range(args).await,
         READ => syscall_read(args).await,
         WRITE => syscall_write(args).await,
         // GETCWD => syscall_getcwd(args),
-        // PIPE2 => syscall_pipe2(args),
-        // DUP => syscall_dup(args),
+        PIPE2 => syscall_pipe2(args).await,
+        DUP => syscall_dup(args).await,
         DUP3 => syscall_dup3(args).await,
         // MKDIRAT => syscall_mkdirat(args),
+        MKNODAT => syscall_mknodat(args).await,
         // CHDIR => syscall_chdir(args),
         // GETDENTS64 => syscall_getdents64(args),
-        // MOUNT => syscall_mount(args),
-        // UNMOUNT => syscall_umount(args),
+        MOUNT => syscall_mount(args).await,
+        UNMOUNT => syscall_umount(args).await,
         FSTAT => syscall_fstat(args).await,
         // RENAMEAT | RENAMEAT2 => syscall_renameat2(args),
         // READV => syscall_readv(args),
         // WRITEV => syscall_writev(args),
-        // FCNTL64 => syscall_fcntl64(args),
-        // FSTATAT => syscall_fstatat(args),
-        // STATFS => syscall_statfs(args),
-        // FCHMODAT => syscall_fchmodat(args),
-        // FACCESSAT => syscall_faccessat(args),
-        // LSEEK => syscall_lseek(args),
+        FCNTL64 => syscall_fcntl64(args).await,
+        FLOCK => syscall_flock(args).await,
+        FSTATAT => syscall_fstatat(args).await,
+        STATFS => syscall_statfs(args).await,
+        FSTATFS => syscall_fstatfs(args).await,
+        FCHMODAT => syscall_fchmodat(args).await,
+        FCHMOD => syscall_fchmod(args).await,
+        FACCESSAT => syscall_faccessat(args).await,
+        LSEEK => syscall_lseek(args).await,
         // PREAD64 => syscall_pread64(args),
-        // PREADLINKAT => syscall_readlinkat(args),
+        PREADLINKAT => syscall_readlinkat(args).await,
         // PWRITE64 => syscall_pwrite64(args),
-        // SENDFILE64 => syscall_sendfile64(args),
-        // FSYNC => Ok(0),
-        // FTRUNCATE64 => {
-        //     syscall_ftruncate64(args)
-        //     // 0
-        // }
+        SENDFILE64 => syscall_sendfile64(args).await,
+        SPLICE => syscall_splice(args).await,
+        TEE => syscall_tee(args).await,
+        FSYNC | FDATASYNC => syscall_fsync(args).await,
+        FTRUNCATE64 => syscall_ftruncate64(args).await,
+        FALLOCATE => syscall_fallocate(args).await,
         IOCTL => syscall_ioctl(args).await,
-        // // 不做处理即可
-        // SYNC => Ok(0),
-        // COPYFILERANGE => syscall_copyfilerange(args),
-        // LINKAT => sys_linkat(args),
-        // UNLINKAT => syscall_unlinkat(args),
-        // SYMLINKAT => Ok(0),
-        // UTIMENSAT => syscall_utimensat(args),
-        // EPOLL_CREATE => syscall_epoll_create1(args),
-        // EPOLL_CTL => syscall_epoll_ctl(args),
-        // EPOLL_PWAIT => syscall_epoll_pwait(args),
-        // PPOLL => syscall_ppoll(args),
-        // PSELECT6 => syscall_pselect6(args),
-        // STATX => syscall_statx(args),
-        // PIDFD_OPEN => syscall_pidfd_open(args),
-        // FCHOWN => Ok(0),
-        // #[cfg(not(target_arch = "x86_64"))]
-        // EVENTFD => syscall_eventfd(args),
-        // #[cfg(target_arch = "x86_64")]
-        // // eventfd syscall in x86_64 does not support flags, use 0 instead
-        // EVENTFD => syscall_eventfd([args[0], 0, 0, 0, 0, 0]),
-        // #[cfg(target_arch = "x86_64")]
-        // EVENTFD2 => syscall_eventfd(args),
-        // #[cfg(target_arch = "x86_64")]
-        // DUP2 => syscall_dup2(args),
-        // #[cfg(target_arch = "x86_64")]
-        // LSTAT => syscall_lstat(args),
+        SYNC => syscall_sync().await,
+        COPYFILERANGE => syscall_copy_file_range(args).await,
+        LINKAT => syscall_linkat(args).await,
+        UNLINKAT => syscall_unlinkat(args).await,
+        SYMLINKAT => syscall_symlinkat(args).await,
+        UTIMENSAT => syscall_utimensat(args).await,
+        #[cfg(not(target_arch = "x86_64"))]
+        EPOLL_CREATE => syscall_epoll_create1(args).await,
+        EPOLL_CTL => syscall_epoll_ctl(args).await,
+        EPOLL_PWAIT => syscall_epoll_pwait(args).await,
+        EPOLL_PWAIT2 => syscall_epoll_pwait2(args).await,
+        PPOLL => syscall_ppoll(args).await,
+        PSELECT6 => syscall_pselect6(args).await,
+        STATX => syscall_statx(args).await,
+        PIDFD_OPEN => syscall_pidfd_open(args).await,
+        PIDFD_GETFD => syscall_pidfd_getfd(args).await,
+        FCHOWNAT => syscall_fchownat(args).await,
+        FCHOWN => syscall_fchown(args).await,
+        SETXATTR => syscall_setxattr(args).await,
+        LSETXATTR => syscall_lsetxattr(args).await,
+        FSETXATTR => syscall_fsetxattr(args).await,
+        GETXATTR => syscall_getxattr(args).await,
+        LGETXATTR => syscall_lgetxattr(args).await,
+        FGETXATTR => syscall_fgetxattr(args).await,
+        LISTXATTR => syscall_listxattr(args).await,
+        LLISTXATTR => syscall_llistxattr(args).await,
+        FLISTXATTR => syscall_flistxattr(args).await,
+        REMOVEXATTR => syscall_removexattr(args).await,
+        LREMOVEXATTR => syscall_lremovexattr(args).await,
+        FREMOVEXATTR => syscall_fremovexattr(args).await,
+        INOTIFY_INIT1 => syscall_inotify_init1(args).await,
+        INOTIFY_ADD_WATCH => syscall_inotify_add_watch(args).await,
+        INOTIFY_RM_WATCH => syscall_inotify_rm_watch(args).await,
+        #[cfg(not(target_arch = "x86_64"))]
+        EVENTFD => syscall_eventfd(args).await,
+        #[cfg(target_arch = "x86_64")]
+        // eventfd syscall in x86_64 does not support flags, use 0 instead
+        EVENTFD => syscall_eventfd([args[0], 0, 0, 0, 0, 0]).await,
+        #[cfg(target_arch = "x86_64")]
+        EVENTFD2 => syscall_eventfd(args).await,
+        TIMERFD_CREATE => syscall_timerfd_create(args).await,
+        TIMERFD_SETTIME => syscall_timerfd_settime(args).await,
+        TIMERFD_GETTIME => syscall_timerfd_gettime(args).await,
+        MQ_OPEN => syscall_mq_open(args).await,
+        MQ_UNLINK => syscall_mq_unlink(args).await,
+        MQ_TIMEDSEND => syscall_mq_timedsend(args).await,
+        MQ_TIMEDRECEIVE => syscall_mq_timedreceive(args).await,
+        MQ_NOTIFY => syscall_mq_notify(args).await,
+        MQ_GETSETATTR => syscall_mq_getsetattr(args).await,
+        MEMFD_CREATE => syscall_memfd_create(args).await,
+        #[cfg(target_arch = "x86_64")]
+        DUP2 => syscall_dup2(args).await,
+        #[cfg(target_arch = "x86_64")]
+        LSTAT => syscall_lstat(args).await,
         // #[cfg(target_arch = "x86_64")]
         // OPEN => syscall_open(args),
-        // #[cfg(target_arch = "x86_64")]
-        // PIPE => syscall_pipe(args),
-        // #[cfg(target_arch = "x86_64")]
-        // POLL => syscall_poll(args),
-        // #[cfg(target_arch = "x86_64")]
-        // STAT => syscall_stat(args),
-        // #[cfg(target_arch = "x86_64")]
-        // UNLINK => syscall_unlink(args),
-        // #[cfg(target_arch = "x86_64")]
-        // ACCESS => syscall_access(args),
+        #[cfg(target_arch = "x86_64")]
+        PIPE => syscall_pipe(args).await,
+        #[cfg(target_arch = "x86_64")]
+        POLL => syscall_poll(args).await,
+        #[cfg(target_arch = "x86_64")]
+        STAT => syscall_stat(args).await,
+        #[cfg(target_arch = "x86_64")]
+        UNLINK => syscall_unlink(args).await,
+        #[cfg(target_arch = "x86_64")]
+        ACCESS => syscall_access(args).await,
         // #[cfg(target_arch = "x86_64")]
         // MKDIR => syscall_mkdir(args),
         // #[cfg(target_arch = "x86_64")]
         // RENAME => syscall_rename(args),
         // #[cfg(target_arch = "x86_64")]
         // RMDIR => syscall_rmdir(args),
-        // #[cfg(target_arch = "x86_64")]
-        // SELECT => syscall_select(args),
-        // #[cfg(target_arch = "x86_64")]
-        // READLINK => syscall_readlink(args),
+        #[cfg(target_arch = "x86_64")]
+        SELECT => syscall_select(args).await,
+        #[cfg(target_arch = "x86_64")]
+        READLINK => syscall_readlink(args).await,
         // #[cfg(target_arch = "x86_64")]
         // CREAT => syscall_creat(args),
-        // #[cfg(target_arch = "x86_64")]
-        // EPOLL_CREATE1 => syscall_epoll_create1(args),
-        // // EPOLL_CREATE1 => unimplemented!("epoll_create1"),
-        // #[cfg(target_arch = "x86_64")]
-        // EPOLL_WAIT => syscall_epoll_wait(args),
-        // // EPOLL_PWAIT => unimplemented!("epoll_ctl"),
-        // #[cfg(target_arch = "x86_64")]
-        // CHMOD => Ok(0),
-        // #[cfg(target_arch = "x86_64")]
-        // CHOWN => Ok(0),
-        // #[cfg(target_arch = "x86_64")]
-        // MKNOD => Ok(0),
+        #[cfg(target_arch = "x86_64")]
+        EPOLL_CREATE => syscall_epoll_create(args).await,
+        #[cfg(target_arch = "x86_64")]
+        EPOLL_CREATE1 => syscall_epoll_create1(args).await,
+        #[cfg(target_arch = "x86_64")]
+        EPOLL_WAIT => syscall_epoll_wait(args).await,
+        #[cfg(target_arch = "x86_64")]
+        CHMOD => syscall_chmod(args).await,
+        #[cfg(target_arch = "x86_64")]
+        CHOWN => syscall_chown(args).await,
+        #[cfg(target_arch = "x86_64")]
+        INOTIFY_INIT => syscall_inotify_init(args).await,
+        #[cfg(target_arch = "x86_64")]
+        MKNOD => syscall_mknod(args).await,
         _ => unimplemented!("syscall_id: {:?}", syscall_id),
     }
 }
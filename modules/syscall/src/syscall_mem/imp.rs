@@ -1,10 +1,17 @@
+use super::ipc::*;
 use crate::{syscall_fs::FileDesc, MMAPFlags, MREMAPFlags, SyscallError, SyscallResult, MMAPPROT};
 extern crate alloc;
 
 use axerrno::AxError;
-use axhal::{arch::flush_tlb, mem::VirtAddr, paging::MappingFlags};
+use alloc::sync::Arc;
+use axhal::{
+    arch::flush_tlb,
+    mem::{VirtAddr, PAGE_SIZE_4K},
+    paging::MappingFlags,
+};
 use axlog::info;
-use async_mem::MemorySet;
+use async_fs::api::VfsSeals;
+use async_mem::{MemorySet, SharedMem, SHARED_MEMS};
 
 use executor::current_executor;
 use bitflags::bitflags;
@@ -70,22 +77,25 @@ pub async fn syscall_mmap(args: [usize; 6]) -> SyscallResult {
     } else {
         // file backend
         axlog::debug!("[mmap] fd: {}, offset: 0x{:x}", fd, offset);
-        if fd >= process.fd_manager.fd_table.lock().await.len() as i32 || fd < 0 {
+        if fd < 0 {
             return Err(SyscallError::EINVAL);
         }
-        let file = match &process.fd_manager.fd_table.lock().await[fd as usize] {
-            // 文件描述符表里面存的是文件描述符，这很合理罢
-            Some(file) => alloc::boxed::Box::new(
-                file.as_any()
-                    .downcast_ref::<FileDesc>()
-                    .expect("Try to mmap with a non-file backend")
-                    .file
-                    .lock()
-                    .await
-                    .clone(),
-            ),
+        let Some(file) = process.fd_manager.get(fd as usize).await else {
             // fd not found
-            None => return Err(SyscallError::EINVAL),
+            return Err(SyscallError::EINVAL);
+        };
+        // 带有 F_SEAL_WRITE 的文件不能以可写的方式共享映射
+        if shared && prot.contains(MMAPPROT::PROT_WRITE) {
+            if let Some(desc) = (*file).as_any().downcast_ref::<FileDesc>() {
+                let seals = desc.file.lock().await.get_seals().await;
+                let write_sealed = VfsSeals::WRITE | VfsSeals::FUTURE_WRITE;
+                if seals.is_ok_and(|seals| seals.intersects(write_sealed)) {
+                    return Err(SyscallError::EPERM);
+                }
+            }
+        }
+        let Ok(file) = file.mmap_file().await else {
+            return Err(SyscallError::ENODEV);
         };
 
         let backend = MemBackend::new(file, offset as u64).await;
@@ -239,7 +249,16 @@ pub async fn syscall_mremap(args: [usize; 6]) -> SyscallResult {
     flush_tlb(None);
     Ok(new_addr)
 }
-const IPC_PRIVATE: i32 = 0;
+/// 共享内存的最小与最大长度
+const SHMMIN: usize = 1;
+const SHMMAX: usize = usize::MAX - (1 << 24);
+
+/// shmctl 的命令
+const SHM_LOCK: i32 = 11;
+const SHM_UNLOCK: i32 = 12;
+const SHM_STAT: i32 = 13;
+const SHM_INFO: i32 = 14;
+const SHM_STAT_ANY: i32 = 15;
 
 bitflags! {
     #[derive(Debug)]
@@ -252,7 +271,48 @@ bitflags! {
     }
 }
 
-// TODO: uid and gid support
+/// 用户空间的 `struct shmid64_ds`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ShmidDs {
+    pub shm_perm: IpcPerm64,
+    pub shm_segsz: usize,
+    pub shm_atime: isize,
+    pub shm_dtime: isize,
+    pub shm_ctime: isize,
+    pub shm_cpid: i32,
+    pub shm_lpid: i32,
+    pub shm_nattch: usize,
+    __unused4: usize,
+    __unused5: usize,
+}
+
+/// IPC_INFO 返回的 `struct shminfo64`
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ShmInfo64 {
+    pub shmmax: usize,
+    pub shmmin: usize,
+    pub shmmni: usize,
+    pub shmseg: usize,
+    pub shmall: usize,
+    __unused: [usize; 4],
+}
+
+/// SHM_INFO 返回的 `struct shm_info`
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ShmInfo {
+    pub used_ids: i32,
+    pub shm_tot: usize,
+    pub shm_rss: usize,
+    pub shm_swp: usize,
+    pub swap_attempts: usize,
+    pub swap_successes: usize,
+}
+
+/// 获取或创建一段共享内存
+///
 /// # Arguments
 /// * `key` - i32
 /// * `size` - usize
@@ -263,53 +323,122 @@ pub async fn syscall_shmget(args: [usize; 6]) -> SyscallResult {
     let flags = args[2] as i32;
 
     let pid = current_executor().pid().as_u64();
+    let (uid, gid) = current_cred();
 
     // 9 bits for permission
-    let mode: u16 = (flags as u16) & ((1 << 10) - 1);
+    let mode: u16 = (flags & 0o777) as u16;
 
-    let Some(flags) = ShmFlags::from_bits(flags - mode as i32) else {
-        // return -1;
+    if ShmFlags::from_bits(flags & !0o777).is_none() {
         return Err(SyscallError::EINVAL);
-    };
-
-    if key == IPC_PRIVATE {
-        let Ok((shmid, mem)) = MemorySet::create_shared_mem(key, size, pid, 0, 0, mode) else {
-            return Err(SyscallError::EINVAL);
-        };
+    }
 
-        current_executor()
-            .memory_set
-            .lock()
-            .await
-            .add_private_shared_mem(shmid, mem);
+    ipc_get(
+        &SHARED_MEMS,
+        key,
+        flags,
+        |mem| {
+            if size > mem.info.lock().size {
+                return Err(SyscallError::EINVAL);
+            }
+            Ok(())
+        },
+        || {
+            if !(SHMMIN..=SHMMAX).contains(&size) {
+                return Err(SyscallError::EINVAL);
+            }
+            SharedMem::try_new(key, size, pid, uid, gid, mode).map_err(|_| SyscallError::ENOMEM)
+        },
+    )
+}
 
-        Ok(shmid as isize)
-    } else {
-        let mut key_map = async_mem::KEY_TO_SHMID.lock();
-
-        match key_map.get(&key) {
-            Some(shmid) => {
-                if flags.contains(ShmFlags::IPC_CREAT) && flags.contains(ShmFlags::IPC_EXCL) {
-                    Err(SyscallError::EEXIST)
-                } else {
-                    Ok(*shmid as isize)
-                }
+/// 共享内存的控制操作
+///
+/// # Arguments
+/// * `shmid` - i32, SHM_STAT 与 SHM_STAT_ANY 时为位置
+/// * `cmd` - i32
+/// * `buf` - *mut ShmidDs, IPC_INFO 时为 `struct shminfo64`，SHM_INFO 时为 `struct shm_info`
+pub async fn syscall_shmctl(args: [usize; 6]) -> SyscallResult {
+    let shmid = args[0] as i32;
+    let cmd = args[1] as i32 & !IPC_64;
+    let buf = args[2];
+    let (uid, gid) = current_cred();
+    match cmd {
+        IPC_INFO => {
+            let info = ShmInfo64 {
+                shmmax: SHMMAX,
+                shmmin: SHMMIN,
+                shmmni: async_mem::SHMMNI,
+                shmseg: async_mem::SHMMNI,
+                shmall: SHMMAX / PAGE_SIZE_4K,
+                __unused: [0; 4],
+            };
+            write_user(buf as *mut ShmInfo64, info).await?;
+            return Ok(ipc_max_index(&SHARED_MEMS));
+        }
+        SHM_INFO => {
+            let ids = SHARED_MEMS.lock();
+            let pages = ids.iter().map(|(_, mem)| mem.size() / PAGE_SIZE_4K).sum();
+            let info = ShmInfo {
+                used_ids: ids.len() as i32,
+                shm_tot: pages,
+                shm_rss: pages,
+                shm_swp: 0,
+                swap_attempts: 0,
+                swap_successes: 0,
+            };
+            drop(ids);
+            write_user(buf as *mut ShmInfo, info).await?;
+            return Ok(ipc_max_index(&SHARED_MEMS));
+        }
+        _ => {}
+    }
+    let by_index = cmd == SHM_STAT || cmd == SHM_STAT_ANY;
+    let (id, mem) = ipc_lookup(&SHARED_MEMS, shmid, by_index)?;
+    let perm = mem.perm();
+    match cmd {
+        IPC_STAT | SHM_STAT | SHM_STAT_ANY => {
+            if cmd != SHM_STAT_ANY && !perm.permits(uid, gid, S_IRUGO) {
+                return Err(SyscallError::EACCES);
             }
-            None => {
-                if flags.contains(ShmFlags::IPC_CREAT) {
-                    let Ok((shmid, mem)) = MemorySet::create_shared_mem(key, size, pid, 0, 0, mode)
-                    else {
-                        return Err(SyscallError::EINVAL);
-                    };
-
-                    key_map.insert(key, shmid);
-                    MemorySet::add_shared_mem(shmid, mem);
-                    Ok(shmid as isize)
-                } else {
-                    Err(SyscallError::ENOENT)
-                }
+            // 除去命名空间与这里持有的引用，其余都来自 attach
+            let nattch = Arc::strong_count(&mem).saturating_sub(2);
+            let info = mem.info.lock();
+            let ds = ShmidDs {
+                shm_perm: IpcPerm64::new(&info.perm, id),
+                shm_segsz: info.size,
+                shm_atime: info.a_time as isize,
+                shm_dtime: info.d_time as isize,
+                shm_ctime: info.c_time as isize,
+                shm_cpid: info.c_pid as i32,
+                shm_lpid: info.l_pid as i32,
+                shm_nattch: nattch,
+                ..Default::default()
+            };
+            drop(info);
+            write_user(buf as *mut ShmidDs, ds).await?;
+            Ok(if by_index { id as isize } else { 0 })
+        }
+        IPC_SET => {
+            let ds = read_user(buf as *const ShmidDs).await?;
+            if !perm.is_owner(uid) {
+                return Err(SyscallError::EPERM);
             }
+            let mut info = mem.info.lock();
+            info.perm.set(ds.shm_perm.uid, ds.shm_perm.gid, ds.shm_perm.mode as u16);
+            info.c_time = now_secs();
+            Ok(0)
         }
+        IPC_RMID => {
+            if !perm.is_owner(uid) {
+                return Err(SyscallError::EPERM);
+            }
+            // 已经 attach 的映射仍然有效，最后一个映射消失时共享内存被释放
+            SHARED_MEMS.lock().remove(id);
+            Ok(0)
+        }
+        // 内存不会被换出
+        SHM_LOCK | SHM_UNLOCK => Ok(0),
+        _ => Err(SyscallError::EINVAL),
     }
 }
 
@@ -338,12 +467,18 @@ pub async fn syscall_shmat(args: [usize; 6]) -> SyscallResult {
 
     let flags = ShmAtFlags::from_bits(flags).unwrap();
 
-    let Some(mem) = memory
-        .get_private_shared_mem(shmid)
-        .or_else(|| MemorySet::get_shared_mem(shmid))
-    else {
+    let Some(mem) = MemorySet::get_shared_mem(shmid) else {
         return Err(SyscallError::EINVAL);
     };
+    let (uid, gid) = current_cred();
+    let flag = if flags.contains(ShmAtFlags::SHM_RDONLY) {
+        S_IRUGO
+    } else {
+        S_IRUGO | S_IWUGO
+    };
+    if !mem.perm().permits(uid, gid, flag) {
+        return Err(SyscallError::EACCES);
+    }
     let size = mem.size();
 
     let addr = if addr == 0 {
@@ -381,6 +516,10 @@ pub async fn syscall_shmat(args: [usize; 6]) -> SyscallResult {
         map_flags |= MappingFlags::EXECUTE;
     }
 
+    let mut info = mem.info.lock();
+    info.a_time = now_secs();
+    info.l_pid = process.pid().as_u64();
+    drop(info);
     memory.attach_shared_mem(mem, addr, map_flags);
     flush_tlb(None);
 
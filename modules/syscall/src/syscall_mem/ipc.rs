@@ -0,0 +1,187 @@
+//! System V IPC 系统调用共用的常量、用户空间结构体与查找逻辑
+//!
+//! 共享内存、信号量集与消息队列的 `*get` 使用相同的键与权限规则，见 [`ipc_get`]
+extern crate alloc;
+
+use crate::{SyscallError, SyscallResult};
+use async_mem::ipc::{IpcIds, IpcPerm, IPCMNI, IPC_PRIVATE};
+use async_mem::SharedMem;
+use executor::current_executor;
+use spinlock::SpinNoIrq;
+
+/// 键不存在时创建对象
+pub const IPC_CREAT: i32 = 0o1000;
+/// 与 IPC_CREAT 一起使用，键已存在时失败
+pub const IPC_EXCL: i32 = 0o2000;
+/// 不阻塞
+pub const IPC_NOWAIT: i32 = 0o4000;
+
+/// `*ctl` 的通用命令
+pub const IPC_RMID: i32 = 0;
+pub const IPC_SET: i32 = 1;
+pub const IPC_STAT: i32 = 2;
+pub const IPC_INFO: i32 = 3;
+/// 部分 C 库为 cmd 加上的标志，表示使用 64 位的结构体，这里总是使用它们
+pub const IPC_64: i32 = 0x100;
+
+/// 请求读权限时传给 [`IpcPerm::permits`] 的 flag
+pub const S_IRUGO: u16 = 0o444;
+/// 请求写权限，对于信号量集是修改信号量的权限
+pub const S_IWUGO: u16 = 0o222;
+
+/// 用户空间的 `struct ipc64_perm`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IpcPerm64 {
+    pub key: i32,
+    pub uid: u32,
+    pub gid: u32,
+    pub cuid: u32,
+    pub cgid: u32,
+    /// x86_64 上为 u16 与两字节的填充，小端序下与 u32 相同
+    pub mode: u32,
+    pub seq: u16,
+    __pad2: u16,
+    __unused1: usize,
+    __unused2: usize,
+}
+
+impl IpcPerm64 {
+    /// 标识符为 `id` 的对象的权限
+    pub fn new(perm: &IpcPerm, id: i32) -> Self {
+        Self {
+            key: perm.key,
+            uid: perm.uid,
+            gid: perm.gid,
+            cuid: perm.cuid,
+            cgid: perm.cgid,
+            mode: perm.mode as u32,
+            seq: (id as usize / IPCMNI) as u16,
+            ..Default::default()
+        }
+    }
+}
+
+/// 拥有 [`IpcPerm`] 的 IPC 对象
+pub trait IpcObject {
+    /// 对象当前的所有者与权限
+    fn perm(&self) -> IpcPerm;
+}
+
+impl IpcObject for SharedMem {
+    fn perm(&self) -> IpcPerm {
+        self.info.lock().perm
+    }
+}
+
+/// 调用者的有效用户与组
+pub fn current_cred() -> (u32, u32) {
+    let executor = current_executor();
+    (executor.cred.euid(), executor.cred.egid())
+}
+
+/// 现在的时间，以秒为单位
+pub fn now_secs() -> usize {
+    axhal::time::wall_time().as_secs() as usize
+}
+
+/// 读取用户空间的结构体
+pub async fn read_user<T: Copy>(ptr: *const T) -> Result<T, SyscallError> {
+    if ptr.is_null()
+        || current_executor()
+            .manual_alloc_type_for_lazy(ptr)
+            .await
+            .is_err()
+    {
+        return Err(SyscallError::EFAULT);
+    }
+    Ok(unsafe { *ptr })
+}
+
+/// 写入用户空间的结构体
+pub async fn write_user<T>(ptr: *mut T, value: T) -> Result<(), SyscallError> {
+    if ptr.is_null()
+        || current_executor()
+            .manual_alloc_type_for_lazy(ptr)
+            .await
+            .is_err()
+    {
+        return Err(SyscallError::EFAULT);
+    }
+    unsafe { ptr.write(value) };
+    Ok(())
+}
+
+/// 检查用户空间的 `[start, start + len)` 是否可以访问
+pub async fn check_range(start: usize, len: usize) -> Result<(), SyscallError> {
+    if len == 0 {
+        return Ok(());
+    }
+    if start == 0 {
+        return Err(SyscallError::EFAULT);
+    }
+    let end = start.checked_add(len - 1).ok_or(SyscallError::EFAULT)?;
+    current_executor()
+        .manual_alloc_range_for_lazy(start.into(), end.into())
+        .await
+        .map_err(|_| SyscallError::EFAULT)
+}
+
+/// `*get` 的公共逻辑：根据键查找对象，不存在时按照 `flags` 创建，返回标识符
+///
+/// 已有的对象先经过 `check` 检查，再检查 `flags` 的低 9 位请求的权限。
+/// `create` 在持有命名空间的锁时被调用
+pub fn ipc_get<T: IpcObject>(
+    ids: &SpinNoIrq<IpcIds<T>>,
+    key: i32,
+    flags: i32,
+    check: impl FnOnce(&T) -> Result<(), SyscallError>,
+    create: impl FnOnce() -> Result<T, SyscallError>,
+) -> SyscallResult {
+    let mut ids = ids.lock();
+    if key != IPC_PRIVATE {
+        if let Some((id, object)) = ids.find_key(key) {
+            if flags & (IPC_CREAT | IPC_EXCL) == IPC_CREAT | IPC_EXCL {
+                return Err(SyscallError::EEXIST);
+            }
+            check(&object)?;
+            let (uid, gid) = current_cred();
+            if !object.perm().permits(uid, gid, (flags & 0o777) as u16) {
+                return Err(SyscallError::EACCES);
+            }
+            return Ok(id as isize);
+        }
+        if flags & IPC_CREAT == 0 {
+            return Err(SyscallError::ENOENT);
+        }
+    }
+    let object = create()?;
+    match ids.insert(key, object) {
+        Some((id, _)) => Ok(id as isize),
+        None => Err(SyscallError::ENOSPC),
+    }
+}
+
+/// `*ctl` 的对象查找：`*_STAT` 与 `*_STAT_ANY` 的参数为位置，其他命令为标识符
+///
+/// 返回对象的标识符与对象
+pub fn ipc_lookup<T>(
+    ids: &SpinNoIrq<IpcIds<T>>,
+    id: i32,
+    by_index: bool,
+) -> Result<(i32, alloc::sync::Arc<T>), SyscallError> {
+    let ids = ids.lock();
+    let found = if by_index {
+        usize::try_from(id)
+            .ok()
+            .and_then(|index| ids.get_by_index(index))
+    } else {
+        ids.get(id).map(|object| (id, object))
+    };
+    found.ok_or(SyscallError::EINVAL)
+}
+
+/// `*_INFO` 与 `IPC_INFO` 的返回值，即已被使用的最大位置
+pub fn ipc_max_index<T>(ids: &SpinNoIrq<IpcIds<T>>) -> isize {
+    ids.lock().max_index().map_or(0, |index| index as isize)
+}
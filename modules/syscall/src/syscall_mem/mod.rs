@@ -3,11 +3,17 @@
 use crate::SyscallResult;
 
 mod imp;
+mod ipc;
+mod msg;
+mod sem;
 
 mod mem_syscall_id;
 pub use mem_syscall_id::MemSyscallId::{self, *};
 
 use imp::*;
+use msg::*;
+use sem::*;
+pub use sem::sem_undo_on_exit;
 /// 与内存相关的系统调用
 pub async fn mem_syscall(syscall_id: mem_syscall_id::MemSyscallId, args: [usize; 6]) -> SyscallResult {
     match syscall_id {
@@ -20,8 +26,16 @@ pub async fn mem_syscall(syscall_id: mem_syscall_id::MemSyscallId, args: [usize;
         MPROTECT => syscall_mprotect(args).await,
         MEMBARRIER => Ok(0),
         SHMGET => syscall_shmget(args).await,
-        SHMCTL => Ok(0),
+        SHMCTL => syscall_shmctl(args).await,
         SHMAT => syscall_shmat(args).await,
+        SEMGET => syscall_semget(args).await,
+        SEMOP => syscall_semop(args).await,
+        SEMTIMEDOP => syscall_semtimedop(args).await,
+        SEMCTL => syscall_semctl(args).await,
+        MSGGET => syscall_msgget(args).await,
+        MSGSND => syscall_msgsnd(args).await,
+        MSGRCV => syscall_msgrcv(args).await,
+        MSGCTL => syscall_msgctl(args).await,
         #[cfg(target_arch = "x86_64")]
         MLOCK => syscall_mlock(args),
         #[allow(unused)]
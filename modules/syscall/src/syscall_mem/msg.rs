@@ -0,0 +1,425 @@
+//! System V 消息队列
+extern crate alloc;
+
+use super::ipc::*;
+use crate::syscall_fs::imp::sleep_until_woken;
+use crate::{SyscallError, SyscallResult};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use async_mem::ipc::{IpcIds, IpcPerm};
+use core::future::poll_fn;
+use core::task::{Context, Poll};
+use executor::current_executor;
+use spinlock::SpinNoIrq;
+use sync::WaitQueue;
+
+/// 一条消息的最大长度
+const MSGMAX: usize = 8192;
+/// 一个队列默认的最大字节数，普通用户不能通过 IPC_SET 超过它
+const MSGMNB: usize = 16384;
+/// 消息队列的最大数目
+const MSGMNI: usize = 32000;
+
+/// msgrcv 的 flags
+/// 消息过长时截断而不是返回 E2BIG
+const MSG_NOERROR: i32 = 0o10000;
+/// 接收第一条类型不等于 msgtyp 的消息
+const MSG_EXCEPT: i32 = 0o20000;
+/// 复制第 msgtyp 条消息而不移除它
+const MSG_COPY: i32 = 0o40000;
+
+/// msgctl 的命令
+const MSG_STAT: i32 = 11;
+const MSG_INFO: i32 = 12;
+const MSG_STAT_ANY: i32 = 13;
+
+/// 所有的消息队列
+static MSG_QUEUES: SpinNoIrq<IpcIds<MsgQueue>> = SpinNoIrq::new(IpcIds::new(MSGMNI));
+
+/// 用户空间的 `struct msqid64_ds`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MsqidDs {
+    pub msg_perm: IpcPerm64,
+    pub msg_stime: isize,
+    pub msg_rtime: isize,
+    pub msg_ctime: isize,
+    pub msg_cbytes: usize,
+    pub msg_qnum: usize,
+    pub msg_qbytes: usize,
+    pub msg_lspid: i32,
+    pub msg_lrpid: i32,
+    __unused4: usize,
+    __unused5: usize,
+}
+
+/// IPC_INFO 与 MSG_INFO 返回的 `struct msginfo`
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct MsgInfo {
+    pub msgpool: i32,
+    pub msgmap: i32,
+    pub msgmax: i32,
+    pub msgmnb: i32,
+    pub msgmni: i32,
+    pub msgssz: i32,
+    pub msgtql: i32,
+    pub msgseg: u16,
+}
+
+struct MsgState {
+    perm: IpcPerm,
+    /// 消息的类型与内容
+    messages: VecDeque<(isize, Vec<u8>)>,
+    /// 所有消息的总长度
+    bytes: usize,
+    /// 队列的最大字节数，也是消息数目的上限
+    qbytes: usize,
+    /// 最后一次发送、接收与修改的时间
+    stime: usize,
+    rtime: usize,
+    ctime: usize,
+    /// 最后一次发送与接收的进程
+    lspid: u64,
+    lrpid: u64,
+    /// 已被 IPC_RMID 删除，等待者会得到 EIDRM
+    removed: bool,
+}
+
+pub struct MsgQueue {
+    state: SpinNoIrq<MsgState>,
+    /// 等待消息的接收者
+    recv_waiters: WaitQueue,
+    /// 等待队列腾出空间的发送者
+    send_waiters: WaitQueue,
+}
+
+impl IpcObject for MsgQueue {
+    fn perm(&self) -> IpcPerm {
+        self.state.lock().perm
+    }
+}
+
+impl MsgQueue {
+    fn new(key: i32, uid: u32, gid: u32, mode: u16) -> Self {
+        Self {
+            state: SpinNoIrq::new(MsgState {
+                perm: IpcPerm::new(key, uid, gid, mode),
+                messages: VecDeque::new(),
+                bytes: 0,
+                qbytes: MSGMNB,
+                stime: 0,
+                rtime: 0,
+                ctime: now_secs(),
+                lspid: 0,
+                lrpid: 0,
+                removed: false,
+            }),
+            recv_waiters: WaitQueue::new(),
+            send_waiters: WaitQueue::new(),
+        }
+    }
+
+    /// 放入一条消息，队列已满时返回 EAGAIN，若给出了 `cx` 则在返回前注册其 waker
+    fn send(
+        &self,
+        mtype: isize,
+        data: &[u8],
+        pid: u64,
+        cx: Option<&mut Context<'_>>,
+    ) -> Result<(), SyscallError> {
+        let mut state = self.state.lock();
+        if state.removed {
+            return Err(SyscallError::EIDRM);
+        }
+        if state.bytes + data.len() > state.qbytes || state.messages.len() + 1 > state.qbytes {
+            if let Some(cx) = cx {
+                let _ = self.send_waiters.wait_until(cx, || false);
+            }
+            return Err(SyscallError::EAGAIN);
+        }
+        state.messages.push_back((mtype, data.to_vec()));
+        state.bytes += data.len();
+        state.stime = now_secs();
+        state.lspid = pid;
+        drop(state);
+        self.recv_waiters.notify_all();
+        Ok(())
+    }
+
+    /// 按照 `msgtyp` 与 `flags` 取出一条消息，返回它的类型与最多 `maxsize` 字节的内容
+    ///
+    /// 没有符合的消息时返回 ENOMSG，若给出了 `cx` 则在返回前注册其 waker
+    fn receive(
+        &self,
+        msgtyp: isize,
+        maxsize: usize,
+        flags: i32,
+        pid: u64,
+        cx: Option<&mut Context<'_>>,
+    ) -> Result<(isize, Vec<u8>), SyscallError> {
+        let mut state = self.state.lock();
+        if state.removed {
+            return Err(SyscallError::EIDRM);
+        }
+        let messages = &state.messages;
+        let index = if flags & MSG_COPY != 0 {
+            usize::try_from(msgtyp)
+                .ok()
+                .filter(|index| *index < messages.len())
+        } else if msgtyp == 0 {
+            (!messages.is_empty()).then_some(0)
+        } else if msgtyp > 0 {
+            let except = flags & MSG_EXCEPT != 0;
+            messages
+                .iter()
+                .position(|(mtype, _)| (*mtype == msgtyp) != except)
+        } else {
+            // 类型不超过 |msgtyp| 的消息中类型最小的第一条
+            messages
+                .iter()
+                .enumerate()
+                .filter(|(_, (mtype, _))| mtype.unsigned_abs() <= msgtyp.unsigned_abs())
+                .min_by_key(|(index, (mtype, _))| (*mtype, *index))
+                .map(|(index, _)| index)
+        };
+        let Some(index) = index else {
+            if let Some(cx) = cx {
+                let _ = self.recv_waiters.wait_until(cx, || false);
+            }
+            return Err(SyscallError::ENOMSG);
+        };
+        if messages[index].1.len() > maxsize && flags & MSG_NOERROR == 0 {
+            return Err(SyscallError::E2BIG);
+        }
+        if flags & MSG_COPY != 0 {
+            let (mtype, data) = &messages[index];
+            return Ok((*mtype, data[..data.len().min(maxsize)].to_vec()));
+        }
+        let (mtype, mut data) = state.messages.remove(index).unwrap();
+        state.bytes -= data.len();
+        state.rtime = now_secs();
+        state.lrpid = pid;
+        drop(state);
+        self.send_waiters.notify_all();
+        data.truncate(maxsize);
+        Ok((mtype, data))
+    }
+
+    /// IPC_RMID：唤醒所有等待者，它们会得到 EIDRM
+    fn remove(&self) {
+        self.state.lock().removed = true;
+        self.recv_waiters.notify_all();
+        self.send_waiters.notify_all();
+    }
+
+    fn stat(&self, id: i32) -> MsqidDs {
+        let state = self.state.lock();
+        MsqidDs {
+            msg_perm: IpcPerm64::new(&state.perm, id),
+            msg_stime: state.stime as isize,
+            msg_rtime: state.rtime as isize,
+            msg_ctime: state.ctime as isize,
+            msg_cbytes: state.bytes,
+            msg_qnum: state.messages.len(),
+            msg_qbytes: state.qbytes,
+            msg_lspid: state.lspid as i32,
+            msg_lrpid: state.lrpid as i32,
+            ..Default::default()
+        }
+    }
+}
+
+/// 获取或创建一个消息队列
+///
+/// # Arguments
+/// * `key`: i32, 键，为 IPC_PRIVATE 时总是创建新的消息队列
+/// * `msgflg`: i32, IPC_CREAT、IPC_EXCL 与低 9 位的权限
+pub async fn syscall_msgget(args: [usize; 6]) -> SyscallResult {
+    let key = args[0] as i32;
+    let flags = args[1] as i32;
+    let (uid, gid) = current_cred();
+    ipc_get(
+        &MSG_QUEUES,
+        key,
+        flags,
+        |_| Ok(()),
+        || Ok(MsgQueue::new(key, uid, gid, (flags & 0o777) as u16)),
+    )
+}
+
+/// 获取标识符为 `msqid` 的消息队列，并检查调用者是否有 `flag` 请求的权限
+fn queue_of(msqid: i32, flag: u16) -> Result<alloc::sync::Arc<MsgQueue>, SyscallError> {
+    let queue = MSG_QUEUES.lock().get(msqid).ok_or(SyscallError::EINVAL)?;
+    let (uid, gid) = current_cred();
+    if !queue.perm().permits(uid, gid, flag) {
+        return Err(SyscallError::EACCES);
+    }
+    Ok(queue)
+}
+
+/// 发送一条消息，队列已满时等待
+///
+/// # Arguments
+/// * `msqid`: i32, 消息队列
+/// * `msgp`: *const u8, `struct msgbuf`，开头为 long 类型的 mtype，之后为内容
+/// * `msgsz`: usize, 内容的长度，不能超过 MSGMAX
+/// * `msgflg`: i32, IPC_NOWAIT 表示队列已满时返回 EAGAIN
+pub async fn syscall_msgsnd(args: [usize; 6]) -> SyscallResult {
+    let msqid = args[0] as i32;
+    let msgp = args[1];
+    let msgsz = args[2];
+    let flags = args[3] as i32;
+    if msgsz > MSGMAX || msqid < 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    let mtype = read_user(msgp as *const isize).await?;
+    if mtype < 1 {
+        return Err(SyscallError::EINVAL);
+    }
+    let text = msgp + core::mem::size_of::<isize>();
+    check_range(text, msgsz).await?;
+    let data: Vec<u8> = if msgsz == 0 {
+        Vec::new()
+    } else {
+        unsafe { core::slice::from_raw_parts(text as *const u8, msgsz) }.to_vec()
+    };
+    let queue = queue_of(msqid, S_IWUGO)?;
+    let pid = current_executor().pid().as_u64();
+    let non_block = flags & IPC_NOWAIT != 0;
+    let waker = poll_fn(|cx| Poll::Ready(cx.waker().clone())).await;
+    loop {
+        let sent =
+            poll_fn(|cx| Poll::Ready(queue.send(mtype, &data, pid, (!non_block).then_some(cx))))
+                .await;
+        match sent {
+            Err(SyscallError::EAGAIN) if !non_block => {
+                sleep_until_woken(None, true).await;
+                queue.send_waiters.remove_task(&waker);
+            }
+            result => return result.map(|_| 0),
+        }
+    }
+}
+
+/// 接收一条消息，没有符合的消息时等待，返回内容的长度
+///
+/// # Arguments
+/// * `msqid`: i32, 消息队列
+/// * `msgp`: *mut u8, 接收消息的 `struct msgbuf`
+/// * `msgsz`: usize, 内容的最大长度
+/// * `msgtyp`: isize, 为 0 时接收第一条消息，为正数时接收第一条该类型的消息，
+///   为负数时接收类型不超过其绝对值的消息中类型最小的一条
+/// * `msgflg`: i32, IPC_NOWAIT、MSG_NOERROR、MSG_EXCEPT 与 MSG_COPY 的组合
+pub async fn syscall_msgrcv(args: [usize; 6]) -> SyscallResult {
+    let msqid = args[0] as i32;
+    let msgp = args[1];
+    let msgsz = args[2];
+    let msgtyp = args[3] as isize;
+    let flags = args[4] as i32;
+    if msqid < 0 || (msgsz as isize) < 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    if flags & MSG_COPY != 0 && (flags & IPC_NOWAIT == 0 || flags & MSG_EXCEPT != 0) {
+        return Err(SyscallError::EINVAL);
+    }
+    check_range(msgp, core::mem::size_of::<isize>() + msgsz).await?;
+    let queue = queue_of(msqid, S_IRUGO)?;
+    let pid = current_executor().pid().as_u64();
+    let non_block = flags & IPC_NOWAIT != 0;
+    let waker = poll_fn(|cx| Poll::Ready(cx.waker().clone())).await;
+    let (mtype, data) = loop {
+        let received = poll_fn(|cx| {
+            Poll::Ready(queue.receive(msgtyp, msgsz, flags, pid, (!non_block).then_some(cx)))
+        })
+        .await;
+        match received {
+            Err(SyscallError::ENOMSG) if !non_block => {
+                sleep_until_woken(None, true).await;
+                queue.recv_waiters.remove_task(&waker);
+            }
+            result => break result?,
+        }
+    };
+    unsafe {
+        (msgp as *mut isize).write(mtype);
+        let text = (msgp + core::mem::size_of::<isize>()) as *mut u8;
+        core::ptr::copy_nonoverlapping(data.as_ptr(), text, data.len());
+    }
+    Ok(data.len() as isize)
+}
+
+/// 消息队列的控制操作
+///
+/// # Arguments
+/// * `msqid`: i32, 消息队列，MSG_STAT 与 MSG_STAT_ANY 时为位置
+/// * `cmd`: i32, 命令
+/// * `buf`: *mut MsqidDs, 读取或写入的结构体，IPC_INFO 与 MSG_INFO 时为 `struct msginfo`
+pub async fn syscall_msgctl(args: [usize; 6]) -> SyscallResult {
+    let msqid = args[0] as i32;
+    let cmd = args[1] as i32 & !IPC_64;
+    let buf = args[2];
+    let (uid, gid) = current_cred();
+    if cmd == IPC_INFO || cmd == MSG_INFO {
+        let ids = MSG_QUEUES.lock();
+        let mut info = MsgInfo {
+            msgpool: (MSGMNI * MSGMNB / 1024) as i32,
+            msgmap: MSGMNB as i32,
+            msgmax: MSGMAX as i32,
+            msgmnb: MSGMNB as i32,
+            msgmni: MSGMNI as i32,
+            msgssz: 16,
+            msgtql: MSGMNB as i32,
+            msgseg: 0xffff,
+        };
+        if cmd == MSG_INFO {
+            info.msgpool = ids.len() as i32;
+            let (count, bytes) = ids.iter().fold((0, 0), |(count, bytes), (_, queue)| {
+                let state = queue.state.lock();
+                (count + state.messages.len(), bytes + state.bytes)
+            });
+            info.msgmap = count as i32;
+            info.msgtql = bytes as i32;
+        }
+        drop(ids);
+        write_user(buf as *mut MsgInfo, info).await?;
+        return Ok(ipc_max_index(&MSG_QUEUES));
+    }
+    let by_index = cmd == MSG_STAT || cmd == MSG_STAT_ANY;
+    let (id, queue) = ipc_lookup(&MSG_QUEUES, msqid, by_index)?;
+    let perm = queue.perm();
+    match cmd {
+        IPC_STAT | MSG_STAT | MSG_STAT_ANY => {
+            if cmd != MSG_STAT_ANY && !perm.permits(uid, gid, S_IRUGO) {
+                return Err(SyscallError::EACCES);
+            }
+            write_user(buf as *mut MsqidDs, queue.stat(id)).await?;
+            Ok(if by_index { id as isize } else { 0 })
+        }
+        IPC_SET => {
+            let ds = read_user(buf as *const MsqidDs).await?;
+            if !perm.is_owner(uid) || (ds.msg_qbytes > MSGMNB && uid != 0) {
+                return Err(SyscallError::EPERM);
+            }
+            let mut state = queue.state.lock();
+            state
+                .perm
+                .set(ds.msg_perm.uid, ds.msg_perm.gid, ds.msg_perm.mode as u16);
+            state.qbytes = ds.msg_qbytes;
+            state.ctime = now_secs();
+            drop(state);
+            // 队列可能变大
+            queue.send_waiters.notify_all();
+            Ok(0)
+        }
+        IPC_RMID => {
+            if !perm.is_owner(uid) {
+                return Err(SyscallError::EPERM);
+            }
+            MSG_QUEUES.lock().remove(id);
+            queue.remove();
+            Ok(0)
+        }
+        _ => Err(SyscallError::EINVAL),
+    }
+}
@@ -0,0 +1,560 @@
+//! System V 信号量集
+//!
+//! 带有 SEM_UNDO 的操作以进程为单位记录调整值，进程退出时由 [`sem_undo_on_exit`] 撤销
+extern crate alloc;
+
+use super::ipc::*;
+use crate::syscall_fs::imp::{sleep_until_woken, timespec_deadline};
+use crate::{SyscallError, SyscallResult, TimeSecs};
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use async_mem::ipc::{IpcIds, IpcPerm};
+use axhal::time::current_time;
+use core::future::poll_fn;
+use core::task::{Context, Poll};
+use executor::current_executor;
+use spinlock::SpinNoIrq;
+use sync::WaitQueue;
+
+/// 一个信号量集中信号量的最大数目
+const SEMMSL: usize = 32000;
+/// 信号量集的最大数目
+const SEMMNI: usize = 32000;
+/// 所有信号量的最大数目
+const SEMMNS: usize = SEMMSL * SEMMNI;
+/// 一次 semop 的最大操作数
+const SEMOPM: usize = 500;
+/// 信号量的最大值，也是 SEM_UNDO 调整值的上限
+const SEMVMX: i32 = 32767;
+
+/// 进程退出时撤销这个操作
+const SEM_UNDO: i16 = 0x1000;
+
+/// semctl 的命令
+const GETPID: i32 = 11;
+const GETVAL: i32 = 12;
+const GETALL: i32 = 13;
+const GETNCNT: i32 = 14;
+const GETZCNT: i32 = 15;
+const SETVAL: i32 = 16;
+const SETALL: i32 = 17;
+const SEM_STAT: i32 = 18;
+const SEM_INFO: i32 = 19;
+const SEM_STAT_ANY: i32 = 20;
+
+/// 所有的信号量集
+static SEM_SETS: SpinNoIrq<IpcIds<SemSet>> = SpinNoIrq::new(IpcIds::new(SEMMNI));
+
+/// semop 的一个操作 `struct sembuf`
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct SemBuf {
+    pub sem_num: u16,
+    /// 正数增加信号量，负数等待信号量足够大后减少它，0 等待信号量变为 0
+    pub sem_op: i16,
+    /// IPC_NOWAIT 与 SEM_UNDO
+    pub sem_flg: i16,
+}
+
+/// 用户空间的 `struct semid64_ds`，x86_64 上时间之后各有一个未使用的字段
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SemidDs {
+    pub sem_perm: IpcPerm64,
+    pub sem_otime: isize,
+    #[cfg(target_arch = "x86_64")]
+    __unused1: usize,
+    pub sem_ctime: isize,
+    #[cfg(target_arch = "x86_64")]
+    __unused2: usize,
+    pub sem_nsems: usize,
+    __unused3: usize,
+    __unused4: usize,
+}
+
+/// IPC_INFO 与 SEM_INFO 返回的 `struct seminfo`
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct SemInfo {
+    pub semmap: i32,
+    pub semmni: i32,
+    pub semmns: i32,
+    pub semmnu: i32,
+    pub semmsl: i32,
+    pub semopm: i32,
+    pub semume: i32,
+    pub semusz: i32,
+    pub semvmx: i32,
+    pub semaem: i32,
+}
+
+struct Sem {
+    val: i32,
+    /// 最后一次操作该信号量的进程
+    pid: u64,
+    /// 等待信号量增加的任务数
+    ncnt: usize,
+    /// 等待信号量变为 0 的任务数
+    zcnt: usize,
+}
+
+struct SemState {
+    perm: IpcPerm,
+    sems: Vec<Sem>,
+    /// 最后一次 semop 与修改的时间
+    otime: usize,
+    ctime: usize,
+    /// SEM_UNDO 记录的调整值，以进程为键，进程退出时加到信号量上
+    undo: BTreeMap<u64, Vec<i32>>,
+    /// 已被 IPC_RMID 删除，等待者会得到 EIDRM
+    removed: bool,
+}
+
+/// semop 的结果
+enum Semop {
+    Done,
+    /// 需要等待第 `sem_num` 个信号量，`zero` 表示等待它变为 0
+    Wait {
+        sem_num: usize,
+        zero: bool,
+    },
+}
+
+pub struct SemSet {
+    state: SpinNoIrq<SemState>,
+    waiters: WaitQueue,
+}
+
+impl IpcObject for SemSet {
+    fn perm(&self) -> IpcPerm {
+        self.state.lock().perm
+    }
+}
+
+/// 见 [`SemSet::waiting`]
+struct SemWaiter<'a> {
+    set: &'a SemSet,
+    sem_num: usize,
+    zero: bool,
+}
+
+impl Drop for SemWaiter<'_> {
+    fn drop(&mut self) {
+        let mut state = self.set.state.lock();
+        let sem = &mut state.sems[self.sem_num];
+        if self.zero {
+            sem.zcnt -= 1;
+        } else {
+            sem.ncnt -= 1;
+        }
+    }
+}
+
+impl SemSet {
+    fn new(key: i32, nsems: usize, uid: u32, gid: u32, mode: u16) -> Self {
+        Self {
+            state: SpinNoIrq::new(SemState {
+                perm: IpcPerm::new(key, uid, gid, mode),
+                sems: (0..nsems)
+                    .map(|_| Sem {
+                        val: 0,
+                        pid: 0,
+                        ncnt: 0,
+                        zcnt: 0,
+                    })
+                    .collect(),
+                otime: 0,
+                ctime: now_secs(),
+                undo: BTreeMap::new(),
+                removed: false,
+            }),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    fn nsems(&self) -> usize {
+        self.state.lock().sems.len()
+    }
+
+    /// 原子地执行 `ops` 中的所有操作，任何一个操作需要等待时都不执行
+    ///
+    /// 需要等待且给出了 `cx` 时在返回前注册其 waker
+    fn semop(
+        &self,
+        ops: &[SemBuf],
+        pid: u64,
+        cx: Option<&mut Context<'_>>,
+    ) -> Result<Semop, SyscallError> {
+        let mut state = self.state.lock();
+        if state.removed {
+            return Err(SyscallError::EIDRM);
+        }
+        let mut vals: Vec<i32> = state.sems.iter().map(|sem| sem.val).collect();
+        let mut adjs = state
+            .undo
+            .get(&pid)
+            .cloned()
+            .unwrap_or_else(|| vec![0; vals.len()]);
+        for op in ops {
+            let sem_num = op.sem_num as usize;
+            let sem_op = op.sem_op as i32;
+            let val = &mut vals[sem_num];
+            let blocked = if sem_op == 0 {
+                *val != 0
+            } else {
+                *val + sem_op < 0
+            };
+            if blocked {
+                if op.sem_flg & IPC_NOWAIT as i16 != 0 {
+                    return Err(SyscallError::EAGAIN);
+                }
+                if let Some(cx) = cx {
+                    let _ = self.waiters.wait_until(cx, || false);
+                }
+                return Ok(Semop::Wait {
+                    sem_num,
+                    zero: sem_op == 0,
+                });
+            }
+            if *val + sem_op > SEMVMX {
+                return Err(SyscallError::ERANGE);
+            }
+            if op.sem_flg & SEM_UNDO != 0 {
+                let adj = adjs[sem_num] - sem_op;
+                if !(-SEMVMX - 1..=SEMVMX).contains(&adj) {
+                    return Err(SyscallError::ERANGE);
+                }
+                adjs[sem_num] = adj;
+            }
+            *val += sem_op;
+        }
+        let mut changed = false;
+        for (sem, val) in state.sems.iter_mut().zip(vals) {
+            changed |= sem.val != val;
+            sem.val = val;
+        }
+        for op in ops {
+            state.sems[op.sem_num as usize].pid = pid;
+        }
+        if ops.iter().any(|op| op.sem_flg & SEM_UNDO != 0) {
+            state.undo.insert(pid, adjs);
+        }
+        state.otime = now_secs();
+        drop(state);
+        if changed {
+            self.waiters.notify_all();
+        }
+        Ok(Semop::Done)
+    }
+
+    /// 标记一个等待第 `sem_num` 个信号量的任务，用于 GETNCNT 与 GETZCNT，返回值被释放时取消标记
+    fn waiting(&self, sem_num: usize, zero: bool) -> SemWaiter<'_> {
+        let mut state = self.state.lock();
+        let sem = &mut state.sems[sem_num];
+        if zero {
+            sem.zcnt += 1;
+        } else {
+            sem.ncnt += 1;
+        }
+        SemWaiter {
+            set: self,
+            sem_num,
+            zero,
+        }
+    }
+
+    /// 设置信号量的值，被设置的信号量上的 SEM_UNDO 调整值被清除
+    ///
+    /// `values` 中的每一项为信号量的位置与新的值
+    fn set_values(&self, values: impl Iterator<Item = (usize, i32)>, pid: u64) {
+        let mut guard = self.state.lock();
+        let state = &mut *guard;
+        for (sem_num, val) in values {
+            state.sems[sem_num].val = val;
+            state.sems[sem_num].pid = pid;
+            for adjs in state.undo.values_mut() {
+                adjs[sem_num] = 0;
+            }
+        }
+        state.ctime = now_secs();
+        drop(guard);
+        self.waiters.notify_all();
+    }
+
+    /// 撤销进程 `pid` 的 SEM_UNDO 操作，结果被限制在 [0, SEMVMX] 之间
+    fn undo(&self, pid: u64) {
+        let mut state = self.state.lock();
+        let Some(adjs) = state.undo.remove(&pid) else {
+            return;
+        };
+        let mut changed = false;
+        for (sem, adj) in state.sems.iter_mut().zip(adjs) {
+            if adj != 0 {
+                sem.val = (sem.val + adj).clamp(0, SEMVMX);
+                sem.pid = pid;
+                changed = true;
+            }
+        }
+        drop(state);
+        if changed {
+            self.waiters.notify_all();
+        }
+    }
+
+    /// IPC_RMID：唤醒所有等待者，它们会得到 EIDRM
+    fn remove(&self) {
+        self.state.lock().removed = true;
+        self.waiters.notify_all();
+    }
+
+    fn stat(&self, id: i32) -> SemidDs {
+        let state = self.state.lock();
+        SemidDs {
+            sem_perm: IpcPerm64::new(&state.perm, id),
+            sem_otime: state.otime as isize,
+            sem_ctime: state.ctime as isize,
+            sem_nsems: state.sems.len(),
+            ..Default::default()
+        }
+    }
+}
+
+/// 进程退出时撤销它在所有信号量集上的 SEM_UNDO 操作
+pub fn sem_undo_on_exit() {
+    let pid = current_executor().pid().as_u64();
+    let sets: Vec<Arc<SemSet>> = SEM_SETS.lock().iter().map(|(_, set)| set.clone()).collect();
+    for set in sets {
+        set.undo(pid);
+    }
+}
+
+/// 获取或创建一个信号量集
+///
+/// # Arguments
+/// * `key`: i32, 键，为 IPC_PRIVATE 时总是创建新的信号量集
+/// * `nsems`: i32, 信号量的数目，获取已有的信号量集时不能超过它的大小
+/// * `semflg`: i32, IPC_CREAT、IPC_EXCL 与低 9 位的权限
+pub async fn syscall_semget(args: [usize; 6]) -> SyscallResult {
+    let key = args[0] as i32;
+    let nsems = args[1] as i32;
+    let flags = args[2] as i32;
+    if nsems < 0 || nsems as usize > SEMMSL {
+        return Err(SyscallError::EINVAL);
+    }
+    let nsems = nsems as usize;
+    let (uid, gid) = current_cred();
+    ipc_get(
+        &SEM_SETS,
+        key,
+        flags,
+        |set| {
+            if nsems > set.nsems() {
+                return Err(SyscallError::EINVAL);
+            }
+            Ok(())
+        },
+        || {
+            if nsems == 0 {
+                return Err(SyscallError::EINVAL);
+            }
+            Ok(SemSet::new(key, nsems, uid, gid, (flags & 0o777) as u16))
+        },
+    )
+}
+
+/// 对信号量集执行一组操作，见 [`syscall_semtimedop`]
+///
+/// # Arguments
+/// * `semid`: i32, 信号量集
+/// * `sops`: *const SemBuf, 操作数组
+/// * `nsops`: usize, 操作的数目
+pub async fn syscall_semop(args: [usize; 6]) -> SyscallResult {
+    syscall_semtimedop([args[0], args[1], args[2], 0, 0, 0]).await
+}
+
+/// 原子地对信号量集执行一组操作，任何一个操作无法立即完成时等待
+///
+/// # Arguments
+/// * `semid`: i32, 信号量集
+/// * `sops`: *const SemBuf, 操作数组
+/// * `nsops`: usize, 操作的数目，不能超过 SEMOPM
+/// * `timeout`: *const TimeSecs, 相对的超时时间，为空时一直等待，超时返回 EAGAIN
+pub async fn syscall_semtimedop(args: [usize; 6]) -> SyscallResult {
+    let semid = args[0] as i32;
+    let sops = args[1] as *const SemBuf;
+    let nsops = args[2];
+    if nsops == 0 || semid < 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    if nsops > SEMOPM {
+        return Err(SyscallError::E2BIG);
+    }
+    check_range(sops as usize, nsops * core::mem::size_of::<SemBuf>()).await?;
+    let ops: Vec<SemBuf> = (0..nsops).map(|i| unsafe { *sops.add(i) }).collect();
+    let deadline = timespec_deadline(args[3] as *const TimeSecs).await?;
+    let set = SEM_SETS.lock().get(semid).ok_or(SyscallError::EINVAL)?;
+    if ops.iter().any(|op| op.sem_num as usize >= set.nsems()) {
+        return Err(SyscallError::EFBIG);
+    }
+    let alter = ops.iter().any(|op| op.sem_op != 0);
+    let (uid, gid) = current_cred();
+    if !set
+        .perm()
+        .permits(uid, gid, if alter { S_IWUGO } else { S_IRUGO })
+    {
+        return Err(SyscallError::EACCES);
+    }
+    let pid = current_executor().pid().as_u64();
+    let waker = poll_fn(|cx| Poll::Ready(cx.waker().clone())).await;
+    loop {
+        // 在释放锁之前注册，避免错过其他进程的唤醒
+        let result = poll_fn(|cx| Poll::Ready(set.semop(&ops, pid, Some(cx)))).await?;
+        let Semop::Wait { sem_num, zero } = result else {
+            return Ok(0);
+        };
+        if deadline.is_some_and(|deadline| current_time() >= deadline) {
+            set.waiters.remove_task(&waker);
+            return Err(SyscallError::EAGAIN);
+        }
+        let waiter = set.waiting(sem_num, zero);
+        sleep_until_woken(deadline, true).await;
+        drop(waiter);
+        set.waiters.remove_task(&waker);
+    }
+}
+
+/// 信号量集的控制操作
+///
+/// # Arguments
+/// * `semid`: i32, 信号量集，SEM_STAT 与 SEM_STAT_ANY 时为位置
+/// * `semnum`: i32, GETVAL 等针对单个信号量的命令操作的信号量
+/// * `cmd`: i32, 命令
+/// * `arg`: union semun, SETVAL 时为新的值，其他命令为用户空间的指针
+pub async fn syscall_semctl(args: [usize; 6]) -> SyscallResult {
+    let semid = args[0] as i32;
+    let semnum = args[1] as i32;
+    let cmd = args[2] as i32 & !IPC_64;
+    let arg = args[3];
+    let (uid, gid) = current_cred();
+    if cmd == IPC_INFO || cmd == SEM_INFO {
+        let ids = SEM_SETS.lock();
+        let mut info = SemInfo {
+            semmap: SEMMNS as i32,
+            semmni: SEMMNI as i32,
+            semmns: SEMMNS as i32,
+            semmnu: SEMMNS as i32,
+            semmsl: SEMMSL as i32,
+            semopm: SEMOPM as i32,
+            semume: SEMOPM as i32,
+            semusz: 20,
+            semvmx: SEMVMX,
+            semaem: SEMVMX,
+        };
+        if cmd == SEM_INFO {
+            info.semusz = ids.len() as i32;
+            info.semaem = ids.iter().map(|(_, set)| set.nsems()).sum::<usize>() as i32;
+        }
+        drop(ids);
+        write_user(arg as *mut SemInfo, info).await?;
+        return Ok(ipc_max_index(&SEM_SETS));
+    }
+    let by_index = cmd == SEM_STAT || cmd == SEM_STAT_ANY;
+    let (id, set) = ipc_lookup(&SEM_SETS, semid, by_index)?;
+    let perm = set.perm();
+    let nsems = set.nsems();
+    let pid = current_executor().pid().as_u64();
+    let check = |flag: u16| {
+        if perm.permits(uid, gid, flag) {
+            Ok(())
+        } else {
+            Err(SyscallError::EACCES)
+        }
+    };
+    let sem_num = usize::try_from(semnum)
+        .ok()
+        .filter(|sem_num| *sem_num < nsems)
+        .ok_or(SyscallError::EINVAL);
+    match cmd {
+        IPC_STAT | SEM_STAT | SEM_STAT_ANY => {
+            if cmd != SEM_STAT_ANY {
+                check(S_IRUGO)?;
+            }
+            write_user(arg as *mut SemidDs, set.stat(id)).await?;
+            Ok(if by_index { id as isize } else { 0 })
+        }
+        IPC_SET => {
+            let ds = read_user(arg as *const SemidDs).await?;
+            if !perm.is_owner(uid) {
+                return Err(SyscallError::EPERM);
+            }
+            let mut state = set.state.lock();
+            state
+                .perm
+                .set(ds.sem_perm.uid, ds.sem_perm.gid, ds.sem_perm.mode as u16);
+            state.ctime = now_secs();
+            Ok(0)
+        }
+        IPC_RMID => {
+            if !perm.is_owner(uid) {
+                return Err(SyscallError::EPERM);
+            }
+            SEM_SETS.lock().remove(id);
+            set.remove();
+            Ok(0)
+        }
+        GETVAL | GETPID | GETNCNT | GETZCNT => {
+            check(S_IRUGO)?;
+            let sem_num = sem_num?;
+            let state = set.state.lock();
+            let sem = &state.sems[sem_num];
+            Ok(match cmd {
+                GETVAL => sem.val as isize,
+                GETPID => sem.pid as isize,
+                GETNCNT => sem.ncnt as isize,
+                _ => sem.zcnt as isize,
+            })
+        }
+        GETALL => {
+            check(S_IRUGO)?;
+            check_range(arg, nsems * core::mem::size_of::<u16>()).await?;
+            let values: Vec<u16> = set
+                .state
+                .lock()
+                .sems
+                .iter()
+                .map(|sem| sem.val as u16)
+                .collect();
+            let array = arg as *mut u16;
+            for (i, val) in values.into_iter().enumerate() {
+                unsafe { array.add(i).write(val) };
+            }
+            Ok(0)
+        }
+        SETVAL => {
+            check(S_IWUGO)?;
+            let sem_num = sem_num?;
+            let val = arg as i32;
+            if !(0..=SEMVMX).contains(&val) {
+                return Err(SyscallError::ERANGE);
+            }
+            set.set_values(core::iter::once((sem_num, val)), pid);
+            Ok(0)
+        }
+        SETALL => {
+            check(S_IWUGO)?;
+            check_range(arg, nsems * core::mem::size_of::<u16>()).await?;
+            let array = arg as *const u16;
+            let values: Vec<i32> = (0..nsems)
+                .map(|i| unsafe { *array.add(i) } as i32)
+                .collect();
+            if values.iter().any(|val| *val > SEMVMX) {
+                return Err(SyscallError::ERANGE);
+            }
+            set.set_values(values.into_iter().enumerate(), pid);
+            Ok(0)
+        }
+        _ => Err(SyscallError::EINVAL),
+    }
+}
@@ -1,98 +1,251 @@
 //! 相关系统调用的具体实现
+//!
+//! AF_UNIX 套接字由 [`UnixSocket`] 实现；启用 `net` feature 时 AF_INET 套接字由
+//! [`InetSocket`](super::inet::InetSocket) 实现，否则 AF_INET 返回 EAFNOSUPPORT
 extern crate alloc;
+#[cfg(feature = "net")]
+use super::inet::{self, InetSocket};
 use super::socket::*;
+use super::unix::{UnixAddr, UnixSocket};
 use core::slice::{from_raw_parts, from_raw_parts_mut};
 
 use alloc::sync::Arc;
-use axfs::api::{FileIO, OpenFlags};
+use alloc::vec;
+use alloc::vec::Vec;
+use async_fs::api::FileIO;
+use executor::{current_executor, FdFlags};
+
+use crate::syscall_fs::imp::fd_err;
+use crate::{IoVec, SyscallError, SyscallResult};
+
+/// 与 Linux 相同，AF_UNIX 套接字的 protocol 只能是 0 或 PF_UNIX
+const PF_UNIX: usize = 1;
+/// `struct sockaddr_storage` 的长度，地址不能比它更长
+const SOCKADDR_MAX: usize = 128;
+
+/// 检查用户空间的 `[start, start + len)` 是否可以访问
+async fn check_range(start: usize, len: usize) -> Result<(), SyscallError> {
+    if len == 0 {
+        return Ok(());
+    }
+    if start == 0 {
+        return Err(SyscallError::EFAULT);
+    }
+    let end = start.checked_add(len - 1).ok_or(SyscallError::EFAULT)?;
+    current_executor()
+        .manual_alloc_range_for_lazy(start.into(), end.into())
+        .await
+        .map_err(|_| SyscallError::EFAULT)
+}
+
+/// 获取 `fd` 对应的文件，并检查它是否为套接字
+async fn get_socket(fd: usize) -> Result<Arc<dyn FileIO>, SyscallError> {
+    let Some(file) = current_executor().fd_manager.get(fd).await else {
+        return Err(SyscallError::EBADF);
+    };
+    let is_socket = file.as_any().is::<UnixSocket>();
+    #[cfg(feature = "net")]
+    let is_socket = is_socket || file.as_any().is::<InetSocket>();
+    if !is_socket {
+        return Err(SyscallError::ENOTSOCK);
+    }
+    Ok(file)
+}
 
-use crate::{syscall_fs::ctype::pipe::make_pipe, SyscallError, SyscallResult};
-use axerrno::AxError;
-use axlog::{debug, error, info, warn};
+/// AF_UNIX 特有的操作，对其他套接字返回 EOPNOTSUPP
+fn as_unix(file: &Arc<dyn FileIO>) -> Result<&UnixSocket, SyscallError> {
+    file.as_any()
+        .downcast_ref::<UnixSocket>()
+        .ok_or(SyscallError::EOPNOTSUPP)
+}
 
-use axprocess::current_process;
-use num_enum::TryFromPrimitive;
+#[cfg(feature = "net")]
+fn as_inet(file: &Arc<dyn FileIO>) -> Option<&InetSocket> {
+    file.as_any().downcast_ref::<InetSocket>()
+}
 
-pub const SOCKET_TYPE_MASK: usize = 0xFF;
+/// 读取用户给出的 `addr_len` 字节的地址
+async fn read_raw_addr<'a>(addr: *const u8, addr_len: usize) -> Result<&'a [u8], SyscallError> {
+    if addr_len > SOCKADDR_MAX {
+        return Err(SyscallError::EINVAL);
+    }
+    check_range(addr as usize, addr_len).await?;
+    if addr_len == 0 {
+        return Ok(&[]);
+    }
+    Ok(unsafe { from_raw_parts(addr, addr_len) })
+}
 
-/// # Arguments
-/// * `domain` - usize
-/// * `s_type` - usize
-/// * `protocol` - usize
-pub fn syscall_socket(args: [usize; 6]) -> SyscallResult {
-    let domain = args[0];
-    let s_type = args[1];
-    let _protocol = args[2];
-    let Ok(domain) = Domain::try_from(domain) else {
-        error!("[socket()] Address Family not supported: {domain}");
-        // return ErrorNo::EAFNOSUPPORT as isize;
-        return Err(SyscallError::EAFNOSUPPORT);
+/// 读取用户给出的 AF_UNIX 地址
+async fn read_addr(addr: *const u8, addr_len: usize) -> Result<UnixAddr, SyscallError> {
+    if addr_len > core::mem::size_of::<u16>() + 108 {
+        return Err(SyscallError::EINVAL);
+    }
+    UnixAddr::from_raw(read_raw_addr(addr, addr_len).await?)
+}
+
+/// 将 AF_UNIX 地址写回用户空间
+async fn write_addr(addr: &UnixAddr, buf: *mut u8, addr_len: *mut u32) -> Result<(), SyscallError> {
+    write_raw_addr(&addr.to_raw(), buf, addr_len).await
+}
+
+/// 将地址写回用户空间，`addr_len` 是值-结果参数
+///
+/// 缓冲区不足时地址被截断，`addr_len` 总是被设置为地址的真实长度
+async fn write_raw_addr(raw: &[u8], buf: *mut u8, addr_len: *mut u32) -> Result<(), SyscallError> {
+    if buf.is_null() {
+        return Ok(());
+    }
+    if addr_len.is_null() || current_executor().manual_alloc_type_for_lazy(addr_len).await.is_err() {
+        return Err(SyscallError::EFAULT);
+    }
+    let len = unsafe { *addr_len } as i32;
+    if len < 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    let copied = raw.len().min(len as usize);
+    check_range(buf as usize, copied).await?;
+    unsafe {
+        from_raw_parts_mut(buf, copied).copy_from_slice(&raw[..copied]);
+        *addr_len = raw.len() as u32;
+    }
+    Ok(())
+}
+
+/// 将 SOCK_NONBLOCK 与 SOCK_CLOEXEC 之外的位视为非法
+fn socket_flags(flags: usize) -> Result<(bool, FdFlags), SyscallError> {
+    if flags & !(SOCK_NONBLOCK | SOCK_CLOEXEC) != 0 {
+        return Err(SyscallError::EINVAL);
+    }
+    let fd_flags = if flags & SOCK_CLOEXEC != 0 {
+        FdFlags::CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
+    Ok((flags & SOCK_NONBLOCK != 0, fd_flags))
+}
+
+/// 解析 socket 与 socketpair 的参数，返回地址族、套接字类型、是否非阻塞与文件描述符的 flags
+///
+/// AF_INET 套接字的类型与协议由 [`InetSocket::new`](super::inet::InetSocket::new) 检查
+fn parse_socket_args(
+    domain: usize,
+    s_type: usize,
+    protocol: usize,
+) -> Result<(Domain, SocketType, bool, FdFlags), SyscallError> {
+    let domain = match Domain::try_from(domain) {
+        Ok(Domain::AF_UNIX) => Domain::AF_UNIX,
+        #[cfg(feature = "net")]
+        Ok(Domain::AF_INET) => Domain::AF_INET,
+        _ => {
+            error!("[socket()] Address Family not supported: {domain}");
+            return Err(SyscallError::EAFNOSUPPORT);
+        }
     };
     let Ok(socket_type) = SocketType::try_from(s_type & SOCKET_TYPE_MASK) else {
-        // return ErrorNo::EINVAL as isize;
         return Err(SyscallError::EINVAL);
     };
-    let socket = Socket::new(domain, socket_type);
-    socket.set_nonblocking((s_type & SOCK_NONBLOCK) != 0);
-    socket.set_close_on_exec((s_type & SOCK_CLOEXEC) != 0);
-    let curr = current_process();
-    let mut fd_table = curr.fd_manager.fd_table.lock();
-    let Ok(fd) = curr.alloc_fd(&mut fd_table) else {
-        return Err(SyscallError::EMFILE);
-    };
-
-    fd_table[fd] = Some(Arc::new(socket));
+    let (nonblock, fd_flags) = socket_flags(s_type & !SOCKET_TYPE_MASK)?;
+    if domain != Domain::AF_UNIX {
+        return Ok((domain, socket_type, nonblock, fd_flags));
+    }
+    let socket_type = match socket_type {
+        SocketType::SOCK_STREAM | SocketType::SOCK_DGRAM | SocketType::SOCK_SEQPACKET => socket_type,
+        // 与 Linux 相同，SOCK_RAW 被当作 SOCK_DGRAM
+        SocketType::SOCK_RAW => SocketType::SOCK_DGRAM,
+        _ => return Err(SyscallError::ESOCKTNOSUPPORT),
+    };
+    if protocol != 0 && protocol != PF_UNIX {
+        return Err(SyscallError::EPROTONOSUPPORT);
+    }
+    Ok((domain, socket_type, nonblock, fd_flags))
+}
 
+/// # Arguments
+/// * `domain` - usize
+/// * `s_type` - usize
+/// * `protocol` - usize
+pub async fn syscall_socket(args: [usize; 6]) -> SyscallResult {
+    let (domain, socket_type, nonblock, fd_flags) = parse_socket_args(args[0], args[1], args[2])?;
+    let socket: Arc<dyn FileIO> = match domain {
+        #[cfg(feature = "net")]
+        Domain::AF_INET => {
+            let socket = InetSocket::new(socket_type, args[2])?;
+            socket.set_nonblocking(nonblock);
+            Arc::new(socket)
+        }
+        _ => UnixSocket::new(socket_type, nonblock),
+    };
+    let fd = current_executor()
+        .fd_manager
+        .alloc(socket, fd_flags)
+        .await
+        .map_err(fd_err)?;
     debug!("[socket()] create socket {fd}");
-
     Ok(fd as isize)
 }
 
+/// # Arguments
+/// * `domain` - usize
+/// * `s_type` - usize
+/// * `protocol` - usize
+/// * `sv` - *mut [i32; 2]
+pub async fn syscall_socketpair(args: [usize; 6]) -> SyscallResult {
+    let sv = args[3] as *mut [i32; 2];
+    let (domain, socket_type, nonblock, fd_flags) = parse_socket_args(args[0], args[1], args[2])?;
+    if domain != Domain::AF_UNIX {
+        return Err(SyscallError::EOPNOTSUPP);
+    }
+    let process = current_executor();
+    if sv.is_null() || process.manual_alloc_type_for_lazy(sv).await.is_err() {
+        return Err(SyscallError::EFAULT);
+    }
+    let (first, second) = UnixSocket::pair(socket_type, nonblock);
+    let first_fd = process.fd_manager.alloc(first, fd_flags).await.map_err(fd_err)?;
+    let second_fd = match process.fd_manager.alloc(second, fd_flags).await {
+        Ok(fd) => fd,
+        Err(err) => {
+            let _ = process.fd_manager.close(first_fd).await;
+            return Err(fd_err(err));
+        }
+    };
+    unsafe {
+        *sv = [first_fd as i32, second_fd as i32];
+    }
+    Ok(0)
+}
+
+/// 地址长度只有 sa_family 时自动绑定到抽象命名空间
 /// # Arguments
 /// * `fd` - usize
 /// * `addr` - *const u8
 /// * `addr_len` - usize
-pub fn syscall_bind(args: [usize; 6]) -> SyscallResult {
-    let fd = args[0];
-    let addr = args[1] as *const u8;
-    let _addr_len = args[2];
-    let curr = current_process();
-
-    let file = match curr.fd_manager.fd_table.lock().get(fd) {
-        Some(Some(file)) => file.clone(),
-        _ => return Err(SyscallError::EBADF),
-    };
-
-    let Some(socket) = file.as_any().downcast_ref::<Socket>() else {
-        return Err(SyscallError::ENOTSOCK);
-    };
-    // different action for AF_INET and AF_UNIX
-    let addr = unsafe { socket_address_from(addr, socket) };
-
-    info!("[bind()] binding socket {} to {:?}", fd, addr);
-
-    Ok(socket.bind(addr).map_or(-1, |_| 0))
+pub async fn syscall_bind(args: [usize; 6]) -> SyscallResult {
+    let file = get_socket(args[0]).await?;
+    #[cfg(feature = "net")]
+    if let Some(socket) = as_inet(&file) {
+        let raw = read_raw_addr(args[1] as *const u8, args[2] as u32 as usize).await?;
+        socket.bind(inet::addr_from_raw(raw)?).map_err(inet::net_err)?;
+        return Ok(0);
+    }
+    let addr = read_addr(args[1] as *const u8, args[2] as u32 as usize).await?;
+    as_unix(&file)?.bind(addr).await?;
+    Ok(0)
 }
 
-// TODO: support change `backlog` for tcp socket
 /// # Arguments
 /// * `fd` - usize
 /// * `backlog` - usize
-pub fn syscall_listen(args: [usize; 6]) -> SyscallResult {
-    let fd = args[0];
-    let _backlog = args[1];
-    let curr = current_process();
-
-    let file = match curr.fd_manager.fd_table.lock().get(fd) {
-        Some(Some(file)) => file.clone(),
-        _ => return Err(SyscallError::EBADF),
-    };
-
-    let Some(socket) = file.as_any().downcast_ref::<Socket>() else {
-        return Err(SyscallError::ENOTSOCK);
-    };
-
-    Ok(socket.listen().map_or(-1, |_| 0))
+pub async fn syscall_listen(args: [usize; 6]) -> SyscallResult {
+    let file = get_socket(args[0]).await?;
+    #[cfg(feature = "net")]
+    if let Some(socket) = as_inet(&file) {
+        socket.listen().map_err(inet::net_err)?;
+        return Ok(0);
+    }
+    // 负数的 backlog 被视为最大值
+    as_unix(&file)?.listen(args[1] as u32 as usize)?;
+    Ok(0)
 }
 
 /// # Arguments
@@ -100,204 +253,123 @@ pub fn syscall_listen(args: [usize; 6]) -> SyscallResult {
 /// * `addr_buf` - *mut u8
 /// * `addr_len` - *mut u32
 /// * `flags` - usize
-pub fn syscall_accept4(args: [usize; 6]) -> SyscallResult {
-    let fd = args[0];
+pub async fn syscall_accept4(args: [usize; 6]) -> SyscallResult {
     let addr_buf = args[1] as *mut u8;
     let addr_len = args[2] as *mut u32;
-    let flags = args[3];
-    let curr = current_process();
-
-    let file = match curr.fd_manager.fd_table.lock().get(fd) {
-        Some(Some(file)) => file.clone(),
-        _ => return Err(SyscallError::EBADF),
-    };
-
-    let Some(socket) = file.as_any().downcast_ref::<Socket>() else {
-        return Err(SyscallError::ENOTSOCK);
-    };
-
-    debug!("[accept()] socket {fd} accept");
-
-    // socket.accept() might block, we need to release all lock now.
-    if curr.manual_alloc_type_for_lazy(addr_len).is_err() {
-        return Err(SyscallError::EFAULT);
-    }
-    let buf_len = unsafe { *addr_len } as usize;
-    if (buf_len as i32) < 0 {
-        return Err(SyscallError::EINVAL);
-    }
-    if curr
-        .manual_alloc_range_for_lazy(args[1].into(), (args[1] + buf_len).into())
-        .is_err()
-    {
-        return Err(SyscallError::EFAULT);
+    let (nonblock, fd_flags) = socket_flags(args[3])?;
+    let file = get_socket(args[0]).await?;
+    #[cfg(feature = "net")]
+    if let Some(socket) = as_inet(&file) {
+        let (socket, peer) = socket.accept().map_err(|err| match err {
+            axerrno::AxError::ConnectionReset => SyscallError::ECONNABORTED,
+            err => inet::net_err(err),
+        })?;
+        socket.set_nonblocking(nonblock);
+        write_raw_addr(&inet::addr_to_raw(&peer), addr_buf, addr_len).await?;
+        let fd = current_executor()
+            .fd_manager
+            .alloc(Arc::new(socket), fd_flags)
+            .await
+            .map_err(fd_err)?;
+        return Ok(fd as isize);
     }
-    match socket.accept() {
-        Ok((s, addr)) => {
-            let _ = unsafe { socket_address_to(addr, addr_buf, buf_len, addr_len) };
-
-            let mut fd_table = curr.fd_manager.fd_table.lock();
-            let Ok(new_fd) = curr.alloc_fd(&mut fd_table) else {
-                return Err(SyscallError::ENFILE); // Maybe ENFILE
-            };
-
-            debug!("[accept()] socket {fd} accept new socket {new_fd} {addr:?}");
-
-            // handle flags
-
-            s.set_nonblocking((flags & SOCK_NONBLOCK) != 0);
-
-            s.set_close_on_exec((flags & SOCK_CLOEXEC) != 0);
+    let socket = as_unix(&file)?.accept().await?;
+    socket.set_nonblock(nonblock);
+    let peer = socket.peer_addr().unwrap_or(UnixAddr::Unnamed);
+    write_addr(&peer, addr_buf, addr_len).await?;
+    let fd = current_executor()
+        .fd_manager
+        .alloc(socket, fd_flags)
+        .await
+        .map_err(fd_err)?;
+    Ok(fd as isize)
+}
 
-            fd_table[new_fd] = Some(Arc::new(s));
-            Ok(new_fd as isize)
-        }
-        Err(AxError::Unsupported) => Err(SyscallError::EOPNOTSUPP),
-        Err(AxError::Interrupted) => Err(SyscallError::EINTR),
-        Err(AxError::ConnectionReset) => Err(SyscallError::ECONNABORTED),
-        Err(AxError::WouldBlock) => Err(SyscallError::EAGAIN),
-        Err(e) => {
-            error!("error: {:?}", e);
-            Err(SyscallError::EPERM)
-        }
-    }
+/// 等价于 flags 为 0 的 accept4
+pub async fn syscall_accept(mut args: [usize; 6]) -> SyscallResult {
+    args[3] = 0;
+    syscall_accept4(args).await
 }
 
+/// 对于数据报套接字，sa_family 为 AF_UNSPEC 时解除连接
 /// # Arguments
 /// * `fd` - usize
 /// * `addr_buf` - *const u8
 /// * `addr_len` - usize
-pub fn syscall_connect(args: [usize; 6]) -> SyscallResult {
-    let fd = args[0];
+pub async fn syscall_connect(args: [usize; 6]) -> SyscallResult {
     let addr_buf = args[1] as *const u8;
-    let _addr_len = args[2];
-    let curr = current_process();
-
-    let file = match curr.fd_manager.fd_table.lock().get(fd) {
-        Some(Some(file)) => file.clone(),
-        _ => return Err(SyscallError::EBADF),
-    };
-
-    let Some(socket) = file.as_any().downcast_ref::<Socket>() else {
-        return Err(SyscallError::ENOTSOCK);
-    };
-
-    let addr = unsafe { socket_address_from(addr_buf, socket) };
-
-    info!("[connect()] socket {fd} connecting to {addr:?}");
-
-    match socket.connect(addr) {
-        Ok(_) => Ok(0),
-        Err(AxError::WouldBlock) => Err(SyscallError::EINPROGRESS),
-        Err(AxError::Interrupted) => Err(SyscallError::EINTR),
-        Err(AxError::AlreadyExists) => Err(SyscallError::EISCONN),
-        // TODO：add more error code
-        Err(_) => Err(SyscallError::ECONNREFUSED),
+    let addr_len = args[2] as u32 as usize;
+    let file = get_socket(args[0]).await?;
+    #[cfg(feature = "net")]
+    if let Some(socket) = as_inet(&file) {
+        let addr = inet::addr_from_raw(read_raw_addr(addr_buf, addr_len).await?)?;
+        return match socket.connect(addr) {
+            Ok(()) => Ok(0),
+            Err(axerrno::AxError::WouldBlock) => Err(SyscallError::EINPROGRESS),
+            Err(err) => Err(inet::net_err(err)),
+        };
+    }
+    let socket = as_unix(&file)?;
+    check_range(addr_buf as usize, addr_len).await?;
+    if socket.socket_type() == SocketType::SOCK_DGRAM
+        && addr_len >= core::mem::size_of::<u16>()
+        && unsafe { (addr_buf as *const u16).read_unaligned() } == 0
+    {
+        socket.connect(&UnixAddr::Unnamed).await?;
+        return Ok(0);
+    }
+    let addr = read_addr(addr_buf, addr_len).await?;
+    if addr == UnixAddr::Unnamed {
+        return Err(SyscallError::EINVAL);
     }
+    socket.connect(&addr).await?;
+    Ok(0)
 }
 
-/// NOTE: linux man 中没有说明若socket未bound应返回什么错误
 /// # Arguments
 /// * `fd` - usize
 /// * `addr` - *mut u8
 /// * `addr_len` - *mut u32
-pub fn syscall_get_sock_name(args: [usize; 6]) -> SyscallResult {
-    let fd = args[0];
-    let addr = args[1] as *mut u8;
-    let addr_len = args[2] as *mut u32;
-    let curr = current_process();
-
-    let file = match curr.fd_manager.fd_table.lock().get(fd) {
-        Some(Some(file)) => file.clone(),
-        _ => return Err(SyscallError::EBADF),
-    };
-
-    let Some(socket) = file.as_any().downcast_ref::<Socket>() else {
-        return Err(SyscallError::ENOTSOCK);
-    };
-    if curr.manual_alloc_type_for_lazy(addr_len).is_err() {
+pub async fn syscall_get_sock_name(args: [usize; 6]) -> SyscallResult {
+    let file = get_socket(args[0]).await?;
+    if args[1] == 0 {
         return Err(SyscallError::EFAULT);
     }
-    let buf_len = unsafe { *addr_len } as usize;
-    if (buf_len as i32) < 0 {
-        return Err(SyscallError::EINVAL);
+    #[cfg(feature = "net")]
+    if let Some(socket) = as_inet(&file) {
+        // 没有绑定时与 Linux 相同返回全零的地址
+        let addr = socket.name().unwrap_or(inet::UNSPECIFIED);
+        write_raw_addr(&inet::addr_to_raw(&addr), args[1] as *mut u8, args[2] as *mut u32).await?;
+        return Ok(0);
     }
-    if curr
-        .manual_alloc_range_for_lazy(args[1].into(), (args[1] + buf_len).into())
-        .is_err()
-    {
-        return Err(SyscallError::EFAULT);
-    }
-
-    debug!("[getsockname()] socket {fd}");
-
-    let Ok(name) = socket.name() else {
-        return Err(SyscallError::EPERM);
-    };
-
-    info!("[getsockname()] socket {fd} name: {:?}", name);
-    Ok(unsafe { socket_address_to(name, addr, buf_len, addr_len) }.map_or(-1, |_| 0))
+    let addr = as_unix(&file)?.local_addr();
+    write_addr(&addr, args[1] as *mut u8, args[2] as *mut u32).await?;
+    Ok(0)
 }
 
-#[allow(unused)]
 /// # Arguments
 /// * `fd` - usize
 /// * `addr_buf` - *mut u8
 /// * `addr_len` - *mut u32
-pub fn syscall_getpeername(args: [usize; 6]) -> SyscallResult {
-    let fd = args[0];
-    let addr_buf = args[1] as *mut u8;
-    let addr_len = args[2] as *mut u32;
-    let curr = current_process();
-
-    let file = match curr.fd_manager.fd_table.lock().get(fd) {
-        Some(Some(file)) => file.clone(),
-        _ => return Err(SyscallError::EBADF),
-    };
-
-    let buf_len = match curr.manual_alloc_type_for_lazy(addr_len) {
-        Ok(_) => unsafe { *addr_len as usize },
-        Err(_) => return Err(SyscallError::EFAULT),
-    };
-    // It seems it could be negative according to Linux man page.
-    if (buf_len as i32) < 0 {
-        return Err(SyscallError::EINVAL);
-    }
-
-    if curr
-        .manual_alloc_range_for_lazy(
-            (addr_buf as usize).into(),
-            unsafe { addr_buf.add(buf_len as usize) as usize }.into(),
-        )
-        .is_err()
-    {
-        return Err(SyscallError::EFAULT);
-    }
-
-    let Some(socket) = file.as_any().downcast_ref::<Socket>() else {
-        return Err(SyscallError::ENOTSOCK);
-    };
-    if curr.manual_alloc_for_lazy(args[2].into()).is_err() {
-        return Err(SyscallError::EFAULT);
+pub async fn syscall_getpeername(args: [usize; 6]) -> SyscallResult {
+    let file = get_socket(args[0]).await?;
+    #[cfg(feature = "net")]
+    if let Some(socket) = as_inet(&file) {
+        let addr = socket.peer_name().map_err(inet::net_err)?;
+        if args[1] == 0 {
+            return Err(SyscallError::EFAULT);
+        }
+        write_raw_addr(&inet::addr_to_raw(&addr), args[1] as *mut u8, args[2] as *mut u32).await?;
+        return Ok(0);
     }
-
-    if curr
-        .manual_alloc_range_for_lazy(args[1].into(), (args[1] + buf_len).into())
-        .is_err()
-    {
+    let addr = as_unix(&file)?.peer_addr()?;
+    if args[1] == 0 {
         return Err(SyscallError::EFAULT);
     }
-    match socket.peer_name() {
-        Ok(name) => {
-            Ok(unsafe { socket_address_to(name, addr_buf, buf_len, addr_len) }.map_or(-1, |_| 0))
-        }
-        Err(AxError::NotConnected) => Err(SyscallError::ENOTCONN),
-        Err(_) => unreachable!(),
-    }
+    write_addr(&addr, args[1] as *mut u8, args[2] as *mut u32).await?;
+    Ok(0)
 }
 
-// TODO: flags
-/// Calling sendto() will bind the socket if it's not bound.
 /// # Arguments
 /// * `fd` - usize
 /// * `buf` - *const u8
@@ -305,66 +377,37 @@ pub fn syscall_getpeername(args: [usize; 6]) -> SyscallResult {
 /// * `flags` - usize
 /// * `addr` - *const u8
 /// * `addr_len` - usize
-pub fn syscall_sendto(args: [usize; 6]) -> SyscallResult {
-    let fd = args[0];
+pub async fn syscall_sendto(args: [usize; 6]) -> SyscallResult {
     let buf = args[1] as *const u8;
     let len = args[2];
-    let _flags = args[3];
+    let flags = MsgFlags::from_bits_truncate(args[3] as u32);
     let addr = args[4] as *const u8;
-    let addr_len = args[5];
-    let curr = current_process();
-
-    let file = match curr.fd_manager.fd_table.lock().get(fd) {
-        Some(Some(file)) => file.clone(),
-        _ => return Err(SyscallError::EBADF),
-    };
-
-    let Some(socket) = file.as_any().downcast_ref::<Socket>() else {
-        return Err(SyscallError::ENOTSOCK);
-    };
-
-    if buf.is_null() {
-        return Err(SyscallError::EFAULT);
-    }
-    let Ok(buf) = curr
-        .manual_alloc_range_for_lazy(
-            (buf as usize).into(),
-            unsafe { buf.add(len) as usize }.into(),
-        )
-        .map(|_| unsafe { from_raw_parts(buf, len) })
-    else {
-        error!("[sendto()] buf address {buf:?} invalid");
-        return Err(SyscallError::EFAULT);
-    };
-
-    let addr = if !addr.is_null() && addr_len != 0 {
-        match curr.manual_alloc_range_for_lazy(
-            (addr as usize).into(),
-            unsafe { addr.add(addr_len) as usize }.into(),
-        ) {
-            Ok(_) => Some(unsafe { socket_address_from(addr, socket) }),
-            Err(_) => {
-                error!("[sendto()] addr address {addr:?} invalid");
-                return Err(SyscallError::EFAULT);
-            }
-        }
+    let file = get_socket(args[0]).await?;
+    check_range(buf as usize, len).await?;
+    let data: &[u8] = if len == 0 {
+        &[]
     } else {
+        unsafe { from_raw_parts(buf, len) }
+    };
+    #[cfg(feature = "net")]
+    if let Some(socket) = as_inet(&file) {
+        let to = if addr.is_null() {
+            None
+        } else {
+            Some(inet::addr_from_raw(read_raw_addr(addr, args[5] as u32 as usize).await?)?)
+        };
+        let len = socket.sendto(data, to).map_err(inet::net_err)?;
+        return Ok(len as isize);
+    }
+    let to = if addr.is_null() {
         None
+    } else {
+        Some(read_addr(addr, args[5] as u32 as usize).await?)
     };
-    match socket.sendto(buf, addr) {
-        Ok(len) => {
-            info!("[sendto()] socket {fd} sent {len} bytes to addr {:?}", addr);
-            Ok(len as isize)
-        }
-        Err(AxError::Interrupted) => Err(SyscallError::EINTR),
-        Err(AxError::Again) | Err(AxError::WouldBlock) => Err(SyscallError::EAGAIN),
-        Err(AxError::NotConnected) => Err(SyscallError::ENOTCONN),
-        Err(AxError::ConnectionReset) => Err(SyscallError::EPIPE),
-        Err(e) => {
-            error!("[sendto()] socket {fd} send error: {e:?}");
-            Err(SyscallError::EPERM)
-        }
-    }
+    let len = as_unix(&file)?
+        .send(data, Vec::new(), to.as_ref(), flags)
+        .await?;
+    Ok(len as isize)
 }
 
 /// # Arguments
@@ -374,132 +417,290 @@ pub fn syscall_sendto(args: [usize; 6]) -> SyscallResult {
 /// * `flags` - usize
 /// * `addr_buf` - *mut u8
 /// * `addr_len` - *mut u32
-pub fn syscall_recvfrom(args: [usize; 6]) -> SyscallResult {
-    let fd = args[0];
+pub async fn syscall_recvfrom(args: [usize; 6]) -> SyscallResult {
     let buf = args[1] as *mut u8;
     let len = args[2];
-    let _flags = args[3];
-    let addr_buf = args[4] as *mut u8;
-    let addr_len = args[5] as *mut u32;
-    let curr = current_process();
-
-    let file = match curr.fd_manager.fd_table.lock().get(fd) {
-        Some(Some(file)) => file.clone(),
-        _ => return Err(SyscallError::EBADF),
-    };
-    let Some(socket) = file.as_any().downcast_ref::<Socket>() else {
-        return Err(SyscallError::ENOTSOCK);
+    let flags = MsgFlags::from_bits_truncate(args[3] as u32);
+    let file = get_socket(args[0]).await?;
+    check_range(buf as usize, len).await?;
+    let buf: &mut [u8] = if len == 0 {
+        &mut []
+    } else {
+        unsafe { from_raw_parts_mut(buf, len) }
     };
-    if !addr_len.is_null() && curr.manual_alloc_type_for_lazy(addr_len).is_err() {
-        error!("[recvfrom()] addr_len address {addr_len:?} invalid");
-        return Err(SyscallError::EFAULT);
+    #[cfg(feature = "net")]
+    if let Some(socket) = as_inet(&file) {
+        let (len, from) = socket.recv_from(buf).map_err(inet::net_err)?;
+        write_raw_addr(&inet::addr_to_raw(&from), args[4] as *mut u8, args[5] as *mut u32).await?;
+        return Ok(len as isize);
+    }
+    let socket = as_unix(&file)?;
+    // 没有控制消息的缓冲区，传递的文件随 meta 一起被关闭
+    let (len, meta) = socket.recv(buf, flags).await?;
+    write_addr(&meta.from, args[4] as *mut u8, args[5] as *mut u32).await?;
+    if flags.contains(MsgFlags::MSG_TRUNC) && socket.socket_type() != SocketType::SOCK_STREAM {
+        return Ok(meta.len as isize);
     }
+    Ok(len as isize)
+}
 
-    if !addr_buf.is_null() && curr.manual_alloc_type_for_lazy(addr_buf).is_err() {
-        return Err(SyscallError::EFAULT);
+/// 读取 `struct msghdr` 中的 iovec 数组
+async fn read_iovecs(header: &MsgHdr) -> Result<Vec<IoVec>, SyscallError> {
+    if header.msg_iovlen > 1024 {
+        return Err(SyscallError::EMSGSIZE);
     }
+    check_range(
+        header.msg_iov as usize,
+        header.msg_iovlen * core::mem::size_of::<IoVec>(),
+    )
+    .await?;
+    let mut iovecs = Vec::with_capacity(header.msg_iovlen);
+    for i in 0..header.msg_iovlen {
+        let iov = unsafe { header.msg_iov.add(i).read() };
+        check_range(iov.base as usize, iov.len).await?;
+        iovecs.push(iov);
+    }
+    Ok(iovecs)
+}
 
-    if curr
-        .manual_alloc_range_for_lazy(args[1].into(), (args[1] + len).into())
-        .is_err()
-    {
-        error!(
-            "[recvfrom()] addr_buf address {addr_buf:?}, len: {} invalid",
-            unsafe { *addr_len }
-        );
+/// 读取用户空间的 `struct msghdr`
+async fn read_msghdr(msg: *mut MsgHdr) -> Result<MsgHdr, SyscallError> {
+    if msg.is_null() || current_executor().manual_alloc_type_for_lazy(msg).await.is_err() {
         return Err(SyscallError::EFAULT);
     }
-    let buf = unsafe { from_raw_parts_mut(buf, len) };
-    info!("recv addr: {:?}", socket.name().unwrap());
-    match socket.recv_from(buf) {
-        Ok((len, addr)) => {
-            info!("socket {fd} recv {len} bytes from {addr:?}");
-            if !addr_buf.is_null() && !addr_len.is_null() {
-                let buf_len = unsafe { *addr_len } as usize;
-                if (buf_len as i32) < 0 {
+    Ok(unsafe { *msg })
+}
+
+/// 解析 sendmsg 的控制消息，返回通过 SCM_RIGHTS 传递的文件
+async fn parse_control(header: &MsgHdr) -> Result<Vec<Arc<dyn FileIO>>, SyscallError> {
+    let mut files: Vec<Arc<dyn FileIO>> = Vec::new();
+    if header.msg_controllen == 0 {
+        return Ok(files);
+    }
+    check_range(header.msg_control as usize, header.msg_controllen).await?;
+    let control = unsafe { from_raw_parts(header.msg_control, header.msg_controllen) };
+    let header_len = core::mem::size_of::<CMsgHdr>();
+    let mut offset = 0;
+    while offset + header_len <= control.len() {
+        let cmsg = unsafe { (control.as_ptr().add(offset) as *const CMsgHdr).read_unaligned() };
+        if cmsg.cmsg_len < CMsgHdr::len(0) || offset + cmsg.cmsg_len > control.len() {
+            return Err(SyscallError::EINVAL);
+        }
+        let data = &control[offset + CMsgHdr::len(0)..offset + cmsg.cmsg_len];
+        if cmsg.cmsg_level != SOL_SOCKET as i32 {
+            return Err(SyscallError::EINVAL);
+        }
+        match cmsg.cmsg_type {
+            SCM_RIGHTS => {
+                let count = data.len() / core::mem::size_of::<i32>();
+                if count == 0 || files.len() + count > SCM_MAX_FD {
+                    return Err(SyscallError::EINVAL);
+                }
+                for fd in data.chunks_exact(core::mem::size_of::<i32>()) {
+                    let fd = i32::from_ne_bytes(fd.try_into().unwrap());
+                    let Some(file) = current_executor().fd_manager.get(fd as usize).await else {
+                        return Err(SyscallError::EBADF);
+                    };
+                    files.push(file);
+                }
+            }
+            SCM_CREDENTIALS => {
+                if data.len() < core::mem::size_of::<UCred>() {
                     return Err(SyscallError::EINVAL);
                 }
-                Ok(
-                    unsafe { socket_address_to(addr, addr_buf, buf_len, addr_len) }
-                        .map_or(-1, |_| len as isize),
-                )
-            } else {
-                Ok(len as isize)
+                let cred = unsafe { (data.as_ptr() as *const UCred).read_unaligned() };
+                // 收到的凭证总是发送者当前的凭证，非特权进程不能冒充其他进程
+                let current = UCred::current();
+                if cred != current && current.uid != 0 {
+                    return Err(SyscallError::EPERM);
+                }
             }
+            _ => return Err(SyscallError::EINVAL),
         }
-        Err(AxError::NotConnected) => Err(SyscallError::ENOTCONN),
-        Err(AxError::ConnectionRefused) => Err(SyscallError::ECONNREFUSED),
-        Err(AxError::Interrupted) => Err(SyscallError::EINTR),
-        Err(AxError::Timeout) | Err(AxError::WouldBlock) => Err(SyscallError::EAGAIN),
-        Err(_) => Err(SyscallError::EPERM),
+        offset += CMsgHdr::align(cmsg.cmsg_len);
     }
+    Ok(files)
 }
 
-/// NOTE: only support socket level options (SOL_SOCKET)
 /// # Arguments
 /// * `fd` - usize
-/// * `level` - usize
-/// * `opt_name` - usize
-/// * `opt_value` - *const u8
-/// * `opt_len` - u32
-pub fn syscall_set_sock_opt(args: [usize; 6]) -> SyscallResult {
-    let fd = args[0];
-    let level = args[1];
-    let opt_name = args[2];
-    let opt_value = args[3] as *const u8;
-    let opt_len = args[4] as u32;
-    let Ok(level) = SocketOptionLevel::try_from(level) else {
-        error!("[setsockopt()] level {level} not supported");
-        unimplemented!();
-    };
-
-    let curr = current_process();
-
-    let file = match curr.fd_manager.fd_table.lock().get(fd) {
-        Some(Some(file)) => file.clone(),
-        _ => return Err(SyscallError::EBADF),
+/// * `msg` - *const MsgHdr
+/// * `flags` - usize
+pub async fn syscall_sendmsg(args: [usize; 6]) -> SyscallResult {
+    let flags = MsgFlags::from_bits_truncate(args[2] as u32);
+    let file = get_socket(args[0]).await?;
+    let header = read_msghdr(args[1] as *mut MsgHdr).await?;
+    let to = if header.msg_name.is_null() || header.msg_namelen == 0 {
+        None
+    } else {
+        Some(read_addr(header.msg_name, header.msg_namelen as usize).await?)
     };
+    let iovecs = read_iovecs(&header).await?;
+    let files = parse_control(&header).await?;
+    let mut data = Vec::new();
+    for iov in iovecs.iter().filter(|iov| iov.len > 0) {
+        data.extend_from_slice(unsafe { from_raw_parts(iov.base as *const u8, iov.len) });
+    }
+    let len = as_unix(&file)?.send(&data, files, to.as_ref(), flags).await?;
+    Ok(len as isize)
+}
 
-    let Some(socket) = file.as_any().downcast_ref::<Socket>() else {
-        return Err(SyscallError::ENOTSOCK);
+/// 向控制消息缓冲区追加一条消息，空间不足时返回 false
+fn push_cmsg(control: &mut [u8], used: &mut usize, cmsg_type: i32, data: &[u8]) -> bool {
+    let space = CMsgHdr::space(data.len());
+    // 最后一条消息不需要末尾的对齐
+    if *used + CMsgHdr::len(data.len()) > control.len() {
+        return false;
+    }
+    let header = CMsgHdr {
+        cmsg_len: CMsgHdr::len(data.len()),
+        cmsg_level: SOL_SOCKET as i32,
+        cmsg_type,
     };
+    unsafe {
+        (control.as_mut_ptr().add(*used) as *mut CMsgHdr).write_unaligned(header);
+    }
+    let start = *used + CMsgHdr::len(0);
+    control[start..start + data.len()].copy_from_slice(data);
+    *used = (*used + space).min(control.len());
+    true
+}
 
-    let opt = unsafe { from_raw_parts(opt_value, opt_len as usize) };
+/// # Arguments
+/// * `fd` - usize
+/// * `msg` - *mut MsgHdr
+/// * `flags` - usize
+pub async fn syscall_recvmsg(args: [usize; 6]) -> SyscallResult {
+    let flags = MsgFlags::from_bits_truncate(args[2] as u32);
+    let file = get_socket(args[0]).await?;
+    let socket = as_unix(&file)?;
+    let msg = args[1] as *mut MsgHdr;
+    let header = read_msghdr(msg).await?;
+    let iovecs = read_iovecs(&header).await?;
+    check_range(header.msg_control as usize, header.msg_controllen).await?;
+
+    let mut buf = vec![0u8; iovecs.iter().map(|iov| iov.len).sum()];
+    let (len, meta) = socket.recv(&mut buf, flags).await?;
+    let mut copied = 0;
+    for iov in iovecs.iter() {
+        if copied == len {
+            break;
+        }
+        let n = iov.len.min(len - copied);
+        unsafe { from_raw_parts_mut(iov.base as *mut u8, n) }.copy_from_slice(&buf[copied..copied + n]);
+        copied += n;
+    }
 
-    match level {
-        SocketOptionLevel::IP => {
-            let Ok(option) = IpOption::try_from(opt_name) else {
-                warn!("[setsockopt()] option {opt_name} not supported in socket level");
-                return Ok(0);
-            };
+    let mut msg_flags = MsgFlags::empty();
+    if socket.socket_type() != SocketType::SOCK_STREAM && meta.len > len {
+        msg_flags |= MsgFlags::MSG_TRUNC;
+    }
+    if socket.socket_type() == SocketType::SOCK_SEQPACKET {
+        msg_flags |= MsgFlags::MSG_EOR;
+    }
 
-            option.set(socket, opt)
+    let control: &mut [u8] = if header.msg_controllen == 0 {
+        &mut []
+    } else {
+        unsafe { from_raw_parts_mut(header.msg_control, header.msg_controllen) }
+    };
+    let mut used = 0;
+    if socket.passcred() {
+        let cred = unsafe {
+            from_raw_parts(
+                &meta.cred as *const UCred as *const u8,
+                core::mem::size_of::<UCred>(),
+            )
+        };
+        if !push_cmsg(control, &mut used, SCM_CREDENTIALS, cred) {
+            msg_flags |= MsgFlags::MSG_CTRUNC;
         }
-        SocketOptionLevel::Socket => {
-            let Ok(option) = SocketOption::try_from(opt_name) else {
-                warn!("[setsockopt()] option {opt_name} not supported in socket level");
-                return Ok(0);
-            };
-
-            option.set(socket, opt)
+    }
+    if !meta.files.is_empty() {
+        // 只安装放得下的文件描述符，其余的文件随之关闭
+        let room = control.len().saturating_sub(used + CMsgHdr::len(0)) / core::mem::size_of::<i32>();
+        if room < meta.files.len() {
+            msg_flags |= MsgFlags::MSG_CTRUNC;
         }
-        SocketOptionLevel::Tcp => {
-            let Ok(option) = TcpSocketOption::try_from(opt_name) else {
-                warn!("[setsockopt()] option {opt_name} not supported in tcp level");
-                return Ok(0);
-            };
-
-            option.set(socket, opt)
+        let fd_flags = if flags.contains(MsgFlags::MSG_CMSG_CLOEXEC) {
+            FdFlags::CLOEXEC
+        } else {
+            FdFlags::empty()
+        };
+        let mut fds = Vec::new();
+        for file in meta.files.into_iter().take(room) {
+            match current_executor().fd_manager.alloc(file, fd_flags).await {
+                Ok(fd) => fds.extend_from_slice(&(fd as i32).to_ne_bytes()),
+                Err(_) => {
+                    msg_flags |= MsgFlags::MSG_CTRUNC;
+                    break;
+                }
+            }
         }
-        SocketOptionLevel::IPv6 => {
-            let Ok(option) = Ipv6Option::try_from(opt_name) else {
-                warn!("[setsockopt()] option {opt_name} not supported in ipv6 level");
-                return Ok(0);
-            };
-
-            option.set(socket, opt)
+        if !fds.is_empty() {
+            push_cmsg(control, &mut used, SCM_RIGHTS, &fds);
         }
     }
+
+    write_addr(&meta.from, header.msg_name, unsafe {
+        core::ptr::addr_of_mut!((*msg).msg_namelen)
+    })
+    .await?;
+    unsafe {
+        (*msg).msg_controllen = used;
+        (*msg).msg_flags = msg_flags.bits();
+    }
+    if flags.contains(MsgFlags::MSG_TRUNC) && socket.socket_type() != SocketType::SOCK_STREAM {
+        return Ok(meta.len as isize);
+    }
+    Ok(len as isize)
+}
+
+/// AF_UNIX 套接字只支持 SOL_SOCKET 层的选项
+/// # Arguments
+/// * `fd` - usize
+/// * `level` - usize
+/// * `opt_name` - usize
+/// * `opt_value` - *const u8
+/// * `opt_len` - u32
+pub async fn syscall_set_sock_opt(args: [usize; 6]) -> SyscallResult {
+    let level = args[1];
+    let opt_name = args[2];
+    let opt_value = args[3] as *const i32;
+    let opt_len = args[4] as u32 as usize;
+    let file = get_socket(args[0]).await?;
+    #[cfg(feature = "net")]
+    if let Some(socket) = as_inet(&file) {
+        check_range(opt_value as usize, opt_len).await?;
+        let opt: &[u8] = if opt_len == 0 {
+            &[]
+        } else {
+            unsafe { from_raw_parts(opt_value as *const u8, opt_len) }
+        };
+        return socket.set_option(level, opt_name, opt);
+    }
+    let socket = as_unix(&file)?;
+    if level != SOL_SOCKET {
+        return Err(SyscallError::ENOPROTOOPT);
+    }
+    let Ok(option) = SocketOption::try_from(opt_name) else {
+        warn!("[setsockopt()] option {opt_name} not supported in socket level");
+        return Err(SyscallError::ENOPROTOOPT);
+    };
+    if opt_len < core::mem::size_of::<i32>() {
+        return Err(SyscallError::EINVAL);
+    }
+    check_range(opt_value as usize, core::mem::size_of::<i32>()).await?;
+    let value = unsafe { opt_value.read_unaligned() };
+    match option {
+        // 地址在套接字关闭时就被释放，不需要重用
+        SocketOption::SO_REUSEADDR => {}
+        SocketOption::SO_SNDBUF => socket.set_sndbuf(value.max(0) as usize),
+        SocketOption::SO_RCVBUF => socket.set_rcvbuf(value.max(0) as usize),
+        SocketOption::SO_PASSCRED => socket.set_passcred(value != 0),
+        _ => return Err(SyscallError::ENOPROTOOPT),
+    }
+    Ok(0)
 }
 
 /// # Arguments
@@ -508,182 +709,77 @@ pub fn syscall_set_sock_opt(args: [usize; 6]) -> SyscallResult {
 /// * `opt_name` - usize
 /// * `opt_value` - *mut u8
 /// * `opt_len` - *mut u32
-pub fn syscall_get_sock_opt(args: [usize; 6]) -> SyscallResult {
-    let fd = args[0];
+pub async fn syscall_get_sock_opt(args: [usize; 6]) -> SyscallResult {
     let level = args[1];
     let opt_name = args[2];
     let opt_value = args[3] as *mut u8;
     let opt_len = args[4] as *mut u32;
-    let Ok(level) = SocketOptionLevel::try_from(level) else {
-        error!("[setsockopt()] level {level} not supported");
-        unimplemented!();
-    };
-
-    if opt_value.is_null() || opt_len.is_null() {
-        return Err(SyscallError::EFAULT);
+    let file = get_socket(args[0]).await?;
+    #[cfg(feature = "net")]
+    if let Some(socket) = as_inet(&file) {
+        let value = socket.get_option(level, opt_name)?;
+        return write_opt(&value, opt_value, opt_len).await;
     }
-
-    let curr = current_process();
-
-    let file = match curr.fd_manager.fd_table.lock().get(fd) {
-        Some(Some(file)) => file.clone(),
-        _ => return Err(SyscallError::EBADF),
-    };
-
-    let Some(socket) = file.as_any().downcast_ref::<Socket>() else {
-        return Err(SyscallError::ENOTSOCK);
+    let socket = as_unix(&file)?;
+    if level != SOL_SOCKET {
+        return Err(SyscallError::ENOPROTOOPT);
+    }
+    let Ok(option) = SocketOption::try_from(opt_name) else {
+        warn!("[getsockopt()] option {opt_name} not supported in socket level");
+        return Err(SyscallError::ENOPROTOOPT);
+    };
+    let int = |value: usize| Vec::from((value as i32).to_ne_bytes());
+    let value = match option {
+        SocketOption::SO_REUSEADDR | SocketOption::SO_ERROR | SocketOption::SO_PROTOCOL => int(0),
+        SocketOption::SO_TYPE => int(socket.socket_type() as usize),
+        SocketOption::SO_SNDBUF => int(socket.sndbuf()),
+        SocketOption::SO_RCVBUF => int(socket.rcvbuf()),
+        SocketOption::SO_PASSCRED => int(socket.passcred() as usize),
+        SocketOption::SO_ACCEPTCONN => int(socket.is_listening() as usize),
+        SocketOption::SO_DOMAIN => int(Domain::AF_UNIX as usize),
+        SocketOption::SO_PEERCRED => {
+            let cred = socket.peer_cred();
+            let mut value = Vec::from(cred.pid.to_ne_bytes());
+            value.extend_from_slice(&cred.uid.to_ne_bytes());
+            value.extend_from_slice(&cred.gid.to_ne_bytes());
+            value
+        }
+        _ => return Err(SyscallError::ENOPROTOOPT),
     };
+    write_opt(&value, opt_value, opt_len).await
+}
 
-    if curr
-        .manual_alloc_type_for_lazy(opt_len as *const u32)
-        .is_err()
-    {
-        error!("[getsockopt()] opt_len address {opt_len:?} invalid");
+/// 将选项的值写回用户空间，`opt_len` 是值-结果参数，缓冲区不足时截断
+async fn write_opt(value: &[u8], opt_value: *mut u8, opt_len: *mut u32) -> SyscallResult {
+    if opt_len.is_null() || current_executor().manual_alloc_type_for_lazy(opt_len).await.is_err() {
         return Err(SyscallError::EFAULT);
     }
-    if curr
-        .manual_alloc_range_for_lazy(
-            (opt_value as usize).into(),
-            (unsafe { opt_value.add(*opt_len as usize) } as usize).into(),
-        )
-        .is_err()
-    {
-        error!(
-            "[getsockopt()] opt_value {opt_value:?}, len {} invalid",
-            unsafe { *opt_len }
-        );
-        return Err(SyscallError::EFAULT);
+    let len = unsafe { *opt_len } as i32;
+    if len < 0 {
+        return Err(SyscallError::EINVAL);
     }
-
-    match level {
-        SocketOptionLevel::IP => {}
-        SocketOptionLevel::Socket => {
-            let Ok(option) = SocketOption::try_from(opt_name) else {
-                panic!("[setsockopt()] option {opt_name} not supported in socket level");
-            };
-
-            option.get(socket, opt_value, opt_len);
-        }
-        SocketOptionLevel::Tcp => {
-            let Ok(option) = TcpSocketOption::try_from(opt_name) else {
-                panic!("[setsockopt()] option {opt_name} not supported in tcp level");
-            };
-
-            if option == TcpSocketOption::TCP_INFO {
-                return Err(SyscallError::ENOPROTOOPT);
-            }
-
-            option.get(socket, opt_value, opt_len);
-        }
-        // TODO: achieve the real implementation of ipv6
-        SocketOptionLevel::IPv6 => {}
+    let copied = value.len().min(len as usize);
+    check_range(opt_value as usize, copied).await?;
+    unsafe {
+        from_raw_parts_mut(opt_value, copied).copy_from_slice(&value[..copied]);
+        *opt_len = copied as u32;
     }
-
     Ok(0)
 }
 
-#[derive(TryFromPrimitive)]
-#[repr(usize)]
-enum SocketShutdown {
-    Read = 0,
-    Write = 1,
-    ReadWrite = 2,
-}
-
 /// # Arguments
 /// * `fd` - usize
 /// * `how` - usize
-pub fn syscall_shutdown(args: [usize; 6]) -> SyscallResult {
-    let fd = args[0];
-    let how = args[1];
-    let curr = current_process();
-
-    let file = match curr.fd_manager.fd_table.lock().get(fd) {
-        Some(Some(file)) => file.clone(),
-        _ => return Err(SyscallError::EBADF),
-    };
-
-    let Some(socket) = file.as_any().downcast_ref::<Socket>() else {
-        return Err(SyscallError::ENOTSOCK);
-    };
-
-    let Ok(how) = SocketShutdown::try_from(how) else {
-        return Err(SyscallError::EINVAL);
-    };
-
-    match how {
-        SocketShutdown::Read => {
-            error!("[shutdown()] SHUT_RD is noop")
-        }
-        SocketShutdown::Write => socket.shutdown(),
-        SocketShutdown::ReadWrite => {
-            socket.abort();
-        }
-    }
-
-    Ok(0)
-}
-
-pub fn syscall_socketpair(args: [usize; 6]) -> SyscallResult {
-    let fd: *mut u32 = args[3] as *mut u32;
-    let s_type = args[1];
-    let domain = args[0];
-    let process = current_process();
-    if process.manual_alloc_for_lazy((fd as usize).into()).is_err() {
-        return Err(SyscallError::EINVAL);
-    }
-    if domain != Domain::AF_UNIX as usize {
-        panic!();
-    }
-    if SocketType::try_from(s_type & SOCKET_TYPE_MASK).is_err() {
-        // return ErrorNo::EINVAL as isize;
+pub async fn syscall_shutdown(args: [usize; 6]) -> SyscallResult {
+    let file = get_socket(args[0]).await?;
+    let Ok(how) = SocketShutdown::try_from(args[1]) else {
         return Err(SyscallError::EINVAL);
     };
-
-    let (fd1, fd2) = make_socketpair(s_type);
-    let mut fd_table = process.fd_manager.fd_table.lock();
-    let fd_num = if let Ok(fd) = process.alloc_fd(&mut fd_table) {
-        fd
-    } else {
-        return Err(SyscallError::EPERM);
-    };
-    fd_table[fd_num] = Some(fd1);
-
-    let fd_num2 = if let Ok(fd) = process.alloc_fd(&mut fd_table) {
-        fd
-    } else {
-        return Err(SyscallError::EPERM);
-    };
-    axlog::info!("alloc fd1 {} fd2 {} as socketpair", fd_num, fd_num2);
-    fd_table[fd_num2] = Some(fd2);
-
-    unsafe {
-        core::ptr::write(fd, fd_num as u32);
-        core::ptr::write(fd.offset(1), fd_num2 as u32);
+    #[cfg(feature = "net")]
+    if let Some(socket) = as_inet(&file) {
+        socket.shutdown(how);
+        return Ok(0);
     }
-
+    as_unix(&file)?.shutdown(how)?;
     Ok(0)
 }
-
-
-/// return sockerpair read write
-pub fn make_socketpair(socket_type: usize) -> (Arc<Socket>, Arc<Socket>) {
-    let s_type = SocketType::try_from(socket_type & SOCKET_TYPE_MASK).unwrap();
-    let mut fd1 = Socket::new(Domain::AF_UNIX, s_type);
-    let mut fd2 = Socket::new(Domain::AF_UNIX, s_type);
-    let mut pipe_flag = OpenFlags::empty();
-    if socket_type & SOCK_NONBLOCK != 0 {
-        pipe_flag |= OpenFlags::NON_BLOCK;
-        fd1.set_nonblocking(true);
-        fd2.set_nonblocking(true);
-    }
-    if socket_type & SOCK_CLOEXEC != 0 {
-        pipe_flag |= OpenFlags::CLOEXEC;
-        fd1.set_close_on_exec(true);
-        fd2.set_close_on_exec(true);
-    }
-    let (pipe1, pipe2) = make_pipe(pipe_flag);
-    fd1.buffer = Some(pipe1);
-    fd2.buffer = Some(pipe2);
-    (Arc::new(fd1), Arc::new(fd2))
-}
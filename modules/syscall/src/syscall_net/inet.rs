@@ -0,0 +1,597 @@
+//! AF_INET 套接字，由 axnet 的 TCP 与 UDP 套接字实现
+//!
+//! 只有启用 `net` feature 时才会编译，此时需要由运行时初始化网络设备。
+//! 目前只支持 IPv4，SOCK_STREAM 对应 TCP，SOCK_DGRAM 对应 UDP
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use async_fs::api::{async_trait, FileIO, FileIOType, OpenFlags};
+use axerrno::{AxError, AxResult};
+use axnet::{add_membership, poll_interfaces, TcpSocket, UdpSocket};
+use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use num_enum::TryFromPrimitive;
+use spinlock::SpinNoIrq;
+
+use super::socket::{Domain, SocketOption, SocketShutdown, SocketType};
+use crate::{SyscallError, SyscallResult, TimeVal};
+
+/// `struct sockaddr_in` 的长度
+pub const SOCKADDR_IN_LEN: usize = 16;
+
+/// 任意地址与端口，用于自动绑定与未绑定时的 getsockname
+pub const UNSPECIFIED: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+
+const IPPROTO_TCP: usize = 6;
+const IPPROTO_UDP: usize = 17;
+
+#[derive(TryFromPrimitive, Debug)]
+#[repr(usize)]
+#[allow(non_camel_case_types)]
+pub enum SocketOptionLevel {
+    IP = 0,
+    Socket = 1,
+    Tcp = 6,
+    IPv6 = 41,
+}
+
+#[derive(TryFromPrimitive, Debug)]
+#[repr(usize)]
+#[allow(non_camel_case_types)]
+pub enum IpOption {
+    IP_MULTICAST_IF = 32,
+    IP_MULTICAST_TTL = 33,
+    IP_MULTICAST_LOOP = 34,
+    IP_ADD_MEMBERSHIP = 35,
+}
+
+#[derive(TryFromPrimitive, PartialEq, Debug)]
+#[repr(usize)]
+#[allow(non_camel_case_types)]
+pub enum TcpSocketOption {
+    TCP_NODELAY = 1, // disable nagle algorithm and flush
+    TCP_MAXSEG = 2,
+    TCP_INFO = 11,
+    TCP_CONGESTION = 13,
+}
+
+/// 解析用户给出的 `struct sockaddr_in`
+pub fn addr_from_raw(raw: &[u8]) -> Result<SocketAddr, SyscallError> {
+    if raw.len() < SOCKADDR_IN_LEN {
+        return Err(SyscallError::EINVAL);
+    }
+    if u16::from_ne_bytes([raw[0], raw[1]]) != Domain::AF_INET as u16 {
+        return Err(SyscallError::EAFNOSUPPORT);
+    }
+    let port = u16::from_be_bytes([raw[2], raw[3]]);
+    let ip = Ipv4Addr::new(raw[4], raw[5], raw[6], raw[7]);
+    Ok(SocketAddr::new(IpAddr::V4(ip), port))
+}
+
+/// 将地址转换为 `struct sockaddr_in`，IPv6 地址的部分被置为零
+pub fn addr_to_raw(addr: &SocketAddr) -> Vec<u8> {
+    let mut raw = vec![0u8; SOCKADDR_IN_LEN];
+    raw[..2].copy_from_slice(&(Domain::AF_INET as u16).to_ne_bytes());
+    raw[2..4].copy_from_slice(&addr.port().to_be_bytes());
+    if let IpAddr::V4(ip) = addr.ip() {
+        raw[4..8].copy_from_slice(&ip.octets());
+    }
+    raw
+}
+
+/// 将 axnet 返回的错误转换为系统调用的错误
+pub fn net_err(err: AxError) -> SyscallError {
+    match err {
+        AxError::WouldBlock | AxError::Timeout => SyscallError::EAGAIN,
+        AxError::Interrupted => SyscallError::EINTR,
+        AxError::NotConnected => SyscallError::ENOTCONN,
+        AxError::ConnectionRefused => SyscallError::ECONNREFUSED,
+        AxError::ConnectionReset => SyscallError::EPIPE,
+        AxError::AlreadyExists => SyscallError::EISCONN,
+        AxError::AddrInUse => SyscallError::EADDRINUSE,
+        AxError::Unsupported => SyscallError::EOPNOTSUPP,
+        AxError::InvalidInput => SyscallError::EINVAL,
+        _ => SyscallError::EIO,
+    }
+}
+
+/// 读取选项的 int 值
+fn int_opt(opt: &[u8]) -> Result<i32, SyscallError> {
+    let bytes = opt.get(..4).ok_or(SyscallError::EINVAL)?;
+    Ok(i32::from_ne_bytes(bytes.try_into().unwrap()))
+}
+
+fn int(value: i32) -> Vec<u8> {
+    Vec::from(value.to_ne_bytes())
+}
+
+/// The transport protocol used by the socket
+pub enum SocketInner {
+    /// TCP socket
+    Tcp(TcpSocket),
+    /// UDP socket
+    Udp(UdpSocket),
+}
+
+/// AF_INET 套接字
+///
+/// 发送、接收缓冲区的大小与拥塞控制算法只是记录下来供 getsockopt 返回
+pub struct InetSocket {
+    socket_type: SocketType,
+    inner: SocketInner,
+    recv_timeout: SpinNoIrq<Option<TimeVal>>,
+    listening: AtomicBool,
+    dont_route: AtomicBool,
+    send_buf_size: AtomicU64,
+    recv_buf_size: AtomicU64,
+    congestion: SpinNoIrq<String>,
+}
+
+impl InetSocket {
+    fn with_inner(socket_type: SocketType, inner: SocketInner) -> Self {
+        Self {
+            socket_type,
+            inner,
+            recv_timeout: SpinNoIrq::new(None),
+            listening: AtomicBool::new(false),
+            dont_route: AtomicBool::new(false),
+            send_buf_size: AtomicU64::new(64 * 1024),
+            recv_buf_size: AtomicU64::new(64 * 1024),
+            congestion: SpinNoIrq::new(String::from("reno")),
+        }
+    }
+
+    /// 创建一个 TCP 或 UDP 套接字，`protocol` 为 0 或与类型对应的协议
+    pub fn new(socket_type: SocketType, protocol: usize) -> Result<Self, SyscallError> {
+        let inner = match socket_type {
+            SocketType::SOCK_STREAM if protocol == 0 || protocol == IPPROTO_TCP => {
+                SocketInner::Tcp(TcpSocket::new())
+            }
+            SocketType::SOCK_DGRAM if protocol == 0 || protocol == IPPROTO_UDP => {
+                SocketInner::Udp(UdpSocket::new())
+            }
+            SocketType::SOCK_STREAM | SocketType::SOCK_DGRAM => {
+                return Err(SyscallError::EPROTONOSUPPORT)
+            }
+            _ => return Err(SyscallError::ESOCKTNOSUPPORT),
+        };
+        Ok(Self::with_inner(socket_type, inner))
+    }
+
+    pub fn socket_type(&self) -> SocketType {
+        self.socket_type
+    }
+
+    /// set the socket to non-blocking mode
+    pub fn set_nonblocking(&self, nonblocking: bool) {
+        match &self.inner {
+            SocketInner::Tcp(s) => s.set_nonblocking(nonblocking),
+            SocketInner::Udp(s) => s.set_nonblocking(nonblocking),
+        }
+    }
+
+    /// Return the non-blocking flag of the socket
+    pub fn is_nonblocking(&self) -> bool {
+        match &self.inner {
+            SocketInner::Tcp(s) => s.is_nonblocking(),
+            SocketInner::Udp(s) => s.is_nonblocking(),
+        }
+    }
+
+    /// Return bound address.
+    pub fn name(&self) -> AxResult<SocketAddr> {
+        match &self.inner {
+            SocketInner::Tcp(s) => s.local_addr(),
+            SocketInner::Udp(s) => s.local_addr(),
+        }
+    }
+
+    /// Return peer address.
+    pub fn peer_name(&self) -> AxResult<SocketAddr> {
+        match &self.inner {
+            SocketInner::Tcp(s) => s.peer_addr(),
+            SocketInner::Udp(s) => s.peer_addr(),
+        }
+    }
+
+    /// Bind the socket to the given address.
+    pub fn bind(&self, addr: SocketAddr) -> AxResult {
+        match &self.inner {
+            SocketInner::Tcp(s) => s.bind(addr),
+            SocketInner::Udp(s) => s.bind(addr),
+        }
+    }
+
+    /// Listen to the bound address, only TCP sockets can listen.
+    pub fn listen(&self) -> AxResult {
+        match &self.inner {
+            SocketInner::Tcp(s) => s.listen()?,
+            SocketInner::Udp(_) => return Err(AxError::Unsupported),
+        }
+        self.listening.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Accept a new connection.
+    pub fn accept(&self) -> AxResult<(Self, SocketAddr)> {
+        let new_socket = match &self.inner {
+            SocketInner::Tcp(s) => s.accept()?,
+            SocketInner::Udp(_) => return Err(AxError::Unsupported),
+        };
+        let addr = new_socket.peer_addr()?;
+        Ok((
+            Self::with_inner(self.socket_type, SocketInner::Tcp(new_socket)),
+            addr,
+        ))
+    }
+
+    /// Connect to the given address.
+    pub fn connect(&self, addr: SocketAddr) -> AxResult {
+        match &self.inner {
+            SocketInner::Tcp(s) => s.connect(addr),
+            SocketInner::Udp(s) => s.connect(addr),
+        }
+    }
+
+    /// 发送数据，UDP 套接字没有绑定时自动绑定到任意端口
+    pub fn sendto(&self, buf: &[u8], addr: Option<SocketAddr>) -> AxResult<usize> {
+        match &self.inner {
+            SocketInner::Udp(s) => {
+                if s.local_addr().is_err() {
+                    s.bind(UNSPECIFIED)?;
+                }
+                match addr {
+                    Some(addr) => s.send_to(buf, addr),
+                    // not connected and no target is given
+                    None if s.peer_addr().is_err() => Err(AxError::NotConnected),
+                    None => s.send(buf),
+                }
+            }
+            SocketInner::Tcp(s) => {
+                if s.is_closed() {
+                    // The local socket has been closed.
+                    return Err(AxError::ConnectionReset);
+                }
+                s.send(buf)
+            }
+        }
+    }
+
+    /// 接收数据，返回接收的长度与对端的地址
+    pub fn recv_from(&self, buf: &mut [u8]) -> AxResult<(usize, SocketAddr)> {
+        let timeout = *self.recv_timeout.lock();
+        match &self.inner {
+            SocketInner::Tcp(s) => {
+                let addr = s.peer_addr()?;
+                match timeout {
+                    Some(time) => s.recv_timeout(buf, time.turn_to_ticks()),
+                    None => s.recv(buf),
+                }
+                .map(|len| (len, addr))
+            }
+            SocketInner::Udp(s) => match timeout {
+                Some(time) => s.recv_from_timeout(buf, time.turn_to_ticks()),
+                None => s.recv_from(buf),
+            },
+        }
+    }
+
+    pub fn shutdown(&self, how: SocketShutdown) {
+        match how {
+            SocketShutdown::Read => warn!("[shutdown()] SHUT_RD is noop"),
+            SocketShutdown::Write => match &self.inner {
+                SocketInner::Udp(s) => {
+                    let _ = s.shutdown();
+                }
+                SocketInner::Tcp(s) => {
+                    let _ = s.close();
+                }
+            },
+            SocketShutdown::ReadWrite => match &self.inner {
+                SocketInner::Udp(s) => {
+                    let _ = s.shutdown();
+                }
+                SocketInner::Tcp(s) => s.with_socket_mut(|s| {
+                    if let Some(s) = s {
+                        s.abort();
+                    }
+                }),
+            },
+        }
+    }
+
+    fn set_reuse_addr(&self, flag: bool) {
+        match &self.inner {
+            SocketInner::Tcp(s) => s.set_reuse_addr(flag),
+            SocketInner::Udp(s) => s.set_reuse_addr(flag),
+        }
+    }
+
+    fn is_reuse_addr(&self) -> bool {
+        match &self.inner {
+            SocketInner::Tcp(s) => s.is_reuse_addr(),
+            SocketInner::Udp(s) => s.is_reuse_addr(),
+        }
+    }
+
+    fn keep_alive(&self) -> bool {
+        match &self.inner {
+            SocketInner::Udp(_) => false,
+            SocketInner::Tcp(s) => s.with_socket(|s| s.is_some_and(|s| s.keep_alive().is_some())),
+        }
+    }
+
+    /// setsockopt，不支持的选项返回 ENOPROTOOPT
+    pub fn set_option(&self, level: usize, name: usize, opt: &[u8]) -> SyscallResult {
+        let Ok(level) = SocketOptionLevel::try_from(level) else {
+            return Err(SyscallError::ENOPROTOOPT);
+        };
+        match level {
+            SocketOptionLevel::IP => {
+                let Ok(option) = IpOption::try_from(name) else {
+                    warn!("[setsockopt()] option {name} not supported in ip level");
+                    return Err(SyscallError::ENOPROTOOPT);
+                };
+                self.set_ip_option(option, opt)
+            }
+            SocketOptionLevel::Socket => {
+                let Ok(option) = SocketOption::try_from(name) else {
+                    warn!("[setsockopt()] option {name} not supported in socket level");
+                    return Err(SyscallError::ENOPROTOOPT);
+                };
+                self.set_socket_option(option, opt)
+            }
+            SocketOptionLevel::Tcp => {
+                let Ok(option) = TcpSocketOption::try_from(name) else {
+                    warn!("[setsockopt()] option {name} not supported in tcp level");
+                    return Err(SyscallError::ENOPROTOOPT);
+                };
+                self.set_tcp_option(option, opt)
+            }
+            // TODO: achieve the real implementation of ipv6
+            SocketOptionLevel::IPv6 => Ok(0),
+        }
+    }
+
+    fn set_ip_option(&self, option: IpOption, opt: &[u8]) -> SyscallResult {
+        match option {
+            // 我们只会使用LOOPBACK作为多播接口
+            IpOption::IP_MULTICAST_IF | IpOption::IP_MULTICAST_LOOP => {}
+            IpOption::IP_MULTICAST_TTL => {
+                if let SocketInner::Udp(s) = &self.inner {
+                    s.set_socket_ttl(*opt.first().ok_or(SyscallError::EINVAL)?);
+                }
+            }
+            IpOption::IP_ADD_MEMBERSHIP => {
+                let opt = opt.get(..8).ok_or(SyscallError::EINVAL)?;
+                let multicast_addr = IpAddr::V4(Ipv4Addr::new(opt[0], opt[1], opt[2], opt[3]));
+                let interface_addr = IpAddr::V4(Ipv4Addr::new(opt[4], opt[5], opt[6], opt[7]));
+                // TODO add membership error handling
+                add_membership(multicast_addr, interface_addr);
+            }
+        }
+        Ok(0)
+    }
+
+    fn set_socket_option(&self, option: SocketOption, opt: &[u8]) -> SyscallResult {
+        match option {
+            SocketOption::SO_REUSEADDR => self.set_reuse_addr(int_opt(opt)? != 0),
+            SocketOption::SO_DONTROUTE => {
+                self.dont_route.store(int_opt(opt)? != 0, Ordering::Release)
+            }
+            SocketOption::SO_SNDBUF => self
+                .send_buf_size
+                .store(int_opt(opt)?.max(0) as u64, Ordering::Release),
+            SocketOption::SO_RCVBUF => self
+                .recv_buf_size
+                .store(int_opt(opt)?.max(0) as u64, Ordering::Release),
+            SocketOption::SO_KEEPALIVE => {
+                let interval = (int_opt(opt)? != 0).then(|| axnet::Duration::from_secs(45));
+                match &self.inner {
+                    SocketInner::Udp(_) => {
+                        warn!("[setsockopt()] set SO_KEEPALIVE on udp socket, ignored")
+                    }
+                    SocketInner::Tcp(s) => s.with_socket_mut(|s| match s {
+                        Some(s) => s.set_keep_alive(interval),
+                        None => warn!(
+                            "[setsockopt()] set keep-alive for tcp socket not created, ignored"
+                        ),
+                    }),
+                }
+            }
+            SocketOption::SO_RCVTIMEO => {
+                if opt.len() < core::mem::size_of::<TimeVal>() {
+                    return Err(SyscallError::EINVAL);
+                }
+                let timeout = unsafe { (opt.as_ptr() as *const TimeVal).read_unaligned() };
+                *self.recv_timeout.lock() = if timeout.sec == 0 && timeout.usec == 0 {
+                    None
+                } else {
+                    Some(timeout)
+                };
+            }
+            _ => return Err(SyscallError::ENOPROTOOPT),
+        }
+        Ok(0)
+    }
+
+    fn set_tcp_option(&self, option: TcpSocketOption, opt: &[u8]) -> SyscallResult {
+        let SocketInner::Tcp(socket) = &self.inner else {
+            return Err(SyscallError::ENOPROTOOPT);
+        };
+        match option {
+            TcpSocketOption::TCP_NODELAY => {
+                let _ = socket.set_nagle_enabled(int_opt(opt)? == 0);
+            }
+            // TODO: support the protocal
+            TcpSocketOption::TCP_INFO | TcpSocketOption::TCP_MAXSEG => {}
+            TcpSocketOption::TCP_CONGESTION => {
+                let name = opt.split(|&c| c == 0).next().unwrap_or_default();
+                let name = core::str::from_utf8(name).map_err(|_| SyscallError::EINVAL)?;
+                *self.congestion.lock() = String::from(name);
+            }
+        }
+        Ok(0)
+    }
+
+    /// getsockopt，返回选项的值，由调用者按照缓冲区的长度截断
+    pub fn get_option(&self, level: usize, name: usize) -> Result<Vec<u8>, SyscallError> {
+        let Ok(level) = SocketOptionLevel::try_from(level) else {
+            return Err(SyscallError::ENOPROTOOPT);
+        };
+        match level {
+            // TODO: achieve the real implementation of ip and ipv6
+            SocketOptionLevel::IP | SocketOptionLevel::IPv6 => Ok(Vec::new()),
+            SocketOptionLevel::Socket => {
+                let Ok(option) = SocketOption::try_from(name) else {
+                    warn!("[getsockopt()] option {name} not supported in socket level");
+                    return Err(SyscallError::ENOPROTOOPT);
+                };
+                self.get_socket_option(option)
+            }
+            SocketOptionLevel::Tcp => {
+                let Ok(option) = TcpSocketOption::try_from(name) else {
+                    warn!("[getsockopt()] option {name} not supported in tcp level");
+                    return Err(SyscallError::ENOPROTOOPT);
+                };
+                self.get_tcp_option(option)
+            }
+        }
+    }
+
+    fn get_socket_option(&self, option: SocketOption) -> Result<Vec<u8>, SyscallError> {
+        let value = match option {
+            SocketOption::SO_REUSEADDR => int(self.is_reuse_addr() as i32),
+            SocketOption::SO_DONTROUTE => int(self.dont_route.load(Ordering::Acquire) as i32),
+            SocketOption::SO_SNDBUF => int(self.send_buf_size.load(Ordering::Acquire) as i32),
+            SocketOption::SO_RCVBUF => int(self.recv_buf_size.load(Ordering::Acquire) as i32),
+            SocketOption::SO_KEEPALIVE => int(self.keep_alive() as i32),
+            SocketOption::SO_RCVTIMEO | SocketOption::SO_SNDTIMEO => {
+                let timeout = match option {
+                    SocketOption::SO_RCVTIMEO => *self.recv_timeout.lock(),
+                    _ => None,
+                };
+                let timeout = timeout.unwrap_or(TimeVal { sec: 0, usec: 0 });
+                let mut value = Vec::from(timeout.sec.to_ne_bytes());
+                value.extend_from_slice(&timeout.usec.to_ne_bytes());
+                value
+            }
+            // 当前没有存储错误列表
+            SocketOption::SO_ERROR | SocketOption::SO_PASSCRED => int(0),
+            SocketOption::SO_TYPE => int(self.socket_type as i32),
+            SocketOption::SO_DOMAIN => int(Domain::AF_INET as i32),
+            SocketOption::SO_PROTOCOL => match self.inner {
+                SocketInner::Tcp(_) => int(IPPROTO_TCP as i32),
+                SocketInner::Udp(_) => int(IPPROTO_UDP as i32),
+            },
+            SocketOption::SO_ACCEPTCONN => int(self.listening.load(Ordering::Acquire) as i32),
+            SocketOption::SO_PEERCRED => return Err(SyscallError::ENOPROTOOPT),
+        };
+        Ok(value)
+    }
+
+    fn get_tcp_option(&self, option: TcpSocketOption) -> Result<Vec<u8>, SyscallError> {
+        let SocketInner::Tcp(socket) = &self.inner else {
+            return Err(SyscallError::ENOPROTOOPT);
+        };
+        let value = match option {
+            TcpSocketOption::TCP_NODELAY => int(!socket.nagle_enabled() as i32),
+            TcpSocketOption::TCP_MAXSEG => int(1500),
+            TcpSocketOption::TCP_INFO => return Err(SyscallError::ENOPROTOOPT),
+            TcpSocketOption::TCP_CONGESTION => self.congestion.lock().as_bytes().to_vec(),
+        };
+        Ok(value)
+    }
+}
+
+#[async_trait]
+impl FileIO for InetSocket {
+    async fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
+        match &self.inner {
+            SocketInner::Tcp(s) => s.recv(buf),
+            SocketInner::Udp(s) => s.recv(buf),
+        }
+    }
+
+    async fn write(&self, buf: &[u8]) -> AxResult<usize> {
+        match &self.inner {
+            SocketInner::Tcp(s) => s.send(buf),
+            SocketInner::Udp(s) => s.send(buf),
+        }
+    }
+
+    async fn readable(&self) -> bool {
+        true
+    }
+
+    async fn writable(&self) -> bool {
+        true
+    }
+
+    async fn executable(&self) -> bool {
+        false
+    }
+
+    async fn get_type(&self) -> FileIOType {
+        FileIOType::Socket
+    }
+
+    async fn get_path(&self) -> String {
+        format!("socket:[{}]", self as *const Self as usize)
+    }
+
+    /// 网络设备不会唤醒等待者，poll 与 epoll 只能轮询
+    async fn ready_to_read(&self) -> bool {
+        poll_interfaces();
+        match &self.inner {
+            SocketInner::Tcp(s) => s.poll().map_or(false, |p| p.readable),
+            SocketInner::Udp(s) => s.poll().map_or(false, |p| p.readable),
+        }
+    }
+
+    async fn ready_to_write(&self) -> bool {
+        poll_interfaces();
+        match &self.inner {
+            SocketInner::Tcp(s) => s.poll().map_or(false, |p| p.writable),
+            SocketInner::Udp(s) => s.poll().map_or(false, |p| p.writable),
+        }
+    }
+
+    /// 只有 O_NONBLOCK 可以修改
+    async fn set_status(&self, flags: OpenFlags) -> bool {
+        self.set_nonblocking(flags.contains(OpenFlags::NON_BLOCK));
+        true
+    }
+
+    async fn get_status(&self) -> OpenFlags {
+        if self.is_nonblocking() {
+            OpenFlags::RDWR | OpenFlags::NON_BLOCK
+        } else {
+            OpenFlags::RDWR
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{addr_from_raw, addr_to_raw, SOCKADDR_IN_LEN};
+    use crate::SyscallError;
+    use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    #[test]
+    fn test_addr_roundtrip() {
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 2, 15)), 8080);
+        let raw = addr_to_raw(&addr);
+        assert_eq!(raw.len(), SOCKADDR_IN_LEN);
+        assert_eq!(&raw[2..8], &[0x1f, 0x90, 10, 0, 2, 15]);
+        assert_eq!(Ok(addr), addr_from_raw(&raw));
+        assert_eq!(Err(SyscallError::EINVAL), addr_from_raw(&raw[..8]));
+        let mut unix = raw.clone();
+        unix[..2].copy_from_slice(&1u16.to_ne_bytes());
+        assert_eq!(Err(SyscallError::EAFNOSUPPORT), addr_from_raw(&unix));
+    }
+}
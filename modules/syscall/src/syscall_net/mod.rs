@@ -5,35 +5,34 @@ mod imp;
 
 #[allow(unused)]
 mod socket;
+mod unix;
+#[cfg(feature = "net")]
+mod inet;
 use imp::*;
-pub use socket::Socket;
+pub use unix::{UnixAddr, UnixSocket};
+#[cfg(feature = "net")]
+pub use inet::InetSocket;
 mod net_syscall_id;
 pub use net_syscall_id::NetSyscallId::{self, *};
 
 /// 进行 syscall 的分发
-pub fn net_syscall(syscall_id: net_syscall_id::NetSyscallId, args: [usize; 6]) -> SyscallResult {
+pub async fn net_syscall(syscall_id: net_syscall_id::NetSyscallId, args: [usize; 6]) -> SyscallResult {
     match syscall_id {
-        SOCKET => syscall_socket(args),
-        BIND => syscall_bind(args),
-        LISTEN => syscall_listen(args),
-        ACCEPT => syscall_accept4(args),
-        CONNECT => syscall_connect(args),
-        GETSOCKNAME => syscall_get_sock_name(args),
-        GETPEERNAME => syscall_getpeername(args),
-        // GETPEERNAME => 0,
-        SENDTO => syscall_sendto(args),
-        RECVFROM => syscall_recvfrom(args),
-        SETSOCKOPT => syscall_set_sock_opt(args),
-        // SETSOCKOPT => 0,
-        GETSOCKOPT => syscall_get_sock_opt(args),
-        SOCKETPAIR => syscall_socketpair(args),
-        ACCEPT4 => syscall_accept4(args),
-        SHUTDOWN => syscall_shutdown(args),
-        #[allow(unused)]
-        _ => {
-            panic!("Invalid Syscall Id: {:?}!", syscall_id);
-            // return -1;
-            // exit(-1)
-        }
+        SOCKET => syscall_socket(args).await,
+        BIND => syscall_bind(args).await,
+        LISTEN => syscall_listen(args).await,
+        ACCEPT => syscall_accept(args).await,
+        CONNECT => syscall_connect(args).await,
+        GETSOCKNAME => syscall_get_sock_name(args).await,
+        GETPEERNAME => syscall_getpeername(args).await,
+        SENDTO => syscall_sendto(args).await,
+        RECVFROM => syscall_recvfrom(args).await,
+        SENDMSG => syscall_sendmsg(args).await,
+        RECVMSG => syscall_recvmsg(args).await,
+        SETSOCKOPT => syscall_set_sock_opt(args).await,
+        GETSOCKOPT => syscall_get_sock_opt(args).await,
+        SOCKETPAIR => syscall_socketpair(args).await,
+        ACCEPT4 => syscall_accept4(args).await,
+        SHUTDOWN => syscall_shutdown(args).await,
     }
 }
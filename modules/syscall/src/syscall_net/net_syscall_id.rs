@@ -26,6 +26,8 @@ pub enum NetSyscallId {
     SETSOCKOPT = 208,
     GETSOCKOPT = 209,
     SHUTDOWN = 210,
+    SENDMSG = 211,
+    RECVMSG = 212,
     ACCEPT4 = 242,
 }
 }
@@ -51,6 +53,8 @@ numeric_enum_macro::numeric_enum! {
         SETSOCKOPT = 54,
         GETSOCKOPT = 55,
         SHUTDOWN = 48,
+        SENDMSG = 46,
+        RECVMSG = 47,
         ACCEPT4 = 288,
     }
 }
@@ -0,0 +1,969 @@
+//! AF_UNIX 套接字
+//!
+//! 数据由发送者直接放入接收者的接收队列。连接双方只通过 [`Weak`] 互相引用，
+//! 套接字的最后一个引用被释放时通知对端连接已经关闭。
+//!
+//! 绑定到路径的套接字会在文件系统中创建一个 socket 节点，节点在套接字关闭后仍然保留，
+//! 与 Linux 相同，连接时既要求节点存在，也要求有套接字绑定在这个路径上。
+//!
+//! 通过 SCM_RIGHTS 传递的文件在接收队列中持有引用，
+//! 因此互相传递自身的套接字在全部关闭后不会被回收
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use async_fs::api::{self, async_trait, FileIO, FileIOType, FileType, LookupFlags, OpenFlags};
+use axerrno::{AxError, AxResult};
+use core::future::poll_fn;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::task::{Context, Poll, Waker};
+use executor::{current_executor, link::FilePath};
+use spinlock::SpinNoIrq;
+use sync::WaitQueue;
+
+use super::socket::{MsgFlags, SocketShutdown, SocketType, UCred};
+use crate::SyscallError;
+
+/// 接收队列的默认容量
+const DEFAULT_BUF_SIZE: usize = 208 * 1024;
+/// 接收队列的最小容量
+const MIN_BUF_SIZE: usize = 2048;
+/// 接收队列的最大容量
+const MAX_BUF_SIZE: usize = 4 * 1024 * 1024;
+/// listen 的最大 backlog
+const SOMAXCONN: usize = 4096;
+/// `struct sockaddr_un` 中 sun_path 的长度
+const UNIX_PATH_MAX: usize = 108;
+/// sa_family 的长度
+const FAMILY_LEN: usize = core::mem::size_of::<u16>();
+
+/// 已经绑定地址的套接字，路径地址以绝对路径为键
+static BOUND: SpinNoIrq<BTreeMap<UnixAddr, Weak<UnixSocket>>> = SpinNoIrq::new(BTreeMap::new());
+
+/// 自动绑定使用的下一个名字
+static AUTOBIND: AtomicU32 = AtomicU32::new(0);
+
+/// AF_UNIX 套接字的地址
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UnixAddr {
+    /// 未绑定的套接字
+    Unnamed,
+    /// 文件系统中的路径
+    Path(String),
+    /// 抽象命名空间中的名字，不包含开头的 '\0'
+    Abstract(Vec<u8>),
+}
+
+impl UnixAddr {
+    /// 从 `struct sockaddr_un` 解析地址，`raw` 的长度为用户给出的 addrlen
+    ///
+    /// 只包含 sa_family 时为 [`UnixAddr::Unnamed`]
+    pub fn from_raw(raw: &[u8]) -> Result<Self, SyscallError> {
+        if raw.len() < FAMILY_LEN || raw.len() > FAMILY_LEN + UNIX_PATH_MAX {
+            return Err(SyscallError::EINVAL);
+        }
+        if u16::from_ne_bytes([raw[0], raw[1]]) != super::socket::Domain::AF_UNIX as u16 {
+            return Err(SyscallError::EINVAL);
+        }
+        let path = &raw[FAMILY_LEN..];
+        match path.first() {
+            None => Ok(Self::Unnamed),
+            Some(0) => Ok(Self::Abstract(path[1..].to_vec())),
+            Some(_) => {
+                let len = path.iter().position(|&c| c == 0).unwrap_or(path.len());
+                let path = core::str::from_utf8(&path[..len]).map_err(|_| SyscallError::EINVAL)?;
+                Ok(Self::Path(path.into()))
+            }
+        }
+    }
+
+    /// 转换为 `struct sockaddr_un`，长度即为返回给用户的 addrlen
+    pub fn to_raw(&self) -> Vec<u8> {
+        let mut raw = Vec::from((super::socket::Domain::AF_UNIX as u16).to_ne_bytes());
+        match self {
+            Self::Unnamed => {}
+            Self::Path(path) => {
+                raw.extend_from_slice(path.as_bytes());
+                raw.push(0);
+            }
+            Self::Abstract(name) => {
+                raw.push(0);
+                raw.extend_from_slice(name);
+            }
+        }
+        raw
+    }
+}
+
+/// 将用户给出的路径解析为绝对路径，`follow` 表示是否跟随末尾的符号链接
+async fn absolute_path(path: &str, follow: bool) -> Result<String, SyscallError> {
+    let path = if path.starts_with('/') {
+        path.into()
+    } else {
+        format!("{}{}", current_executor().get_cwd().await, path)
+    };
+    let flags = if follow {
+        LookupFlags::empty()
+    } else {
+        LookupFlags::NOFOLLOW
+    };
+    match FilePath::new_with_flags(&path, flags).await {
+        Ok(path) => Ok(path.path().into()),
+        Err(api::PathError::Loop) => Err(SyscallError::ELOOP),
+        Err(_) => Err(SyscallError::ENOENT),
+    }
+}
+
+/// 查找绑定在 `addr` 上的套接字
+async fn lookup(addr: &UnixAddr) -> Result<Arc<UnixSocket>, SyscallError> {
+    let key = match addr {
+        UnixAddr::Unnamed => return Err(SyscallError::EINVAL),
+        UnixAddr::Abstract(_) => addr.clone(),
+        UnixAddr::Path(path) => {
+            let path = absolute_path(path, true).await?;
+            let attr = match api::get_attr(&path, LookupFlags::empty()).await {
+                Ok(attr) => attr,
+                Err(AxError::NotFound) => return Err(SyscallError::ENOENT),
+                Err(AxError::NotADirectory) => return Err(SyscallError::ENOTDIR),
+                Err(_) => return Err(SyscallError::EACCES),
+            };
+            if attr.file_type() != FileType::Socket {
+                return Err(SyscallError::ECONNREFUSED);
+            }
+            UnixAddr::Path(path)
+        }
+    };
+    BOUND
+        .lock()
+        .get(&key)
+        .and_then(Weak::upgrade)
+        .ok_or(SyscallError::ECONNREFUSED)
+}
+
+/// 接收队列中的一条消息
+struct Message {
+    data: Vec<u8>,
+    /// 已经被读取的字节数，只有流式套接字会部分读取消息
+    offset: usize,
+    /// 发送者的地址
+    from: UnixAddr,
+    /// 发送者的凭证
+    cred: UCred,
+    /// 通过 SCM_RIGHTS 传递的文件
+    files: Vec<Arc<dyn FileIO>>,
+}
+
+impl Message {
+    fn remaining(&self) -> &[u8] {
+        &self.data[self.offset..]
+    }
+}
+
+/// 一次接收得到的附加信息
+pub struct RecvMeta {
+    /// 发送者的地址
+    pub from: UnixAddr,
+    /// 发送者的凭证
+    pub cred: UCred,
+    /// 通过 SCM_RIGHTS 传递的文件
+    pub files: Vec<Arc<dyn FileIO>>,
+    /// 数据报的真实长度，大于读取的长度时说明被截断
+    pub len: usize,
+}
+
+/// 正在监听的套接字的状态
+struct Listener {
+    backlog: usize,
+    /// 等待 accept 的连接，已经与发起连接的套接字相连
+    pending: VecDeque<Arc<UnixSocket>>,
+}
+
+struct State {
+    local: UnixAddr,
+    /// 在 [`BOUND`] 中登记的键
+    key: Option<UnixAddr>,
+    /// 连接的对端，对于数据报套接字是默认的目的地址
+    peer: Option<Weak<UnixSocket>>,
+    /// 面向连接的套接字是否已经建立连接
+    connected: bool,
+    /// 建立连接时对端的凭证
+    peer_cred: Option<UCred>,
+    listener: Option<Listener>,
+    queue: VecDeque<Message>,
+    /// 接收队列中的字节数
+    queued: usize,
+    rcvbuf: usize,
+    sndbuf: usize,
+    /// 是否接收发送者的凭证
+    passcred: bool,
+    /// 不会再收到数据：本端关闭了读，或者对端关闭了写
+    rcv_shutdown: bool,
+    /// 不能再发送数据：本端关闭了写，或者对端关闭了读
+    snd_shutdown: bool,
+}
+
+impl State {
+    fn space(&self) -> usize {
+        self.rcvbuf.saturating_sub(self.queued)
+    }
+}
+
+/// AF_UNIX 套接字，支持 SOCK_STREAM、SOCK_DGRAM 与 SOCK_SEQPACKET
+pub struct UnixSocket {
+    ty: SocketType,
+    this: Weak<UnixSocket>,
+    /// 创建者的凭证
+    cred: UCred,
+    state: SpinNoIrq<State>,
+    flags: SpinNoIrq<OpenFlags>,
+    /// 状态变化时唤醒：收到数据或连接、对端读取了数据、连接关闭
+    waiters: WaitQueue,
+    /// 接收队列腾出空间时唤醒向本套接字发送数据报的任务
+    space_waiters: WaitQueue,
+}
+
+impl UnixSocket {
+    /// 创建一个未绑定、未连接的套接字，`ty` 必须是 SOCK_STREAM、SOCK_DGRAM 或 SOCK_SEQPACKET
+    pub fn new(ty: SocketType, nonblock: bool) -> Arc<Self> {
+        let mut flags = OpenFlags::RDWR;
+        if nonblock {
+            flags |= OpenFlags::NON_BLOCK;
+        }
+        Arc::new_cyclic(|this| Self {
+            ty,
+            this: this.clone(),
+            cred: UCred::current(),
+            state: SpinNoIrq::new(State {
+                local: UnixAddr::Unnamed,
+                key: None,
+                peer: None,
+                connected: false,
+                peer_cred: None,
+                listener: None,
+                queue: VecDeque::new(),
+                queued: 0,
+                rcvbuf: DEFAULT_BUF_SIZE,
+                sndbuf: DEFAULT_BUF_SIZE,
+                passcred: false,
+                rcv_shutdown: false,
+                snd_shutdown: false,
+            }),
+            flags: SpinNoIrq::new(flags),
+            waiters: WaitQueue::new(),
+            space_waiters: WaitQueue::new(),
+        })
+    }
+
+    /// 创建一对互相连接的套接字，用于 socketpair
+    pub fn pair(ty: SocketType, nonblock: bool) -> (Arc<Self>, Arc<Self>) {
+        let first = Self::new(ty, nonblock);
+        let second = Self::new(ty, nonblock);
+        for (socket, peer) in [(&first, &second), (&second, &first)] {
+            let mut state = socket.state.lock();
+            state.peer = Some(Arc::downgrade(peer));
+            state.connected = true;
+            state.peer_cred = Some(peer.cred);
+        }
+        (first, second)
+    }
+
+    pub fn socket_type(&self) -> SocketType {
+        self.ty
+    }
+
+    /// 是否为面向连接的套接字
+    fn is_connection(&self) -> bool {
+        self.ty != SocketType::SOCK_DGRAM
+    }
+
+    fn is_nonblock(&self) -> bool {
+        self.flags.lock().contains(OpenFlags::NON_BLOCK)
+    }
+
+    /// 设置是否为非阻塞模式
+    pub fn set_nonblock(&self, nonblock: bool) {
+        let mut flags = self.flags.lock();
+        flags.set(OpenFlags::NON_BLOCK, nonblock);
+    }
+
+    /// 本端绑定的地址
+    pub fn local_addr(&self) -> UnixAddr {
+        self.state.lock().local.clone()
+    }
+
+    /// 对端绑定的地址
+    pub fn peer_addr(&self) -> Result<UnixAddr, SyscallError> {
+        let peer = self.state.lock().peer.clone();
+        match peer.and_then(|peer| peer.upgrade()) {
+            Some(peer) => Ok(peer.local_addr()),
+            None => Err(SyscallError::ENOTCONN),
+        }
+    }
+
+    /// 建立连接时对端的凭证，没有对端时返回无效的凭证
+    pub fn peer_cred(&self) -> UCred {
+        self.state.lock().peer_cred.unwrap_or(UCred::INVALID)
+    }
+
+    /// 是否正在监听
+    pub fn is_listening(&self) -> bool {
+        self.state.lock().listener.is_some()
+    }
+
+    pub fn passcred(&self) -> bool {
+        self.state.lock().passcred
+    }
+
+    pub fn set_passcred(&self, passcred: bool) {
+        self.state.lock().passcred = passcred;
+    }
+
+    pub fn rcvbuf(&self) -> usize {
+        self.state.lock().rcvbuf
+    }
+
+    pub fn sndbuf(&self) -> usize {
+        self.state.lock().sndbuf
+    }
+
+    /// 与 Linux 相同，设置的缓冲区大小会被加倍
+    pub fn set_rcvbuf(&self, size: usize) {
+        self.state.lock().rcvbuf = size.saturating_mul(2).clamp(MIN_BUF_SIZE, MAX_BUF_SIZE);
+        self.space_waiters.notify_all();
+    }
+
+    pub fn set_sndbuf(&self, size: usize) {
+        self.state.lock().sndbuf = size.saturating_mul(2).clamp(MIN_BUF_SIZE, MAX_BUF_SIZE);
+    }
+
+    /// 绑定地址，[`UnixAddr::Unnamed`] 表示自动绑定到一个抽象地址
+    pub async fn bind(&self, addr: UnixAddr) -> Result<(), SyscallError> {
+        if self.state.lock().local != UnixAddr::Unnamed {
+            return Err(SyscallError::EINVAL);
+        }
+        let key = match &addr {
+            UnixAddr::Path(path) => {
+                let path = absolute_path(path, false).await?;
+                match api::create_node(&path, FileType::Socket).await {
+                    Ok(()) => {}
+                    Err(AxError::AlreadyExists) => return Err(SyscallError::EADDRINUSE),
+                    Err(AxError::NotFound) => return Err(SyscallError::ENOENT),
+                    Err(AxError::NotADirectory) => return Err(SyscallError::ENOTDIR),
+                    Err(AxError::Unsupported) => return Err(SyscallError::EPERM),
+                    Err(_) => return Err(SyscallError::EACCES),
+                }
+                UnixAddr::Path(path)
+            }
+            _ => addr.clone(),
+        };
+        let mut bound = BOUND.lock();
+        let (addr, key) = match key {
+            UnixAddr::Unnamed => loop {
+                // 抽象命名空间中 5 位十六进制数的名字，与 Linux 相同
+                let name = format!("{:05x}", AUTOBIND.fetch_add(1, Ordering::Relaxed) & 0xfffff);
+                let key = UnixAddr::Abstract(name.into_bytes());
+                if bound.get(&key).and_then(Weak::upgrade).is_none() {
+                    break (key.clone(), key);
+                }
+            },
+            UnixAddr::Abstract(_) => {
+                if bound.get(&key).and_then(Weak::upgrade).is_some() {
+                    return Err(SyscallError::EADDRINUSE);
+                }
+                (addr, key)
+            }
+            // 新建的节点上不会有存活的套接字
+            UnixAddr::Path(_) => (addr, key),
+        };
+        bound.insert(key.clone(), self.this.clone());
+        drop(bound);
+        let mut state = self.state.lock();
+        state.local = addr;
+        state.key = Some(key);
+        Ok(())
+    }
+
+    /// 开始监听，已经在监听时只修改 backlog
+    pub fn listen(&self, backlog: usize) -> Result<(), SyscallError> {
+        if !self.is_connection() {
+            return Err(SyscallError::EOPNOTSUPP);
+        }
+        let mut state = self.state.lock();
+        if state.connected || state.local == UnixAddr::Unnamed {
+            return Err(SyscallError::EINVAL);
+        }
+        let backlog = backlog.min(SOMAXCONN);
+        match &mut state.listener {
+            Some(listener) => listener.backlog = backlog,
+            None => {
+                state.listener = Some(Listener {
+                    backlog,
+                    pending: VecDeque::new(),
+                })
+            }
+        }
+        Ok(())
+    }
+
+    /// 连接到 `addr`
+    ///
+    /// 对于数据报套接字只是设置默认的目的地址，[`UnixAddr::Unnamed`] 表示解除连接
+    pub async fn connect(&self, addr: &UnixAddr) -> Result<(), SyscallError> {
+        if !self.is_connection() {
+            let peer = match addr {
+                UnixAddr::Unnamed => None,
+                _ => {
+                    let target = lookup(addr).await?;
+                    if target.ty != self.ty {
+                        return Err(SyscallError::EPROTOTYPE);
+                    }
+                    Some(Arc::downgrade(&target))
+                }
+            };
+            self.state.lock().peer = peer;
+            return Ok(());
+        }
+
+        let target = lookup(addr).await?;
+        if target.ty != self.ty {
+            return Err(SyscallError::EPROTOTYPE);
+        }
+        {
+            let state = self.state.lock();
+            if state.listener.is_some() {
+                return Err(SyscallError::EINVAL);
+            }
+            if state.connected {
+                return Err(SyscallError::EISCONN);
+            }
+        }
+        // 服务端的套接字在 accept 之前就已经连接，可以接收数据
+        let server = Self::new(self.ty, false);
+        {
+            let mut state = server.state.lock();
+            state.local = target.local_addr();
+            state.peer = Some(self.this.clone());
+            state.connected = true;
+            state.peer_cred = Some(UCred::current());
+        }
+        let nonblock = self.is_nonblock();
+        let mut server = Some(server);
+        let server = poll_fn(|cx| {
+            let mut state = target.state.lock();
+            let Some(listener) = state.listener.as_mut() else {
+                return Poll::Ready(Err(SyscallError::ECONNREFUSED));
+            };
+            if listener.pending.len() > listener.backlog {
+                if nonblock {
+                    return Poll::Ready(Err(SyscallError::EAGAIN));
+                }
+                let _ = target.waiters.wait_until(cx, || false);
+                return Poll::Pending;
+            }
+            let server = server.take().unwrap();
+            listener.pending.push_back(server.clone());
+            Poll::Ready(Ok(server))
+        })
+        .await?;
+        target.waiters.notify_all();
+
+        let mut state = self.state.lock();
+        state.peer = Some(Arc::downgrade(&server));
+        state.connected = true;
+        state.peer_cred = Some(target.cred);
+        Ok(())
+    }
+
+    /// 接受一个连接，返回已经连接的套接字
+    pub async fn accept(&self) -> Result<Arc<UnixSocket>, SyscallError> {
+        if !self.is_connection() {
+            return Err(SyscallError::EOPNOTSUPP);
+        }
+        let nonblock = self.is_nonblock();
+        let socket = poll_fn(|cx| {
+            let mut state = self.state.lock();
+            let Some(listener) = state.listener.as_mut() else {
+                return Poll::Ready(Err(SyscallError::EINVAL));
+            };
+            if let Some(socket) = listener.pending.pop_front() {
+                return Poll::Ready(Ok(socket));
+            }
+            if nonblock {
+                return Poll::Ready(Err(SyscallError::EAGAIN));
+            }
+            let _ = self.waiters.wait_until(cx, || false);
+            Poll::Pending
+        })
+        .await?;
+        // 唤醒等待 backlog 空间的连接者
+        self.waiters.notify_all();
+        Ok(socket)
+    }
+
+    /// 关闭连接的读端、写端或者全部
+    pub fn shutdown(&self, how: SocketShutdown) -> Result<(), SyscallError> {
+        let (rd, wr) = match how {
+            SocketShutdown::Read => (true, false),
+            SocketShutdown::Write => (false, true),
+            SocketShutdown::ReadWrite => (true, true),
+        };
+        let peer = {
+            let mut state = self.state.lock();
+            if self.is_connection() && !state.connected {
+                return Err(SyscallError::ENOTCONN);
+            }
+            state.rcv_shutdown |= rd;
+            state.snd_shutdown |= wr;
+            state.peer.as_ref().and_then(Weak::upgrade)
+        };
+        self.waiters.notify_all();
+        if let Some(peer) = peer.filter(|_| self.is_connection()) {
+            let mut state = peer.state.lock();
+            state.rcv_shutdown |= wr;
+            state.snd_shutdown |= rd;
+            drop(state);
+            peer.waiters.notify_all();
+        }
+        Ok(())
+    }
+
+    /// 发送数据，`to` 为数据报的目的地址，`files` 随数据的第一个字节一起传递
+    ///
+    /// 阻塞的流式套接字会等待全部数据发送完毕
+    pub async fn send(
+        &self,
+        data: &[u8],
+        files: Vec<Arc<dyn FileIO>>,
+        to: Option<&UnixAddr>,
+        flags: MsgFlags,
+    ) -> Result<usize, SyscallError> {
+        let nonblock = self.is_nonblock() || flags.contains(MsgFlags::MSG_DONTWAIT);
+        if self.is_connection() {
+            if to.is_some() {
+                return Err(if self.state.lock().connected {
+                    SyscallError::EISCONN
+                } else {
+                    SyscallError::EOPNOTSUPP
+                });
+            }
+            return self.send_connected(data, files, nonblock).await;
+        }
+
+        let target = match to {
+            Some(addr) => Arc::downgrade(&lookup(addr).await?),
+            None => self.state.lock().peer.clone().ok_or(SyscallError::ENOTCONN)?,
+        };
+        let local = self.local_addr();
+        let cred = UCred::current();
+        let mut files = Some(files);
+        poll_fn(|cx| {
+            // 每次都重新获取目的套接字，使它关闭后能够被释放
+            let Some(target) = target.upgrade() else {
+                return Poll::Ready(Err(SyscallError::ECONNREFUSED));
+            };
+            if target.ty != self.ty {
+                return Poll::Ready(Err(SyscallError::EPROTOTYPE));
+            }
+            let mut state = target.state.lock();
+            // 已经连接的数据报套接字只接收对端的数据
+            if let Some(peer) = &state.peer {
+                if !Weak::ptr_eq(peer, &self.this) {
+                    return Poll::Ready(Err(SyscallError::EPERM));
+                }
+            }
+            if state.rcv_shutdown {
+                return Poll::Ready(Err(SyscallError::EPIPE));
+            }
+            if data.len() > state.rcvbuf {
+                return Poll::Ready(Err(SyscallError::EMSGSIZE));
+            }
+            if data.len() > state.space() {
+                if nonblock {
+                    return Poll::Ready(Err(SyscallError::EAGAIN));
+                }
+                let _ = target.space_waiters.wait_until(cx, || false);
+                return Poll::Pending;
+            }
+            state.queued += data.len();
+            state.queue.push_back(Message {
+                data: data.to_vec(),
+                offset: 0,
+                from: local.clone(),
+                cred,
+                files: files.take().unwrap(),
+            });
+            drop(state);
+            target.waiters.notify_all();
+            Poll::Ready(Ok(data.len()))
+        })
+        .await
+    }
+
+    /// 获取已经连接的对端，连接已经关闭时返回 EPIPE
+    fn connected_peer(&self) -> Result<Arc<UnixSocket>, SyscallError> {
+        let state = self.state.lock();
+        if !state.connected {
+            return Err(SyscallError::ENOTCONN);
+        }
+        if state.snd_shutdown {
+            return Err(SyscallError::EPIPE);
+        }
+        state
+            .peer
+            .as_ref()
+            .and_then(Weak::upgrade)
+            .ok_or(SyscallError::EPIPE)
+    }
+
+    async fn send_connected(
+        &self,
+        data: &[u8],
+        files: Vec<Arc<dyn FileIO>>,
+        nonblock: bool,
+    ) -> Result<usize, SyscallError> {
+        let packet = self.ty == SocketType::SOCK_SEQPACKET;
+        let local = self.local_addr();
+        let cred = UCred::current();
+        let mut files = Some(files);
+        let mut sent = 0;
+        loop {
+            let result = poll_fn(|cx| {
+                let peer = match self.connected_peer() {
+                    Ok(peer) => peer,
+                    Err(err) => return Poll::Ready(Err(err)),
+                };
+                let mut state = peer.state.lock();
+                if state.rcv_shutdown {
+                    return Poll::Ready(Err(SyscallError::EPIPE));
+                }
+                if packet && data.len() > state.rcvbuf {
+                    return Poll::Ready(Err(SyscallError::EMSGSIZE));
+                }
+                let space = state.space();
+                if space == 0 || (packet && data.len() > space) {
+                    if nonblock {
+                        return Poll::Ready(Err(SyscallError::EAGAIN));
+                    }
+                    // 对端读取数据后唤醒本端
+                    let _ = self.waiters.wait_until(cx, || false);
+                    return Poll::Pending;
+                }
+                let len = (data.len() - sent).min(space);
+                let chunk = &data[sent..sent + len];
+                let files = files.take().unwrap_or_default();
+                match state.queue.back_mut() {
+                    // 没有传递文件的流数据合并到上一条消息中
+                    Some(last) if !packet && files.is_empty() && last.files.is_empty() => {
+                        last.data.extend_from_slice(chunk)
+                    }
+                    _ => state.queue.push_back(Message {
+                        data: chunk.to_vec(),
+                        offset: 0,
+                        from: local.clone(),
+                        cred,
+                        files,
+                    }),
+                }
+                state.queued += len;
+                drop(state);
+                peer.waiters.notify_all();
+                Poll::Ready(Ok(len))
+            })
+            .await;
+            match result {
+                Ok(len) => sent += len,
+                Err(SyscallError::EAGAIN) if sent > 0 => return Ok(sent),
+                Err(err) => return Err(err),
+            }
+            if sent == data.len() {
+                return Ok(sent);
+            }
+        }
+    }
+
+    /// 接收数据，流式套接字的返回值为 0 表示连接已经关闭
+    pub async fn recv(&self, buf: &mut [u8], flags: MsgFlags) -> Result<(usize, RecvMeta), SyscallError> {
+        let (mut len, mut meta) = self.recv_once(buf, flags).await?;
+        if self.ty == SocketType::SOCK_STREAM
+            && flags.contains(MsgFlags::MSG_WAITALL)
+            && !flags.contains(MsgFlags::MSG_PEEK)
+        {
+            while len > 0 && len < buf.len() {
+                match self.recv_once(&mut buf[len..], flags).await {
+                    Ok((0, _)) | Err(_) => break,
+                    Ok((more, more_meta)) => {
+                        len += more;
+                        meta.files.extend(more_meta.files);
+                    }
+                }
+            }
+            meta.len = len;
+        }
+        Ok((len, meta))
+    }
+
+    async fn recv_once(&self, buf: &mut [u8], flags: MsgFlags) -> Result<(usize, RecvMeta), SyscallError> {
+        let nonblock = self.is_nonblock() || flags.contains(MsgFlags::MSG_DONTWAIT);
+        let peek = flags.contains(MsgFlags::MSG_PEEK);
+        let stream = self.ty == SocketType::SOCK_STREAM;
+        let (len, meta, peer) = poll_fn(|cx| {
+            let mut state = self.state.lock();
+            if state.listener.is_some() {
+                return Poll::Ready(Err(SyscallError::EINVAL));
+            }
+            if state.queue.is_empty() {
+                if state.rcv_shutdown {
+                    let meta = RecvMeta {
+                        from: UnixAddr::Unnamed,
+                        cred: UCred::INVALID,
+                        files: Vec::new(),
+                        len: 0,
+                    };
+                    return Poll::Ready(Ok((0, meta, None)));
+                }
+                if self.is_connection() && !state.connected {
+                    return Poll::Ready(Err(SyscallError::ENOTCONN));
+                }
+                if nonblock {
+                    return Poll::Ready(Err(SyscallError::EAGAIN));
+                }
+                let _ = self.waiters.wait_until(cx, || false);
+                return Poll::Pending;
+            }
+            let front = state.queue.front_mut().unwrap();
+            let mut meta = RecvMeta {
+                from: front.from.clone(),
+                cred: front.cred,
+                files: if peek {
+                    front.files.clone()
+                } else {
+                    core::mem::take(&mut front.files)
+                },
+                len: front.remaining().len(),
+            };
+            let mut len = 0;
+            if stream {
+                // 流数据可以跨越消息读取，但不会越过传递了文件的消息
+                for (i, message) in state.queue.iter().enumerate() {
+                    if len == buf.len() || (i > 0 && !message.files.is_empty()) {
+                        break;
+                    }
+                    let data = message.remaining();
+                    let n = data.len().min(buf.len() - len);
+                    buf[len..len + n].copy_from_slice(&data[..n]);
+                    len += n;
+                }
+                meta.len = len;
+                if !peek {
+                    let mut left = len;
+                    while left > 0 {
+                        let front = state.queue.front_mut().unwrap();
+                        let n = front.remaining().len().min(left);
+                        front.offset += n;
+                        left -= n;
+                        if front.remaining().is_empty() {
+                            state.queue.pop_front();
+                        }
+                    }
+                    state.queued -= len;
+                }
+            } else {
+                let data = front.remaining();
+                len = data.len().min(buf.len());
+                buf[..len].copy_from_slice(&data[..len]);
+                if !peek {
+                    let message = state.queue.pop_front().unwrap();
+                    state.queued -= message.data.len();
+                }
+            }
+            let peer = if peek {
+                None
+            } else {
+                state.peer.clone()
+            };
+            Poll::Ready(Ok((len, meta, peer)))
+        })
+        .await?;
+        if !peek {
+            // 接收队列腾出了空间
+            if self.is_connection() {
+                if let Some(peer) = peer.and_then(|peer| peer.upgrade()) {
+                    peer.waiters.notify_all();
+                }
+            } else {
+                self.space_waiters.notify_all();
+            }
+        }
+        Ok((len, meta))
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        let (key, peer, listener) = {
+            let mut state = self.state.lock();
+            (state.key.take(), state.peer.take(), state.listener.take())
+        };
+        if let Some(key) = key {
+            let mut bound = BOUND.lock();
+            if bound.get(&key).is_some_and(|socket| Weak::ptr_eq(socket, &self.this)) {
+                bound.remove(&key);
+            }
+        }
+        // 尚未被接受的连接随之关闭
+        drop(listener);
+        if self.is_connection() {
+            if let Some(peer) = peer.and_then(|peer| peer.upgrade()) {
+                let mut state = peer.state.lock();
+                state.rcv_shutdown = true;
+                state.snd_shutdown = true;
+                drop(state);
+                peer.waiters.notify_all();
+            }
+        }
+        // 等待发送数据报的任务将发现本套接字已经关闭
+        self.space_waiters.notify_all();
+    }
+}
+
+/// 将套接字的错误转换为文件读写的错误
+fn as_ax_err(err: SyscallError) -> AxError {
+    match err {
+        SyscallError::EAGAIN => AxError::WouldBlock,
+        SyscallError::EPIPE => AxError::ConnectionReset,
+        SyscallError::ENOTCONN => AxError::NotConnected,
+        SyscallError::ECONNREFUSED => AxError::ConnectionRefused,
+        SyscallError::EINVAL | SyscallError::EMSGSIZE => AxError::InvalidInput,
+        _ => AxError::BadState,
+    }
+}
+
+#[async_trait]
+impl FileIO for UnixSocket {
+    async fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
+        self.recv(buf, MsgFlags::empty())
+            .await
+            .map(|(len, _)| len)
+            .map_err(as_ax_err)
+    }
+
+    async fn write(&self, buf: &[u8]) -> AxResult<usize> {
+        self.send(buf, Vec::new(), None, MsgFlags::empty())
+            .await
+            .map_err(as_ax_err)
+    }
+
+    async fn readable(&self) -> bool {
+        true
+    }
+
+    async fn writable(&self) -> bool {
+        true
+    }
+
+    async fn executable(&self) -> bool {
+        false
+    }
+
+    async fn get_type(&self) -> FileIOType {
+        FileIOType::Socket
+    }
+
+    async fn get_path(&self) -> String {
+        format!("socket:[{}]", self as *const Self as usize)
+    }
+
+    async fn ready_to_read(&self) -> bool {
+        let state = self.state.lock();
+        match &state.listener {
+            Some(listener) => !listener.pending.is_empty(),
+            None => !state.queue.is_empty() || state.rcv_shutdown,
+        }
+    }
+
+    async fn ready_to_write(&self) -> bool {
+        let (peer, snd_shutdown) = {
+            let state = self.state.lock();
+            if self.is_connection() && !state.connected {
+                return false;
+            }
+            (state.peer.clone(), state.snd_shutdown)
+        };
+        if snd_shutdown {
+            // 写入会立即失败
+            return true;
+        }
+        match peer {
+            Some(peer) => peer.upgrade().map_or(true, |peer| peer.state.lock().space() > 0),
+            None => true,
+        }
+    }
+
+    async fn is_hang_up(&self) -> bool {
+        let state = self.state.lock();
+        self.is_connection() && state.rcv_shutdown && state.snd_shutdown
+    }
+
+    /// 数据报套接字的对端腾出空间时不会通知本端，只能轮询
+    fn register_ready_waker(&self, cx: &mut Context<'_>) -> bool {
+        let _ = self.waiters.wait_until(cx, || false);
+        self.is_connection() || self.state.lock().peer.is_none()
+    }
+
+    fn unregister_ready_waker(&self, waker: &Waker) {
+        self.waiters.remove_task(waker);
+    }
+
+    /// 只有 O_NONBLOCK 可以修改
+    async fn set_status(&self, flags: OpenFlags) -> bool {
+        self.set_nonblock(flags.contains(OpenFlags::NON_BLOCK));
+        true
+    }
+
+    async fn get_status(&self) -> OpenFlags {
+        *self.flags.lock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UnixAddr;
+    use crate::SyscallError;
+
+    fn raw(path: &[u8]) -> alloc::vec::Vec<u8> {
+        let mut raw = alloc::vec::Vec::from(1u16.to_ne_bytes());
+        raw.extend_from_slice(path);
+        raw
+    }
+
+    #[test]
+    fn test_parse_addr() {
+        assert_eq!(Ok(UnixAddr::Unnamed), UnixAddr::from_raw(&raw(b"")));
+        assert_eq!(
+            Ok(UnixAddr::Path("/tmp/sock".into())),
+            UnixAddr::from_raw(&raw(b"/tmp/sock\0garbage"))
+        );
+        assert_eq!(
+            Ok(UnixAddr::Abstract(b"name\0".to_vec())),
+            UnixAddr::from_raw(&raw(b"\0name\0"))
+        );
+        assert_eq!(Err(SyscallError::EINVAL), UnixAddr::from_raw(&[1]));
+        assert_eq!(Err(SyscallError::EINVAL), UnixAddr::from_raw(&raw(&[b'a'; 109])));
+    }
+
+    #[test]
+    fn test_addr_roundtrip() {
+        for addr in [
+            UnixAddr::Unnamed,
+            UnixAddr::Path("sock".into()),
+            UnixAddr::Abstract(b"\0x".to_vec()),
+        ] {
+            assert_eq!(Ok(addr.clone()), UnixAddr::from_raw(&addr.to_raw()));
+        }
+        assert_eq!(raw(b"sock\0"), UnixAddr::Path("sock".into()).to_raw());
+    }
+}
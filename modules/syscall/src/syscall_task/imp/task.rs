@@ -15,7 +15,7 @@ use crate::{
     //     // ctype::pidfd::{new_pidfd, PidFd},
     //     // imp::solve_path,
     // },
-    SyscallResult,
+    SyscallError, SyscallResult,
     // CloneArgs, RLimit, SyscallError, TimeSecs, WaitFlags, RLIMIT_AS, RLIMIT_NOFILE,
     // RLIMIT_STACK,
 };
@@ -39,6 +39,10 @@ extern crate alloc;
 pub async fn syscall_exit(args: [usize; 6]) -> SyscallResult {
     let exit_code = args[0] as i32;
     info!("exit: exit_code = {}", exit_code);
+    crate::syscall_fs::release_locks_on_exit().await;
+    crate::syscall_mem::sem_undo_on_exit();
+    // 目前每个进程只有一个任务，它退出时进程即退出，pidfd 变为可读
+    current_executor().set_exited(exit_code);
     executor::exit().await;
     Ok(exit_code as isize)
     // let cases = ["fcanf", "fgetwc_buffering", "lat_pipe"];
@@ -488,23 +492,57 @@ pub fn syscall_umask(args: [usize; 6]) -> SyscallResult {
     Ok(current_executor().fd_manager.set_mask(new_mask) as isize)
 }
 
-/// 获取用户 id。在实现多用户权限前默认为最高权限
+/// 获取用户 id
 pub fn syscall_getuid() -> SyscallResult {
-    Ok(0)
+    Ok(current_executor().cred.uid() as isize)
 }
 
-/// 获取有效用户 id，即相当于哪个用户的权限。在实现多用户权限前默认为最高权限
+/// 获取有效用户 id，即相当于哪个用户的权限
 pub fn syscall_geteuid() -> SyscallResult {
-    Ok(0)
+    Ok(current_executor().cred.euid() as isize)
 }
 
-/// 获取用户组 id。在实现多用户权限前默认为最高权限
+/// 获取用户组 id
 pub fn syscall_getgid() -> SyscallResult {
-    Ok(0)
+    Ok(current_executor().cred.gid() as isize)
 }
 
-/// 获取有效用户组 id，即相当于哪个用户的权限。在实现多用户权限前默认为最高权限
+/// 获取有效用户组 id，即相当于哪个用户的权限
 pub fn syscall_getegid() -> SyscallResult {
+    Ok(current_executor().cred.egid() as isize)
+}
+
+/// 设置用户 id
+///
+/// root 同时设置真实与有效用户 id，其他用户只能将有效用户 id 设为自己的真实用户 id
+/// # Arguments
+/// * `uid` - u32
+pub fn syscall_setuid(args: [usize; 6]) -> SyscallResult {
+    let uid = args[0] as u32;
+    let cred = &current_executor().cred;
+    if cred.is_root() {
+        cred.set_uid(uid, uid);
+    } else if uid == cred.uid() {
+        cred.set_uid(cred.uid(), uid);
+    } else {
+        return Err(SyscallError::EPERM);
+    }
+    Ok(0)
+}
+
+/// 设置用户组 id，规则与 setuid 相同
+/// # Arguments
+/// * `gid` - u32
+pub fn syscall_setgid(args: [usize; 6]) -> SyscallResult {
+    let gid = args[0] as u32;
+    let cred = &current_executor().cred;
+    if cred.is_root() {
+        cred.set_gid(gid, gid);
+    } else if gid == cred.gid() {
+        cred.set_gid(cred.gid(), gid);
+    } else {
+        return Err(SyscallError::EPERM);
+    }
     Ok(0)
 }
 
@@ -49,7 +49,8 @@ pub async fn task_syscall(syscall_id: task_syscall_id::TaskSyscallId, args: [usi
         GETUID => syscall_getuid(),
         GETEUID => syscall_geteuid(),
         GETGID => syscall_getgid(),
-        SETGID => Ok(0),
+        SETUID => syscall_setuid(args),
+        SETGID => syscall_setgid(args),
         GETEGID => syscall_getegid(),
         GETTID => syscall_gettid(),
         // FUTEX => syscall_futex(args),
@@ -42,6 +42,7 @@ pub enum TaskSyscallId {
     GETEUID = 175,
     GETGID = 176,
     SETGID = 144,
+    SETUID = 146,
     GETEGID = 177,
     GETTID = 178,
     SYSINFO = 179,
@@ -106,6 +107,7 @@ numeric_enum_macro::numeric_enum! {
         GETEUID = 107,
         GETGID = 104,
         SETGID = 106,
+        SETUID = 105,
         GETPGID = 121,
         SETPGID = 109,
         GETEGID = 108,
@@ -0,0 +1,69 @@
+//! 单元测试共用的辅助函数，只作为其他模块的 dev-dependency 使用
+//!
+//! 测试中的 future 都在当前线程上直接轮询，没有执行器：
+//! [`block_on`] 执行不会真正阻塞的操作，[`poll_once`] 检查操作是否需要等待，
+//! [`WakeCounter`] 记录注册在文件等对象上的唤醒器被唤醒的次数
+
+#![no_std]
+#![feature(noop_waker)]
+
+extern crate alloc;
+
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::future::Future;
+use core::pin::pin;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker};
+
+/// 让出后最多重新轮询的次数
+const MAX_POLLS: usize = 64;
+
+/// 轮询一次 `future`
+pub fn poll_once<F: Future>(future: F) -> Poll<F::Output> {
+    pin!(future).poll(&mut Context::from_waker(Waker::noop()))
+}
+
+/// 执行一个不会阻塞的操作，让出时立即重新轮询
+///
+/// 多次轮询后仍未完成说明操作在等待其他任务，此时 panic
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    for _ in 0..MAX_POLLS {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+    panic!("the operation blocked");
+}
+
+/// 记录被唤醒次数的唤醒器
+#[derive(Default)]
+pub struct WakeCounter(AtomicUsize);
+
+impl WakeCounter {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// 唤醒时增加计数的 waker，它们互相 `will_wake`
+    pub fn waker(self: &Arc<Self>) -> Waker {
+        Waker::from(self.clone())
+    }
+
+    /// 到目前为止被唤醒的次数
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+impl Wake for WakeCounter {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
@@ -3,7 +3,7 @@ use core::sync::atomic::AtomicI32;
 use alloc::{boxed::Box, format, string::String, sync::Arc, vec, vec::Vec};
 use async_mem::MemorySet;
 use axerrno::{AxError, AxResult};
-use executor::{load_app, Executor, FdTable, Stderr, Stdin, Stdout, KERNEL_EXECUTOR_ID, PID2PC, TID2TASK};
+use executor::{load_app, Credentials, Executor, FdEntry, FdTable, Stderr, Stdin, Stdout, KERNEL_EXECUTOR_ID, PID2PC, TID2TASK};
 use sync::Mutex;
 use taskctx::{BaseScheduler, Task, TaskId, TaskInner, TaskRef, TrapFrame};
 use async_axhal::mem::VirtAddr;
@@ -43,8 +43,10 @@ pub async fn init_user(args: Vec<String>, envs: &Vec<String>) -> AxResult<TaskRe
         };
     }
     log::debug!("write page table done");
+    // 第一个用户进程由内核以 root 身份加载
+    let cred = Credentials::root();
     let (entry, user_stack_bottom, heap_bottom) =
-        if let Ok(ans) = load_app(path.clone(), args, envs, &mut memory_set).await {
+        if let Ok(ans) = load_app(path.clone(), args, envs, &mut memory_set, &cred).await {
             ans
         } else {
             error!("Failed to load app {}", path);
@@ -52,17 +54,17 @@ pub async fn init_user(args: Vec<String>, envs: &Vec<String>) -> AxResult<TaskRe
         };
     let new_fd_table: FdTable = Arc::new(Mutex::new(vec![
         // 标准输入
-        Some(Arc::new(Stdin {
+        Some(FdEntry::new(Arc::new(Stdin {
             flags: Mutex::new(OpenFlags::empty()),
-        })),
+        }))),
         // 标准输出
-        Some(Arc::new(Stdout {
+        Some(FdEntry::new(Arc::new(Stdout {
             flags: Mutex::new(OpenFlags::empty()),
-        })),
+        }))),
         // 标准错误
-        Some(Arc::new(Stderr {
+        Some(FdEntry::new(Arc::new(Stderr {
             flags: Mutex::new(OpenFlags::empty()),
-        })),
+        }))),
     ]));
     let new_executor = Arc::new(Executor::new(
         TaskId::new(),
@@ -1,6 +1,31 @@
 use executor::link::{FilePath, create_link};
 
+/// 周期性地将块缓存中的脏块写回设备
+#[cfg(feature = "irq")]
+async fn writeback_task() -> i32 {
+    let interval = core::time::Duration::from_millis(async_fs::api::WRITEBACK_INTERVAL_MS as u64);
+    loop {
+        executor::sleep(interval).await;
+        if let Err(e) = async_fs::api::sync().await {
+            warn!("block cache write-back failed: {:?}", e);
+        }
+    }
+}
+
+/// 从 initramfs 启动时第一个用户程序为其中的 `/init`
+pub async fn init_program() -> Option<alloc::string::String> {
+    if async_fs::api::is_initramfs_root() && async_fs::api::path_exists("/init").await {
+        Some("/init".into())
+    } else {
+        None
+    }
+}
+
 pub async fn fs_init() {
+    // 没有时钟中断时 sleep 会退化为忙等，此时只在 sync/fsync 时回写
+    #[cfg(feature = "irq")]
+    executor::spawn_raw(writeback_task, "writeback".into());
+
     use alloc::format;
     use alloc::string::ToString;
     #[cfg(target_arch = "riscv64")]
@@ -160,6 +185,24 @@ pub async fn fs_init() {
         &FilePath::new(format!("{}/libstdc++.so.6", src_dir).as_str()).await.unwrap(),
         &FilePath::new(format!("{}/libstdc++.so.6.0.29", src_dir).as_str()).await.unwrap(),
     ).await;
+
+    // gcc 与 musl 的头文件目录都指向同一个目录，FAT32 不支持目录链接，这里用绑定挂载代替
+    let include_dir = "/riscv64-linux-musl-native/include";
+    let include_links = [
+        "/riscv64-linux-musl-native/lib/gcc/riscv64-linux-musl/11.2.1/include",
+        "/riscv64-linux-musl-native/riscv64-linux-musl/include",
+    ];
+    if async_fs::api::path_exists(include_dir).await {
+        for link in include_links {
+            if !async_fs::api::path_exists(link).await {
+                let _ = async_fs::api::create_dir_all(link).await;
+            }
+            let flags = async_fs::api::MountFlags::BIND;
+            if let Err(e) = async_fs::api::mount(include_dir, link, "", flags).await {
+                warn!("failed to bind {} to {}: {:?}", include_dir, link, e);
+            }
+        }
+    }
 }
 
 
@@ -16,7 +16,7 @@ mod task_api;
 mod trap_api;
 
 use core::task::{Context, Poll};
-pub use fs_api::fs_init;
+pub use fs_api::{fs_init, init_program};
 use alloc::sync::Arc;
 pub use arch::init_interrupt;
 pub use init_api::*;
@@ -31,6 +31,10 @@ qemu_args-$(BLK) += \
   -device virtio-blk-$(vdev-suffix),drive=disk0 \
   -drive id=disk0,if=none,format=raw,file=$(DISK_IMG)
 
+ifneq ($(INITRAMFS),)
+  qemu_args-y += -initrd $(INITRAMFS)
+endif
+
 # 如果 FEATURES 包括 e1000_net，则添加 -device e1000,netdev=net0 -netdev user,id=net0,hostfwd=tcp::5555-:5555,hostfwd=udp::5555-:5555
 ifeq ($(findstring e1000_net,$(FEATURES)),e1000_net)
   qemu_args-$(NET) += \